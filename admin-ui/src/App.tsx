@@ -1,17 +1,19 @@
 import { useState, useEffect } from 'react'
-import { storage } from '@/lib/storage'
+import { getSession } from '@/api/credentials'
 import { LoginPage } from '@/components/login-page'
 import { Dashboard } from '@/components/dashboard'
 import { Toaster } from '@/components/ui/sonner'
 
 function App() {
   const [isLoggedIn, setIsLoggedIn] = useState(false)
+  const [checking, setChecking] = useState(true)
 
   useEffect(() => {
-    // 检查是否已经有保存的 API Key
-    if (storage.getApiKey()) {
-      setIsLoggedIn(true)
-    }
+    // 检查会话 Cookie 是否仍然有效
+    getSession()
+      .then(() => setIsLoggedIn(true))
+      .catch(() => setIsLoggedIn(false))
+      .finally(() => setChecking(false))
   }, [])
 
   const handleLogin = () => {
@@ -22,6 +24,10 @@ function App() {
     setIsLoggedIn(false)
   }
 
+  if (checking) {
+    return null
+  }
+
   return (
     <>
       {isLoggedIn ? (
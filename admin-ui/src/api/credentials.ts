@@ -3,12 +3,46 @@ import { storage } from '@/lib/storage'
 import type {
   CredentialsStatusResponse,
   BalanceResponse,
+  BalancesResponse,
+  CredentialUsageResponse,
   SuccessResponse,
   SetDisabledRequest,
   SetPriorityRequest,
+  SetProxyRequest,
+  SetUsageWatermarkRequest,
+  SetTagsRequest,
+  SetNoteRequest,
+  SetModelsRequest,
+  DedupeCredentialsResponse,
+  DiscoveryReport,
   AddCredentialRequest,
   AddCredentialResponse,
   CloudPassStatus,
+  CloudPassConfig,
+  UpdateCloudPassConfigRequest,
+  ClaimCloudPassResponse,
+  ConfigResponse,
+  UpdateConfigRequest,
+  UpdateConfigResponse,
+  ApiKeysResponse,
+  CreateApiKeyRequest,
+  CreateApiKeyResponse,
+  ApiKeyItem,
+  SetApiKeyQuotasRequest,
+  SessionResponse,
+  AdminKeysResponse,
+  CreateAdminKeyRequest,
+  CreateAdminKeyResponse,
+  TestWebhooksResponse,
+  AuditLogsQuery,
+  AuditLogsResponse,
+  StatsResponse,
+  DriftReport,
+  DebugStateResponse,
+  CaptureStatus,
+  LogLevelResponse,
+  PriorityPresetsResponse,
+  AppliedPriorityPreset,
 } from '@/types/api'
 
 // 创建 axios 实例
@@ -19,18 +53,46 @@ const api = axios.create({
   },
 })
 
-// 请求拦截器添加 API Key
+// 请求拦截器添加 CSRF Token（会话本身通过 HttpOnly Cookie 携带）
 api.interceptors.request.use((config) => {
-  const apiKey = storage.getApiKey()
-  if (apiKey) {
-    config.headers['x-api-key'] = apiKey
+  const csrfToken = storage.getCsrfToken()
+  if (csrfToken) {
+    config.headers['x-csrf-token'] = csrfToken
   }
   return config
 })
 
-// 获取所有凭据状态
-export async function getCredentials(): Promise<CredentialsStatusResponse> {
-  const { data } = await api.get<CredentialsStatusResponse>('/credentials')
+// 用 Admin API Key 登录，换取会话 Cookie
+export async function login(adminKey: string): Promise<SessionResponse> {
+  const { data } = await api.post<SessionResponse>('/session/login', { adminKey })
+  storage.setCsrfToken(data.csrfToken)
+  return data
+}
+
+// 恢复当前会话（页面刷新后重新获取 CSRF Token）
+export async function getSession(): Promise<SessionResponse> {
+  const { data } = await api.get<SessionResponse>('/session')
+  storage.setCsrfToken(data.csrfToken)
+  return data
+}
+
+// 退出登录
+export async function logout(): Promise<SuccessResponse> {
+  try {
+    const { data } = await api.post<SuccessResponse>('/session/logout')
+    return data
+  } finally {
+    storage.clear()
+  }
+}
+
+// 获取凭据状态（可按标签、禁用状态筛选）
+export async function getCredentials(
+  filter: { tag?: string; disabled?: boolean } = {}
+): Promise<CredentialsStatusResponse> {
+  const { data } = await api.get<CredentialsStatusResponse>('/credentials', {
+    params: filter,
+  })
   return data
 }
 
@@ -58,6 +120,69 @@ export async function setCredentialPriority(
   return data
 }
 
+// 设置凭据代理
+export async function setCredentialProxy(
+  id: number,
+  req: SetProxyRequest
+): Promise<SuccessResponse> {
+  const { data } = await api.put<SuccessResponse>(`/credentials/${id}/proxy`, req)
+  return data
+}
+
+// 设置凭据级用量警戒线
+export async function setCredentialUsageWatermark(
+  id: number,
+  req: SetUsageWatermarkRequest
+): Promise<SuccessResponse> {
+  const { data } = await api.put<SuccessResponse>(`/credentials/${id}/usage-watermark`, req)
+  return data
+}
+
+// 设置凭据标签
+export async function setCredentialTags(
+  id: number,
+  tags: string[]
+): Promise<SuccessResponse> {
+  const { data } = await api.put<SuccessResponse>(
+    `/credentials/${id}/tags`,
+    { tags } as SetTagsRequest
+  )
+  return data
+}
+
+// 设置凭据备注（为空时清除）
+export async function setCredentialNote(
+  id: number,
+  note: string | null
+): Promise<SuccessResponse> {
+  const { data } = await api.put<SuccessResponse>(
+    `/credentials/${id}/note`,
+    { note } as SetNoteRequest
+  )
+  return data
+}
+
+// 设置凭据模型白名单 / 黑名单
+export async function setCredentialModels(
+  id: number,
+  req: SetModelsRequest
+): Promise<SuccessResponse> {
+  const { data } = await api.put<SuccessResponse>(`/credentials/${id}/models`, req)
+  return data
+}
+
+// 检测并合并重复凭据
+export async function dedupeCredentials(dryRun: boolean): Promise<DedupeCredentialsResponse> {
+  const { data } = await api.post<DedupeCredentialsResponse>('/credentials/dedupe', { dryRun })
+  return data
+}
+
+// 扫描并导入 Kiro IDE 的凭据
+export async function discoverCredentials(): Promise<DiscoveryReport> {
+  const { data } = await api.post<DiscoveryReport>('/credentials/discover')
+  return data
+}
+
 // 重置失败计数
 export async function resetCredentialFailure(
   id: number
@@ -66,9 +191,41 @@ export async function resetCredentialFailure(
   return data
 }
 
-// 获取凭据余额
-export async function getCredentialBalance(id: number): Promise<BalanceResponse> {
-  const { data } = await api.get<BalanceResponse>(`/credentials/${id}/balance`)
+// 获取凭据余额（force 为 true 时跳过服务端缓存）
+export async function getCredentialBalance(id: number, force = false): Promise<BalanceResponse> {
+  const { data } = await api.get<BalanceResponse>(`/credentials/${id}/balance`, {
+    params: force ? { force: true } : undefined,
+  })
+  return data
+}
+
+// 批量获取凭据余额（ids 为空时查询所有凭据）
+export async function getCredentialBalances(
+  ids?: number[],
+  force = false
+): Promise<BalancesResponse> {
+  const params: Record<string, string | boolean> = {}
+  if (ids && ids.length > 0) params.ids = ids.join(',')
+  if (force) params.force = true
+  const { data } = await api.get<BalancesResponse>('/credentials/balance', { params })
+  return data
+}
+
+// 清除余额缓存（不传 id 时清除全部）
+export async function invalidateBalanceCache(id?: number): Promise<{ removed: number }> {
+  const url = id === undefined ? '/credentials/balance/cache' : `/credentials/${id}/balance/cache`
+  const { data } = await api.delete<{ removed: number }>(url)
+  return data
+}
+
+// 获取凭据按日用量（日期格式 YYYY-MM-DD）
+export async function getCredentialUsage(
+  id: number,
+  range: { from?: string; to?: string } = {}
+): Promise<CredentialUsageResponse> {
+  const { data } = await api.get<CredentialUsageResponse>(`/credentials/${id}/usage`, {
+    params: range,
+  })
   return data
 }
 
@@ -86,6 +243,131 @@ export async function deleteCredential(id: number): Promise<SuccessResponse> {
   return data
 }
 
+// 获取托管 API Key 列表
+export async function getApiKeys(): Promise<ApiKeysResponse> {
+  const { data } = await api.get<ApiKeysResponse>('/api-keys')
+  return data
+}
+
+// 创建托管 API Key（明文 key 仅在此返回一次）
+export async function createApiKey(req: CreateApiKeyRequest): Promise<CreateApiKeyResponse> {
+  const { data } = await api.post<CreateApiKeyResponse>('/api-keys', req)
+  return data
+}
+
+// 获取 Admin 密钥列表
+export async function getAdminKeys(): Promise<AdminKeysResponse> {
+  const { data } = await api.get<AdminKeysResponse>('/admin-keys')
+  return data
+}
+
+// 创建 Admin 密钥（明文 key 仅在此返回一次）
+export async function createAdminKey(req: CreateAdminKeyRequest): Promise<CreateAdminKeyResponse> {
+  const { data } = await api.post<CreateAdminKeyResponse>('/admin-keys', req)
+  return data
+}
+
+// 删除 Admin 密钥
+export async function deleteAdminKey(name: string): Promise<SuccessResponse> {
+  const { data } = await api.delete<SuccessResponse>(`/admin-keys/${encodeURIComponent(name)}`)
+  return data
+}
+
+// 向所有已配置的 Webhook 与聊天渠道发送测试事件
+export async function testWebhooks(): Promise<TestWebhooksResponse> {
+  const { data } = await api.post<TestWebhooksResponse>('/webhooks/test')
+  return data
+}
+
+// 设置托管 API Key 配额（整体替换）
+export async function setApiKeyQuotas(
+  id: string,
+  req: SetApiKeyQuotasRequest
+): Promise<ApiKeyItem> {
+  const { data } = await api.put<ApiKeyItem>(`/api-keys/${id}/quotas`, req)
+  return data
+}
+
+// 删除托管 API Key
+export async function deleteApiKey(id: string): Promise<SuccessResponse> {
+  const { data } = await api.delete<SuccessResponse>(`/api-keys/${id}`)
+  return data
+}
+
+// 获取上游延迟统计
+export async function getStats(): Promise<StatsResponse> {
+  const { data } = await api.get<StatsResponse>('/stats')
+  return data
+}
+
+// 获取上游协议漂移报告
+export async function getDriftReport(): Promise<DriftReport> {
+  const { data } = await api.get<DriftReport>('/drift')
+  return data
+}
+
+// 获取优先级预设
+export async function getPriorityPresets(): Promise<PriorityPresetsResponse> {
+  const { data } = await api.get<PriorityPresetsResponse>('/presets')
+  return data
+}
+
+// 应用优先级预设
+export async function applyPriorityPreset(name: string): Promise<AppliedPriorityPreset> {
+  const { data } = await api.post<AppliedPriorityPreset>(
+    `/presets/${encodeURIComponent(name)}/apply`
+  )
+  return data
+}
+
+// 获取调试状态（后台任务存活）
+export async function getDebugState(): Promise<DebugStateResponse> {
+  const { data } = await api.get<DebugStateResponse>('/debug/state')
+  return data
+}
+
+// 获取当前日志过滤规则
+export async function getLogLevel(): Promise<LogLevelResponse> {
+  const { data } = await api.get<LogLevelResponse>('/log-level')
+  return data
+}
+
+// 运行时调整日志过滤规则（为空时恢复启动时的规则）
+export async function setLogLevel(filter: string): Promise<LogLevelResponse> {
+  const { data } = await api.put<LogLevelResponse>('/log-level', { filter })
+  return data
+}
+
+// 获取请求捕获状态
+export async function getCaptureStatus(): Promise<CaptureStatus> {
+  const { data } = await api.get<CaptureStatus>('/debug/capture')
+  return data
+}
+
+// 开启请求捕获（接下来 count 个请求）
+export async function startCapture(count: number, dir?: string): Promise<CaptureStatus> {
+  const { data } = await api.post<CaptureStatus>('/debug/capture', { count, dir })
+  return data
+}
+
+// 关闭请求捕获
+export async function stopCapture(): Promise<CaptureStatus> {
+  const { data } = await api.delete<CaptureStatus>('/debug/capture')
+  return data
+}
+
+// 下载脱敏诊断包（zip）
+export async function downloadDiagnosticsBundle(): Promise<Blob> {
+  const { data } = await api.get<Blob>('/diagnostics/bundle', { responseType: 'blob' })
+  return data
+}
+
+// 分页查询请求审计日志
+export async function getAuditLogs(query: AuditLogsQuery = {}): Promise<AuditLogsResponse> {
+  const { data } = await api.get<AuditLogsResponse>('/logs', { params: query })
+  return data
+}
+
 // 获取负载均衡模式
 export async function getLoadBalancingMode(): Promise<{ mode: 'priority' | 'balanced' }> {
   const { data } = await api.get<{ mode: 'priority' | 'balanced' }>('/config/load-balancing')
@@ -98,14 +380,48 @@ export async function setLoadBalancingMode(mode: 'priority' | 'balanced'): Promi
   return data
 }
 
+// 获取可编辑的配置
+export async function getConfig(): Promise<ConfigResponse> {
+  const { data } = await api.get<ConfigResponse>('/config')
+  return data
+}
+
+// 更新配置
+export async function updateConfig(req: UpdateConfigRequest): Promise<UpdateConfigResponse> {
+  const { data } = await api.patch<UpdateConfigResponse>('/config', req)
+  return data
+}
+
 // 获取 Cloud Pass 状态
 export async function getCloudPassStatus(): Promise<CloudPassStatus> {
   const { data } = await api.get<CloudPassStatus>('/cloud-pass/status')
   return data
 }
 
+// 获取 Cloud Pass 配置（激活码脱敏）
+export async function getCloudPassConfig(): Promise<CloudPassConfig> {
+  const { data } = await api.get<CloudPassConfig>('/cloud-pass/config')
+  return data
+}
+
+// 更新 Cloud Pass 配置（写回 config.json 并立即生效）
+export async function updateCloudPassConfig(
+  req: UpdateCloudPassConfigRequest
+): Promise<CloudPassConfig> {
+  const { data } = await api.put<CloudPassConfig>('/cloud-pass/config', req)
+  return data
+}
+
 // 手动刷新 Cloud Pass
 export async function refreshCloudPass(): Promise<SuccessResponse> {
   const { data } = await api.post<SuccessResponse>('/cloud-pass/refresh')
   return data
 }
+
+// 手动抢占 Cloud Pass 设备（不指定激活码时抢占所有激活码）
+export async function claimCloudPass(license?: string): Promise<ClaimCloudPassResponse> {
+  const { data } = await api.post<ClaimCloudPassResponse>('/cloud-pass/claim', null, {
+    params: license ? { license } : undefined,
+  })
+  return data
+}
@@ -30,7 +30,7 @@ interface CredentialCardProps {
   onToggleSelect: () => void
   balance: BalanceResponse | null
   loadingBalance: boolean
-  cloudPassCredentialId?: number
+  cloudPassCredentialIds?: number[]
 }
 
 function formatLastUsed(lastUsedAt: string | null): string {
@@ -56,7 +56,7 @@ export function CredentialCard({
   onToggleSelect,
   balance,
   loadingBalance,
-  cloudPassCredentialId,
+  cloudPassCredentialIds,
 }: CredentialCardProps) {
   const [editingPriority, setEditingPriority] = useState(false)
   const [priorityValue, setPriorityValue] = useState(String(credential.priority))
@@ -67,7 +67,7 @@ export function CredentialCard({
   const resetFailure = useResetFailure()
   const deleteCredential = useDeleteCredential()
 
-  const isCloudPass = !!(cloudPassCredentialId && credential.id === cloudPassCredentialId)
+  const isCloudPass = !!cloudPassCredentialIds?.includes(credential.id)
 
   const handleToggleDisabled = () => {
     setDisabled.mutate(
@@ -154,6 +154,16 @@ export function CredentialCard({
                   <Badge variant="outline">Cloud Pass</Badge>
                 )}
               </CardTitle>
+              {credential.accountId && (
+                <span className="text-xs text-muted-foreground font-mono" title="账户 ID">
+                  {credential.accountId}
+                </span>
+              )}
+              {credential.note && (
+                <span className="text-xs text-muted-foreground truncate max-w-[16rem]" title={credential.note}>
+                  {credential.note}
+                </span>
+              )}
             </div>
             <div className="flex items-center gap-2">
               <span className="text-sm text-muted-foreground">启用</span>
@@ -221,7 +231,7 @@ export function CredentialCard({
               <span className="font-medium">
                 {loadingBalance ? (
                   <Loader2 className="inline w-3 h-3 animate-spin" />
-                ) : balance?.subscriptionTitle || '未知'}
+                ) : balance?.subscriptionTitle || credential.subscriptionTitle || '未知'}
               </span>
             </div>
             <div>
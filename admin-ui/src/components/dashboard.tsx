@@ -2,7 +2,7 @@ import { useState, useEffect, useRef } from 'react'
 import { RefreshCw, LogOut, Moon, Sun, Server, Plus, Upload, FileUp, Trash2, RotateCcw, CheckCircle2 } from 'lucide-react'
 import { useQueryClient } from '@tanstack/react-query'
 import { toast } from 'sonner'
-import { storage } from '@/lib/storage'
+import { logout } from '@/api/credentials'
 import { Card, CardContent, CardHeader, CardTitle } from '@/components/ui/card'
 import { Button } from '@/components/ui/button'
 import { Badge } from '@/components/ui/badge'
@@ -12,8 +12,8 @@ import { AddCredentialDialog } from '@/components/add-credential-dialog'
 import { BatchImportDialog } from '@/components/batch-import-dialog'
 import { KamImportDialog } from '@/components/kam-import-dialog'
 import { BatchVerifyDialog, type VerifyResult } from '@/components/batch-verify-dialog'
-import { useCredentials, useDeleteCredential, useResetFailure, useLoadBalancingMode, useSetLoadBalancingMode, useCloudPassStatus, useRefreshCloudPass } from '@/hooks/use-credentials'
-import { getCredentialBalance } from '@/api/credentials'
+import { useCredentials, useDeleteCredential, useResetFailure, useLoadBalancingMode, useSetLoadBalancingMode, useCloudPassStatus, useRefreshCloudPass, useClaimCloudPass } from '@/hooks/use-credentials'
+import { getCredentialBalance, getCredentialBalances } from '@/api/credentials'
 import { extractErrorMessage } from '@/lib/utils'
 import type { BalanceResponse } from '@/types/api'
 
@@ -54,6 +54,10 @@ export function Dashboard({ onLogout }: DashboardProps) {
   const { mutate: setLoadBalancingMode, isPending: isSettingMode } = useSetLoadBalancingMode()
   const { data: cloudPassStatus } = useCloudPassStatus()
   const { mutate: triggerCloudPassRefresh, isPending: isRefreshingCloudPass } = useRefreshCloudPass()
+  const { mutate: claimCloudPass, isPending: isClaimingCloudPass } = useClaimCloudPass()
+  const cloudPassCredentialIds = (cloudPassStatus?.licenses ?? [])
+    .map((license) => license.injectedCredentialId)
+    .filter((id): id is number => id !== null)
 
   // 计算分页
   const totalPages = Math.ceil((data?.credentials.length || 0) / itemsPerPage)
@@ -121,9 +125,12 @@ export function Dashboard({ onLogout }: DashboardProps) {
   }
 
   const handleLogout = () => {
-    storage.removeApiKey()
-    queryClient.clear()
-    onLogout()
+    logout()
+      .catch(() => {})
+      .finally(() => {
+        queryClient.clear()
+        onLogout()
+      })
   }
 
   // 选择管理
@@ -295,7 +302,7 @@ export function Dashboard({ onLogout }: DashboardProps) {
     deselectAll()
   }
 
-  // 查询当前页凭据信息（逐个查询，避免瞬时并发）
+  // 查询当前页凭据信息（批量接口，服务端限制并发）
   const handleQueryCurrentPageInfo = async () => {
     if (currentCredentials.length === 0) {
       toast.error('当前页没有可查询的凭据')
@@ -313,43 +320,30 @@ export function Dashboard({ onLogout }: DashboardProps) {
 
     setQueryingInfo(true)
     setQueryInfoProgress({ current: 0, total: ids.length })
+    setLoadingBalanceIds(new Set(ids))
 
     let successCount = 0
     let failCount = 0
 
-    for (let i = 0; i < ids.length; i++) {
-      const id = ids[i]
+    try {
+      const { balances, errors } = await getCredentialBalances(ids)
+      successCount = balances.length
+      failCount = errors.length
 
-      setLoadingBalanceIds(prev => {
-        const next = new Set(prev)
-        next.add(id)
+      setBalanceMap(prev => {
+        const next = new Map(prev)
+        balances.forEach(balance => next.set(balance.id, balance))
         return next
       })
-
-      try {
-        const balance = await getCredentialBalance(id)
-        successCount++
-
-        setBalanceMap(prev => {
-          const next = new Map(prev)
-          next.set(id, balance)
-          return next
-        })
-      } catch (error) {
-        failCount++
-      } finally {
-        setLoadingBalanceIds(prev => {
-          const next = new Set(prev)
-          next.delete(id)
-          return next
-        })
-      }
-
-      setQueryInfoProgress({ current: i + 1, total: ids.length })
+    } catch (error) {
+      toast.error(`查询失败: ${extractErrorMessage(error)}`)
+      return
+    } finally {
+      setLoadingBalanceIds(new Set())
+      setQueryInfoProgress({ current: ids.length, total: ids.length })
+      setQueryingInfo(false)
     }
 
-    setQueryingInfo(false)
-
     if (failCount === 0) {
       toast.success(`查询完成：成功 ${successCount}/${ids.length}`)
     } else {
@@ -593,53 +587,119 @@ export function Dashboard({ onLogout }: DashboardProps) {
                 </Button>
               </div>
             </CardHeader>
-            <CardContent>
+            <CardContent className="space-y-4">
               <div className="grid grid-cols-2 md:grid-cols-4 gap-4 text-sm">
                 <div>
                   <span className="text-muted-foreground">服务器：</span>
                   <span className="font-medium">{cloudPassStatus.serverUrl}</span>
                 </div>
-                <div>
-                  <span className="text-muted-foreground">设备 ID：</span>
-                  <span className="font-medium font-mono text-xs">{cloudPassStatus.deviceId.slice(0, 12)}...</span>
-                </div>
-                <div>
-                  <span className="text-muted-foreground">激活码：</span>
-                  <span className="font-medium">{cloudPassStatus.licenseCodeMasked}</span>
-                </div>
                 <div>
                   <span className="text-muted-foreground">刷新间隔：</span>
                   <span className="font-medium">{cloudPassStatus.refreshInterval}s</span>
                 </div>
                 <div>
-                  <span className="text-muted-foreground">上次刷新：</span>
+                  <span className="text-muted-foreground">心跳间隔：</span>
                   <span className="font-medium">
-                    {cloudPassStatus.lastRefreshAt
-                      ? new Date(cloudPassStatus.lastRefreshAt).toLocaleTimeString()
-                  : '未刷新'}
+                    {cloudPassStatus.heartbeatInterval > 0 ? `${cloudPassStatus.heartbeatInterval}s` : '已关闭'}
                   </span>
                 </div>
                 <div>
-                  <span className="text-muted-foreground">刷新状态：</span>
-                  <span className={cloudPassStatus.lastRefreshOk ? 'text-green-600 font-medium' : 'text-red-500 font-medium'}>
-                    {cloudPassStatus.lastRefreshOk ? '成功' : (cloudPassStatus.lastRefreshError || '未刷新')}
-                  </span>
-                </div>
-                <div>
-                  <span className="text-muted-foreground">成功/失败：</span>
-                  <span className="font-medium">
-                    {cloudPassStatus.refreshSuccessCount}/{cloudPassStatus.refreshFailureCount}
-                  </span>
-                </div>
-                <div>
-                  <span className="text-muted-foreground">License 到期：</span>
-                  <span className="font-medium">
-                    {cloudPassStatus.licenseExpiresAt
-                      ? new Date(cloudPassStatus.licenseExpiresAt).toLocaleString()
-                      : '未知'}
-                  </span>
+                  <span className="text-muted-foreground">激活码数量：</span>
+                  <span className="font-medium">{cloudPassStatus.licenses.length}</span>
                 </div>
               </div>
+              {cloudPassStatus.licenses.map((license) => (
+                <div key={license.deviceId} className="grid grid-cols-2 md:grid-cols-4 gap-4 text-sm border-t pt-3">
+                  <div>
+                    <span className="text-muted-foreground">激活码：</span>
+                    <span className="font-medium">{license.licenseCodeMasked}</span>
+                    {license.kicked && (
+                      <>
+                        <Badge variant="destructive" className="ml-2">已被踢出</Badge>
+                        <Button
+                          variant="link"
+                          size="sm"
+                          className="h-auto p-0 ml-2"
+                          disabled={isClaimingCloudPass}
+                          onClick={() => {
+                            claimCloudPass(license.licenseCodeMasked, {
+                              onSuccess: (response) => {
+                                const failed = response.results.find((r) => !r.success)
+                                if (failed) {
+                                  toast.error(`抢占失败: ${failed.error}`)
+                                } else {
+                                  toast.success('已重新抢占，正在刷新凭证')
+                                }
+                              },
+                              onError: (error) => {
+                                toast.error(`抢占失败: ${extractErrorMessage(error)}`)
+                              },
+                            })
+                          }}
+                        >
+                          重新抢占
+                        </Button>
+                      </>
+                    )}
+                  </div>
+                  <div>
+                    <span className="text-muted-foreground">设备 ID：</span>
+                    <span className="font-medium font-mono text-xs">{license.deviceId.slice(0, 12)}...</span>
+                  </div>
+                  <div>
+                    <span className="text-muted-foreground">上次刷新：</span>
+                    <span className="font-medium">
+                      {license.lastRefreshAt
+                        ? new Date(license.lastRefreshAt).toLocaleTimeString()
+                        : '未刷新'}
+                    </span>
+                  </div>
+                  <div>
+                    <span className="text-muted-foreground">刷新状态：</span>
+                    <span className={license.lastRefreshOk ? 'text-green-600 font-medium' : 'text-red-500 font-medium'}>
+                      {license.lastRefreshOk ? '成功' : (license.lastRefreshError || '未刷新')}
+                    </span>
+                  </div>
+                  <div>
+                    <span className="text-muted-foreground">成功/失败：</span>
+                    <span className="font-medium">
+                      {license.refreshSuccessCount}/{license.refreshFailureCount}
+                    </span>
+                  </div>
+                  <div>
+                    <span className="text-muted-foreground">下次刷新：</span>
+                    <span className={license.retryBackoffSecs !== null ? 'text-yellow-600 font-medium' : 'font-medium'}>
+                      {license.nextRefreshAt
+                        ? new Date(license.nextRefreshAt).toLocaleTimeString()
+                        : '未计划'}
+                      {license.retryBackoffSecs !== null &&
+                        `（连续失败 ${license.consecutiveFailures} 次，退避 ${license.retryBackoffSecs}s）`}
+                    </span>
+                  </div>
+                  <div>
+                    <span className="text-muted-foreground">上次心跳：</span>
+                    <span className={license.lastHeartbeatAt && !license.lastHeartbeatOk ? 'text-red-500 font-medium' : 'font-medium'}>
+                      {license.lastHeartbeatAt
+                        ? `${new Date(license.lastHeartbeatAt).toLocaleTimeString()}${license.lastHeartbeatOk ? '' : `（${license.lastHeartbeatError}）`}`
+                        : '无'}
+                    </span>
+                  </div>
+                  <div>
+                    <span className="text-muted-foreground">注入凭据：</span>
+                    <span className="font-medium">
+                      {license.injectedCredentialId !== null ? `#${license.injectedCredentialId}` : '无'}
+                    </span>
+                  </div>
+                  <div>
+                    <span className="text-muted-foreground">License 到期：</span>
+                    <span className="font-medium">
+                      {license.licenseExpiresAt
+                        ? new Date(license.licenseExpiresAt).toLocaleString()
+                        : '未知'}
+                    </span>
+                  </div>
+                </div>
+              ))}
             </CardContent>
           </Card>
         )}
@@ -743,7 +803,7 @@ export function Dashboard({ onLogout }: DashboardProps) {
                     onToggleSelect={() => toggleSelect(credential.id)}
                     balance={balanceMap.get(credential.id) || null}
                     loadingBalance={loadingBalanceIds.has(credential.id)}
-                    cloudPassCredentialId={cloudPassStatus?.enabled ? (cloudPassStatus.injectedCredentialId ?? undefined) : undefined}
+                    cloudPassCredentialIds={cloudPassStatus?.enabled ? cloudPassCredentialIds : undefined}
                   />
                 ))}
               </div>
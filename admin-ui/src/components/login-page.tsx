@@ -1,30 +1,35 @@
-import { useState, useEffect } from 'react'
+import { useState } from 'react'
 import { KeyRound } from 'lucide-react'
-import { storage } from '@/lib/storage'
+import { toast } from 'sonner'
+import { login } from '@/api/credentials'
+import { extractErrorMessage } from '@/lib/utils'
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card'
 import { Input } from '@/components/ui/input'
 import { Button } from '@/components/ui/button'
 
 interface LoginPageProps {
-  onLogin: (apiKey: string) => void
+  onLogin: () => void
 }
 
 export function LoginPage({ onLogin }: LoginPageProps) {
   const [apiKey, setApiKey] = useState('')
+  const [submitting, setSubmitting] = useState(false)
 
-  useEffect(() => {
-    // 从 storage 读取保存的 API Key
-    const savedKey = storage.getApiKey()
-    if (savedKey) {
-      setApiKey(savedKey)
-    }
-  }, [])
-
-  const handleSubmit = (e: React.FormEvent) => {
+  const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault()
-    if (apiKey.trim()) {
-      storage.setApiKey(apiKey.trim())
-      onLogin(apiKey.trim())
+    if (!apiKey.trim()) {
+      return
+    }
+    setSubmitting(true)
+    try {
+      // Key 只用于换取会话 Cookie，不在浏览器中保存
+      await login(apiKey.trim())
+      setApiKey('')
+      onLogin()
+    } catch (error) {
+      toast.error(`登录失败: ${extractErrorMessage(error)}`)
+    } finally {
+      setSubmitting(false)
     }
   }
 
@@ -51,7 +56,7 @@ export function LoginPage({ onLogin }: LoginPageProps) {
                 className="text-center"
               />
             </div>
-            <Button type="submit" className="w-full" disabled={!apiKey.trim()}>
+            <Button type="submit" className="w-full" disabled={!apiKey.trim() || submitting}>
               登录
             </Button>
           </form>
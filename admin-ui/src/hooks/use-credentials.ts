@@ -11,6 +11,7 @@ import {
   setLoadBalancingMode,
   getCloudPassStatus,
   refreshCloudPass,
+  claimCloudPass,
 } from '@/api/credentials'
 import type { AddCredentialRequest } from '@/types/api'
 
@@ -119,6 +120,21 @@ export function useCloudPassStatus() {
   })
 }
 
+// 手动抢占 Cloud Pass 设备
+export function useClaimCloudPass() {
+  const queryClient = useQueryClient()
+  return useMutation({
+    mutationFn: (license?: string) => claimCloudPass(license),
+    onSuccess: () => {
+      // 抢占成功后会触发刷新，延迟更新状态
+      setTimeout(() => {
+        queryClient.invalidateQueries({ queryKey: ['cloudPassStatus'] })
+        queryClient.invalidateQueries({ queryKey: ['credentials'] })
+      }, 2000)
+    },
+  })
+}
+
 // 手动刷新 Cloud Pass
 export function useRefreshCloudPass() {
   const queryClient = useQueryClient()
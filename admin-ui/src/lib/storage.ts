@@ -1,7 +1,16 @@
-const API_KEY_STORAGE_KEY = 'adminApiKey'
+// 旧版本把 Admin API Key 保存在 localStorage，现已改为 HttpOnly 会话 Cookie
+const LEGACY_API_KEY_STORAGE_KEY = 'adminApiKey'
+localStorage.removeItem(LEGACY_API_KEY_STORAGE_KEY)
+
+// CSRF Token 仅保存在内存中，页面刷新后通过 GET /session 重新获取
+let csrfToken: string | null = null
 
 export const storage = {
-  getApiKey: () => localStorage.getItem(API_KEY_STORAGE_KEY),
-  setApiKey: (key: string) => localStorage.setItem(API_KEY_STORAGE_KEY, key),
-  removeApiKey: () => localStorage.removeItem(API_KEY_STORAGE_KEY),
+  getCsrfToken: () => csrfToken,
+  setCsrfToken: (token: string) => {
+    csrfToken = token
+  },
+  clear: () => {
+    csrfToken = null
+  },
 }
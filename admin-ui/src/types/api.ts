@@ -14,15 +14,70 @@ export interface CredentialStatusItem {
   failureCount: number
   isCurrent: boolean
   expiresAt: string | null
+  nextRefreshAt: string | null
   authMethod: string | null
   hasProfileArn: boolean
   email?: string
+  accountId?: string
+  subscriptionTitle?: string
   refreshTokenHash?: string
   successCount: number
   lastUsedAt: string | null
   hasProxy: boolean
   proxyUrl?: string
   machineId?: string
+  lastError?: UpstreamError
+  healthStatus: 'unknown' | 'healthy' | 'degraded'
+  lastHealthCheckAt: string | null
+  tags: string[]
+  note?: string
+  allowedModels?: string[]
+  blockedModels?: string[]
+  quotaReset?: ZonedTime
+  maxConcurrency?: number
+  inFlight: number
+  usageCap?: UsageCap
+  windowUsage: WindowUsage
+  usageWatermark?: UsageWatermark
+  watermarkReached: boolean
+}
+
+// 凭据滚动窗口用量上限（未设置或为 0 表示不限制）
+export interface UsageCap {
+  hourlyRequests?: number
+  hourlyTokens?: number
+  dailyRequests?: number
+  dailyTokens?: number
+}
+
+// 用量警戒线：已用额度百分比达到 percent 时触发 action
+export type WatermarkAction = 'alert' | 'deprioritize' | 'disable'
+
+export interface UsageWatermark {
+  percent: number
+  action: WatermarkAction
+}
+
+// 设置凭据级用量警戒线（percent 为 null 表示回退到全局配置）
+export interface SetUsageWatermarkRequest {
+  percent: number | null
+  action?: WatermarkAction
+}
+
+// 最近 1 小时 / 24 小时用量
+export interface WindowUsage {
+  hourRequests: number
+  hourTokens: number
+  dayRequests: number
+  dayTokens: number
+}
+
+// 上游错误记录
+export interface UpstreamError {
+  errorType: string
+  status?: number
+  message: string
+  occurredAt: string
 }
 
 // 余额响应
@@ -34,6 +89,298 @@ export interface BalanceResponse {
   remaining: number
   usagePercentage: number
   nextResetAt: number | null
+  nextReset?: ZonedTime
+}
+
+// 批量余额查询响应（按凭据 ID 排序）
+export interface BalancesResponse {
+  balances: BalanceResponse[]
+  errors: { id: number; error: string }[]
+}
+
+// 同时以 UTC 和运营者时区表示的时间
+export interface ZonedTime {
+  timestamp: number
+  utc: string
+  local: string
+  timezone: string
+}
+
+// 单日用量
+export interface DailyUsage {
+  requests: number
+  inputTokens: number
+  outputTokens: number
+  errors: number
+  contentFiltered: number
+}
+
+// 凭据用量响应
+export interface CredentialUsageResponse {
+  id: number
+  days: (DailyUsage & { date: string })[]
+  total: DailyUsage
+}
+
+// 登录会话
+export interface SessionResponse {
+  csrfToken: string
+  expiresAt: string
+  role: AdminRole
+}
+
+// Admin 密钥（viewer 只读）
+export type AdminRole = 'viewer' | 'admin'
+
+export interface AdminKeyItem {
+  name: string
+  role: AdminRole
+  keyPrefix: string
+}
+
+export interface AdminKeysResponse {
+  keys: AdminKeyItem[]
+}
+
+export interface CreateAdminKeyRequest {
+  name: string
+  role?: AdminRole
+  key?: string
+}
+
+export interface CreateAdminKeyResponse extends AdminKeyItem {
+  key: string
+}
+
+// 托管 API Key
+export interface ApiKeyQuotas {
+  dailyTokens?: number
+  dailyRequests?: number
+  monthlyTokens?: number
+  monthlyRequests?: number
+}
+
+export interface PeriodUsage {
+  start?: string
+  requests: number
+  tokens: number
+}
+
+export interface QuotaExceeded {
+  quota: 'totalTokens' | 'dailyTokens' | 'dailyRequests' | 'monthlyTokens' | 'monthlyRequests'
+  limit: number
+  used: number
+  resetAt: ZonedTime | null
+}
+
+export interface ApiKeyItem {
+  id: string
+  name: string
+  keyPrefix: string
+  tokenQuota?: number
+  quotas: ApiKeyQuotas
+  quotaExceeded: QuotaExceeded | null
+  usage: {
+    requests: number
+    inputTokens: number
+    outputTokens: number
+    lastUsedAt?: string
+  }
+  daily: PeriodUsage
+  monthly: PeriodUsage
+  createdAt: string
+}
+
+export interface ApiKeysResponse {
+  keys: ApiKeyItem[]
+}
+
+export interface CreateApiKeyRequest {
+  name: string
+  tokenQuota?: number
+  quotas?: ApiKeyQuotas
+  key?: string
+}
+
+export interface SetApiKeyQuotasRequest {
+  tokenQuota?: number
+  quotas?: ApiKeyQuotas
+}
+
+export interface CreateApiKeyResponse extends ApiKeyItem {
+  key: string
+}
+
+// 上游延迟汇总
+export interface LatencySummary {
+  count: number
+  avgMs: number
+  p50Ms: number
+  p95Ms: number
+  p99Ms: number
+  maxMs: number
+  histogram: { leMs: number | null; count: number }[]
+}
+
+export interface CredentialLatency {
+  id: number
+  ttfb: LatencySummary
+  total: LatencySummary
+}
+
+export interface SizeBucket {
+  leBytes?: number
+  count: number
+}
+
+export interface CorruptFrameSummary {
+  preludeCrcMismatches: number
+  messageCrcMismatches: number
+  toleratedFrames: number
+}
+
+export interface DecoderBufferSummary {
+  highWaterBytes: number
+  overflows: number
+}
+
+export interface RequestSizeSummary {
+  totalRequests: number
+  totalBytes: number
+  count: number
+  avgBytes: number
+  p50Bytes: number
+  p95Bytes: number
+  p99Bytes: number
+  maxBytes: number
+  histogram: SizeBucket[]
+}
+
+export interface ResponseCacheStats {
+  hits: number
+  misses: number
+  entries: number
+}
+
+export interface StatsResponse {
+  ttfb: LatencySummary
+  total: LatencySummary
+  credentials: CredentialLatency[]
+  requestSize: RequestSizeSummary
+  corruptFrames: CorruptFrameSummary
+  decoderBuffer: DecoderBufferSummary
+  responseCache?: ResponseCacheStats
+}
+
+// 上游协议漂移
+export type DriftKind =
+  | 'unknown_message_type'
+  | 'unknown_event_type'
+  | 'unexpected_field'
+  | 'schema_error'
+
+export interface DriftEntry {
+  kind: DriftKind
+  eventType: string
+  detail: string
+  count: number
+  firstSeen: string
+  lastSeen: string
+  sample: string
+}
+
+export interface DriftReport {
+  totalFrames: number
+  totalDrift: number
+  lastHourFrames: number
+  lastHourDrift: number
+  entries: DriftEntry[]
+}
+
+// 优先级预设
+export interface PriorityPreset {
+  tags: Record<string, number>
+  defaultPriority?: number
+  schedule?: string[]
+}
+
+export interface AppliedPriorityPreset {
+  name: string
+  appliedAt: string
+  changed: number
+}
+
+export interface PriorityPresetsResponse {
+  presets: Record<string, PriorityPreset>
+  active?: AppliedPriorityPreset
+}
+
+// 后台任务存活状态
+export interface WorkerStatus {
+  name: string
+  state: 'running' | 'restarting' | 'failed' | 'stopped'
+  restarts: number
+  startedAt: string
+  lastExitAt?: string
+  lastError?: string
+}
+
+// 主备选主状态
+export interface LeaderStatus {
+  nodeId: string
+  leader: boolean
+}
+
+export interface LogLevelResponse {
+  filter: string
+}
+
+export interface CaptureStatus {
+  armed: boolean
+  remaining: number
+  dir: string | null
+  recent: string[]
+}
+
+export interface DebugStateResponse {
+  workers: WorkerStatus[]
+  leader?: LeaderStatus
+}
+
+// 请求审计记录
+export interface AuditRecord {
+  timestamp: string
+  endpoint: string
+  apiKey: string
+  clientIp?: string
+  credentialId?: number
+  model: string
+  stream: boolean
+  inputTokens: number
+  outputTokens: number
+  requestBytes?: number
+  latencyMs: number
+  status: number
+  event?: string
+}
+
+export interface AuditLogsQuery {
+  page?: number
+  pageSize?: number
+  apiKey?: string
+  credentialId?: number
+  model?: string
+  status?: number
+  event?: string
+  from?: string
+  to?: string
+}
+
+export interface AuditLogsResponse {
+  total: number
+  page: number
+  pageSize: number
+  records: AuditRecord[]
 }
 
 // 成功响应
@@ -59,6 +406,60 @@ export interface SetPriorityRequest {
   priority: number
 }
 
+// 设置凭据标签请求
+export interface SetTagsRequest {
+  tags: string[]
+}
+
+// 设置凭据备注请求（为空时清除）
+export interface SetNoteRequest {
+  note: string | null
+}
+
+// 设置凭据模型白名单 / 黑名单请求（整体替换）
+export interface SetModelsRequest {
+  allowedModels: string[]
+  blockedModels: string[]
+}
+
+// 重复凭据分组
+export interface DuplicateGroup {
+  keep: number
+  remove: number[]
+  reasons: string[]
+}
+
+// 合并重复凭据响应
+export interface DedupeCredentialsResponse {
+  dryRun: boolean
+  groups: DuplicateGroup[]
+  removed: number
+}
+
+// 凭据自动发现结果（单个 Token 文件）
+export interface DiscoveryResult {
+  path: string
+  status: 'imported' | 'duplicate' | 'failed'
+  credentialId?: number
+  message?: string
+}
+
+// 凭据自动发现报告
+export interface DiscoveryReport {
+  paths: string[]
+  imported: number
+  duplicates: number
+  failed: number
+  results: DiscoveryResult[]
+}
+
+// 设置凭据代理请求（proxyUrl 为 null 回退到全局代理，"direct" 表示直连）
+export interface SetProxyRequest {
+  proxyUrl: string | null
+  proxyUsername?: string
+  proxyPassword?: string
+}
+
 // 添加凭据请求
 export interface AddCredentialRequest {
   refreshToken: string
@@ -72,6 +473,7 @@ export interface AddCredentialRequest {
   proxyUrl?: string
   proxyUsername?: string
   proxyPassword?: string
+  note?: string
 }
 
 // 添加凭据响应
@@ -87,17 +489,119 @@ export interface CloudPassStatus {
   enabled: boolean
   connected: boolean
   serverUrl: string
-  deviceId: string
-  licenseCodeMasked: string
   refreshInterval: number
+  heartbeatInterval: number
   reassign: boolean
   clientVersion: string
+  kicked: boolean
+  licenses: CloudPassLicenseStatus[]
+}
+
+// Cloud Pass 配置（激活码脱敏）
+export interface CloudPassConfig {
+  configured: boolean
+  enabled: boolean
+  serverUrl: string
+  refreshInterval: number
+  heartbeatInterval: number
+  reassign: boolean
+  clientVersion: string
+  licenses: string[]
+}
+
+// 更新 Cloud Pass 配置请求（省略的字段保持不变）
+export interface UpdateCloudPassConfigRequest {
+  enabled?: boolean
+  serverUrl?: string
+  refreshInterval?: number
+  heartbeatInterval?: number
+  reassign?: boolean
+  licenseCode?: string
+  licenses?: { licenseCode: string; deviceId?: string; machineId?: string }[]
+}
+
+// 手动抢占 Cloud Pass 设备的结果
+export interface ClaimCloudPassResponse {
+  results: {
+    license: string
+    deviceId: string
+    success: boolean
+    error?: string
+  }[]
+}
+
+// Cloud Pass 单个激活码的运行时状态
+export interface CloudPassLicenseStatus {
+  licenseCodeMasked: string
+  deviceId: string
+  connected: boolean
   lastRefreshAt: string | null
   lastRefreshOk: boolean
   lastRefreshError: string | null
   refreshSuccessCount: number
   refreshFailureCount: number
+  consecutiveFailures: number
+  retryBackoffSecs: number | null
+  nextRefreshAt: string | null
+  lastHeartbeatAt: string | null
+  lastHeartbeatOk: boolean
+  lastHeartbeatError: string | null
   licenseExpiresAt: string | null
   kicked: boolean
   injectedCredentialId: number | null
+  supersededCredentialIds: number[]
+}
+
+// 可编辑的配置子集
+export interface ConfigResponse {
+  region: string
+  authRegion: string | null
+  apiRegion: string | null
+  kiroVersion: string
+  systemVersion: string
+  nodeVersion: string
+  proxyUrl: string | null
+  proxyUsername: string | null
+  hasProxyPassword: boolean
+  countTokensApiUrl: string | null
+  hasCountTokensApiKey: boolean
+  countTokensAuthType: string
+  timezone: string | null
+}
+
+// 更新配置请求（未提供的字段保持不变，空字符串表示清除）
+export interface UpdateConfigRequest {
+  region?: string
+  authRegion?: string
+  apiRegion?: string
+  kiroVersion?: string
+  systemVersion?: string
+  nodeVersion?: string
+  proxyUrl?: string
+  proxyUsername?: string
+  proxyPassword?: string
+  countTokensApiUrl?: string
+  countTokensApiKey?: string
+  countTokensAuthType?: 'x-api-key' | 'bearer'
+  timezone?: string
+}
+
+// 更新配置响应
+export interface UpdateConfigResponse {
+  config: ConfigResponse
+  requiresRestart: string[]
+}
+
+// 单个通知目标的测试投递结果
+export interface WebhookTestResult {
+  channel: 'webhook' | 'telegram' | 'slack' | 'discord'
+  url?: string
+  success: boolean
+  status?: number
+  error?: string
+}
+
+// 通知测试投递响应
+export interface TestWebhooksResponse {
+  results: WebhookTestResult[]
 }
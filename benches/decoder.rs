@@ -0,0 +1,119 @@
+//! 事件流解码基准测试
+//!
+//! 模拟大体积流式响应（大量小的 assistantResponseEvent 帧 + 一个大帧），比较：
+//! - `EventStreamDecoder`：按 16 KB 分块送入，帧直接切分解码器缓冲区（零复制）
+//! - `parse_frame`：逐帧解析并复制帧数据（重构前解码器的做法）
+//!
+//! 两者耗时都以 CRC 校验为主，差别主要体现在内存分配次数上，开始计时前会打印每帧平均分配次数。
+//!
+//! 运行：`cargo bench --bench decoder`
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use kiro_rs::kiro::parser::decoder::EventStreamDecoder;
+use kiro_rs::kiro::parser::frame::{FrameBuilder, parse_frame};
+
+/// 上游分包大小
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// 统计分配次数的全局分配器
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// 约 9 MB 的流式响应：8000 个 1 KB 文本帧与一个 1 MB 的工具调用帧
+fn large_stream() -> (Vec<u8>, usize) {
+    let text = FrameBuilder::event("assistantResponseEvent")
+        .payload(format!(r#"{{"content":"{}"}}"#, "x".repeat(1000)))
+        .to_bytes();
+    let tool = FrameBuilder::event("toolUseEvent")
+        .payload(format!(r#"{{"input":"{}"}}"#, "y".repeat(1024 * 1024)))
+        .to_bytes();
+    let mut data = Vec::new();
+    for i in 0..8000 {
+        data.extend_from_slice(&text);
+        if i == 4000 {
+            data.extend_from_slice(&tool);
+        }
+    }
+    (data, 8001)
+}
+
+fn decode_zero_copy(data: &[u8]) -> usize {
+    let mut decoder = EventStreamDecoder::new();
+    let mut decoded = 0;
+    for chunk in data.chunks(CHUNK_SIZE) {
+        decoder.feed(chunk).unwrap();
+        for frame in decoder.decode_iter() {
+            black_box(frame.unwrap());
+            decoded += 1;
+        }
+    }
+    decoded
+}
+
+fn decode_copy(data: &[u8]) -> usize {
+    let mut offset = 0;
+    let mut decoded = 0;
+    while let Some((frame, consumed)) = parse_frame(&data[offset..]).unwrap() {
+        black_box(frame);
+        offset += consumed;
+        decoded += 1;
+    }
+    decoded
+}
+
+/// 打印每帧平均分配次数
+fn report_allocations(name: &str, data: &[u8], frames: usize, decode: fn(&[u8]) -> usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    assert_eq!(decode(data), frames);
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "{}: {} 次分配，每帧 {:.2} 次",
+        name,
+        allocations,
+        allocations as f64 / frames as f64
+    );
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let (data, frames) = large_stream();
+    report_allocations("decoder_zero_copy", &data, frames, decode_zero_copy);
+    report_allocations("parse_frame_copy", &data, frames, decode_copy);
+
+    let mut group = c.benchmark_group("large_stream");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.sample_size(20);
+    group.bench_function("decoder_zero_copy", |b| {
+        b.iter(|| assert_eq!(decode_zero_copy(&data), frames))
+    });
+    group.bench_function("parse_frame_copy", |b| {
+        b.iter(|| assert_eq!(decode_copy(&data), frames))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
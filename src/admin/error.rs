@@ -5,12 +5,13 @@ use std::fmt;
 use axum::http::StatusCode;
 
 use super::types::AdminErrorResponse;
+use crate::kiro::model::credentials::CredentialId;
 
 /// Admin 服务错误类型
 #[derive(Debug)]
 pub enum AdminServiceError {
     /// 凭据不存在
-    NotFound { id: u64 },
+    NotFound { id: CredentialId },
 
     /// 上游服务调用失败（网络、API 错误等）
     UpstreamError(String),
@@ -20,6 +21,18 @@ pub enum AdminServiceError {
 
     /// 凭据无效（验证失败）
     InvalidCredential(String),
+
+    /// 请求参数无效
+    InvalidRequest(String),
+
+    /// 托管 API Key 不存在
+    ApiKeyNotFound { id: String },
+
+    /// 优先级预设不存在
+    PresetNotFound { name: String },
+
+    /// Admin Key 不存在
+    AdminKeyNotFound { name: String },
 }
 
 impl fmt::Display for AdminServiceError {
@@ -31,6 +44,10 @@ impl fmt::Display for AdminServiceError {
             AdminServiceError::UpstreamError(msg) => write!(f, "上游服务错误: {}", msg),
             AdminServiceError::InternalError(msg) => write!(f, "内部错误: {}", msg),
             AdminServiceError::InvalidCredential(msg) => write!(f, "凭据无效: {}", msg),
+            AdminServiceError::InvalidRequest(msg) => write!(f, "请求无效: {}", msg),
+            AdminServiceError::ApiKeyNotFound { id } => write!(f, "API Key 不存在: {}", id),
+            AdminServiceError::PresetNotFound { name } => write!(f, "优先级预设不存在: {}", name),
+            AdminServiceError::AdminKeyNotFound { name } => write!(f, "Admin Key 不存在: {}", name),
         }
     }
 }
@@ -41,22 +58,32 @@ impl AdminServiceError {
     /// 获取对应的 HTTP 状态码
     pub fn status_code(&self) -> StatusCode {
         match self {
-            AdminServiceError::NotFound { .. } => StatusCode::NOT_FOUND,
+            AdminServiceError::NotFound { .. }
+            | AdminServiceError::ApiKeyNotFound { .. }
+            | AdminServiceError::PresetNotFound { .. }
+            | AdminServiceError::AdminKeyNotFound { .. } => StatusCode::NOT_FOUND,
             AdminServiceError::UpstreamError(_) => StatusCode::BAD_GATEWAY,
             AdminServiceError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            AdminServiceError::InvalidCredential(_) => StatusCode::BAD_REQUEST,
+            AdminServiceError::InvalidCredential(_) | AdminServiceError::InvalidRequest(_) => {
+                StatusCode::BAD_REQUEST
+            }
         }
     }
 
     /// 转换为 API 错误响应
     pub fn into_response(self) -> AdminErrorResponse {
         match &self {
-            AdminServiceError::NotFound { .. } => AdminErrorResponse::not_found(self.to_string()),
+            AdminServiceError::NotFound { .. }
+            | AdminServiceError::ApiKeyNotFound { .. }
+            | AdminServiceError::PresetNotFound { .. }
+            | AdminServiceError::AdminKeyNotFound { .. } => {
+                AdminErrorResponse::not_found(self.to_string())
+            }
             AdminServiceError::UpstreamError(_) => AdminErrorResponse::api_error(self.to_string()),
             AdminServiceError::InternalError(_) => {
                 AdminErrorResponse::internal_error(self.to_string())
             }
-            AdminServiceError::InvalidCredential(_) => {
+            AdminServiceError::InvalidCredential(_) | AdminServiceError::InvalidRequest(_) => {
                 AdminErrorResponse::invalid_request(self.to_string())
             }
         }
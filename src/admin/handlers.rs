@@ -2,22 +2,39 @@
 
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::{ConnectInfo, Extension, OriginalUri, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
     response::IntoResponse,
 };
 
 use super::{
     middleware::AdminState,
+    session::{self, Session},
     types::{
-        AddCredentialRequest, SetDisabledRequest, SetLoadBalancingModeRequest, SetPriorityRequest,
-        SuccessResponse,
+        AddCredentialRequest, AdminErrorResponse, BalanceQuery, BalancesQuery, ClaimCloudPassQuery,
+        CreateAdminKeyRequest, CreateApiKeyRequest, CredentialsQuery,
+        DebugStateResponse, DedupeCredentialsRequest, ImportCredentialsRequest,
+        InvalidateBalanceCacheResponse, LoginRequest,
+        SessionResponse, SetApiKeyQuotasRequest, SetDisabledRequest, SetLoadBalancingModeRequest,
+        SetLogLevelRequest,
+        SetModelsRequest, SetNoteRequest, SetPriorityRequest, SetProxyRequest, SetRateLimitRequest, SetTagsRequest,
+        SetUsageWatermarkRequest, StartCaptureRequest, SuccessResponse, UpdateCloudPassConfigRequest, UpdateConfigRequest,
+        UsageQuery,
     },
 };
+use crate::audit::AuditQuery;
+use crate::common::ip_filter::ClientAddr;
+use crate::kiro::model::credentials::CredentialId;
+use crate::kiro::peer_sync::etag_matches;
+use crate::model::config::AdminRole;
 
 /// GET /api/admin/credentials
-/// 获取所有凭据状态
-pub async fn get_all_credentials(State(state): State<AdminState>) -> impl IntoResponse {
-    let response = state.service.get_all_credentials();
+/// 获取凭据状态（支持 `?tag=prod&disabled=false` 筛选）
+pub async fn get_all_credentials(
+    State(state): State<AdminState>,
+    Query(query): Query<CredentialsQuery>,
+) -> impl IntoResponse {
+    let response = state.service.get_all_credentials(&query);
     Json(response)
 }
 
@@ -25,7 +42,7 @@ pub async fn get_all_credentials(State(state): State<AdminState>) -> impl IntoRe
 /// 设置凭据禁用状态
 pub async fn set_credential_disabled(
     State(state): State<AdminState>,
-    Path(id): Path<u64>,
+    Path(id): Path<CredentialId>,
     Json(payload): Json<SetDisabledRequest>,
 ) -> impl IntoResponse {
     match state.service.set_disabled(id, payload.disabled) {
@@ -41,7 +58,7 @@ pub async fn set_credential_disabled(
 /// 设置凭据优先级
 pub async fn set_credential_priority(
     State(state): State<AdminState>,
-    Path(id): Path<u64>,
+    Path(id): Path<CredentialId>,
     Json(payload): Json<SetPriorityRequest>,
 ) -> impl IntoResponse {
     match state.service.set_priority(id, payload.priority) {
@@ -54,11 +71,103 @@ pub async fn set_credential_priority(
     }
 }
 
+/// POST /api/admin/credentials/:id/rate-limit
+/// 设置凭据级速率限制
+pub async fn set_credential_rate_limit(
+    State(state): State<AdminState>,
+    Path(id): Path<CredentialId>,
+    Json(payload): Json<SetRateLimitRequest>,
+) -> impl IntoResponse {
+    match state.service.set_rate_limit(id, payload.rpm) {
+        Ok(_) => {
+            let message = match payload.rpm {
+                Some(0) => format!("凭据 #{} 已取消速率限制", id),
+                Some(rpm) => format!("凭据 #{} 速率限制已设置为 {} 次/分钟", id, rpm),
+                None => format!("凭据 #{} 速率限制已恢复为全局配置", id),
+            };
+            Json(SuccessResponse::new(message)).into_response()
+        }
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// PUT /api/admin/credentials/:id/tags
+/// 设置凭据标签
+pub async fn set_credential_tags(
+    State(state): State<AdminState>,
+    Path(id): Path<CredentialId>,
+    Json(payload): Json<SetTagsRequest>,
+) -> impl IntoResponse {
+    match state.service.set_tags(id, payload.tags) {
+        Ok(_) => Json(SuccessResponse::new(format!("凭据 #{} 标签已更新", id))).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// PUT /api/admin/credentials/:id/note
+/// 设置凭据备注
+pub async fn set_credential_note(
+    State(state): State<AdminState>,
+    Path(id): Path<CredentialId>,
+    Json(payload): Json<SetNoteRequest>,
+) -> impl IntoResponse {
+    match state.service.set_note(id, payload.note) {
+        Ok(_) => Json(SuccessResponse::new(format!("凭据 #{} 备注已更新", id))).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// PUT /api/admin/credentials/:id/models
+/// 设置凭据模型白名单 / 黑名单
+pub async fn set_credential_models(
+    State(state): State<AdminState>,
+    Path(id): Path<CredentialId>,
+    Json(payload): Json<SetModelsRequest>,
+) -> impl IntoResponse {
+    match state.service.set_models(id, payload) {
+        Ok(_) => Json(SuccessResponse::new(format!("凭据 #{} 模型限制已更新", id))).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// PUT /api/admin/credentials/:id/proxy
+/// 设置凭据级出站代理
+pub async fn set_credential_proxy(
+    State(state): State<AdminState>,
+    Path(id): Path<CredentialId>,
+    Json(payload): Json<SetProxyRequest>,
+) -> impl IntoResponse {
+    match state.service.set_proxy(id, payload) {
+        Ok(_) => Json(SuccessResponse::new(format!("凭据 #{} 代理已更新", id))).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// PUT /api/admin/credentials/:id/usage-watermark
+/// 设置凭据级用量警戒线
+pub async fn set_credential_usage_watermark(
+    State(state): State<AdminState>,
+    Path(id): Path<CredentialId>,
+    Json(payload): Json<SetUsageWatermarkRequest>,
+) -> impl IntoResponse {
+    let percent = payload.percent;
+    match state.service.set_usage_watermark(id, payload) {
+        Ok(_) => {
+            let message = match percent {
+                Some(percent) => format!("凭据 #{} 用量警戒线已设置为 {}%", id, percent),
+                None => format!("凭据 #{} 用量警戒线已恢复为全局配置", id),
+            };
+            Json(SuccessResponse::new(message)).into_response()
+        }
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// POST /api/admin/credentials/:id/reset
 /// 重置失败计数并重新启用
 pub async fn reset_failure_count(
     State(state): State<AdminState>,
-    Path(id): Path<u64>,
+    Path(id): Path<CredentialId>,
 ) -> impl IntoResponse {
     match state.service.reset_and_enable(id) {
         Ok(_) => Json(SuccessResponse::new(format!(
@@ -74,14 +183,97 @@ pub async fn reset_failure_count(
 /// 获取指定凭据的余额
 pub async fn get_credential_balance(
     State(state): State<AdminState>,
-    Path(id): Path<u64>,
+    Path(id): Path<CredentialId>,
+    Query(query): Query<BalanceQuery>,
+) -> impl IntoResponse {
+    match state.service.get_balance(id, query.force).await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// GET /api/admin/credentials/balance
+/// 批量获取凭据余额（支持 `?ids=1,2,3&force=true`）
+pub async fn get_credential_balances(
+    State(state): State<AdminState>,
+    Query(query): Query<BalancesQuery>,
+) -> impl IntoResponse {
+    match state.service.get_balances(&query).await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// DELETE /api/admin/credentials/:id/balance/cache
+/// 清除指定凭据的余额缓存
+pub async fn invalidate_credential_balance_cache(
+    State(state): State<AdminState>,
+    Path(id): Path<CredentialId>,
+) -> impl IntoResponse {
+    match state.service.invalidate_balance_cache(Some(id)) {
+        Ok(removed) => Json(InvalidateBalanceCacheResponse { removed }).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// DELETE /api/admin/credentials/balance/cache
+/// 清除所有凭据的余额缓存
+pub async fn invalidate_balance_cache(State(state): State<AdminState>) -> impl IntoResponse {
+    match state.service.invalidate_balance_cache(None) {
+        Ok(removed) => Json(InvalidateBalanceCacheResponse { removed }).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// GET /api/admin/credentials/:id/usage
+/// 获取指定凭据的按日用量（支持 `?from=2025-01-01&to=2025-01-31`）
+pub async fn get_credential_usage(
+    State(state): State<AdminState>,
+    Path(id): Path<CredentialId>,
+    Query(query): Query<UsageQuery>,
 ) -> impl IntoResponse {
-    match state.service.get_balance(id).await {
+    match state.service.get_usage(id, &query) {
         Ok(response) => Json(response).into_response(),
         Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
     }
 }
 
+/// GET /api/admin/subscription-changes
+/// 获取最近的订阅变更记录
+pub async fn get_subscription_changes(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_subscription_changes())
+}
+
+/// GET /api/admin/stats
+/// 获取各凭据的上游延迟统计（TTFB / 总耗时的 P50/P95/P99 与直方图）
+pub async fn get_stats(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_stats())
+}
+
+/// GET /api/admin/drift
+/// 获取上游协议漂移报告
+pub async fn get_drift_report(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_drift_report())
+}
+
+/// GET /api/admin/presets
+/// 获取优先级预设与最近一次应用记录
+pub async fn get_priority_presets(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_priority_presets())
+}
+
+/// POST /api/admin/presets/:name/apply
+/// 应用优先级预设（按凭据标签重新设置优先级）
+pub async fn apply_priority_preset(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match state.service.apply_priority_preset(&name) {
+        Ok(applied) => Json(applied).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// POST /api/admin/credentials
 /// 添加新凭据
 pub async fn add_credential(
@@ -94,11 +286,44 @@ pub async fn add_credential(
     }
 }
 
+/// POST /api/admin/credentials/import
+/// 从 CSV / TSV 批量导入凭据（支持列映射与 dry-run）
+pub async fn import_credentials(
+    State(state): State<AdminState>,
+    Json(payload): Json<ImportCredentialsRequest>,
+) -> impl IntoResponse {
+    match state.service.import_credentials(payload).await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/credentials/discover
+/// 扫描 Kiro IDE 的 Token 缓存并导入新凭据
+pub async fn discover_credentials(State(state): State<AdminState>) -> impl IntoResponse {
+    match state.service.discover_credentials().await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/credentials/dedupe
+/// 检测并合并重复凭据（refreshToken 或账号邮箱相同，支持 dry-run）
+pub async fn dedupe_credentials(
+    State(state): State<AdminState>,
+    Json(payload): Json<DedupeCredentialsRequest>,
+) -> impl IntoResponse {
+    match state.service.dedupe_credentials(payload) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// DELETE /api/admin/credentials/:id
 /// 删除凭据
 pub async fn delete_credential(
     State(state): State<AdminState>,
-    Path(id): Path<u64>,
+    Path(id): Path<CredentialId>,
 ) -> impl IntoResponse {
     match state.service.delete_credential(id) {
         Ok(_) => Json(SuccessResponse::new(format!("凭据 #{} 已删除", id))).into_response(),
@@ -106,6 +331,88 @@ pub async fn delete_credential(
     }
 }
 
+/// GET /api/admin/api-keys
+/// 获取所有托管 API Key（含用量统计）
+pub async fn list_api_keys(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.list_api_keys())
+}
+
+/// POST /api/admin/api-keys
+/// 创建托管 API Key
+pub async fn create_api_key(
+    State(state): State<AdminState>,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> impl IntoResponse {
+    match state.service.create_api_key(payload) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// PUT /api/admin/api-keys/:id/quotas
+/// 设置托管 API Key 的总量 / 按日 / 按月配额
+pub async fn set_api_key_quotas(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+    Json(payload): Json<SetApiKeyQuotasRequest>,
+) -> impl IntoResponse {
+    match state.service.set_api_key_quotas(&id, payload) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// DELETE /api/admin/api-keys/:id
+/// 删除托管 API Key
+pub async fn delete_api_key(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.service.delete_api_key(&id) {
+        Ok(_) => Json(SuccessResponse::new(format!("API Key #{} 已删除", id))).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// GET /api/admin/admin-keys
+/// 列出 Admin Key（角色与前缀）
+pub async fn list_admin_keys(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.list_admin_keys())
+}
+
+/// POST /api/admin/admin-keys
+/// 创建带角色的 Admin Key（写入 config.json 并立即生效）
+pub async fn create_admin_key(
+    State(state): State<AdminState>,
+    Json(payload): Json<CreateAdminKeyRequest>,
+) -> impl IntoResponse {
+    match state.service.create_admin_key(payload) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// DELETE /api/admin/admin-keys/:name
+/// 删除 Admin Key
+pub async fn delete_admin_key(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match state.service.delete_admin_key(&name) {
+        Ok(_) => Json(SuccessResponse::new(format!("Admin Key {} 已删除", name))).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/webhooks/test
+/// 向所有已配置的 Webhook 与聊天渠道发送测试事件
+pub async fn test_webhooks(State(state): State<AdminState>) -> impl IntoResponse {
+    match state.service.test_webhooks().await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// GET /api/admin/config/load-balancing
 /// 获取负载均衡模式
 pub async fn get_load_balancing_mode(State(state): State<AdminState>) -> impl IntoResponse {
@@ -125,6 +432,259 @@ pub async fn set_load_balancing_mode(
     }
 }
 
+/// GET /api/admin/config
+/// 获取可编辑的配置子集
+pub async fn get_config(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_config())
+}
+
+/// PATCH /api/admin/config
+/// 更新配置子集并持久化
+pub async fn update_config(
+    State(state): State<AdminState>,
+    Json(payload): Json<UpdateConfigRequest>,
+) -> impl IntoResponse {
+    match state.service.update_config(payload) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/config/reload
+/// 重新加载 config.json 并热应用
+pub async fn reload_config(State(state): State<AdminState>) -> impl IntoResponse {
+    match state.service.reload_config() {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/session/login
+/// 用 Admin API Key 换取 HttpOnly 会话 Cookie（与 Admin API 共用认证失败锁定）
+pub async fn login(
+    State(state): State<AdminState>,
+    connect_info: Option<Extension<ConnectInfo<ClientAddr>>>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    Json(payload): Json<LoginRequest>,
+) -> impl IntoResponse {
+    let client_ip = connect_info.and_then(|Extension(ConnectInfo(addr))| addr.0);
+    if let Some(response) = state.locked_response(client_ip) {
+        return response;
+    }
+
+    let role = state.admin_role(payload.admin_key.trim());
+    state.record_auth(client_ip, role.is_some(), uri.path());
+    let Some(role) = role else {
+        tracing::warn!("Admin 面板登录失败：Admin API Key 错误");
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(AdminErrorResponse::authentication_error()),
+        )
+            .into_response();
+    };
+
+    let created = state.sessions.create(role);
+    let cookie = session::session_cookie(
+        &created.id,
+        state.sessions.ttl_secs(),
+        session::is_https(&headers),
+    );
+    (
+        [(header::SET_COOKIE, cookie)],
+        Json(session_response(&created)),
+    )
+        .into_response()
+}
+
+/// GET /api/admin/session
+/// 获取当前会话（页面刷新后重新取得 CSRF Token）
+pub async fn get_session(State(state): State<AdminState>, headers: HeaderMap) -> impl IntoResponse {
+    match session::session_id_from_headers(&headers).and_then(|id| state.sessions.get(&id)) {
+        Some(current) => Json(session_response(&current)).into_response(),
+        None => (
+            StatusCode::UNAUTHORIZED,
+            Json(AdminErrorResponse::authentication_error()),
+        )
+            .into_response(),
+    }
+}
+
+/// POST /api/admin/session/logout
+/// 注销会话并清除 Cookie
+pub async fn logout(State(state): State<AdminState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(id) = session::session_id_from_headers(&headers) {
+        state.sessions.remove(&id);
+    }
+    let cookie = session::session_cookie("", 0, session::is_https(&headers));
+    (
+        [(header::SET_COOKIE, cookie)],
+        Json(SuccessResponse::new("已退出登录")),
+    )
+}
+
+fn session_response(session: &Session) -> SessionResponse {
+    SessionResponse {
+        csrf_token: session.csrf_token.clone(),
+        expires_at: session.expires_at,
+        role: session.role,
+    }
+}
+
+/// GET /api/admin/logs
+/// 分页查询请求审计日志（支持 `?apiKey=&credentialId=&model=&status=&from=&to=` 筛选）
+pub async fn get_audit_logs(
+    State(state): State<AdminState>,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    let Some(audit_log) = state.audit_log.clone() else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(AdminErrorResponse::invalid_request("审计日志未启用")),
+        )
+            .into_response();
+    };
+
+    // 查询需要扫描日志文件，放到阻塞线程池执行
+    match tokio::task::spawn_blocking(move || audit_log.query(&query)).await {
+        Ok(page) => Json(page).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(AdminErrorResponse::internal_error(format!(
+                "查询审计日志失败: {}",
+                e
+            ))),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /api/admin/log-level
+/// 获取当前日志过滤规则
+pub async fn get_log_level(State(state): State<AdminState>) -> impl IntoResponse {
+    match state.service.get_log_level() {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// PUT /api/admin/log-level
+/// 运行时调整日志过滤规则
+pub async fn set_log_level(
+    State(state): State<AdminState>,
+    Json(payload): Json<SetLogLevelRequest>,
+) -> impl IntoResponse {
+    match state.service.set_log_level(payload) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// GET /api/admin/debug/capture
+/// 获取请求捕获状态
+pub async fn get_capture_status(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_capture_status())
+}
+
+/// POST /api/admin/debug/capture
+/// 开启请求捕获：接下来的 N 个请求写入捕获目录
+pub async fn start_capture(
+    State(state): State<AdminState>,
+    Json(payload): Json<StartCaptureRequest>,
+) -> impl IntoResponse {
+    match state.service.start_capture(payload) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// DELETE /api/admin/debug/capture
+/// 关闭请求捕获
+pub async fn stop_capture(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.stop_capture())
+}
+
+/// GET /api/admin/debug/state
+/// 获取调试状态（后台任务存活、重启次数与最近停止原因）
+pub async fn get_debug_state(State(state): State<AdminState>) -> impl IntoResponse {
+    let workers = state
+        .supervisor
+        .as_ref()
+        .map(|s| s.statuses())
+        .unwrap_or_default();
+    let leader = state.leader_election.as_ref().map(|e| e.status());
+    Json(DebugStateResponse { workers, leader })
+}
+
+/// GET /api/admin/diagnostics/bundle
+/// 下载脱敏诊断包（zip）
+pub async fn get_diagnostics_bundle(State(state): State<AdminState>) -> impl IntoResponse {
+    let workers = state
+        .supervisor
+        .as_ref()
+        .map(|s| s.statuses())
+        .unwrap_or_default();
+    match state.service.diagnostics_bundle(workers) {
+        Ok(bundle) => {
+            let filename = format!(
+                "kiro-diagnostics-{}.zip",
+                chrono::Utc::now().format("%Y%m%d-%H%M%S")
+            );
+            (
+                [
+                    (header::CONTENT_TYPE, "application/zip".to_string()),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{}\"", filename),
+                    ),
+                ],
+                bundle,
+            )
+                .into_response()
+        }
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// GET /api/admin/sync
+/// 凭据同步快照（供其他实例的 `peerSync` 拉取，含凭据密钥，仅限 admin 角色；支持 `If-None-Match`）
+pub async fn get_sync_snapshot(
+    State(state): State<AdminState>,
+    Extension(role): Extension<AdminRole>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if role != AdminRole::Admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(AdminErrorResponse::new(
+                "forbidden",
+                "Credential sync requires an admin key",
+            )),
+        )
+            .into_response();
+    }
+    match state.service.sync_snapshot() {
+        Ok((body, etag)) => {
+            let not_modified = headers
+                .get(header::IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| etag_matches(v, &etag));
+            if not_modified {
+                return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+            }
+            (
+                [
+                    (header::CONTENT_TYPE, "application/json".to_string()),
+                    (header::ETAG, etag),
+                ],
+                body,
+            )
+                .into_response()
+        }
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// GET /api/admin/cloud-pass/status
 /// 获取 Cloud Pass 运行时状态
 pub async fn get_cloud_pass_status(State(state): State<AdminState>) -> impl IntoResponse {
@@ -137,15 +697,60 @@ pub async fn get_cloud_pass_status(State(state): State<AdminState>) -> impl Into
     }
 }
 
+/// GET /api/admin/cloud-pass/config
+/// 获取 Cloud Pass 配置（激活码脱敏）
+pub async fn get_cloud_pass_config(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_cloud_pass_config())
+}
+
+/// PUT /api/admin/cloud-pass/config
+/// 更新 Cloud Pass 配置（写回 config.json，后台任务立即按新配置重启）
+pub async fn update_cloud_pass_config(
+    State(state): State<AdminState>,
+    Json(payload): Json<UpdateCloudPassConfigRequest>,
+) -> impl IntoResponse {
+    match state.service.update_cloud_pass_config(payload) {
+        Ok(response) => {
+            if let Some(cp_state) = &state.cloud_pass_state {
+                cp_state.request_reconcile();
+            }
+            Json(response).into_response()
+        }
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/cloud-pass/claim
+/// 手动抢占 Cloud Pass 设备（可用 `?license=` 指定激活码），成功后立即触发刷新
+pub async fn claim_cloud_pass(
+    State(state): State<AdminState>,
+    Query(query): Query<ClaimCloudPassQuery>,
+) -> impl IntoResponse {
+    match state
+        .service
+        .claim_cloud_pass(query.license.as_deref())
+        .await
+    {
+        Ok(response) => {
+            let claimed = response.results.iter().any(|r| r.success);
+            if let Some(cp_state) = state.cloud_pass_state.as_ref().filter(|_| claimed) {
+                cp_state.trigger_refresh();
+            }
+            Json(response).into_response()
+        }
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// POST /api/admin/cloud-pass/refresh
 /// 手动触发 Cloud Pass 凭证刷新
 pub async fn refresh_cloud_pass(State(state): State<AdminState>) -> impl IntoResponse {
     match &state.cloud_pass_state {
-        Some(cp_state) => {
+        Some(cp_state) if cp_state.snapshot().enabled => {
             cp_state.trigger_refresh();
             Json(SuccessResponse::new("已触发 Cloud Pass 手动刷新".to_string())).into_response()
         }
-        None => (
+        _ => (
             axum::http::StatusCode::BAD_REQUEST,
             Json(serde_json::json!({
                 "error": "Cloud Pass 未启用"
@@ -1,59 +1,216 @@
 //! Admin API 中间件
 
+use std::net::IpAddr;
 use std::sync::Arc;
 
 use axum::{
     body::Body,
-    extract::State,
-    http::{Request, StatusCode},
+    extract::{ConnectInfo, OriginalUri, State},
+    http::{HeaderValue, Method, Request, StatusCode, header},
     middleware::Next,
     response::{IntoResponse, Json, Response},
 };
 
 use super::service::AdminService;
+use super::session::{self, CSRF_HEADER, SessionStore};
 use super::types::AdminErrorResponse;
+use crate::audit::AuditLog;
 use crate::cloud_pass::state::CloudPassState;
 use crate::common::auth;
+use crate::common::ip_filter::ClientAddr;
+use crate::common::lockout::AuthLockout;
+use crate::leader::LeaderElection;
+use crate::model::config::AdminRole;
+use crate::supervisor::WorkerSupervisor;
 
 /// Admin API 共享状态
 #[derive(Clone)]
 pub struct AdminState {
-    /// Admin API 密钥
+    /// Admin API 密钥（adminApiKey，启动时确定，拥有完整管理权限；为空表示仅使用 adminKeys）
     pub admin_api_key: String,
     /// Admin 服务
     pub service: Arc<AdminService>,
     /// Cloud Pass 运行时状态
     pub cloud_pass_state: Option<CloudPassState>,
+    /// Admin UI 登录会话
+    pub sessions: Arc<SessionStore>,
+    /// 请求审计日志（未配置 audit 时为 None）
+    pub audit_log: Option<Arc<AuditLog>>,
+    /// 后台任务监督器
+    pub supervisor: Option<Arc<WorkerSupervisor>>,
+    /// 主备选主（未配置 leaderElection 时为 None）
+    pub leader_election: Option<Arc<LeaderElection>>,
+    /// 认证失败锁定（与 API 认证共用，未配置 authLockout 时为 None）
+    pub auth_lockout: Option<Arc<AuthLockout>>,
 }
 
+/// 默认会话有效期（秒）
+const DEFAULT_SESSION_TTL_SECS: u64 = 12 * 3600;
+
 impl AdminState {
     pub fn new(admin_api_key: impl Into<String>, service: AdminService) -> Self {
         Self {
             admin_api_key: admin_api_key.into(),
             service: Arc::new(service),
             cloud_pass_state: None,
+            sessions: Arc::new(SessionStore::new(DEFAULT_SESSION_TTL_SECS)),
+            audit_log: None,
+            supervisor: None,
+            leader_election: None,
+            auth_lockout: None,
         }
     }
 
+    pub fn with_session_ttl(mut self, ttl_secs: u64) -> Self {
+        self.sessions = Arc::new(SessionStore::new(ttl_secs));
+        self
+    }
+
     pub fn with_cloud_pass(mut self, state: CloudPassState) -> Self {
         self.cloud_pass_state = Some(state);
         self
     }
+
+    pub fn with_audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    pub fn with_supervisor(mut self, supervisor: Arc<WorkerSupervisor>) -> Self {
+        self.supervisor = Some(supervisor);
+        self
+    }
+
+    pub fn with_leader_election(mut self, election: Arc<LeaderElection>) -> Self {
+        self.leader_election = Some(election);
+        self
+    }
+
+    pub fn with_auth_lockout(mut self, lockout: Arc<AuthLockout>) -> Self {
+        self.auth_lockout = Some(lockout);
+        self
+    }
+
+    /// 客户端 IP 处于锁定期时返回 429 响应
+    pub fn locked_response(&self, client_ip: Option<IpAddr>) -> Option<Response> {
+        let retry_after = self.auth_lockout.as_ref()?.locked(client_ip?)?;
+        let error = AdminErrorResponse::new(
+            "rate_limit_error",
+            "Too many failed authentication attempts, try again later",
+        );
+        let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(error)).into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        Some(response)
+    }
+
+    /// 记录一次 Admin Key 认证结果，失败次数过多时锁定该 IP 并写入审计安全事件
+    pub fn record_auth(&self, client_ip: Option<IpAddr>, success: bool, endpoint: &str) {
+        let Some((lockout, ip)) = self.auth_lockout.as_ref().zip(client_ip) else {
+            return;
+        };
+        if success {
+            lockout.record_success(ip);
+        } else if lockout.record_failure(ip) {
+            tracing::warn!("{} Admin 认证失败次数过多，已锁定: {}", ip, endpoint);
+            if let Some(audit) = &self.audit_log {
+                audit.record_security_event(
+                    "auth_lockout",
+                    endpoint,
+                    Some(ip),
+                    StatusCode::UNAUTHORIZED.as_u16(),
+                );
+            }
+        }
+    }
+
+    /// 按 Admin Key 查找角色（adminKeys 读取当前配置，增删后立即生效）
+    pub fn admin_role(&self, key: &str) -> Option<AdminRole> {
+        if key.trim().is_empty() {
+            return None;
+        }
+        if !self.admin_api_key.is_empty() && auth::constant_time_eq(key, &self.admin_api_key) {
+            return Some(AdminRole::Admin);
+        }
+        self.service.admin_key_role(key)
+    }
 }
 
 /// Admin API 认证中间件
+///
+/// 支持两种方式：
+/// - 请求头携带 Admin API Key（脚本调用）
+/// - 会话 Cookie（管理面板），非只读请求需同时携带匹配的 `x-csrf-token`
+///
+/// 只读（viewer）角色的 Key 及其登录会话只能调用 GET 端点，其余请求返回 403。
+/// 配置 `authLockout` 时，Admin Key 错误次数过多的 IP 在锁定期内直接返回 429
 pub async fn admin_auth_middleware(
     State(state): State<AdminState>,
-    request: Request<Body>,
+    mut request: Request<Body>,
     next: Next,
 ) -> Response {
-    let api_key = auth::extract_api_key(&request);
+    let client_ip = client_ip(&request);
+    if let Some(response) = state.locked_response(client_ip) {
+        return response;
+    }
+    let read_only = matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
 
-    match api_key {
-        Some(key) if auth::constant_time_eq(&key, &state.admin_api_key) => next.run(request).await,
-        _ => {
-            let error = AdminErrorResponse::authentication_error();
-            (StatusCode::UNAUTHORIZED, Json(error)).into_response()
+    let role = if let Some(key) = auth::extract_api_key(&request) {
+        let role = state.admin_role(&key);
+        state.record_auth(client_ip, role.is_some(), &endpoint(&request));
+        match role {
+            Some(role) => role,
+            None => return unauthorized(),
         }
+    } else {
+        let Some(session) = session::session_id_from_headers(request.headers())
+            .and_then(|id| state.sessions.get(&id))
+        else {
+            return unauthorized();
+        };
+
+        let csrf_valid = request
+            .headers()
+            .get(CSRF_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|token| auth::constant_time_eq(token, &session.csrf_token));
+        if !read_only && !csrf_valid {
+            return forbidden("Missing or invalid CSRF token");
+        }
+        session.role
+    };
+
+    if role == AdminRole::Viewer && !read_only {
+        return forbidden("Read-only admin key cannot modify resources");
     }
+    // 供需要完整权限的只读端点（如凭据同步）检查角色
+    request.extensions_mut().insert(role);
+    next.run(request).await
+}
+
+/// 客户端 IP（Unix socket 等地址未知的连接为 None）
+fn client_ip(request: &Request<Body>) -> Option<IpAddr> {
+    request
+        .extensions()
+        .get::<ConnectInfo<ClientAddr>>()
+        .and_then(|info| info.0.0)
+}
+
+/// 请求路径（nest 前的完整路径）
+fn endpoint(request: &Request<Body>) -> String {
+    match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path().to_string(),
+        None => request.uri().path().to_string(),
+    }
+}
+
+fn unauthorized() -> Response {
+    let error = AdminErrorResponse::authentication_error();
+    (StatusCode::UNAUTHORIZED, Json(error)).into_response()
+}
+
+fn forbidden(message: &str) -> Response {
+    let error = AdminErrorResponse::new("forbidden", message);
+    (StatusCode::FORBIDDEN, Json(error)).into_response()
 }
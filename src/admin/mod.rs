@@ -8,19 +8,22 @@
 //! - 修改凭据优先级
 //! - 重置失败计数
 //! - 查询凭据余额
+//! - 管理下游托管 API Key
 //!
 //! # 使用
 //! ```ignore
-//! let admin_service = AdminService::new(token_manager.clone());
+//! let admin_service = AdminService::new(token_manager.clone(), api_keys.clone());
 //! let admin_state = AdminState::new(admin_api_key, admin_service);
 //! let admin_router = create_admin_router(admin_state);
 //! ```
 
+mod diagnostics;
 mod error;
 mod handlers;
 mod middleware;
 mod router;
 mod service;
+mod session;
 pub mod types;
 
 pub use middleware::AdminState;
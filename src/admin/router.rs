@@ -2,14 +2,26 @@
 
 use axum::{
     Router, middleware,
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
 };
 
 use super::{
     handlers::{
-        add_credential, delete_credential, get_all_credentials, get_cloud_pass_status,
-        get_credential_balance, get_load_balancing_mode, refresh_cloud_pass, reset_failure_count,
-        set_credential_disabled, set_credential_priority, set_load_balancing_mode,
+        add_credential, apply_priority_preset, claim_cloud_pass, create_admin_key, create_api_key,
+        dedupe_credentials, delete_admin_key, discover_credentials, delete_api_key, delete_credential, get_all_credentials, get_audit_logs,
+        get_cloud_pass_config, get_cloud_pass_status, get_config, get_credential_balance, get_credential_balances,
+        get_capture_status, get_credential_usage,
+        get_debug_state, get_diagnostics_bundle, get_drift_report, get_load_balancing_mode, get_log_level,
+        get_priority_presets,
+        get_session, get_stats, get_sync_snapshot,
+        get_subscription_changes, import_credentials, invalidate_balance_cache,
+        invalidate_credential_balance_cache, list_admin_keys, list_api_keys,
+        login, logout,
+        refresh_cloud_pass, reload_config, reset_failure_count, set_api_key_quotas,
+        set_credential_disabled, set_credential_models, set_credential_priority,
+        set_credential_proxy, set_credential_rate_limit, set_credential_note, set_credential_tags,
+        set_credential_usage_watermark, set_load_balancing_mode, set_log_level, start_capture, stop_capture, test_webhooks, update_cloud_pass_config,
+        update_config,
     },
     middleware::{AdminState, admin_auth_middleware},
 };
@@ -17,40 +29,142 @@ use super::{
 /// 创建 Admin API 路由
 ///
 /// # 端点
-/// - `GET /credentials` - 获取所有凭据状态
+/// - `GET /credentials` - 获取凭据状态（支持 `?tag=` / `?disabled=` 筛选）
 /// - `POST /credentials` - 添加新凭据
+/// - `POST /credentials/import` - 从 CSV / TSV 批量导入凭据
+/// - `POST /credentials/dedupe` - 检测并合并重复凭据
+/// - `POST /credentials/discover` - 扫描并导入 Kiro IDE 的凭据
+/// - `GET /credentials/balance` - 批量获取凭据余额（`?ids=&force=`）
+/// - `DELETE /credentials/balance/cache` - 清除所有凭据的余额缓存
 /// - `DELETE /credentials/:id` - 删除凭据
 /// - `POST /credentials/:id/disabled` - 设置凭据禁用状态
 /// - `POST /credentials/:id/priority` - 设置凭据优先级
+/// - `POST /credentials/:id/rate-limit` - 设置凭据级速率限制
+/// - `PUT /credentials/:id/tags` - 设置凭据标签
+/// - `PUT /credentials/:id/note` - 设置凭据备注
+/// - `PUT /credentials/:id/models` - 设置凭据模型白名单 / 黑名单
+/// - `PUT /credentials/:id/proxy` - 设置凭据级出站代理
+/// - `PUT /credentials/:id/usage-watermark` - 设置凭据级用量警戒线
 /// - `POST /credentials/:id/reset` - 重置失败计数
-/// - `GET /credentials/:id/balance` - 获取凭据余额
+/// - `GET /credentials/:id/balance` - 获取凭据余额（`?force=true` 跳过缓存）
+/// - `DELETE /credentials/:id/balance/cache` - 清除凭据的余额缓存
+/// - `GET /credentials/:id/usage` - 获取凭据按日用量（`?from=&to=`）
+/// - `GET /subscription-changes` - 获取最近的订阅变更记录
+/// - `GET /stats` - 获取各凭据的上游延迟统计
+/// - `GET /drift` - 获取上游协议漂移报告
+/// - `GET /presets` - 获取优先级预设与最近一次应用记录
+/// - `POST /presets/:name/apply` - 应用优先级预设
+/// - `GET /debug/state` - 获取调试状态（后台任务存活）
+/// - `GET /debug/capture` - 获取请求捕获状态
+/// - `POST /debug/capture` - 开启请求捕获（接下来 N 个请求的上游原始字节与下游 SSE）
+/// - `DELETE /debug/capture` - 关闭请求捕获
+/// - `GET /log-level` - 获取当前日志过滤规则
+/// - `PUT /log-level` - 运行时调整日志过滤规则
+/// - `GET /diagnostics/bundle` - 下载脱敏诊断包（zip）
+/// - `GET /sync` - 凭据同步快照（供其他实例拉取，需 admin 角色）
+/// - `GET /api-keys` - 获取托管 API Key 列表（含用量）
+/// - `POST /api-keys` - 创建托管 API Key
+/// - `PUT /api-keys/:id/quotas` - 设置托管 API Key 配额
+/// - `DELETE /api-keys/:id` - 删除托管 API Key
+/// - `GET /admin-keys` - 获取 Admin Key 列表（角色与前缀）
+/// - `POST /admin-keys` - 创建带角色的 Admin Key（写入 config.json）
+/// - `DELETE /admin-keys/:name` - 删除 Admin Key
+/// - `POST /webhooks/test` - 向所有已配置的 Webhook 与聊天渠道发送测试事件
+/// - `GET /logs` - 分页查询请求审计日志
+/// - `GET /config` - 获取可编辑的配置子集
+/// - `PATCH /config` - 更新配置子集（校验后写回 config.json）
 /// - `GET /config/load-balancing` - 获取负载均衡模式
 /// - `PUT /config/load-balancing` - 设置负载均衡模式
+/// - `POST /config/reload` - 重新加载 config.json 并热应用
+/// - `POST /session/login` - 用 Admin API Key 换取会话 Cookie（无需认证）
+/// - `GET /session` - 获取当前会话的 CSRF Token
+/// - `POST /session/logout` - 注销会话
 ///
 /// # 认证
 /// 需要 Admin API Key 认证，支持：
 /// - `x-api-key` header
 /// - `Authorization: Bearer <token>` header
+/// - 会话 Cookie（非只读请求需携带 `x-csrf-token` header）
+///
+/// viewer 角色只能调用 GET 端点
 pub fn create_admin_router(state: AdminState) -> Router {
+    // 会话端点自行校验，不经过认证中间件
+    let session_routes = Router::new()
+        .route("/session", get(get_session))
+        .route("/session/login", post(login))
+        .route("/session/logout", post(logout));
+
     Router::new()
         .route(
             "/credentials",
             get(get_all_credentials).post(add_credential),
         )
+        .route("/credentials/import", post(import_credentials))
+        .route("/credentials/dedupe", post(dedupe_credentials))
+        .route("/credentials/discover", post(discover_credentials))
+        .route("/credentials/balance", get(get_credential_balances))
+        .route(
+            "/credentials/balance/cache",
+            delete(invalidate_balance_cache),
+        )
         .route("/credentials/{id}", delete(delete_credential))
         .route("/credentials/{id}/disabled", post(set_credential_disabled))
         .route("/credentials/{id}/priority", post(set_credential_priority))
+        .route("/credentials/{id}/rate-limit", post(set_credential_rate_limit))
+        .route("/credentials/{id}/tags", put(set_credential_tags))
+        .route("/credentials/{id}/note", put(set_credential_note))
+        .route("/credentials/{id}/models", put(set_credential_models))
+        .route("/credentials/{id}/proxy", put(set_credential_proxy))
+        .route(
+            "/credentials/{id}/usage-watermark",
+            put(set_credential_usage_watermark),
+        )
         .route("/credentials/{id}/reset", post(reset_failure_count))
         .route("/credentials/{id}/balance", get(get_credential_balance))
+        .route(
+            "/credentials/{id}/balance/cache",
+            delete(invalidate_credential_balance_cache),
+        )
+        .route("/credentials/{id}/usage", get(get_credential_usage))
+        .route("/subscription-changes", get(get_subscription_changes))
+        .route("/stats", get(get_stats))
+        .route("/drift", get(get_drift_report))
+        .route("/presets", get(get_priority_presets))
+        .route("/presets/{name}/apply", post(apply_priority_preset))
+        .route("/debug/state", get(get_debug_state))
+        .route(
+            "/debug/capture",
+            get(get_capture_status)
+                .post(start_capture)
+                .delete(stop_capture),
+        )
+        .route("/log-level", get(get_log_level).put(set_log_level))
+        .route("/diagnostics/bundle", get(get_diagnostics_bundle))
+        .route("/sync", get(get_sync_snapshot))
+        .route("/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/api-keys/{id}", delete(delete_api_key))
+        .route("/api-keys/{id}/quotas", put(set_api_key_quotas))
+        .route("/admin-keys", get(list_admin_keys).post(create_admin_key))
+        .route("/admin-keys/{name}", delete(delete_admin_key))
+        .route("/webhooks/test", post(test_webhooks))
+        .route("/logs", get(get_audit_logs))
+        .route("/config", get(get_config).patch(update_config))
         .route(
             "/config/load-balancing",
             get(get_load_balancing_mode).put(set_load_balancing_mode),
         )
+        .route("/config/reload", post(reload_config))
         .route("/cloud-pass/status", get(get_cloud_pass_status))
+        .route(
+            "/cloud-pass/config",
+            get(get_cloud_pass_config).put(update_cloud_pass_config),
+        )
         .route("/cloud-pass/refresh", post(refresh_cloud_pass))
+        .route("/cloud-pass/claim", post(claim_cloud_pass))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             admin_auth_middleware,
         ))
+        .merge(session_routes)
         .with_state(state)
 }
@@ -4,22 +4,57 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use chrono::Utc;
+use chrono::{NaiveDate, Utc};
+use futures::StreamExt;
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
-use crate::kiro::model::credentials::KiroCredentials;
-use crate::kiro::token_manager::MultiTokenManager;
+use crate::anthropic::cache::ResponseCache;
+use crate::anthropic::capture::{self, CaptureStatus};
+use crate::api_keys::ApiKeyStore;
+use crate::cloud_pass::client::CloudPassClient;
+use crate::cloud_pass::state::mask_license_code;
+use crate::common::auth;
+use crate::common::log_buffer;
+use crate::common::logging;
+use crate::common::time::{ZonedTime, parse_utc_offset};
+use crate::kiro::credential_discovery::{self, DiscoveryReport};
+use crate::kiro::credential_import::{self, ColumnMapping, ImportFormat, ImportReport};
+use crate::kiro::drift::{self, DriftReport};
+use crate::kiro::model::credentials::{CredentialId, KiroCredentials};
+use crate::kiro::model::usage_limits::UsageLimitsResponse;
+use crate::kiro::parser::stats as parser_stats;
+use crate::kiro::peer_sync::SyncSnapshot;
+use crate::kiro::token_manager::{AppliedPriorityPreset, DailyUsage, MultiTokenManager};
+use crate::model::config::{
+    AdminKeyConfig, AdminRole, CloudPassConfig, CloudPassLicense, Config, UsageWatermark,
+};
+use crate::notify::chat::{self, ChatChannel};
+use crate::notify::{EventKind, NotificationEvent, webhook};
+use crate::supervisor::{RestartPolicy, WorkerState, WorkerStatus};
 
+use super::diagnostics::{SelfCheck, VersionInfo, build_zip, sanitize_text, sanitized_json};
 use super::error::AdminServiceError;
 use super::types::{
-    AddCredentialRequest, AddCredentialResponse, BalanceResponse, CredentialStatusItem,
-    CredentialsStatusResponse, LoadBalancingModeResponse, SetLoadBalancingModeRequest,
+    AddCredentialRequest, AddCredentialResponse, AdminKeyItem, AdminKeysResponse, ApiKeyItem,
+    ApiKeysResponse, BalanceError, BalanceResponse, BalancesQuery, BalancesResponse, ClaimCloudPassResponse, ClaimCloudPassResult, CloudPassConfigResponse, CredentialStatusItem, ConfigResponse, CreateAdminKeyRequest,
+    CreateAdminKeyResponse, CreateApiKeyRequest, CreateApiKeyResponse,
+    CredentialUsageResponse, CredentialsQuery, CredentialsStatusResponse,
+    DedupeCredentialsRequest, DedupeCredentialsResponse, ImportCredentialsRequest,
+    LoadBalancingModeResponse, LogLevelResponse, PriorityPresetsResponse, ReloadConfigResponse,
+    SetApiKeyQuotasRequest, SetLogLevelRequest, StartCaptureRequest,
+    SetLoadBalancingModeRequest, SetModelsRequest, SetProxyRequest, SetUsageWatermarkRequest,
+    StatsResponse,
+    SubscriptionChangesResponse, TestWebhooksResponse,
+    UpdateCloudPassConfigRequest, UpdateConfigRequest, UpdateConfigResponse, UsageBucket, UsageQuery, WebhookTestResult,
 };
 
 /// 余额缓存过期时间（秒），5 分钟
 const BALANCE_CACHE_TTL_SECS: i64 = 300;
 
+/// 批量查询余额时同时向上游查询的最大凭据数
+const BALANCE_FETCH_CONCURRENCY: usize = 4;
+
 /// 缓存的余额条目（含时间戳）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct CachedBalance {
@@ -34,12 +69,14 @@ struct CachedBalance {
 /// 封装所有 Admin API 的业务逻辑
 pub struct AdminService {
     token_manager: Arc<MultiTokenManager>,
-    balance_cache: Mutex<HashMap<u64, CachedBalance>>,
+    api_keys: Arc<ApiKeyStore>,
+    balance_cache: Mutex<HashMap<CredentialId, CachedBalance>>,
     cache_path: Option<PathBuf>,
+    response_cache: Option<Arc<ResponseCache>>,
 }
 
 impl AdminService {
-    pub fn new(token_manager: Arc<MultiTokenManager>) -> Self {
+    pub fn new(token_manager: Arc<MultiTokenManager>, api_keys: Arc<ApiKeyStore>) -> Self {
         let cache_path = token_manager
             .cache_dir()
             .map(|d| d.join("kiro_balance_cache.json"));
@@ -48,18 +85,31 @@ impl AdminService {
 
         Self {
             token_manager,
+            api_keys,
             balance_cache: Mutex::new(balance_cache),
             cache_path,
+            response_cache: None,
         }
     }
 
-    /// 获取所有凭据状态
-    pub fn get_all_credentials(&self) -> CredentialsStatusResponse {
+    /// 绑定响应缓存（统计中附带命中/未命中次数）
+    pub fn with_response_cache(mut self, cache: Arc<ResponseCache>) -> Self {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    /// 获取凭据状态（可按标签、禁用状态筛选）
+    pub fn get_all_credentials(&self, query: &CredentialsQuery) -> CredentialsStatusResponse {
         let snapshot = self.token_manager.snapshot();
+        let offset = self.token_manager.config().timezone_offset();
 
         let mut credentials: Vec<CredentialStatusItem> = snapshot
             .entries
             .into_iter()
+            .filter(|entry| {
+                query.disabled.is_none_or(|d| entry.disabled == d)
+                    && query.tag.as_ref().is_none_or(|t| entry.tags.contains(t))
+            })
             .map(|entry| CredentialStatusItem {
                 id: entry.id,
                 priority: entry.priority,
@@ -67,15 +117,33 @@ impl AdminService {
                 failure_count: entry.failure_count,
                 is_current: entry.id == snapshot.current_id,
                 expires_at: entry.expires_at,
+                next_refresh_at: entry.next_refresh_at,
                 auth_method: entry.auth_method,
                 has_profile_arn: entry.has_profile_arn,
                 refresh_token_hash: entry.refresh_token_hash,
                 email: entry.email,
+                account_id: entry.account_id,
+                subscription_title: entry.subscription_title,
                 success_count: entry.success_count,
-                last_used_at: entry.last_used_at.clone(),
+                last_used_at: entry.last_used_at,
                 has_proxy: entry.has_proxy,
                 proxy_url: entry.proxy_url,
                 machine_id: entry.machine_id,
+                rate_limit_rpm: entry.rate_limit_rpm,
+                max_concurrency: entry.max_concurrency,
+                in_flight: entry.in_flight,
+                usage_cap: entry.usage_cap,
+                window_usage: entry.window_usage,
+                usage_watermark: entry.usage_watermark,
+                watermark_reached: entry.watermark_reached,
+                tags: entry.tags,
+                note: entry.note,
+                allowed_models: entry.allowed_models,
+                blocked_models: entry.blocked_models,
+                quota_reset: entry.quota_reset_at.map(|t| ZonedTime::new(t, offset)),
+                last_error: entry.last_error,
+                health_status: entry.health_status,
+                last_health_check_at: entry.last_health_check_at,
             })
             .collect();
 
@@ -91,7 +159,7 @@ impl AdminService {
     }
 
     /// 设置凭据禁用状态
-    pub fn set_disabled(&self, id: u64, disabled: bool) -> Result<(), AdminServiceError> {
+    pub fn set_disabled(&self, id: CredentialId, disabled: bool) -> Result<(), AdminServiceError> {
         // 先获取当前凭据 ID，用于判断是否需要切换
         let snapshot = self.token_manager.snapshot();
         let current_id = snapshot.current_id;
@@ -108,34 +176,209 @@ impl AdminService {
     }
 
     /// 设置凭据优先级
-    pub fn set_priority(&self, id: u64, priority: u32) -> Result<(), AdminServiceError> {
+    pub fn set_priority(&self, id: CredentialId, priority: u32) -> Result<(), AdminServiceError> {
         self.token_manager
             .set_priority(id, priority)
             .map_err(|e| self.classify_error(e, id))
     }
 
+    /// 设置凭据级速率限制
+    pub fn set_rate_limit(
+        &self,
+        id: CredentialId,
+        rpm: Option<u32>,
+    ) -> Result<(), AdminServiceError> {
+        self.token_manager
+            .set_rate_limit(id, rpm)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 设置凭据级用量警戒线
+    pub fn set_usage_watermark(
+        &self,
+        id: CredentialId,
+        req: SetUsageWatermarkRequest,
+    ) -> Result<(), AdminServiceError> {
+        let watermark = match req.percent {
+            Some(percent) if !(percent > 0.0 && percent <= 100.0) => {
+                return Err(AdminServiceError::InvalidRequest(
+                    "percent 需在 (0, 100] 范围内".to_string(),
+                ));
+            }
+            Some(percent) => Some(UsageWatermark {
+                percent,
+                action: req.action,
+            }),
+            None => None,
+        };
+        self.token_manager
+            .set_usage_watermark(id, watermark)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 获取凭据按日用量
+    pub fn get_usage(
+        &self,
+        id: CredentialId,
+        query: &UsageQuery,
+    ) -> Result<CredentialUsageResponse, AdminServiceError> {
+        let from = parse_usage_date("from", query.from.as_deref())?;
+        let to = parse_usage_date("to", query.to.as_deref())?;
+        if let (Some(from), Some(to)) = (from, to)
+            && from > to
+        {
+            return Err(AdminServiceError::InvalidRequest("from 不能晚于 to".to_string()));
+        }
+
+        let history = self
+            .token_manager
+            .usage_history(id, from, to)
+            .map_err(|e| self.classify_error(e, id))?;
+
+        let mut total = DailyUsage::default();
+        let days = history
+            .into_iter()
+            .map(|(date, usage)| {
+                total.requests += usage.requests;
+                total.input_tokens += usage.input_tokens;
+                total.output_tokens += usage.output_tokens;
+                total.errors += usage.errors;
+                total.content_filtered += usage.content_filtered;
+                UsageBucket {
+                    date: date.to_string(),
+                    usage,
+                }
+            })
+            .collect();
+
+        Ok(CredentialUsageResponse { id, days, total })
+    }
+
+    /// 设置凭据标签
+    pub fn set_tags(&self, id: CredentialId, tags: Vec<String>) -> Result<(), AdminServiceError> {
+        self.token_manager
+            .set_tags(id, tags)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 设置凭据备注
+    pub fn set_note(
+        &self,
+        id: CredentialId,
+        note: Option<String>,
+    ) -> Result<(), AdminServiceError> {
+        if note
+            .as_ref()
+            .is_some_and(|n| n.trim().chars().count() > KiroCredentials::MAX_NOTE_CHARS)
+        {
+            return Err(AdminServiceError::InvalidRequest(format!(
+                "备注不能超过 {} 个字符",
+                KiroCredentials::MAX_NOTE_CHARS
+            )));
+        }
+        self.token_manager
+            .set_note(id, note)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 设置凭据模型白名单 / 黑名单
+    pub fn set_models(
+        &self,
+        id: CredentialId,
+        req: SetModelsRequest,
+    ) -> Result<(), AdminServiceError> {
+        self.token_manager
+            .set_models(id, req.allowed_models, req.blocked_models)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 设置凭据级代理
+    pub fn set_proxy(
+        &self,
+        id: CredentialId,
+        req: SetProxyRequest,
+    ) -> Result<(), AdminServiceError> {
+        let proxy_url = match req.proxy_url.and_then(optional) {
+            Some(url) if url.eq_ignore_ascii_case(KiroCredentials::PROXY_DIRECT) => {
+                Some(KiroCredentials::PROXY_DIRECT.to_string())
+            }
+            Some(url) => {
+                Some(validate_proxy_url(&url).map_err(AdminServiceError::InvalidRequest)?)
+            }
+            None => None,
+        };
+        let proxy_username = req.proxy_username.and_then(optional);
+        let proxy_password = req.proxy_password.and_then(optional);
+        if proxy_username.is_some() != proxy_password.is_some() {
+            return Err(AdminServiceError::InvalidRequest(
+                "proxyUsername 与 proxyPassword 需同时提供".to_string(),
+            ));
+        }
+
+        self.token_manager
+            .set_proxy(id, proxy_url, proxy_username, proxy_password)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
     /// 重置失败计数并重新启用
-    pub fn reset_and_enable(&self, id: u64) -> Result<(), AdminServiceError> {
+    pub fn reset_and_enable(&self, id: CredentialId) -> Result<(), AdminServiceError> {
         self.token_manager
             .reset_and_enable(id)
             .map_err(|e| self.classify_error(e, id))
     }
 
-    /// 获取凭据余额（带缓存）
-    pub async fn get_balance(&self, id: u64) -> Result<BalanceResponse, AdminServiceError> {
-        // 先查缓存
-        {
-            let cache = self.balance_cache.lock();
-            if let Some(cached) = cache.get(&id) {
-                let now = Utc::now().timestamp() as f64;
-                if (now - cached.cached_at) < BALANCE_CACHE_TTL_SECS as f64 {
-                    tracing::debug!("凭据 #{} 余额命中缓存", id);
-                    return Ok(cached.data.clone());
+    /// 获取凭据余额（带缓存，`force` 为 true 时跳过缓存直接查询上游）
+    pub async fn get_balance(
+        &self,
+        id: CredentialId,
+        force: bool,
+    ) -> Result<BalanceResponse, AdminServiceError> {
+        if force {
+            return self.fetch_and_cache_balance(id).await;
+        }
+
+        // 先查缓存（定时刷新、健康检查等途径得到的更新的额度数据优先）
+        let observed = self.token_manager.latest_usage_limits(id);
+        let (hit, refreshed) = {
+            let mut cache = self.balance_cache.lock();
+            let now = Utc::now().timestamp() as f64;
+            let mut refreshed = false;
+            if let Some((at, usage)) = observed {
+                let observed_at = at.timestamp() as f64;
+                if cache.get(&id).is_none_or(|c| c.cached_at < observed_at) {
+                    cache.insert(
+                        id,
+                        CachedBalance {
+                            cached_at: observed_at,
+                            data: Self::balance_from_usage(id, &usage),
+                        },
+                    );
+                    refreshed = true;
                 }
             }
+            let hit = cache
+                .get(&id)
+                .filter(|cached| (now - cached.cached_at) < BALANCE_CACHE_TTL_SECS as f64)
+                .map(|cached| cached.data.clone());
+            (hit, refreshed)
+        };
+        if refreshed {
+            self.save_balance_cache();
+        }
+        if let Some(balance) = hit {
+            tracing::debug!("凭据 #{} 余额命中缓存", id);
+            return Ok(self.with_zoned_reset(balance));
         }
 
         // 缓存未命中或已过期，从上游获取
+        self.fetch_and_cache_balance(id).await
+    }
+
+    /// 从上游获取余额并写入缓存
+    async fn fetch_and_cache_balance(
+        &self,
+        id: CredentialId,
+    ) -> Result<BalanceResponse, AdminServiceError> {
         let balance = self.fetch_balance(id).await?;
 
         // 更新缓存
@@ -151,17 +394,108 @@ impl AdminService {
         }
         self.save_balance_cache();
 
-        Ok(balance)
+        Ok(self.with_zoned_reset(balance))
+    }
+
+    /// 批量获取凭据余额（带缓存，缓存未命中的凭据限制并发查询）
+    ///
+    /// `ids` 为空时查询所有凭据；单个凭据查询失败记录在 `errors` 中，不影响其他凭据
+    pub async fn get_balances(
+        &self,
+        query: &BalancesQuery,
+    ) -> Result<BalancesResponse, AdminServiceError> {
+        let requested = query.ids.as_deref().map(str::trim).filter(|s| !s.is_empty());
+        let ids = match requested {
+            Some(ids) => ids
+                .split(',')
+                .map(|id| {
+                    id.trim().parse::<CredentialId>().map_err(|_| {
+                        AdminServiceError::InvalidRequest(format!("无效的凭据 ID: {}", id))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => self
+                .token_manager
+                .snapshot()
+                .entries
+                .iter()
+                .map(|e| e.id)
+                .collect(),
+        };
+
+        let results: Vec<_> = futures::stream::iter(ids)
+            .map(|id| async move { (id, self.get_balance(id, query.force).await) })
+            .buffer_unordered(BALANCE_FETCH_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut response = BalancesResponse::default();
+        for (id, result) in results {
+            match result {
+                Ok(balance) => response.balances.push(balance),
+                Err(e) => response.errors.push(BalanceError {
+                    id,
+                    error: e.to_string(),
+                }),
+            }
+        }
+        response.balances.sort_by_key(|b| b.id);
+        response.errors.sort_by_key(|e| e.id);
+        Ok(response)
+    }
+
+    /// 清除余额缓存（`id` 为 None 时清除全部），下次查询时重新从上游获取
+    ///
+    /// 返回清除的缓存条目数
+    pub fn invalidate_balance_cache(
+        &self,
+        id: Option<CredentialId>,
+    ) -> Result<usize, AdminServiceError> {
+        // 定时刷新等途径保存在内存中的额度数据同样会被余额查询复用，一并清除
+        match id {
+            Some(id) => self
+                .token_manager
+                .clear_latest_usage(id)
+                .map_err(|e| self.classify_error(e, id))?,
+            None => self.token_manager.clear_all_latest_usage(),
+        }
+        let removed = {
+            let mut cache = self.balance_cache.lock();
+            match id {
+                Some(id) => usize::from(cache.remove(&id).is_some()),
+                None => {
+                    let count = cache.len();
+                    cache.clear();
+                    count
+                }
+            }
+        };
+        self.save_balance_cache();
+        Ok(removed)
+    }
+
+    /// 按当前配置的时区填充余额响应中的重置时间
+    fn with_zoned_reset(&self, mut balance: BalanceResponse) -> BalanceResponse {
+        let offset = self.token_manager.config().timezone_offset();
+        balance.next_reset = balance
+            .next_reset_at
+            .and_then(|ts| ZonedTime::from_timestamp(ts, offset));
+        balance
     }
 
     /// 从上游获取余额（无缓存）
-    async fn fetch_balance(&self, id: u64) -> Result<BalanceResponse, AdminServiceError> {
+    async fn fetch_balance(&self, id: CredentialId) -> Result<BalanceResponse, AdminServiceError> {
         let usage = self
             .token_manager
             .get_usage_limits_for(id)
             .await
             .map_err(|e| self.classify_balance_error(e, id))?;
 
+        Ok(Self::balance_from_usage(id, &usage))
+    }
+
+    /// 由额度查询结果生成余额响应
+    fn balance_from_usage(id: CredentialId, usage: &UsageLimitsResponse) -> BalanceResponse {
         let current_usage = usage.current_usage();
         let usage_limit = usage.usage_limit();
         let remaining = (usage_limit - current_usage).max(0.0);
@@ -171,15 +505,179 @@ impl AdminService {
             0.0
         };
 
-        Ok(BalanceResponse {
+        BalanceResponse {
             id,
             subscription_title: usage.subscription_title().map(|s| s.to_string()),
             current_usage,
             usage_limit,
             remaining,
             usage_percentage,
-            next_reset_at: usage.next_date_reset,
-        })
+            next_reset_at: usage.next_reset_at(),
+            next_reset: None,
+        }
+    }
+
+    /// 获取最近的订阅变更记录
+    pub fn get_subscription_changes(&self) -> SubscriptionChangesResponse {
+        SubscriptionChangesResponse {
+            changes: self.token_manager.subscription_changes(),
+        }
+    }
+
+    /// 获取上游延迟与请求体大小统计
+    pub fn get_stats(&self) -> StatsResponse {
+        let (ttfb, total) = self.token_manager.latency_overall();
+        StatsResponse {
+            ttfb,
+            total,
+            credentials: self.token_manager.latency_per_credential(),
+            request_size: self.token_manager.request_size_summary(),
+            corrupt_frames: parser_stats::corrupt_frames().summary(),
+            decoder_buffer: parser_stats::buffers().summary(),
+            response_cache: self.response_cache.as_ref().map(|cache| cache.stats()),
+        }
+    }
+
+    /// 凭据同步快照：序列化后的响应体与 ETag
+    pub fn sync_snapshot(&self) -> Result<(Vec<u8>, String), AdminServiceError> {
+        SyncSnapshot {
+            credentials: self.token_manager.export_credentials(),
+        }
+        .encode()
+        .map_err(|e| AdminServiceError::InternalError(format!("序列化凭据失败: {}", e)))
+    }
+
+    /// 获取当前日志过滤规则
+    pub fn get_log_level(&self) -> Result<LogLevelResponse, AdminServiceError> {
+        logging::current_filter()
+            .map(|filter| LogLevelResponse { filter })
+            .ok_or_else(|| AdminServiceError::InternalError("日志未由 kiro-rs 初始化".to_string()))
+    }
+
+    /// 运行时调整日志过滤规则（不写入配置，重启后恢复 `RUST_LOG`）
+    pub fn set_log_level(
+        &self,
+        req: SetLogLevelRequest,
+    ) -> Result<LogLevelResponse, AdminServiceError> {
+        let filter = logging::set_filter(&req.filter)
+            .map_err(|e| AdminServiceError::InvalidRequest(format!("{:#}", e)))?;
+        tracing::info!("日志过滤规则已调整为: {}", filter);
+        Ok(LogLevelResponse { filter })
+    }
+
+    /// 获取请求捕获状态
+    pub fn get_capture_status(&self) -> CaptureStatus {
+        capture::controller().status()
+    }
+
+    /// 开启请求捕获（覆盖进行中的设置）
+    pub fn start_capture(
+        &self,
+        req: StartCaptureRequest,
+    ) -> Result<CaptureStatus, AdminServiceError> {
+        if req.count == 0 || req.count > capture::MAX_CAPTURE_COUNT {
+            return Err(AdminServiceError::InvalidRequest(format!(
+                "count 必须在 1-{} 之间",
+                capture::MAX_CAPTURE_COUNT
+            )));
+        }
+        let dir = match req.dir.filter(|d| !d.trim().is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => self
+                .token_manager
+                .cache_dir()
+                .unwrap_or_default()
+                .join("captures"),
+        };
+        capture::controller()
+            .arm(dir, req.count)
+            .map_err(|e| AdminServiceError::InternalError(format!("{:#}", e)))?;
+        Ok(capture::controller().status())
+    }
+
+    /// 关闭请求捕获
+    pub fn stop_capture(&self) -> CaptureStatus {
+        capture::controller().disarm();
+        capture::controller().status()
+    }
+
+    /// 获取上游协议漂移报告
+    pub fn get_drift_report(&self) -> DriftReport {
+        drift::tracker().report()
+    }
+
+    /// 生成脱敏诊断包（zip）
+    ///
+    /// 包含版本信息、脱敏配置、自检结果（含后台任务状态）、脱敏凭据状态、
+    /// 解码器统计（协议漂移报告）、延迟统计与最近日志
+    pub fn diagnostics_bundle(
+        &self,
+        workers: Vec<WorkerStatus>,
+    ) -> Result<Vec<u8>, AdminServiceError> {
+        let config = self.token_manager.config();
+        let credentials = self.get_all_credentials(&CredentialsQuery::default());
+        let drift_report = self.get_drift_report();
+
+        let mut checks = vec![
+            match config.config_path() {
+                Some(path) => {
+                    SelfCheck::new("config_file", path.exists(), path.display().to_string())
+                }
+                None => SelfCheck::new("config_file", false, "配置文件路径未知"),
+            },
+            SelfCheck::new(
+                "credentials",
+                credentials.available > 0,
+                format!("{}/{} 个凭据可用", credentials.available, credentials.total),
+            ),
+            SelfCheck::new(
+                "upstream_drift",
+                drift_report.last_hour_drift == 0,
+                format!(
+                    "最近一小时 {} 个漂移事件 / {} 帧",
+                    drift_report.last_hour_drift, drift_report.last_hour_frames
+                ),
+            ),
+        ];
+        let failed_workers: Vec<&str> = workers
+            .iter()
+            .filter(|w| w.state != WorkerState::Running)
+            .map(|w| w.name.as_str())
+            .collect();
+        checks.push(SelfCheck::new(
+            "workers",
+            failed_workers.is_empty(),
+            if failed_workers.is_empty() {
+                format!("{} 个后台任务运行中", workers.len())
+            } else {
+                format!("未运行: {}", failed_workers.join(", "))
+            },
+        ));
+
+        let self_check = serde_json::json!({ "checks": checks, "workers": workers });
+        let mut files = Vec::new();
+        for (name, content) in [
+            ("version.json", sanitized_json(&VersionInfo::current())),
+            ("config.json", sanitized_json(&*config)),
+            ("self-check.json", sanitized_json(&self_check)),
+            ("credentials.json", sanitized_json(&credentials)),
+            ("decoder.json", sanitized_json(&drift_report)),
+            ("stats.json", sanitized_json(&self.get_stats())),
+        ] {
+            let content = content.map_err(|e| {
+                AdminServiceError::InternalError(format!("序列化诊断信息失败: {}", e))
+            })?;
+            files.push((name, content));
+        }
+        let logs: String = log_buffer::buffer()
+            .recent()
+            .iter()
+            .map(|line| sanitize_text(line) + "\n")
+            .collect();
+        files.push(("logs.txt", logs.into_bytes()));
+
+        build_zip(&files)
+            .map_err(|e| AdminServiceError::InternalError(format!("打包诊断包失败: {}", e)))
     }
 
     /// 添加新凭据
@@ -204,10 +702,23 @@ impl AdminService {
             api_region: req.api_region,
             machine_id: req.machine_id,
             email: req.email,
+            account_id: None,
             subscription_title: None, // 将在首次获取使用额度时自动更新
             proxy_url: req.proxy_url,
             proxy_username: req.proxy_username,
             proxy_password: req.proxy_password,
+            tls_backend: None,
+            ca_cert_path: None,
+            pinned_cert_sha256: Vec::new(),
+            rate_limit_rpm: req.rate_limit_rpm,
+            max_concurrency: None,
+            usage_cap: None,
+            usage_watermark: None,
+            tags: KiroCredentials::normalize_tags(req.tags),
+            note: KiroCredentials::normalize_note(req.note),
+            allowed_models: KiroCredentials::normalize_tags(req.allowed_models),
+            blocked_models: KiroCredentials::normalize_tags(req.blocked_models),
+            peer_source_id: None,
             disabled: false, // 新添加的凭据默认启用
         };
 
@@ -231,8 +742,63 @@ impl AdminService {
         })
     }
 
+    /// 从 CSV / TSV 批量导入凭据
+    ///
+    /// 表头或列映射错误时整体失败；单行错误记录在报告中
+    pub async fn import_credentials(
+        &self,
+        req: ImportCredentialsRequest,
+    ) -> Result<ImportReport, AdminServiceError> {
+        let format = req
+            .format
+            .unwrap_or_else(|| ImportFormat::detect(&req.content));
+        let mapping = ColumnMapping::new(req.mapping);
+        let rows = credential_import::parse_table(&req.content, format, &mapping)
+            .map_err(|e| AdminServiceError::InvalidRequest(e.to_string()))?;
+
+        Ok(credential_import::import_credentials(&self.token_manager, rows, req.dry_run).await)
+    }
+
+    /// 扫描 Kiro IDE 的 Token 缓存并导入新凭据
+    pub async fn discover_credentials(&self) -> Result<DiscoveryReport, AdminServiceError> {
+        if !self.token_manager.is_multiple_format() {
+            return Err(AdminServiceError::InvalidRequest(
+                "单凭据格式无法保存导入的凭据，请先将凭证文件改为数组格式".to_string(),
+            ));
+        }
+        let config = self.token_manager.config();
+        let paths = credential_discovery::resolve_paths(config.credential_discovery.as_ref());
+        Ok(credential_discovery::discover_credentials(&self.token_manager, &paths).await)
+    }
+
+    /// 检测并合并重复凭据
+    pub fn dedupe_credentials(
+        &self,
+        req: DedupeCredentialsRequest,
+    ) -> Result<DedupeCredentialsResponse, AdminServiceError> {
+        let groups = self
+            .token_manager
+            .merge_duplicates(req.dry_run)
+            .map_err(|e| AdminServiceError::InternalError(e.to_string()))?;
+        let removed: Vec<CredentialId> = groups.iter().flat_map(|g| g.remove.clone()).collect();
+
+        // 清理已删除凭据的余额缓存
+        if !req.dry_run && !removed.is_empty() {
+            self.balance_cache
+                .lock()
+                .retain(|id, _| !removed.contains(id));
+            self.save_balance_cache();
+        }
+
+        Ok(DedupeCredentialsResponse {
+            dry_run: req.dry_run,
+            groups,
+            removed: removed.len(),
+        })
+    }
+
     /// 删除凭据
-    pub fn delete_credential(&self, id: u64) -> Result<(), AdminServiceError> {
+    pub fn delete_credential(&self, id: CredentialId) -> Result<(), AdminServiceError> {
         self.token_manager
             .delete_credential(id)
             .map_err(|e| self.classify_delete_error(e, id))?;
@@ -247,6 +813,244 @@ impl AdminService {
         Ok(())
     }
 
+    /// 获取所有托管 API Key
+    pub fn list_api_keys(&self) -> ApiKeysResponse {
+        let now = Utc::now();
+        let offset = self.token_manager.config().timezone_offset();
+        ApiKeysResponse {
+            keys: self
+                .api_keys
+                .list()
+                .into_iter()
+                .map(|record| ApiKeyItem::new(record, now, offset))
+                .collect(),
+        }
+    }
+
+    /// 创建托管 API Key（明文 Key 仅在响应中返回一次）
+    pub fn create_api_key(
+        &self,
+        req: CreateApiKeyRequest,
+    ) -> Result<CreateApiKeyResponse, AdminServiceError> {
+        let primary = self.token_manager.config().api_key.clone().unwrap_or_default();
+        let token_quota = req.token_quota.filter(|q| *q > 0);
+        let (record, key) = self
+            .api_keys
+            .create(&req.name, req.key, token_quota, req.quotas.normalized(), &primary)
+            .map_err(|e| {
+                let msg = e.to_string();
+                if msg.contains("文件") {
+                    AdminServiceError::InternalError(msg)
+                } else {
+                    AdminServiceError::InvalidRequest(msg)
+                }
+            })?;
+
+        let offset = self.token_manager.config().timezone_offset();
+        Ok(CreateApiKeyResponse {
+            key,
+            api_key: ApiKeyItem::new(record, Utc::now(), offset),
+        })
+    }
+
+    /// 设置托管 API Key 的配额
+    pub fn set_api_key_quotas(
+        &self,
+        id: &str,
+        req: SetApiKeyQuotasRequest,
+    ) -> Result<ApiKeyItem, AdminServiceError> {
+        let token_quota = req.token_quota.filter(|q| *q > 0);
+        let record = self
+            .api_keys
+            .set_quotas(id, token_quota, req.quotas.normalized())
+            .map_err(|e| self.classify_api_key_error(e, id))?;
+
+        let offset = self.token_manager.config().timezone_offset();
+        Ok(ApiKeyItem::new(record, Utc::now(), offset))
+    }
+
+    /// 删除托管 API Key
+    pub fn delete_api_key(&self, id: &str) -> Result<(), AdminServiceError> {
+        self.api_keys
+            .delete(id)
+            .map_err(|e| self.classify_api_key_error(e, id))
+    }
+
+    // ============ Admin Key ============
+
+    /// 按 adminKeys 查找 Key 的角色
+    pub fn admin_key_role(&self, key: &str) -> Option<AdminRole> {
+        self.token_manager
+            .config()
+            .admin_keys
+            .iter()
+            .find(|k| !k.key.is_empty() && auth::constant_time_eq(key, &k.key))
+            .map(|k| k.role)
+    }
+
+    /// 列出 adminKeys（不含明文 Key）
+    pub fn list_admin_keys(&self) -> AdminKeysResponse {
+        AdminKeysResponse {
+            keys: self
+                .token_manager
+                .config()
+                .admin_keys
+                .iter()
+                .map(admin_key_item)
+                .collect(),
+        }
+    }
+
+    /// 创建 Admin Key 并写入 config.json（明文 Key 仅在响应中返回一次）
+    pub fn create_admin_key(
+        &self,
+        req: CreateAdminKeyRequest,
+    ) -> Result<CreateAdminKeyResponse, AdminServiceError> {
+        let name = req.name.trim().to_string();
+        if name.is_empty() {
+            return Err(AdminServiceError::InvalidRequest("名称不能为空".to_string()));
+        }
+        let key = match req.key.map(|k| k.trim().to_string()) {
+            Some(k) if k.len() < 16 => {
+                return Err(AdminServiceError::InvalidRequest(
+                    "Admin Key 长度不能少于 16 个字符".to_string(),
+                ));
+            }
+            Some(k) => k,
+            None => format!("sk-admin-{}", uuid::Uuid::new_v4().simple()),
+        };
+
+        let created = AdminKeyConfig {
+            name: name.clone(),
+            key: key.clone(),
+            role: req.role,
+        };
+        self.update_admin_keys(|config| {
+            if config.admin_keys.iter().any(|k| k.name == name) {
+                return Err(format!("名称已存在: {}", name));
+            }
+            let duplicated = config.admin_api_key.as_deref() == Some(key.as_str())
+                || config.admin_keys.iter().any(|k| k.key == key);
+            if duplicated {
+                return Err("Admin Key 已存在".to_string());
+            }
+            config.admin_keys.push(created.clone());
+            Ok(())
+        })?;
+
+        tracing::info!("已创建 Admin Key {}（{:?}）", created.name, created.role);
+        Ok(CreateAdminKeyResponse {
+            key,
+            admin_key: admin_key_item(&created),
+        })
+    }
+
+    /// 删除 Admin Key（已登录的会话在过期前保持有效）
+    pub fn delete_admin_key(&self, name: &str) -> Result<(), AdminServiceError> {
+        if !self
+            .token_manager
+            .config()
+            .admin_keys
+            .iter()
+            .any(|k| k.name == name)
+        {
+            return Err(AdminServiceError::AdminKeyNotFound {
+                name: name.to_string(),
+            });
+        }
+        self.update_admin_keys(|config| {
+            config.admin_keys.retain(|k| k.name != name);
+            Ok(())
+        })?;
+        tracing::info!("已删除 Admin Key {}", name);
+        Ok(())
+    }
+
+    /// 基于磁盘上的最新配置修改 adminKeys，保存后热应用
+    fn update_admin_keys(
+        &self,
+        modify: impl FnOnce(&mut Config) -> Result<(), String>,
+    ) -> Result<(), AdminServiceError> {
+        let current = self.token_manager.config();
+        let path = current.config_path().ok_or_else(|| {
+            AdminServiceError::InternalError("配置文件路径未知，无法保存配置".to_string())
+        })?;
+        let mut updated = Config::load(path).map_err(|e| {
+            AdminServiceError::InternalError(format!("读取配置文件失败: {}", e))
+        })?;
+        modify(&mut updated).map_err(AdminServiceError::InvalidRequest)?;
+
+        updated
+            .save()
+            .map_err(|e| AdminServiceError::InternalError(e.to_string()))?;
+        self.token_manager
+            .reload_config(updated)
+            .map_err(|e| AdminServiceError::InvalidRequest(e.to_string()))
+    }
+
+    /// 向所有已配置的 Webhook 与聊天渠道发送一次测试事件（不重试，直接返回各自结果）
+    pub async fn test_webhooks(&self) -> Result<TestWebhooksResponse, AdminServiceError> {
+        let config = self.token_manager.config();
+        let channels = ChatChannel::from_config(&config);
+        if config.webhooks.is_empty() && channels.is_empty() {
+            return Err(AdminServiceError::InvalidRequest(
+                "未配置 webhooks 或 telegram / slack / discord".to_string(),
+            ));
+        }
+        let event = NotificationEvent::new(EventKind::Test, "kiro-rs 通知测试事件");
+        let tls_backend = config.tls_backend;
+        let proxy = config.proxy_config();
+        let to_result =
+            |channel: &str, url: Option<String>, result: anyhow::Result<u16>| WebhookTestResult {
+                channel: channel.to_string(),
+                url,
+                success: result.is_ok(),
+                status: result.as_ref().ok().copied(),
+                error: result.err().map(|e| format!("{:#}", e)),
+            };
+        let webhooks = futures::future::join_all(config.webhooks.iter().map(|w| {
+            let event = &event;
+            async move {
+                let result = webhook::deliver_once(w, event, tls_backend).await;
+                to_result("webhook", Some(w.url.clone()), result)
+            }
+        }));
+        let chats = futures::future::join_all(channels.iter().map(|c| {
+            let event = &event;
+            let proxy = proxy.as_ref();
+            async move {
+                let result = chat::deliver_once(c, event, proxy, tls_backend).await;
+                to_result(c.name(), None, result)
+            }
+        }));
+        let (mut results, chats) = futures::future::join(webhooks, chats).await;
+        results.extend(chats);
+        Ok(TestWebhooksResponse { results })
+    }
+
+    /// 获取优先级预设及最近一次应用记录
+    pub fn get_priority_presets(&self) -> PriorityPresetsResponse {
+        PriorityPresetsResponse {
+            presets: self.token_manager.config().priority_presets.clone(),
+            active: self.token_manager.active_priority_preset(),
+        }
+    }
+
+    /// 应用优先级预设
+    pub fn apply_priority_preset(
+        &self,
+        name: &str,
+    ) -> Result<AppliedPriorityPreset, AdminServiceError> {
+        if !self.token_manager.config().priority_presets.contains_key(name) {
+            return Err(AdminServiceError::PresetNotFound {
+                name: name.to_string(),
+            });
+        }
+        self.token_manager
+            .apply_priority_preset(name)
+            .map_err(|e| AdminServiceError::InternalError(e.to_string()))
+    }
+
     /// 获取负载均衡模式
     pub fn get_load_balancing_mode(&self) -> LoadBalancingModeResponse {
         LoadBalancingModeResponse {
@@ -273,9 +1077,146 @@ impl AdminService {
         Ok(LoadBalancingModeResponse { mode: req.mode })
     }
 
+    /// 获取可编辑的配置子集
+    pub fn get_config(&self) -> ConfigResponse {
+        config_response(&self.token_manager.config())
+    }
+
+    /// 更新配置子集：校验后写回 config.json 并热应用
+    pub fn update_config(
+        &self,
+        req: UpdateConfigRequest,
+    ) -> Result<UpdateConfigResponse, AdminServiceError> {
+        let current = self.token_manager.config();
+        let path = current.config_path().ok_or_else(|| {
+            AdminServiceError::InternalError("配置文件路径未知，无法保存配置".to_string())
+        })?;
+
+        // 基于磁盘上的最新内容修改，避免覆盖外部编辑
+        let mut updated = Config::load(path).map_err(|e| {
+            AdminServiceError::InternalError(format!("读取配置文件失败: {}", e))
+        })?;
+        apply_config_update(&mut updated, req).map_err(AdminServiceError::InvalidRequest)?;
+
+        updated
+            .save()
+            .map_err(|e| AdminServiceError::InternalError(e.to_string()))?;
+
+        let requires_restart = restart_required_changes(&current, &updated);
+        let config = config_response(&updated);
+        self.token_manager
+            .reload_config(updated)
+            .map_err(|e| AdminServiceError::InvalidRequest(e.to_string()))?;
+
+        Ok(UpdateConfigResponse {
+            config,
+            requires_restart,
+        })
+    }
+
+    /// 获取 Cloud Pass 配置（激活码脱敏）
+    pub fn get_cloud_pass_config(&self) -> CloudPassConfigResponse {
+        cloud_pass_config_response(self.token_manager.config().cloud_pass.as_ref())
+    }
+
+    /// 更新 Cloud Pass 配置：校验后写回 config.json 并热应用
+    ///
+    /// 调用方随后通知 Cloud Pass 后台任务，按新配置立即重启受影响的激活码
+    pub fn update_cloud_pass_config(
+        &self,
+        req: UpdateCloudPassConfigRequest,
+    ) -> Result<CloudPassConfigResponse, AdminServiceError> {
+        let current = self.token_manager.config();
+        let path = current.config_path().ok_or_else(|| {
+            AdminServiceError::InternalError("配置文件路径未知，无法保存配置".to_string())
+        })?;
+
+        let mut updated = Config::load(path)
+            .map_err(|e| AdminServiceError::InternalError(format!("读取配置文件失败: {}", e)))?;
+        apply_cloud_pass_update(&mut updated, req).map_err(AdminServiceError::InvalidRequest)?;
+
+        updated
+            .save()
+            .map_err(|e| AdminServiceError::InternalError(e.to_string()))?;
+
+        let response = cloud_pass_config_response(updated.cloud_pass.as_ref());
+        self.token_manager
+            .reload_config(updated)
+            .map_err(|e| AdminServiceError::InvalidRequest(e.to_string()))?;
+        Ok(response)
+    }
+
+    /// 手动抢占 Cloud Pass 设备（调用 claim-active）
+    ///
+    /// `license` 为空时抢占所有激活码；调用方随后触发刷新，刷新成功后清除被踢出状态
+    pub async fn claim_cloud_pass(
+        &self,
+        license: Option<&str>,
+    ) -> Result<ClaimCloudPassResponse, AdminServiceError> {
+        let config = self.token_manager.config();
+        let cloud_pass = config
+            .active_cloud_pass()
+            .ok_or_else(|| AdminServiceError::InvalidRequest("Cloud Pass 未启用".to_string()))?;
+        let clients: Vec<_> = CloudPassClient::for_config(cloud_pass)
+            .into_iter()
+            .filter(|client| {
+                license.is_none_or(|l| {
+                    client.license_code() == l || mask_license_code(client.license_code()) == l
+                })
+            })
+            .collect();
+        if clients.is_empty() {
+            return Err(AdminServiceError::InvalidRequest(format!(
+                "未找到激活码: {}",
+                license.unwrap_or_default()
+            )));
+        }
+
+        let results = futures::future::join_all(clients.iter().map(|client| async move {
+            let result = client.claim_active().await;
+            ClaimCloudPassResult {
+                license: mask_license_code(client.license_code()),
+                device_id: client.device_id().to_string(),
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            }
+        }))
+        .await;
+        Ok(ClaimCloudPassResponse { results })
+    }
+
+    /// 从磁盘重新加载 config.json 并热应用
+    pub fn reload_config(&self) -> Result<ReloadConfigResponse, AdminServiceError> {
+        let current = self.token_manager.config();
+        let path = current.config_path().ok_or_else(|| {
+            AdminServiceError::InternalError("配置文件路径未知，无法重新加载".to_string())
+        })?;
+
+        let latest = Config::load(path).map_err(|e| {
+            AdminServiceError::InvalidRequest(format!("配置文件解析失败: {}", e))
+        })?;
+        let requires_restart = restart_required_changes(&current, &latest);
+
+        self.token_manager
+            .reload_config(latest)
+            .map_err(|e| AdminServiceError::InvalidRequest(e.to_string()))?;
+
+        if !requires_restart.is_empty() {
+            tracing::warn!("以下配置项需要重启服务才能生效: {}", requires_restart.join(", "));
+        }
+
+        Ok(ReloadConfigResponse {
+            success: true,
+            message: "配置已重新加载".to_string(),
+            requires_restart,
+        })
+    }
+
     // ============ 余额缓存持久化 ============
 
-    fn load_balance_cache_from(cache_path: &Option<PathBuf>) -> HashMap<u64, CachedBalance> {
+    fn load_balance_cache_from(
+        cache_path: &Option<PathBuf>,
+    ) -> HashMap<CredentialId, CachedBalance> {
         let path = match cache_path {
             Some(p) => p,
             None => return HashMap::new(),
@@ -298,7 +1239,7 @@ impl AdminService {
         let now = Utc::now().timestamp() as f64;
         map.into_iter()
             .filter_map(|(k, v)| {
-                let id = k.parse::<u64>().ok()?;
+                let id = k.parse::<CredentialId>().ok()?;
                 // 丢弃超过 TTL 的条目
                 if (now - v.cached_at) < BALANCE_CACHE_TTL_SECS as f64 {
                     Some((id, v))
@@ -333,7 +1274,7 @@ impl AdminService {
     // ============ 错误分类 ============
 
     /// 分类简单操作错误（set_disabled, set_priority, reset_and_enable）
-    fn classify_error(&self, e: anyhow::Error, id: u64) -> AdminServiceError {
+    fn classify_error(&self, e: anyhow::Error, id: CredentialId) -> AdminServiceError {
         let msg = e.to_string();
         if msg.contains("不存在") {
             AdminServiceError::NotFound { id }
@@ -342,8 +1283,18 @@ impl AdminService {
         }
     }
 
+    /// 分类托管 API Key 操作错误
+    fn classify_api_key_error(&self, e: anyhow::Error, id: &str) -> AdminServiceError {
+        let msg = e.to_string();
+        if msg.contains("不存在") {
+            AdminServiceError::ApiKeyNotFound { id: id.to_string() }
+        } else {
+            AdminServiceError::InternalError(msg)
+        }
+    }
+
     /// 分类余额查询错误（可能涉及上游 API 调用）
-    fn classify_balance_error(&self, e: anyhow::Error, id: u64) -> AdminServiceError {
+    fn classify_balance_error(&self, e: anyhow::Error, id: CredentialId) -> AdminServiceError {
         let msg = e.to_string();
 
         // 1. 凭据不存在
@@ -402,7 +1353,7 @@ impl AdminService {
     }
 
     /// 分类删除凭据错误
-    fn classify_delete_error(&self, e: anyhow::Error, id: u64) -> AdminServiceError {
+    fn classify_delete_error(&self, e: anyhow::Error, id: CredentialId) -> AdminServiceError {
         let msg = e.to_string();
         if msg.contains("不存在") {
             AdminServiceError::NotFound { id }
@@ -413,3 +1364,432 @@ impl AdminService {
         }
     }
 }
+
+fn config_response(config: &Config) -> ConfigResponse {
+    ConfigResponse {
+        region: config.region.clone(),
+        auth_region: config.auth_region.clone(),
+        api_region: config.api_region.clone(),
+        kiro_version: config.kiro_version.clone(),
+        system_version: config.system_version.clone(),
+        node_version: config.node_version.clone(),
+        proxy_url: config.proxy_url.clone(),
+        proxy_username: config.proxy_username.clone(),
+        has_proxy_password: config.proxy_password.is_some(),
+        count_tokens_api_url: config.count_tokens_api_url.clone(),
+        has_count_tokens_api_key: config.count_tokens_api_key.is_some(),
+        count_tokens_auth_type: config.count_tokens_auth_type.clone(),
+        timezone: config.timezone.clone(),
+    }
+}
+
+fn cloud_pass_config_response(cloud_pass: Option<&CloudPassConfig>) -> CloudPassConfigResponse {
+    let config = cloud_pass.cloned().unwrap_or_default();
+    CloudPassConfigResponse {
+        configured: cloud_pass.is_some(),
+        enabled: cloud_pass.is_some() && !config.disabled,
+        licenses: config
+            .all_licenses()
+            .iter()
+            .map(|l| mask_license_code(&l.license_code))
+            .collect(),
+        server_url: config.server_url,
+        refresh_interval: config.refresh_interval,
+        heartbeat_interval: config.heartbeat_interval,
+        reassign: config.reassign,
+        client_version: config.client_version,
+    }
+}
+
+/// Cloud Pass 刷新间隔下限（秒）
+const MIN_CLOUD_PASS_INTERVAL_SECS: u64 = 60;
+/// Cloud Pass 心跳间隔下限（秒，0 表示不发送心跳）
+const MIN_CLOUD_PASS_HEARTBEAT_SECS: u64 = 30;
+
+/// 校验并应用 Cloud Pass 配置更新（未配置时以默认值创建）
+fn apply_cloud_pass_update(
+    config: &mut Config,
+    req: UpdateCloudPassConfigRequest,
+) -> Result<(), String> {
+    let cloud_pass = config
+        .cloud_pass
+        .get_or_insert_with(CloudPassConfig::default);
+    if let Some(enabled) = req.enabled {
+        cloud_pass.disabled = !enabled;
+    }
+    if let Some(url) = req.server_url {
+        cloud_pass.server_url = validate_http_url("serverUrl", url.trim())?;
+    }
+    if let Some(interval) = req.refresh_interval {
+        if interval < MIN_CLOUD_PASS_INTERVAL_SECS {
+            return Err(format!(
+                "refreshInterval 不能小于 {} 秒",
+                MIN_CLOUD_PASS_INTERVAL_SECS
+            ));
+        }
+        cloud_pass.refresh_interval = interval;
+    }
+    if let Some(interval) = req.heartbeat_interval {
+        if interval != 0 && interval < MIN_CLOUD_PASS_HEARTBEAT_SECS {
+            return Err(format!(
+                "heartbeatInterval 不能小于 {} 秒（0 表示不发送心跳）",
+                MIN_CLOUD_PASS_HEARTBEAT_SECS
+            ));
+        }
+        cloud_pass.heartbeat_interval = interval;
+    }
+    if let Some(reassign) = req.reassign {
+        cloud_pass.reassign = reassign;
+    }
+    if let Some(code) = req.license_code {
+        cloud_pass.license_code = code.trim().to_string();
+    }
+    if let Some(licenses) = req.licenses {
+        if licenses.iter().any(|l| l.license_code.trim().is_empty()) {
+            return Err("licenses 中的 licenseCode 不能为空".to_string());
+        }
+        cloud_pass.licenses = licenses
+            .into_iter()
+            .map(|l| CloudPassLicense {
+                license_code: l.license_code.trim().to_string(),
+                ..l
+            })
+            .collect();
+    }
+    if !cloud_pass.disabled && cloud_pass.all_licenses().is_empty() {
+        return Err("启用 Cloud Pass 需要至少一个激活码（licenseCode / licenses）".to_string());
+    }
+    Ok(())
+}
+
+/// 校验并应用配置更新，任一字段无效则整体拒绝
+fn apply_config_update(config: &mut Config, req: UpdateConfigRequest) -> Result<(), String> {
+    if let Some(region) = req.region {
+        config.region = validate_region(&region)?;
+    }
+    if let Some(region) = req.auth_region {
+        config.auth_region = optional(region).map(|r| validate_region(&r)).transpose()?;
+    }
+    if let Some(region) = req.api_region {
+        config.api_region = optional(region).map(|r| validate_region(&r)).transpose()?;
+    }
+    if let Some(version) = req.kiro_version {
+        config.kiro_version = required("kiroVersion", version)?;
+    }
+    if let Some(version) = req.system_version {
+        config.system_version = required("systemVersion", version)?;
+    }
+    if let Some(version) = req.node_version {
+        config.node_version = required("nodeVersion", version)?;
+    }
+    if let Some(url) = req.proxy_url {
+        config.proxy_url = optional(url).map(|u| validate_proxy_url(&u)).transpose()?;
+    }
+    if let Some(username) = req.proxy_username {
+        config.proxy_username = optional(username);
+    }
+    if let Some(password) = req.proxy_password {
+        config.proxy_password = optional(password);
+    }
+    if let Some(url) = req.count_tokens_api_url {
+        config.count_tokens_api_url = optional(url)
+            .map(|u| validate_http_url("countTokensApiUrl", &u))
+            .transpose()?;
+    }
+    if let Some(key) = req.count_tokens_api_key {
+        config.count_tokens_api_key = optional(key);
+    }
+    if let Some(auth_type) = req.count_tokens_auth_type {
+        let auth_type = auth_type.trim().to_string();
+        if auth_type != "x-api-key" && auth_type != "bearer" {
+            return Err("countTokensAuthType 必须是 'x-api-key' 或 'bearer'".to_string());
+        }
+        config.count_tokens_auth_type = auth_type;
+    }
+    if let Some(tz) = req.timezone {
+        config.timezone = optional(tz)
+            .map(|tz| match parse_utc_offset(&tz) {
+                Some(_) => Ok(tz),
+                None => Err(format!("无效的时区: {}（应为 UTC 偏移，如 +08:00）", tz)),
+            })
+            .transpose()?;
+    }
+    Ok(())
+}
+
+/// 解析用量查询日期（YYYY-MM-DD）
+fn parse_usage_date(
+    field: &str,
+    value: Option<&str>,
+) -> Result<Option<NaiveDate>, AdminServiceError> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(v) => NaiveDate::parse_from_str(v, "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| {
+                AdminServiceError::InvalidRequest(format!(
+                    "{} 日期格式无效（应为 YYYY-MM-DD）: {}",
+                    field, v
+                ))
+            }),
+    }
+}
+
+/// 空字符串视为清除
+fn optional(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn required(name: &str, value: String) -> Result<String, String> {
+    optional(value).ok_or_else(|| format!("{} 不能为空", name))
+}
+
+fn validate_region(region: &str) -> Result<String, String> {
+    let region = region.trim();
+    let valid = !region.is_empty()
+        && region.contains('-')
+        && region
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(region.to_string())
+    } else {
+        Err(format!("无效的区域: {}", region))
+    }
+}
+
+fn validate_proxy_url(url: &str) -> Result<String, String> {
+    const SCHEMES: [&str; 4] = ["http://", "https://", "socks5://", "socks5h://"];
+    match SCHEMES.iter().find_map(|scheme| url.strip_prefix(scheme)) {
+        Some(host) if !host.is_empty() => Ok(url.to_string()),
+        _ => Err(format!("proxyUrl 仅支持 http/https/socks5 协议: {}", url)),
+    }
+}
+
+fn admin_key_item(key: &AdminKeyConfig) -> AdminKeyItem {
+    AdminKeyItem {
+        name: key.name.clone(),
+        role: key.role,
+        key_prefix: key.key.chars().take(12).collect(),
+    }
+}
+
+fn validate_http_url(name: &str, url: &str) -> Result<String, String> {
+    let url = url.trim_end_matches('/');
+    let host = url
+        .strip_prefix("http://")
+        .or_else(|| url.strip_prefix("https://"));
+    match host {
+        Some(host) if !host.is_empty() => Ok(url.to_string()),
+        _ => Err(format!("{} 必须是 http(s) 地址: {}", name, url)),
+    }
+}
+
+/// 列出热重载无法应用、需要重启才能生效的配置变更
+///
+/// 监听地址、认证密钥和全局 HTTP 客户端参数在启动时即固定；
+/// 后台任务仅在启动时按开关决定是否创建
+fn restart_required_changes(current: &Config, latest: &Config) -> Vec<String> {
+    let mut changed = Vec::new();
+    let mut check = |name: &str, differs: bool| {
+        if differs {
+            changed.push(name.to_string());
+        }
+    };
+
+    check("host", current.host != latest.host);
+    check("port", current.port != latest.port);
+    check("apiKey", current.api_key != latest.api_key);
+    check("adminApiKey", current.admin_api_key != latest.admin_api_key);
+    check("tlsBackend", current.tls_backend != latest.tls_backend);
+    check("logFormat", current.log_format != latest.log_format);
+    check(
+        "countTokensApiUrl",
+        current.count_tokens_api_url != latest.count_tokens_api_url,
+    );
+    check(
+        "countTokensApiKey",
+        current.count_tokens_api_key != latest.count_tokens_api_key,
+    );
+    check(
+        "countTokensAuthType",
+        current.count_tokens_auth_type != latest.count_tokens_auth_type,
+    );
+    check("tokenizer", current.tokenizer != latest.tokenizer);
+    check(
+        "healthCheckInterval",
+        (current.health_check_interval > 0) != (latest.health_check_interval > 0),
+    );
+    check(
+        "exerciseHourUtc",
+        current.exercise_hour_utc != latest.exercise_hour_utc,
+    );
+    check(
+        "priorityPresets",
+        !current.has_scheduled_presets() && latest.has_scheduled_presets(),
+    );
+    check(
+        "peerSync",
+        current.peer_sync.is_none() && latest.peer_sync.is_some(),
+    );
+    check(
+        "sharedState",
+        current.shared_state.is_none() && latest.shared_state.is_some(),
+    );
+    check(
+        "leaderElection",
+        current.leader_election != latest.leader_election,
+    );
+    check(
+        "workerRestart",
+        RestartPolicy::from_config(current) != RestartPolicy::from_config(latest),
+    );
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_config_update_validates_and_clears() {
+        let mut config = Config::default();
+        config.proxy_url = Some("http://127.0.0.1:7890".to_string());
+
+        let req = UpdateConfigRequest {
+            region: Some("eu-west-1".to_string()),
+            proxy_url: Some(String::new()),
+            count_tokens_auth_type: Some("bearer".to_string()),
+            ..Default::default()
+        };
+        apply_config_update(&mut config, req).unwrap();
+        assert_eq!(config.region, "eu-west-1");
+        assert_eq!(config.proxy_url, None);
+        assert_eq!(config.count_tokens_auth_type, "bearer");
+
+        let invalid = UpdateConfigRequest {
+            proxy_url: Some("ftp://proxy:21".to_string()),
+            ..Default::default()
+        };
+        assert!(apply_config_update(&mut config, invalid).is_err());
+
+        let invalid = UpdateConfigRequest {
+            region: Some("US East".to_string()),
+            ..Default::default()
+        };
+        assert!(apply_config_update(&mut config, invalid).is_err());
+        assert_eq!(config.region, "eu-west-1");
+    }
+
+    #[test]
+    fn test_apply_cloud_pass_update_creates_and_pauses() {
+        let mut config = Config::default();
+        let missing_license = UpdateCloudPassConfigRequest {
+            enabled: Some(true),
+            ..Default::default()
+        };
+        assert!(apply_cloud_pass_update(&mut config, missing_license).is_err());
+
+        let mut config = Config::default();
+        let req = UpdateCloudPassConfigRequest {
+            license_code: Some(" KCP-AAAA-1111 ".to_string()),
+            refresh_interval: Some(600),
+            ..Default::default()
+        };
+        apply_cloud_pass_update(&mut config, req).unwrap();
+        let cloud_pass = config.active_cloud_pass().unwrap();
+        assert_eq!(cloud_pass.license_code, "KCP-AAAA-1111");
+        assert_eq!(cloud_pass.refresh_interval, 600);
+        assert_eq!(cloud_pass.server_url, "http://kiro.eskysoft.com:9123");
+
+        // 暂停后保留激活码，响应中激活码脱敏
+        let pause = UpdateCloudPassConfigRequest {
+            enabled: Some(false),
+            ..Default::default()
+        };
+        apply_cloud_pass_update(&mut config, pause).unwrap();
+        assert!(config.active_cloud_pass().is_none());
+        let response = cloud_pass_config_response(config.cloud_pass.as_ref());
+        assert!(response.configured && !response.enabled);
+        assert_eq!(response.licenses, vec!["KCP-AA***"]);
+
+        let too_fast = UpdateCloudPassConfigRequest {
+            refresh_interval: Some(10),
+            ..Default::default()
+        };
+        assert!(apply_cloud_pass_update(&mut config, too_fast).is_err());
+
+        let no_heartbeat = UpdateCloudPassConfigRequest {
+            heartbeat_interval: Some(0),
+            ..Default::default()
+        };
+        apply_cloud_pass_update(&mut config, no_heartbeat).unwrap();
+        let response = cloud_pass_config_response(config.cloud_pass.as_ref());
+        assert_eq!(response.heartbeat_interval, 0);
+        let heartbeat_too_fast = UpdateCloudPassConfigRequest {
+            heartbeat_interval: Some(5),
+            ..Default::default()
+        };
+        assert!(apply_cloud_pass_update(&mut config, heartbeat_too_fast).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_balances_collects_cached_and_errors() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![KiroCredentials::default()],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let service = AdminService::new(Arc::new(manager), Arc::new(ApiKeyStore::in_memory()));
+        let usage: UsageLimitsResponse = serde_json::from_str(
+            r#"{"usageBreakdownList": [{"currentUsageWithPrecision": 30, "usageLimitWithPrecision": 100}]}"#,
+        )
+        .unwrap();
+        service.balance_cache.lock().insert(
+            CredentialId(1),
+            CachedBalance {
+                cached_at: Utc::now().timestamp() as f64,
+                data: AdminService::balance_from_usage(CredentialId(1), &usage),
+            },
+        );
+
+        let query = BalancesQuery {
+            ids: Some("3, 1".to_string()),
+            force: false,
+        };
+        let response = service.get_balances(&query).await.unwrap();
+        assert_eq!(response.balances.len(), 1);
+        assert_eq!(response.balances[0].id, CredentialId(1));
+        assert_eq!(response.balances[0].remaining, 70.0);
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(response.errors[0].id, CredentialId(3));
+
+        let invalid = BalancesQuery {
+            ids: Some("1,x".to_string()),
+            force: false,
+        };
+        assert!(matches!(
+            service.get_balances(&invalid).await,
+            Err(AdminServiceError::InvalidRequest(_))
+        ));
+
+        // 清除缓存后不再命中，不存在的凭据返回 404
+        assert_eq!(
+            service
+                .invalidate_balance_cache(Some(CredentialId(1)))
+                .unwrap(),
+            1
+        );
+        assert!(service.balance_cache.lock().is_empty());
+        assert!(matches!(
+            service.invalidate_balance_cache(Some(CredentialId(9))),
+            Err(AdminServiceError::NotFound { .. })
+        ));
+        assert_eq!(service.invalidate_balance_cache(None).unwrap(), 0);
+    }
+}
@@ -0,0 +1,169 @@
+//! Admin UI 会话管理
+//!
+//! 管理面板通过 `POST /api/admin/session/login` 用 Admin API Key 换取一次 HttpOnly 会话 Cookie，
+//! 浏览器端不再保存 Key。基于会话 Cookie 的写操作需在 `x-csrf-token` 请求头中携带登录时返回的 CSRF Token。
+
+use std::collections::HashMap;
+
+use axum::http::{HeaderMap, header};
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+
+use crate::model::config::AdminRole;
+
+/// 会话 Cookie 名称
+pub const SESSION_COOKIE: &str = "kiro_admin_session";
+
+/// CSRF Token 请求头
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// 会话信息
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// 会话 ID（Cookie 值）
+    pub id: String,
+    /// CSRF Token
+    pub csrf_token: String,
+    /// 过期时间
+    pub expires_at: DateTime<Utc>,
+    /// 登录所用 Key 的角色
+    pub role: AdminRole,
+}
+
+/// 会话存储（仅内存，服务重启后需重新登录）
+pub struct SessionStore {
+    sessions: Mutex<HashMap<String, Session>>,
+    ttl: Duration,
+}
+
+impl SessionStore {
+    pub fn new(ttl_secs: u64) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            ttl: Duration::seconds(ttl_secs.max(60) as i64),
+        }
+    }
+
+    /// 会话有效期（秒）
+    pub fn ttl_secs(&self) -> i64 {
+        self.ttl.num_seconds()
+    }
+
+    /// 创建新会话（同时清理已过期的会话）
+    pub fn create(&self, role: AdminRole) -> Session {
+        let now = Utc::now();
+        let session = Session {
+            id: random_token(),
+            csrf_token: random_token(),
+            expires_at: now + self.ttl,
+            role,
+        };
+
+        let mut sessions = self.sessions.lock();
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(session.id.clone(), session.clone());
+        session
+    }
+
+    /// 获取未过期的会话
+    pub fn get(&self, id: &str) -> Option<Session> {
+        let mut sessions = self.sessions.lock();
+        match sessions.get(id) {
+            Some(s) if s.expires_at > Utc::now() => Some(s.clone()),
+            Some(_) => {
+                sessions.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// 注销会话
+    pub fn remove(&self, id: &str) {
+        self.sessions.lock().remove(id);
+    }
+}
+
+/// 从 Cookie 请求头中读取会话 ID
+pub fn session_id_from_headers(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// 构建会话 Cookie（`max_age_secs` 为 0 时用于清除 Cookie）
+///
+/// 经 HTTPS 反向代理访问（`x-forwarded-proto: https`）时附加 `Secure`
+pub fn session_cookie(value: &str, max_age_secs: i64, secure: bool) -> String {
+    format!(
+        "{}={}; Path=/api/admin; HttpOnly; SameSite=Strict; Max-Age={}{}",
+        SESSION_COOKIE,
+        value,
+        max_age_secs,
+        if secure { "; Secure" } else { "" }
+    )
+}
+
+/// 请求是否经 HTTPS 到达
+pub fn is_https(headers: &HeaderMap) -> bool {
+    headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("https"))
+}
+
+/// 生成 256 位随机 Token（十六进制）
+fn random_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_session_lifecycle_and_cookie_parsing() {
+        let store = SessionStore::new(3600);
+        let session = store.create(AdminRole::Admin);
+        assert_eq!(session.id.len(), 64);
+        assert_ne!(session.id, session.csrf_token);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("theme=dark; {}={}", SESSION_COOKIE, session.id))
+                .unwrap(),
+        );
+        let id = session_id_from_headers(&headers).unwrap();
+        assert_eq!(store.get(&id).unwrap().csrf_token, session.csrf_token);
+
+        store.remove(&id);
+        assert!(store.get(&id).is_none());
+        assert!(session_id_from_headers(&HeaderMap::new()).is_none());
+
+        let cookie = session_cookie(&session.id, 3600, true);
+        assert!(cookie.contains("HttpOnly; SameSite=Strict; Max-Age=3600; Secure"));
+    }
+
+    #[test]
+    fn test_expired_session_is_rejected() {
+        let store = SessionStore::new(3600);
+        let session = store.create(AdminRole::Admin);
+        store.sessions.lock().get_mut(&session.id).unwrap().expires_at =
+            Utc::now() - Duration::seconds(1);
+
+        assert!(store.get(&session.id).is_none());
+        assert!(store.sessions.lock().is_empty());
+    }
+}
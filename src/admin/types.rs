@@ -1,7 +1,29 @@
 //! Admin API 类型定义
 
+use std::collections::{BTreeMap, HashMap};
+
 use serde::{Deserialize, Serialize};
 
+use chrono::{DateTime, FixedOffset, Utc};
+
+use crate::anthropic::cache::ResponseCacheStats;
+use crate::api_keys::{ApiKeyQuotas, ApiKeyRecord, ApiKeyUsage, PeriodUsage, QuotaExceeded};
+use crate::common::time::ZonedTime;
+use crate::kiro::credential_import::ImportFormat;
+use crate::kiro::latency::{CredentialLatency, LatencySummary};
+use crate::kiro::model::credentials::CredentialId;
+use crate::kiro::parser::stats::{BufferSummary, CorruptFrameSummary};
+use crate::kiro::request_size::RequestSizeSummary;
+use crate::kiro::token_manager::{
+    AppliedPriorityPreset, DailyUsage, DuplicateGroup, HealthStatus, SubscriptionChangeEvent,
+    UpstreamErrorRecord, WindowUsage,
+};
+use crate::leader::LeaderStatus;
+use crate::model::config::{
+    AdminRole, CloudPassLicense, PriorityPreset, UsageCap, UsageWatermark, WatermarkAction,
+};
+use crate::supervisor::WorkerStatus;
+
 // ============ 凭据状态 ============
 
 /// 所有凭据状态响应
@@ -13,7 +35,7 @@ pub struct CredentialsStatusResponse {
     /// 可用凭据数量（未禁用）
     pub available: usize,
     /// 当前活跃凭据 ID
-    pub current_id: u64,
+    pub current_id: CredentialId,
     /// 各凭据状态列表
     pub credentials: Vec<CredentialStatusItem>,
 }
@@ -23,7 +45,7 @@ pub struct CredentialsStatusResponse {
 #[serde(rename_all = "camelCase")]
 pub struct CredentialStatusItem {
     /// 凭据唯一 ID
-    pub id: u64,
+    pub id: CredentialId,
     /// 优先级（数字越小优先级越高）
     pub priority: u32,
     /// 是否被禁用
@@ -33,7 +55,9 @@ pub struct CredentialStatusItem {
     /// 是否为当前活跃凭据
     pub is_current: bool,
     /// Token 过期时间（RFC3339 格式）
-    pub expires_at: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 计划的主动刷新时间（RFC3339 格式，未启用主动刷新时为空）
+    pub next_refresh_at: Option<DateTime<Utc>>,
     /// 认证方式
     pub auth_method: Option<String>,
     /// 是否有 Profile ARN
@@ -42,10 +66,16 @@ pub struct CredentialStatusItem {
     pub refresh_token_hash: Option<String>,
     /// 用户邮箱（用于前端显示）
     pub email: Option<String>,
+    /// 上游账户 ID（查询使用额度时自动获取）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    /// 订阅等级（KIRO PRO+ / KIRO FREE 等）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription_title: Option<String>,
     /// API 调用成功次数
     pub success_count: u64,
     /// 最后一次 API 调用时间（RFC3339 格式）
-    pub last_used_at: Option<String>,
+    pub last_used_at: Option<DateTime<Utc>>,
     /// 是否配置了凭据级代理
     pub has_proxy: bool,
     /// 代理 URL（用于前端展示）
@@ -54,6 +84,45 @@ pub struct CredentialStatusItem {
     /// 凭据级 Machine ID（用于标识 Cloud Pass 来源）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub machine_id: Option<String>,
+    /// 生效的速率限制（每分钟请求数，未限制时省略）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_rpm: Option<u32>,
+    /// 生效的最大并发请求数（未限制时省略）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u32>,
+    /// 正在进行的上游请求数
+    pub in_flight: usize,
+    /// 生效的滚动窗口用量上限（未限制时省略）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_cap: Option<UsageCap>,
+    /// 最近 1 小时 / 24 小时的请求数与 token 用量
+    pub window_usage: WindowUsage,
+    /// 生效的用量警戒线（未配置时省略）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_watermark: Option<UsageWatermark>,
+    /// 最近一次查询余额时用量是否已达到警戒线
+    pub watermark_reached: bool,
+    /// 凭据标签
+    pub tags: Vec<String>,
+    /// 备注
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// 模型白名单（为空表示不限制）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allowed_models: Vec<String>,
+    /// 模型黑名单
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocked_models: Vec<String>,
+    /// 下次额度重置时间（UTC 与运营者时区，查询过余额后可用）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_reset: Option<ZonedTime>,
+    /// 最近一次上游错误（类型、截断后的信息、时间）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<UpstreamErrorRecord>,
+    /// 健康检查状态（unknown / healthy / degraded）
+    pub health_status: HealthStatus,
+    /// 最后一次健康检查时间（RFC3339 格式）
+    pub last_health_check_at: Option<DateTime<Utc>>,
 }
 
 // ============ 操作请求 ============
@@ -74,6 +143,77 @@ pub struct SetPriorityRequest {
     pub priority: u32,
 }
 
+/// 凭据列表筛选条件（查询参数）
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialsQuery {
+    /// 仅返回带有该标签的凭据
+    pub tag: Option<String>,
+    /// 仅返回指定禁用状态的凭据
+    pub disabled: Option<bool>,
+}
+
+/// 设置凭据标签请求（整体替换）
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetTagsRequest {
+    pub tags: Vec<String>,
+}
+
+/// 设置凭据备注请求（`note` 为空或省略时清除备注）
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetNoteRequest {
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// 设置凭据模型白名单 / 黑名单请求（整体替换）
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetModelsRequest {
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    #[serde(default)]
+    pub blocked_models: Vec<String>,
+}
+
+/// 设置速率限制请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetRateLimitRequest {
+    /// 每分钟请求数（null 表示回退到全局配置，0 表示不限制）
+    #[serde(default)]
+    pub rpm: Option<u32>,
+}
+
+/// 设置凭据级用量警戒线请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetUsageWatermarkRequest {
+    /// 用量百分比（0-100，null 表示回退到全局配置）
+    #[serde(default)]
+    pub percent: Option<f64>,
+    /// 达到警戒线后的处理方式（默认仅通知）
+    #[serde(default)]
+    pub action: WatermarkAction,
+}
+
+/// 设置凭据代理请求（整体替换）
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetProxyRequest {
+    /// 代理 URL（null 或空字符串表示回退到全局代理，"direct" 表示不使用代理）
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    /// 代理认证用户名（可选）
+    #[serde(default)]
+    pub proxy_username: Option<String>,
+    /// 代理认证密码（可选）
+    #[serde(default)]
+    pub proxy_password: Option<String>,
+}
+
 /// 添加凭据请求
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -120,12 +260,71 @@ pub struct AddCredentialRequest {
 
     /// 凭据级代理认证密码（可选）
     pub proxy_password: Option<String>,
+
+    /// 凭据级速率限制（可选，每分钟请求数，0 表示不限制）
+    pub rate_limit_rpm: Option<u32>,
+
+    /// 凭据标签（可选）
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// 备注（可选）
+    #[serde(default)]
+    pub note: Option<String>,
+
+    /// 模型白名单（可选）
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+
+    /// 模型黑名单（可选）
+    #[serde(default)]
+    pub blocked_models: Vec<String>,
 }
 
 fn default_auth_method() -> String {
     "social".to_string()
 }
 
+/// 批量导入凭据请求（CSV / TSV）
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportCredentialsRequest {
+    /// 表格内容（首行为表头）
+    pub content: String,
+
+    /// 表格格式（可选，未指定时根据表头自动识别）
+    #[serde(default)]
+    pub format: Option<ImportFormat>,
+
+    /// 列映射：凭据字段 -> 表格列名（可选，未映射的字段按同名列匹配）
+    #[serde(default)]
+    pub mapping: HashMap<String, String>,
+
+    /// 仅校验不导入
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// 合并重复凭据请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DedupeCredentialsRequest {
+    /// 仅检测不合并
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// 合并重复凭据响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DedupeCredentialsResponse {
+    pub dry_run: bool,
+    /// 重复凭据分组
+    pub groups: Vec<DuplicateGroup>,
+    /// 删除（或 dry-run 时将删除）的凭据数
+    pub removed: usize,
+}
+
 /// 添加凭据成功响应
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -133,7 +332,7 @@ pub struct AddCredentialResponse {
     pub success: bool,
     pub message: String,
     /// 新添加的凭据 ID
-    pub credential_id: u64,
+    pub credential_id: CredentialId,
     /// 用户邮箱（如果获取成功）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
@@ -146,7 +345,7 @@ pub struct AddCredentialResponse {
 #[serde(rename_all = "camelCase")]
 pub struct BalanceResponse {
     /// 凭据 ID
-    pub id: u64,
+    pub id: CredentialId,
     /// 订阅类型
     pub subscription_title: Option<String>,
     /// 当前使用量
@@ -159,6 +358,326 @@ pub struct BalanceResponse {
     pub usage_percentage: f64,
     /// 下次重置时间（Unix 时间戳）
     pub next_reset_at: Option<f64>,
+    /// 下次重置时间（UTC 与运营者时区，按当前配置的时区在响应时生成）
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub next_reset: Option<ZonedTime>,
+}
+
+/// 余额查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct BalanceQuery {
+    /// 跳过缓存直接查询上游（如刚充值后）
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// 批量余额查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct BalancesQuery {
+    /// 逗号分隔的凭据 ID（为空时查询所有凭据）
+    pub ids: Option<String>,
+    /// 跳过缓存直接查询上游
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// 清除余额缓存响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidateBalanceCacheResponse {
+    /// 清除的缓存条目数
+    pub removed: usize,
+}
+
+/// 单个凭据的余额查询失败
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceError {
+    pub id: CredentialId,
+    pub error: String,
+}
+
+/// 批量余额查询响应（按凭据 ID 排序）
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalancesResponse {
+    pub balances: Vec<BalanceResponse>,
+    pub errors: Vec<BalanceError>,
+}
+
+// ============ 用量统计 ============
+
+/// 用量查询参数（日期格式 YYYY-MM-DD，运营者时区，闭区间）
+#[derive(Debug, Default, Deserialize)]
+pub struct UsageQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// 单日用量
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageBucket {
+    /// 日期（YYYY-MM-DD，运营者时区）
+    pub date: String,
+    #[serde(flatten)]
+    pub usage: DailyUsage,
+}
+
+/// 凭据用量响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialUsageResponse {
+    /// 凭据 ID
+    pub id: CredentialId,
+    /// 按日用量（仅含有记录的日期，升序）
+    pub days: Vec<UsageBucket>,
+    /// 查询区间内的合计
+    pub total: DailyUsage,
+}
+
+// ============ 订阅变更 ============
+
+/// 订阅变更记录响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionChangesResponse {
+    /// 最近的订阅变更（最新的在前）
+    pub changes: Vec<SubscriptionChangeEvent>,
+}
+
+/// 上游延迟统计响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsResponse {
+    /// 所有凭据合并的首字节时间
+    pub ttfb: LatencySummary,
+    /// 所有凭据合并的总耗时
+    pub total: LatencySummary,
+    /// 各凭据的延迟（按 ID 升序，仅包含有样本的凭据）
+    pub credentials: Vec<CredentialLatency>,
+    /// 上游请求体大小
+    pub request_size: RequestSizeSummary,
+    /// 上游事件帧 CRC 校验失败统计
+    pub corrupt_frames: CorruptFrameSummary,
+    /// 解码缓冲区用量
+    pub decoder_buffer: BufferSummary,
+    /// 响应缓存命中统计（未配置 responseCache 时省略）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_cache: Option<ResponseCacheStats>,
+}
+
+/// 优先级预设列表响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriorityPresetsResponse {
+    /// 配置中的预设（名称 → 预设）
+    pub presets: BTreeMap<String, PriorityPreset>,
+    /// 最近一次应用的预设（手动或定时，重启后为空）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<AppliedPriorityPreset>,
+}
+
+/// 日志级别响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLevelResponse {
+    /// 当前生效的过滤规则（语法同 `RUST_LOG`）
+    pub filter: String,
+}
+
+/// 调整日志级别请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetLogLevelRequest {
+    /// 过滤规则（如 `info,kiro_rs::kiro::parser=trace`），为空时恢复启动时的规则
+    #[serde(default)]
+    pub filter: String,
+}
+
+/// 开启请求捕获请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartCaptureRequest {
+    /// 捕获接下来的请求数（1-100）
+    pub count: u32,
+    /// 捕获目录（默认为凭据文件所在目录下的 `captures`）
+    #[serde(default)]
+    pub dir: Option<String>,
+}
+
+/// 调试状态响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugStateResponse {
+    /// 后台任务存活状态（按名称排序）
+    pub workers: Vec<WorkerStatus>,
+    /// 主备选主状态（未配置 leaderElection 时省略）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leader: Option<LeaderStatus>,
+}
+
+// ============ 下游 API Key ============
+
+/// 托管 API Key 信息（不含明文 Key）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyItem {
+    /// API Key ID
+    pub id: String,
+    /// 名称
+    pub name: String,
+    /// Key 前缀（用于辨认）
+    pub key_prefix: String,
+    /// 总 token 配额（输入 + 输出），未设置表示不限制
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_quota: Option<u64>,
+    /// 按日 / 按月配额
+    pub quotas: ApiKeyQuotas,
+    /// 当前已超限的配额项（未超限时为 null）
+    pub quota_exceeded: Option<QuotaExceeded>,
+    /// 累计用量统计
+    pub usage: ApiKeyUsage,
+    /// 当日用量（运营者时区）
+    pub daily: PeriodUsage,
+    /// 当月用量（运营者时区）
+    pub monthly: PeriodUsage,
+    /// 创建时间（RFC3339）
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiKeyItem {
+    pub fn new(record: ApiKeyRecord, now: DateTime<Utc>, offset: FixedOffset) -> Self {
+        let (daily, monthly) = record.period_usage(now, offset);
+        Self {
+            quota_exceeded: record.check_quota(now, offset).err(),
+            id: record.id,
+            name: record.name,
+            key_prefix: record.key_prefix,
+            token_quota: record.token_quota,
+            quotas: record.quotas,
+            usage: record.usage,
+            daily,
+            monthly,
+            created_at: record.created_at,
+        }
+    }
+}
+
+/// 托管 API Key 列表响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeysResponse {
+    pub keys: Vec<ApiKeyItem>,
+}
+
+/// 创建托管 API Key 请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyRequest {
+    /// 名称（唯一）
+    pub name: String,
+    /// 总 token 配额（输入 + 输出），未设置或为 0 表示不限制
+    #[serde(default)]
+    pub token_quota: Option<u64>,
+    /// 按日 / 按月配额
+    #[serde(default)]
+    pub quotas: ApiKeyQuotas,
+    /// 自定义 Key（至少 16 个字符），未设置时自动生成
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+/// 设置托管 API Key 配额请求（整体替换，未设置或为 0 表示不限制）
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetApiKeyQuotasRequest {
+    /// 总 token 配额（输入 + 输出）
+    #[serde(default)]
+    pub token_quota: Option<u64>,
+    /// 按日 / 按月配额
+    #[serde(default)]
+    pub quotas: ApiKeyQuotas,
+}
+
+/// 创建托管 API Key 响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyResponse {
+    /// 明文 Key（仅在创建时返回一次）
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyItem,
+}
+
+// ============ Admin Key ============
+
+/// Admin Key 信息（不含明文 Key）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminKeyItem {
+    pub name: String,
+    pub role: AdminRole,
+    /// Key 前缀（用于展示辨认）
+    pub key_prefix: String,
+}
+
+/// Admin Key 列表响应（不含配置文件中的 adminApiKey）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminKeysResponse {
+    pub keys: Vec<AdminKeyItem>,
+}
+
+/// 创建 Admin Key 请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAdminKeyRequest {
+    /// 名称（唯一）
+    pub name: String,
+    /// 角色（默认 admin）
+    #[serde(default)]
+    pub role: AdminRole,
+    /// 自定义 Key（至少 16 个字符），未设置时自动生成
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+/// 创建 Admin Key 响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAdminKeyResponse {
+    /// 明文 Key（仅在创建时返回一次）
+    pub key: String,
+    #[serde(flatten)]
+    pub admin_key: AdminKeyItem,
+}
+
+// ============ Webhook ============
+
+/// 单个通知目标的测试投递结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookTestResult {
+    /// 渠道类型：webhook / telegram / slack / discord
+    pub channel: String,
+    /// Webhook 地址（聊天渠道不返回，避免泄露 Token）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub success: bool,
+    /// 成功时的 HTTP 状态码
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// 失败原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 通知测试投递响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestWebhooksResponse {
+    pub results: Vec<WebhookTestResult>,
 }
 
 // ============ 负载均衡配置 ============
@@ -179,6 +698,147 @@ pub struct SetLoadBalancingModeRequest {
     pub mode: String,
 }
 
+/// 可通过 Admin API 查看的配置子集
+///
+/// 密钥类字段不回显，仅返回是否已配置
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigResponse {
+    pub region: String,
+    pub auth_region: Option<String>,
+    pub api_region: Option<String>,
+    pub kiro_version: String,
+    pub system_version: String,
+    pub node_version: String,
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    pub has_proxy_password: bool,
+    pub count_tokens_api_url: Option<String>,
+    pub has_count_tokens_api_key: bool,
+    pub count_tokens_auth_type: String,
+    pub timezone: Option<String>,
+}
+
+/// 更新配置请求（PATCH 语义，未提供的字段保持不变）
+///
+/// 可选字段传空字符串表示清除
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateConfigRequest {
+    pub region: Option<String>,
+    pub auth_region: Option<String>,
+    pub api_region: Option<String>,
+    pub kiro_version: Option<String>,
+    pub system_version: Option<String>,
+    pub node_version: Option<String>,
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    pub count_tokens_api_url: Option<String>,
+    pub count_tokens_api_key: Option<String>,
+    pub count_tokens_auth_type: Option<String>,
+    pub timezone: Option<String>,
+}
+
+/// 更新配置响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateConfigResponse {
+    pub config: ConfigResponse,
+    /// 已保存但需要重启服务才能生效的配置项
+    pub requires_restart: Vec<String>,
+}
+
+/// 更新 Cloud Pass 配置请求（省略的字段保持不变）
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCloudPassConfigRequest {
+    /// 启用或暂停（暂停时保留配置但不刷新）
+    pub enabled: Option<bool>,
+    pub server_url: Option<String>,
+    pub refresh_interval: Option<u64>,
+    /// 心跳间隔（秒，0 表示不发送心跳）
+    pub heartbeat_interval: Option<u64>,
+    pub reassign: Option<bool>,
+    /// 主激活码（传空字符串表示清除，此时需通过 `licenses` 提供激活码）
+    pub license_code: Option<String>,
+    /// 额外的激活码（整体替换）
+    pub licenses: Option<Vec<CloudPassLicense>>,
+}
+
+/// Cloud Pass 配置（激活码脱敏）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudPassConfigResponse {
+    /// 是否已配置
+    pub configured: bool,
+    pub enabled: bool,
+    pub server_url: String,
+    pub refresh_interval: u64,
+    pub heartbeat_interval: u64,
+    pub reassign: bool,
+    pub client_version: String,
+    /// 所有激活码（脱敏，顺序与刷新任务一致）
+    pub licenses: Vec<String>,
+}
+
+/// 手动抢占 Cloud Pass 设备的查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct ClaimCloudPassQuery {
+    /// 只抢占指定激活码（完整或脱敏形式，为空时抢占所有激活码）
+    pub license: Option<String>,
+}
+
+/// 单个激活码的抢占结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimCloudPassResult {
+    /// 激活码（脱敏）
+    pub license: String,
+    pub device_id: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 手动抢占 Cloud Pass 设备响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimCloudPassResponse {
+    pub results: Vec<ClaimCloudPassResult>,
+}
+
+/// 配置热重载响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReloadConfigResponse {
+    pub success: bool,
+    pub message: String,
+    /// 已变更但需要重启服务才能生效的配置项
+    pub requires_restart: Vec<String>,
+}
+
+// ============ 登录会话 ============
+
+/// 管理面板登录请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginRequest {
+    pub admin_key: String,
+}
+
+/// 登录会话响应（会话 ID 仅通过 HttpOnly Cookie 下发）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionResponse {
+    /// CSRF Token，非只读请求需通过 `x-csrf-token` 请求头携带
+    pub csrf_token: String,
+    /// 会话过期时间（RFC3339）
+    pub expires_at: DateTime<Utc>,
+    /// 当前角色（viewer 只能查看）
+    pub role: AdminRole,
+}
+
 // ============ 通用响应 ============
 
 /// 操作成功响应
@@ -0,0 +1,243 @@
+//! 响应缓存
+//!
+//! 启用 `responseCache` 后，模型、system、消息、工具与参数完全相同的非流式请求在有效期内
+//! 直接返回缓存的响应，不再消耗上游额度。请求先规范化为 JSON（对象键按字典序排列）再取
+//! SHA-256 作为缓存键，客户端字段顺序不同不影响命中。缓存按下游托管 API Key 隔离，
+//! 不同 Key 的相同请求互不命中。只缓存成功响应，超过条目上限时淘汰最早写入的响应
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::{Body, to_bytes},
+    http::header,
+    response::Response,
+};
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use super::types::MessagesRequest;
+use crate::model::config::ResponseCacheConfig;
+
+/// 缓存键（规范化请求的 SHA-256）
+pub type CacheKey = [u8; 32];
+
+/// 缓存的响应体
+struct CachedResponse {
+    body: Bytes,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<CacheKey, CachedResponse>,
+    /// 写入顺序（用于超过上限时淘汰最早的条目）
+    order: VecDeque<CacheKey>,
+}
+
+/// 响应缓存统计
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// 当前缓存的响应数（含尚未清理的过期条目）
+    pub entries: usize,
+}
+
+/// 非流式响应缓存
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(config: &ResponseCacheConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_secs),
+            max_entries: config.max_entries,
+            entries: Mutex::new(Entries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// 计算请求的缓存键（忽略 stream 与 metadata，按托管 API Key ID 隔离）
+    pub fn key(payload: &MessagesRequest, api_key_id: Option<&str>) -> CacheKey {
+        let normalized = json!({
+            "api_key_id": api_key_id,
+            "model": payload.model,
+            "max_tokens": payload.max_tokens,
+            "system": payload.system,
+            "messages": payload.messages,
+            "tools": payload.tools,
+            "tool_choice": payload.tool_choice,
+            "thinking": payload.thinking,
+            "output_config": payload.output_config,
+            "stop_sequences": payload.stop_sequences,
+        });
+        Sha256::digest(normalized.to_string().as_bytes()).into()
+    }
+
+    /// 查找未过期的缓存响应，同时计入命中/未命中
+    pub fn get(&self, key: &CacheKey) -> Option<Response> {
+        let body = {
+            let mut entries = self.entries.lock();
+            match entries.map.get(key) {
+                Some(cached) if cached.expires_at > Instant::now() => Some(cached.body.clone()),
+                Some(_) => {
+                    entries.map.remove(key);
+                    entries.order.retain(|k| k != key);
+                    None
+                }
+                None => None,
+            }
+        };
+
+        match body {
+            Some(body) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(
+                    Response::builder()
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// 缓存成功响应的响应体并原样返回响应（失败响应不缓存）
+    pub async fn store(&self, key: CacheKey, response: Response) -> Response {
+        if !response.status().is_success() {
+            return response;
+        }
+
+        let (parts, body) = response.into_parts();
+        let body = match to_bytes(body, usize::MAX).await {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!("读取响应体失败，跳过缓存: {}", e);
+                return Response::from_parts(parts, Body::empty());
+            }
+        };
+        self.insert(key, body.clone());
+        Response::from_parts(parts, Body::from(body))
+    }
+
+    fn insert(&self, key: CacheKey, body: Bytes) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock();
+        let cached = CachedResponse {
+            body,
+            expires_at: Instant::now() + self.ttl,
+        };
+        if entries.map.insert(key, cached).is_none() {
+            entries.order.push_back(key);
+        }
+        while entries.map.len() > self.max_entries {
+            let Some(oldest) = entries.order.pop_front() else {
+                break;
+            };
+            entries.map.remove(&oldest);
+        }
+    }
+
+    pub fn stats(&self) -> ResponseCacheStats {
+        ResponseCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().map.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(body: serde_json::Value) -> MessagesRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn test_key_ignores_stream_and_field_order() {
+        let a = request(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": [{"type": "text", "text": "hi"}]}],
+        }));
+        let b = request(json!({
+            "messages": [{"content": [{"text": "hi", "type": "text"}], "role": "user"}],
+            "max_tokens": 1024,
+            "stream": true,
+            "model": "claude-sonnet-4-5",
+        }));
+        let c = request(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 2048,
+            "messages": [{"role": "user", "content": [{"type": "text", "text": "hi"}]}],
+        }));
+        assert_eq!(ResponseCache::key(&a, None), ResponseCache::key(&b, None));
+        assert_ne!(ResponseCache::key(&a, None), ResponseCache::key(&c, None));
+    }
+
+    #[test]
+    fn test_key_is_scoped_to_api_key() {
+        let payload = request(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": "hi"}],
+        }));
+        assert_eq!(
+            ResponseCache::key(&payload, Some("key-a")),
+            ResponseCache::key(&payload, Some("key-a"))
+        );
+        assert_ne!(
+            ResponseCache::key(&payload, Some("key-a")),
+            ResponseCache::key(&payload, Some("key-b"))
+        );
+        assert_ne!(
+            ResponseCache::key(&payload, Some("key-a")),
+            ResponseCache::key(&payload, None)
+        );
+    }
+
+    #[test]
+    fn test_evicts_oldest_and_counts_hits() {
+        let cache = ResponseCache::new(&ResponseCacheConfig {
+            ttl_secs: 300,
+            max_entries: 2,
+        });
+        cache.insert([1; 32], Bytes::from_static(b"1"));
+        cache.insert([2; 32], Bytes::from_static(b"2"));
+        cache.insert([3; 32], Bytes::from_static(b"3"));
+
+        assert!(cache.get(&[1; 32]).is_none());
+        assert!(cache.get(&[3; 32]).is_some());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 2));
+
+        // 过期条目视为未命中并被清理
+        let cache = ResponseCache::new(&ResponseCacheConfig {
+            ttl_secs: 0,
+            max_entries: 2,
+        });
+        cache.insert([1; 32], Bytes::from_static(b"1"));
+        assert!(cache.get(&[1; 32]).is_none());
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
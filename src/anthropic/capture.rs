@@ -0,0 +1,264 @@
+//! 请求捕获（调试）
+//!
+//! 通过 Admin API 开启后，接下来的 N 个 `/v1/messages` 与 `/cc/v1/messages` 请求各写入一个目录：
+//! - `meta.json`：请求 ID、端点、模型、thinking、输入 token 估算与脱敏后的请求头
+//! - `kiro-request.json`：发往上游的 Kiro 请求体
+//! - `upstream.bin`：上游返回的原始 AWS Event Stream 字节（流中断续传时依次追加）
+//! - `downstream.sse` / `downstream.json`：转换后发往客户端的响应体
+//!
+//! 协议转换问题可据此离线复现（`kiro-rs replay <目录>`），无需再次请求上游
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+
+use anyhow::Context;
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::common::request_id;
+
+/// 单次开启最多捕获的请求数
+pub const MAX_CAPTURE_COUNT: u32 = 100;
+/// 状态中保留的最近捕获目录数
+const RECENT_CAPTURES: usize = 20;
+/// 上游原始字节文件名
+pub const UPSTREAM_FILE: &str = "upstream.bin";
+/// 元数据文件名
+pub const META_FILE: &str = "meta.json";
+/// 脱敏后的替换文本
+const REDACTED: &str = "[REDACTED]";
+
+/// 捕获元数据（`meta.json`）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureMeta {
+    pub request_id: Option<String>,
+    pub captured_at: DateTime<Utc>,
+    pub endpoint: String,
+    pub model: String,
+    pub stream: bool,
+    pub thinking_enabled: bool,
+    pub input_tokens: i32,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+/// 捕获状态（Admin API 展示）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureStatus {
+    pub armed: bool,
+    pub remaining: u32,
+    pub dir: Option<String>,
+    /// 最近的捕获目录（新的在前）
+    pub recent: Vec<String>,
+}
+
+struct Armed {
+    dir: PathBuf,
+    remaining: u32,
+}
+
+/// 捕获开关
+#[derive(Default)]
+pub struct CaptureController {
+    armed: Mutex<Option<Armed>>,
+    recent: Mutex<VecDeque<String>>,
+}
+
+impl CaptureController {
+    /// 开启捕获：接下来的 `count` 个请求写入 `dir`（目录不存在时自动创建）
+    pub fn arm(&self, dir: PathBuf, count: u32) -> anyhow::Result<()> {
+        fs::create_dir_all(&dir).with_context(|| format!("创建捕获目录失败: {}", dir.display()))?;
+        tracing::info!(
+            "已开启请求捕获：接下来 {} 个请求写入 {}",
+            count,
+            dir.display()
+        );
+        *self.armed.lock() = Some(Armed {
+            dir,
+            remaining: count,
+        });
+        Ok(())
+    }
+
+    /// 关闭捕获（进行中的捕获不受影响）
+    pub fn disarm(&self) {
+        if self.armed.lock().take().is_some() {
+            tracing::info!("已关闭请求捕获");
+        }
+    }
+
+    pub fn status(&self) -> CaptureStatus {
+        let armed = self.armed.lock();
+        CaptureStatus {
+            armed: armed.is_some(),
+            remaining: armed.as_ref().map_or(0, |a| a.remaining),
+            dir: armed.as_ref().map(|a| a.dir.display().to_string()),
+            recent: self.recent.lock().iter().cloned().collect(),
+        }
+    }
+
+    /// 占用一个捕获名额，名额用完后自动关闭
+    fn take(&self) -> Option<PathBuf> {
+        let mut guard = self.armed.lock();
+        let armed = guard.as_mut()?;
+        armed.remaining = armed.remaining.saturating_sub(1);
+        let dir = armed.dir.clone();
+        if armed.remaining == 0 {
+            *guard = None;
+            tracing::info!("请求捕获名额已用完，自动关闭");
+        }
+        Some(dir)
+    }
+
+    fn push_recent(&self, path: &Path) {
+        let mut recent = self.recent.lock();
+        recent.push_front(path.display().to_string());
+        recent.truncate(RECENT_CAPTURES);
+    }
+}
+
+/// 全局捕获开关（Admin API 与请求处理共享）
+pub fn controller() -> &'static CaptureController {
+    static CONTROLLER: LazyLock<CaptureController> = LazyLock::new(CaptureController::default);
+    &CONTROLLER
+}
+
+/// 单次请求的捕获
+///
+/// 文件写入失败时只记录日志并停止写入对应文件，不影响请求处理
+pub struct CaptureSession {
+    upstream: Mutex<Option<File>>,
+    downstream: Mutex<Option<File>>,
+}
+
+impl CaptureSession {
+    /// 追加一段上游原始字节
+    pub fn upstream(&self, chunk: &[u8]) {
+        append(&self.upstream, chunk);
+    }
+
+    /// 追加一段发往客户端的响应字节
+    pub fn downstream(&self, chunk: &[u8]) {
+        append(&self.downstream, chunk);
+    }
+}
+
+fn append(file: &Mutex<Option<File>>, chunk: &[u8]) {
+    let mut guard = file.lock();
+    if let Some(f) = guard.as_mut()
+        && let Err(e) = f.write_all(chunk)
+    {
+        tracing::warn!("写入请求捕获失败: {}", e);
+        *guard = None;
+    }
+}
+
+/// 捕获开启时为本次请求创建捕获目录并写入元数据与 Kiro 请求体
+pub fn begin(
+    headers: &HeaderMap,
+    mut meta: CaptureMeta,
+    kiro_request: &str,
+) -> Option<Arc<CaptureSession>> {
+    let dir = controller().take()?;
+    meta.headers = redact_headers(headers);
+    meta.request_id = request_id::current();
+    let stamp = meta.captured_at.format("%Y%m%d-%H%M%S%.3f");
+    let suffix = meta
+        .request_id
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+    let path = dir.join(format!("{}-{}", stamp, suffix));
+
+    match create_session(&path, &meta, kiro_request) {
+        Ok(session) => {
+            tracing::info!("捕获本次请求到 {}", path.display());
+            controller().push_recent(&path);
+            Some(Arc::new(session))
+        }
+        Err(e) => {
+            tracing::warn!("创建请求捕获失败: {:#}", e);
+            None
+        }
+    }
+}
+
+fn create_session(
+    path: &Path,
+    meta: &CaptureMeta,
+    kiro_request: &str,
+) -> anyhow::Result<CaptureSession> {
+    fs::create_dir_all(path)?;
+    fs::write(path.join(META_FILE), serde_json::to_vec_pretty(meta)?)?;
+    fs::write(path.join("kiro-request.json"), kiro_request)?;
+    let downstream = if meta.stream {
+        "downstream.sse"
+    } else {
+        "downstream.json"
+    };
+    Ok(CaptureSession {
+        upstream: Mutex::new(Some(File::create(path.join(UPSTREAM_FILE))?)),
+        downstream: Mutex::new(Some(File::create(path.join(downstream))?)),
+    })
+}
+
+/// 请求头脱敏：认证相关的头只保留名称
+fn redact_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = name.as_str().to_string();
+            let value = if is_sensitive_header(&name) {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+fn is_sensitive_header(name: &str) -> bool {
+    ["auth", "key", "token", "secret", "cookie", "session"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "sk-secret".parse().unwrap());
+        headers.insert("authorization", "Bearer sk-secret".parse().unwrap());
+        headers.insert("cookie", "sid=1".parse().unwrap());
+        headers.insert("anthropic-version", "2023-06-01".parse().unwrap());
+
+        let redacted = redact_headers(&headers);
+        assert_eq!(redacted["x-api-key"], REDACTED);
+        assert_eq!(redacted["authorization"], REDACTED);
+        assert_eq!(redacted["cookie"], REDACTED);
+        assert_eq!(redacted["anthropic-version"], "2023-06-01");
+    }
+
+    #[test]
+    fn test_capture_count_auto_disarms() {
+        let controller = CaptureController::default();
+        let dir = std::env::temp_dir().join(format!("kiro-capture-{}", uuid::Uuid::new_v4()));
+        controller.arm(dir.clone(), 2).unwrap();
+        assert_eq!(controller.take(), Some(dir.clone()));
+        assert_eq!(controller.status().remaining, 1);
+        assert_eq!(controller.take(), Some(dir.clone()));
+        assert!(!controller.status().armed);
+        assert_eq!(controller.take(), None);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
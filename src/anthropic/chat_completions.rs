@@ -0,0 +1,686 @@
+//! OpenAI Chat Completions（`/v1/chat/completions`）兼容
+//!
+//! 将 OpenAI 的 `messages` / `tools` / `tool_calls` / `tool` 角色消息转换为 Anthropic 的
+//! `tool_use` / `tool_result` 内容块交给 `/v1/messages` 的处理逻辑，再把响应转换回
+//! `chat.completion` 格式。流式响应中工具参数以 `tool_calls[].function.arguments`
+//! 增量输出，与 OpenAI 一致；thinking 内容以 `reasoning_content` 输出。
+//! `n`、`logprobs`、`temperature` 等采样相关参数不受支持，会被忽略
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    Json as JsonExtractor,
+    body::Body,
+    extract::{Extension, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use crate::audit::AuditTracker;
+
+use super::completions::{Stop, next_event_end};
+use super::handlers::post_messages;
+use super::image::image_url_block;
+use super::middleware::{AppState, ManagedApiKey};
+use super::types::{ErrorResponse, Message, MessagesRequest, SystemMessage, Tool};
+use super::validation::ValidatedJson;
+
+/// 未指定 `max_tokens` / `max_completion_tokens` 时的默认输出上限
+const DEFAULT_MAX_TOKENS: i32 = 8192;
+
+/// 非流式响应体读取上限
+const MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+/// 对话消息
+#[derive(Debug, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    /// 字符串或内容片段数组（assistant 仅调用工具时可为 null）
+    #[serde(default)]
+    pub content: Value,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    /// `tool` 角色消息对应的调用 ID
+    pub tool_call_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// JSON 字符串形式的参数
+    #[serde(default)]
+    pub arguments: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatTool {
+    pub function: FunctionDefinition,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub parameters: Option<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StreamOptions {
+    #[serde(default)]
+    pub include_usage: bool,
+}
+
+/// Chat Completions 请求
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub max_tokens: Option<i32>,
+    pub max_completion_tokens: Option<i32>,
+    #[serde(default)]
+    pub stream: bool,
+    pub stream_options: Option<StreamOptions>,
+    #[serde(default)]
+    pub tools: Vec<ChatTool>,
+    pub tool_choice: Option<Value>,
+    /// 停止序列（字符串或字符串数组），由代理在本地匹配
+    pub stop: Option<Stop>,
+}
+
+/// 将 OpenAI 内容片段转换为 Anthropic 内容块（仅支持文本与图片）
+fn convert_content(content: Value) -> Result<Vec<Value>, String> {
+    let parts = match content {
+        Value::Null => return Ok(Vec::new()),
+        Value::String(text) => return Ok(text_block(text).into_iter().collect()),
+        Value::Array(parts) => parts,
+        _ => return Err("content 必须是字符串或数组".to_string()),
+    };
+
+    let mut blocks = Vec::new();
+    for part in parts {
+        match part["type"].as_str() {
+            Some("text") => {
+                let text = part["text"].as_str().unwrap_or_default().to_string();
+                blocks.extend(text_block(text));
+            }
+            Some("image_url") => {
+                let url = part["image_url"]["url"].as_str().unwrap_or_default();
+                blocks.push(image_url_block(url));
+            }
+            other => return Err(format!("不支持的内容类型: {}", other.unwrap_or("null"))),
+        }
+    }
+    Ok(blocks)
+}
+
+fn text_block(text: String) -> Option<Value> {
+    (!text.is_empty()).then(|| json!({"type": "text", "text": text}))
+}
+
+/// 内容的纯文本形式（用于 system 消息与工具结果）
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// OpenAI tool_choice 映射为 Anthropic tool_choice（`auto` 为默认行为，不传）
+fn tool_choice(choice: &Value) -> Option<Value> {
+    match choice {
+        Value::String(mode) => match mode.as_str() {
+            "none" => Some(json!({"type": "none"})),
+            "required" => Some(json!({"type": "any"})),
+            _ => None,
+        },
+        Value::Object(_) => choice["function"]["name"]
+            .as_str()
+            .map(|name| json!({"type": "tool", "name": name})),
+        _ => None,
+    }
+}
+
+impl ChatCompletionRequest {
+    fn include_usage(&self) -> bool {
+        self.stream_options
+            .as_ref()
+            .is_some_and(|o| o.include_usage)
+    }
+
+    /// 转换为 Messages 请求
+    fn into_messages_request(self) -> Result<MessagesRequest, String> {
+        let mut system = Vec::new();
+        let mut messages: Vec<Message> = Vec::new();
+        // 连续的 tool 消息需要合并到同一条 user 消息中
+        let mut last_was_tool = false;
+
+        for message in self.messages {
+            let is_tool = message.role == "tool";
+            match message.role.as_str() {
+                "system" | "developer" => {
+                    let text = content_text(&message.content);
+                    if !text.is_empty() {
+                        system.push(SystemMessage {
+                            text,
+                            cache_control: None,
+                        });
+                    }
+                }
+                "user" => {
+                    let blocks = convert_content(message.content)?;
+                    if !blocks.is_empty() {
+                        messages.push(Message {
+                            role: "user".to_string(),
+                            content: Value::Array(blocks),
+                        });
+                    }
+                }
+                "assistant" => {
+                    let mut blocks = convert_content(message.content)?;
+                    for call in message.tool_calls {
+                        let input: Value = if call.function.arguments.trim().is_empty() {
+                            json!({})
+                        } else {
+                            serde_json::from_str(&call.function.arguments).map_err(|e| {
+                                format!("tool_calls {} 的 arguments 不是合法 JSON: {}", call.id, e)
+                            })?
+                        };
+                        blocks.push(json!({
+                            "type": "tool_use",
+                            "id": call.id,
+                            "name": call.function.name,
+                            "input": input,
+                        }));
+                    }
+                    if !blocks.is_empty() {
+                        messages.push(Message {
+                            role: "assistant".to_string(),
+                            content: Value::Array(blocks),
+                        });
+                    }
+                }
+                "tool" => {
+                    let id = message.tool_call_id.ok_or("tool 消息缺少 tool_call_id")?;
+                    let block = json!({
+                        "type": "tool_result",
+                        "tool_use_id": id,
+                        "content": content_text(&message.content),
+                    });
+                    match messages.last_mut() {
+                        Some(Message {
+                            content: Value::Array(blocks),
+                            ..
+                        }) if last_was_tool => blocks.push(block),
+                        _ => messages.push(Message {
+                            role: "user".to_string(),
+                            content: Value::Array(vec![block]),
+                        }),
+                    }
+                }
+                other => return Err(format!("不支持的消息角色: {}", other)),
+            }
+            last_was_tool = is_tool;
+        }
+        if messages.is_empty() {
+            return Err("messages 不能为空".to_string());
+        }
+
+        let tools: Vec<Tool> = self
+            .tools
+            .into_iter()
+            .map(|tool| Tool {
+                tool_type: None,
+                name: tool.function.name,
+                description: tool.function.description,
+                input_schema: match tool.function.parameters {
+                    Some(Value::Object(map)) => map.into_iter().collect(),
+                    _ => HashMap::from([
+                        ("type".to_string(), json!("object")),
+                        ("properties".to_string(), json!({})),
+                    ]),
+                },
+                max_uses: None,
+                cache_control: None,
+            })
+            .collect();
+
+        Ok(MessagesRequest {
+            model: self.model,
+            max_tokens: self
+                .max_completion_tokens
+                .or(self.max_tokens)
+                .unwrap_or(DEFAULT_MAX_TOKENS),
+            messages,
+            stream: self.stream,
+            system: (!system.is_empty()).then_some(system),
+            tools: (!tools.is_empty()).then_some(tools),
+            tool_choice: self.tool_choice.as_ref().and_then(tool_choice),
+            thinking: None,
+            output_config: None,
+            stop_sequences: Stop::into_stop_sequences(self.stop),
+            metadata: None,
+        })
+    }
+}
+
+/// Anthropic stop_reason 映射为 OpenAI finish_reason
+fn finish_reason(stop_reason: &str) -> &'static str {
+    match stop_reason {
+        "tool_use" => "tool_calls",
+        "max_tokens" | "model_context_window_exceeded" => "length",
+        "refusal" => "content_filter",
+        _ => "stop",
+    }
+}
+
+fn usage(input_tokens: i64, output_tokens: i64) -> Value {
+    json!({
+        "prompt_tokens": input_tokens,
+        "completion_tokens": output_tokens,
+        "total_tokens": input_tokens + output_tokens,
+    })
+}
+
+/// 对话补全响应的公共字段
+struct ChatMeta {
+    id: String,
+    created: i64,
+    model: String,
+}
+
+impl ChatMeta {
+    fn new(model: &str) -> Self {
+        Self {
+            id: format!("chatcmpl-{}", Uuid::new_v4().simple()),
+            created: chrono::Utc::now().timestamp(),
+            model: model.to_string(),
+        }
+    }
+
+    /// 将非流式 Messages 响应转换为 chat.completion 对象
+    fn completion(&self, message: &Value) -> Value {
+        let blocks = message["content"].as_array().into_iter().flatten();
+        let text: String = blocks
+            .clone()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        let reasoning: String = blocks
+            .clone()
+            .filter(|block| block["type"] == "thinking")
+            .filter_map(|block| block["thinking"].as_str())
+            .collect();
+        let tool_calls: Vec<Value> = blocks
+            .filter(|block| block["type"] == "tool_use")
+            .map(|block| {
+                json!({
+                    "id": block["id"],
+                    "type": "function",
+                    "function": {"name": block["name"], "arguments": block["input"].to_string()},
+                })
+            })
+            .collect();
+
+        let mut reply = json!({
+            "role": "assistant",
+            "content": (!text.is_empty()).then_some(text),
+        });
+        if !reasoning.is_empty() {
+            reply["reasoning_content"] = Value::String(reasoning);
+        }
+        if !tool_calls.is_empty() {
+            reply["tool_calls"] = Value::Array(tool_calls);
+        }
+
+        let mut completion = json!({
+            "id": self.id,
+            "object": "chat.completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "message": reply,
+                "logprobs": null,
+                "finish_reason": message["stop_reason"].as_str().map(finish_reason),
+            }],
+            "usage": usage(
+                message["usage"]["input_tokens"].as_i64().unwrap_or(0),
+                message["usage"]["output_tokens"].as_i64().unwrap_or(0),
+            ),
+        });
+        // 保留 x-kiro-meta 请求的元数据回显
+        if let Some(meta) = message.get("kiro_meta") {
+            completion["kiro_meta"] = meta.clone();
+        }
+        completion
+    }
+
+    fn chunk(&self, delta: Value, finish_reason: Option<&str>) -> Value {
+        json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "delta": delta,
+                "logprobs": null,
+                "finish_reason": finish_reason,
+            }],
+        })
+    }
+}
+
+/// Anthropic SSE 事件到 OpenAI 流式块的转换状态
+struct ChatStream {
+    meta: ChatMeta,
+    include_usage: bool,
+    input_tokens: i64,
+    /// Anthropic 内容块索引 -> tool_calls 序号
+    tool_indices: HashMap<u64, usize>,
+}
+
+impl ChatStream {
+    fn new(meta: ChatMeta, include_usage: bool) -> Self {
+        Self {
+            meta,
+            include_usage,
+            input_tokens: 0,
+            tool_indices: HashMap::new(),
+        }
+    }
+
+    fn data(value: &Value) -> String {
+        format!("data: {}\n\n", value)
+    }
+
+    /// 将一个 Anthropic SSE 事件（含结尾空行）转换为 OpenAI 流式块
+    ///
+    /// thinking 增量输出为 `reasoning_content`，ping 等其他事件被丢弃
+    fn translate_event(&mut self, event: &str) -> String {
+        let Some(data) = event.lines().find_map(|line| line.strip_prefix("data: ")) else {
+            return String::new();
+        };
+        let Ok(data) = serde_json::from_str::<Value>(data) else {
+            return String::new();
+        };
+        let block_index = data["index"].as_u64().unwrap_or(0);
+
+        match data["type"].as_str() {
+            Some("message_start") => {
+                self.input_tokens = data["message"]["usage"]["input_tokens"]
+                    .as_i64()
+                    .unwrap_or(0);
+                let delta = json!({"role": "assistant", "content": ""});
+                Self::data(&self.meta.chunk(delta, None))
+            }
+            Some("content_block_start") if data["content_block"]["type"] == "tool_use" => {
+                let index = self.tool_indices.len();
+                self.tool_indices.insert(block_index, index);
+                let block = &data["content_block"];
+                let delta = json!({"tool_calls": [{
+                    "index": index,
+                    "id": block["id"],
+                    "type": "function",
+                    "function": {"name": block["name"], "arguments": ""},
+                }]});
+                Self::data(&self.meta.chunk(delta, None))
+            }
+            Some("content_block_delta") => match data["delta"]["type"].as_str() {
+                Some("text_delta") => {
+                    let delta = json!({"content": data["delta"]["text"]});
+                    Self::data(&self.meta.chunk(delta, None))
+                }
+                Some("thinking_delta") if data["delta"]["thinking"] != "" => {
+                    let delta = json!({"reasoning_content": data["delta"]["thinking"]});
+                    Self::data(&self.meta.chunk(delta, None))
+                }
+                Some("input_json_delta") => match self.tool_indices.get(&block_index) {
+                    Some(index) => {
+                        let delta = json!({"tool_calls": [{
+                            "index": index,
+                            "function": {"arguments": data["delta"]["partial_json"]},
+                        }]});
+                        Self::data(&self.meta.chunk(delta, None))
+                    }
+                    None => String::new(),
+                },
+                _ => String::new(),
+            },
+            Some("message_delta") => {
+                let reason = data["delta"]["stop_reason"].as_str().map(finish_reason);
+                let mut chunk = self.meta.chunk(json!({}), reason);
+                if let Some(meta) = data.get("kiro_meta") {
+                    chunk["kiro_meta"] = meta.clone();
+                }
+                let mut out = Self::data(&chunk);
+                if self.include_usage {
+                    let output_tokens = data["usage"]["output_tokens"].as_i64().unwrap_or(0);
+                    let mut usage_chunk = self.meta.chunk(json!({}), None);
+                    usage_chunk["choices"] = json!([]);
+                    usage_chunk["usage"] = usage(self.input_tokens, output_tokens);
+                    out.push_str(&Self::data(&usage_chunk));
+                }
+                out
+            }
+            Some("message_stop") => "data: [DONE]\n\n".to_string(),
+            Some("error") => Self::data(&json!({ "error": data["error"] })),
+            _ => String::new(),
+        }
+    }
+}
+
+/// 将 Anthropic SSE 响应体转换为 OpenAI 对话补全流
+fn chat_stream(body: Body, mut translator: ChatStream) -> Body {
+    let mut buffer: Vec<u8> = Vec::new();
+    let stream = body.into_data_stream().map(move |chunk| {
+        let chunk = chunk?;
+        buffer.extend_from_slice(&chunk);
+        let mut out = String::new();
+        while let Some(end) = next_event_end(&buffer) {
+            let event: Vec<u8> = buffer.drain(..end).collect();
+            out.push_str(&translator.translate_event(&String::from_utf8_lossy(&event)));
+        }
+        Ok::<_, axum::Error>(Bytes::from(out))
+    });
+    Body::from_stream(stream)
+}
+
+/// POST /v1/chat/completions
+///
+/// OpenAI 对话补全接口（含 function calling），内部按 `/v1/messages` 处理后转换响应格式
+pub async fn post_chat_completions(
+    state: State<AppState>,
+    managed_key: Option<Extension<ManagedApiKey>>,
+    audit: Option<Extension<Arc<AuditTracker>>>,
+    headers: HeaderMap,
+    JsonExtractor(payload): JsonExtractor<ChatCompletionRequest>,
+) -> Response {
+    let meta = ChatMeta::new(&payload.model);
+    let include_usage = payload.include_usage();
+    let request = match payload.into_messages_request() {
+        Ok(request) => request,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new("invalid_request_error", message)),
+            )
+                .into_response();
+        }
+    };
+    let stream = request.stream;
+
+    let response = post_messages(state, managed_key, audit, headers, ValidatedJson(request)).await;
+    // 错误响应原样返回
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    if stream {
+        let translator = ChatStream::new(meta, include_usage);
+        let mut response = Response::new(chat_stream(body, translator));
+        *response.status_mut() = parts.status;
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, "text/event-stream".parse().unwrap());
+        headers.insert(header::CACHE_CONTROL, "no-cache".parse().unwrap());
+        return response;
+    }
+
+    let message = match axum::body::to_bytes(body, MAX_RESPONSE_BYTES).await {
+        Ok(bytes) => serde_json::from_slice::<Value>(&bytes).unwrap_or_default(),
+        Err(e) => {
+            tracing::error!("读取对话补全响应失败: {}", e);
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(ErrorResponse::new(
+                    "api_error",
+                    format!("读取上游响应失败: {}", e),
+                )),
+            )
+                .into_response();
+        }
+    };
+    Json(meta.completion(&message)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta() -> ChatMeta {
+        ChatMeta {
+            id: "chatcmpl-test".to_string(),
+            created: 1,
+            model: "claude-sonnet-4-5".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_tool_call_round_trip_request() {
+        let request: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [
+                {"role": "system", "content": "be brief"},
+                {"role": "user", "content": "weather in SF and NYC?"},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function",
+                     "function": {"name": "get_weather", "arguments": "{\"city\":\"SF\"}"}},
+                    {"id": "call_2", "type": "function",
+                     "function": {"name": "get_weather", "arguments": "{\"city\":\"NYC\"}"}},
+                ]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "20C"},
+                {"role": "tool", "tool_call_id": "call_2", "content": "25C"},
+            ],
+            "tools": [{"type": "function", "function": {
+                "name": "get_weather",
+                "parameters": {"type": "object", "properties": {"city": {"type": "string"}}},
+            }}],
+            "tool_choice": "required",
+            "stop": ["Observation:"],
+        }))
+        .unwrap();
+        let messages = request.into_messages_request().unwrap();
+
+        assert_eq!(messages.max_tokens, DEFAULT_MAX_TOKENS);
+        assert_eq!(
+            messages.stop_sequences,
+            Some(vec!["Observation:".to_string()])
+        );
+        assert_eq!(messages.system.unwrap()[0].text, "be brief");
+        assert_eq!(messages.tool_choice, Some(json!({"type": "any"})));
+        assert_eq!(messages.tools.unwrap()[0].input_schema["type"], "object");
+
+        let roles: Vec<_> = messages.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        let calls = &messages.messages[1].content;
+        assert_eq!(calls[1]["type"], "tool_use");
+        assert_eq!(calls[1]["input"]["city"], "NYC");
+        // 连续的 tool 消息合并为一条 user 消息
+        let results = messages.messages[2].content.as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1]["tool_use_id"], "call_2");
+        assert_eq!(results[1]["content"], "25C");
+    }
+
+    #[test]
+    fn test_non_stream_tool_call_response() {
+        let completion = meta().completion(&json!({
+            "content": [
+                {"type": "thinking", "thinking": "need weather", "signature": ""},
+                {"type": "text", "text": "Checking"},
+                {"type": "tool_use", "id": "toolu_1", "name": "f", "input": {"a": 1}},
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 5, "output_tokens": 7},
+        }));
+        let choice = &completion["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert_eq!(choice["message"]["content"], "Checking");
+        assert_eq!(choice["message"]["reasoning_content"], "need weather");
+        let call = &choice["message"]["tool_calls"][0];
+        assert_eq!(call["id"], "toolu_1");
+        assert_eq!(call["function"]["arguments"], "{\"a\":1}");
+        assert_eq!(completion["usage"]["total_tokens"], 12);
+    }
+
+    #[test]
+    fn test_stream_tool_call_deltas() {
+        let mut stream = ChatStream::new(meta(), true);
+        let events = [
+            json!({"type": "message_start", "message": {"usage": {"input_tokens": 3}}}),
+            json!({"type": "content_block_start", "index": 1,
+                   "content_block": {"type": "tool_use", "id": "toolu_1", "name": "f"}}),
+            json!({"type": "content_block_delta", "index": 1,
+                   "delta": {"type": "input_json_delta", "partial_json": "{\"a\":"}}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"},
+                   "usage": {"output_tokens": 4}}),
+            json!({"type": "message_stop"}),
+        ];
+        let chunks: Vec<String> = events
+            .iter()
+            .map(|e| stream.translate_event(&format!("event: x\ndata: {}\n\n", e)))
+            .collect();
+        let parse = |s: &str| -> Vec<Value> {
+            s.split("\n\n")
+                .filter_map(|line| line.strip_prefix("data: "))
+                .map(|data| serde_json::from_str(data).unwrap())
+                .collect()
+        };
+
+        assert_eq!(
+            parse(&chunks[0])[0]["choices"][0]["delta"]["role"],
+            "assistant"
+        );
+        let start = &parse(&chunks[1])[0]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(
+            (start["index"].as_u64(), &start["id"]),
+            (Some(0), &json!("toolu_1"))
+        );
+        let args = &parse(&chunks[2])[0]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(args["function"]["arguments"], "{\"a\":");
+
+        let finish = parse(&chunks[3]);
+        assert_eq!(finish[0]["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(finish[1]["usage"]["total_tokens"], 7);
+        assert_eq!(chunks[4], "data: [DONE]\n\n");
+    }
+}
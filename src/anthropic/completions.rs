@@ -0,0 +1,407 @@
+//! OpenAI 旧版文本补全（`/v1/completions`）兼容
+//!
+//! 部分老工具（llama.cpp 基准脚本、一些 IDE 插件）只支持 `prompt` 形式的文本补全接口。
+//! 这里将 `prompt` 包装为单轮用户消息交给 `/v1/messages` 的处理逻辑，
+//! 再把 Anthropic 响应（JSON 或 SSE）转换为 `text_completion` 格式。
+//! `n`、`logprobs`、`echo`、`suffix` 等采样相关参数不受支持，会被忽略
+
+use std::sync::Arc;
+
+use axum::{
+    Json as JsonExtractor,
+    body::Body,
+    extract::{Extension, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use crate::audit::AuditTracker;
+
+use super::handlers::post_messages;
+use super::middleware::{AppState, ManagedApiKey};
+use super::types::{ErrorResponse, Message, MessagesRequest};
+use super::validation::ValidatedJson;
+
+/// 非流式响应体读取上限
+const MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+/// 文本补全请求
+#[derive(Debug, Deserialize)]
+pub struct CompletionRequest {
+    pub model: String,
+    /// 字符串，或仅包含一个字符串的数组
+    pub prompt: Value,
+    /// 与 OpenAI 一致，默认 16
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: i32,
+    #[serde(default)]
+    pub stream: bool,
+    /// 停止序列（字符串或字符串数组），由代理在本地匹配
+    pub stop: Option<Stop>,
+}
+
+fn default_max_tokens() -> i32 {
+    16
+}
+
+/// OpenAI `stop` 参数：单个字符串或字符串数组
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Stop {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Stop {
+    /// 转换为 Messages 请求的 stop_sequences（忽略空字符串，全部为空时为 None）
+    pub fn into_stop_sequences(stop: Option<Stop>) -> Option<Vec<String>> {
+        let sequences: Vec<String> = match stop? {
+            Stop::One(sequence) => vec![sequence],
+            Stop::Many(sequences) => sequences,
+        };
+        let sequences: Vec<String> = sequences.into_iter().filter(|s| !s.is_empty()).collect();
+        (!sequences.is_empty()).then_some(sequences)
+    }
+}
+
+impl CompletionRequest {
+    /// 取出单个 prompt 文本，不支持批量 prompt 与 token 数组
+    fn prompt_text(&self) -> Result<String, String> {
+        match &self.prompt {
+            Value::String(s) => Ok(s.clone()),
+            Value::Array(items) => match items.as_slice() {
+                [Value::String(s)] => Ok(s.clone()),
+                [] => Err("prompt 不能为空".to_string()),
+                [_] => Err("不支持 token 数组形式的 prompt".to_string()),
+                _ => Err("仅支持单个 prompt".to_string()),
+            },
+            _ => Err("prompt 必须是字符串".to_string()),
+        }
+    }
+
+    /// 转换为单轮用户消息的 Messages 请求
+    fn into_messages_request(self) -> Result<MessagesRequest, String> {
+        let prompt = self.prompt_text()?;
+        if prompt.is_empty() {
+            return Err("prompt 不能为空".to_string());
+        }
+        Ok(MessagesRequest {
+            model: self.model,
+            max_tokens: self.max_tokens,
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: Value::String(prompt),
+            }],
+            stream: self.stream,
+            system: None,
+            tools: None,
+            tool_choice: None,
+            thinking: None,
+            output_config: None,
+            stop_sequences: Stop::into_stop_sequences(self.stop),
+            metadata: None,
+        })
+    }
+}
+
+/// Anthropic stop_reason 映射为 OpenAI finish_reason
+fn finish_reason(stop_reason: &str) -> &'static str {
+    match stop_reason {
+        "max_tokens" | "model_context_window_exceeded" => "length",
+        "refusal" => "content_filter",
+        _ => "stop",
+    }
+}
+
+/// 补全响应的公共字段
+struct CompletionMeta {
+    id: String,
+    created: i64,
+    model: String,
+}
+
+impl CompletionMeta {
+    fn new(model: &str) -> Self {
+        Self {
+            id: format!("cmpl-{}", Uuid::new_v4().simple()),
+            created: chrono::Utc::now().timestamp(),
+            model: model.to_string(),
+        }
+    }
+
+    /// 将非流式 Messages 响应转换为 text_completion 对象
+    fn completion(&self, message: &Value) -> Value {
+        let text: String = message["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        let finish = message["stop_reason"].as_str().map(finish_reason);
+        let prompt_tokens = message["usage"]["input_tokens"].as_i64().unwrap_or(0);
+        let completion_tokens = message["usage"]["output_tokens"].as_i64().unwrap_or(0);
+
+        let mut completion = json!({
+            "id": self.id,
+            "object": "text_completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "text": text,
+                "index": 0,
+                "logprobs": null,
+                "finish_reason": finish,
+            }],
+            "usage": {
+                "prompt_tokens": prompt_tokens,
+                "completion_tokens": completion_tokens,
+                "total_tokens": prompt_tokens + completion_tokens,
+            },
+        });
+        // 保留 x-kiro-meta 请求的元数据回显
+        if let Some(meta) = message.get("kiro_meta") {
+            completion["kiro_meta"] = meta.clone();
+        }
+        completion
+    }
+
+    fn chunk(&self, text: &str, finish_reason: Option<&str>) -> String {
+        self.chunk_with_meta(text, finish_reason, None)
+    }
+
+    fn chunk_with_meta(
+        &self,
+        text: &str,
+        finish_reason: Option<&str>,
+        meta: Option<&Value>,
+    ) -> String {
+        let mut chunk = json!({
+            "id": self.id,
+            "object": "text_completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "text": text,
+                "index": 0,
+                "logprobs": null,
+                "finish_reason": finish_reason,
+            }],
+        });
+        if let Some(meta) = meta {
+            chunk["kiro_meta"] = meta.clone();
+        }
+        format!("data: {}\n\n", chunk)
+    }
+
+    /// 将一个 Anthropic SSE 事件（含结尾空行）转换为 OpenAI 流式块
+    ///
+    /// 只转发文本增量；thinking、ping 等其他事件被丢弃
+    fn translate_event(&self, event: &str) -> String {
+        let Some(data) = event.lines().find_map(|line| line.strip_prefix("data: ")) else {
+            return String::new();
+        };
+        let Ok(data) = serde_json::from_str::<Value>(data) else {
+            return String::new();
+        };
+
+        match data["type"].as_str() {
+            Some("content_block_delta") if data["delta"]["type"] == "text_delta" => {
+                data["delta"]["text"]
+                    .as_str()
+                    .map(|text| self.chunk(text, None))
+                    .unwrap_or_default()
+            }
+            Some("message_delta") => data["delta"]["stop_reason"]
+                .as_str()
+                .map(|reason| {
+                    let meta = data.get("kiro_meta");
+                    self.chunk_with_meta("", Some(finish_reason(reason)), meta)
+                })
+                .unwrap_or_default(),
+            Some("message_stop") => "data: [DONE]\n\n".to_string(),
+            Some("error") => format!("data: {}\n\n", json!({ "error": data["error"] })),
+            _ => String::new(),
+        }
+    }
+}
+
+/// 在缓冲区中查找完整 SSE 事件的结束位置（包含 `\n\n`）
+pub(super) fn next_event_end(buffer: &[u8]) -> Option<usize> {
+    buffer
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .map(|pos| pos + 2)
+}
+
+/// 将 Anthropic SSE 响应体转换为 OpenAI 文本补全流
+fn completion_stream(body: Body, meta: CompletionMeta) -> Body {
+    let mut buffer: Vec<u8> = Vec::new();
+    let stream = body.into_data_stream().map(move |chunk| {
+        let chunk = chunk?;
+        buffer.extend_from_slice(&chunk);
+        let mut out = String::new();
+        while let Some(end) = next_event_end(&buffer) {
+            let event: Vec<u8> = buffer.drain(..end).collect();
+            out.push_str(&meta.translate_event(&String::from_utf8_lossy(&event)));
+        }
+        Ok::<_, axum::Error>(Bytes::from(out))
+    });
+    Body::from_stream(stream)
+}
+
+/// POST /v1/completions
+///
+/// 旧版文本补全接口，内部按 `/v1/messages` 处理后转换响应格式
+pub async fn post_completions(
+    state: State<AppState>,
+    managed_key: Option<Extension<ManagedApiKey>>,
+    audit: Option<Extension<Arc<AuditTracker>>>,
+    headers: HeaderMap,
+    JsonExtractor(payload): JsonExtractor<CompletionRequest>,
+) -> Response {
+    let meta = CompletionMeta::new(&payload.model);
+    let request = match payload.into_messages_request() {
+        Ok(request) => request,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new("invalid_request_error", message)),
+            )
+                .into_response();
+        }
+    };
+    let stream = request.stream;
+
+    let response = post_messages(state, managed_key, audit, headers, ValidatedJson(request)).await;
+    // 错误响应原样返回
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    if stream {
+        let mut response = Response::new(completion_stream(body, meta));
+        *response.status_mut() = parts.status;
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, "text/event-stream".parse().unwrap());
+        headers.insert(header::CACHE_CONTROL, "no-cache".parse().unwrap());
+        return response;
+    }
+
+    let message = match axum::body::to_bytes(body, MAX_RESPONSE_BYTES).await {
+        Ok(bytes) => serde_json::from_slice::<Value>(&bytes).unwrap_or_default(),
+        Err(e) => {
+            tracing::error!("读取补全响应失败: {}", e);
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(ErrorResponse::new(
+                    "api_error",
+                    format!("读取上游响应失败: {}", e),
+                )),
+            )
+                .into_response();
+        }
+    };
+    Json(meta.completion(&message)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta() -> CompletionMeta {
+        CompletionMeta {
+            id: "cmpl-test".to_string(),
+            created: 1,
+            model: "claude-sonnet-4-5".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_prompt_becomes_single_user_turn() {
+        let request: CompletionRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "prompt": ["def fib(n):"],
+            "stream": true,
+        }))
+        .unwrap();
+        let messages = request.into_messages_request().unwrap();
+        assert_eq!(messages.max_tokens, 16);
+        assert!(messages.stream);
+        assert_eq!(messages.messages.len(), 1);
+        assert_eq!(messages.messages[0].role, "user");
+        assert_eq!(messages.messages[0].content, json!("def fib(n):"));
+        assert_eq!(messages.stop_sequences, None);
+
+        for (stop, expected) in [
+            (json!("\n\n"), Some(vec!["\n\n".to_string()])),
+            (json!(["END", ""]), Some(vec!["END".to_string()])),
+            (json!([""]), None),
+        ] {
+            let request: CompletionRequest =
+                serde_json::from_value(json!({ "model": "m", "prompt": "p", "stop": stop }))
+                    .unwrap();
+            assert_eq!(
+                request.into_messages_request().unwrap().stop_sequences,
+                expected
+            );
+        }
+
+        for prompt in [json!(["a", "b"]), json!([[1, 2]]), json!(""), json!(1)] {
+            let request: CompletionRequest =
+                serde_json::from_value(json!({ "model": "m", "prompt": prompt })).unwrap();
+            assert!(request.into_messages_request().is_err());
+        }
+    }
+
+    #[test]
+    fn test_non_stream_response_conversion() {
+        let completion = meta().completion(&json!({
+            "content": [
+                {"type": "thinking", "thinking": "hmm"},
+                {"type": "text", "text": "Hello"},
+                {"type": "text", "text": " world"},
+            ],
+            "stop_reason": "max_tokens",
+            "usage": {"input_tokens": 5, "output_tokens": 7},
+        }));
+        assert_eq!(completion["object"], "text_completion");
+        assert_eq!(completion["choices"][0]["text"], "Hello world");
+        assert_eq!(completion["choices"][0]["finish_reason"], "length");
+        assert_eq!(completion["usage"]["total_tokens"], 12);
+    }
+
+    #[test]
+    fn test_sse_event_translation() {
+        let meta = meta();
+        let delta = "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\
+                     \"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n";
+        let chunk = meta.translate_event(delta);
+        let data: Value =
+            serde_json::from_str(chunk.strip_prefix("data: ").unwrap().trim()).unwrap();
+        assert_eq!(data["choices"][0]["text"], "Hi");
+        assert!(data["choices"][0]["finish_reason"].is_null());
+
+        let stop = "event: message_delta\ndata: {\"type\":\"message_delta\",\
+                    \"delta\":{\"stop_reason\":\"end_turn\"}}\n\n";
+        assert!(
+            meta.translate_event(stop)
+                .contains("\"finish_reason\":\"stop\"")
+        );
+        let done = "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n";
+        assert_eq!(meta.translate_event(done), "data: [DONE]\n\n");
+        assert_eq!(
+            meta.translate_event("event: ping\ndata: {\"type\":\"ping\"}\n\n"),
+            ""
+        );
+
+        assert_eq!(next_event_end(b"event: a\ndata: {}\n\nevent"), Some(19));
+        assert_eq!(next_event_end(b"event: a\ndata: {}\n"), None);
+    }
+}
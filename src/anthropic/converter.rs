@@ -2,6 +2,7 @@
 //!
 //! 负责将 Anthropic API 请求格式转换为 Kiro API 请求格式
 
+use serde::Serialize;
 use uuid::Uuid;
 
 use crate::kiro::model::requests::conversation::{
@@ -102,11 +103,51 @@ pub fn map_model(model: &str) -> Option<String> {
     }
 }
 
+/// 转换过程中对请求内容所做的改写
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transformation {
+    /// 系统消息前注入 thinking 标签
+    ThinkingPrefix,
+    /// 系统消息末尾追加分块写入策略
+    ChunkedWritePolicy,
+    /// Write/Edit 工具描述追加分块写入提示
+    ToolDescriptionSuffix,
+    /// 为历史中引用但未声明的工具补充占位定义
+    PlaceholderTools,
+    /// 丢弃末尾的 assistant 消息（prefill）
+    PrefillDropped,
+    /// 工具描述超过长度上限被截断
+    ToolDescriptionTruncated,
+    /// 移除没有对应 tool_result 的 tool_use
+    OrphanedToolUseRemoved,
+    /// 移除没有对应 tool_use 的 tool_result
+    OrphanedToolResultRemoved,
+    /// 剥离上游不支持的 prompt caching 断点（cache_control）
+    CacheControlStripped,
+}
+
+impl Transformation {
+    /// 是否属于截断/丢弃类改写（否则为提示词注入类）
+    pub fn is_truncation(self) -> bool {
+        matches!(
+            self,
+            Self::PrefillDropped
+                | Self::ToolDescriptionTruncated
+                | Self::OrphanedToolUseRemoved
+                | Self::OrphanedToolResultRemoved
+                | Self::CacheControlStripped
+        )
+    }
+}
+
 /// 转换结果
 #[derive(Debug)]
 pub struct ConversionResult {
     /// 转换后的 Kiro 请求
     pub conversation_state: ConversationState,
+    /// 按发生顺序记录的改写（不重复）
+    pub transformations: Vec<Transformation>,
 }
 
 /// 转换错误
@@ -185,6 +226,34 @@ fn create_placeholder_tool(name: &str) -> Tool {
     }
 }
 
+/// 请求中是否带有 prompt caching 断点（system、tools 或消息内容块）
+fn has_cache_control(req: &MessagesRequest) -> bool {
+    let in_system = req
+        .system
+        .iter()
+        .flatten()
+        .any(|s| s.cache_control.is_some());
+    let in_tools = req.tools.iter().flatten().any(|t| t.cache_control.is_some());
+    in_system
+        || in_tools
+        || req
+            .messages
+            .iter()
+            .any(|m| content_has_cache_control(&m.content))
+}
+
+/// 内容块（含 tool_result 的嵌套内容）中是否带有 cache_control
+fn content_has_cache_control(content: &serde_json::Value) -> bool {
+    match content {
+        serde_json::Value::Array(blocks) => blocks.iter().any(content_has_cache_control),
+        serde_json::Value::Object(block) => {
+            block.contains_key("cache_control")
+                || block.get("content").is_some_and(content_has_cache_control)
+        }
+        _ => false,
+    }
+}
+
 /// 将 Anthropic 请求转换为 Kiro 请求
 pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, ConversionError> {
     // 1. 映射模型
@@ -196,10 +265,18 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
         return Err(ConversionError::EmptyMessages);
     }
 
+    let mut applied = Vec::new();
+
+    // Kiro API 不支持 prompt caching，cache_control 只在这里记录，不会转发上游
+    if has_cache_control(req) {
+        applied.push(Transformation::CacheControlStripped);
+    }
+
     // 2.5. 预处理 prefill：如果末尾是 assistant，静默丢弃并截断到最后一条 user
     // Claude 4.x 已弃用 assistant prefill，Kiro API 也不支持
     let messages: &[_] = if req.messages.last().is_some_and(|m| m.role != "user") {
         tracing::info!("检测到末尾 assistant 消息（prefill），静默丢弃");
+        applied.push(Transformation::PrefillDropped);
         let last_user_idx = req
             .messages
             .iter()
@@ -228,18 +305,24 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
     let (text_content, images, tool_results) = process_message_content(&last_message.content)?;
 
     // 6. 转换工具定义
-    let mut tools = convert_tools(&req.tools);
+    let mut tools = convert_tools(&req.tools, &mut applied);
 
     // 7. 构建历史消息（需要先构建，以便收集历史中使用的工具）
-    let mut history = build_history(req, messages, &model_id)?;
+    let mut history = build_history(req, messages, &model_id, &mut applied)?;
 
     // 8. 验证并过滤 tool_use/tool_result 配对
     // 移除孤立的 tool_result（没有对应的 tool_use）
     // 同时返回孤立的 tool_use_id 集合，用于后续清理
     let (validated_tool_results, orphaned_tool_use_ids) =
         validate_tool_pairing(&history, &tool_results);
+    if validated_tool_results.len() < tool_results.len() {
+        applied.push(Transformation::OrphanedToolResultRemoved);
+    }
 
     // 9. 从历史中移除孤立的 tool_use（Kiro API 要求 tool_use 必须有对应的 tool_result）
+    if !orphaned_tool_use_ids.is_empty() {
+        applied.push(Transformation::OrphanedToolUseRemoved);
+    }
     remove_orphaned_tool_uses(&mut history, &orphaned_tool_use_ids);
 
     // 10. 收集历史中使用的工具名称，为缺失的工具生成占位符定义
@@ -254,6 +337,9 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
     for tool_name in history_tool_names {
         if !existing_tool_names.contains(&tool_name.to_lowercase()) {
             tools.push(create_placeholder_tool(&tool_name));
+            if !applied.contains(&Transformation::PlaceholderTools) {
+                applied.push(Transformation::PlaceholderTools);
+            }
         }
     }
 
@@ -288,7 +374,10 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
         .with_current_message(current_message)
         .with_history(history);
 
-    Ok(ConversionResult { conversation_state })
+    Ok(ConversionResult {
+        conversation_state,
+        transformations: applied,
+    })
 }
 
 /// 确定聊天触发类型
@@ -505,12 +594,17 @@ fn remove_orphaned_tool_uses(
 }
 
 /// 转换工具定义
-fn convert_tools(tools: &Option<Vec<super::types::Tool>>) -> Vec<Tool> {
+fn convert_tools(
+    tools: &Option<Vec<super::types::Tool>>,
+    applied: &mut Vec<Transformation>,
+) -> Vec<Tool> {
     let Some(tools) = tools else {
         return Vec::new();
     };
 
-    tools
+    let mut suffixed = false;
+    let mut truncated = false;
+    let tools = tools
         .iter()
         .map(|t| {
             let mut description = t.description.clone();
@@ -524,11 +618,15 @@ fn convert_tools(tools: &Option<Vec<super::types::Tool>>) -> Vec<Tool> {
             if !suffix.is_empty() {
                 description.push('\n');
                 description.push_str(suffix);
+                suffixed = true;
             }
 
             // 限制描述长度为 10000 字符（安全截断 UTF-8，单次遍历）
             let description = match description.char_indices().nth(10000) {
-                Some((idx, _)) => description[..idx].to_string(),
+                Some((idx, _)) => {
+                    truncated = true;
+                    description[..idx].to_string()
+                }
                 None => description,
             };
 
@@ -540,7 +638,15 @@ fn convert_tools(tools: &Option<Vec<super::types::Tool>>) -> Vec<Tool> {
                 },
             }
         })
-        .collect()
+        .collect();
+
+    if suffixed {
+        applied.push(Transformation::ToolDescriptionSuffix);
+    }
+    if truncated {
+        applied.push(Transformation::ToolDescriptionTruncated);
+    }
+    tools
 }
 
 /// 生成thinking标签前缀
@@ -579,7 +685,13 @@ fn has_thinking_tags(content: &str) -> bool {
 ///   注意：该切片与 `req.messages` 可能不同（prefill 时会截断末尾的 assistant 消息），
 ///   调用方应始终使用此参数而非 `req.messages`。
 /// * `model_id` - 已映射的 Kiro 模型 ID
-fn build_history(req: &MessagesRequest, messages: &[super::types::Message], model_id: &str) -> Result<Vec<Message>, ConversionError> {
+/// * `applied` - 记录对系统消息所做的注入
+fn build_history(
+    req: &MessagesRequest,
+    messages: &[super::types::Message],
+    model_id: &str,
+    applied: &mut Vec<Transformation>,
+) -> Result<Vec<Message>, ConversionError> {
     let mut history = Vec::new();
 
     // 生成thinking前缀（如果需要）
@@ -596,10 +708,12 @@ fn build_history(req: &MessagesRequest, messages: &[super::types::Message], mode
         if !system_content.is_empty() {
             // 追加分块写入策略到系统消息
             let system_content = format!("{}\n{}", system_content, SYSTEM_CHUNKED_POLICY);
+            applied.push(Transformation::ChunkedWritePolicy);
 
             // 注入thinking标签到系统消息最前面（如果需要且不存在）
             let final_content = if let Some(ref prefix) = thinking_prefix {
                 if !has_thinking_tags(&system_content) {
+                    applied.push(Transformation::ThinkingPrefix);
                     format!("{}\n{}", prefix, system_content)
                 } else {
                     system_content
@@ -617,6 +731,7 @@ fn build_history(req: &MessagesRequest, messages: &[super::types::Message], mode
         }
     } else if let Some(ref prefix) = thinking_prefix {
         // 没有系统消息但有thinking配置，插入新的系统消息
+        applied.push(Transformation::ThinkingPrefix);
         let user_msg = HistoryUserMessage::new(prefix.clone(), model_id);
         history.push(Message::User(user_msg));
 
@@ -901,6 +1016,7 @@ mod tests {
             tool_choice: None,
             thinking: None,
             output_config: None,
+            stop_sequences: None,
             metadata: None,
         };
         assert_eq!(determine_chat_trigger_type(&req), "MANUAL");
@@ -980,6 +1096,7 @@ mod tests {
             tool_choice: None,
             thinking: None,
             output_config: None,
+            stop_sequences: None,
             metadata: None,
         };
 
@@ -1045,6 +1162,7 @@ mod tests {
             tool_choice: None,
             thinking: None,
             output_config: None,
+            stop_sequences: None,
             metadata: Some(Metadata {
                 user_id: Some(
                     "user_0dede55c6dcc4a11a30bbb5e7f22e6fdf86cdeba3820019cc27612af4e1243cd_account__session_a0662283-7fd3-4399-a7eb-52b9a717ae88".to_string(),
@@ -1059,6 +1177,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_convert_request_records_transformations() {
+        use super::super::types::{Message as AnthropicMessage, Thinking};
+
+        let req = MessagesRequest {
+            model: "claude-sonnet-4".to_string(),
+            max_tokens: 1024,
+            messages: vec![
+                AnthropicMessage {
+                    role: "user".to_string(),
+                    content: serde_json::json!("Hello"),
+                },
+                AnthropicMessage {
+                    role: "assistant".to_string(),
+                    content: serde_json::json!("Hi, "),
+                },
+            ],
+            stream: false,
+            system: None,
+            tools: None,
+            tool_choice: None,
+            thinking: Some(Thinking {
+                thinking_type: "enabled".to_string(),
+                budget_tokens: 1024,
+            }),
+            output_config: None,
+            stop_sequences: None,
+            metadata: None,
+        };
+
+        let result = convert_request(&req).unwrap();
+        assert_eq!(
+            result.transformations,
+            vec![Transformation::PrefillDropped, Transformation::ThinkingPrefix]
+        );
+        assert!(result.transformations[0].is_truncation());
+        assert!(!result.transformations[1].is_truncation());
+    }
+
+    #[test]
+    fn test_convert_request_accepts_cache_control() {
+        let req: MessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 1024,
+            "system": [{"type": "text", "text": "You are helpful", "cache_control": {"type": "ephemeral", "ttl": "1h"}}],
+            "messages": [{"role": "user", "content": [
+                {"type": "text", "text": "Hello", "cache_control": {"type": "ephemeral"}}
+            ]}]
+        }))
+        .unwrap();
+        assert!(req.system.as_ref().unwrap()[0].cache_control.is_some());
+
+        let result = convert_request(&req).unwrap();
+        assert_eq!(
+            result.transformations[0],
+            Transformation::CacheControlStripped
+        );
+        let body = serde_json::to_string(&result.conversation_state).unwrap();
+        assert!(!body.contains("cache_control") && !body.contains("ephemeral"));
+
+        let nested = serde_json::json!([{"type": "tool_result", "tool_use_id": "t", "content": [
+            {"type": "text", "text": "ok", "cache_control": {"type": "ephemeral"}}
+        ]}]);
+        assert!(content_has_cache_control(&nested));
+        assert!(!content_has_cache_control(&serde_json::json!("plain")));
+    }
+
     #[test]
     fn test_convert_request_without_metadata() {
         use super::super::types::Message as AnthropicMessage;
@@ -1077,6 +1262,7 @@ mod tests {
             tool_choice: None,
             thinking: None,
             output_config: None,
+            stop_sequences: None,
             metadata: None,
         };
 
@@ -1507,6 +1693,7 @@ mod tests {
             tool_choice: None,
             thinking: None,
             output_config: None,
+            stop_sequences: None,
             metadata: None,
         };
 
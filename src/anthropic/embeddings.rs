@@ -0,0 +1,94 @@
+//! Embeddings 转发
+//!
+//! Kiro 上游不提供 embeddings。部分 Agent 框架要求同一个 base URL 下存在 `/v1/embeddings`，
+//! 因此配置了 `embeddingsApiUrl` 时将请求体原样转发给外部 OpenAI 兼容服务，
+//! 未配置时返回明确的 501 错误，而不是 404
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+
+use crate::http_client::build_client;
+use crate::model::config::Config;
+
+use super::middleware::AppState;
+use super::types::ErrorResponse;
+
+/// 外部 embeddings 请求超时（秒）
+const EMBEDDINGS_TIMEOUT_SECS: u64 = 120;
+
+fn not_supported() -> Response {
+    (
+        StatusCode::NOT_IMPLEMENTED,
+        Json(ErrorResponse::new(
+            "not_supported_error",
+            "Embeddings are not supported by the Kiro upstream; \
+             set embeddingsApiUrl to forward /v1/embeddings to an external provider",
+        )),
+    )
+        .into_response()
+}
+
+/// POST /v1/embeddings
+///
+/// 原样转发请求体，并原样返回外部服务的状态码与响应体
+pub async fn post_embeddings(State(state): State<AppState>, body: Bytes) -> Response {
+    let Some(config) = state
+        .kiro_provider
+        .as_ref()
+        .map(|p| p.token_manager().config())
+    else {
+        return not_supported();
+    };
+    let Some(api_url) = config.embeddings_api_url.as_deref() else {
+        return not_supported();
+    };
+
+    match forward(&config, api_url, body).await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::warn!("转发 embeddings 请求失败: {}", e);
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(ErrorResponse::new(
+                    "api_error",
+                    format!("转发 embeddings 请求失败: {}", e),
+                )),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn forward(config: &Config, api_url: &str, body: Bytes) -> anyhow::Result<Response> {
+    let client = build_client(
+        config.proxy_config().as_ref(),
+        EMBEDDINGS_TIMEOUT_SECS,
+        config.tls_backend,
+    )?;
+
+    let mut request = client
+        .post(api_url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body);
+    if let Some(api_key) = &config.embeddings_api_key {
+        request = request.bearer_auth(api_key);
+    }
+
+    let upstream = request.send().await?;
+    let status = upstream.status();
+    let content_type = upstream
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .cloned()
+        .unwrap_or_else(|| header::HeaderValue::from_static("application/json"));
+    let bytes = upstream.bytes().await?;
+    if !status.is_success() {
+        tracing::warn!("外部 embeddings API 返回错误: {}", status);
+    }
+
+    Ok((status, [(header::CONTENT_TYPE, content_type)], bytes).into_response())
+}
@@ -0,0 +1,768 @@
+//! Gemini API 兼容（`generateContent` / `streamGenerateContent`）
+//!
+//! 供只支持 Google AI SDK 的工具接入：将 Gemini 请求（`contents`、`systemInstruction`、
+//! `functionDeclarations` 等）转换为 Messages 请求交给 `/v1/messages` 的处理逻辑，
+//! 再把 Anthropic 响应转换回 `GenerateContentResponse`。
+//!
+//! 流式响应默认为 JSON 数组，`alt=sse` 时为 SSE。
+//! `stopSequences` 转换为 `stop_sequences` 由代理在本地匹配，
+//! `temperature`、`topP` 等采样参数不受支持，会被忽略
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use axum::{
+    Json as JsonExtractor,
+    body::Body,
+    extract::{Extension, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use bytes::Bytes;
+use futures::{StreamExt, stream};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::audit::AuditTracker;
+
+use super::completions::next_event_end;
+use super::handlers::post_messages;
+use super::middleware::{AppState, ManagedApiKey};
+use super::types::{Message, MessagesRequest, SystemMessage, Thinking, Tool};
+use super::validation::ValidatedJson;
+
+/// 未指定 `maxOutputTokens` 时的默认输出上限
+const DEFAULT_MAX_OUTPUT_TOKENS: i32 = 8192;
+
+/// 非流式响应体读取上限
+const MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Gemini 内容（一轮对话）
+#[derive(Debug, Deserialize)]
+pub struct Content {
+    /// `user` / `model`，旧版 SDK 的函数结果使用 `function`
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub parts: Vec<Part>,
+}
+
+/// 内容片段
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Part {
+    pub text: Option<String>,
+    pub inline_data: Option<InlineData>,
+    pub function_call: Option<FunctionCall>,
+    pub function_response: Option<FunctionResponse>,
+    pub file_data: Option<Value>,
+    /// 思考内容（回传的历史 thought 片段会被丢弃）
+    #[serde(default)]
+    pub thought: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineData {
+    pub mime_type: String,
+    pub data: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionCall {
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub args: Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionResponse {
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub response: Value,
+}
+
+/// 工具定义（仅支持 `functionDeclarations`）
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiTool {
+    #[serde(default)]
+    pub function_declarations: Vec<FunctionDeclaration>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionDeclaration {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub parameters: Option<Value>,
+    pub parameters_json_schema: Option<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationConfig {
+    pub max_output_tokens: Option<i32>,
+    pub thinking_config: Option<ThinkingConfig>,
+    #[serde(default)]
+    pub stop_sequences: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThinkingConfig {
+    pub thinking_budget: Option<i32>,
+}
+
+/// generateContent 请求体
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateContentRequest {
+    pub contents: Vec<Content>,
+    #[serde(alias = "system_instruction")]
+    pub system_instruction: Option<Content>,
+    #[serde(default)]
+    pub tools: Vec<GeminiTool>,
+    pub tool_config: Option<Value>,
+    #[serde(default)]
+    pub generation_config: GenerationConfig,
+}
+
+/// Gemini 的 schema 类型为大写枚举（`OBJECT`、`STRING`），转换为 JSON Schema 小写形式
+fn normalize_schema(schema: &mut Value) {
+    match schema {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::String(s) if key == "type" => *s = s.to_ascii_lowercase(),
+                    _ => normalize_schema(value),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(normalize_schema),
+        _ => {}
+    }
+}
+
+impl FunctionDeclaration {
+    fn into_tool(self) -> Tool {
+        let mut schema = self
+            .parameters_json_schema
+            .or(self.parameters)
+            .unwrap_or_else(|| json!({"type": "object", "properties": {}}));
+        normalize_schema(&mut schema);
+        let input_schema = match schema {
+            Value::Object(map) => map.into_iter().collect(),
+            _ => HashMap::new(),
+        };
+        Tool {
+            tool_type: None,
+            name: self.name,
+            description: self.description,
+            input_schema,
+            max_uses: None,
+            cache_control: None,
+        }
+    }
+}
+
+/// `toolConfig.functionCallingConfig.mode` 映射为 tool_choice
+fn tool_choice(tool_config: &Value) -> Option<Value> {
+    let config = &tool_config["functionCallingConfig"];
+    match config["mode"].as_str()? {
+        "ANY" => match config["allowedFunctionNames"].as_array().map(Vec::as_slice) {
+            Some([Value::String(name)]) => Some(json!({"type": "tool", "name": name})),
+            _ => Some(json!({"type": "any"})),
+        },
+        "NONE" => Some(json!({"type": "none"})),
+        _ => None,
+    }
+}
+
+/// 转换单个片段；`pending` 记录尚未收到结果的函数调用 id，用于为无 id 的结果配对
+fn convert_part(
+    part: Part,
+    pending: &mut HashMap<String, VecDeque<String>>,
+    next_id: &mut usize,
+) -> Result<Option<Value>, String> {
+    if part.thought {
+        return Ok(None);
+    }
+    if let Some(text) = part.text {
+        return Ok((!text.is_empty()).then(|| json!({"type": "text", "text": text})));
+    }
+    if let Some(data) = part.inline_data {
+        if !data.mime_type.starts_with("image/") {
+            return Err(format!("不支持的 inlineData 类型: {}", data.mime_type));
+        }
+        return Ok(Some(json!({
+            "type": "image",
+            "source": {"type": "base64", "media_type": data.mime_type, "data": data.data},
+        })));
+    }
+    if let Some(call) = part.function_call {
+        let id = call.id.unwrap_or_else(|| {
+            *next_id += 1;
+            format!("toolu_gemini_{}", next_id)
+        });
+        pending
+            .entry(call.name.clone())
+            .or_default()
+            .push_back(id.clone());
+        return Ok(Some(json!({
+            "type": "tool_use",
+            "id": id,
+            "name": call.name,
+            "input": call.args,
+        })));
+    }
+    if let Some(result) = part.function_response {
+        let queued = pending.get_mut(&result.name).and_then(VecDeque::pop_front);
+        let id = result
+            .id
+            .or(queued)
+            .ok_or_else(|| format!("functionResponse {} 没有对应的 functionCall", result.name))?;
+        return Ok(Some(json!({
+            "type": "tool_result",
+            "tool_use_id": id,
+            "content": result.response.to_string(),
+        })));
+    }
+    if part.file_data.is_some() {
+        return Err("不支持 fileData，请改用 inlineData".to_string());
+    }
+    Ok(None)
+}
+
+impl GenerateContentRequest {
+    /// 转换为 Messages 请求
+    fn into_messages_request(self, model: &str, stream: bool) -> Result<MessagesRequest, String> {
+        let mut pending = HashMap::new();
+        let mut next_id = 0;
+        let mut messages = Vec::new();
+        for content in self.contents {
+            let role = match content.role.as_deref() {
+                Some("model") => "assistant",
+                _ => "user",
+            };
+            let mut blocks = Vec::new();
+            for part in content.parts {
+                blocks.extend(convert_part(part, &mut pending, &mut next_id)?);
+            }
+            if !blocks.is_empty() {
+                messages.push(Message {
+                    role: role.to_string(),
+                    content: Value::Array(blocks),
+                });
+            }
+        }
+        if messages.is_empty() {
+            return Err("contents 不能为空".to_string());
+        }
+
+        let system: Vec<SystemMessage> = self
+            .system_instruction
+            .into_iter()
+            .flat_map(|content| content.parts)
+            .filter_map(|part| part.text)
+            .map(|text| SystemMessage {
+                text,
+                cache_control: None,
+            })
+            .collect();
+        let tools: Vec<Tool> = self
+            .tools
+            .into_iter()
+            .flat_map(|tool| tool.function_declarations)
+            .map(FunctionDeclaration::into_tool)
+            .collect();
+        // 复用 Thinking 的反序列化逻辑（预算上限裁剪）
+        let thinking = self
+            .generation_config
+            .thinking_config
+            .and_then(|config| config.thinking_budget)
+            .filter(|budget| *budget > 0)
+            .and_then(|budget| {
+                serde_json::from_value::<Thinking>(
+                    json!({"type": "enabled", "budget_tokens": budget}),
+                )
+                .ok()
+            });
+
+        let stop_sequences: Vec<String> = self
+            .generation_config
+            .stop_sequences
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        Ok(MessagesRequest {
+            model: model.to_string(),
+            max_tokens: self
+                .generation_config
+                .max_output_tokens
+                .unwrap_or(DEFAULT_MAX_OUTPUT_TOKENS),
+            messages,
+            stream,
+            system: (!system.is_empty()).then_some(system),
+            tools: (!tools.is_empty()).then_some(tools),
+            tool_choice: self.tool_config.as_ref().and_then(tool_choice),
+            thinking,
+            output_config: None,
+            stop_sequences: (!stop_sequences.is_empty()).then_some(stop_sequences),
+            metadata: None,
+        })
+    }
+}
+
+/// Anthropic stop_reason 映射为 Gemini finishReason
+fn finish_reason(stop_reason: &str) -> &'static str {
+    match stop_reason {
+        "max_tokens" | "model_context_window_exceeded" => "MAX_TOKENS",
+        "refusal" => "SAFETY",
+        _ => "STOP",
+    }
+}
+
+/// HTTP 状态码对应的 Google API 错误状态
+fn error_status(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 | 413 => "INVALID_ARGUMENT",
+        401 => "UNAUTHENTICATED",
+        403 => "PERMISSION_DENIED",
+        404 => "NOT_FOUND",
+        429 => "RESOURCE_EXHAUSTED",
+        501 => "UNIMPLEMENTED",
+        503 => "UNAVAILABLE",
+        504 => "DEADLINE_EXCEEDED",
+        _ => "INTERNAL",
+    }
+}
+
+fn gemini_error(status: StatusCode, message: &str) -> Value {
+    json!({
+        "error": {
+            "code": status.as_u16(),
+            "message": message,
+            "status": error_status(status),
+        }
+    })
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(gemini_error(status, message))).into_response()
+}
+
+/// Anthropic 内容块转换为 Gemini 片段（thinking 等其他块被丢弃）
+fn block_to_part(block: &Value) -> Option<Value> {
+    match block["type"].as_str()? {
+        "text" => Some(json!({"text": block["text"]})),
+        "tool_use" => Some(json!({
+            "functionCall": {"id": block["id"], "name": block["name"], "args": block["input"]},
+        })),
+        _ => None,
+    }
+}
+
+fn usage_metadata(input_tokens: i64, output_tokens: i64) -> Value {
+    json!({
+        "promptTokenCount": input_tokens,
+        "candidatesTokenCount": output_tokens,
+        "totalTokenCount": input_tokens + output_tokens,
+    })
+}
+
+/// 将非流式 Messages 响应转换为 GenerateContentResponse
+fn generate_content_response(message: &Value, model: &str) -> Value {
+    let parts: Vec<Value> = message["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(block_to_part)
+        .collect();
+    let mut response = json!({
+        "candidates": [{
+            "content": {"role": "model", "parts": parts},
+            "finishReason": message["stop_reason"].as_str().map(finish_reason),
+            "index": 0,
+        }],
+        "usageMetadata": usage_metadata(
+            message["usage"]["input_tokens"].as_i64().unwrap_or(0),
+            message["usage"]["output_tokens"].as_i64().unwrap_or(0),
+        ),
+        "modelVersion": model,
+        "responseId": message["id"],
+    });
+    // 保留 x-kiro-meta 请求的元数据回显
+    if let Some(meta) = message.get("kiro_meta") {
+        response["kiro_meta"] = meta.clone();
+    }
+    response
+}
+
+/// 流式响应格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamFormat {
+    /// `alt=sse`
+    Sse,
+    /// 默认：逐步输出的 JSON 数组
+    JsonArray,
+}
+
+/// Anthropic SSE 事件到 Gemini 流式块的转换状态
+struct StreamTranslator {
+    model: String,
+    format: StreamFormat,
+    /// 是否已输出过数据块（JSON 数组格式需要分隔符）
+    started: bool,
+    input_tokens: i64,
+    /// 进行中的 tool_use 块：index -> (id, name, 累积的 JSON 输入)
+    tool_blocks: HashMap<u64, (String, String, String)>,
+}
+
+impl StreamTranslator {
+    fn new(model: &str, format: StreamFormat) -> Self {
+        Self {
+            model: model.to_string(),
+            format,
+            started: false,
+            input_tokens: 0,
+            tool_blocks: HashMap::new(),
+        }
+    }
+
+    fn emit(&mut self, value: &Value) -> String {
+        match self.format {
+            StreamFormat::Sse => format!("data: {}\n\n", value),
+            StreamFormat::JsonArray => {
+                let sep = if self.started { ",\n" } else { "[" };
+                self.started = true;
+                format!("{}{}", sep, value)
+            }
+        }
+    }
+
+    fn candidate_chunk(&mut self, parts: Vec<Value>) -> String {
+        let chunk = json!({
+            "candidates": [{"content": {"role": "model", "parts": parts}, "index": 0}],
+            "modelVersion": self.model,
+        });
+        self.emit(&chunk)
+    }
+
+    /// JSON 数组格式的结尾
+    fn finish(&mut self) -> String {
+        match self.format {
+            StreamFormat::Sse => String::new(),
+            StreamFormat::JsonArray if self.started => "]".to_string(),
+            StreamFormat::JsonArray => "[]".to_string(),
+        }
+    }
+
+    /// 将一个 Anthropic SSE 事件（含结尾空行）转换为 Gemini 流式块
+    fn translate_event(&mut self, event: &str) -> String {
+        let Some(data) = event.lines().find_map(|line| line.strip_prefix("data: ")) else {
+            return String::new();
+        };
+        let Ok(data) = serde_json::from_str::<Value>(data) else {
+            return String::new();
+        };
+        let index = data["index"].as_u64().unwrap_or(0);
+
+        match data["type"].as_str() {
+            Some("message_start") => {
+                self.input_tokens = data["message"]["usage"]["input_tokens"]
+                    .as_i64()
+                    .unwrap_or(0);
+                String::new()
+            }
+            Some("content_block_start") if data["content_block"]["type"] == "tool_use" => {
+                let block = &data["content_block"];
+                let id = block["id"].as_str().unwrap_or_default().to_string();
+                let name = block["name"].as_str().unwrap_or_default().to_string();
+                self.tool_blocks.insert(index, (id, name, String::new()));
+                String::new()
+            }
+            Some("content_block_delta") => match data["delta"]["type"].as_str() {
+                Some("text_delta") => match data["delta"]["text"].as_str() {
+                    Some(text) => self.candidate_chunk(vec![json!({"text": text})]),
+                    None => String::new(),
+                },
+                Some("input_json_delta") => {
+                    if let (Some((_, _, input)), Some(partial)) = (
+                        self.tool_blocks.get_mut(&index),
+                        data["delta"]["partial_json"].as_str(),
+                    ) {
+                        input.push_str(partial);
+                    }
+                    String::new()
+                }
+                _ => String::new(),
+            },
+            Some("content_block_stop") => match self.tool_blocks.remove(&index) {
+                Some((id, name, input)) => {
+                    let args: Value = serde_json::from_str(&input).unwrap_or_else(|_| json!({}));
+                    let part = json!({"functionCall": {"id": id, "name": name, "args": args}});
+                    self.candidate_chunk(vec![part])
+                }
+                None => String::new(),
+            },
+            Some("message_delta") => {
+                let output_tokens = data["usage"]["output_tokens"].as_i64().unwrap_or(0);
+                let mut chunk = json!({
+                    "candidates": [{
+                        "content": {"role": "model", "parts": []},
+                        "finishReason": data["delta"]["stop_reason"].as_str().map(finish_reason),
+                        "index": 0,
+                    }],
+                    "usageMetadata": usage_metadata(self.input_tokens, output_tokens),
+                    "modelVersion": self.model,
+                });
+                if let Some(meta) = data.get("kiro_meta") {
+                    chunk["kiro_meta"] = meta.clone();
+                }
+                self.emit(&chunk)
+            }
+            Some("error") => {
+                let message = data["error"]["message"]
+                    .as_str()
+                    .unwrap_or("upstream error");
+                let error = gemini_error(StatusCode::INTERNAL_SERVER_ERROR, message);
+                self.emit(&error)
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+/// 将 Anthropic SSE 响应体转换为 Gemini 流式响应
+fn gemini_stream(body: Body, translator: StreamTranslator) -> Body {
+    let translator = Arc::new(parking_lot::Mutex::new(translator));
+    let tail = translator.clone();
+    let mut buffer: Vec<u8> = Vec::new();
+    let events = body.into_data_stream().map(move |chunk| {
+        let chunk = chunk?;
+        buffer.extend_from_slice(&chunk);
+        let mut translator = translator.lock();
+        let mut out = String::new();
+        while let Some(end) = next_event_end(&buffer) {
+            let event: Vec<u8> = buffer.drain(..end).collect();
+            out.push_str(&translator.translate_event(&String::from_utf8_lossy(&event)));
+        }
+        Ok::<_, axum::Error>(Bytes::from(out))
+    });
+    let end = stream::once(async move { Ok(Bytes::from(tail.lock().finish())) });
+    Body::from_stream(events.chain(end))
+}
+
+/// 将 `/v1/messages` 的错误响应转换为 Google API 错误格式
+async fn convert_error(response: Response) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let bytes = axum::body::to_bytes(body, MAX_RESPONSE_BYTES)
+        .await
+        .unwrap_or_default();
+    let detail = serde_json::from_slice::<Value>(&bytes).unwrap_or_default();
+    let message = detail["error"]["message"]
+        .as_str()
+        .unwrap_or_else(|| parts.status.canonical_reason().unwrap_or("error"));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    let body = gemini_error(parts.status, message).to_string();
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// POST /v1beta/models/{model}:generateContent 与 :streamGenerateContent
+///
+/// 内部按 `/v1/messages` 处理后转换响应格式
+pub async fn post_generate_content(
+    state: State<AppState>,
+    managed_key: Option<Extension<ManagedApiKey>>,
+    audit: Option<Extension<Arc<AuditTracker>>>,
+    headers: HeaderMap,
+    Path(target): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    JsonExtractor(payload): JsonExtractor<GenerateContentRequest>,
+) -> Response {
+    let (model, stream) = match target.rsplit_once(':') {
+        Some((model, "generateContent")) => (model, false),
+        Some((model, "streamGenerateContent")) => (model, true),
+        _ => {
+            return error_response(StatusCode::NOT_FOUND, &format!("不支持的方法: {}", target));
+        }
+    };
+    let format = match query.get("alt").map(String::as_str) {
+        Some("sse") => StreamFormat::Sse,
+        _ => StreamFormat::JsonArray,
+    };
+    let request = match payload.into_messages_request(model, stream) {
+        Ok(request) => request,
+        Err(message) => return error_response(StatusCode::BAD_REQUEST, &message),
+    };
+
+    let response = post_messages(state, managed_key, audit, headers, ValidatedJson(request)).await;
+    if !response.status().is_success() {
+        return convert_error(response).await;
+    }
+
+    let (parts, body) = response.into_parts();
+    if stream {
+        let mut response = Response::new(gemini_stream(body, StreamTranslator::new(model, format)));
+        *response.status_mut() = parts.status;
+        let content_type = match format {
+            StreamFormat::Sse => "text/event-stream",
+            StreamFormat::JsonArray => "application/json",
+        };
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+        headers.insert(header::CACHE_CONTROL, "no-cache".parse().unwrap());
+        return response;
+    }
+
+    let message = match axum::body::to_bytes(body, MAX_RESPONSE_BYTES).await {
+        Ok(bytes) => serde_json::from_slice::<Value>(&bytes).unwrap_or_default(),
+        Err(e) => {
+            tracing::error!("读取 Gemini 兼容响应失败: {}", e);
+            return error_response(StatusCode::BAD_GATEWAY, &format!("读取上游响应失败: {}", e));
+        }
+    };
+    Json(generate_content_response(&message, model)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_conversion() {
+        let request: GenerateContentRequest = serde_json::from_value(json!({
+            "systemInstruction": {"parts": [{"text": "be brief"}]},
+            "contents": [
+                {"role": "user", "parts": [{"text": "weather?"}]},
+                {"role": "model", "parts": [
+                    {"functionCall": {"name": "get_weather", "args": {"city": "SF"}}},
+                ]},
+                {"role": "user", "parts": [
+                    {"functionResponse": {"name": "get_weather", "response": {"temp": 20}}},
+                ]},
+            ],
+            "tools": [{"functionDeclarations": [{
+                "name": "get_weather",
+                "description": "Get weather",
+                "parameters": {"type": "OBJECT", "properties": {"city": {"type": "STRING"}}},
+            }]}],
+            "toolConfig": {"functionCallingConfig": {"mode": "ANY"}},
+            "generationConfig": {
+                "maxOutputTokens": 256,
+                "thinkingConfig": {"thinkingBudget": 1024},
+                "stopSequences": ["STOP", ""],
+            },
+        }))
+        .unwrap();
+        let messages = request
+            .into_messages_request("claude-sonnet-4-5", true)
+            .unwrap();
+
+        assert_eq!(messages.max_tokens, 256);
+        assert!(messages.stream);
+        assert_eq!(messages.system.unwrap()[0].text, "be brief");
+        assert_eq!(messages.thinking.unwrap().budget_tokens, 1024);
+        assert_eq!(messages.stop_sequences, Some(vec!["STOP".to_string()]));
+        assert_eq!(messages.tool_choice, Some(json!({"type": "any"})));
+        let tool = &messages.tools.unwrap()[0];
+        assert_eq!(tool.input_schema["type"], "object");
+        assert_eq!(tool.input_schema["properties"]["city"]["type"], "string");
+
+        let roles: Vec<_> = messages.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        let call = &messages.messages[1].content[0];
+        let result = &messages.messages[2].content[0];
+        assert_eq!(call["type"], "tool_use");
+        assert_eq!(result["type"], "tool_result");
+        assert_eq!(result["tool_use_id"], call["id"]);
+
+        let orphan: GenerateContentRequest = serde_json::from_value(json!({
+            "contents": [{"role": "user", "parts": [{"functionResponse": {"name": "x"}}]}],
+        }))
+        .unwrap();
+        assert!(orphan.into_messages_request("m", false).is_err());
+    }
+
+    #[test]
+    fn test_non_stream_response_conversion() {
+        let response = generate_content_response(
+            &json!({
+                "id": "msg_1",
+                "content": [
+                    {"type": "thinking", "thinking": "hmm"},
+                    {"type": "text", "text": "Calling"},
+                    {"type": "tool_use", "id": "toolu_1", "name": "f", "input": {"a": 1}},
+                ],
+                "stop_reason": "tool_use",
+                "usage": {"input_tokens": 3, "output_tokens": 4},
+            }),
+            "claude-sonnet-4-5",
+        );
+        let candidate = &response["candidates"][0];
+        assert_eq!(candidate["finishReason"], "STOP");
+        assert_eq!(candidate["content"]["parts"][0]["text"], "Calling");
+        assert_eq!(
+            candidate["content"]["parts"][1]["functionCall"]["args"]["a"],
+            1
+        );
+        assert_eq!(response["usageMetadata"]["totalTokenCount"], 7);
+    }
+
+    #[test]
+    fn test_stream_translation() {
+        let mut translator = StreamTranslator::new("claude-sonnet-4-5", StreamFormat::JsonArray);
+        let events = [
+            r#"{"type":"message_start","message":{"usage":{"input_tokens":5}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#,
+            r#"{"type":"content_block_start","index":1,
+                "content_block":{"type":"tool_use","id":"t1","name":"f"}}"#,
+            r#"{"type":"content_block_delta","index":1,
+                "delta":{"type":"input_json_delta","partial_json":"{\"a\":"}}"#,
+            r#"{"type":"content_block_delta","index":1,
+                "delta":{"type":"input_json_delta","partial_json":"1}"}}"#,
+            r#"{"type":"content_block_stop","index":1}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"max_tokens"},
+                "usage":{"output_tokens":2}}"#,
+            r#"{"type":"message_stop"}"#,
+        ];
+        let mut out = String::new();
+        for data in events {
+            // 压缩为单行 data
+            let data: Value = serde_json::from_str(data).unwrap();
+            out.push_str(&translator.translate_event(&format!("event: x\ndata: {}\n\n", data)));
+        }
+        out.push_str(&translator.finish());
+
+        let chunks: Vec<Value> = serde_json::from_str(&out).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            chunks[0]["candidates"][0]["content"]["parts"][0]["text"],
+            "Hi"
+        );
+        let call = &chunks[1]["candidates"][0]["content"]["parts"][0]["functionCall"];
+        assert_eq!(
+            (&call["name"], &call["args"]["a"]),
+            (&json!("f"), &json!(1))
+        );
+        assert_eq!(chunks[2]["candidates"][0]["finishReason"], "MAX_TOKENS");
+        assert_eq!(chunks[2]["usageMetadata"]["totalTokenCount"], 7);
+
+        let mut sse = StreamTranslator::new("m", StreamFormat::Sse);
+        let chunk = sse.translate_event(
+            "data: {\"type\":\"content_block_delta\",\
+             \"delta\":{\"type\":\"text_delta\",\"text\":\"x\"}}\n\n",
+        );
+        assert!(chunk.starts_with("data: {") && chunk.ends_with("\n\n"));
+        assert_eq!(sse.finish(), "");
+    }
+}
@@ -5,30 +5,93 @@ use std::convert::Infallible;
 use anyhow::Error;
 use crate::kiro::model::events::Event;
 use crate::kiro::model::requests::kiro::KiroRequest;
+use crate::kiro::parser::codec::EventStreamCodec;
 use crate::kiro::parser::decoder::EventStreamDecoder;
+use crate::kiro::parser::error::ParseError;
+use crate::kiro::parser::frame::Frame;
+use crate::api_keys::ApiKeyStore;
+use crate::audit::AuditTracker;
+use crate::common::auth;
+use crate::dev;
+use crate::kiro::concurrency::{ConcurrencyLimited, ConcurrencyPermit};
+use crate::kiro::model::credentials::CredentialId;
+use crate::kiro::provider::{
+    KiroProvider, RequestRouting, RetryPolicy, UpstreamAttempts, UpstreamTiming,
+};
+use crate::kiro::token_manager::{NoAvailableCredentials, QuotaInsufficient};
+use crate::model::config::ThinkingOutput;
 use crate::token;
 use axum::{
-    Json as JsonExtractor,
     body::Body,
-    extract::State,
-    http::{StatusCode, header},
+    extract::{Extension, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
     response::{IntoResponse, Json, Response},
 };
 use bytes::Bytes;
+use futures::stream::BoxStream;
 use futures::{Stream, StreamExt, stream};
 use serde_json::json;
 use std::time::Duration;
 use tokio::time::interval;
+use tokio_util::codec::FramedRead;
+use tokio_util::io::StreamReader;
 use uuid::Uuid;
 
+use super::archive::ArchiveTee;
+use super::cache::{CacheKey, ResponseCache};
+use super::capture::{self, CaptureMeta, CaptureSession};
 use super::converter::{ConversionError, convert_request};
-use super::middleware::AppState;
-use super::stream::{BufferedStreamContext, SseEvent, StreamContext};
+use super::image;
+use super::kiro_meta::{self, KiroMeta};
+use super::middleware::{AppState, ManagedApiKey};
+use super::resume::StreamResumer;
+use super::stop_sequence::StopSequenceMatcher;
+use super::stream::{
+    BufferedStreamContext, OutputLimits, SseEvent, StreamContext, estimate_tokens, split_thinking,
+    truncate_to_tokens,
+};
 use super::types::{CountTokensRequest, CountTokensResponse, ErrorResponse, MessagesRequest, Model, ModelsResponse, OutputConfig, Thinking};
+use super::usage_inject;
+use super::validation::{ValidatedJson, check_input_tokens, check_message_count};
 use super::websearch;
 
 /// 将 KiroProvider 错误映射为 HTTP 响应
 fn map_provider_error(err: Error) -> Response {
+    // 凭据并发已满且无法排队（队列已满或等待超时）
+    if let Some(limited) = err.downcast_ref::<ConcurrencyLimited>() {
+        tracing::warn!("并发限制拒绝请求: {}", limited);
+        let mut response = (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse::new(
+                "overloaded_error",
+                "Too many concurrent requests for the upstream credential. Please retry later.",
+            )),
+        )
+            .into_response();
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(limited.retry_after_secs),
+        );
+        return response;
+    }
+
+    // 配额感知准入拒绝（可用凭据的剩余额度都不足以承担本次请求）
+    if let Some(quota) = err.downcast_ref::<QuotaInsufficient>() {
+        tracing::warn!("配额感知准入拒绝请求: {}", quota);
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse::new(
+                "rate_limit_error",
+                format!(
+                    "Remaining quota is insufficient for this request (needs {:.2}, available {:.2}). \
+                     Reduce the request size or retry later.",
+                    quota.required, quota.remaining
+                ),
+            )),
+        )
+            .into_response();
+    }
+
     let err_str = err.to_string();
 
     // 上下文窗口满了（对话历史累积超出模型上下文窗口限制）
@@ -67,13 +130,196 @@ fn map_provider_error(err: Error) -> Response {
         .into_response()
 }
 
+/// 将 API 调用错误映射为 HTTP 响应，无可用凭据时按 degradationMode 降级
+///
+/// - "fallback": 返回格式正确的兜底回复（流式请求返回完整 SSE 事件序列）
+/// - 其他（"error" / "wait" 等待超时）: 返回 503 overloaded_error
+fn map_call_error(provider: &KiroProvider, err: Error, model: &str, stream: bool) -> Response {
+    if !err.is::<NoAvailableCredentials>() {
+        return map_provider_error(err);
+    }
+
+    let config = provider.token_manager().config();
+    tracing::warn!(
+        mode = %config.degradation_mode,
+        "无可用凭据，执行降级策略: {}",
+        err
+    );
+
+    if config.degradation_mode == "fallback" {
+        return fallback_response(model, &config.degradation_message, stream);
+    }
+
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ErrorResponse::new(
+            "overloaded_error",
+            "No upstream credentials are currently available. Please retry later.",
+        )),
+    )
+        .into_response()
+}
+
+/// 构建降级兜底回复（Anthropic Messages 格式）
+fn fallback_response(model: &str, text: &str, stream: bool) -> Response {
+    let id = format!("msg_{}", Uuid::new_v4().to_string().replace('-', ""));
+    let usage = json!({ "input_tokens": 0, "output_tokens": 0 });
+
+    if !stream {
+        let body = json!({
+            "id": id,
+            "type": "message",
+            "role": "assistant",
+            "content": [{ "type": "text", "text": text }],
+            "model": model,
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": usage
+        });
+        return (StatusCode::OK, Json(body)).into_response();
+    }
+
+    let events = [
+        SseEvent::new(
+            "message_start",
+            json!({
+                "type": "message_start",
+                "message": {
+                    "id": id,
+                    "type": "message",
+                    "role": "assistant",
+                    "content": [],
+                    "model": model,
+                    "stop_reason": null,
+                    "stop_sequence": null,
+                    "usage": usage
+                }
+            }),
+        ),
+        SseEvent::new(
+            "content_block_start",
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": { "type": "text", "text": "" }
+            }),
+        ),
+        SseEvent::new(
+            "content_block_delta",
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": { "type": "text_delta", "text": text }
+            }),
+        ),
+        SseEvent::new(
+            "content_block_stop",
+            json!({ "type": "content_block_stop", "index": 0 }),
+        ),
+        SseEvent::new(
+            "message_delta",
+            json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": "end_turn", "stop_sequence": null },
+                "usage": { "output_tokens": 0 }
+            }),
+        ),
+        SseEvent::new("message_stop", json!({ "type": "message_stop" })),
+    ];
+    let body: String = events.iter().map(|e| e.to_sse_string()).collect();
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// 计算粘性会话的客户端标识
+///
+/// 未启用粘性会话时返回 None；优先使用配置的请求头，缺失时回退到客户端 API Key
+fn sticky_session_key(provider: &KiroProvider, headers: &HeaderMap) -> Option<String> {
+    let config = provider.token_manager().config();
+    if !config.sticky_session {
+        return None;
+    }
+
+    config
+        .sticky_session_header
+        .as_deref()
+        .and_then(|name| headers.get(name))
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(|v| format!("header:{}", v))
+        .or_else(|| auth::extract_api_key_from_headers(headers).map(|k| format!("key:{}", k)))
+}
+
+/// 解析单次请求的路由参数（重试策略、粘性会话、凭据池）
+fn request_routing(provider: &KiroProvider, headers: &HeaderMap) -> RequestRouting {
+    RequestRouting {
+        policy: RetryPolicy::from_headers(headers),
+        session_key: sticky_session_key(provider, headers),
+        pool: RequestRouting::pool_from_headers(headers),
+        ..Default::default()
+    }
+}
+
 /// GET /v1/models
 ///
-/// 返回可用的模型列表
-pub async fn get_models() -> impl IntoResponse {
+/// 返回当前可用凭据能够处理的模型列表（含 modelAliases 中目标模型可用的别名），
+/// 按 `x-kiro-pool` 请求头限定凭据池
+pub async fn get_models(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
     tracing::info!("Received GET /v1/models request");
 
-    let models = vec![
+    let mut models = builtin_models();
+    if let Some(provider) = &state.kiro_provider {
+        let token_manager = provider.token_manager();
+        let config = token_manager.config();
+        let pool = RequestRouting::pool_from_headers(&headers);
+        let is_available =
+            |model: &str| token_manager.has_credential_for_model(model, pool.as_deref());
+
+        // 别名沿用目标模型的元数据，需在过滤内置模型前生成
+        let aliases: Vec<Model> = config
+            .model_aliases
+            .iter()
+            .filter(|(_, target)| is_available(target))
+            .map(|(alias, target)| alias_model(&models, alias, target))
+            .collect();
+        models.retain(|m| is_available(&m.id));
+        models.extend(aliases);
+    }
+
+    Json(ModelsResponse {
+        object: "list".to_string(),
+        data: models,
+    })
+}
+
+/// 为模型别名生成列表项（目标模型不在内置列表中时使用通用元数据）
+fn alias_model(models: &[Model], alias: &str, target: &str) -> Model {
+    let mut model = models
+        .iter()
+        .find(|m| m.id.eq_ignore_ascii_case(target))
+        .cloned()
+        .unwrap_or_else(|| Model {
+            id: String::new(),
+            object: "model".to_string(),
+            created: 0,
+            owned_by: "anthropic".to_string(),
+            display_name: target.to_string(),
+            model_type: "chat".to_string(),
+            max_tokens: 32000,
+        });
+    model.id = alias.to_string();
+    model.display_name = format!("{} (→ {})", alias, model.display_name);
+    model
+}
+
+/// 内置模型列表
+fn builtin_models() -> Vec<Model> {
+    vec![
         Model {
             id: "claude-sonnet-4-5-20250929".to_string(),
             object: "model".to_string(),
@@ -164,12 +410,7 @@ pub async fn get_models() -> impl IntoResponse {
             model_type: "chat".to_string(),
             max_tokens: 32000,
         },
-    ];
-
-    Json(ModelsResponse {
-        object: "list".to_string(),
-        data: models,
-    })
+    ]
 }
 
 /// POST /v1/messages
@@ -177,7 +418,10 @@ pub async fn get_models() -> impl IntoResponse {
 /// 创建消息（对话）
 pub async fn post_messages(
     State(state): State<AppState>,
-    JsonExtractor(mut payload): JsonExtractor<MessagesRequest>,
+    managed_key: Option<Extension<ManagedApiKey>>,
+    audit: Option<Extension<std::sync::Arc<AuditTracker>>>,
+    headers: HeaderMap,
+    ValidatedJson(mut payload): ValidatedJson<MessagesRequest>,
 ) -> Response {
     tracing::info!(
         model = %payload.model,
@@ -202,9 +446,39 @@ pub async fn post_messages(
         }
     };
 
+    // 消息数上限（在转换请求之前拒绝失控的 agent 循环）
+    let max_messages = provider.token_manager().config().max_messages;
+    if let Err(e) = check_message_count(payload.messages.len(), max_messages) {
+        return e.into_response();
+    }
+
+    // 响应元数据回显（x-kiro-meta）
+    let mut meta = kiro_meta::is_requested(&headers).then(KiroMeta::default);
+
+    // 模型别名映射（需在记录用量、选择凭据与转换请求前应用）
+    if let Some(from) = apply_model_alias(&provider, &mut payload)
+        && let Some(meta) = &mut meta
+    {
+        meta.set_model_remap(from, &payload.model);
+    }
+
+    // 请求级重试策略（x-kiro-max-retries / x-kiro-no-failover）
+    let mut routing = request_routing(&provider, &headers);
+    let cache_scope = managed_key
+        .as_ref()
+        .map(|Extension(ManagedApiKey(id))| id.clone());
+    let usage =
+        UsageRecorder::new(&provider, &state.api_keys, managed_key).with_audit(audit, &payload);
+
     // 检测模型名是否包含 "thinking" 后缀，若包含则覆写 thinking 配置
     override_thinking_from_model_name(&mut payload);
 
+    // 响应归档（需在消费 payload 前捕获请求内容）
+    let archive = state
+        .archive
+        .as_ref()
+        .and_then(|a| a.begin("/v1/messages", &headers, &payload));
+
     // 检查是否为 WebSearch 请求
     if websearch::has_web_search_tool(&payload) {
         tracing::info!("检测到 WebSearch 工具，路由到 WebSearch 处理");
@@ -217,9 +491,23 @@ pub async fn post_messages(
             payload.tools.clone(),
         ) as i32;
 
-        return websearch::handle_websearch_request(provider, &payload, input_tokens).await;
+        let response = websearch::handle_websearch_request(provider, &payload, input_tokens).await;
+        return usage_inject::attach(response, input_tokens);
     }
 
+    // 整理图片输入并计算响应缓存键
+    let cache_slot = match prepare_images_and_cache_slot(
+        &state,
+        &provider,
+        &mut payload,
+        cache_scope.as_deref(),
+    )
+    .await
+    {
+        Ok(slot) => slot,
+        Err(response) => return response,
+    };
+
     // 转换请求
     let conversion_result = match convert_request(&payload) {
         Ok(result) => result,
@@ -241,6 +529,10 @@ pub async fn post_messages(
         }
     };
 
+    if let Some(meta) = &mut meta {
+        meta.set_transformations(&conversion_result.transformations);
+    }
+
     // 构建 Kiro 请求
     let kiro_request = KiroRequest {
         conversation_state: conversion_result.conversation_state,
@@ -263,6 +555,10 @@ pub async fn post_messages(
     };
 
     tracing::debug!("Kiro request body: {}", request_body);
+    dev::print_request("Kiro request", &request_body);
+
+    // 停止序列与 max_tokens 由代理在本地执行
+    let limits = output_limits(&provider, &payload);
 
     // 估算输入 tokens
     let input_tokens = token::count_all_tokens(
@@ -271,6 +567,21 @@ pub async fn post_messages(
         payload.messages,
         payload.tools,
     ) as i32;
+    // 估算输入 tokens 上限（在调用上游之前拒绝，避免消耗凭据额度）
+    if let Err(e) = check_input_tokens(
+        input_tokens,
+        provider.token_manager().config().max_input_tokens,
+    ) {
+        return e.into_response();
+    }
+
+    // 响应缓存：相同的非流式请求在有效期内直接使用缓存响应（与上游响应同样经过后处理）
+    let cached = cache_slot.as_ref().and_then(CacheSlot::get);
+    if cached.is_none() {
+        usage.record_request_size(request_body.len());
+    }
+    // 配额感知准入按输入估算加上 max_tokens 作为本次请求的预计开销
+    routing.estimated_tokens = input_tokens.max(0) as u64 + payload.max_tokens.max(0) as u64;
 
     // 检查是否启用了thinking
     let thinking_enabled = payload
@@ -279,7 +590,30 @@ pub async fn post_messages(
         .map(|t| t.is_enabled())
         .unwrap_or(false);
 
-    if payload.stream {
+    // 调试捕获（Admin API 开启后生效）
+    let capture = capture::begin(
+        &headers,
+        CaptureMeta {
+            request_id: None,
+            captured_at: chrono::Utc::now(),
+            endpoint: "/v1/messages".to_string(),
+            model: payload.model.clone(),
+            stream: payload.stream,
+            thinking_enabled,
+            input_tokens,
+            headers: Default::default(),
+        },
+        &request_body,
+    );
+    let usage = usage.with_capture(capture.clone());
+
+    let response = if let Some(response) = cached {
+        tracing::info!("响应缓存命中");
+        if let Some(meta) = &mut meta {
+            meta.cache_hit = true;
+        }
+        response
+    } else if payload.stream {
         // 流式响应
         handle_stream_request(
             provider,
@@ -287,45 +621,264 @@ pub async fn post_messages(
             &payload.model,
             input_tokens,
             thinking_enabled,
+            limits,
+            &routing,
+            usage,
         )
         .await
     } else {
         // 非流式响应
-        handle_non_stream_request(provider, &request_body, &payload.model, input_tokens).await
-    }
+        let response = handle_non_stream_request(
+            provider,
+            &request_body,
+            &payload.model,
+            input_tokens,
+            thinking_enabled,
+            limits,
+            &routing,
+            usage,
+        )
+        .await;
+        match cache_slot {
+            Some(slot) => slot.store(response).await,
+            None => response,
+        }
+    };
+
+    let response = usage_inject::attach(response, input_tokens);
+    let response = kiro_meta::attach(response, meta).await;
+    let response = tee_to_capture(response, capture);
+    tee_to_archive(response, archive)
 }
 
 /// 处理流式请求
+#[allow(clippy::too_many_arguments)]
 async fn handle_stream_request(
     provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
     request_body: &str,
     model: &str,
     input_tokens: i32,
     thinking_enabled: bool,
+    limits: OutputLimits,
+    routing: &RequestRouting,
+    usage: UsageRecorder,
 ) -> Response {
     // 调用 Kiro API（支持多凭据故障转移）
-    let response = match provider.call_api_stream(request_body).await {
+    let response = match provider
+        .call_api_stream_with_policy(request_body, routing)
+        .await
+    {
         Ok(resp) => resp,
-        Err(e) => return map_provider_error(e),
+        Err(e) => return map_call_error(&provider, e, model, true),
     };
 
     // 创建流处理上下文
-    let mut ctx = StreamContext::new_with_thinking(model, input_tokens, thinking_enabled);
+    let config = provider.token_manager().config();
+    let mut ctx = StreamContext::new_with_thinking(model, input_tokens, thinking_enabled)
+        .with_thinking_output(config.thinking_output)
+        .with_output_limits(limits);
+    let usage = usage.with_credential(&response);
+    let attempts = response.extensions().get::<UpstreamAttempts>().copied();
 
     // 生成初始事件
     let initial_events = ctx.generate_initial_events();
 
     // 创建 SSE 流
-    let stream = create_sse_stream(response, ctx, initial_events);
+    let max_delta_bytes = config.sse_max_chunk_bytes;
+    let frames = upstream_frames(response, &provider, &usage);
+    let resumer = StreamResumer::new(&provider, request_body, routing);
+    let stream = create_sse_stream(frames, ctx, initial_events, usage, resumer, max_delta_bytes);
 
     // 返回 SSE 响应
-    Response::builder()
+    let response = Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "text/event-stream")
         .header(header::CACHE_CONTROL, "no-cache")
         .header(header::CONNECTION, "keep-alive")
         .body(Body::from_stream(stream))
-        .unwrap()
+        .unwrap();
+    with_upstream_attempts(response, attempts)
+}
+
+/// 将上游尝试统计写入下游响应扩展（供 kiro_meta 回显）
+fn with_upstream_attempts(mut response: Response, attempts: Option<UpstreamAttempts>) -> Response {
+    if let Some(attempts) = attempts {
+        response.extensions_mut().insert(attempts);
+    }
+    response
+}
+
+/// 将成功响应的响应体旁路写入归档（未启用归档时原样返回）
+fn tee_to_archive(response: Response, archive: Option<ArchiveTee>) -> Response {
+    let Some(mut archive) = archive else {
+        return response;
+    };
+    if !response.status().is_success() {
+        return response;
+    }
+
+    response.map(|body| {
+        Body::from_stream(body.into_data_stream().map(move |item| {
+            if let Ok(bytes) = &item {
+                archive.observe(bytes);
+            }
+            item
+        }))
+    })
+}
+
+/// 将响应体旁路写入请求捕获（未开启捕获时原样返回）
+fn tee_to_capture(response: Response, capture: Option<std::sync::Arc<CaptureSession>>) -> Response {
+    let Some(capture) = capture else {
+        return response;
+    };
+
+    response.map(|body| {
+        Body::from_stream(body.into_data_stream().map(move |item| {
+            if let Ok(bytes) = &item {
+                capture.downstream(bytes);
+            }
+            item
+        }))
+    })
+}
+
+/// 创建上游事件流解码器（启用 decodeErrorHexdump 时解析错误附带偏移与 hexdump，
+/// 启用 decodeCrcLenient 时 CRC 校验失败的帧照常处理，缓冲区上限取 decodeMaxBufferBytes）
+fn event_stream_decoder(provider: &KiroProvider) -> EventStreamDecoder {
+    let config = provider.token_manager().config();
+    EventStreamDecoder::new()
+        .with_error_context(config.decode_error_hexdump)
+        .with_lenient_crc(config.decode_crc_lenient)
+        .with_max_buffer_size(config.decode_max_buffer_bytes)
+}
+
+/// 上游事件流的帧
+type UpstreamFrames = BoxStream<'static, Result<Frame, ParseError>>;
+
+/// 按帧读取上游事件流（可恢复的解码错误由编解码器跳过），开启请求捕获时同时记录原始字节
+fn upstream_frames(
+    response: reqwest::Response,
+    provider: &KiroProvider,
+    usage: &UsageRecorder,
+) -> UpstreamFrames {
+    let capture = usage.capture.clone();
+    let body = response.bytes_stream().map(move |chunk| {
+        if let (Ok(chunk), Some(capture)) = (&chunk, &capture) {
+            capture.upstream(chunk);
+        }
+        chunk.map_err(std::io::Error::other)
+    });
+    let codec = EventStreamCodec::new(event_stream_decoder(provider));
+    FramedRead::new(StreamReader::new(body), codec).boxed()
+}
+
+/// token 用量记录器
+///
+/// 从上游响应扩展中读取处理本次请求的凭据 ID，响应结束后把 token 用量与上游延迟计入该凭据，
+/// 请求来自托管 API Key 时同时计入该 Key
+struct UsageRecorder {
+    provider: std::sync::Arc<KiroProvider>,
+    credential_id: Option<CredentialId>,
+    timing: Option<UpstreamTiming>,
+    api_key: Option<(std::sync::Arc<ApiKeyStore>, String)>,
+    audit: Option<std::sync::Arc<AuditTracker>>,
+    capture: Option<std::sync::Arc<CaptureSession>>,
+    /// 凭据并发许可（持有到响应结束，流式响应覆盖整个流）
+    _permit: Option<ConcurrencyPermit>,
+}
+
+impl UsageRecorder {
+    fn new(
+        provider: &std::sync::Arc<KiroProvider>,
+        api_keys: &std::sync::Arc<ApiKeyStore>,
+        managed_key: Option<Extension<ManagedApiKey>>,
+    ) -> Self {
+        Self {
+            provider: provider.clone(),
+            credential_id: None,
+            timing: None,
+            api_key: managed_key.map(|Extension(key)| (api_keys.clone(), key.0)),
+            audit: None,
+            capture: None,
+            _permit: None,
+        }
+    }
+
+    /// 绑定审计跟踪器（由审计中间件写入请求扩展），并记录请求模型
+    fn with_audit(
+        mut self,
+        audit: Option<Extension<std::sync::Arc<AuditTracker>>>,
+        payload: &MessagesRequest,
+    ) -> Self {
+        self.audit = audit.map(|Extension(tracker)| tracker);
+        if let Some(audit) = &self.audit {
+            audit.set_model(&payload.model, payload.stream);
+        }
+        self
+    }
+
+    /// 绑定请求捕获（上游原始字节随解码一并写入）
+    fn with_capture(mut self, capture: Option<std::sync::Arc<CaptureSession>>) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// 捕获一段上游原始字节（未开启捕获时忽略）
+    fn capture_upstream(&self, chunk: &[u8]) {
+        if let Some(capture) = &self.capture {
+            capture.upstream(chunk);
+        }
+    }
+
+    /// 输出被内容策略拦截时计入处理本次请求的凭据
+    fn record_content_filter(&self, filtered: bool) {
+        if let (true, Some(id)) = (filtered, self.credential_id) {
+            self.provider.token_manager().record_content_filter(id);
+        }
+    }
+
+    /// 记录发往上游的请求体大小（整体统计与审计记录）
+    fn record_request_size(&self, bytes: usize) {
+        self.provider.token_manager().record_request_size(bytes);
+        if let Some(audit) = &self.audit {
+            audit.set_request_bytes(bytes);
+        }
+    }
+
+    /// 绑定处理本次请求的凭据
+    fn with_credential(mut self, response: &reqwest::Response) -> Self {
+        self.credential_id = response.extensions().get::<CredentialId>().copied();
+        self.timing = response.extensions().get::<UpstreamTiming>().copied();
+        self._permit = response.extensions().get::<ConcurrencyPermit>().cloned();
+        if let (Some(audit), Some(id)) = (&self.audit, self.credential_id) {
+            audit.set_credential(id);
+        }
+        if let (Some(audit), Some(attempts)) =
+            (&self.audit, response.extensions().get::<UpstreamAttempts>())
+        {
+            audit.set_attempts(attempts.attempts, attempts.failovers);
+        }
+        self
+    }
+
+    /// 记录 (input_tokens, output_tokens)
+    fn record(&self, (input_tokens, output_tokens): (i32, i32)) {
+        if let Some(id) = self.credential_id {
+            let token_manager = self.provider.token_manager();
+            token_manager.record_token_usage(id, input_tokens, output_tokens);
+            if let Some(timing) = self.timing {
+                token_manager.record_latency(id, timing.ttfb, timing.started.elapsed());
+            }
+        }
+        if let Some((api_keys, id)) = &self.api_key {
+            let offset = self.provider.token_manager().config().timezone_offset();
+            api_keys.record_usage(id, input_tokens, output_tokens, offset);
+        }
+        if let Some(audit) = &self.audit {
+            audit.set_tokens(input_tokens, output_tokens);
+        }
+    }
 }
 
 /// Ping 事件间隔（25秒）
@@ -338,9 +891,12 @@ fn create_ping_sse() -> Bytes {
 
 /// 创建 SSE 事件流
 fn create_sse_stream(
-    response: reqwest::Response,
+    frames: UpstreamFrames,
     ctx: StreamContext,
     initial_events: Vec<SseEvent>,
+    usage: UsageRecorder,
+    resumer: Option<StreamResumer>,
+    max_delta_bytes: usize,
 ) -> impl Stream<Item = Result<Bytes, Infallible>> {
     // 先发送初始事件
     let initial_stream = stream::iter(
@@ -350,11 +906,9 @@ fn create_sse_stream(
     );
 
     // 然后处理 Kiro 响应流，同时每25秒发送 ping 保活
-    let body_stream = response.bytes_stream();
-
     let processing_stream = stream::unfold(
-        (body_stream, ctx, EventStreamDecoder::new(), false, interval(Duration::from_secs(PING_INTERVAL_SECS))),
-        |(mut body_stream, mut ctx, mut decoder, finished, mut ping_interval)| async move {
+        (frames, ctx, false, interval(Duration::from_secs(PING_INTERVAL_SECS)), usage, resumer),
+        move |(mut frames, mut ctx, finished, mut ping_interval, mut usage, mut resumer)| async move {
             if finished {
                 return None;
             }
@@ -362,55 +916,68 @@ fn create_sse_stream(
             // 使用 select! 同时等待数据和 ping 定时器
             tokio::select! {
                 // 处理数据流
-                chunk_result = body_stream.next() => {
-                    match chunk_result {
-                        Some(Ok(chunk)) => {
-                            // 解码事件
-                            if let Err(e) = decoder.feed(&chunk) {
-                                tracing::warn!("缓冲区溢出: {}", e);
-                            }
-
-                            let mut events = Vec::new();
-                            for result in decoder.decode_iter() {
-                                match result {
-                                    Ok(frame) => {
-                                        if let Ok(event) = Event::from_frame(frame) {
-                                            let sse_events = ctx.process_kiro_event(&event);
-                                            events.extend(sse_events);
-                                        }
-                                    }
-                                    Err(e) => {
-                                        tracing::warn!("解码事件失败: {}", e);
-                                    }
+                frame_result = frames.next() => {
+                    match frame_result {
+                        Some(Ok(frame)) => {
+                            let mut events = match Event::from_frame(frame) {
+                                Ok(event) => {
+                                    dev::print_event(&event);
+                                    ctx.process_kiro_event(&event)
                                 }
+                                Err(_) => Vec::new(),
+                            };
+
+                            // 命中停止序列或达到 max_tokens：结束输出并断开上游，避免继续消耗额度
+                            let limited = ctx.output_limit_reached();
+                            if limited {
+                                events.extend(ctx.generate_final_events());
+                                usage.record(ctx.token_usage());
+                                usage.record_content_filter(ctx.content_filtered);
+                                frames = stream::empty().boxed();
                             }
 
                             // 转换为 SSE 字节流
                             let bytes: Vec<Result<Bytes, Infallible>> = events
                                 .into_iter()
+                                .flat_map(|e| e.split_delta(max_delta_bytes))
                                 .map(|e| Ok(Bytes::from(e.to_sse_string())))
                                 .collect();
 
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)))
+                            Some((stream::iter(bytes), (frames, ctx, limited, ping_interval, usage, resumer)))
                         }
                         Some(Err(e)) => {
                             tracing::error!("读取响应流失败: {}", e);
+                            // 启用续传时换凭据接上新的上游流，下游无感知
+                            if let Some(r) = resumer.as_mut()
+                                && let Some(content) = ctx.resume_content().map(str::to_string)
+                                && let Some(response) = r.resume(usage.credential_id, &content).await
+                            {
+                                usage = usage.with_credential(&response);
+                                frames = upstream_frames(response, r.provider(), &usage);
+                                return Some((stream::iter(Vec::new()), (frames, ctx, false, ping_interval, usage, resumer)));
+                            }
                             // 发送最终事件并结束
                             let final_events = ctx.generate_final_events();
+                            usage.record(ctx.token_usage());
+                            usage.record_content_filter(ctx.content_filtered);
                             let bytes: Vec<Result<Bytes, Infallible>> = final_events
                                 .into_iter()
+                                .flat_map(|e| e.split_delta(max_delta_bytes))
                                 .map(|e| Ok(Bytes::from(e.to_sse_string())))
                                 .collect();
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)))
+                            Some((stream::iter(bytes), (frames, ctx, true, ping_interval, usage, resumer)))
                         }
                         None => {
                             // 流结束，发送最终事件
                             let final_events = ctx.generate_final_events();
+                            usage.record(ctx.token_usage());
+                            usage.record_content_filter(ctx.content_filtered);
                             let bytes: Vec<Result<Bytes, Infallible>> = final_events
                                 .into_iter()
+                                .flat_map(|e| e.split_delta(max_delta_bytes))
                                 .map(|e| Ok(Bytes::from(e.to_sse_string())))
                                 .collect();
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)))
+                            Some((stream::iter(bytes), (frames, ctx, true, ping_interval, usage, resumer)))
                         }
                     }
                 }
@@ -418,7 +985,7 @@ fn create_sse_stream(
                 _ = ping_interval.tick() => {
                     tracing::trace!("发送 ping 保活事件");
                     let bytes: Vec<Result<Bytes, Infallible>> = vec![Ok(create_ping_sse())];
-                    Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)))
+                    Some((stream::iter(bytes), (frames, ctx, false, ping_interval, usage, resumer)))
                 }
             }
         },
@@ -432,17 +999,27 @@ fn create_sse_stream(
 const CONTEXT_WINDOW_SIZE: i32 = 200_000;
 
 /// 处理非流式请求
+#[allow(clippy::too_many_arguments)]
 async fn handle_non_stream_request(
     provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
     request_body: &str,
     model: &str,
     input_tokens: i32,
+    thinking_enabled: bool,
+    limits: OutputLimits,
+    routing: &RequestRouting,
+    usage: UsageRecorder,
 ) -> Response {
     // 调用 Kiro API（支持多凭据故障转移）
-    let response = match provider.call_api(request_body).await {
+    let response = match provider
+        .call_api_with_policy(request_body, routing)
+        .await
+    {
         Ok(resp) => resp,
-        Err(e) => return map_provider_error(e),
+        Err(e) => return map_call_error(&provider, e, model, false),
     };
+    let usage = usage.with_credential(&response);
+    let attempts = response.extensions().get::<UpstreamAttempts>().copied();
 
     // 读取响应体
     let body_bytes = match response.bytes().await {
@@ -461,15 +1038,17 @@ async fn handle_non_stream_request(
     };
 
     // 解析事件流
-    let mut decoder = EventStreamDecoder::new();
-    if let Err(e) = decoder.feed(&body_bytes) {
-        tracing::warn!("缓冲区溢出: {}", e);
-    }
+    usage.capture_upstream(&body_bytes);
+    let codec = EventStreamCodec::new(event_stream_decoder(&provider));
+    let mut frames = FramedRead::new(&body_bytes[..], codec);
 
     let mut text_content = String::new();
+    let mut reasoning_content = String::new();
+    let mut reasoning_signature: Option<String> = None;
     let mut tool_uses: Vec<serde_json::Value> = Vec::new();
     let mut has_tool_use = false;
     let mut stop_reason = "end_turn".to_string();
+    let mut content_filtered = false;
     // 从 contextUsageEvent 计算的实际输入 tokens
     let mut context_input_tokens: Option<i32> = None;
 
@@ -477,14 +1056,26 @@ async fn handle_non_stream_request(
     let mut tool_json_buffers: std::collections::HashMap<String, String> =
         std::collections::HashMap::new();
 
-    for result in decoder.decode_iter() {
+    while let Some(result) = frames.next().await {
         match result {
             Ok(frame) => {
                 if let Ok(event) = Event::from_frame(frame) {
+                    dev::print_event(&event);
+                    if event.is_content_filter() {
+                        tracing::warn!("输出被上游内容策略拦截: {:?}", event);
+                        content_filtered = true;
+                        continue;
+                    }
                     match event {
                         Event::AssistantResponse(resp) => {
                             text_content.push_str(&resp.content);
                         }
+                        Event::ReasoningContent(reasoning) => {
+                            reasoning_content.push_str(&reasoning.text);
+                            if reasoning.signature.is_some() {
+                                reasoning_signature = reasoning.signature;
+                            }
+                        }
                         Event::ToolUse(tool_use) => {
                             has_tool_use = true;
 
@@ -551,13 +1142,52 @@ async fn handle_non_stream_request(
     }
 
     // 确定 stop_reason
-    if has_tool_use && stop_reason == "end_turn" {
+    if content_filtered {
+        stop_reason = "refusal".to_string();
+    } else if has_tool_use && stop_reason == "end_turn" {
         stop_reason = "tool_use".to_string();
     }
 
+    // 推理内容：上游 reasoningContentEvent，或启用 thinking 时正文开头的 <thinking> 块
+    if reasoning_content.is_empty()
+        && thinking_enabled
+        && let Some((thinking, rest)) = split_thinking(&text_content)
+    {
+        reasoning_content = thinking.to_string();
+        text_content = rest.to_string();
+    }
+
+    // 本地执行停止序列与 max_tokens：输出在此结束，其后的工具调用一并丢弃
+    let mut stop_sequence: Option<String> = None;
+    if !content_filtered
+        && let Some((reason, sequence)) =
+            limit_non_stream_output(limits, &mut reasoning_content, &mut text_content)
+    {
+        stop_reason = reason.to_string();
+        stop_sequence = sequence;
+        tool_uses.clear();
+    }
+
     // 构建响应内容
     let mut content: Vec<serde_json::Value> = Vec::new();
 
+    if !reasoning_content.is_empty() {
+        match provider.token_manager().config().thinking_output {
+            ThinkingOutput::Passthrough => content.push(json!({
+                "type": "thinking",
+                "thinking": reasoning_content,
+                "signature": reasoning_signature.unwrap_or_default()
+            })),
+            ThinkingOutput::Tags => {
+                text_content = format!(
+                    "<thinking>\n{}\n</thinking>\n\n{}",
+                    reasoning_content, text_content
+                );
+            }
+            ThinkingOutput::Strip => {}
+        }
+    }
+
     if !text_content.is_empty() {
         content.push(json!({
             "type": "text",
@@ -572,6 +1202,8 @@ async fn handle_non_stream_request(
 
     // 使用从 contextUsageEvent 计算的 input_tokens，如果没有则使用估算值
     let final_input_tokens = context_input_tokens.unwrap_or(input_tokens);
+    usage.record((final_input_tokens, output_tokens));
+    usage.record_content_filter(content_filtered);
 
     // 构建 Anthropic 响应
     let response_body = json!({
@@ -581,14 +1213,121 @@ async fn handle_non_stream_request(
         "content": content,
         "model": model,
         "stop_reason": stop_reason,
-        "stop_sequence": null,
+        "stop_sequence": stop_sequence,
         "usage": {
             "input_tokens": final_input_tokens,
+            "cache_creation_input_tokens": 0,
+            "cache_read_input_tokens": 0,
             "output_tokens": output_tokens
         }
     });
 
-    (StatusCode::OK, Json(response_body)).into_response()
+    with_upstream_attempts((StatusCode::OK, Json(response_body)).into_response(), attempts)
+}
+
+/// 对非流式响应执行本地输出限制，输出被截断时返回 (stop_reason, stop_sequence)
+fn limit_non_stream_output(
+    limits: OutputLimits,
+    reasoning: &mut String,
+    text: &mut String,
+) -> Option<(&'static str, Option<String>)> {
+    let mut limited = None;
+
+    let mut matcher = StopSequenceMatcher::new(limits.stop_sequences);
+    let kept = matcher.push(text);
+    if let Some(sequence) = matcher.matched() {
+        limited = Some(("stop_sequence", Some(sequence.to_string())));
+        *text = kept;
+    }
+
+    if let Some(max_tokens) = limits.max_tokens {
+        let reasoning_len = truncate_to_tokens(reasoning, max_tokens).len();
+        let budget = max_tokens - estimate_tokens(&reasoning[..reasoning_len]);
+        let text_len = truncate_to_tokens(text, budget).len();
+        if reasoning_len < reasoning.len() || text_len < text.len() {
+            reasoning.truncate(reasoning_len);
+            text.truncate(text_len);
+            limited = Some(("max_tokens", None));
+        }
+    }
+    limited
+}
+
+/// 需要由代理在本地执行的输出限制（max_tokens 受 enforceMaxTokens 控制）
+fn output_limits(provider: &KiroProvider, payload: &MessagesRequest) -> OutputLimits {
+    let config = provider.token_manager().config();
+    OutputLimits {
+        stop_sequences: payload.stop_sequences.clone().unwrap_or_default(),
+        max_tokens: config.enforce_max_tokens.then_some(payload.max_tokens),
+    }
+}
+
+/// 按 modelAliases 配置将请求模型名替换为实际模型名，发生映射时返回原模型名
+fn apply_model_alias(provider: &KiroProvider, payload: &mut MessagesRequest) -> Option<String> {
+    let config = provider.token_manager().config();
+    let target = config.resolve_model_alias(&payload.model)?;
+    tracing::debug!("模型别名映射: {} -> {}", payload.model, target);
+    Some(std::mem::replace(&mut payload.model, target.to_string()))
+}
+
+/// 整理请求中的图片，失败时返回 400 响应
+/// 非流式请求在响应缓存中的位置
+struct CacheSlot<'a> {
+    cache: &'a ResponseCache,
+    key: CacheKey,
+}
+
+impl CacheSlot<'_> {
+    fn get(&self) -> Option<Response> {
+        self.cache.get(&self.key)
+    }
+
+    async fn store(self, response: Response) -> Response {
+        self.cache.store(self.key, response).await
+    }
+}
+
+/// 整理图片输入，再计算响应缓存键（非流式请求，按托管 API Key 隔离）
+///
+/// 缓存键在 URL 图片下载之后计算，按图片内容而非 URL 区分请求，
+/// 同一 URL 的图片变化后不会命中旧响应
+async fn prepare_images_and_cache_slot<'a>(
+    state: &'a AppState,
+    provider: &KiroProvider,
+    payload: &mut MessagesRequest,
+    cache_scope: Option<&str>,
+) -> Result<Option<CacheSlot<'a>>, Response> {
+    prepare_request_images(provider, payload).await?;
+    Ok(state
+        .response_cache
+        .as_deref()
+        .filter(|_| !payload.stream)
+        .map(|cache| CacheSlot {
+            cache,
+            key: ResponseCache::key(payload, cache_scope),
+        }))
+}
+
+async fn prepare_request_images(
+    provider: &KiroProvider,
+    payload: &mut MessagesRequest,
+) -> Result<(), Response> {
+    let config = provider.token_manager().config();
+    match image::prepare_images(&config, &payload.model, &mut payload.messages).await {
+        Ok(0) => Ok(()),
+        Ok(count) => {
+            tracing::debug!("请求包含 {} 张图片", count);
+            Ok(())
+        }
+        Err(e) => {
+            tracing::warn!("图片输入处理失败: {}", e);
+            Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new("invalid_request_error", e.to_string())),
+            )
+                .into_response())
+        }
+    }
 }
 
 /// 检测模型名是否包含 "thinking" 后缀，若包含则覆写 thinking 配置
@@ -633,7 +1372,7 @@ fn override_thinking_from_model_name(payload: &mut MessagesRequest) {
 ///
 /// 计算消息的 token 数量
 pub async fn count_tokens(
-    JsonExtractor(payload): JsonExtractor<CountTokensRequest>,
+    ValidatedJson(payload): ValidatedJson<CountTokensRequest>,
 ) -> impl IntoResponse {
     tracing::info!(
         model = %payload.model,
@@ -660,7 +1399,10 @@ pub async fn count_tokens(
 /// - message_start 中的 input_tokens 是从 contextUsageEvent 计算的准确值
 pub async fn post_messages_cc(
     State(state): State<AppState>,
-    JsonExtractor(mut payload): JsonExtractor<MessagesRequest>,
+    managed_key: Option<Extension<ManagedApiKey>>,
+    audit: Option<Extension<std::sync::Arc<AuditTracker>>>,
+    headers: HeaderMap,
+    ValidatedJson(mut payload): ValidatedJson<MessagesRequest>,
 ) -> Response {
     tracing::info!(
         model = %payload.model,
@@ -686,9 +1428,39 @@ pub async fn post_messages_cc(
         }
     };
 
+    // 消息数上限（在转换请求之前拒绝失控的 agent 循环）
+    let max_messages = provider.token_manager().config().max_messages;
+    if let Err(e) = check_message_count(payload.messages.len(), max_messages) {
+        return e.into_response();
+    }
+
+    // 响应元数据回显（x-kiro-meta）
+    let mut meta = kiro_meta::is_requested(&headers).then(KiroMeta::default);
+
+    // 模型别名映射（需在记录用量、选择凭据与转换请求前应用）
+    if let Some(from) = apply_model_alias(&provider, &mut payload)
+        && let Some(meta) = &mut meta
+    {
+        meta.set_model_remap(from, &payload.model);
+    }
+
+    // 请求级重试策略（x-kiro-max-retries / x-kiro-no-failover）
+    let mut routing = request_routing(&provider, &headers);
+    let cache_scope = managed_key
+        .as_ref()
+        .map(|Extension(ManagedApiKey(id))| id.clone());
+    let usage =
+        UsageRecorder::new(&provider, &state.api_keys, managed_key).with_audit(audit, &payload);
+
     // 检测模型名是否包含 "thinking" 后缀，若包含则覆写 thinking 配置
     override_thinking_from_model_name(&mut payload);
 
+    // 响应归档（需在消费 payload 前捕获请求内容）
+    let archive = state
+        .archive
+        .as_ref()
+        .and_then(|a| a.begin("/cc/v1/messages", &headers, &payload));
+
     // 检查是否为 WebSearch 请求
     if websearch::has_web_search_tool(&payload) {
         tracing::info!("检测到 WebSearch 工具，路由到 WebSearch 处理");
@@ -701,9 +1473,23 @@ pub async fn post_messages_cc(
             payload.tools.clone(),
         ) as i32;
 
-        return websearch::handle_websearch_request(provider, &payload, input_tokens).await;
+        let response = websearch::handle_websearch_request(provider, &payload, input_tokens).await;
+        return usage_inject::attach(response, input_tokens);
     }
 
+    // 整理图片输入并计算响应缓存键
+    let cache_slot = match prepare_images_and_cache_slot(
+        &state,
+        &provider,
+        &mut payload,
+        cache_scope.as_deref(),
+    )
+    .await
+    {
+        Ok(slot) => slot,
+        Err(response) => return response,
+    };
+
     // 转换请求
     let conversion_result = match convert_request(&payload) {
         Ok(result) => result,
@@ -725,6 +1511,10 @@ pub async fn post_messages_cc(
         }
     };
 
+    if let Some(meta) = &mut meta {
+        meta.set_transformations(&conversion_result.transformations);
+    }
+
     // 构建 Kiro 请求
     let kiro_request = KiroRequest {
         conversation_state: conversion_result.conversation_state,
@@ -747,6 +1537,10 @@ pub async fn post_messages_cc(
     };
 
     tracing::debug!("Kiro request body: {}", request_body);
+    dev::print_request("Kiro request", &request_body);
+
+    // 停止序列与 max_tokens 由代理在本地执行
+    let limits = output_limits(&provider, &payload);
 
     // 估算输入 tokens
     let input_tokens = token::count_all_tokens(
@@ -755,6 +1549,21 @@ pub async fn post_messages_cc(
         payload.messages,
         payload.tools,
     ) as i32;
+    // 估算输入 tokens 上限（在调用上游之前拒绝，避免消耗凭据额度）
+    if let Err(e) = check_input_tokens(
+        input_tokens,
+        provider.token_manager().config().max_input_tokens,
+    ) {
+        return e.into_response();
+    }
+
+    // 响应缓存：相同的非流式请求在有效期内直接使用缓存响应（与上游响应同样经过后处理）
+    let cached = cache_slot.as_ref().and_then(CacheSlot::get);
+    if cached.is_none() {
+        usage.record_request_size(request_body.len());
+    }
+    // 配额感知准入按输入估算加上 max_tokens 作为本次请求的预计开销
+    routing.estimated_tokens = input_tokens.max(0) as u64 + payload.max_tokens.max(0) as u64;
 
     // 检查是否启用了thinking
     let thinking_enabled = payload
@@ -763,7 +1572,30 @@ pub async fn post_messages_cc(
         .map(|t| t.is_enabled())
         .unwrap_or(false);
 
-    if payload.stream {
+    // 调试捕获（Admin API 开启后生效）
+    let capture = capture::begin(
+        &headers,
+        CaptureMeta {
+            request_id: None,
+            captured_at: chrono::Utc::now(),
+            endpoint: "/cc/v1/messages".to_string(),
+            model: payload.model.clone(),
+            stream: payload.stream,
+            thinking_enabled,
+            input_tokens,
+            headers: Default::default(),
+        },
+        &request_body,
+    );
+    let usage = usage.with_capture(capture.clone());
+
+    let response = if let Some(response) = cached {
+        tracing::info!("响应缓存命中");
+        if let Some(meta) = &mut meta {
+            meta.cache_hit = true;
+        }
+        response
+    } else if payload.stream {
         // 流式响应（缓冲模式）
         handle_stream_request_buffered(
             provider,
@@ -771,45 +1603,83 @@ pub async fn post_messages_cc(
             &payload.model,
             input_tokens,
             thinking_enabled,
+            limits,
+            &routing,
+            usage,
         )
         .await
     } else {
         // 非流式响应（复用现有逻辑，已经使用正确的 input_tokens）
-        handle_non_stream_request(provider, &request_body, &payload.model, input_tokens).await
-    }
+        let response = handle_non_stream_request(
+            provider,
+            &request_body,
+            &payload.model,
+            input_tokens,
+            thinking_enabled,
+            limits,
+            &routing,
+            usage,
+        )
+        .await;
+        match cache_slot {
+            Some(slot) => slot.store(response).await,
+            None => response,
+        }
+    };
+
+    let response = usage_inject::attach(response, input_tokens);
+    let response = kiro_meta::attach(response, meta).await;
+    let response = tee_to_capture(response, capture);
+    tee_to_archive(response, archive)
 }
 
 /// 处理流式请求（缓冲版本）
 ///
 /// 与 `handle_stream_request` 不同，此函数会缓冲所有事件直到流结束，
 /// 然后用从 contextUsageEvent 计算的正确 input_tokens 生成 message_start 事件。
+#[allow(clippy::too_many_arguments)]
 async fn handle_stream_request_buffered(
     provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
     request_body: &str,
     model: &str,
     estimated_input_tokens: i32,
     thinking_enabled: bool,
+    limits: OutputLimits,
+    routing: &RequestRouting,
+    usage: UsageRecorder,
 ) -> Response {
     // 调用 Kiro API（支持多凭据故障转移）
-    let response = match provider.call_api_stream(request_body).await {
+    let response = match provider
+        .call_api_stream_with_policy(request_body, routing)
+        .await
+    {
         Ok(resp) => resp,
-        Err(e) => return map_provider_error(e),
+        Err(e) => return map_call_error(&provider, e, model, true),
     };
 
     // 创建缓冲流处理上下文
-    let ctx = BufferedStreamContext::new(model, estimated_input_tokens, thinking_enabled);
+    let config = provider.token_manager().config();
+    let ctx = BufferedStreamContext::new(model, estimated_input_tokens, thinking_enabled)
+        .with_thinking_output(config.thinking_output)
+        .with_output_limits(limits);
+    let usage = usage.with_credential(&response);
+    let attempts = response.extensions().get::<UpstreamAttempts>().copied();
 
     // 创建缓冲 SSE 流
-    let stream = create_buffered_sse_stream(response, ctx);
+    let max_delta_bytes = config.sse_max_chunk_bytes;
+    let frames = upstream_frames(response, &provider, &usage);
+    let resumer = StreamResumer::new(&provider, request_body, routing);
+    let stream = create_buffered_sse_stream(frames, ctx, usage, resumer, max_delta_bytes);
 
     // 返回 SSE 响应
-    Response::builder()
+    let response = Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "text/event-stream")
         .header(header::CACHE_CONTROL, "no-cache")
         .header(header::CONNECTION, "keep-alive")
         .body(Body::from_stream(stream))
-        .unwrap()
+        .unwrap();
+    with_upstream_attempts(response, attempts)
 }
 
 /// 创建缓冲 SSE 事件流
@@ -820,20 +1690,22 @@ async fn handle_stream_request_buffered(
 /// 3. 流结束后，用正确的 input_tokens 更正 message_start 事件
 /// 4. 一次性发送所有事件
 fn create_buffered_sse_stream(
-    response: reqwest::Response,
+    frames: UpstreamFrames,
     ctx: BufferedStreamContext,
+    usage: UsageRecorder,
+    resumer: Option<StreamResumer>,
+    max_delta_bytes: usize,
 ) -> impl Stream<Item = Result<Bytes, Infallible>> {
-    let body_stream = response.bytes_stream();
-
     stream::unfold(
         (
-            body_stream,
+            frames,
             ctx,
-            EventStreamDecoder::new(),
             false,
             interval(Duration::from_secs(PING_INTERVAL_SECS)),
+            usage,
+            resumer,
         ),
-        |(mut body_stream, mut ctx, mut decoder, finished, mut ping_interval)| async move {
+        move |(mut frames, mut ctx, finished, mut ping_interval, mut usage, mut resumer)| async move {
             if finished {
                 return None;
             }
@@ -848,51 +1720,66 @@ fn create_buffered_sse_stream(
                     _ = ping_interval.tick() => {
                         tracing::trace!("发送 ping 保活事件（缓冲模式）");
                         let bytes: Vec<Result<Bytes, Infallible>> = vec![Ok(create_ping_sse())];
-                        return Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)));
+                        return Some((stream::iter(bytes), (frames, ctx, false, ping_interval, usage, resumer)));
                     }
 
                     // 然后处理数据流
-                    chunk_result = body_stream.next() => {
-                        match chunk_result {
-                            Some(Ok(chunk)) => {
-                                // 解码事件
-                                if let Err(e) = decoder.feed(&chunk) {
-                                    tracing::warn!("缓冲区溢出: {}", e);
+                    frame_result = frames.next() => {
+                        match frame_result {
+                            Some(Ok(frame)) => {
+                                if let Ok(event) = Event::from_frame(frame) {
+                                    dev::print_event(&event);
+                                    // 缓冲事件（复用 StreamContext 的处理逻辑）
+                                    ctx.process_and_buffer(&event);
                                 }
-
-                                for result in decoder.decode_iter() {
-                                    match result {
-                                        Ok(frame) => {
-                                            if let Ok(event) = Event::from_frame(frame) {
-                                                // 缓冲事件（复用 StreamContext 的处理逻辑）
-                                                ctx.process_and_buffer(&event);
-                                            }
-                                        }
-                                        Err(e) => {
-                                            tracing::warn!("解码事件失败: {}", e);
-                                        }
-                                    }
+                                // 命中停止序列或达到 max_tokens：断开上游并返回所有事件
+                                if ctx.output_limit_reached() {
+                                    let all_events = ctx.finish_and_get_all_events();
+                                    usage.record(ctx.token_usage());
+                                    usage.record_content_filter(ctx.content_filtered());
+                                    let bytes: Vec<Result<Bytes, Infallible>> = all_events
+                                        .into_iter()
+                                        .flat_map(|e| e.split_delta(max_delta_bytes))
+                                        .map(|e| Ok(Bytes::from(e.to_sse_string())))
+                                        .collect();
+                                    let frames: UpstreamFrames = stream::empty().boxed();
+                                    return Some((stream::iter(bytes), (frames, ctx, true, ping_interval, usage, resumer)));
                                 }
-                                // 继续读取下一个 chunk，不发送任何数据
+                                // 继续读取下一帧，不发送任何数据
                             }
                             Some(Err(e)) => {
                                 tracing::error!("读取响应流失败: {}", e);
+                                // 启用续传时换凭据接上新的上游流，继续缓冲
+                                if let Some(r) = resumer.as_mut()
+                                    && let Some(content) = ctx.resume_content().map(str::to_string)
+                                    && let Some(response) = r.resume(usage.credential_id, &content).await
+                                {
+                                    usage = usage.with_credential(&response);
+                                    frames = upstream_frames(response, r.provider(), &usage);
+                                    continue;
+                                }
                                 // 发生错误，完成处理并返回所有事件
                                 let all_events = ctx.finish_and_get_all_events();
+                                usage.record(ctx.token_usage());
+                                usage.record_content_filter(ctx.content_filtered());
                                 let bytes: Vec<Result<Bytes, Infallible>> = all_events
                                     .into_iter()
+                                    .flat_map(|e| e.split_delta(max_delta_bytes))
                                     .map(|e| Ok(Bytes::from(e.to_sse_string())))
                                     .collect();
-                                return Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)));
+                                return Some((stream::iter(bytes), (frames, ctx, true, ping_interval, usage, resumer)));
                             }
                             None => {
                                 // 流结束，完成处理并返回所有事件（已更正 input_tokens）
                                 let all_events = ctx.finish_and_get_all_events();
+                                usage.record(ctx.token_usage());
+                                usage.record_content_filter(ctx.content_filtered());
                                 let bytes: Vec<Result<Bytes, Infallible>> = all_events
                                     .into_iter()
+                                    .flat_map(|e| e.split_delta(max_delta_bytes))
                                     .map(|e| Ok(Bytes::from(e.to_sse_string())))
                                     .collect();
-                                return Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)));
+                                return Some((stream::iter(bytes), (frames, ctx, true, ping_interval, usage, resumer)));
                             }
                         }
                     }
@@ -0,0 +1,452 @@
+//! 图片输入预处理
+//!
+//! 在转换为 Kiro 请求前统一整理消息中的图片：
+//! - OpenAI 风格的 `image_url` 块改写为 Anthropic `image` 块
+//! - `source.type = "url"` 的图片由服务端下载后改为 base64（上游只接受内联字节）。
+//!   下载默认关闭；开启后只访问解析到公网地址的 http(s) 地址，重定向的每一跳重新校验，
+//!   响应体超过 `maxImageBytes` 时立即中止读取
+//! - 按文件头识别真实格式并修正错误的 `media_type`，拒绝不支持的格式与超大图片
+//!
+//! 服务端没有图片编解码能力，超过 `maxImageBytes` 的图片无法缩放，只能返回 400 让客户端压缩后重试
+
+use std::net::{IpAddr, SocketAddr};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use reqwest::{Client, Response, Url, header, redirect};
+use serde_json::{Value, json};
+
+use crate::http_client::{TlsConfig, client_builder};
+use crate::model::config::Config;
+
+use super::types::Message;
+
+/// 下载 URL 图片的超时时间（秒）
+const FETCH_TIMEOUT_SECS: u64 = 30;
+/// 下载 URL 图片时最多跟随的重定向次数
+const MAX_REDIRECTS: usize = 5;
+
+/// 图片预处理错误（均为客户端请求问题，映射为 400）
+#[derive(Debug)]
+pub enum ImageError {
+    /// 模型不支持图片输入
+    VisionUnsupported(String),
+    /// 未开启 URL 图片下载
+    UrlFetchDisabled,
+    /// URL 图片下载失败
+    Fetch { url: String, reason: String },
+    /// base64 数据无法解码
+    InvalidData,
+    /// 不支持的图片格式
+    UnsupportedFormat(String),
+    /// 图片超过大小上限
+    TooLarge { bytes: u64, limit: u64 },
+}
+
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageError::VisionUnsupported(model) => {
+                write!(f, "模型 {} 不支持图片输入，请移除图片或改用其他模型", model)
+            }
+            ImageError::UrlFetchDisabled => {
+                write!(f, "未启用 URL 图片下载，请以 base64 形式提供图片")
+            }
+            ImageError::Fetch { url, reason } => write!(f, "下载图片失败 ({}): {}", url, reason),
+            ImageError::InvalidData => write!(f, "图片 base64 数据无效"),
+            ImageError::UnsupportedFormat(media_type) => write!(
+                f,
+                "不支持的图片格式: {}（仅支持 jpeg、png、gif、webp）",
+                media_type
+            ),
+            ImageError::TooLarge { bytes, limit } => write!(
+                f,
+                "图片大小 {} 字节超过上限 {} 字节，请压缩或缩小后重试",
+                bytes, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// 将 OpenAI `image_url` 地址转换为 Anthropic `image` 块（data URL 内联，其余按 URL 图片处理）
+pub fn image_url_block(url: &str) -> Value {
+    let source = match url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+    {
+        Some((media_type, data)) => {
+            json!({"type": "base64", "media_type": media_type, "data": data})
+        }
+        None => json!({"type": "url", "url": url}),
+    };
+    json!({"type": "image", "source": source})
+}
+
+/// 整理请求中的图片，返回图片数量
+pub async fn prepare_images(
+    config: &Config,
+    model: &str,
+    messages: &mut [Message],
+) -> Result<usize, ImageError> {
+    let mut images = Vec::new();
+    for message in messages.iter_mut() {
+        let Value::Array(blocks) = &mut message.content else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            match block["type"].as_str() {
+                Some("image_url") => {
+                    let url = match &block["image_url"] {
+                        Value::String(url) => url.clone(),
+                        other => other["url"].as_str().unwrap_or_default().to_string(),
+                    };
+                    *block = image_url_block(&url);
+                    images.push(block);
+                }
+                Some("image") => images.push(block),
+                _ => {}
+            }
+        }
+    }
+
+    if images.is_empty() {
+        return Ok(0);
+    }
+    if !config.supports_vision(model) {
+        return Err(ImageError::VisionUnsupported(model.to_string()));
+    }
+
+    let count = images.len();
+    for block in images {
+        let source = &mut block["source"];
+        let bytes = match source["type"].as_str() {
+            Some("url") => {
+                let url = source["url"].as_str().unwrap_or_default().to_string();
+                fetch_image(config, &url).await?
+            }
+            _ => {
+                let data = source["data"].as_str().unwrap_or_default();
+                STANDARD
+                    .decode(data.trim())
+                    .map_err(|_| ImageError::InvalidData)?
+            }
+        };
+
+        if bytes.len() as u64 > config.max_image_bytes {
+            return Err(ImageError::TooLarge {
+                bytes: bytes.len() as u64,
+                limit: config.max_image_bytes,
+            });
+        }
+
+        let declared = source["media_type"].as_str().unwrap_or_default();
+        let media_type = match sniff_media_type(&bytes) {
+            Some(detected) => {
+                if !declared.is_empty() && declared != detected {
+                    tracing::debug!("图片 media_type 声明为 {}，实际为 {}", declared, detected);
+                }
+                detected
+            }
+            None => return Err(ImageError::UnsupportedFormat(declared.to_string())),
+        };
+
+        if source["type"] != "base64" || source["media_type"] != media_type {
+            *source = json!({
+                "type": "base64",
+                "media_type": media_type,
+                "data": STANDARD.encode(&bytes),
+            });
+        }
+    }
+    Ok(count)
+}
+
+/// 下载 URL 图片（仅 http/https 公网地址，手动跟随重定向并逐跳校验）
+async fn fetch_image(config: &Config, url: &str) -> Result<Vec<u8>, ImageError> {
+    if !config.image_url_fetch {
+        return Err(ImageError::UrlFetchDisabled);
+    }
+    let fetch_error = |reason: String| ImageError::Fetch {
+        url: url.to_string(),
+        reason,
+    };
+
+    let mut target = Url::parse(url).map_err(|e| fetch_error(e.to_string()))?;
+    for _ in 0..=MAX_REDIRECTS {
+        let client = pinned_client(config, &target).await.map_err(&fetch_error)?;
+        let response = client
+            .get(target.clone())
+            .send()
+            .await
+            .map_err(|e| fetch_error(e.to_string()))?;
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| fetch_error(format!("HTTP {} 缺少 Location", response.status())))?;
+            target = target
+                .join(location)
+                .map_err(|e| fetch_error(e.to_string()))?;
+            continue;
+        }
+        if !response.status().is_success() {
+            return Err(fetch_error(format!("HTTP {}", response.status())));
+        }
+        return read_limited(response, config.max_image_bytes)
+            .await
+            .map_err(|e| match e {
+                ReadError::TooLarge(bytes) => ImageError::TooLarge {
+                    bytes,
+                    limit: config.max_image_bytes,
+                },
+                ReadError::Http(e) => fetch_error(e.to_string()),
+            });
+    }
+    Err(fetch_error(format!("重定向超过 {} 次", MAX_REDIRECTS)))
+}
+
+/// 为单次请求构建客户端：校验地址只解析到公网 IP，并把连接固定到校验过的地址（防止 DNS 重绑定）
+async fn pinned_client(config: &Config, url: &Url) -> Result<Client, String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("仅支持 http/https 地址".to_string());
+    }
+    let host = url.host_str().ok_or("缺少主机名")?;
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let literal = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .ok();
+    let addrs: Vec<SocketAddr> = match literal {
+        Some(ip) => vec![SocketAddr::new(ip, port)],
+        None => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("解析主机名失败: {}", e))?
+            .collect(),
+    };
+    if addrs.is_empty() {
+        return Err("主机名没有可用地址".to_string());
+    }
+    if let Some(addr) = addrs.iter().find(|a| !is_public_ip(a.ip())) {
+        return Err(format!("拒绝访问本机、内网或保留地址 {}", addr.ip()));
+    }
+
+    let mut builder = client_builder(
+        config.proxy_config().as_ref(),
+        FETCH_TIMEOUT_SECS,
+        &TlsConfig::new(config.tls_backend),
+    )
+    .map_err(|e| e.to_string())?
+    .redirect(redirect::Policy::none());
+    if literal.is_none() {
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// 是否为可以访问的公网地址（拒绝回环、私有、链路本地、未指定等地址）
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                // 100.64.0.0/10（运营商级 NAT）与 0.0.0.0/8
+                || (a == 100 && (b & 0xC0) == 64)
+                || a == 0)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // fc00::/7（唯一本地地址）与 fe80::/10（链路本地地址）
+                || (first & 0xFE00) == 0xFC00
+                || (first & 0xFFC0) == 0xFE80)
+        }
+    }
+}
+
+/// 读取响应体失败的原因
+enum ReadError {
+    /// 已读取的字节数超过上限
+    TooLarge(u64),
+    Http(reqwest::Error),
+}
+
+/// 分块读取响应体，超过 `limit` 字节时立即中止（不依赖 Content-Length）
+async fn read_limited(mut response: Response, limit: u64) -> Result<Vec<u8>, ReadError> {
+    if let Some(length) = response.content_length()
+        && length > limit
+    {
+        return Err(ReadError::TooLarge(length));
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(ReadError::Http)? {
+        let total = (bytes.len() + chunk.len()) as u64;
+        if total > limit {
+            return Err(ReadError::TooLarge(total));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// 按文件头识别图片格式（仅限上游支持的 jpeg/png/gif/webp）
+fn sniff_media_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_HEADER: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    fn user_message(content: Value) -> Message {
+        Message {
+            role: "user".to_string(),
+            content,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prepare_images_normalizes_and_validates() {
+        let config = Config::default();
+        let data = STANDARD.encode(PNG_HEADER);
+        let mut messages = vec![user_message(json!([
+            {"type": "text", "text": "看图"},
+            {"type": "image_url", "image_url": {"url": format!("data:image/jpeg;base64,{}", data)}},
+        ]))];
+
+        let count = prepare_images(&config, "claude-sonnet-4-6", &mut messages)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        // image_url 改写为 image 块，且按文件头修正 media_type
+        assert_eq!(
+            messages[0].content[1],
+            json!({
+                "type": "image",
+                "source": {"type": "base64", "media_type": "image/png", "data": data},
+            })
+        );
+
+        let mut bmp = vec![user_message(json!([{
+            "type": "image",
+            "source": {"type": "base64", "media_type": "image/bmp", "data": STANDARD.encode(b"BM\0\0")},
+        }]))];
+        assert!(matches!(
+            prepare_images(&config, "claude-sonnet-4-6", &mut bmp).await,
+            Err(ImageError::UnsupportedFormat(_))
+        ));
+
+        let mut small = Config::default();
+        small.max_image_bytes = 4;
+        small.image_url_fetch = false;
+        assert!(matches!(
+            prepare_images(&small, "claude-sonnet-4-6", &mut messages).await,
+            Err(ImageError::TooLarge { limit: 4, .. })
+        ));
+
+        let mut remote = vec![user_message(json!([
+            {"type": "image", "source": {"type": "url", "url": "https://example.com/a.png"}},
+        ]))];
+        assert!(matches!(
+            prepare_images(&small, "claude-sonnet-4-6", &mut remote).await,
+            Err(ImageError::UrlFetchDisabled)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_image_rejects_internal_addresses() {
+        let mut config = Config::default();
+        assert!(matches!(
+            fetch_image(&config, "https://example.com/a.png").await,
+            Err(ImageError::UrlFetchDisabled)
+        ));
+
+        config.image_url_fetch = true;
+        for url in [
+            "http://127.0.0.1:8080/api/admin/credentials",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://10.0.0.1/a.png",
+            "http://[::1]/a.png",
+            "http://[::ffff:192.168.1.1]/a.png",
+            "http://localhost/a.png",
+            "file:///etc/passwd",
+        ] {
+            let err = fetch_image(&config, url).await.unwrap_err();
+            assert!(matches!(err, ImageError::Fetch { .. }), "{}: {}", url, err);
+        }
+
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(is_public_ip("2606:4700::1111".parse().unwrap()));
+        assert!(!is_public_ip("100.64.0.1".parse().unwrap()));
+        assert!(!is_public_ip("fd00::1".parse().unwrap()));
+        assert!(!is_public_ip("fe80::1".parse().unwrap()));
+        assert!(!is_public_ip("0.0.0.0".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_read_limited_stops_without_content_length() {
+        // 4 个 4 字节的分块，没有 Content-Length
+        let response = || {
+            let chunks = (0..4).map(|_| Ok::<_, std::io::Error>(vec![0u8; 4]));
+            let body = reqwest::Body::wrap_stream(futures::stream::iter(chunks));
+            Response::from(http::Response::new(body))
+        };
+        assert!(response().content_length().is_none());
+        assert!(matches!(
+            read_limited(response(), 10).await,
+            Err(ReadError::TooLarge(12))
+        ));
+        assert_eq!(read_limited(response(), 16).await.ok().unwrap().len(), 16);
+    }
+
+    #[tokio::test]
+    async fn test_prepare_images_rejects_non_vision_model() {
+        let mut config = Config::default();
+        config.non_vision_models = vec!["Haiku".to_string()];
+        let mut messages = vec![user_message(json!([
+            {"type": "image", "source": {"type": "url", "url": "https://example.com/a.png"}},
+        ]))];
+        let err = prepare_images(&config, "claude-haiku-4-5", &mut messages)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ImageError::VisionUnsupported(_)));
+        assert!(err.to_string().contains("claude-haiku-4-5"));
+
+        // 不含图片的请求不受影响
+        let mut text_only = vec![user_message(json!("hello"))];
+        assert_eq!(
+            prepare_images(&config, "claude-haiku-4-5", &mut text_only)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! 响应元数据回显（`kiro_meta`）
+//!
+//! 请求携带 `x-kiro-meta: true` 时，在最终响应中附加 `kiro_meta` 对象，列出代理对本次请求
+//! 所做的改写（模型别名映射、提示词注入、截断）以及上游重试、故障转移与缓存命中情况，
+//! 便于排查“为什么输出和预期不同”而无需翻阅服务端日志。
+//!
+//! 非流式响应写入 JSON 顶层；流式响应写入 `message_delta` 事件
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, header},
+    response::Response,
+};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde::Serialize;
+
+use crate::kiro::provider::UpstreamAttempts;
+
+use super::converter::Transformation;
+
+/// 启用元数据回显的请求头
+pub const KIRO_META_HEADER: &str = "x-kiro-meta";
+
+/// 非流式响应体读取上限
+const MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+/// 请求是否要求回显 kiro_meta
+pub fn is_requested(headers: &HeaderMap) -> bool {
+    headers
+        .get(KIRO_META_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+}
+
+/// 模型别名映射
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelRemap {
+    pub from: String,
+    pub to: String,
+}
+
+/// 本次请求实际应用的改写
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KiroMeta {
+    pub model_remap: Option<ModelRemap>,
+    /// 注入到提示词中的内容
+    pub prompt_injection: Vec<Transformation>,
+    /// 被截断或丢弃的内容
+    pub truncation: Vec<Transformation>,
+    /// 上游重试次数（不含首次请求）
+    pub retries: u32,
+    /// 切换凭据的次数
+    pub failovers: u32,
+    pub cache_hit: bool,
+}
+
+impl KiroMeta {
+    /// 记录模型别名映射（`from` 为客户端请求的模型名）
+    pub fn set_model_remap(&mut self, from: String, to: &str) {
+        self.model_remap = Some(ModelRemap {
+            from,
+            to: to.to_string(),
+        });
+    }
+
+    /// 记录请求转换过程中的改写
+    pub fn set_transformations(&mut self, transformations: &[Transformation]) {
+        let (truncation, prompt_injection) =
+            transformations.iter().partition(|t| t.is_truncation());
+        self.prompt_injection = prompt_injection;
+        self.truncation = truncation;
+    }
+
+    /// 记录上游尝试统计
+    fn set_attempts(&mut self, attempts: UpstreamAttempts) {
+        self.retries = attempts.attempts.saturating_sub(1);
+        self.failovers = attempts.failovers;
+    }
+}
+
+/// 在 `message_delta` SSE 事件中写入 kiro_meta，其他事件原样返回
+///
+/// 每个数据块恰好是一个完整事件（见 `SseEvent::to_sse_string`）
+fn inject_into_event(chunk: Bytes, meta: &serde_json::Value) -> Bytes {
+    let Some(data) = chunk
+        .strip_prefix(b"event: message_delta\ndata: ")
+        .and_then(|rest| rest.strip_suffix(b"\n\n"))
+    else {
+        return chunk;
+    };
+    let Ok(mut data) = serde_json::from_slice::<serde_json::Value>(data) else {
+        return chunk;
+    };
+    data["kiro_meta"] = meta.clone();
+    Bytes::from(format!("event: message_delta\ndata: {}\n\n", data))
+}
+
+/// 将 kiro_meta 附加到成功响应（未请求或响应失败时原样返回）
+///
+/// 上游尝试统计从响应扩展 `UpstreamAttempts` 中读取
+pub async fn attach(response: Response, meta: Option<KiroMeta>) -> Response {
+    let Some(mut meta) = meta else {
+        return response;
+    };
+    if !response.status().is_success() {
+        return response;
+    }
+    if let Some(attempts) = response.extensions().get::<UpstreamAttempts>() {
+        meta.set_attempts(*attempts);
+    }
+    let meta = serde_json::to_value(&meta).unwrap_or_default();
+
+    let is_stream = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"text/event-stream"));
+    let (mut parts, body) = response.into_parts();
+
+    if is_stream {
+        let stream = body
+            .into_data_stream()
+            .map(move |item| item.map(|chunk| inject_into_event(chunk, &meta)));
+        return Response::from_parts(parts, Body::from_stream(stream));
+    }
+
+    let bytes = match axum::body::to_bytes(body, MAX_RESPONSE_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("读取响应体失败，跳过 kiro_meta: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(mut value) if value.is_object() => {
+            value["kiro_meta"] = meta;
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(value.to_string())
+        }
+        _ => Body::from(bytes),
+    };
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_is_requested() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_requested(&headers));
+        headers.insert(KIRO_META_HEADER, HeaderValue::from_static("True"));
+        assert!(is_requested(&headers));
+        headers.insert(KIRO_META_HEADER, HeaderValue::from_static("0"));
+        assert!(!is_requested(&headers));
+    }
+
+    #[test]
+    fn test_meta_serialization_and_stream_injection() {
+        let mut meta = KiroMeta::default();
+        meta.set_model_remap("fast".to_string(), "claude-haiku-4-5");
+        meta.set_transformations(&[
+            Transformation::PrefillDropped,
+            Transformation::ThinkingPrefix,
+            Transformation::ChunkedWritePolicy,
+        ]);
+        meta.set_attempts(UpstreamAttempts {
+            attempts: 3,
+            failovers: 1,
+        });
+        let value = serde_json::to_value(&meta).unwrap();
+        assert_eq!(value["model_remap"]["from"], "fast");
+        assert_eq!(
+            value["prompt_injection"],
+            serde_json::json!(["thinking_prefix", "chunked_write_policy"])
+        );
+        assert_eq!(value["truncation"], serde_json::json!(["prefill_dropped"]));
+        assert_eq!(
+            (value["retries"].as_u64(), value["failovers"].as_u64()),
+            (Some(2), Some(1))
+        );
+        assert_eq!(value["cache_hit"], false);
+
+        let delta = Bytes::from(
+            "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{}}\n\n",
+        );
+        let injected = inject_into_event(delta, &value);
+        let data: serde_json::Value = serde_json::from_slice(
+            injected
+                .strip_prefix(b"event: message_delta\ndata: ")
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(data["kiro_meta"]["retries"], 2);
+
+        let ping = Bytes::from("event: ping\ndata: {\"type\": \"ping\"}\n\n");
+        assert_eq!(inject_into_event(ping.clone(), &value), ping);
+    }
+}
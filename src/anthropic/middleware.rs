@@ -4,16 +4,28 @@ use std::sync::Arc;
 
 use axum::{
     body::Body,
-    extract::State,
-    http::{Request, StatusCode},
+    extract::{ConnectInfo, OriginalUri, State},
+    http::{HeaderValue, Request, StatusCode, header},
     middleware::Next,
     response::{IntoResponse, Json, Response},
 };
 
+use chrono::{FixedOffset, Utc};
+use futures::StreamExt;
+use http_body_util::LengthLimitError;
+
+use crate::api_keys::{ApiKeyAuth, ApiKeyStore};
+use crate::audit::AuditLog;
 use crate::common::auth;
+use crate::common::ip_filter::ClientAddr;
+use crate::common::lockout::AuthLockout;
 use crate::kiro::provider::KiroProvider;
+use crate::model::config::DEFAULT_MAX_REQUEST_BYTES;
+use crate::server_tls::ClientCert;
 
-use super::types::ErrorResponse;
+use super::archive::ResponseArchive;
+use super::cache::ResponseCache;
+use super::types::{ErrorResponse, QuotaErrorResponse};
 
 /// 应用共享状态
 #[derive(Clone)]
@@ -25,8 +37,22 @@ pub struct AppState {
     pub kiro_provider: Option<Arc<KiroProvider>>,
     /// Profile ARN（可选，用于请求）
     pub profile_arn: Option<String>,
+    /// 响应归档器（可选，未配置 archive 时为 None）
+    pub archive: Option<Arc<ResponseArchive>>,
+    /// 下游托管 API Key 存储
+    pub api_keys: Arc<ApiKeyStore>,
+    /// 请求审计日志（可选，未配置 audit 时为 None）
+    pub audit: Option<Arc<AuditLog>>,
+    /// 响应缓存（可选，未配置 responseCache 时为 None）
+    pub response_cache: Option<Arc<ResponseCache>>,
+    /// 认证失败锁定（可选，未配置 authLockout 时为 None）
+    pub auth_lockout: Option<Arc<AuthLockout>>,
 }
 
+/// 通过认证的托管 API Key ID（由认证中间件写入请求扩展）
+#[derive(Debug, Clone)]
+pub struct ManagedApiKey(pub String);
+
 impl AppState {
     /// 创建新的应用状态
     pub fn new(api_key: impl Into<String>) -> Self {
@@ -34,6 +60,11 @@ impl AppState {
             api_key: api_key.into(),
             kiro_provider: None,
             profile_arn: None,
+            archive: None,
+            api_keys: Arc::new(ApiKeyStore::in_memory()),
+            audit: None,
+            response_cache: None,
+            auth_lockout: None,
         }
     }
 
@@ -48,23 +79,268 @@ impl AppState {
         self.profile_arn = Some(arn.into());
         self
     }
+
+    /// 设置响应归档器
+    pub fn with_archive(mut self, archive: ResponseArchive) -> Self {
+        self.archive = Some(Arc::new(archive));
+        self
+    }
+
+    /// 设置下游托管 API Key 存储
+    pub fn with_api_keys(mut self, api_keys: Arc<ApiKeyStore>) -> Self {
+        self.api_keys = api_keys;
+        self
+    }
+
+    /// 设置请求审计日志
+    pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// 设置响应缓存
+    pub fn with_response_cache(mut self, cache: Arc<ResponseCache>) -> Self {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    /// 设置认证失败锁定
+    pub fn with_auth_lockout(mut self, lockout: Arc<AuthLockout>) -> Self {
+        self.auth_lockout = Some(lockout);
+        self
+    }
 }
 
 /// API Key 认证中间件
+///
+/// 接受配置文件中的主 Key 或托管 API Key，托管 Key 的 ID 写入请求扩展供后续中间件和处理器使用。
+/// 启用 mTLS 时，客户端证书 CN 与托管 Key 名称相同的请求直接作为该 Key 通过认证。
+/// 配置 `authLockout` 时，认证失败次数过多的 IP 在锁定期内直接返回 429
 pub async fn auth_middleware(
     State(state): State<AppState>,
-    request: Request<Body>,
+    mut request: Request<Body>,
     next: Next,
 ) -> Response {
-    match auth::extract_api_key(&request) {
-        Some(key) if auth::constant_time_eq(&key, &state.api_key) => next.run(request).await,
-        _ => {
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<ClientAddr>>()
+        .and_then(|info| info.0.0);
+    let lockout = state.auth_lockout.as_ref().zip(client_ip);
+    if let Some(retry_after) = lockout.and_then(|(lockout, ip)| lockout.locked(ip)) {
+        return locked_response(retry_after);
+    }
+
+    let cert_key = request
+        .extensions()
+        .get::<ClientCert>()
+        .and_then(|cert| state.api_keys.find_by_name(&cert.common_name));
+    let auth = match (cert_key, auth::extract_api_key(&request)) {
+        (Some(id), _) => ApiKeyAuth::Managed(id),
+        (None, Some(key)) => state.api_keys.authenticate(&key, &state.api_key),
+        (None, None) => ApiKeyAuth::Invalid,
+    };
+
+    if let Some((lockout, ip)) = lockout {
+        if !matches!(auth, ApiKeyAuth::Invalid) {
+            lockout.record_success(ip);
+        } else if lockout.record_failure(ip) {
+            let endpoint = match request.extensions().get::<OriginalUri>() {
+                Some(OriginalUri(uri)) => uri.path().to_string(),
+                None => request.uri().path().to_string(),
+            };
+            tracing::warn!("{} 认证失败次数过多，已锁定: {}", ip, endpoint);
+            if let Some(audit) = &state.audit {
+                audit.record_security_event(
+                    "auth_lockout",
+                    &endpoint,
+                    Some(ip),
+                    StatusCode::UNAUTHORIZED.as_u16(),
+                );
+            }
+        }
+    }
+
+    match auth {
+        ApiKeyAuth::Primary => next.run(request).await,
+        ApiKeyAuth::Managed(id) => {
+            request.extensions_mut().insert(ManagedApiKey(id));
+            next.run(request).await
+        }
+        ApiKeyAuth::Invalid => {
             let error = ErrorResponse::authentication_error();
             (StatusCode::UNAUTHORIZED, Json(error)).into_response()
         }
     }
 }
 
+/// IP 被锁定时的 429 响应
+fn locked_response(retry_after: u64) -> Response {
+    let error = ErrorResponse::new(
+        "rate_limit_error",
+        "Too many failed authentication attempts, try again later",
+    );
+    let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(error)).into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
+
+/// 托管 API Key 配额中间件
+///
+/// 位于认证中间件之后、对话处理器之前，总量 / 按日 / 按月配额任一超限时返回 429，
+/// 响应体附带超限的配额项与重置时间，并设置 `Retry-After`。
+/// token 用量在响应结束后才计入，因此配额边界上的最后一个请求可能略微超出配额
+pub async fn quota_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(ManagedApiKey(id)) = request.extensions().get::<ManagedApiKey>().cloned() else {
+        return next.run(request).await;
+    };
+
+    let offset = state
+        .kiro_provider
+        .as_ref()
+        .map(|p| p.token_manager().config().timezone_offset())
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    let now = Utc::now();
+    let exceeded = match state.api_keys.check_quota(&id, now, offset) {
+        Ok(()) => return next.run(request).await,
+        Err(exceeded) => exceeded,
+    };
+
+    tracing::warn!(
+        "API Key #{} 配额超限: {} {}/{}",
+        id,
+        exceeded.quota,
+        exceeded.used,
+        exceeded.limit
+    );
+    let retry_after = exceeded
+        .reset_at
+        .as_ref()
+        .map(|reset| (reset.timestamp - now.timestamp()).max(1));
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(QuotaErrorResponse::new(exceeded)),
+    )
+        .into_response();
+    if let Some(secs) = retry_after {
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+    }
+    response
+}
+
+/// 对话请求体大小限制中间件（maxRequestBytes，支持热更新）
+///
+/// 位于配额中间件之后、对话处理器之前，读取完整请求体，超过上限时返回 413 `request_too_large`，
+/// 读取请求体的其他错误（客户端断开等）返回 400。
+/// 对话路由关闭了固定的 `DefaultBodyLimit`，请求体大小完全由此中间件控制
+pub async fn body_limit_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let limit = state
+        .kiro_provider
+        .as_ref()
+        .map_or(DEFAULT_MAX_REQUEST_BYTES, |p| {
+            p.token_manager().config().max_request_bytes
+        });
+
+    let too_large = || {
+        tracing::warn!("请求体超过 {} 字节上限，已拒绝", limit);
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(ErrorResponse::new(
+                "request_too_large",
+                format!("请求体超过 {} 字节上限", limit),
+            )),
+        )
+            .into_response()
+    };
+
+    // 声明了 Content-Length 时无需读取请求体即可拒绝
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if declared.is_some_and(|len| len > limit) {
+        return too_large();
+    }
+
+    let (parts, body) = request.into_parts();
+    match axum::body::to_bytes(body, limit).await {
+        Ok(bytes) => {
+            let request = Request::from_parts(parts, Body::from(bytes));
+            next.run(request).await
+        }
+        Err(e) if is_length_limit_error(&e) => too_large(),
+        Err(e) => {
+            tracing::warn!("读取请求体失败: {}", e);
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new(
+                    "invalid_request_error",
+                    format!("读取请求体失败: {}", e),
+                )),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// 错误链中是否包含请求体长度超限错误
+fn is_length_limit_error(err: &axum::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(e) = source {
+        if e.is::<LengthLimitError>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// 请求审计中间件
+///
+/// 位于认证中间件之后、配额中间件之前（配额拒绝的请求同样会被记录）。
+/// 跟踪器写入请求扩展供处理器补充模型、凭据与 token 用量，并随响应体一起释放，
+/// 因此记录的耗时覆盖到流式响应发送完毕
+pub async fn audit_middleware(
+    State(state): State<AppState>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(audit) = &state.audit else {
+        return next.run(request).await;
+    };
+
+    let endpoint = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path().to_string(),
+        None => request.uri().path().to_string(),
+    };
+    let api_key = request.extensions().get::<ManagedApiKey>().map(|k| k.0.clone());
+    let tracker = audit.begin(&endpoint, api_key.as_deref());
+    let client_ip = request.extensions().get::<ConnectInfo<ClientAddr>>();
+    tracker.set_client_ip(client_ip.and_then(|info| info.0.0));
+    request.extensions_mut().insert(tracker.clone());
+
+    let response = next.run(request).await;
+    tracker.set_status(response.status().as_u16());
+    response.map(|body| {
+        Body::from_stream(body.into_data_stream().map(move |item| {
+            let _ = &tracker;
+            item
+        }))
+    })
+}
+
 /// CORS 中间件层
 ///
 /// **安全说明**：当前配置允许所有来源（Any），这是为了支持公开 API 服务。
@@ -74,6 +350,7 @@ pub async fn auth_middleware(
 /// - `allow_origin(Any)`: 允许任何来源的请求
 /// - `allow_methods(Any)`: 允许任何 HTTP 方法
 /// - `allow_headers(Any)`: 允许任何请求头
+/// - `expose_headers`: 浏览器客户端可读取 `X-Request-Id`
 pub fn cors_layer() -> tower_http::cors::CorsLayer {
     use tower_http::cors::{Any, CorsLayer};
 
@@ -81,4 +358,32 @@ pub fn cors_layer() -> tower_http::cors::CorsLayer {
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any)
+        .expose_headers([http::HeaderName::from_static(
+            crate::common::request_id::REQUEST_ID_HEADER,
+        )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_body(body: Body, limit: usize) -> axum::Error {
+        axum::body::to_bytes(body, limit).await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn test_length_limit_error_detected() {
+        let err = read_body(Body::from(vec![0u8; 16]), 8).await;
+        assert!(is_length_limit_error(&err));
+    }
+
+    #[tokio::test]
+    async fn test_body_read_error_is_not_length_limit() {
+        let stream = futures::stream::iter(vec![
+            Ok(bytes::Bytes::from_static(b"ab")),
+            Err(std::io::Error::other("connection reset")),
+        ]);
+        let err = read_body(Body::from_stream(stream), 1024).await;
+        assert!(!is_length_limit_error(&err));
+    }
 }
@@ -8,6 +8,13 @@
 //! - `GET /v1/models` - 获取可用模型列表
 //! - `POST /v1/messages` - 创建消息（对话）
 //! - `POST /v1/messages/count_tokens` - 计算 token 数量
+//! - `POST /v1/chat/completions` - OpenAI 对话补全（含 function calling）
+//! - `POST /v1/completions` - OpenAI 旧版文本补全（转换为单轮对话）
+//! - `POST /v1/embeddings` - 转发到外部 embeddings 服务
+//!
+//! ## Gemini 兼容端点 (/v1beta)
+//! - `POST /v1beta/models/{model}:generateContent` - Gemini 格式的对话
+//! - `POST /v1beta/models/{model}:streamGenerateContent` - Gemini 格式的流式对话（支持 `alt=sse`）
 //!
 //! ## Claude Code 兼容端点 (/cc/v1)
 //! - `POST /cc/v1/messages` - 创建消息（流式响应会等待 contextUsageEvent 后再发送 message_start，确保 input_tokens 准确）
@@ -22,12 +29,26 @@
 //! axum::serve(listener, app).await?;
 //! ```
 
+pub mod archive;
+pub mod cache;
+pub mod capture;
+mod chat_completions;
+mod completions;
 mod converter;
+mod embeddings;
+mod gemini;
 mod handlers;
+mod image;
+mod kiro_meta;
 mod middleware;
+pub mod replay;
+mod resume;
 mod router;
+mod stop_sequence;
 mod stream;
 pub mod types;
+mod usage_inject;
+mod validation;
 mod websearch;
 
 pub use router::create_router_with_provider;
@@ -0,0 +1,201 @@
+//! 捕获回放（`kiro-rs replay`）
+//!
+//! 把捕获的上游原始 AWS Event Stream 字节重新送入 `EventStreamDecoder` 与流式转换（`StreamContext`），
+//! 依次打印解码出的帧与转换得到的 SSE，用于离线排查用户报告的解码与协议转换问题。
+//!
+//! 输入可以是请求捕获目录（读取其中的 `upstream.bin`，模型与 thinking 取自 `meta.json`），
+//! 也可以是任意原始事件流文件
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::kiro::model::events::Event;
+use crate::kiro::parser::decoder::EventStreamDecoder;
+
+use super::capture::{CaptureMeta, META_FILE, UPSTREAM_FILE};
+use super::stream::StreamContext;
+
+/// 回放参数（未指定的项取自捕获目录中的 `meta.json`）
+#[derive(Debug, Default, Clone)]
+pub struct ReplayOptions {
+    pub model: Option<String>,
+    pub thinking: Option<bool>,
+    /// 按固定大小分块送入解码器，模拟上游分包（0 表示一次性送入）
+    pub chunk_size: usize,
+    /// 宽松 CRC 模式（校验失败的帧照常处理）
+    pub lenient_crc: bool,
+}
+
+/// 回放结果统计
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplaySummary {
+    pub bytes: usize,
+    pub frames: usize,
+    pub errors: usize,
+    pub sse_events: usize,
+}
+
+/// 回放捕获目录或原始事件流文件，帧与 SSE 输出写入 `out`
+pub fn replay_path(
+    path: &Path,
+    options: ReplayOptions,
+    out: &mut impl Write,
+) -> anyhow::Result<ReplaySummary> {
+    let (upstream, meta_path) = if path.is_dir() {
+        (path.join(UPSTREAM_FILE), path.join(META_FILE))
+    } else {
+        let meta_path = path
+            .parent()
+            .map(|dir| dir.join(META_FILE))
+            .unwrap_or_else(|| PathBuf::from(META_FILE));
+        (path.to_path_buf(), meta_path)
+    };
+    let data =
+        std::fs::read(&upstream).with_context(|| format!("读取 {} 失败", upstream.display()))?;
+    let meta: Option<CaptureMeta> = std::fs::read(&meta_path)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok());
+
+    let model = options
+        .model
+        .clone()
+        .or_else(|| meta.as_ref().map(|m| m.model.clone()))
+        .unwrap_or_else(|| "claude-sonnet-4-5".to_string());
+    let thinking = options
+        .thinking
+        .or_else(|| meta.as_ref().map(|m| m.thinking_enabled))
+        .unwrap_or(false);
+    let input_tokens = meta.as_ref().map_or(0, |m| m.input_tokens);
+
+    writeln!(
+        out,
+        "# 回放 {}（{} 字节，模型 {}，thinking {}）",
+        upstream.display(),
+        data.len(),
+        model,
+        if thinking { "开启" } else { "关闭" }
+    )?;
+    let ctx = StreamContext::new_with_thinking(model, input_tokens, thinking);
+    let decoder = EventStreamDecoder::new()
+        .with_error_context(true)
+        .with_lenient_crc(options.lenient_crc);
+    replay(&data, ctx, decoder, options.chunk_size, out)
+}
+
+/// 回放一段原始事件流
+fn replay(
+    data: &[u8],
+    mut ctx: StreamContext,
+    mut decoder: EventStreamDecoder,
+    chunk_size: usize,
+    out: &mut impl Write,
+) -> anyhow::Result<ReplaySummary> {
+    let mut summary = ReplaySummary {
+        bytes: data.len(),
+        ..Default::default()
+    };
+
+    let initial = ctx.generate_initial_events();
+    summary.sse_events += initial.len();
+    for event in initial {
+        write!(out, "{}", event.to_sse_string())?;
+    }
+
+    let chunk_size = if chunk_size == 0 {
+        data.len().max(1)
+    } else {
+        chunk_size
+    };
+    for chunk in data.chunks(chunk_size) {
+        if let Err(e) = decoder.feed(chunk) {
+            summary.errors += 1;
+            writeln!(out, "! 送入解码器失败: {}", e)?;
+        }
+        for result in decoder.decode_iter() {
+            let frame = match result {
+                Ok(frame) => frame,
+                Err(e) => {
+                    summary.errors += 1;
+                    writeln!(out, "! 解码失败: {}", e)?;
+                    continue;
+                }
+            };
+            summary.frames += 1;
+            writeln!(
+                out,
+                "── 帧 #{} {}/{}: {}",
+                summary.frames,
+                frame.message_type().unwrap_or("-"),
+                frame.event_type().unwrap_or("-"),
+                frame.payload_as_str()
+            )?;
+            match Event::from_frame(frame) {
+                Ok(event) => {
+                    let events = ctx.process_kiro_event(&event);
+                    summary.sse_events += events.len();
+                    for event in events {
+                        write!(out, "{}", event.to_sse_string())?;
+                    }
+                }
+                Err(e) => {
+                    summary.errors += 1;
+                    writeln!(out, "! 事件解析失败: {}", e)?;
+                }
+            }
+        }
+    }
+
+    let final_events = ctx.generate_final_events();
+    summary.sse_events += final_events.len();
+    for event in final_events {
+        write!(out, "{}", event.to_sse_string())?;
+    }
+    if decoder.buffer_len() > 0 {
+        writeln!(out, "! 流末尾有 {} 字节未构成完整帧", decoder.buffer_len())?;
+    }
+    writeln!(
+        out,
+        "# 完成：{} 帧，{} 个 SSE 事件，{} 个错误，{} 帧 CRC 校验失败，跳过 {} 字节",
+        summary.frames,
+        summary.sse_events,
+        summary.errors,
+        decoder.corrupt_frames(),
+        decoder.bytes_skipped()
+    )?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::parser::frame::FrameBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn test_replay_prints_frames_and_sse() {
+        let mut data = Vec::new();
+        for content in ["Hello", " world"] {
+            let frame = FrameBuilder::event("assistantResponseEvent")
+                .json(&json!({ "content": content }))
+                .to_bytes();
+            data.extend_from_slice(&frame);
+        }
+
+        // 逐字节送入与一次性送入的结果一致
+        for chunk_size in [0, 1] {
+            let ctx = StreamContext::new_with_thinking("claude-sonnet-4-5", 10, false);
+            let mut out = Vec::new();
+            let decoder = EventStreamDecoder::new();
+            let summary = replay(&data, ctx, decoder, chunk_size, &mut out).unwrap();
+            assert_eq!(summary.frames, 2);
+            assert_eq!(summary.errors, 0);
+
+            let out = String::from_utf8(out).unwrap();
+            assert!(out.contains("── 帧 #1 event/assistantResponseEvent"));
+            assert!(out.contains(r#""text":" world""#));
+            assert!(out.contains("event: message_stop"));
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! 流中断续传
+//!
+//! 启用 `streamResume` 后，上游事件流在响应中途断开时换一个凭据重放对话：
+//! 已输出的内容作为助手消息追加到历史，并要求模型从断点处继续。新的事件流接在原流之后，
+//! 由同一个流处理上下文继续处理，下游看到的仍是一条完整的 SSE 响应。
+//! 已开始工具调用的响应无法续接，按原逻辑结束
+
+use std::sync::Arc;
+
+use serde_json::{Value, json};
+
+use crate::kiro::model::credentials::CredentialId;
+use crate::kiro::provider::{KiroProvider, RequestRouting};
+
+/// 单个响应最多续传的次数
+const MAX_RESUMES: u32 = 2;
+
+/// 续传时追加的用户指令
+const RESUME_PROMPT: &str = "Your previous response was cut off. Continue exactly where it \
+                             stopped, without repeating earlier text or adding any preamble.";
+
+/// 流中断续传器（每个流式响应一个）
+pub struct StreamResumer {
+    provider: Arc<KiroProvider>,
+    request_body: String,
+    routing: RequestRouting,
+    resumes: u32,
+}
+
+impl StreamResumer {
+    /// 创建续传器，未启用 streamResume 时返回 None
+    pub fn new(
+        provider: &Arc<KiroProvider>,
+        request_body: &str,
+        routing: &RequestRouting,
+    ) -> Option<Self> {
+        provider
+            .token_manager()
+            .config()
+            .stream_resume
+            .then(|| Self {
+                provider: provider.clone(),
+                request_body: request_body.to_string(),
+                routing: routing.clone(),
+                resumes: 0,
+            })
+    }
+
+    pub fn provider(&self) -> &KiroProvider {
+        &self.provider
+    }
+
+    /// 避开中断的凭据重放对话，返回接续的上游响应（次数用尽或请求失败时返回 None）
+    ///
+    /// `emitted` 为截至目前上游输出的全部原始内容（含 thinking 标签）
+    pub async fn resume(
+        &mut self,
+        failed: Option<CredentialId>,
+        emitted: &str,
+    ) -> Option<reqwest::Response> {
+        if self.resumes >= MAX_RESUMES {
+            return None;
+        }
+        self.resumes += 1;
+
+        let body = if emitted.is_empty() {
+            self.request_body.clone()
+        } else {
+            resume_request_body(&self.request_body, emitted)?
+        };
+        self.routing.avoid.extend(failed);
+
+        match self
+            .provider
+            .call_api_stream_with_policy(&body, &self.routing)
+            .await
+        {
+            Ok(response) => {
+                tracing::info!(
+                    "上游流中断，已续传（第 {}/{} 次，已输出 {} 字符）",
+                    self.resumes,
+                    MAX_RESUMES,
+                    emitted.chars().count()
+                );
+                Some(response)
+            }
+            Err(e) => {
+                tracing::warn!("上游流中断后续传失败: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// 构造续传请求体：原当前消息移入历史，追加已输出的助手内容，当前消息改为续写指令
+fn resume_request_body(body: &str, emitted: &str) -> Option<String> {
+    let mut value: Value = serde_json::from_str(body).ok()?;
+    let state = value.get_mut("conversationState")?;
+    let mut current = state
+        .get_mut("currentMessage")?
+        .get_mut("userInputMessage")?
+        .take();
+
+    // 工具定义只出现在当前消息中，历史消息只保留工具结果
+    let tools = current["userInputMessageContext"]
+        .as_object_mut()
+        .and_then(|context| context.remove("tools"));
+    let mut context = json!({});
+    if let Some(tools) = tools {
+        context["tools"] = tools;
+    }
+    let next = json!({
+        "userInputMessage": {
+            "userInputMessageContext": context,
+            "content": RESUME_PROMPT,
+            "modelId": current["modelId"],
+            "origin": current["origin"],
+        }
+    });
+
+    let history = state
+        .as_object_mut()?
+        .entry("history")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()?;
+    history.push(json!({ "userInputMessage": current }));
+    history.push(json!({ "assistantResponseMessage": { "content": emitted } }));
+    state["currentMessage"] = next;
+
+    serde_json::to_string(&value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_request_body_replays_emitted_text() {
+        let body = json!({
+            "conversationState": {
+                "conversationId": "c1",
+                "currentMessage": {
+                    "userInputMessage": {
+                        "userInputMessageContext": {
+                            "tools": [{"toolSpecification": {"name": "read"}}],
+                            "toolResults": [{"toolUseId": "t1"}],
+                        },
+                        "content": "写一首诗",
+                        "modelId": "claude-sonnet-4.5",
+                        "origin": "AI_EDITOR",
+                    }
+                },
+                "history": [],
+            },
+            "profileArn": "arn",
+        });
+
+        let resumed = resume_request_body(&body.to_string(), "床前明月光").unwrap();
+        let resumed: Value = serde_json::from_str(&resumed).unwrap();
+        let state = &resumed["conversationState"];
+        assert_eq!(
+            state["history"],
+            json!([
+                {"userInputMessage": {
+                    "userInputMessageContext": {"toolResults": [{"toolUseId": "t1"}]},
+                    "content": "写一首诗",
+                    "modelId": "claude-sonnet-4.5",
+                    "origin": "AI_EDITOR",
+                }},
+                {"assistantResponseMessage": {"content": "床前明月光"}},
+            ])
+        );
+        let current = &state["currentMessage"]["userInputMessage"];
+        assert_eq!(current["content"], RESUME_PROMPT);
+        assert_eq!(current["modelId"], "claude-sonnet-4.5");
+        assert_eq!(
+            current["userInputMessageContext"]["tools"][0]["toolSpecification"]["name"],
+            "read"
+        );
+        assert_eq!(resumed["profileArn"], "arn");
+        assert_eq!(state["conversationId"], "c1");
+    }
+}
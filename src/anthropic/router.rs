@@ -1,5 +1,7 @@
 //! Anthropic API 路由配置
 
+use std::sync::Arc;
+
 use axum::{
     Router,
     extract::DefaultBodyLimit,
@@ -7,14 +9,26 @@ use axum::{
     routing::{get, post},
 };
 
+use crate::api_keys::ApiKeyStore;
+use crate::audit::AuditLog;
+use crate::common::lockout::AuthLockout;
 use crate::kiro::provider::KiroProvider;
 
 use super::{
+    archive::ResponseArchive,
+    cache::ResponseCache,
+    chat_completions::post_chat_completions,
+    completions::post_completions,
+    embeddings::post_embeddings,
+    gemini::post_generate_content,
     handlers::{count_tokens, get_models, post_messages, post_messages_cc},
-    middleware::{AppState, auth_middleware, cors_layer},
+    middleware::{
+        AppState, audit_middleware, auth_middleware, body_limit_middleware, cors_layer,
+        quota_middleware,
+    },
 };
 
-/// 请求体最大大小限制 (50MB)
+/// 请求体最大大小限制 (50MB，对话端点改由 maxRequestBytes 控制)
 const MAX_BODY_SIZE: usize = 50 * 1024 * 1024;
 
 /// 创建 Anthropic API 路由
@@ -23,35 +37,95 @@ const MAX_BODY_SIZE: usize = 50 * 1024 * 1024;
 /// - `GET /v1/models` - 获取可用模型列表
 /// - `POST /v1/messages` - 创建消息（对话）
 /// - `POST /v1/messages/count_tokens` - 计算 token 数量
+/// - `POST /v1/chat/completions` - OpenAI 对话补全
+/// - `POST /v1/completions` - OpenAI 旧版文本补全
+/// - `POST /v1/embeddings` - 转发到外部 embeddings 服务
+/// - `POST /v1beta/models/{model}:generateContent` - Gemini 兼容对话
+/// - `POST /v1beta/models/{model}:streamGenerateContent` - Gemini 兼容流式对话
 ///
 /// # 认证
-/// 所有 `/v1` 路径需要 API Key 认证，支持：
+/// 所有 `/v1` 路径需要 API Key 认证（主 Key 或托管 API Key），支持：
 /// - `x-api-key` header
 /// - `Authorization: Bearer <token>` header
+/// - `x-goog-api-key` header（Google AI SDK）
 ///
 /// # 参数
 /// - `api_key`: API 密钥，用于验证客户端请求
 /// - `kiro_provider`: 可选的 KiroProvider，用于调用上游 API
+/// - `archive`: 可选的响应归档器
+/// - `api_keys`: 下游托管 API Key 存储
+/// - `audit`: 可选的请求审计日志
+/// - `response_cache`: 可选的非流式响应缓存
+/// - `auth_lockout`: 可选的认证失败锁定
 
 /// 创建带有 KiroProvider 的 Anthropic API 路由
+#[allow(clippy::too_many_arguments)]
 pub fn create_router_with_provider(
     api_key: impl Into<String>,
     kiro_provider: Option<KiroProvider>,
     profile_arn: Option<String>,
+    archive: Option<ResponseArchive>,
+    api_keys: Arc<ApiKeyStore>,
+    audit: Option<Arc<AuditLog>>,
+    response_cache: Option<Arc<ResponseCache>>,
+    auth_lockout: Option<Arc<AuthLockout>>,
 ) -> Router {
-    let mut state = AppState::new(api_key);
+    let mut state = AppState::new(api_key).with_api_keys(api_keys);
     if let Some(provider) = kiro_provider {
         state = state.with_kiro_provider(provider);
     }
     if let Some(arn) = profile_arn {
         state = state.with_profile_arn(arn);
     }
+    if let Some(archive) = archive {
+        state = state.with_archive(archive);
+    }
+    if let Some(audit) = audit {
+        state = state.with_audit_log(audit);
+    }
+    if let Some(cache) = response_cache {
+        state = state.with_response_cache(cache);
+    }
+    if let Some(lockout) = auth_lockout {
+        state = state.with_auth_lockout(lockout);
+    }
+
+    // 托管 API Key 配额检查（仅作用于对话端点，在认证之后执行）
+    let quota_layer = middleware::from_fn_with_state(state.clone(), quota_middleware);
+    // 请求审计（包裹配额检查，被拒绝的请求同样记录）
+    let audit_layer = middleware::from_fn_with_state(state.clone(), audit_middleware);
+    // 对话请求体大小限制（在配额检查之后读取请求体）
+    let body_limit_layer = middleware::from_fn_with_state(state.clone(), body_limit_middleware);
 
     // 需要认证的 /v1 路由
     let v1_routes = Router::new()
         .route("/models", get(get_models))
-        .route("/messages", post(post_messages))
+        .route(
+            "/messages",
+            post(post_messages)
+                .layer(DefaultBodyLimit::disable())
+                .layer(body_limit_layer.clone())
+                .layer(quota_layer.clone())
+                .layer(audit_layer.clone()),
+        )
         .route("/messages/count_tokens", post(count_tokens))
+        .route(
+            "/chat/completions",
+            post(post_chat_completions)
+                .layer(DefaultBodyLimit::disable())
+                .layer(body_limit_layer.clone())
+                .layer(quota_layer.clone())
+                .layer(audit_layer.clone()),
+        )
+        .route(
+            "/completions",
+            post(post_completions)
+                .layer(DefaultBodyLimit::disable())
+                .layer(body_limit_layer.clone())
+                .layer(quota_layer.clone())
+                .layer(audit_layer.clone()),
+        )
+        .route("/embeddings", post(post_embeddings))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             auth_middleware,
@@ -60,16 +134,40 @@ pub fn create_router_with_provider(
     // 需要认证的 /cc/v1 路由（Claude Code 兼容端点）
     // 与 /v1 的区别：流式响应会等待 contextUsageEvent 后再发送 message_start
     let cc_v1_routes = Router::new()
-        .route("/messages", post(post_messages_cc))
+        .route(
+            "/messages",
+            post(post_messages_cc)
+                .layer(DefaultBodyLimit::disable())
+                .layer(body_limit_layer.clone())
+                .layer(quota_layer.clone())
+                .layer(audit_layer.clone()),
+        )
         .route("/messages/count_tokens", post(count_tokens))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             auth_middleware,
         ));
 
+    // 需要认证的 /v1beta 路由（Gemini 兼容端点）
+    // 路径形如 `/models/claude-sonnet-4-5:generateContent`，方法名在处理器中解析
+    let gemini_routes = Router::new()
+        .route(
+            "/models/{target}",
+            post(post_generate_content)
+                .layer(DefaultBodyLimit::disable())
+                .layer(body_limit_layer.clone())
+                .layer(quota_layer)
+                .layer(audit_layer),
+        )
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth_middleware,
+        ));
+
     Router::new()
         .nest("/v1", v1_routes)
         .nest("/cc/v1", cc_v1_routes)
+        .nest("/v1beta", gemini_routes)
         .layer(cors_layer())
         .layer(DefaultBodyLimit::max(MAX_BODY_SIZE))
         .with_state(state)
@@ -0,0 +1,113 @@
+//! 本地执行 stop_sequences
+//!
+//! 上游不支持 `stop_sequences`，由代理在输出文本中匹配：命中后截断其后的内容并结束输出。
+//! 可能是停止序列开头的尾部文本会暂缓输出，确认不构成停止序列后再放行
+
+/// 停止序列匹配器
+#[derive(Debug, Default)]
+pub struct StopSequenceMatcher {
+    sequences: Vec<String>,
+    /// 暂缓输出的尾部文本
+    pending: String,
+    /// 命中的停止序列
+    matched: Option<String>,
+}
+
+impl StopSequenceMatcher {
+    /// 创建匹配器（忽略空字符串）
+    pub fn new(sequences: Vec<String>) -> Self {
+        Self {
+            sequences: sequences.into_iter().filter(|s| !s.is_empty()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// 命中的停止序列
+    pub fn matched(&self) -> Option<&str> {
+        self.matched.as_deref()
+    }
+
+    /// 送入一段输出文本，返回可以立即输出的部分
+    ///
+    /// 命中停止序列后返回其之前的文本，此后送入的文本全部丢弃
+    pub fn push(&mut self, text: &str) -> String {
+        if self.matched.is_some() {
+            return String::new();
+        }
+        if self.sequences.is_empty() {
+            return text.to_string();
+        }
+        self.pending.push_str(text);
+
+        let first_match = self
+            .sequences
+            .iter()
+            .filter_map(|seq| self.pending.find(seq.as_str()).map(|pos| (pos, seq)))
+            .min_by_key(|(pos, _)| *pos);
+        if let Some((pos, seq)) = first_match {
+            self.matched = Some(seq.clone());
+            let mut output = std::mem::take(&mut self.pending);
+            output.truncate(pos);
+            return output;
+        }
+
+        let split = self.pending.len() - self.holdback_len();
+        self.pending.drain(..split).collect()
+    }
+
+    /// 取出暂缓的文本（后续不会再有相邻的正文时调用，如工具调用开始或流结束）
+    pub fn flush(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// 末尾需要暂缓的字节数：最长的、同时是某个停止序列真前缀的后缀
+    fn holdback_len(&self) -> usize {
+        let max_len = self.sequences.iter().map(String::len).max().unwrap_or(0);
+        let min_start = self.pending.len().saturating_sub(max_len);
+        self.pending
+            .char_indices()
+            .map(|(i, _)| i)
+            .filter(|&i| i >= min_start)
+            .find(|&i| {
+                let suffix = &self.pending[i..];
+                self.sequences
+                    .iter()
+                    .any(|seq| seq.len() > suffix.len() && seq.starts_with(suffix))
+            })
+            .map_or(0, |i| self.pending.len() - i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_sequence_across_chunks() {
+        let mut matcher = StopSequenceMatcher::new(vec!["\n\nHuman:".to_string()]);
+        assert_eq!(matcher.push("Hello\n"), "Hello");
+        assert_eq!(matcher.push("\nHu"), "");
+        assert_eq!(matcher.push("man: bye"), "");
+        assert_eq!(matcher.matched(), Some("\n\nHuman:"));
+        assert_eq!(matcher.push("more"), "");
+        assert_eq!(matcher.flush(), "");
+    }
+
+    #[test]
+    fn test_stop_sequence_holdback_released() {
+        let mut matcher = StopSequenceMatcher::new(vec!["END".to_string(), "你好".to_string()]);
+        assert_eq!(matcher.push("the EN"), "the ");
+        assert_eq!(matcher.push("D"), "");
+        assert_eq!(matcher.matched(), Some("END"));
+
+        let mut matcher = StopSequenceMatcher::new(vec!["END".to_string(), "你好".to_string()]);
+        assert_eq!(matcher.push("ENTER 你"), "ENTER ");
+        assert_eq!(matcher.push("们"), "你们");
+        assert_eq!(matcher.push("E"), "");
+        assert_eq!(matcher.flush(), "E");
+        assert_eq!(matcher.matched(), None);
+
+        let mut matcher = StopSequenceMatcher::new(Vec::new());
+        assert_eq!(matcher.push("END"), "END");
+    }
+}
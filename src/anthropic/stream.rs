@@ -7,7 +7,10 @@ use std::collections::HashMap;
 use serde_json::json;
 use uuid::Uuid;
 
-use crate::kiro::model::events::Event;
+use crate::kiro::model::events::{Event, ReasoningContentEvent};
+use crate::model::config::ThinkingOutput;
+
+use super::stop_sequence::StopSequenceMatcher;
 
 /// 找到小于等于目标位置的最近有效UTF-8字符边界
 ///
@@ -142,6 +145,26 @@ fn find_real_thinking_end_tag_at_buffer_end(buffer: &str) -> Option<usize> {
     None
 }
 
+/// 拆分非流式响应正文开头的 thinking 块，返回 (thinking 内容, 其后的正文)
+///
+/// `<thinking>` 之前只允许空白字符；结束标签缺失时全部内容视为 thinking
+pub(crate) fn split_thinking(text: &str) -> Option<(&str, &str)> {
+    let start = find_real_thinking_start_tag(text)?;
+    if !text[..start].trim().is_empty() {
+        return None;
+    }
+    let body = &text[start + "<thinking>".len()..];
+    let body = body.strip_prefix('\n').unwrap_or(body);
+
+    if let Some(end) = find_real_thinking_end_tag(body) {
+        Some((&body[..end], &body[end + "</thinking>\n\n".len()..]))
+    } else if let Some(end) = find_real_thinking_end_tag_at_buffer_end(body) {
+        Some((&body[..end], ""))
+    } else {
+        Some((body, ""))
+    }
+}
+
 /// 查找真正的 thinking 开始标签（不被引用字符包裹）
 ///
 /// 与 `find_real_thinking_end_tag` 类似，跳过被引用字符包裹的开始标签。
@@ -194,6 +217,64 @@ impl SseEvent {
             serde_json::to_string(&self.data).unwrap_or_default()
         )
     }
+
+    /// 将内容超过 `max_bytes` 的 content_block_delta 事件拆分为多个同索引的增量事件
+    ///
+    /// 按内容字段（text / thinking / partial_json）的 UTF-8 字节数切分，不会截断多字节字符；
+    /// 其他事件或内容未超限时原样返回，`max_bytes` 为 0 表示不拆分
+    pub fn split_delta(self, max_bytes: usize) -> Vec<SseEvent> {
+        if max_bytes == 0 || self.event != "content_block_delta" {
+            return vec![self];
+        }
+        let Some((field, content)) = delta_content(&self.data) else {
+            return vec![self];
+        };
+        if content.len() <= max_bytes {
+            return vec![self];
+        }
+
+        split_at_char_boundaries(content, max_bytes)
+            .into_iter()
+            .map(|chunk| {
+                let mut data = self.data.clone();
+                data["delta"][field] = json!(chunk);
+                SseEvent::new(self.event.clone(), data)
+            })
+            .collect()
+    }
+}
+
+/// 获取增量事件中可拆分的内容字段名及内容
+fn delta_content(data: &serde_json::Value) -> Option<(&'static str, &str)> {
+    let delta = data.get("delta")?;
+    let field = match delta.get("type")?.as_str()? {
+        "text_delta" => "text",
+        "thinking_delta" => "thinking",
+        "input_json_delta" => "partial_json",
+        _ => return None,
+    };
+    Some((field, delta.get(field)?.as_str()?))
+}
+
+/// 按最大字节数切分字符串，切分点回退到字符边界
+///
+/// 单个字符超过 `max_bytes` 时该字符独占一段
+fn split_at_char_boundaries(s: &str, max_bytes: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let mut end = max_bytes.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
 }
 
 /// 内容块状态
@@ -235,6 +316,8 @@ pub struct SseStateManager {
     next_block_index: i32,
     /// 当前 stop_reason
     stop_reason: Option<String>,
+    /// 命中的停止序列
+    stop_sequence: Option<String>,
     /// 是否有工具调用
     has_tool_use: bool,
 }
@@ -254,6 +337,7 @@ impl SseStateManager {
             message_ended: false,
             next_block_index: 0,
             stop_reason: None,
+            stop_sequence: None,
             has_tool_use: false,
         }
     }
@@ -282,6 +366,12 @@ impl SseStateManager {
         self.stop_reason = Some(reason.into());
     }
 
+    /// 以命中的停止序列结束消息
+    pub fn set_stop_sequence(&mut self, sequence: impl Into<String>) {
+        self.stop_reason = Some("stop_sequence".to_string());
+        self.stop_sequence = Some(sequence.into());
+    }
+
     /// 检查是否存在非 thinking 类型的内容块（如 text 或 tool_use）
     fn has_non_thinking_blocks(&self) -> bool {
         self.active_blocks
@@ -430,10 +520,12 @@ impl SseStateManager {
                     "type": "message_delta",
                     "delta": {
                         "stop_reason": self.get_stop_reason(),
-                        "stop_sequence": null
+                        "stop_sequence": self.stop_sequence
                     },
                     "usage": {
                         "input_tokens": input_tokens,
+                        "cache_creation_input_tokens": 0,
+                        "cache_read_input_tokens": 0,
                         "output_tokens": output_tokens
                     }
                 }),
@@ -456,6 +548,15 @@ impl SseStateManager {
 /// 上下文窗口大小（200k tokens）
 const CONTEXT_WINDOW_SIZE: i32 = 200_000;
 
+/// 由代理在本地执行的输出限制（上游不支持 stop_sequences 与 max_tokens）
+#[derive(Debug, Clone, Default)]
+pub struct OutputLimits {
+    /// 请求的停止序列
+    pub stop_sequences: Vec<String>,
+    /// 输出 tokens 上限（`None` 表示不限制）
+    pub max_tokens: Option<i32>,
+}
+
 /// 流处理上下文
 pub struct StreamContext {
     /// SSE 状态管理器
@@ -468,8 +569,10 @@ pub struct StreamContext {
     pub input_tokens: i32,
     /// 从 contextUsageEvent 计算的实际输入 tokens
     pub context_input_tokens: Option<i32>,
-    /// 输出 tokens 累计
+    /// 工具调用参数的输出 tokens 累计（文本与推理内容见 `output_text`）
     pub output_tokens: i32,
+    /// 已输出文本与推理内容的整体估算（逐增量估算会因取整累加而偏大）
+    output_text: TokenEstimate,
     /// 工具块索引映射 (tool_id -> block_index)
     pub tool_block_indices: HashMap<String, i32>,
     /// thinking 是否启用
@@ -487,6 +590,22 @@ pub struct StreamContext {
     /// 是否需要剥离 thinking 内容开头的换行符
     /// 模型输出 `<thinking>\n` 时，`\n` 可能与标签在同一 chunk 或下一 chunk
     strip_thinking_leading_newline: bool,
+    /// 推理内容的输出方式
+    thinking_output: ThinkingOutput,
+    /// 是否输出过推理内容（含被剥离的）
+    thinking_seen: bool,
+    /// 是否处于上游 reasoningContentEvent 形成的推理块内
+    reasoning_open: bool,
+    /// 请求的停止序列匹配
+    stop_sequences: StopSequenceMatcher,
+    /// 本地执行的输出 tokens 上限
+    max_tokens: Option<i32>,
+    /// 输出已因停止序列或 max_tokens 结束（后续上游事件被忽略）
+    output_limited: bool,
+    /// 输出是否被上游内容策略拦截
+    pub content_filtered: bool,
+    /// 上游已输出的原始助手内容（含 thinking 标签，用于流中断续传）
+    upstream_content: String,
 }
 
 impl StreamContext {
@@ -503,6 +622,7 @@ impl StreamContext {
             input_tokens,
             context_input_tokens: None,
             output_tokens: 0,
+            output_text: TokenEstimate::default(),
             tool_block_indices: HashMap::new(),
             thinking_enabled,
             thinking_buffer: String::new(),
@@ -511,9 +631,35 @@ impl StreamContext {
             thinking_block_index: None,
             text_block_index: None,
             strip_thinking_leading_newline: false,
+            thinking_output: ThinkingOutput::default(),
+            thinking_seen: false,
+            reasoning_open: false,
+            stop_sequences: StopSequenceMatcher::default(),
+            max_tokens: None,
+            output_limited: false,
+            content_filtered: false,
+            upstream_content: String::new(),
         }
     }
 
+    /// 设置推理内容的输出方式（默认作为 thinking 内容块输出）
+    pub fn with_thinking_output(mut self, thinking_output: ThinkingOutput) -> Self {
+        self.thinking_output = thinking_output;
+        self
+    }
+
+    /// 在本地执行请求的停止序列与 max_tokens
+    pub fn with_output_limits(mut self, limits: OutputLimits) -> Self {
+        self.stop_sequences = StopSequenceMatcher::new(limits.stop_sequences);
+        self.max_tokens = limits.max_tokens;
+        self
+    }
+
+    /// 输出是否已因停止序列或 max_tokens 结束（此时应结束流并取消上游请求）
+    pub fn output_limit_reached(&self) -> bool {
+        self.output_limited
+    }
+
     /// 生成 message_start 事件
     pub fn create_message_start_event(&self) -> serde_json::Value {
         json!({
@@ -528,6 +674,8 @@ impl StreamContext {
                 "stop_sequence": null,
                 "usage": {
                     "input_tokens": self.input_tokens,
+                    "cache_creation_input_tokens": 0,
+                    "cache_read_input_tokens": 0,
                     "output_tokens": 1
                 }
             }
@@ -575,9 +723,22 @@ impl StreamContext {
 
     /// 处理 Kiro 事件并转换为 Anthropic SSE 事件
     pub fn process_kiro_event(&mut self, event: &Event) -> Vec<SseEvent> {
+        if self.output_limited {
+            return Vec::new();
+        }
+
+        // 内容策略拦截：以 refusal 结束消息，而不是当作普通错误丢弃
+        if event.is_content_filter() {
+            tracing::warn!("输出被上游内容策略拦截: {:?}", event);
+            self.content_filtered = true;
+            self.state_manager.set_stop_reason("refusal");
+            return Vec::new();
+        }
+
         match event {
             Event::AssistantResponse(resp) => self.process_assistant_response(&resp.content),
             Event::ToolUse(tool_use) => self.process_tool_use(tool_use),
+            Event::ReasoningContent(reasoning) => self.process_reasoning(reasoning),
             Event::ContextUsage(context_usage) => {
                 // 从上下文使用百分比计算实际的 input_tokens
                 // 公式: percentage * 200000 / 100 = percentage * 2000
@@ -619,23 +780,168 @@ impl StreamContext {
         }
     }
 
+    /// 流中断续传所需的已输出内容；已开始工具调用时无法续接，返回 None
+    pub fn resume_content(&self) -> Option<&str> {
+        self.tool_block_indices
+            .is_empty()
+            .then_some(self.upstream_content.as_str())
+    }
+
     /// 处理助手响应事件
     fn process_assistant_response(&mut self, content: &str) -> Vec<SseEvent> {
+        let content = self.count_output(content);
         if content.is_empty() {
             return Vec::new();
         }
 
-        // 估算 tokens
-        self.output_tokens += estimate_tokens(content);
+        self.upstream_content.push_str(content);
+
+        let mut events = self.close_reasoning();
 
         // 如果启用了thinking，需要处理thinking块
         if self.thinking_enabled {
-            return self.process_content_with_thinking(content);
+            events.extend(self.process_content_with_thinking(content));
+            return events;
         }
 
         // 非 thinking 模式同样复用统一的 text_delta 发送逻辑，
         // 以便在 tool_use 自动关闭文本块后能够自愈重建新的文本块，避免“吞字”。
-        self.create_text_delta_events(content)
+        events.extend(self.create_text_delta_events(content));
+        events
+    }
+
+    /// 处理推理内容事件
+    fn process_reasoning(&mut self, reasoning: &ReasoningContentEvent) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        if !self.reasoning_open {
+            self.reasoning_open = true;
+            events.extend(self.start_thinking_events());
+        }
+        let text = self.count_output(&reasoning.text);
+        if !text.is_empty() {
+            events.extend(self.thinking_delta_events(text));
+        }
+        if let Some(signature) = &reasoning.signature {
+            events.extend(self.signature_delta_event(signature));
+        }
+        events
+    }
+
+    /// 累计输出内容的 tokens 估算，超出 max_tokens 时截断并结束输出
+    fn count_output<'a>(&mut self, content: &'a str) -> &'a str {
+        for (i, c) in content.char_indices() {
+            let mut estimate = self.output_text;
+            estimate.push(c);
+            if let Some(max_tokens) = self.max_tokens
+                && self.output_tokens + estimate.tokens() > max_tokens
+            {
+                tracing::debug!("输出达到 max_tokens={}，结束输出", max_tokens);
+                self.output_limited = true;
+                self.state_manager.set_stop_reason("max_tokens");
+                return &content[..i];
+            }
+            self.output_text = estimate;
+        }
+        content
+    }
+
+    /// 结束上游推理事件形成的推理块（在后续正文、工具调用或流结束前调用）
+    fn close_reasoning(&mut self) -> Vec<SseEvent> {
+        if !self.reasoning_open {
+            return Vec::new();
+        }
+        self.reasoning_open = false;
+        // 推理内容已由独立事件给出，后续正文不再探测 <thinking> 标签
+        self.thinking_extracted = true;
+        self.stop_thinking_events()
+    }
+
+    /// 开始输出推理内容
+    ///
+    /// passthrough 模式创建 thinking 内容块（先关闭仍打开的文本块，保证内容块依次输出），
+    /// tags 模式在正文中输出 `<thinking>` 开始标签，strip 模式不输出。
+    /// 推理内容不参与停止序列匹配（tags 模式下同样直接输出）
+    fn start_thinking_events(&mut self) -> Vec<SseEvent> {
+        self.thinking_seen = true;
+        match self.thinking_output {
+            ThinkingOutput::Strip => Vec::new(),
+            ThinkingOutput::Tags => {
+                let mut events = self.flush_stop_sequence_holdback();
+                events.extend(self.emit_text_delta_events("<thinking>\n"));
+                events
+            }
+            ThinkingOutput::Passthrough => {
+                let mut events = self.flush_stop_sequence_holdback();
+                if let Some(text_index) = self.text_block_index.take() {
+                    events.extend(self.state_manager.handle_content_block_stop(text_index));
+                }
+
+                let thinking_index = self.state_manager.next_block_index();
+                self.thinking_block_index = Some(thinking_index);
+                events.extend(self.state_manager.handle_content_block_start(
+                    thinking_index,
+                    "thinking",
+                    json!({
+                        "type": "content_block_start",
+                        "index": thinking_index,
+                        "content_block": {
+                            "type": "thinking",
+                            "thinking": ""
+                        }
+                    }),
+                ));
+                events
+            }
+        }
+    }
+
+    /// 输出推理内容增量
+    fn thinking_delta_events(&mut self, thinking: &str) -> Vec<SseEvent> {
+        match self.thinking_output {
+            ThinkingOutput::Strip => Vec::new(),
+            ThinkingOutput::Tags => self.emit_text_delta_events(thinking),
+            ThinkingOutput::Passthrough => self
+                .thinking_block_index
+                .map(|index| self.create_thinking_delta_event(index, thinking))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// 结束输出推理内容
+    fn stop_thinking_events(&mut self) -> Vec<SseEvent> {
+        match self.thinking_output {
+            ThinkingOutput::Strip => Vec::new(),
+            ThinkingOutput::Tags => self.emit_text_delta_events("\n</thinking>\n\n"),
+            ThinkingOutput::Passthrough => {
+                let Some(thinking_index) = self.thinking_block_index else {
+                    return Vec::new();
+                };
+                // 先发送空的 thinking_delta，再发送 content_block_stop
+                let mut events = vec![self.create_thinking_delta_event(thinking_index, "")];
+                events.extend(self.state_manager.handle_content_block_stop(thinking_index));
+                events
+            }
+        }
+    }
+
+    /// 创建 signature_delta 事件（仅 passthrough 模式输出）
+    fn signature_delta_event(&self, signature: &str) -> Option<SseEvent> {
+        if self.thinking_output != ThinkingOutput::Passthrough {
+            return None;
+        }
+        let index = self.thinking_block_index?;
+        Some(SseEvent::new(
+            "content_block_delta",
+            json!({
+                "type": "content_block_delta",
+                "index": index,
+                "delta": {
+                    "type": "signature_delta",
+                    "signature": signature
+                }
+            }),
+        ))
     }
 
     /// 处理包含thinking块的内容
@@ -663,22 +969,8 @@ impl StreamContext {
                     self.thinking_buffer =
                         self.thinking_buffer[start_pos + "<thinking>".len()..].to_string();
 
-                    // 创建 thinking 块的 content_block_start 事件
-                    let thinking_index = self.state_manager.next_block_index();
-                    self.thinking_block_index = Some(thinking_index);
-                    let start_events = self.state_manager.handle_content_block_start(
-                        thinking_index,
-                        "thinking",
-                        json!({
-                            "type": "content_block_start",
-                            "index": thinking_index,
-                            "content_block": {
-                                "type": "thinking",
-                                "thinking": ""
-                            }
-                        }),
-                    );
-                    events.extend(start_events);
+                    // 开始输出 thinking 内容（passthrough 模式为 thinking 块的 content_block_start）
+                    events.extend(self.start_thinking_events());
                 } else {
                     // 没有找到 <thinking>，检查是否可能是部分标签
                     // 保留可能是部分标签的内容
@@ -719,28 +1011,13 @@ impl StreamContext {
                     // 提取 thinking 内容
                     let thinking_content = self.thinking_buffer[..end_pos].to_string();
                     if !thinking_content.is_empty() {
-                        if let Some(thinking_index) = self.thinking_block_index {
-                            events.push(
-                                self.create_thinking_delta_event(thinking_index, &thinking_content),
-                            );
-                        }
+                        events.extend(self.thinking_delta_events(&thinking_content));
                     }
 
                     // 结束 thinking 块
                     self.in_thinking_block = false;
                     self.thinking_extracted = true;
-
-                    // 发送空的 thinking_delta 事件，然后发送 content_block_stop 事件
-                    if let Some(thinking_index) = self.thinking_block_index {
-                        // 先发送空的 thinking_delta
-                        events.push(self.create_thinking_delta_event(thinking_index, ""));
-                        // 再发送 content_block_stop
-                        if let Some(stop_event) =
-                            self.state_manager.handle_content_block_stop(thinking_index)
-                        {
-                            events.push(stop_event);
-                        }
-                    }
+                    events.extend(self.stop_thinking_events());
 
                     // 剥离 `</thinking>\n\n`（find_real_thinking_end_tag 已确认 \n\n 存在）
                     self.thinking_buffer =
@@ -760,11 +1037,7 @@ impl StreamContext {
                     if safe_len > 0 {
                         let safe_content = self.thinking_buffer[..safe_len].to_string();
                         if !safe_content.is_empty() {
-                            if let Some(thinking_index) = self.thinking_block_index {
-                                events.push(
-                                    self.create_thinking_delta_event(thinking_index, &safe_content),
-                                );
-                            }
+                            events.extend(self.thinking_delta_events(&safe_content));
                         }
                         self.thinking_buffer = self.thinking_buffer[safe_len..].to_string();
                     }
@@ -791,6 +1064,31 @@ impl StreamContext {
     ///
     /// 返回值包含可能的 content_block_start 事件和 content_block_delta 事件。
     fn create_text_delta_events(&mut self, text: &str) -> Vec<SseEvent> {
+        let text = self.stop_sequences.push(text);
+        if let Some(sequence) = self.stop_sequences.matched()
+            && !self.output_limited
+        {
+            tracing::debug!("输出命中停止序列 {:?}，结束输出", sequence);
+            self.output_limited = true;
+            self.state_manager.set_stop_sequence(sequence);
+        }
+        if text.is_empty() {
+            return Vec::new();
+        }
+        self.emit_text_delta_events(&text)
+    }
+
+    /// 输出停止序列匹配暂缓的文本（其后不再有相邻正文时调用）
+    fn flush_stop_sequence_holdback(&mut self) -> Vec<SseEvent> {
+        let pending = self.stop_sequences.flush();
+        if pending.is_empty() {
+            return Vec::new();
+        }
+        self.emit_text_delta_events(&pending)
+    }
+
+    /// 输出 text_delta（不经停止序列匹配）
+    fn emit_text_delta_events(&mut self, text: &str) -> Vec<SseEvent> {
         let mut events = Vec::new();
 
         // 如果当前 text_block_index 指向的块已经被关闭（例如 tool_use 开始时自动 stop），
@@ -864,7 +1162,8 @@ impl StreamContext {
         &mut self,
         tool_use: &crate::kiro::model::events::ToolUseEvent,
     ) -> Vec<SseEvent> {
-        let mut events = Vec::new();
+        let mut events = self.close_reasoning();
+        events.extend(self.flush_stop_sequence_holdback());
 
         self.state_manager.set_has_tool_use(true);
 
@@ -876,27 +1175,13 @@ impl StreamContext {
             if let Some(end_pos) = find_real_thinking_end_tag_at_buffer_end(&self.thinking_buffer) {
                 let thinking_content = self.thinking_buffer[..end_pos].to_string();
                 if !thinking_content.is_empty() {
-                    if let Some(thinking_index) = self.thinking_block_index {
-                        events.push(
-                            self.create_thinking_delta_event(thinking_index, &thinking_content),
-                        );
-                    }
+                    events.extend(self.thinking_delta_events(&thinking_content));
                 }
 
                 // 结束 thinking 块
                 self.in_thinking_block = false;
                 self.thinking_extracted = true;
-
-                if let Some(thinking_index) = self.thinking_block_index {
-                    // 先发送空的 thinking_delta
-                    events.push(self.create_thinking_delta_event(thinking_index, ""));
-                    // 再发送 content_block_stop
-                    if let Some(stop_event) =
-                        self.state_manager.handle_content_block_stop(thinking_index)
-                    {
-                        events.push(stop_event);
-                    }
-                }
+                events.extend(self.stop_thinking_events());
 
                 // 把结束标签后的内容当作普通文本（通常为空或空白）
                 let after_pos = end_pos + "</thinking>".len();
@@ -978,7 +1263,7 @@ impl StreamContext {
 
     /// 生成最终事件序列
     pub fn generate_final_events(&mut self) -> Vec<SseEvent> {
-        let mut events = Vec::new();
+        let mut events = self.close_reasoning();
 
         // Flush thinking_buffer 中的剩余内容
         if self.thinking_enabled && !self.thinking_buffer.is_empty() {
@@ -989,50 +1274,27 @@ impl StreamContext {
                 {
                     let thinking_content = self.thinking_buffer[..end_pos].to_string();
                     if !thinking_content.is_empty() {
-                        if let Some(thinking_index) = self.thinking_block_index {
-                            events.push(
-                                self.create_thinking_delta_event(thinking_index, &thinking_content),
-                            );
-                        }
+                        events.extend(self.thinking_delta_events(&thinking_content));
                     }
 
-                    // 关闭 thinking 块：先发送空的 thinking_delta，再发送 content_block_stop
-                    if let Some(thinking_index) = self.thinking_block_index {
-                        events.push(self.create_thinking_delta_event(thinking_index, ""));
-                        if let Some(stop_event) =
-                            self.state_manager.handle_content_block_stop(thinking_index)
-                        {
-                            events.push(stop_event);
-                        }
-                    }
+                    // 关闭 thinking 块
+                    self.in_thinking_block = false;
+                    self.thinking_extracted = true;
+                    events.extend(self.stop_thinking_events());
 
                     // 把结束标签后的内容当作普通文本（通常为空或空白）
                     let after_pos = end_pos + "</thinking>".len();
                     let remaining = self.thinking_buffer[after_pos..].trim_start().to_string();
                     self.thinking_buffer.clear();
-                    self.in_thinking_block = false;
-                    self.thinking_extracted = true;
                     if !remaining.is_empty() {
                         events.extend(self.create_text_delta_events(&remaining));
                     }
                 } else {
-                    // 如果还在 thinking 块内，发送剩余内容作为 thinking_delta
-                    if let Some(thinking_index) = self.thinking_block_index {
-                        events.push(
-                            self.create_thinking_delta_event(thinking_index, &self.thinking_buffer),
-                        );
-                    }
-                    // 关闭 thinking 块：先发送空的 thinking_delta，再发送 content_block_stop
-                    if let Some(thinking_index) = self.thinking_block_index {
-                        // 先发送空的 thinking_delta
-                        events.push(self.create_thinking_delta_event(thinking_index, ""));
-                        // 再发送 content_block_stop
-                        if let Some(stop_event) =
-                            self.state_manager.handle_content_block_stop(thinking_index)
-                        {
-                            events.push(stop_event);
-                        }
-                    }
+                    // 如果还在 thinking 块内，发送剩余内容作为 thinking_delta 后关闭 thinking 块
+                    let remaining = std::mem::take(&mut self.thinking_buffer);
+                    events.extend(self.thinking_delta_events(&remaining));
+                    self.in_thinking_block = false;
+                    events.extend(self.stop_thinking_events());
                 }
             } else {
                 // 否则发送剩余内容作为 text_delta
@@ -1042,27 +1304,43 @@ impl StreamContext {
             self.thinking_buffer.clear();
         }
 
+        // thinking 内容已全部输出、但结束标签未到达时同样需要结束 thinking 输出
+        if self.in_thinking_block {
+            self.in_thinking_block = false;
+            events.extend(self.stop_thinking_events());
+        }
+        events.extend(self.flush_stop_sequence_holdback());
+
         // 如果整个流中只产生了 thinking 块，没有 text 也没有 tool_use，
         // 则设置 stop_reason 为 max_tokens（表示模型耗尽了 token 预算在思考上），
         // 并补发一套完整的 text 事件（内容为一个空格），确保 content 数组中有 text 块
-        if self.thinking_enabled
-            && self.thinking_block_index.is_some()
-            && !self.state_manager.has_non_thinking_blocks()
-        {
-            self.state_manager.set_stop_reason("max_tokens");
-            events.extend(self.create_text_delta_events(" "));
+        if self.thinking_seen && !self.state_manager.has_non_thinking_blocks() {
+            if !self.content_filtered && !self.output_limited {
+                self.state_manager.set_stop_reason("max_tokens");
+            }
+            events.extend(self.emit_text_delta_events(" "));
         }
 
         // 使用从 contextUsageEvent 计算的 input_tokens，如果没有则使用估算值
-        let final_input_tokens = self.context_input_tokens.unwrap_or(self.input_tokens);
+        let (final_input_tokens, output_tokens) = self.token_usage();
 
         // 生成最终事件
         events.extend(
             self.state_manager
-                .generate_final_events(final_input_tokens, self.output_tokens),
+                .generate_final_events(final_input_tokens, output_tokens),
         );
         events
     }
+
+    /// 当前的 token 用量：(input_tokens, output_tokens)
+    ///
+    /// input_tokens 优先使用从 contextUsageEvent 计算的值，没有则使用估算值
+    pub fn token_usage(&self) -> (i32, i32) {
+        (
+            self.context_input_tokens.unwrap_or(self.input_tokens),
+            self.output_tokens + self.output_text.tokens(),
+        )
+    }
 }
 
 /// 缓冲流处理上下文 - 用于 /cc/v1/messages 流式请求
@@ -1103,6 +1381,38 @@ impl BufferedStreamContext {
         }
     }
 
+    /// 设置推理内容的输出方式（见 [`StreamContext::with_thinking_output`]）
+    pub fn with_thinking_output(mut self, thinking_output: ThinkingOutput) -> Self {
+        self.inner = self.inner.with_thinking_output(thinking_output);
+        self
+    }
+
+    /// 在本地执行请求的停止序列与 max_tokens（见 [`StreamContext::with_output_limits`]）
+    pub fn with_output_limits(mut self, limits: OutputLimits) -> Self {
+        self.inner = self.inner.with_output_limits(limits);
+        self
+    }
+
+    /// 输出是否已因停止序列或 max_tokens 结束
+    pub fn output_limit_reached(&self) -> bool {
+        self.inner.output_limit_reached()
+    }
+
+    /// 当前的 token 用量：(input_tokens, output_tokens)
+    pub fn token_usage(&self) -> (i32, i32) {
+        self.inner.token_usage()
+    }
+
+    /// 输出是否被上游内容策略拦截
+    pub fn content_filtered(&self) -> bool {
+        self.inner.content_filtered
+    }
+
+    /// 流中断续传所需的已输出内容（见 [`StreamContext::resume_content`]）
+    pub fn resume_content(&self) -> Option<&str> {
+        self.inner.resume_content()
+    }
+
     /// 处理 Kiro 事件并缓冲结果
     ///
     /// 复用 StreamContext 的事件处理逻辑，但把结果缓存而不是立即发送。
@@ -1158,25 +1468,58 @@ impl BufferedStreamContext {
     }
 }
 
-/// 简单的 token 估算
-fn estimate_tokens(text: &str) -> i32 {
-    let chars: Vec<char> = text.chars().collect();
-    let mut chinese_count = 0;
-    let mut other_count = 0;
-
-    for c in &chars {
-        if *c >= '\u{4E00}' && *c <= '\u{9FFF}' {
-            chinese_count += 1;
+/// 截取估算 tokens 不超过 `budget` 的最长前缀（在字符边界截断）
+pub(crate) fn truncate_to_tokens(text: &str, budget: i32) -> &str {
+    if budget <= 0 {
+        return "";
+    }
+    if estimate_tokens(text) <= budget {
+        return text;
+    }
+    // 前缀的估算值随长度单调不减，二分查找字符数
+    let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+    let (mut low, mut high) = (0, boundaries.len());
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        let end = boundaries.get(mid).copied().unwrap_or(text.len());
+        if estimate_tokens(&text[..end]) <= budget {
+            low = mid;
         } else {
-            other_count += 1;
+            high = mid - 1;
         }
     }
+    &text[..boundaries.get(low).copied().unwrap_or(text.len())]
+}
+
+/// 简单的 token 估算
+pub(crate) fn estimate_tokens(text: &str) -> i32 {
+    let mut estimate = TokenEstimate::default();
+    text.chars().for_each(|c| estimate.push(c));
+    estimate.tokens().max(1)
+}
+
+/// 可逐字符追加的 token 估算（按中文与其他字符分别计数）
+#[derive(Debug, Default, Clone, Copy)]
+struct TokenEstimate {
+    chinese_count: i32,
+    other_count: i32,
+}
 
-    // 中文约 1.5 字符/token，英文约 4 字符/token
-    let chinese_tokens = (chinese_count * 2 + 2) / 3;
-    let other_tokens = (other_count + 3) / 4;
+impl TokenEstimate {
+    fn push(&mut self, c: char) {
+        if ('\u{4E00}'..='\u{9FFF}').contains(&c) {
+            self.chinese_count += 1;
+        } else {
+            self.other_count += 1;
+        }
+    }
 
-    (chinese_tokens + other_tokens).max(1)
+    fn tokens(&self) -> i32 {
+        // 中文约 1.5 字符/token，英文约 4 字符/token
+        let chinese_tokens = (self.chinese_count * 2 + 2) / 3;
+        let other_tokens = (self.other_count + 3) / 4;
+        chinese_tokens + other_tokens
+    }
 }
 
 #[cfg(test)]
@@ -1193,6 +1536,48 @@ mod tests {
         assert!(sse_str.ends_with("\n\n"));
     }
 
+    #[test]
+    fn test_split_delta_respects_max_bytes_and_char_boundaries() {
+        let event = SseEvent::new(
+            "content_block_delta",
+            json!({
+                "type": "content_block_delta",
+                "index": 2,
+                "delta": {"type": "text_delta", "text": "ab你好cd"}
+            }),
+        );
+
+        let chunks = event.clone().split_delta(4);
+        let texts: Vec<&str> = chunks
+            .iter()
+            .map(|e| e.data["delta"]["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(texts, vec!["ab", "你", "好c", "d"]);
+        assert!(chunks.iter().all(|e| e.data["index"] == 2));
+
+        // 0 表示不拆分；未超限与非增量事件原样返回
+        assert_eq!(event.clone().split_delta(0).len(), 1);
+        assert_eq!(event.split_delta(64).len(), 1);
+        let stop = SseEvent::new("content_block_stop", json!({"type": "content_block_stop"}));
+        assert_eq!(stop.split_delta(1).len(), 1);
+
+        // 工具参数增量同样拆分，拼接后与原内容一致
+        let json_delta = SseEvent::new(
+            "content_block_delta",
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "input_json_delta", "partial_json": "{\"city\":\"Tokyo\"}"}
+            }),
+        );
+        let joined: String = json_delta
+            .split_delta(5)
+            .iter()
+            .map(|e| e.data["delta"]["partial_json"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(joined, "{\"city\":\"Tokyo\"}");
+    }
+
     #[test]
     fn test_sse_state_manager_message_start() {
         let mut manager = SseStateManager::new();
@@ -1892,4 +2277,224 @@ mod tests {
             "stop_reason should be tool_use when tool_use is present"
         );
     }
+
+    #[test]
+    fn test_content_filter_exception_sets_refusal_stop_reason() {
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false);
+        let mut all_events = ctx.generate_initial_events();
+        all_events.extend(ctx.process_assistant_response("partial"));
+        all_events.extend(ctx.process_kiro_event(&Event::Exception {
+            exception_type: "GuardrailInterventionException".to_string(),
+            message: "blocked".to_string(),
+        }));
+        all_events.extend(ctx.generate_final_events());
+
+        assert!(ctx.content_filtered);
+        let message_delta = all_events
+            .iter()
+            .find(|e| e.event == "message_delta")
+            .expect("should have message_delta event");
+        assert_eq!(message_delta.data["delta"]["stop_reason"], "refusal");
+        assert!(all_events.iter().all(|e| e.event != "error"));
+    }
+
+    fn reasoning(text: &str, signature: Option<&str>) -> Event {
+        Event::ReasoningContent(ReasoningContentEvent {
+            text: text.to_string(),
+            signature: signature.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn test_reasoning_events_become_thinking_block() {
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, true);
+        let mut all_events = ctx.generate_initial_events();
+        all_events.extend(ctx.process_kiro_event(&reasoning("Let me ", None)));
+        all_events.extend(ctx.process_kiro_event(&reasoning("think", Some("sig"))));
+        all_events.extend(ctx.process_assistant_response("Answer"));
+        all_events.extend(ctx.generate_final_events());
+
+        let kinds: Vec<String> = all_events
+            .iter()
+            .filter(|e| e.event.starts_with("content_block"))
+            .map(|e| {
+                let detail = &e.data["delta"]["type"];
+                let detail = detail.as_str().or(e.data["content_block"]["type"].as_str());
+                format!("{}:{}:{}", e.event, e.data["index"], detail.unwrap_or(""))
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "content_block_start:0:thinking",
+                "content_block_delta:0:thinking_delta",
+                "content_block_delta:0:thinking_delta",
+                "content_block_delta:0:signature_delta",
+                "content_block_delta:0:thinking_delta",
+                "content_block_stop:0:",
+                "content_block_start:1:text",
+                "content_block_delta:1:text_delta",
+                "content_block_stop:1:",
+            ]
+        );
+        let message_delta = all_events.iter().find(|e| e.event == "message_delta");
+        assert_eq!(message_delta.unwrap().data["delta"]["stop_reason"], "end_turn");
+    }
+
+    #[test]
+    fn test_thinking_output_tags_and_strip() {
+        let text_of = |events: &[SseEvent]| -> String {
+            events
+                .iter()
+                .filter(|e| e.data["delta"]["type"] == "text_delta")
+                .filter_map(|e| e.data["delta"]["text"].as_str())
+                .collect()
+        };
+
+        for (mode, expected) in [
+            (ThinkingOutput::Tags, "<thinking>\nhmm\n</thinking>\n\nHi"),
+            (ThinkingOutput::Strip, "Hi"),
+        ] {
+            // 上游推理事件
+            let mut ctx =
+                StreamContext::new_with_thinking("test-model", 1, false).with_thinking_output(mode);
+            let mut events = ctx.generate_initial_events();
+            events.extend(ctx.process_kiro_event(&reasoning("hmm", Some("sig"))));
+            events.extend(ctx.process_assistant_response("Hi"));
+            events.extend(ctx.generate_final_events());
+            assert_eq!(text_of(&events), expected);
+
+            // 正文中的 <thinking> 标签
+            let mut ctx =
+                StreamContext::new_with_thinking("test-model", 1, true).with_thinking_output(mode);
+            let mut events = ctx.generate_initial_events();
+            events.extend(ctx.process_assistant_response("<thinking>\nhmm</thinking>\n\nHi"));
+            events.extend(ctx.generate_final_events());
+            assert_eq!(text_of(&events), expected);
+            assert!(events.iter().all(|e| {
+                e.data["content_block"]["type"] != "thinking"
+                    && e.data["delta"]["type"] != "thinking_delta"
+            }));
+        }
+    }
+
+    #[test]
+    fn test_split_thinking() {
+        assert_eq!(
+            split_thinking("\n\n<thinking>\nabc</thinking>\n\nanswer"),
+            Some(("abc", "answer"))
+        );
+        assert_eq!(split_thinking("<thinking>abc</thinking>"), Some(("abc", "")));
+        assert_eq!(split_thinking("<thinking>unfinished"), Some(("unfinished", "")));
+        assert_eq!(split_thinking("answer <thinking>x</thinking>\n\n"), None);
+        assert_eq!(split_thinking("plain answer"), None);
+    }
+
+    #[test]
+    fn test_stop_sequence_ends_output() {
+        let limits = OutputLimits {
+            stop_sequences: vec!["STOP".to_string()],
+            max_tokens: None,
+        };
+        let mut ctx =
+            StreamContext::new_with_thinking("test-model", 1, false).with_output_limits(limits);
+        let mut events = ctx.generate_initial_events();
+        events.extend(ctx.process_assistant_response("Hello ST"));
+        assert!(!ctx.output_limit_reached());
+        events.extend(ctx.process_assistant_response("OP world"));
+        assert!(ctx.output_limit_reached());
+        events.extend(ctx.process_kiro_event(&reasoning("ignored", None)));
+        events.extend(ctx.generate_final_events());
+
+        let text: String = events
+            .iter()
+            .filter_map(|e| e.data["delta"]["text"].as_str())
+            .collect();
+        assert_eq!(text, "Hello ");
+        let message_delta = events.iter().find(|e| e.event == "message_delta").unwrap();
+        assert_eq!(message_delta.data["delta"]["stop_reason"], "stop_sequence");
+        assert_eq!(message_delta.data["delta"]["stop_sequence"], "STOP");
+    }
+
+    #[test]
+    fn test_stop_sequence_ignores_tagged_reasoning() {
+        let limits = OutputLimits {
+            stop_sequences: vec!["STOP".to_string()],
+            max_tokens: None,
+        };
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false)
+            .with_thinking_output(ThinkingOutput::Tags)
+            .with_output_limits(limits);
+        let mut events = ctx.generate_initial_events();
+        events.extend(ctx.process_kiro_event(&reasoning("think STOP here", None)));
+        assert!(!ctx.output_limit_reached());
+        events.extend(ctx.process_assistant_response("Answer STOP tail"));
+        assert!(ctx.output_limit_reached());
+        events.extend(ctx.generate_final_events());
+
+        let text: String = events
+            .iter()
+            .filter_map(|e| e.data["delta"]["text"].as_str())
+            .collect();
+        assert_eq!(text, "<thinking>\nthink STOP here\n</thinking>\n\nAnswer ");
+        let message_delta = events.iter().find(|e| e.event == "message_delta").unwrap();
+        assert_eq!(message_delta.data["delta"]["stop_reason"], "stop_sequence");
+    }
+
+    #[test]
+    fn test_max_tokens_truncates_output() {
+        let limits = OutputLimits {
+            stop_sequences: Vec::new(),
+            max_tokens: Some(3),
+        };
+        let mut ctx =
+            StreamContext::new_with_thinking("test-model", 1, false).with_output_limits(limits);
+        let mut events = ctx.generate_initial_events();
+        events.extend(ctx.process_assistant_response(&"word ".repeat(20)));
+        assert!(ctx.output_limit_reached());
+        events.extend(ctx.generate_final_events());
+
+        let text: String = events
+            .iter()
+            .filter_map(|e| e.data["delta"]["text"].as_str())
+            .collect();
+        assert!(!text.is_empty());
+        assert!(estimate_tokens(&text) <= 3);
+        assert!(ctx.token_usage().1 <= 3);
+        let message_delta = events.iter().find(|e| e.event == "message_delta").unwrap();
+        assert_eq!(message_delta.data["delta"]["stop_reason"], "max_tokens");
+        assert!(message_delta.data["delta"]["stop_sequence"].is_null());
+
+        assert_eq!(truncate_to_tokens("你好世界", 0), "");
+        assert_eq!(truncate_to_tokens("hi", 10), "hi");
+    }
+
+    #[test]
+    fn test_max_tokens_counts_small_deltas_as_a_whole() {
+        // 逐字符的增量各自估算会被取整为 1 token，整体估算时 40 个字符只有 10 tokens
+        let limits = OutputLimits {
+            stop_sequences: Vec::new(),
+            max_tokens: Some(10),
+        };
+        let mut ctx =
+            StreamContext::new_with_thinking("test-model", 1, false).with_output_limits(limits);
+        let mut events = ctx.generate_initial_events();
+        for c in "abcd".repeat(10).chars() {
+            events.extend(ctx.process_assistant_response(&c.to_string()));
+        }
+        assert!(!ctx.output_limit_reached());
+        assert_eq!(ctx.token_usage().1, 10);
+
+        events.extend(ctx.process_assistant_response("e"));
+        assert!(ctx.output_limit_reached());
+        events.extend(ctx.generate_final_events());
+
+        let text: String = events
+            .iter()
+            .filter_map(|e| e.data["delta"]["text"].as_str())
+            .collect();
+        assert_eq!(text, "abcd".repeat(10));
+        let message_delta = events.iter().find(|e| e.event == "message_delta").unwrap();
+        assert_eq!(message_delta.data["delta"]["stop_reason"], "max_tokens");
+    }
 }
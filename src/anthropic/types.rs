@@ -3,6 +3,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::api_keys::QuotaExceeded;
+
 // === 错误响应 ===
 
 /// API 错误响应
@@ -36,10 +38,32 @@ impl ErrorResponse {
     }
 }
 
+/// 托管 API Key 配额超限响应（在标准错误结构外附带超限的配额项与重置时间）
+#[derive(Debug, Serialize)]
+pub struct QuotaErrorResponse {
+    pub error: ErrorDetail,
+    pub quota: QuotaExceeded,
+}
+
+impl QuotaErrorResponse {
+    pub fn new(quota: QuotaExceeded) -> Self {
+        Self {
+            error: ErrorDetail {
+                error_type: "rate_limit_error".to_string(),
+                message: format!(
+                    "API key {} quota exceeded ({}/{})",
+                    quota.quota, quota.used, quota.limit
+                ),
+            },
+            quota,
+        }
+    }
+}
+
 // === Models 端点类型 ===
 
 /// 模型信息
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Model {
     pub id: String,
     pub object: String,
@@ -64,7 +88,7 @@ pub struct ModelsResponse {
 const MAX_BUDGET_TOKENS: i32 = 24576;
 
 /// Thinking 配置
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Thinking {
     #[serde(rename = "type")]
     pub thinking_type: String,
@@ -94,7 +118,7 @@ where
 }
 
 /// OutputConfig 配置
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct OutputConfig {
     #[serde(default = "default_effort")]
     pub effort: String,
@@ -125,6 +149,9 @@ pub struct MessagesRequest {
     pub tool_choice: Option<serde_json::Value>,
     pub thinking: Option<Thinking>,
     pub output_config: Option<OutputConfig>,
+    /// 自定义停止序列（上游不支持，由代理在输出中匹配）
+    #[serde(default)]
+    pub stop_sequences: Option<Vec<String>>,
     /// Claude Code 请求中的 metadata，包含 session 信息
     pub metadata: Option<Metadata>,
 }
@@ -152,6 +179,7 @@ where
         {
             Ok(Some(vec![SystemMessage {
                 text: value.to_string(),
+                cache_control: None,
             }]))
         }
 
@@ -200,6 +228,18 @@ pub struct Message {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SystemMessage {
     pub text: String,
+    /// Prompt caching 断点（上游不支持，转换时剥离）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// Prompt caching 断点标记（`{"type": "ephemeral", "ttl": "5m"}`）
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheControl {
+    #[serde(rename = "type")]
+    pub cache_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
 }
 
 /// 工具定义
@@ -224,6 +264,9 @@ pub struct Tool {
     /// 最大使用次数（仅 WebSearch 工具）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_uses: Option<i32>,
+    /// Prompt caching 断点（上游不支持，转换时剥离）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
 }
 
 impl Tool {
@@ -0,0 +1,174 @@
+//! 流式响应用量补全
+//!
+//! 并非所有流式路径都能在最终的 `message_delta` 中给出完整的 `usage`（例如 WebSearch
+//! 只带 `output_tokens`）。这里在响应出口观察 `message_start` 与全部内容增量，
+//! `message_delta` 缺少某项用量时用本地计数补全：输入沿用 `message_start` 中的值，
+//! 没有时使用请求的输入估算（配置了外部 count_tokens API 时即为其返回值）；
+//! 输出按累计输出内容计数。已有的用量字段保持不变
+
+use axum::{body::Body, http::header, response::Response};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde_json::{Value, json};
+
+use crate::token;
+
+/// 流式用量补全状态
+pub struct UsageInjector {
+    /// 请求的输入 tokens 估算
+    estimated_input_tokens: i32,
+    /// `message_start` 中的 input_tokens
+    start_input_tokens: Option<i64>,
+    /// 累计的输出内容（文本、thinking、工具参数）
+    output: String,
+}
+
+impl UsageInjector {
+    pub fn new(estimated_input_tokens: i32) -> Self {
+        Self {
+            estimated_input_tokens,
+            start_input_tokens: None,
+            output: String::new(),
+        }
+    }
+
+    /// 处理一个 SSE 数据块（每个数据块恰好是一个完整事件），必要时改写 `message_delta`
+    pub fn process(&mut self, chunk: Bytes) -> Bytes {
+        let Some((event, data)) = split_event(&chunk) else {
+            return chunk;
+        };
+        match event {
+            "message_start" => {
+                if let Ok(value) = serde_json::from_slice::<Value>(data) {
+                    self.start_input_tokens = value["message"]["usage"]["input_tokens"].as_i64();
+                }
+                chunk
+            }
+            "content_block_delta" => {
+                if let Ok(value) = serde_json::from_slice::<Value>(data) {
+                    let delta = &value["delta"];
+                    for key in ["text", "thinking", "partial_json"] {
+                        if let Some(text) = delta[key].as_str() {
+                            self.output.push_str(text);
+                        }
+                    }
+                }
+                chunk
+            }
+            "message_delta" => {
+                let Ok(mut value) = serde_json::from_slice::<Value>(data) else {
+                    return chunk;
+                };
+                if self.fill_usage(&mut value) {
+                    Bytes::from(format!("event: message_delta\ndata: {}\n\n", value))
+                } else {
+                    chunk
+                }
+            }
+            _ => chunk,
+        }
+    }
+
+    /// 补全缺失的用量字段，返回是否有改动
+    fn fill_usage(&self, value: &mut Value) -> bool {
+        if !value["usage"].is_object() {
+            value["usage"] = json!({});
+        }
+        let usage = &mut value["usage"];
+        let mut changed = false;
+        if usage.get("input_tokens").is_none() {
+            usage["input_tokens"] = json!(
+                self.start_input_tokens
+                    .unwrap_or(self.estimated_input_tokens as i64)
+            );
+            changed = true;
+        }
+        if usage.get("output_tokens").is_none() {
+            usage["output_tokens"] = json!(token::count_tokens(&self.output));
+            changed = true;
+        }
+        changed
+    }
+}
+
+/// 拆分 `event: <name>\ndata: <json>\n\n` 格式的事件
+fn split_event(chunk: &[u8]) -> Option<(&str, &[u8])> {
+    let rest = chunk.strip_prefix(b"event: ")?;
+    let newline = rest.iter().position(|b| *b == b'\n')?;
+    let event = std::str::from_utf8(&rest[..newline]).ok()?;
+    let data = rest[newline + 1..]
+        .strip_prefix(b"data: ")?
+        .strip_suffix(b"\n\n")?;
+    Some((event, data))
+}
+
+/// 为成功的流式响应补全 `message_delta` 用量（非流式或失败响应原样返回）
+pub fn attach(response: Response, estimated_input_tokens: i32) -> Response {
+    let is_stream = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"text/event-stream"));
+    if !response.status().is_success() || !is_stream {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let mut injector = UsageInjector::new(estimated_input_tokens);
+    let stream = body
+        .into_data_stream()
+        .map(move |item| item.map(|chunk| injector.process(chunk)));
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str, data: Value) -> Bytes {
+        Bytes::from(format!("event: {}\ndata: {}\n\n", name, data))
+    }
+
+    fn data_of(chunk: &Bytes) -> Value {
+        serde_json::from_slice(split_event(chunk).unwrap().1).unwrap()
+    }
+
+    #[test]
+    fn test_fills_missing_usage() {
+        let mut injector = UsageInjector::new(42);
+        injector.process(event(
+            "message_start",
+            json!({"type": "message_start", "message": {"usage": {"input_tokens": 120, "output_tokens": 0}}}),
+        ));
+        injector.process(event(
+            "content_block_delta",
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "hello world"}}),
+        ));
+
+        let delta = injector.process(event(
+            "message_delta",
+            json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"server_tool_use": {"web_search_requests": 1}}}),
+        ));
+        let usage = &data_of(&delta)["usage"];
+        assert_eq!(usage["input_tokens"], 120);
+        assert_eq!(usage["output_tokens"], token::count_tokens("hello world"));
+        assert_eq!(usage["server_tool_use"]["web_search_requests"], 1);
+
+        // 没有 message_start 时使用请求估算
+        let mut injector = UsageInjector::new(42);
+        let delta = injector.process(event("message_delta", json!({"type": "message_delta"})));
+        assert_eq!(data_of(&delta)["usage"]["input_tokens"], 42);
+    }
+
+    #[test]
+    fn test_keeps_complete_usage() {
+        let mut injector = UsageInjector::new(42);
+        let delta = event(
+            "message_delta",
+            json!({"type": "message_delta", "usage": {"input_tokens": 7, "output_tokens": 0}}),
+        );
+        assert_eq!(injector.process(delta.clone()), delta);
+
+        let ping = Bytes::from("event: ping\ndata: {\"type\": \"ping\"}\n\n");
+        assert_eq!(injector.process(ping.clone()), ping);
+    }
+}
@@ -0,0 +1,627 @@
+//! 请求体校验
+//!
+//! 在反序列化前按 Anthropic API 的约定检查请求体：messages 结构、角色、内容块类型与参数范围。
+//! 校验失败时返回 400 `invalid_request_error`，错误信息以 `messages.1.content.0.type: ...`
+//! 的形式指出出错的字段与位置，替代 serde 笼统的反序列化错误
+
+use axum::Json;
+use axum::extract::{FromRequest, Request};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use super::types::{CountTokensRequest, ErrorResponse, MessagesRequest};
+
+/// thinking.budget_tokens 的下限
+const MIN_BUDGET_TOKENS: i64 = 1024;
+
+/// 允许出现在消息中的内容块类型
+const CONTENT_BLOCK_TYPES: &[&str] = &[
+    "text",
+    "image",
+    "image_url",
+    "document",
+    "tool_use",
+    "tool_result",
+    "thinking",
+    "redacted_thinking",
+    "server_tool_use",
+    "web_search_tool_result",
+    "search_result",
+];
+
+/// 校验错误：出错字段的路径与原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl IntoResponse for ValidationError {
+    fn into_response(self) -> Response {
+        invalid_request(self.to_string())
+    }
+}
+
+/// 400 invalid_request_error 响应
+fn invalid_request(message: String) -> Response {
+    tracing::debug!("请求体校验失败: {}", message);
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request_error", message)),
+    )
+        .into_response()
+}
+
+/// 反序列化前需要校验的请求体
+pub trait ValidateRequest {
+    fn validate(body: &Value) -> Result<(), ValidationError>;
+}
+
+impl ValidateRequest for MessagesRequest {
+    fn validate(body: &Value) -> Result<(), ValidationError> {
+        let body = object(body, "")?;
+        required_model(body)?;
+        let max_tokens = required(body, "max_tokens")?;
+        integer_in(max_tokens, "max_tokens", 1, i32::MAX as i64)?;
+        validate_messages(body)?;
+        optional_bool(body, "stream")?;
+        validate_system(body)?;
+        validate_tools(body)?;
+        validate_tool_choice(body)?;
+        validate_thinking(body)?;
+        validate_sampling(body)?;
+        if let Some(stop_sequences) = present(body, "stop_sequences") {
+            let items = array(stop_sequences, "stop_sequences")?;
+            for (i, item) in items.iter().enumerate() {
+                string(item, &format!("stop_sequences.{}", i))?;
+            }
+        }
+        if let Some(metadata) = present(body, "metadata") {
+            object(metadata, "metadata")?;
+        }
+        Ok(())
+    }
+}
+
+impl ValidateRequest for CountTokensRequest {
+    fn validate(body: &Value) -> Result<(), ValidationError> {
+        let body = object(body, "")?;
+        required_model(body)?;
+        validate_messages(body)?;
+        validate_system(body)?;
+        validate_tools(body)?;
+        Ok(())
+    }
+}
+
+/// 先校验再反序列化的 JSON 提取器（替代 `axum::Json`）
+pub struct ValidatedJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + ValidateRequest,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let value: Value = serde_json::from_slice(&body)
+            .map_err(|e| invalid_request(format!("请求体不是合法的 JSON: {}", e)))?;
+        T::validate(&value).map_err(IntoResponse::into_response)?;
+        // 校验已覆盖常见问题，剩余的反序列化错误原样返回
+        serde_json::from_value(value)
+            .map(ValidatedJson)
+            .map_err(|e| invalid_request(e.to_string()))
+    }
+}
+
+/// 检查消息数上限（maxMessages，0 表示不限制）
+pub fn check_message_count(count: usize, max: Option<usize>) -> Result<(), ValidationError> {
+    match max {
+        Some(max) if max > 0 && count > max => Err(ValidationError::new(
+            "messages",
+            format!("消息数 {} 超过上限 {}", count, max),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 检查估算输入 tokens 上限（maxInputTokens，0 表示不限制）
+pub fn check_input_tokens(tokens: i32, max: Option<u64>) -> Result<(), ValidationError> {
+    match max {
+        Some(max) if max > 0 && tokens.max(0) as u64 > max => Err(ValidationError::new(
+            "",
+            format!("输入过长：估算 {} tokens，超过上限 {}", tokens, max),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn required_model(body: &Map<String, Value>) -> Result<(), ValidationError> {
+    let model = string(required(body, "model")?, "model")?;
+    if model.trim().is_empty() {
+        return Err(ValidationError::new("model", "不能为空"));
+    }
+    Ok(())
+}
+
+/// messages 必须是非空数组，首条消息为 user，角色只能是 user / assistant
+///
+/// 相邻的同角色消息是允许的（与 Anthropic API 一致，转换时合并）
+fn validate_messages(body: &Map<String, Value>) -> Result<(), ValidationError> {
+    let messages = array(required(body, "messages")?, "messages")?;
+    if messages.is_empty() {
+        return Err(ValidationError::new("messages", "至少需要一条消息"));
+    }
+
+    for (i, message) in messages.iter().enumerate() {
+        let path = format!("messages.{}", i);
+        let message = object(message, &path)?;
+
+        let role_path = format!("{}.role", path);
+        let role = string(required_at(message, "role", &path)?, &role_path)?;
+        if role != "user" && role != "assistant" {
+            return Err(ValidationError::new(
+                role_path,
+                format!("必须为 \"user\" 或 \"assistant\"，实际为 {:?}", role),
+            ));
+        }
+        if i == 0 && role != "user" {
+            return Err(ValidationError::new(role_path, "第一条消息必须是 \"user\""));
+        }
+
+        let content_path = format!("{}.content", path);
+        match required_at(message, "content", &path)? {
+            Value::String(_) => {}
+            Value::Array(blocks) => {
+                for (j, block) in blocks.iter().enumerate() {
+                    validate_content_block(block, role, &format!("{}.{}", content_path, j))?;
+                }
+            }
+            other => {
+                return Err(type_error(&content_path, "字符串或内容块数组", other));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 校验单个内容块：类型已知、必需字段齐全，tool_use / tool_result 出现在正确的角色中
+fn validate_content_block(block: &Value, role: &str, path: &str) -> Result<(), ValidationError> {
+    let block = object(block, path)?;
+    let type_path = format!("{}.type", path);
+    let block_type = string(required_at(block, "type", path)?, &type_path)?;
+    if !CONTENT_BLOCK_TYPES.contains(&block_type) {
+        return Err(ValidationError::new(
+            type_path,
+            format!(
+                "不支持的内容块类型 {:?}（支持：{}）",
+                block_type,
+                CONTENT_BLOCK_TYPES.join(", ")
+            ),
+        ));
+    }
+
+    let field = |name: &str| format!("{}.{}", path, name);
+    match block_type {
+        "text" => {
+            string(required_at(block, "text", path)?, &field("text"))?;
+        }
+        "thinking" => {
+            string(required_at(block, "thinking", path)?, &field("thinking"))?;
+        }
+        "image" | "document" => {
+            object(required_at(block, "source", path)?, &field("source"))?;
+        }
+        // OpenAI 风格的图片块（字符串或 `{"url": ...}`），转换前改写为 image 块
+        "image_url" => match required_at(block, "image_url", path)? {
+            Value::String(_) => {}
+            Value::Object(image_url) => {
+                let url_path = format!("{}.url", field("image_url"));
+                string(
+                    required_at(image_url, "url", &field("image_url"))?,
+                    &url_path,
+                )?;
+            }
+            other => return Err(type_error(&field("image_url"), "字符串或对象", other)),
+        },
+        "tool_use" => {
+            if role != "assistant" {
+                return Err(ValidationError::new(
+                    type_path,
+                    "tool_use 只能出现在 assistant 消息中",
+                ));
+            }
+            string(required_at(block, "id", path)?, &field("id"))?;
+            string(required_at(block, "name", path)?, &field("name"))?;
+            object(required_at(block, "input", path)?, &field("input"))?;
+        }
+        "tool_result" => {
+            if role != "user" {
+                return Err(ValidationError::new(
+                    type_path,
+                    "tool_result 只能出现在 user 消息中",
+                ));
+            }
+            string(
+                required_at(block, "tool_use_id", path)?,
+                &field("tool_use_id"),
+            )?;
+            if let Some(is_error) = present(block, "is_error")
+                && !is_error.is_boolean()
+            {
+                return Err(type_error(&field("is_error"), "布尔值", is_error));
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// system 可以是字符串或 `{"type": "text", "text": ...}` 数组
+fn validate_system(body: &Map<String, Value>) -> Result<(), ValidationError> {
+    match present(body, "system") {
+        None | Some(Value::String(_)) => Ok(()),
+        Some(Value::Array(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                let path = format!("system.{}", i);
+                let item = object(item, &path)?;
+                string(required_at(item, "text", &path)?, &format!("{}.text", path))?;
+            }
+            Ok(())
+        }
+        Some(other) => Err(type_error("system", "字符串或数组", other)),
+    }
+}
+
+/// 普通工具需要 name 与 input_schema，服务端工具（带 type，如 web_search）只需 name
+fn validate_tools(body: &Map<String, Value>) -> Result<(), ValidationError> {
+    let Some(tools) = present(body, "tools") else {
+        return Ok(());
+    };
+    for (i, tool) in array(tools, "tools")?.iter().enumerate() {
+        let path = format!("tools.{}", i);
+        let tool = object(tool, &path)?;
+        let name = string(required_at(tool, "name", &path)?, &format!("{}.name", path))?;
+        if name.is_empty() {
+            return Err(ValidationError::new(format!("{}.name", path), "不能为空"));
+        }
+        if present(tool, "type").is_none() || present(tool, "input_schema").is_some() {
+            object(
+                required_at(tool, "input_schema", &path)?,
+                &format!("{}.input_schema", path),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn validate_tool_choice(body: &Map<String, Value>) -> Result<(), ValidationError> {
+    let Some(tool_choice) = present(body, "tool_choice") else {
+        return Ok(());
+    };
+    let tool_choice = object(tool_choice, "tool_choice")?;
+    let choice_type = string(
+        required_at(tool_choice, "type", "tool_choice")?,
+        "tool_choice.type",
+    )?;
+    match choice_type {
+        "auto" | "any" | "none" => Ok(()),
+        "tool" => {
+            string(
+                required_at(tool_choice, "name", "tool_choice")?,
+                "tool_choice.name",
+            )?;
+            Ok(())
+        }
+        other => Err(ValidationError::new(
+            "tool_choice.type",
+            format!(
+                "必须为 \"auto\"、\"any\"、\"tool\" 或 \"none\"，实际为 {:?}",
+                other
+            ),
+        )),
+    }
+}
+
+fn validate_thinking(body: &Map<String, Value>) -> Result<(), ValidationError> {
+    let Some(thinking) = present(body, "thinking") else {
+        return Ok(());
+    };
+    let thinking = object(thinking, "thinking")?;
+    let thinking_type = string(required_at(thinking, "type", "thinking")?, "thinking.type")?;
+    match thinking_type {
+        "enabled" | "adaptive" => {
+            // 未指定 budget_tokens 时使用默认预算
+            if let Some(budget) = present(thinking, "budget_tokens") {
+                integer_in(
+                    budget,
+                    "thinking.budget_tokens",
+                    MIN_BUDGET_TOKENS,
+                    i32::MAX as i64,
+                )?;
+            }
+            Ok(())
+        }
+        "disabled" => Ok(()),
+        other => Err(ValidationError::new(
+            "thinking.type",
+            format!(
+                "必须为 \"enabled\"、\"disabled\" 或 \"adaptive\"，实际为 {:?}",
+                other
+            ),
+        )),
+    }
+}
+
+/// temperature / top_p 取值 [0, 1]，top_k 为非负整数（上游不使用，仅校验范围）
+fn validate_sampling(body: &Map<String, Value>) -> Result<(), ValidationError> {
+    for name in ["temperature", "top_p"] {
+        if let Some(value) = present(body, name) {
+            let Some(number) = value.as_f64() else {
+                return Err(type_error(name, "数字", value));
+            };
+            if !(0.0..=1.0).contains(&number) {
+                return Err(ValidationError::new(
+                    name,
+                    format!("取值范围为 0 到 1，实际为 {}", number),
+                ));
+            }
+        }
+    }
+    if let Some(top_k) = present(body, "top_k") {
+        integer_in(top_k, "top_k", 0, i64::MAX)?;
+    }
+    Ok(())
+}
+
+// === 基础检查 ===
+
+/// 字段存在且不为 null
+fn present<'a>(map: &'a Map<String, Value>, key: &str) -> Option<&'a Value> {
+    map.get(key).filter(|value| !value.is_null())
+}
+
+fn required<'a>(map: &'a Map<String, Value>, key: &str) -> Result<&'a Value, ValidationError> {
+    present(map, key).ok_or_else(|| ValidationError::new(key, "缺少必需字段"))
+}
+
+fn required_at<'a>(
+    map: &'a Map<String, Value>,
+    key: &str,
+    parent: &str,
+) -> Result<&'a Value, ValidationError> {
+    present(map, key)
+        .ok_or_else(|| ValidationError::new(format!("{}.{}", parent, key), "缺少必需字段"))
+}
+
+fn object<'a>(value: &'a Value, path: &str) -> Result<&'a Map<String, Value>, ValidationError> {
+    value
+        .as_object()
+        .ok_or_else(|| type_error(path, "对象", value))
+}
+
+fn array<'a>(value: &'a Value, path: &str) -> Result<&'a Vec<Value>, ValidationError> {
+    value
+        .as_array()
+        .ok_or_else(|| type_error(path, "数组", value))
+}
+
+fn string<'a>(value: &'a Value, path: &str) -> Result<&'a str, ValidationError> {
+    value
+        .as_str()
+        .ok_or_else(|| type_error(path, "字符串", value))
+}
+
+fn optional_bool(map: &Map<String, Value>, key: &str) -> Result<(), ValidationError> {
+    match present(map, key) {
+        Some(value) if !value.is_boolean() => Err(type_error(key, "布尔值", value)),
+        _ => Ok(()),
+    }
+}
+
+fn integer_in(value: &Value, path: &str, min: i64, max: i64) -> Result<i64, ValidationError> {
+    let Some(number) = value.as_i64() else {
+        return Err(type_error(path, "整数", value));
+    };
+    if number < min || number > max {
+        return Err(ValidationError::new(
+            path,
+            format!("取值范围为 {} 到 {}，实际为 {}", min, max, number),
+        ));
+    }
+    Ok(number)
+}
+
+fn type_error(path: &str, expected: &str, actual: &Value) -> ValidationError {
+    let actual = match actual {
+        Value::Null => "null",
+        Value::Bool(_) => "布尔值",
+        Value::Number(_) => "数字",
+        Value::String(_) => "字符串",
+        Value::Array(_) => "数组",
+        Value::Object(_) => "对象",
+    };
+    ValidationError::new(path, format!("应为{}，实际为{}", expected, actual))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn validate(body: Value) -> Result<(), String> {
+        MessagesRequest::validate(&body).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_valid_messages_request() {
+        let body = json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "system": [{"type": "text", "text": "be brief"}],
+            "messages": [
+                {"role": "user", "content": "hi"},
+                {"role": "assistant", "content": [
+                    {"type": "text", "text": "calling"},
+                    {"type": "tool_use", "id": "t1", "name": "ls", "input": {}}
+                ]},
+                {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "t1", "content": "ok"}]},
+                {"role": "user", "content": "and then?"}
+            ],
+            "tools": [
+                {"name": "ls", "description": "", "input_schema": {"type": "object"}},
+                {"type": "web_search_20250305", "name": "web_search", "max_uses": 8}
+            ],
+            "thinking": {"type": "enabled", "budget_tokens": 2048},
+            "temperature": 0.5,
+            "stop_sequences": ["END"]
+        });
+        assert_eq!(validate(body), Ok(()));
+    }
+
+    #[test]
+    fn test_image_url_block() {
+        let body = |image_url: Value| {
+            json!({
+                "model": "claude-sonnet-4-5",
+                "max_tokens": 1024,
+                "messages": [{"role": "user", "content": [
+                    {"type": "text", "text": "看图"},
+                    {"type": "image_url", "image_url": image_url}
+                ]}]
+            })
+        };
+        assert_eq!(
+            validate(body(json!({"url": "https://example.com/a.png"}))),
+            Ok(())
+        );
+        assert_eq!(validate(body(json!("data:image/png;base64,AAAA"))), Ok(()));
+        assert_eq!(
+            validate(body(json!({"url": 1}))),
+            Err("messages.0.content.1.image_url.url: 应为字符串，实际为数字".to_string())
+        );
+        assert!(
+            validate(body(json!({})))
+                .unwrap_err()
+                .starts_with("messages.0.content.1.image_url.url: ")
+        );
+    }
+
+    #[test]
+    fn test_validation_errors_name_field() {
+        let base = || {
+            json!({
+                "model": "claude-sonnet-4-5",
+                "max_tokens": 1024,
+                "messages": [{"role": "user", "content": "hi"}],
+                "temperature": 1,
+                "thinking": {"type": "disabled"}
+            })
+        };
+        let cases = [
+            (
+                "/max_tokens",
+                json!(0),
+                "max_tokens: 取值范围为 1 到 2147483647，实际为 0",
+            ),
+            (
+                "/max_tokens",
+                json!("8"),
+                "max_tokens: 应为整数，实际为字符串",
+            ),
+            ("/messages", json!([]), "messages: 至少需要一条消息"),
+            (
+                "/messages/0/role",
+                json!("system"),
+                "messages.0.role: 必须为 \"user\" 或 \"assistant\"，实际为 \"system\"",
+            ),
+            (
+                "/messages/0/content",
+                json!([{"type": "text"}]),
+                "messages.0.content.0.text: 缺少必需字段",
+            ),
+            (
+                "/messages/0/content",
+                json!([{"type": "tool_use", "id": "t", "name": "x", "input": {}}]),
+                "messages.0.content.0.type: tool_use 只能出现在 assistant 消息中",
+            ),
+            (
+                "/temperature",
+                json!(1.5),
+                "temperature: 取值范围为 0 到 1，实际为 1.5",
+            ),
+            (
+                "/thinking",
+                json!({"type": "enabled", "budget_tokens": 100}),
+                "thinking.budget_tokens: 取值范围为 1024 到 2147483647，实际为 100",
+            ),
+        ];
+        for (pointer, value, expected) in cases {
+            let mut body = base();
+            *body.pointer_mut(pointer).unwrap() = value;
+            assert_eq!(validate(body), Err(expected.to_string()), "{}", pointer);
+        }
+
+        let mut body = base();
+        body["messages"][0]["content"] = json!([{"type": "video"}]);
+        let err = validate(body).unwrap_err();
+        assert!(err.starts_with("messages.0.content.0.type: 不支持的内容块类型 \"video\""));
+
+        let mut body = base();
+        body.as_object_mut().unwrap().remove("model");
+        assert_eq!(validate(body), Err("model: 缺少必需字段".to_string()));
+
+        let mut body = base();
+        body["messages"] = json!([{"role": "assistant", "content": "hi"}]);
+        assert_eq!(
+            validate(body),
+            Err("messages.0.role: 第一条消息必须是 \"user\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_request_limits() {
+        assert_eq!(check_message_count(3, None), Ok(()));
+        assert_eq!(check_message_count(3, Some(0)), Ok(()));
+        assert_eq!(check_message_count(3, Some(3)), Ok(()));
+        assert_eq!(
+            check_message_count(4, Some(3)).unwrap_err().to_string(),
+            "messages: 消息数 4 超过上限 3"
+        );
+
+        assert_eq!(check_input_tokens(1000, None), Ok(()));
+        assert_eq!(check_input_tokens(1000, Some(1000)), Ok(()));
+        assert_eq!(
+            check_input_tokens(1001, Some(1000))
+                .unwrap_err()
+                .to_string(),
+            "输入过长：估算 1001 tokens，超过上限 1000"
+        );
+    }
+}
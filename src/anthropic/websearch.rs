@@ -565,10 +565,12 @@ mod tests {
                 description: String::new(),
                 input_schema: Default::default(),
                 max_uses: Some(8),
+                cache_control: None,
             }]),
             tool_choice: None,
             thinking: None,
             output_config: None,
+            stop_sequences: None,
             metadata: None,
         };
 
@@ -595,6 +597,7 @@ mod tests {
                     description: String::new(),
                     input_schema: Default::default(),
                     max_uses: Some(8),
+                    cache_control: None,
                 },
                 Tool {
                     tool_type: None,
@@ -602,11 +605,13 @@ mod tests {
                     description: "Other tool".to_string(),
                     input_schema: Default::default(),
                     max_uses: None,
+                    cache_control: None,
                 },
             ]),
             tool_choice: None,
             thinking: None,
             output_config: None,
+            stop_sequences: None,
             metadata: None,
         };
 
@@ -634,6 +639,7 @@ mod tests {
             tool_choice: None,
             thinking: None,
             output_config: None,
+            stop_sequences: None,
             metadata: None,
         };
 
@@ -659,6 +665,7 @@ mod tests {
             tool_choice: None,
             thinking: None,
             output_config: None,
+            stop_sequences: None,
             metadata: None,
         };
 
@@ -0,0 +1,665 @@
+//! 下游 API Key 管理
+//!
+//! 除 `config.apiKey` 外，支持通过 Admin API 创建多个下游 API Key，
+//! 每个 Key 有独立的名称、配额（总 token 量及按日/按月的 token 与请求数）和用量统计，
+//! 持久化到凭据文件所在目录的 `kiro_api_keys.json`。
+//! 文件中只保存 Key 的 SHA-256 摘要和前缀，明文 Key 仅在创建时返回一次。
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use chrono::{DateTime, Datelike, FixedOffset, Months, NaiveDate, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::common::auth;
+use crate::common::time::ZonedTime;
+
+/// 用量数据持久化防抖间隔
+const USAGE_SAVE_DEBOUNCE: Duration = Duration::from_secs(30);
+
+/// 生成的 API Key 前缀
+const GENERATED_KEY_PREFIX: &str = "sk-kiro-";
+
+/// 列表中展示的 Key 前缀长度
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+/// 下游 API Key 用量统计
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyUsage {
+    /// 完成的请求数
+    pub requests: u64,
+    /// 输入 tokens 累计
+    pub input_tokens: u64,
+    /// 输出 tokens 累计
+    pub output_tokens: u64,
+    /// 最后一次使用时间（RFC3339）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKeyUsage {
+    /// 已使用的 tokens 总数（输入 + 输出）
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+/// 按日 / 按月配额（None 或 0 表示不限制）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyQuotas {
+    /// 每日 token 配额（输入 + 输出）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_tokens: Option<u64>,
+    /// 每日请求数配额
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_requests: Option<u64>,
+    /// 每月 token 配额（输入 + 输出）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_tokens: Option<u64>,
+    /// 每月请求数配额
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_requests: Option<u64>,
+}
+
+impl ApiKeyQuotas {
+    /// 将 0 视为不限制
+    pub fn normalized(self) -> Self {
+        let limit = |v: Option<u64>| v.filter(|v| *v > 0);
+        Self {
+            daily_tokens: limit(self.daily_tokens),
+            daily_requests: limit(self.daily_requests),
+            monthly_tokens: limit(self.monthly_tokens),
+            monthly_requests: limit(self.monthly_requests),
+        }
+    }
+}
+
+/// 单个配额周期内的用量
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodUsage {
+    /// 周期起始日期（按日为当天，按月为当月 1 日；运营者时区）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<NaiveDate>,
+    /// 周期内完成的请求数
+    pub requests: u64,
+    /// 周期内的 tokens（输入 + 输出）
+    pub tokens: u64,
+}
+
+impl PeriodUsage {
+    /// 获取指定周期的用量（记录属于更早的周期时视为 0）
+    pub fn current(&self, start: NaiveDate) -> PeriodUsage {
+        if self.start == Some(start) {
+            self.clone()
+        } else {
+            PeriodUsage {
+                start: Some(start),
+                ..Default::default()
+            }
+        }
+    }
+
+    fn add(&mut self, start: NaiveDate, tokens: u64) {
+        *self = self.current(start);
+        self.requests += 1;
+        self.tokens += tokens;
+    }
+}
+
+/// 配额周期
+#[derive(Debug, Clone, Copy)]
+enum QuotaPeriod {
+    Day,
+    Month,
+}
+
+impl QuotaPeriod {
+    /// 包含 `date` 的周期的起始日期
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            QuotaPeriod::Day => date,
+            QuotaPeriod::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    /// 下一个周期的起始时间（运营者时区零点）
+    fn next_reset(self, start: NaiveDate, offset: FixedOffset) -> Option<DateTime<Utc>> {
+        let next = match self {
+            QuotaPeriod::Day => start.succ_opt()?,
+            QuotaPeriod::Month => start.checked_add_months(Months::new(1))?,
+        };
+        next.and_hms_opt(0, 0, 0)?
+            .and_local_timezone(offset)
+            .single()
+            .map(|t| t.with_timezone(&Utc))
+    }
+}
+
+/// 配额超限信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaExceeded {
+    /// 超限的配额项：totalTokens / dailyTokens / dailyRequests / monthlyTokens / monthlyRequests
+    pub quota: &'static str,
+    /// 配额上限
+    pub limit: u64,
+    /// 当前周期已用量
+    pub used: u64,
+    /// 配额重置时间（总 token 配额不会自动重置）
+    pub reset_at: Option<ZonedTime>,
+}
+
+/// 下游 API Key 记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyRecord {
+    /// 唯一 ID
+    pub id: String,
+    /// 名称（租户标识）
+    pub name: String,
+    /// Key 的 SHA-256 摘要（十六进制）
+    pub key_hash: String,
+    /// Key 前缀（用于展示辨认）
+    pub key_prefix: String,
+    /// token 配额（输入 + 输出，None 表示不限制）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_quota: Option<u64>,
+    /// 按日 / 按月配额
+    #[serde(default)]
+    pub quotas: ApiKeyQuotas,
+    /// 用量统计
+    #[serde(default)]
+    pub usage: ApiKeyUsage,
+    /// 当日用量
+    #[serde(default)]
+    pub daily: PeriodUsage,
+    /// 当月用量
+    #[serde(default)]
+    pub monthly: PeriodUsage,
+    /// 创建时间（RFC3339）
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiKeyRecord {
+    /// 检查配额，返回第一个已超限的配额项
+    ///
+    /// 周期按运营者时区（`offset`）的自然日 / 自然月划分
+    pub fn check_quota(
+        &self,
+        now: DateTime<Utc>,
+        offset: FixedOffset,
+    ) -> Result<(), QuotaExceeded> {
+        if let Some(limit) = self.token_quota {
+            let used = self.usage.total_tokens();
+            if used >= limit {
+                return Err(QuotaExceeded {
+                    quota: "totalTokens",
+                    limit,
+                    used,
+                    reset_at: None,
+                });
+            }
+        }
+
+        let today = now.with_timezone(&offset).date_naive();
+        let periods = [
+            (
+                QuotaPeriod::Day,
+                &self.daily,
+                [
+                    ("dailyRequests", self.quotas.daily_requests, false),
+                    ("dailyTokens", self.quotas.daily_tokens, true),
+                ],
+            ),
+            (
+                QuotaPeriod::Month,
+                &self.monthly,
+                [
+                    ("monthlyRequests", self.quotas.monthly_requests, false),
+                    ("monthlyTokens", self.quotas.monthly_tokens, true),
+                ],
+            ),
+        ];
+        for (period, usage, limits) in periods {
+            let start = period.start(today);
+            let current = usage.current(start);
+            for (quota, limit, is_tokens) in limits {
+                let Some(limit) = limit else {
+                    continue;
+                };
+                let used = if is_tokens {
+                    current.tokens
+                } else {
+                    current.requests
+                };
+                if used >= limit {
+                    return Err(QuotaExceeded {
+                        quota,
+                        limit,
+                        used,
+                        reset_at: period
+                            .next_reset(start, offset)
+                            .map(|t| ZonedTime::new(t, offset)),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 获取运营者时区下当日 / 当月的用量
+    pub fn period_usage(
+        &self,
+        now: DateTime<Utc>,
+        offset: FixedOffset,
+    ) -> (PeriodUsage, PeriodUsage) {
+        let today = now.with_timezone(&offset).date_naive();
+        (
+            self.daily.current(QuotaPeriod::Day.start(today)),
+            self.monthly.current(QuotaPeriod::Month.start(today)),
+        )
+    }
+}
+
+/// 下游 API Key 认证结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiKeyAuth {
+    /// 配置文件中的主 Key（不计配额）
+    Primary,
+    /// 托管的 API Key（ID）
+    Managed(String),
+    /// 无效的 Key
+    Invalid,
+}
+
+/// 下游 API Key 存储
+pub struct ApiKeyStore {
+    keys: RwLock<Vec<ApiKeyRecord>>,
+    /// 持久化文件路径（None 表示仅内存）
+    path: Option<PathBuf>,
+    last_save_at: Mutex<Option<Instant>>,
+    dirty: AtomicBool,
+}
+
+impl ApiKeyStore {
+    /// 创建存储并从磁盘加载已有 Key（文件不存在时为空）
+    pub fn load(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let keys = match &path {
+            Some(p) if p.exists() => {
+                let content = std::fs::read_to_string(p)
+                    .with_context(|| format!("读取 API Key 文件失败: {:?}", p))?;
+                serde_json::from_str(&content)
+                    .with_context(|| format!("解析 API Key 文件失败: {:?}", p))?
+            }
+            _ => Vec::new(),
+        };
+
+        Ok(Self {
+            keys: RwLock::new(keys),
+            path,
+            last_save_at: Mutex::new(None),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// 创建仅内存的空存储
+    pub fn in_memory() -> Self {
+        Self {
+            keys: RwLock::new(Vec::new()),
+            path: None,
+            last_save_at: Mutex::new(None),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// 校验客户端提供的 API Key
+    pub fn authenticate(&self, key: &str, primary: &str) -> ApiKeyAuth {
+        if auth::constant_time_eq(key, primary) {
+            return ApiKeyAuth::Primary;
+        }
+
+        let hash = hash_key(key);
+        let keys = self.keys.read();
+        match keys.iter().find(|k| auth::constant_time_eq(&k.key_hash, &hash)) {
+            Some(record) => ApiKeyAuth::Managed(record.id.clone()),
+            None => ApiKeyAuth::Invalid,
+        }
+    }
+
+    /// 按名称查找托管 Key 的 ID（mTLS 客户端证书 CN 与 Key 名称对应）
+    pub fn find_by_name(&self, name: &str) -> Option<String> {
+        if name.is_empty() {
+            return None;
+        }
+        let keys = self.keys.read();
+        keys.iter().find(|k| k.name == name).map(|k| k.id.clone())
+    }
+
+    /// 检查托管 Key 的配额（Key 不存在时视为通过）
+    pub fn check_quota(
+        &self,
+        id: &str,
+        now: DateTime<Utc>,
+        offset: FixedOffset,
+    ) -> Result<(), QuotaExceeded> {
+        match self.keys.read().iter().find(|k| k.id == id) {
+            Some(record) => record.check_quota(now, offset),
+            None => Ok(()),
+        }
+    }
+
+    /// 列出所有托管 Key（按创建时间排序）
+    pub fn list(&self) -> Vec<ApiKeyRecord> {
+        self.keys.read().clone()
+    }
+
+    /// 创建托管 Key，返回记录和明文 Key
+    ///
+    /// 未提供 `key` 时自动生成；名称和 Key 均不可重复，且 Key 不能与主 Key 相同
+    pub fn create(
+        &self,
+        name: &str,
+        key: Option<String>,
+        token_quota: Option<u64>,
+        quotas: ApiKeyQuotas,
+        primary: &str,
+    ) -> anyhow::Result<(ApiKeyRecord, String)> {
+        let name = name.trim();
+        if name.is_empty() {
+            anyhow::bail!("名称不能为空");
+        }
+        let key = match key.map(|k| k.trim().to_string()) {
+            Some(k) if k.len() < 16 => anyhow::bail!("API Key 长度不能少于 16 个字符"),
+            Some(k) => k,
+            None => generate_key(),
+        };
+        if auth::constant_time_eq(&key, primary) {
+            anyhow::bail!("API Key 不能与主 apiKey 相同");
+        }
+
+        let record = {
+            let mut keys = self.keys.write();
+            if keys.iter().any(|k| k.name == name) {
+                anyhow::bail!("名称已存在: {}", name);
+            }
+            let hash = hash_key(&key);
+            if keys.iter().any(|k| k.key_hash == hash) {
+                anyhow::bail!("API Key 已存在");
+            }
+
+            let record = ApiKeyRecord {
+                id: uuid::Uuid::new_v4().simple().to_string()[..12].to_string(),
+                name: name.to_string(),
+                key_hash: hash,
+                key_prefix: key.chars().take(DISPLAY_PREFIX_LEN).collect(),
+                token_quota,
+                quotas,
+                usage: ApiKeyUsage::default(),
+                daily: PeriodUsage::default(),
+                monthly: PeriodUsage::default(),
+                created_at: Utc::now(),
+            };
+            keys.push(record.clone());
+            record
+        };
+
+        self.save()?;
+        tracing::info!("已创建 API Key #{}（{}）", record.id, record.name);
+        Ok((record, key))
+    }
+
+    /// 设置托管 Key 的配额（整体替换）
+    pub fn set_quotas(
+        &self,
+        id: &str,
+        token_quota: Option<u64>,
+        quotas: ApiKeyQuotas,
+    ) -> anyhow::Result<ApiKeyRecord> {
+        let record = {
+            let mut keys = self.keys.write();
+            let Some(record) = keys.iter_mut().find(|k| k.id == id) else {
+                anyhow::bail!("API Key 不存在: {}", id);
+            };
+            record.token_quota = token_quota;
+            record.quotas = quotas;
+            record.clone()
+        };
+        self.save()?;
+        Ok(record)
+    }
+
+    /// 删除托管 Key
+    pub fn delete(&self, id: &str) -> anyhow::Result<()> {
+        {
+            let mut keys = self.keys.write();
+            let before = keys.len();
+            keys.retain(|k| k.id != id);
+            if keys.len() == before {
+                anyhow::bail!("API Key 不存在: {}", id);
+            }
+        }
+        self.save()?;
+        tracing::info!("已删除 API Key #{}", id);
+        Ok(())
+    }
+
+    /// 记录一次完成的请求及其 token 用量
+    ///
+    /// 按日 / 按月用量按运营者时区（`offset`）划分周期
+    pub fn record_usage(
+        &self,
+        id: &str,
+        input_tokens: i32,
+        output_tokens: i32,
+        offset: FixedOffset,
+    ) {
+        {
+            let mut keys = self.keys.write();
+            let Some(record) = keys.iter_mut().find(|k| k.id == id) else {
+                return;
+            };
+            let now = Utc::now();
+            let today = now.with_timezone(&offset).date_naive();
+            let input_tokens = input_tokens.max(0) as u64;
+            let output_tokens = output_tokens.max(0) as u64;
+
+            record.usage.requests += 1;
+            record.usage.input_tokens += input_tokens;
+            record.usage.output_tokens += output_tokens;
+            record.usage.last_used_at = Some(now);
+            record
+                .daily
+                .add(QuotaPeriod::Day.start(today), input_tokens + output_tokens);
+            record
+                .monthly
+                .add(QuotaPeriod::Month.start(today), input_tokens + output_tokens);
+        }
+        self.save_debounced();
+    }
+
+    /// 将所有 Key 写入磁盘
+    fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(&*self.keys.read())?;
+        std::fs::write(path, json)
+            .with_context(|| format!("写入 API Key 文件失败: {:?}", path))?;
+        *self.last_save_at.lock() = Some(Instant::now());
+        self.dirty.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 标记用量已更新，并按 debounce 策略决定是否立即落盘
+    fn save_debounced(&self) {
+        self.dirty.store(true, Ordering::Relaxed);
+        let should_flush = self
+            .last_save_at
+            .lock()
+            .is_none_or(|at| at.elapsed() >= USAGE_SAVE_DEBOUNCE);
+        if should_flush && let Err(e) = self.save() {
+            tracing::warn!("保存 API Key 用量失败: {}", e);
+        }
+    }
+}
+
+impl Drop for ApiKeyStore {
+    fn drop(&mut self) {
+        if self.dirty.load(Ordering::Relaxed)
+            && let Err(e) = self.save()
+        {
+            tracing::warn!("保存 API Key 用量失败: {}", e);
+        }
+    }
+}
+
+/// 计算 Key 的 SHA-256 摘要
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// 生成新的 API Key（sk-kiro- + 32 位十六进制随机串）
+fn generate_key() -> String {
+    format!("{}{}", GENERATED_KEY_PREFIX, uuid::Uuid::new_v4().simple())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc() -> FixedOffset {
+        FixedOffset::east_opt(0).unwrap()
+    }
+
+    #[test]
+    fn test_create_authenticate_and_quota() {
+        let store = ApiKeyStore::in_memory();
+        let (record, key) = store
+            .create("team-a", None, Some(100), ApiKeyQuotas::default(), "primary-key")
+            .unwrap();
+        assert!(key.starts_with(GENERATED_KEY_PREFIX));
+        assert_eq!(record.key_prefix, &key[..DISPLAY_PREFIX_LEN]);
+
+        assert_eq!(store.authenticate("primary-key", "primary-key"), ApiKeyAuth::Primary);
+        assert_eq!(
+            store.authenticate(&key, "primary-key"),
+            ApiKeyAuth::Managed(record.id.clone())
+        );
+        assert_eq!(store.authenticate("unknown", "primary-key"), ApiKeyAuth::Invalid);
+        assert_eq!(store.find_by_name("team-a"), Some(record.id.clone()));
+        assert_eq!(store.find_by_name(""), None);
+
+        assert!(store.check_quota(&record.id, Utc::now(), utc()).is_ok());
+        store.record_usage(&record.id, 80, 20, utc());
+        let exceeded = store.check_quota(&record.id, Utc::now(), utc()).unwrap_err();
+        assert_eq!(exceeded.quota, "totalTokens");
+        assert_eq!(exceeded.used, 100);
+        assert!(exceeded.reset_at.is_none());
+        assert_eq!(store.list()[0].usage.requests, 1);
+
+        // 名称重复、Key 过短、与主 Key 相同均被拒绝
+        let quotas = ApiKeyQuotas::default;
+        assert!(store.create("team-a", None, None, quotas(), "primary-key").is_err());
+        assert!(
+            store
+                .create("team-b", Some("short".into()), None, quotas(), "primary-key")
+                .is_err()
+        );
+        let primary = "primary-key-0123456";
+        assert!(
+            store
+                .create("team-b", Some(primary.into()), None, quotas(), primary)
+                .is_err()
+        );
+
+        store.delete(&record.id).unwrap();
+        assert_eq!(store.authenticate(&key, "primary-key"), ApiKeyAuth::Invalid);
+        assert!(store.delete(&record.id).is_err());
+    }
+
+    #[test]
+    fn test_period_quotas_reset_at_operator_midnight() {
+        let east8 = FixedOffset::east_opt(8 * 3600).unwrap();
+        let mut record = ApiKeyRecord {
+            id: "k1".to_string(),
+            name: "team-a".to_string(),
+            key_hash: String::new(),
+            key_prefix: String::new(),
+            token_quota: None,
+            quotas: ApiKeyQuotas {
+                daily_requests: Some(2),
+                monthly_tokens: Some(1000),
+                ..Default::default()
+            },
+            usage: ApiKeyUsage::default(),
+            daily: PeriodUsage::default(),
+            monthly: PeriodUsage::default(),
+            created_at: DateTime::default(),
+        };
+
+        // 2026-01-31T20:00Z 为 +08:00 的 2026-02-01 04:00
+        let now = "2026-01-31T20:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let today = NaiveDate::from_ymd_opt(2026, 2, 1).unwrap();
+        record.daily.add(today, 10);
+        record.daily.add(today, 10);
+        record.monthly.add(today, 20);
+
+        let exceeded = record.check_quota(now, east8).unwrap_err();
+        assert_eq!(exceeded.quota, "dailyRequests");
+        assert_eq!(exceeded.limit, 2);
+        let reset = exceeded.reset_at.unwrap();
+        assert_eq!(reset.utc, "2026-02-01T16:00:00Z");
+        assert_eq!(reset.local, "2026-02-02T00:00:00+08:00");
+
+        // 次日按日配额重置，按月用量保留
+        let tomorrow = now + chrono::Duration::days(1);
+        assert!(record.check_quota(tomorrow, east8).is_ok());
+        record.monthly.tokens = 1000;
+        let exceeded = record.check_quota(tomorrow, east8).unwrap_err();
+        assert_eq!(exceeded.quota, "monthlyTokens");
+        assert_eq!(exceeded.reset_at.unwrap().local, "2026-03-01T00:00:00+08:00");
+
+        // 记录属于上一周期时视为 0
+        let (daily, monthly) = record.period_usage(tomorrow, east8);
+        assert_eq!(daily.requests, 0);
+        assert_eq!(monthly.tokens, 1000);
+    }
+
+    #[test]
+    fn test_persists_hash_only_and_reloads() {
+        let path = std::env::temp_dir()
+            .join(format!("kiro-api-keys-{}.json", uuid::Uuid::new_v4()));
+        let store = ApiKeyStore::load(Some(path.clone())).unwrap();
+        let (record, key) = store
+            .create(
+                "team-a",
+                Some("sk-custom-0123456789".into()),
+                None,
+                ApiKeyQuotas::default(),
+                "primary",
+            )
+            .unwrap();
+        store.record_usage(&record.id, 10, 5, utc());
+        drop(store);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains(&key));
+
+        let reloaded = ApiKeyStore::load(Some(path.clone())).unwrap();
+        assert_eq!(
+            reloaded.authenticate(&key, "primary"),
+            ApiKeyAuth::Managed(record.id)
+        );
+        assert_eq!(reloaded.list()[0].usage.total_tokens(), 15);
+        assert_eq!(reloaded.list()[0].daily.tokens, 15);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
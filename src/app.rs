@@ -0,0 +1,477 @@
+//! 应用组装
+//!
+//! 根据 `Config` 和预先构建的 `MultiTokenManager` 组装完整的 axum `Router`
+//! （Anthropic API、Admin API 与 Admin UI），并负责启动后台任务。
+//! 独立运行时由 `main` 使用；嵌入其他 Rust 应用时可将路由挂载到任意路径下：
+//!
+//! ```ignore
+//! let app = kiro_rs::KiroApp::new(config, token_manager)?;
+//! app.spawn_background_workers();
+//! let shutdown = app.shutdown_handle();
+//! let router = axum::Router::new().nest("/kiro", app.into_router());
+//! // 服务停止后
+//! shutdown.shutdown();
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use axum::{Router, middleware};
+
+use crate::admin::{self, AdminService, AdminState};
+use crate::admin_ui;
+use crate::anthropic::{self, archive::ResponseArchive, cache::ResponseCache};
+use crate::api_keys::ApiKeyStore;
+use crate::audit::AuditLog;
+use crate::cloud_pass::{self, client::CloudPassClient, state::CloudPassState};
+use crate::common::ip_filter::{IpFilter, IpFilterState, ip_filter_middleware};
+use crate::common::lockout::AuthLockout;
+use crate::common::request_id;
+use crate::kiro::provider::KiroProvider;
+use crate::kiro::token_manager::MultiTokenManager;
+use crate::kiro::{
+    balance_refresh, credential_discovery, exerciser, health_check, peer_sync, priority_preset,
+    shared_state,
+};
+use crate::leader::{self, LeaderElection};
+use crate::model::config::Config;
+use crate::notify;
+use crate::supervisor::{RestartPolicy, WorkerSupervisor};
+use crate::token;
+
+/// 组装好的 kiro-rs 应用
+pub struct KiroApp {
+    config: Config,
+    token_manager: Arc<MultiTokenManager>,
+    api_key: String,
+    profile_arn: Option<String>,
+    api_key_store: Arc<ApiKeyStore>,
+    archive: Option<ResponseArchive>,
+    audit_log: Option<Arc<AuditLog>>,
+    response_cache: Option<Arc<ResponseCache>>,
+    api_ip_filter: Option<Arc<IpFilter>>,
+    admin_ip_filter: Option<Arc<IpFilter>>,
+    auth_lockout: Option<Arc<AuthLockout>>,
+    cloud_pass_state: CloudPassState,
+    leader_election: Option<Arc<LeaderElection>>,
+    supervisor: Arc<WorkerSupervisor>,
+}
+
+impl KiroApp {
+    /// 创建应用
+    ///
+    /// 加载下游托管 API Key、初始化 count_tokens 配置、响应归档、审计日志、响应缓存与 IP 访问控制；
+    /// 配置中未设置 apiKey 或初始化失败时返回错误。
+    /// count_tokens 配置为进程级全局状态，同一进程内再次创建应用时须使用相同的配置，否则返回错误
+    pub fn new(config: Config, token_manager: Arc<MultiTokenManager>) -> anyhow::Result<Self> {
+        let api_key = config.api_key.clone().context("配置文件中未设置 apiKey")?;
+
+        // 加载下游托管 API Key（与统计缓存同目录）
+        let api_key_store = ApiKeyStore::load(
+            token_manager
+                .cache_dir()
+                .map(|d| d.join("kiro_api_keys.json")),
+        )
+        .context("加载 API Key 失败")?;
+
+        // 初始化 count_tokens 配置
+        token::init_config(token::CountTokensConfig {
+            api_url: config.count_tokens_api_url.clone(),
+            api_key: config.count_tokens_api_key.clone(),
+            auth_type: config.count_tokens_auth_type.clone(),
+            tokenizer: config.tokenizer,
+            proxy: config.proxy_config(),
+            tls_backend: config.tls_backend,
+        })
+        .context("初始化 count_tokens 配置失败")?;
+
+        // 创建 Cloud Pass 共享状态
+        // 未配置时也创建状态，便于运行时通过 Admin API 启用
+        let cloud_pass_state = match config.active_cloud_pass() {
+            // 先创建临时 client 获取各激活码的 device_id
+            Some(cp_config) => {
+                CloudPassState::from_config(cp_config, &CloudPassClient::for_config(cp_config))
+            }
+            None => CloudPassState::disabled(),
+        };
+
+        // 创建响应归档器（如果配置了）
+        let archive = match config.archive.clone() {
+            Some(archive_config) => {
+                let dir = archive_config.dir.clone();
+                let archive = ResponseArchive::new(archive_config).context("初始化响应归档失败")?;
+                tracing::info!("响应归档已启用: {}", dir);
+                Some(archive)
+            }
+            None => None,
+        };
+
+        // 创建请求审计日志（如果配置了）
+        let audit_log = match &config.audit {
+            Some(audit_config) => {
+                let audit_log = AuditLog::new(audit_config).context("初始化审计日志失败")?;
+                tracing::info!("请求审计日志已启用: {}", audit_config.dir);
+                Some(Arc::new(audit_log))
+            }
+            None => None,
+        };
+
+        // 创建响应缓存（如果配置了）
+        let response_cache = config.response_cache.as_ref().map(|cache_config| {
+            tracing::info!(
+                "响应缓存已启用: 有效期 {}s，最多 {} 条",
+                cache_config.ttl_secs,
+                cache_config.max_entries
+            );
+            Arc::new(ResponseCache::new(cache_config))
+        });
+
+        // 解析客户端 IP 访问控制（如果配置了）
+        let api_ip_filter = config
+            .api_ip_filter
+            .as_ref()
+            .map(|c| IpFilter::new(c).map(Arc::new))
+            .transpose()
+            .context("解析 apiIpFilter 失败")?;
+        let admin_ip_filter = config
+            .admin_ip_filter
+            .as_ref()
+            .map(|c| IpFilter::new(c).map(Arc::new))
+            .transpose()
+            .context("解析 adminIpFilter 失败")?;
+
+        // 认证失败锁定（如果配置了）
+        let auth_lockout = config.auth_lockout.as_ref().map(|lockout_config| {
+            tracing::info!(
+                "认证失败锁定已启用: {}s 内失败 {} 次锁定 {}s",
+                lockout_config.window_secs,
+                lockout_config.max_failures,
+                lockout_config.lockout_secs
+            );
+            Arc::new(AuthLockout::new(lockout_config))
+        });
+
+        // 主备模式选主（如果配置了）
+        let leader_election = config
+            .leader_election
+            .as_ref()
+            .map(|c| Arc::new(LeaderElection::new(c)));
+
+        // 后台任务监督器：任务 panic 或意外退出时按退避策略自动重启
+        let supervisor = Arc::new(WorkerSupervisor::new(RestartPolicy::from_config(&config)));
+
+        Ok(Self {
+            config,
+            token_manager,
+            api_key,
+            profile_arn: None,
+            api_key_store: Arc::new(api_key_store),
+            archive,
+            audit_log,
+            response_cache,
+            api_ip_filter,
+            admin_ip_filter,
+            auth_lockout,
+            cloud_pass_state,
+            leader_election,
+            supervisor,
+        })
+    }
+
+    /// 设置请求使用的 Profile ARN（通常取自第一个凭据）
+    pub fn with_profile_arn(mut self, arn: impl Into<String>) -> Self {
+        self.profile_arn = Some(arn.into());
+        self
+    }
+
+    /// 获取关闭句柄（`into_router` 之后仍可用于停止后台任务与落盘统计）
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            token_manager: self.token_manager.clone(),
+            leader_election: self.leader_election.clone(),
+            supervisor: self.supervisor.clone(),
+        }
+    }
+
+    /// 是否启用了 Admin API（配置了非空的 adminApiKey 或 adminKeys）
+    pub fn admin_enabled(&self) -> bool {
+        // 安全检查：空字符串被视为未配置，防止空 key 绕过认证
+        self.config.admin_enabled()
+    }
+
+    /// 在监督下启动后台任务（Cloud Pass 刷新、通知投递、自动恢复、保活探测、Token 主动刷新、
+    /// 健康检查、优先级预设切换），
+    /// 并执行一次凭据自动发现
+    pub fn spawn_background_workers(&self) {
+        let config = &self.config;
+        let supervisor = &self.supervisor;
+
+        // 启动主备选主任务（如果配置了）
+        if let Some(election) = &self.leader_election {
+            let election = election.clone();
+            supervisor.spawn("leader-election", move || election.clone().run());
+        }
+
+        // 启动 Cloud Pass 后台任务（始终运行，未配置或暂停时空转，便于运行时启用；启用选主时仅主节点运行）
+        if config.active_cloud_pass().is_some() {
+            tracing::info!("Cloud Pass 已配置，启动后台凭证刷新任务");
+        }
+        let tm = self.token_manager.clone();
+        let cp_state = self.cloud_pass_state.clone();
+        let election = self.leader_election.clone();
+        supervisor.spawn("cloud-pass", move || {
+            let (tm, cp_state) = (tm.clone(), cp_state.clone());
+            leader::run_as_leader(election.clone(), "cloud-pass", move || {
+                cloud_pass::worker::start_cloud_pass_worker(tm.clone(), cp_state.clone())
+            })
+        });
+
+        // 自动导入 Kiro IDE 的凭据（如果配置了）
+        if let Some(discovery) = &config.credential_discovery {
+            if self.token_manager.is_multiple_format() {
+                let tm = self.token_manager.clone();
+                let paths = credential_discovery::resolve_paths(Some(discovery));
+                tokio::spawn(async move {
+                    let report = credential_discovery::discover_credentials(&tm, &paths).await;
+                    tracing::info!(
+                        "凭据自动发现完成：导入 {}，已存在 {}，失败 {}",
+                        report.imported,
+                        report.duplicates,
+                        report.failed
+                    );
+                    for result in report.results.iter().filter(|r| r.message.is_some()) {
+                        tracing::warn!(
+                            "导入 {} 失败: {}",
+                            result.path,
+                            result.message.as_deref().unwrap_or_default()
+                        );
+                    }
+                });
+            } else {
+                tracing::warn!("凭证文件为单凭据格式，已跳过凭据自动发现");
+            }
+        }
+
+        // 启动通知投递任务（Webhook 配置支持热重载，始终运行）
+        {
+            let tm = self.token_manager.clone();
+            supervisor.spawn("notifier", move || {
+                notify::start_notification_worker(tm.clone())
+            });
+        }
+
+        // 启动禁用凭据自动恢复后台任务（冷却恢复与额度重置恢复）
+        {
+            let tm = self.token_manager.clone();
+            supervisor.spawn("recovery", move || {
+                health_check::start_recovery_worker(tm.clone())
+            });
+        }
+
+        // 启动闲置凭据夜间保活探测任务（如果配置了）
+        if let Some(hour) = config.exercise_hour_utc {
+            let tm = self.token_manager.clone();
+            supervisor.spawn("exerciser", move || {
+                let provider = KiroProvider::new(tm.clone());
+                exerciser::start_exerciser_worker(tm.clone(), provider, hour)
+            });
+        }
+
+        // 启动 Token 主动刷新任务（如果配置了；启用选主时仅主节点运行）
+        if config.proactive_refresh_margin_secs > 0 {
+            let tm = self.token_manager.clone();
+            let election = self.leader_election.clone();
+            supervisor.spawn("token-refresh", move || {
+                let tm = tm.clone();
+                leader::run_as_leader(election.clone(), "token-refresh", move || {
+                    let tm = tm.clone();
+                    async move { tm.run_refresh_scheduler().await }
+                })
+            });
+        }
+
+        // 启动凭据健康检查后台任务（如果配置了）
+        if config.health_check_interval > 0 {
+            let tm = self.token_manager.clone();
+            let interval = config.health_check_interval;
+            supervisor.spawn("health-check", move || {
+                health_check::start_health_check_worker(tm.clone(), interval)
+            });
+        }
+
+        // 启动定时余额刷新任务（如果配置了）
+        if config.balance_refresh_interval_secs > 0 {
+            let tm = self.token_manager.clone();
+            let interval = config.balance_refresh_interval_secs;
+            supervisor.spawn("balance-refresh", move || {
+                balance_refresh::start_balance_refresh_worker(tm.clone(), interval)
+            });
+        }
+
+        // 启动多实例凭据同步任务（如果配置了）
+        if config.peer_sync.is_some() {
+            let tm = self.token_manager.clone();
+            supervisor.spawn("peer-sync", move || {
+                peer_sync::start_peer_sync_worker(tm.clone())
+            });
+        }
+
+        // 启动多副本共享状态同步任务（如果配置了）
+        if config.shared_state.is_some() {
+            let tm = self.token_manager.clone();
+            supervisor.spawn("shared-state", move || {
+                shared_state::start_shared_state_worker(tm.clone())
+            });
+        }
+
+        // 启动优先级预设定时切换任务（如果有预设配置了 schedule）
+        if config.has_scheduled_presets() {
+            let tm = self.token_manager.clone();
+            supervisor.spawn("priority-presets", move || {
+                priority_preset::start_priority_preset_worker(tm.clone())
+            });
+        }
+    }
+
+    /// 组装完整路由
+    ///
+    /// Anthropic API 位于根路径，启用 Admin API 时同时挂载 `/api/admin` 与 `/admin`。
+    /// 所有请求分配请求 ID（`X-Request-Id`）。
+    /// 返回的 `Router` 即 tower `Service`，可直接 `axum::serve` 或 `nest` 到现有路由下
+    pub fn into_router(self) -> Router {
+        self.build_router()
+            .layer(middleware::from_fn(request_id::request_id_middleware))
+    }
+
+    fn build_router(self) -> Router {
+        let admin_enabled = self.admin_enabled();
+        let anthropic_app = anthropic::create_router_with_provider(
+            &self.api_key,
+            Some(KiroProvider::new(self.token_manager.clone())),
+            self.profile_arn,
+            self.archive,
+            self.api_key_store.clone(),
+            self.audit_log.clone(),
+            self.response_cache.clone(),
+            self.auth_lockout.clone(),
+        );
+        let anthropic_app =
+            with_ip_filter(anthropic_app, self.api_ip_filter, "api", &self.audit_log);
+
+        if !admin_enabled {
+            if self.config.admin_api_key.is_some() {
+                tracing::warn!("admin_api_key 配置为空，Admin API 未启用");
+            }
+            return anthropic_app;
+        }
+        let admin_key = self.config.admin_api_key.clone().unwrap_or_default();
+
+        let mut admin_service = AdminService::new(self.token_manager.clone(), self.api_key_store);
+        if let Some(cache) = self.response_cache {
+            admin_service = admin_service.with_response_cache(cache);
+        }
+        let mut admin_state = AdminState::new(admin_key, admin_service)
+            .with_session_ttl(self.config.admin_session_ttl_secs)
+            .with_supervisor(self.supervisor);
+        if let Some(election) = self.leader_election {
+            admin_state = admin_state.with_leader_election(election);
+        }
+        admin_state = admin_state.with_cloud_pass(self.cloud_pass_state);
+        if let Some(audit_log) = self.audit_log.clone() {
+            admin_state = admin_state.with_audit_log(audit_log);
+        }
+        if let Some(lockout) = self.auth_lockout {
+            admin_state = admin_state.with_auth_lockout(lockout);
+        }
+
+        tracing::info!("Admin API 已启用");
+        tracing::info!("Admin UI 已启用: /admin");
+        let admin_app = Router::new()
+            .nest("/api/admin", admin::create_admin_router(admin_state))
+            .nest("/admin", admin_ui::create_admin_ui_router());
+        let admin_app = with_ip_filter(admin_app, self.admin_ip_filter, "admin", &self.audit_log);
+        anthropic_app.merge(admin_app)
+    }
+}
+
+/// 为路由加上 IP 访问控制（未配置时原样返回）
+fn with_ip_filter(
+    router: Router,
+    filter: Option<Arc<IpFilter>>,
+    scope: &'static str,
+    audit: &Option<Arc<AuditLog>>,
+) -> Router {
+    let Some(filter) = filter else {
+        return router;
+    };
+    let state = IpFilterState {
+        filter,
+        scope,
+        audit: audit.clone(),
+    };
+    router.layer(middleware::from_fn_with_state(state, ip_filter_middleware))
+}
+
+/// 应用关闭句柄
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    token_manager: Arc<MultiTokenManager>,
+    leader_election: Option<Arc<LeaderElection>>,
+    supervisor: Arc<WorkerSupervisor>,
+}
+
+impl ShutdownHandle {
+    /// 等待进行中请求完成的最长时间（取自当前配置，支持热重载）
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.token_manager.config().shutdown_timeout_secs)
+    }
+
+    /// 停止后台任务（含 Cloud Pass 刷新）并把尚未落盘的统计数据写入磁盘
+    ///
+    /// 余额缓存在每次更新时已同步写入，无需额外落盘；作为主节点运行时同时释放选主租约
+    pub fn shutdown(&self) {
+        self.supervisor.shutdown();
+        if let Some(election) = &self.leader_election {
+            election.release();
+        }
+        self.token_manager.flush_stats();
+        tracing::info!("后台任务已停止，统计数据已落盘");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_router_can_be_nested_under_prefix() {
+        let dir = std::env::temp_dir().join(format!("kiro-app-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config.json"), r#"{"apiKey":"sk-embedded"}"#).unwrap();
+        let config = Config::load(dir.join("config.json")).unwrap();
+        let token_manager =
+            MultiTokenManager::new(config.clone(), Vec::new(), None, None, false).unwrap();
+
+        let app = KiroApp::new(config, Arc::new(token_manager)).unwrap();
+        let router = Router::new().nest("/kiro", app.into_router());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let url = format!("http://{}/kiro/v1/models", addr);
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+        let response = client
+            .get(&url)
+            .header("x-api-key", "sk-embedded")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
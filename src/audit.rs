@@ -0,0 +1,468 @@
+//! 请求审计日志（JSONL）
+//!
+//! 记录每个经代理转发的对话请求（时间、下游 Key、凭据、模型、token 数、耗时、状态码），
+//! 写入 `kiro-audit.jsonl`，超过大小上限后轮转为 `kiro-audit.1.jsonl`、`kiro-audit.2.jsonl`…，
+//! 只保留最近的若干个文件。Admin API `GET /api/admin/logs` 从这些文件中分页查询。
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::kiro::model::credentials::CredentialId;
+use crate::model::config::AuditConfig;
+
+/// 当前审计文件名
+const AUDIT_FILE_NAME: &str = "kiro-audit.jsonl";
+
+/// 单页最大记录数
+const MAX_PAGE_SIZE: usize = 500;
+
+/// 审计记录
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    /// 请求开始时间（RFC3339）
+    pub timestamp: DateTime<Utc>,
+    /// 请求端点
+    pub endpoint: String,
+    /// 下游 API Key（托管 Key 的 ID，配置文件中的主 Key 记为 "primary"，认证前被拒绝的请求记为 "anonymous"）
+    pub api_key: String,
+    /// 客户端 IP（经 Unix socket 接入时为空）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<IpAddr>,
+    /// 处理请求的凭据 ID（未到达上游时为空）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<CredentialId>,
+    /// 请求模型
+    pub model: String,
+    /// 是否流式请求
+    pub stream: bool,
+    /// 输入 tokens
+    pub input_tokens: i32,
+    /// 输出 tokens
+    pub output_tokens: i32,
+    /// 发往上游的请求体大小（字节，未到达转换阶段时为空）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_bytes: Option<u64>,
+    /// 总耗时（毫秒，流式请求截至响应体发送完毕）
+    pub latency_ms: u64,
+    /// 上游尝试次数（含最终成功的一次，请求失败或未到达上游时为空）
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub attempts: u32,
+    /// 本次请求切换凭据的次数（故障转移）
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub failovers: u32,
+    /// 返回给客户端的 HTTP 状态码
+    pub status: u16,
+    /// 安全事件类型（如 `auth_lockout`，普通请求为空）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+}
+
+/// 审计日志查询条件
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditQuery {
+    /// 页码（从 1 开始，默认 1）
+    #[serde(default)]
+    pub page: Option<usize>,
+    /// 每页记录数（默认 50，最大 500）
+    #[serde(default)]
+    pub page_size: Option<usize>,
+    /// 下游 API Key（托管 Key ID 或 "primary"）
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// 凭据 ID
+    #[serde(default)]
+    pub credential_id: Option<CredentialId>,
+    /// 模型（包含匹配）
+    #[serde(default)]
+    pub model: Option<String>,
+    /// HTTP 状态码
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// 安全事件类型
+    #[serde(default)]
+    pub event: Option<String>,
+    /// 起始时间（RFC3339，包含）
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// 截止时间（RFC3339，不包含）
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl AuditQuery {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        if self.api_key.as_ref().is_some_and(|k| *k != record.api_key)
+            || self.credential_id.is_some_and(|id| record.credential_id != Some(id))
+            || self.status.is_some_and(|s| s != record.status)
+            || self.event.as_ref().is_some_and(|e| record.event.as_ref() != Some(e))
+            || self
+                .model
+                .as_ref()
+                .is_some_and(|m| !record.model.contains(m.as_str()))
+        {
+            return false;
+        }
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+        let timestamp = record.timestamp;
+        self.from.is_none_or(|from| timestamp >= from) && self.to.is_none_or(|to| timestamp < to)
+    }
+}
+
+/// 审计日志分页查询结果（最新的在前）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditPage {
+    /// 符合条件的记录总数
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+    pub records: Vec<AuditRecord>,
+}
+
+/// 审计日志
+pub struct AuditLog {
+    dir: PathBuf,
+    max_files: usize,
+    writer: Mutex<AuditWriter>,
+}
+
+impl AuditLog {
+    /// 创建审计日志（目录不存在时自动创建）
+    pub fn new(config: &AuditConfig) -> anyhow::Result<Self> {
+        let dir = PathBuf::from(&config.dir);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("创建审计日志目录失败: {}", dir.display()))?;
+
+        Ok(Self {
+            writer: Mutex::new(AuditWriter::new(
+                dir.clone(),
+                config.max_file_bytes,
+                config.max_files.max(1),
+            )),
+            dir,
+            max_files: config.max_files.max(1),
+        })
+    }
+
+    /// 开始记录一次请求，返回的跟踪器在最后一个引用释放时写出记录
+    pub fn begin(self: &Arc<Self>, endpoint: &str, api_key: Option<&str>) -> Arc<AuditTracker> {
+        Arc::new(AuditTracker {
+            log: self.clone(),
+            started: Instant::now(),
+            record: Mutex::new(AuditRecord {
+                timestamp: Utc::now(),
+                endpoint: endpoint.to_string(),
+                api_key: api_key.unwrap_or("primary").to_string(),
+                ..Default::default()
+            }),
+        })
+    }
+
+    /// 记录一次在认证之前被拒绝的请求（如 IP 访问控制）
+    pub fn record_rejection(&self, endpoint: &str, client_ip: Option<IpAddr>, status: u16) {
+        self.write(&AuditRecord {
+            timestamp: Utc::now(),
+            endpoint: endpoint.to_string(),
+            api_key: "anonymous".to_string(),
+            client_ip,
+            status,
+            ..Default::default()
+        });
+    }
+
+    /// 记录一条安全事件（如认证失败次数过多导致 IP 被锁定）
+    pub fn record_security_event(
+        &self,
+        event: &str,
+        endpoint: &str,
+        client_ip: Option<IpAddr>,
+        status: u16,
+    ) {
+        self.write(&AuditRecord {
+            timestamp: Utc::now(),
+            endpoint: endpoint.to_string(),
+            api_key: "anonymous".to_string(),
+            client_ip,
+            status,
+            event: Some(event.to_string()),
+            ..Default::default()
+        });
+    }
+
+    fn write(&self, record: &AuditRecord) {
+        let line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!("序列化审计记录失败: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = self.writer.lock().write_line(&line) {
+            tracing::warn!("写入审计记录失败: {}", e);
+        }
+    }
+
+    /// 按条件分页查询（从最新的记录开始扫描所有保留的文件）
+    pub fn query(&self, query: &AuditQuery) -> AuditPage {
+        let page = query.page.unwrap_or(1).max(1);
+        let page_size = query.page_size.unwrap_or(50).clamp(1, MAX_PAGE_SIZE);
+        let skip = (page - 1) * page_size;
+
+        let mut total = 0;
+        let mut records = Vec::new();
+        for index in 0..self.max_files {
+            let Ok(file) = File::open(self.dir.join(audit_file_name(index))) else {
+                continue;
+            };
+            let mut lines: Vec<String> =
+                BufReader::new(file).lines().map_while(Result::ok).collect();
+            lines.reverse();
+
+            for line in lines {
+                let Ok(record) = serde_json::from_str::<AuditRecord>(&line) else {
+                    continue;
+                };
+                if !query.matches(&record) {
+                    continue;
+                }
+                if total >= skip && records.len() < page_size {
+                    records.push(record);
+                }
+                total += 1;
+            }
+        }
+
+        AuditPage {
+            total,
+            page,
+            page_size,
+            records,
+        }
+    }
+}
+
+/// 单次请求的审计跟踪器
+///
+/// 由审计中间件创建并写入请求扩展，处理器和响应体共享同一个引用，
+/// 全部释放（响应体发送完毕或客户端断开）时写出记录
+pub struct AuditTracker {
+    log: Arc<AuditLog>,
+    started: Instant,
+    record: Mutex<AuditRecord>,
+}
+
+impl AuditTracker {
+    pub fn set_model(&self, model: &str, stream: bool) {
+        let mut record = self.record.lock();
+        record.model = model.to_string();
+        record.stream = stream;
+    }
+
+    pub fn set_credential(&self, id: CredentialId) {
+        self.record.lock().credential_id = Some(id);
+    }
+
+    pub fn set_tokens(&self, input_tokens: i32, output_tokens: i32) {
+        let mut record = self.record.lock();
+        record.input_tokens = input_tokens;
+        record.output_tokens = output_tokens;
+    }
+
+    pub fn set_request_bytes(&self, bytes: usize) {
+        self.record.lock().request_bytes = Some(bytes as u64);
+    }
+
+    pub fn set_attempts(&self, attempts: u32, failovers: u32) {
+        let mut record = self.record.lock();
+        record.attempts = attempts;
+        record.failovers = failovers;
+    }
+
+    pub fn set_client_ip(&self, ip: Option<IpAddr>) {
+        self.record.lock().client_ip = ip;
+    }
+
+    pub fn set_status(&self, status: u16) {
+        self.record.lock().status = status;
+    }
+}
+
+impl Drop for AuditTracker {
+    fn drop(&mut self) {
+        let record = self.record.get_mut();
+        record.latency_ms = self.started.elapsed().as_millis() as u64;
+        self.log.write(record);
+    }
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+/// 第 `index` 个审计文件名（0 为当前文件）
+fn audit_file_name(index: usize) -> String {
+    if index == 0 {
+        AUDIT_FILE_NAME.to_string()
+    } else {
+        format!("kiro-audit.{}.jsonl", index)
+    }
+}
+
+/// 按大小轮转的 JSONL 写入器
+struct AuditWriter {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    max_files: usize,
+    file: Option<File>,
+    written: u64,
+}
+
+impl AuditWriter {
+    fn new(dir: PathBuf, max_file_bytes: u64, max_files: usize) -> Self {
+        Self {
+            dir,
+            max_file_bytes,
+            max_files,
+            file: None,
+            written: 0,
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> anyhow::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.file.is_none() {
+            self.open()?;
+        }
+        if self.written > 0 && self.written + len > self.max_file_bytes {
+            self.rotate()?;
+        }
+
+        let file = self.file.as_mut().expect("审计文件已打开");
+        writeln!(file, "{}", line)?;
+        self.written += len;
+        Ok(())
+    }
+
+    /// 打开（追加）当前文件
+    fn open(&mut self) -> anyhow::Result<()> {
+        let path = self.dir.join(AUDIT_FILE_NAME);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("打开审计文件失败: {}", path.display()))?;
+        self.written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// 依次后移历史文件（超出保留数量的删除），再新建当前文件
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        self.file = None;
+        let oldest = self.dir.join(audit_file_name(self.max_files - 1));
+        if oldest.exists() {
+            fs::remove_file(&oldest)
+                .with_context(|| format!("删除审计文件失败: {}", oldest.display()))?;
+        }
+        for index in (0..self.max_files - 1).rev() {
+            let from = self.dir.join(audit_file_name(index));
+            if from.exists() {
+                fs::rename(&from, self.dir.join(audit_file_name(index + 1)))
+                    .with_context(|| format!("轮转审计文件失败: {}", from.display()))?;
+            }
+        }
+        self.open()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config(max_file_bytes: u64, max_files: usize) -> AuditConfig {
+        AuditConfig {
+            dir: std::env::temp_dir()
+                .join(format!("kiro-audit-{}", uuid::Uuid::new_v4()))
+                .to_string_lossy()
+                .into_owned(),
+            max_file_bytes,
+            max_files,
+        }
+    }
+
+    fn record_request(log: &Arc<AuditLog>, api_key: Option<&str>, model: &str, status: u16) {
+        let tracker = log.begin("/v1/messages", api_key);
+        tracker.set_model(model, true);
+        tracker.set_credential(CredentialId(1));
+        tracker.set_tokens(10, 20);
+        tracker.set_status(status);
+    }
+
+    #[test]
+    fn test_records_and_queries_newest_first_with_filters() {
+        let config = temp_config(1024 * 1024, 3);
+        let log = Arc::new(AuditLog::new(&config).unwrap());
+        record_request(&log, None, "claude-sonnet-4", 200);
+        record_request(&log, Some("k1"), "claude-opus-4", 429);
+        record_request(&log, Some("k1"), "claude-sonnet-4", 200);
+
+        let page = log.query(&AuditQuery::default());
+        assert_eq!(page.total, 3);
+        assert_eq!(page.records[0].api_key, "k1");
+        assert_eq!(page.records[0].model, "claude-sonnet-4");
+        assert_eq!(page.records[2].api_key, "primary");
+        assert_eq!(page.records[2].input_tokens, 10);
+
+        let filtered = log.query(&AuditQuery {
+            api_key: Some("k1".to_string()),
+            model: Some("sonnet".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(filtered.total, 1);
+
+        let second_page = log.query(&AuditQuery {
+            page: Some(2),
+            page_size: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(second_page.total, 3);
+        assert_eq!(second_page.records.len(), 1);
+        assert_eq!(second_page.records[0].status, 200);
+        assert_eq!(second_page.records[0].api_key, "primary");
+
+        fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotation_keeps_max_files() {
+        let config = temp_config(200, 2);
+        let log = Arc::new(AuditLog::new(&config).unwrap());
+        for _ in 0..6 {
+            record_request(&log, None, "claude-sonnet-4", 200);
+        }
+
+        let dir = PathBuf::from(&config.dir);
+        assert!(dir.join(audit_file_name(0)).exists());
+        assert!(dir.join(audit_file_name(1)).exists());
+        assert!(!dir.join(audit_file_name(2)).exists());
+
+        // 只能查询到保留文件中的记录
+        let page = log.query(&AuditQuery::default());
+        assert!(page.total > 0 && page.total < 6);
+
+        fs::remove_dir_all(&config.dir).unwrap();
+    }
+}
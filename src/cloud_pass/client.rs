@@ -12,7 +12,8 @@ use rsa::pkcs8::DecodePublicKey;
 use rsa::traits::PublicKeyParts;
 use rsa::{BigUint, RsaPublicKey};
 
-use crate::model::config::CloudPassConfig;
+use crate::kiro::token_manager::sha256_hex;
+use crate::model::config::{CloudPassConfig, CloudPassLicense};
 
 use super::model::{
     CloudPassRawResponse, CloudPassResponse, GetCredentialsRequest, HeartbeatRequest,
@@ -31,6 +32,7 @@ GQIDAQAB
 -----END PUBLIC KEY-----";
 
 /// Cloud Pass API 客户端
+#[derive(Clone)]
 pub struct CloudPassClient {
     http_client: reqwest::Client,
     server_url: String,
@@ -41,12 +43,19 @@ pub struct CloudPassClient {
 }
 
 impl CloudPassClient {
-    /// 创建客户端实例
-    pub fn new(config: &CloudPassConfig) -> Self {
-        let device_id = config
-            .device_id
-            .clone()
-            .unwrap_or_else(|| Self::read_or_generate_device_id());
+    /// 为指定激活码创建客户端实例
+    ///
+    /// 未配置设备 ID 时，顶层 `licenseCode` 使用本机设备 ID，
+    /// 其他激活码使用由本机设备 ID 与激活码派生的设备 ID（各激活码互不冲突且重启后保持不变）
+    pub fn new(config: &CloudPassConfig, license: &CloudPassLicense) -> Self {
+        let device_id = license.device_id.clone().unwrap_or_else(|| {
+            let base = Self::read_or_generate_device_id();
+            if license.license_code == config.license_code {
+                base
+            } else {
+                derive_device_id(&base, &license.license_code)
+            }
+        });
 
         let http_client = reqwest::Client::builder()
             .timeout(Duration::from_secs(30))
@@ -59,18 +68,32 @@ impl CloudPassClient {
         Self {
             http_client,
             server_url: config.server_url.clone(),
-            license_code: config.license_code.clone(),
+            license_code: license.license_code.clone(),
             device_id,
             client_version: config.client_version.clone(),
             rsa_public_key,
         }
     }
 
+    /// 为配置中的每个激活码创建客户端
+    pub fn for_config(config: &CloudPassConfig) -> Vec<CloudPassClient> {
+        config
+            .all_licenses()
+            .iter()
+            .map(|license| Self::new(config, license))
+            .collect()
+    }
+
     /// 获取设备 ID
     pub fn device_id(&self) -> &str {
         &self.device_id
     }
 
+    /// 获取激活码
+    pub fn license_code(&self) -> &str {
+        &self.license_code
+    }
+
     /// 调用 /api/get-credentials 获取凭证
     pub async fn get_credentials(
         &self,
@@ -210,7 +233,7 @@ impl CloudPassClient {
             .await?;
 
         if !resp.status().is_success() {
-            tracing::warn!("心跳请求失败: HTTP {}", resp.status());
+            anyhow::bail!("心跳请求失败: HTTP {}", resp.status());
         }
 
         Ok(())
@@ -233,7 +256,7 @@ impl CloudPassClient {
             .await?;
 
         if !resp.status().is_success() {
-            tracing::warn!("claim-active 请求失败: HTTP {}", resp.status());
+            anyhow::bail!("claim-active 请求失败: HTTP {}", resp.status());
         }
 
         Ok(())
@@ -268,6 +291,11 @@ impl CloudPassClient {
     }
 }
 
+/// 由本机设备 ID 与激活码派生设备 ID（32 位 hex）
+fn derive_device_id(base: &str, license_code: &str) -> String {
+    sha256_hex(&format!("{}:{}", base, license_code))[..32].to_string()
+}
+
 /// RSA 公钥解密（等价于 Node.js crypto.publicDecrypt）
 ///
 /// 执行原始 RSA 操作：m = c^e mod n，然后去除 PKCS#1 v1.5 type 1 padding
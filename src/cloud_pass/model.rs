@@ -1,5 +1,6 @@
 //! Cloud Pass API 数据模型
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 /// 获取凭证请求体
@@ -56,7 +57,7 @@ pub struct CloudPassRawResponse {
     #[serde(default)]
     pub client_secret: Option<String>,
     #[serde(default)]
-    pub expires_at: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
     #[serde(default)]
     pub region: Option<String>,
     #[serde(default)]
@@ -64,7 +65,7 @@ pub struct CloudPassRawResponse {
     #[serde(default)]
     pub kicked: Option<bool>,
     #[serde(default)]
-    pub license_expires_at: Option<String>,
+    pub license_expires_at: Option<DateTime<Utc>>,
 
     // 嵌套的 credentials 对象（服务器可能返回）
     #[serde(default)]
@@ -89,7 +90,7 @@ pub struct CloudPassResponse {
     #[serde(default)]
     pub client_secret: Option<String>,
     #[serde(default)]
-    pub expires_at: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
     #[serde(default)]
     pub region: Option<String>,
     #[serde(default)]
@@ -97,7 +98,7 @@ pub struct CloudPassResponse {
     #[serde(default)]
     pub kicked: Option<bool>,
     #[serde(default)]
-    pub license_expires_at: Option<String>,
+    pub license_expires_at: Option<DateTime<Utc>>,
 
     #[serde(default)]
     pub credentials: Option<CloudPassCredentials>,
@@ -119,7 +120,7 @@ pub struct CloudPassData {
     #[serde(default)]
     pub client_secret: Option<String>,
     #[serde(default)]
-    pub expires_at: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
     #[serde(default)]
     pub region: Option<String>,
     #[serde(default)]
@@ -127,7 +128,7 @@ pub struct CloudPassData {
     #[serde(default)]
     pub kicked: Option<bool>,
     #[serde(default)]
-    pub license_expires_at: Option<String>,
+    pub license_expires_at: Option<DateTime<Utc>>,
     #[serde(default)]
     pub credentials: Option<CloudPassCredentials>,
 }
@@ -145,7 +146,7 @@ pub struct CloudPassCredentials {
     #[serde(default)]
     pub client_secret: Option<String>,
     #[serde(default)]
-    pub expires_at: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
     #[serde(default)]
     pub region: Option<String>,
     #[serde(default)]
@@ -164,11 +165,11 @@ pub struct ResolvedCredentials {
     pub refresh_token: Option<String>,
     pub client_id: Option<String>,
     pub client_secret: Option<String>,
-    pub expires_at: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
     pub region: Option<String>,
     pub profile_arn: Option<String>,
     pub kicked: bool,
-    pub license_expires_at: Option<String>,
+    pub license_expires_at: Option<DateTime<Utc>>,
 }
 
 impl CloudPassResponse {
@@ -205,9 +206,9 @@ impl CloudPassResponse {
             .or_else(|| self.client_secret.clone());
 
         let expires_at = nested_creds
-            .and_then(|c| c.expires_at.clone())
-            .or_else(|| data.and_then(|d| d.expires_at.clone()))
-            .or_else(|| self.expires_at.clone());
+            .and_then(|c| c.expires_at)
+            .or_else(|| data.and_then(|d| d.expires_at))
+            .or(self.expires_at);
 
         let region = nested_creds
             .and_then(|c| c.region.clone())
@@ -225,8 +226,8 @@ impl CloudPassResponse {
             .unwrap_or(false);
 
         let license_expires_at = data
-            .and_then(|d| d.license_expires_at.clone())
-            .or_else(|| self.license_expires_at.clone());
+            .and_then(|d| d.license_expires_at)
+            .or(self.license_expires_at);
 
         ResolvedCredentials {
             access_token,
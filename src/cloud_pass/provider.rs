@@ -0,0 +1,244 @@
+//! Cloud Pass 凭证来源
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+
+use crate::credential_provider::{CredentialProvider, ProvidedCredentials};
+use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::token_manager::MultiTokenManager;
+use crate::model::config::{CloudPassConfig, CloudPassLicense};
+use crate::notify::{EventKind, NotificationEvent};
+
+use super::client::CloudPassClient;
+use super::model::ResolvedCredentials;
+use super::state::{CloudPassState, LicenseStatusHandle, mask_license_code};
+
+/// 单个激活码对应的凭证来源
+pub struct CloudPassProvider {
+    name: String,
+    client: CloudPassClient,
+    config: CloudPassConfig,
+    license: CloudPassLicense,
+    token_manager: Arc<MultiTokenManager>,
+    state: CloudPassState,
+    status: LicenseStatusHandle,
+    /// 已发出到期提醒的 License 到期时间（续期后到期时间变化会重新提醒）
+    license_warned_for: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl CloudPassProvider {
+    pub fn new(
+        config: CloudPassConfig,
+        license: CloudPassLicense,
+        client: CloudPassClient,
+        token_manager: Arc<MultiTokenManager>,
+        state: CloudPassState,
+    ) -> Self {
+        Self {
+            name: format!(
+                "Cloud Pass 激活码 {}",
+                mask_license_code(&license.license_code)
+            ),
+            status: state.license_handle(&license.license_code),
+            client,
+            config,
+            license,
+            token_manager,
+            state,
+            license_warned_for: Mutex::new(None),
+        }
+    }
+
+    /// 设备 ID
+    pub fn device_id(&self) -> &str {
+        self.client.device_id()
+    }
+
+    /// 获取凭证，被踢出时按配置重新抢占
+    async fn resolve(&self) -> anyhow::Result<ResolvedCredentials> {
+        let reassign = self.config.reassign;
+        let creds = self.client.get_credentials(reassign).await?;
+        if !creds.kicked {
+            return Ok(creds);
+        }
+
+        // 同一次被踢出只通知一次，恢复（刷新成功）后再次被踢出会重新通知
+        let newly_kicked = !self
+            .state
+            .license(&self.license.license_code)
+            .is_some_and(|l| l.kicked);
+        self.state.record_kicked(&self.license.license_code);
+        tracing::warn!("Cloud Pass: 当前设备已被踢出");
+        if !reassign {
+            if newly_kicked {
+                self.notify_kicked("未启用 reassign，需手动抢占");
+            }
+            anyhow::bail!("设备已被踢出，启用 reassign 可自动抢占");
+        }
+        self.reclaim().await.inspect_err(|e| {
+            if newly_kicked {
+                self.notify_kicked(&format!("自动抢占失败: {}", e));
+            }
+        })
+    }
+
+    /// 被踢出后按 `claimRetries` / `claimRetryDelay` 重新抢占并获取凭证
+    async fn reclaim(&self) -> anyhow::Result<ResolvedCredentials> {
+        let attempts = self.config.claim_retries.max(1);
+        let mut last_error = anyhow::anyhow!("重新抢占失败");
+        for attempt in 1..=attempts {
+            if attempt > 1 {
+                tokio::time::sleep(Duration::from_secs(self.config.claim_retry_delay)).await;
+            }
+            tracing::info!("Cloud Pass: 尝试重新抢占（{}/{}）...", attempt, attempts);
+            let result = match self.client.claim_active().await {
+                Ok(()) => self.client.get_credentials(true).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(creds) if !creds.kicked => return Ok(creds),
+                Ok(_) => last_error = anyhow::anyhow!("重新抢占后仍被踢出，请检查激活码"),
+                Err(e) => last_error = e,
+            }
+        }
+        anyhow::bail!("重新抢占 {} 次均失败: {}", attempts, last_error)
+    }
+
+    /// 发出设备被踢出通知
+    fn notify_kicked(&self, detail: &str) {
+        let masked = mask_license_code(&self.license.license_code);
+        self.token_manager.notify(
+            NotificationEvent::new(
+                EventKind::CloudPassKicked,
+                format!(
+                    "Cloud Pass 设备 {}（激活码 {}）已被踢出，{}",
+                    self.client.device_id(),
+                    masked,
+                    detail
+                ),
+            )
+            .with_data(serde_json::json!({
+                "license": masked,
+                "deviceId": self.client.device_id(),
+            })),
+        );
+    }
+
+    /// License 剩余天数不足 `licenseExpiryWarnDays` 时发出提醒（每个到期时间只提醒一次）
+    fn warn_license_expiry(&self, expires_at: DateTime<Utc>) {
+        let warn_days = self.token_manager.config().license_expiry_warn_days;
+        let days_left = (expires_at - Utc::now()).num_days();
+        let mut warned_for = self.license_warned_for.lock();
+        if days_left >= warn_days as i64 || *warned_for == Some(expires_at) {
+            return;
+        }
+        *warned_for = Some(expires_at);
+        let masked = mask_license_code(&self.license.license_code);
+        self.token_manager.notify(
+            NotificationEvent::new(
+                EventKind::LicenseExpiring,
+                format!(
+                    "Cloud Pass License {} 将于 {} 到期",
+                    masked,
+                    expires_at.to_rfc3339()
+                ),
+            )
+            .with_data(serde_json::json!({
+                "license": masked,
+                "licenseExpiresAt": expires_at,
+            })),
+        );
+    }
+}
+
+impl CredentialProvider for CloudPassProvider {
+    type Status = LicenseStatusHandle;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch(&self) -> anyhow::Result<ProvidedCredentials> {
+        let creds = self.resolve().await?;
+
+        if let Some(expires) = creds.license_expires_at {
+            tracing::info!("Cloud Pass license 有效至: {}", expires);
+            self.warn_license_expiry(expires);
+        }
+
+        let refresh_token = creds
+            .refresh_token
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("服务器未返回 refreshToken"))?;
+
+        // 日志（脱敏）
+        tracing::info!(
+            "Cloud Pass 凭证: accessToken={}***, refreshToken={}***, region={}, profileArn={}",
+            creds
+                .access_token
+                .as_deref()
+                .unwrap_or("N/A")
+                .get(..8)
+                .unwrap_or("N/A"),
+            refresh_token.get(..8).unwrap_or("N/A"),
+            creds.region.as_deref().unwrap_or("N/A"),
+            creds.profile_arn.as_deref().unwrap_or("N/A"),
+        );
+
+        // 构建完整的 KiroCredentials，写入所有字段
+        let credentials = KiroCredentials {
+            id: None,
+            access_token: creds.access_token.clone(),
+            refresh_token: Some(refresh_token.clone()),
+            profile_arn: creds.profile_arn.clone(),
+            expires_at: creds.expires_at,
+            auth_method: Some("idc".to_string()),
+            client_id: creds.client_id.clone(),
+            client_secret: creds.client_secret.clone(),
+            priority: 0,
+            region: creds.region.clone(),
+            auth_region: None,
+            api_region: None,
+            machine_id: self
+                .license
+                .machine_id
+                .clone()
+                .or_else(|| Some(self.client.device_id().to_string())), // 优先使用配置的固定 machineId，否则用 deviceId
+            email: None,
+            account_id: None,
+            subscription_title: None,
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            tls_backend: None,
+            ca_cert_path: None,
+            pinned_cert_sha256: Vec::new(),
+            rate_limit_rpm: None,
+            max_concurrency: None,
+            usage_cap: None,
+            usage_watermark: None,
+            tags: Vec::new(),
+            note: None,
+            allowed_models: Vec::new(),
+            blocked_models: Vec::new(),
+            peer_source_id: None,
+            disabled: false,
+        };
+
+        Ok(ProvidedCredentials {
+            credentials,
+            valid_until: creds.license_expires_at,
+        })
+    }
+
+    async fn heartbeat(&self) -> anyhow::Result<()> {
+        self.client.heartbeat().await
+    }
+
+    fn status(&self) -> &LicenseStatusHandle {
+        &self.status
+    }
+}
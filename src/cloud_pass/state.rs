@@ -3,17 +3,27 @@
 //! Worker 写入，Admin API 读取
 
 use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::{DateTime, Utc};
 use parking_lot::RwLock;
 use serde::Serialize;
 use tokio::sync::Notify;
 
+use crate::credential_provider::ProviderStatus;
+use crate::kiro::model::credentials::CredentialId;
+use crate::model::config::CloudPassConfig;
+
+use super::client::CloudPassClient;
+
 /// Cloud Pass 运行时状态（线程安全共享）
 #[derive(Clone)]
 pub struct CloudPassState {
     inner: Arc<RwLock<CloudPassStatusInner>>,
     /// 手动刷新通知器
     refresh_notify: Arc<Notify>,
+    /// 配置变更通知器（唤醒后台任务立即按新配置重启）
+    reconcile_notify: Arc<Notify>,
 }
 
 /// 内部状态数据
@@ -22,22 +32,39 @@ pub struct CloudPassState {
 pub struct CloudPassStatusInner {
     /// 是否已启用（配置了 cloud_pass）
     pub enabled: bool,
-    /// 是否已连接（至少成功刷新过一次）
+    /// 是否已连接（任一激活码至少成功刷新过一次）
     pub connected: bool,
     /// 服务器地址
     pub server_url: String,
-    /// 设备 ID
-    pub device_id: String,
-    /// 激活码（脱敏，只显示前6位）
-    pub license_code_masked: String,
     /// 刷新间隔（秒）
     pub refresh_interval: u64,
+    /// 心跳间隔（秒，0 表示不发送心跳）
+    pub heartbeat_interval: u64,
     /// 是否启用抢占
     pub reassign: bool,
     /// 客户端版本
     pub client_version: String,
+    /// 是否有激活码的设备被踢出
+    pub kicked: bool,
+    /// 各激活码的状态（顺序与配置一致）
+    pub licenses: Vec<LicenseStatus>,
+}
+
+/// 单个激活码的运行时状态
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LicenseStatus {
+    /// 激活码（仅用于内部匹配，不对外输出）
+    #[serde(skip)]
+    license_code: String,
+    /// 激活码（脱敏，只显示前6位）
+    pub license_code_masked: String,
+    /// 设备 ID
+    pub device_id: String,
+    /// 是否已连接（至少成功刷新过一次）
+    pub connected: bool,
     /// 上次刷新时间（RFC3339）
-    pub last_refresh_at: Option<String>,
+    pub last_refresh_at: Option<DateTime<Utc>>,
     /// 上次刷新是否成功
     pub last_refresh_ok: bool,
     /// 上次刷新错误信息
@@ -46,12 +73,52 @@ pub struct CloudPassStatusInner {
     pub refresh_success_count: u64,
     /// 失败刷新次数
     pub refresh_failure_count: u64,
+    /// 连续失败次数（成功后清零）
+    pub consecutive_failures: u32,
+    /// 当前的失败重试退避时长（秒，刷新正常时为空）
+    pub retry_backoff_secs: Option<u64>,
+    /// 下次计划刷新时间
+    pub next_refresh_at: Option<DateTime<Utc>>,
+    /// 上次心跳时间
+    pub last_heartbeat_at: Option<DateTime<Utc>>,
+    /// 上次心跳是否成功
+    pub last_heartbeat_ok: bool,
+    /// 上次心跳错误信息
+    pub last_heartbeat_error: Option<String>,
     /// License 到期时间
-    pub license_expires_at: Option<String>,
+    pub license_expires_at: Option<DateTime<Utc>>,
     /// 是否被踢出
     pub kicked: bool,
     /// 注入的凭据 ID（最近一次）
-    pub injected_credential_id: Option<u64>,
+    pub injected_credential_id: Option<CredentialId>,
+    /// 已被后续注入替换、尚未清理的旧凭据 ID（按替换顺序）
+    pub superseded_credential_ids: Vec<CredentialId>,
+}
+
+impl LicenseStatus {
+    fn new(license_code: &str, device_id: &str) -> Self {
+        Self {
+            license_code: license_code.to_string(),
+            license_code_masked: mask_license_code(license_code),
+            device_id: device_id.to_string(),
+            connected: false,
+            last_refresh_at: None,
+            last_refresh_ok: false,
+            last_refresh_error: None,
+            refresh_success_count: 0,
+            refresh_failure_count: 0,
+            consecutive_failures: 0,
+            retry_backoff_secs: None,
+            next_refresh_at: None,
+            last_heartbeat_at: None,
+            last_heartbeat_ok: false,
+            last_heartbeat_error: None,
+            license_expires_at: None,
+            kicked: false,
+            injected_credential_id: None,
+            superseded_credential_ids: Vec::new(),
+        }
+    }
 }
 
 impl CloudPassState {
@@ -59,100 +126,143 @@ impl CloudPassState {
     pub fn disabled() -> Self {
         Self {
             refresh_notify: Arc::new(Notify::new()),
+            reconcile_notify: Arc::new(Notify::new()),
             inner: Arc::new(RwLock::new(CloudPassStatusInner {
                 enabled: false,
                 connected: false,
                 server_url: String::new(),
-                device_id: String::new(),
-                license_code_masked: String::new(),
                 refresh_interval: 0,
+                heartbeat_interval: 0,
                 reassign: false,
                 client_version: String::new(),
-                last_refresh_at: None,
-                last_refresh_ok: false,
-                last_refresh_error: None,
-                refresh_success_count: 0,
-                refresh_failure_count: 0,
-                license_expires_at: None,
                 kicked: false,
-                injected_credential_id: None,
+                licenses: Vec::new(),
             })),
         }
     }
 
-    /// 从配置创建初始状态
-    pub fn from_config(
-        server_url: &str,
-        device_id: &str,
-        license_code: &str,
-        refresh_interval: u64,
-        reassign: bool,
-        client_version: &str,
-    ) -> Self {
-        let masked = if license_code.len() > 6 {
-            format!("{}***", &license_code[..6])
-        } else {
-            format!("{}***", license_code)
-        };
+    /// 从配置创建初始状态（`clients` 为各激活码的客户端，用于获取设备 ID）
+    pub fn from_config(config: &CloudPassConfig, clients: &[CloudPassClient]) -> Self {
+        let state = Self::disabled();
+        state.apply_config(config, clients);
+        state
+    }
 
-        Self {
-            refresh_notify: Arc::new(Notify::new()),
-            inner: Arc::new(RwLock::new(CloudPassStatusInner {
-                enabled: true,
-                connected: false,
-                server_url: server_url.to_string(),
-                device_id: device_id.to_string(),
-                license_code_masked: masked,
-                refresh_interval,
-                reassign,
-                client_version: client_version.to_string(),
-                last_refresh_at: None,
-                last_refresh_ok: false,
-                last_refresh_error: None,
-                refresh_success_count: 0,
-                refresh_failure_count: 0,
-                license_expires_at: None,
-                kicked: false,
-                injected_credential_id: None,
-            })),
-        }
+    /// 配置热重载后更新展示字段（保留仍在配置中的激活码的刷新统计）
+    pub fn apply_config(&self, config: &CloudPassConfig, clients: &[CloudPassClient]) {
+        let mut inner = self.inner.write();
+        inner.enabled = true;
+        inner.server_url = config.server_url.clone();
+        inner.refresh_interval = config.refresh_interval;
+        inner.heartbeat_interval = config.heartbeat_interval;
+        inner.reassign = config.reassign;
+        inner.client_version = config.client_version.clone();
+
+        let mut previous = std::mem::take(&mut inner.licenses);
+        inner.licenses = clients
+            .iter()
+            .map(|client| {
+                match previous
+                    .iter()
+                    .position(|l| l.license_code == client.license_code())
+                {
+                    Some(index) => {
+                        let mut status = previous.swap_remove(index);
+                        if status.device_id != client.device_id() {
+                            status = LicenseStatus::new(client.license_code(), client.device_id());
+                        }
+                        status
+                    }
+                    None => LicenseStatus::new(client.license_code(), client.device_id()),
+                }
+            })
+            .collect();
+        inner.refresh_aggregates();
+    }
+
+    /// 配置中移除或暂停 Cloud Pass 后标记为未启用
+    pub fn mark_disabled(&self) {
+        let mut inner = self.inner.write();
+        inner.enabled = false;
+        inner.connected = false;
+        inner.kicked = false;
+        inner.licenses.clear();
     }
 
     /// 记录刷新成功
     pub fn record_success(
         &self,
-        credential_id: Option<u64>,
-        license_expires_at: Option<String>,
-        kicked: bool,
+        license_code: &str,
+        credential_id: Option<CredentialId>,
+        license_expires_at: Option<DateTime<Utc>>,
     ) {
-        let mut inner = self.inner.write();
-        inner.connected = true;
-        inner.last_refresh_at = Some(chrono::Utc::now().to_rfc3339());
-        inner.last_refresh_ok = true;
-        inner.last_refresh_error = None;
-        inner.refresh_success_count += 1;
-        inner.kicked = kicked;
-        if let Some(id) = credential_id {
-            inner.injected_credential_id = Some(id);
-        }
-        if license_expires_at.is_some() {
-            inner.license_expires_at = license_expires_at;
-        }
+        self.update_license(license_code, |license| {
+            license.connected = true;
+            license.last_refresh_at = Some(Utc::now());
+            license.last_refresh_ok = true;
+            license.last_refresh_error = None;
+            license.refresh_success_count += 1;
+            license.consecutive_failures = 0;
+            license.retry_backoff_secs = None;
+            license.kicked = false;
+            if let Some(id) = credential_id {
+                if let Some(previous) = license.injected_credential_id.filter(|&p| p != id)
+                    && !license.superseded_credential_ids.contains(&previous)
+                {
+                    license.superseded_credential_ids.push(previous);
+                }
+                license.superseded_credential_ids.retain(|&p| p != id);
+                license.injected_credential_id = Some(id);
+            }
+            if license_expires_at.is_some() {
+                license.license_expires_at = license_expires_at;
+            }
+        });
     }
 
-    /// 记录刷新失败
-    pub fn record_failure(&self, error: &str) {
-        let mut inner = self.inner.write();
-        inner.last_refresh_at = Some(chrono::Utc::now().to_rfc3339());
-        inner.last_refresh_ok = false;
-        inner.last_refresh_error = Some(error.to_string());
-        inner.refresh_failure_count += 1;
+    /// 记录刷新失败，返回连续失败次数
+    pub fn record_failure(&self, license_code: &str, error: &str) -> u32 {
+        self.update_license(license_code, |license| {
+            license.last_refresh_at = Some(Utc::now());
+            license.last_refresh_ok = false;
+            license.last_refresh_error = Some(error.to_string());
+            license.refresh_failure_count += 1;
+            license.consecutive_failures += 1;
+            license.consecutive_failures
+        })
+        .unwrap_or(1)
+    }
+
+    /// 记录下次刷新计划（`backoff` 为失败重试的退避时长）
+    pub fn record_schedule(&self, license_code: &str, delay: Duration, backoff: bool) {
+        self.update_license(license_code, |license| {
+            license.next_refresh_at = chrono::Duration::from_std(delay)
+                .ok()
+                .map(|d| Utc::now() + d);
+            license.retry_backoff_secs = backoff.then_some(delay.as_secs());
+        });
+    }
+
+    /// 取出全部待清理的旧凭据 ID（取出后清空）
+    pub fn take_superseded_credentials(&self, license_code: &str) -> Vec<CredentialId> {
+        self.update_license(license_code, |license| {
+            std::mem::take(&mut license.superseded_credential_ids)
+        })
+        .unwrap_or_default()
+    }
+
+    /// 记录心跳结果
+    pub fn record_heartbeat(&self, license_code: &str, error: Option<String>) {
+        self.update_license(license_code, |license| {
+            license.last_heartbeat_at = Some(Utc::now());
+            license.last_heartbeat_ok = error.is_none();
+            license.last_heartbeat_error = error;
+        });
     }
 
     /// 记录被踢出
-    pub fn record_kicked(&self) {
-        let mut inner = self.inner.write();
-        inner.kicked = true;
+    pub fn record_kicked(&self, license_code: &str) {
+        self.update_license(license_code, |license| license.kicked = true);
     }
 
     /// 获取当前状态快照
@@ -160,18 +270,166 @@ impl CloudPassState {
         self.inner.read().clone()
     }
 
-    /// 获取设备 ID
-    pub fn device_id(&self) -> String {
-        self.inner.read().device_id.clone()
+    /// 指定激活码的状态记录句柄（供通用刷新循环写入）
+    pub fn license_handle(&self, license_code: &str) -> LicenseStatusHandle {
+        LicenseStatusHandle {
+            state: self.clone(),
+            license_code: license_code.to_string(),
+        }
+    }
+
+    /// 获取指定激活码的状态快照
+    pub fn license(&self, license_code: &str) -> Option<LicenseStatus> {
+        self.inner
+            .read()
+            .licenses
+            .iter()
+            .find(|l| l.license_code == license_code)
+            .cloned()
     }
 
-    /// 触发手动刷新
+    /// 触发手动刷新（所有激活码）
     pub fn trigger_refresh(&self) {
-        self.refresh_notify.notify_one();
+        self.refresh_notify.notify_waiters();
     }
 
     /// 等待手动刷新通知
     pub fn wait_for_refresh(&self) -> Arc<Notify> {
         self.refresh_notify.clone()
     }
+
+    /// 通知后台任务配置已变更
+    pub fn request_reconcile(&self) {
+        self.reconcile_notify.notify_one();
+    }
+
+    /// 等待配置变更通知
+    pub async fn wait_for_reconcile(&self) {
+        self.reconcile_notify.notified().await;
+    }
+
+    fn update_license<R>(
+        &self,
+        license_code: &str,
+        update: impl FnOnce(&mut LicenseStatus) -> R,
+    ) -> Option<R> {
+        let mut inner = self.inner.write();
+        let result = inner
+            .licenses
+            .iter_mut()
+            .find(|l| l.license_code == license_code)
+            .map(update);
+        inner.refresh_aggregates();
+        result
+    }
+}
+
+/// 单个激活码的状态记录句柄
+pub struct LicenseStatusHandle {
+    state: CloudPassState,
+    license_code: String,
+}
+
+impl ProviderStatus for LicenseStatusHandle {
+    fn record_success(
+        &self,
+        credential_id: Option<CredentialId>,
+        valid_until: Option<DateTime<Utc>>,
+    ) {
+        self.state
+            .record_success(&self.license_code, credential_id, valid_until);
+    }
+
+    fn record_failure(&self, error: &str) -> u32 {
+        self.state.record_failure(&self.license_code, error)
+    }
+
+    fn record_schedule(&self, delay: Duration, backoff: bool) {
+        self.state
+            .record_schedule(&self.license_code, delay, backoff);
+    }
+
+    fn record_heartbeat(&self, error: Option<String>) {
+        self.state.record_heartbeat(&self.license_code, error);
+    }
+
+    fn injected_credential_id(&self) -> Option<CredentialId> {
+        self.state
+            .license(&self.license_code)
+            .and_then(|l| l.injected_credential_id)
+    }
+
+    fn has_superseded_credentials(&self) -> bool {
+        self.state
+            .license(&self.license_code)
+            .is_some_and(|l| !l.superseded_credential_ids.is_empty())
+    }
+
+    fn take_superseded_credentials(&self) -> Vec<CredentialId> {
+        self.state.take_superseded_credentials(&self.license_code)
+    }
+}
+
+impl CloudPassStatusInner {
+    fn refresh_aggregates(&mut self) {
+        self.connected = self.licenses.iter().any(|l| l.connected);
+        self.kicked = self.licenses.iter().any(|l| l.kicked);
+    }
+}
+
+/// 激活码脱敏（只显示前6位）
+pub fn mask_license_code(license_code: &str) -> String {
+    if license_code.len() > 6 {
+        format!("{}***", &license_code[..6])
+    } else {
+        format!("{}***", license_code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LICENSE: &str = "LICENSE-CODE";
+
+    fn state_with_license() -> CloudPassState {
+        let state = CloudPassState::disabled();
+        state
+            .inner
+            .write()
+            .licenses
+            .push(LicenseStatus::new(LICENSE, "device-id"));
+        state
+    }
+
+    #[test]
+    fn test_record_success_tracks_superseded_credentials() {
+        let state = state_with_license();
+        let (a, b, c) = (CredentialId(1), CredentialId(2), CredentialId(3));
+
+        state.record_success(LICENSE, Some(a), None);
+        assert!(state.take_superseded_credentials(LICENSE).is_empty());
+
+        // 重复注入同一凭据不视为替换
+        state.record_success(LICENSE, Some(a), None);
+        assert!(state.take_superseded_credentials(LICENSE).is_empty());
+
+        // 连续两次轮换（中间未清理），两个旧凭据都待清理
+        state.record_success(LICENSE, Some(b), None);
+        state.record_success(LICENSE, Some(c), None);
+        let license = state.license(LICENSE).unwrap();
+        assert_eq!(license.injected_credential_id, Some(c));
+        assert_eq!(license.superseded_credential_ids, vec![a, b]);
+
+        // 取出后清空
+        assert_eq!(state.take_superseded_credentials(LICENSE), vec![a, b]);
+        assert!(state.take_superseded_credentials(LICENSE).is_empty());
+        assert!(
+            state
+                .license(LICENSE)
+                .unwrap()
+                .superseded_credential_ids
+                .is_empty()
+        );
+    }
 }
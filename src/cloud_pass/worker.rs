@@ -1,180 +1,157 @@
 //! Cloud Pass 后台刷新任务
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::kiro::model::credentials::KiroCredentials;
+use tokio::task::AbortHandle;
+
+use crate::credential_provider::CredentialProvider;
+use crate::credential_provider::worker::{RefreshOptions, run_heartbeat_loop, run_refresh_loop};
 use crate::kiro::token_manager::MultiTokenManager;
-use crate::model::config::CloudPassConfig;
+use crate::model::config::{CloudPassConfig, CloudPassLicense};
 
 use super::client::CloudPassClient;
+use super::provider::CloudPassProvider;
 use super::state::CloudPassState;
 
-/// 启动 Cloud Pass 后台刷新任务
-///
-/// 定时从 eskysoft 服务器获取凭证并注入到 token_manager
-pub async fn start_cloud_pass_worker(
-    token_manager: Arc<MultiTokenManager>,
+/// 配置检查间隔：直接编辑 config.json 后最迟在该间隔内生效（经 Admin API 修改时立即生效）
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 一个激活码的刷新任务与心跳任务
+struct LicenseTask {
     config: CloudPassConfig,
-    state: CloudPassState,
-) {
-    let client = CloudPassClient::new(&config);
-    let interval = Duration::from_secs(config.refresh_interval);
-    let reassign = config.reassign;
+    license: CloudPassLicense,
+    handle: AbortHandle,
+    heartbeat: Option<AbortHandle>,
+}
+
+impl LicenseTask {
+    /// 任务仍在运行且配置未变化
+    fn is_current(&self, config: &CloudPassConfig, license: &CloudPassLicense) -> bool {
+        !self.handle.is_finished()
+            && self.license == *license
+            && self.config.server_url == config.server_url
+            && self.config.refresh_interval == config.refresh_interval
+            && self.config.heartbeat_interval == config.heartbeat_interval
+            && self.config.reassign == config.reassign
+            && self.config.claim_retries == config.claim_retries
+            && self.config.claim_retry_delay == config.claim_retry_delay
+            && self.config.stale_credential_action == config.stale_credential_action
+            && self.config.client_version == config.client_version
+    }
+}
 
-    tracing::info!("Cloud Pass 后台刷新任务启动");
-    tracing::info!("  服务器: {}", config.server_url);
-    tracing::info!("  设备 ID: {}", client.device_id());
-    tracing::info!("  刷新间隔: {}s", config.refresh_interval);
-    tracing::info!(
-        "  激活码: {}***",
-        &config.license_code[..config.license_code.len().min(6)]
-    );
+impl Drop for LicenseTask {
+    fn drop(&mut self) {
+        self.handle.abort();
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.abort();
+        }
+    }
+}
+
+/// 启动 Cloud Pass 后台刷新任务
+///
+/// 为每个激活码启动独立的刷新任务（各自的设备 ID 与刷新循环），定时从 eskysoft 服务器获取凭证并注入到 token_manager；
+/// 配置热重载时只重启发生变化的激活码任务，Cloud Pass 被移除或暂停时停止所有任务、启用后自动开始
+pub async fn start_cloud_pass_worker(token_manager: Arc<MultiTokenManager>, state: CloudPassState) {
+    tracing::info!("Cloud Pass 后台任务启动");
 
     // 等待 5 秒让 kiro-rs 完成初始化
     tokio::time::sleep(Duration::from_secs(5)).await;
 
+    let mut tasks: HashMap<String, LicenseTask> = HashMap::new();
     loop {
-        match do_refresh(&client, &token_manager, reassign, &state, &config).await {
-            Ok(()) => {
-                tracing::info!("Cloud Pass 凭证刷新成功");
-            }
-            Err(e) => {
-                state.record_failure(&e.to_string());
-                tracing::error!("Cloud Pass 凭证刷新失败: {}", e);
+        match token_manager.config().active_cloud_pass().cloned() {
+            Some(config) => reconcile(&token_manager, &state, &config, &mut tasks),
+            None => {
+                if !tasks.is_empty() {
+                    tracing::info!("Cloud Pass 已移除或暂停，停止刷新");
+                    tasks.clear();
+                }
+                state.mark_disabled();
             }
         }
-
-        // 心跳保活（失败不影响主流程）
-        if let Err(e) = client.heartbeat().await {
-            tracing::warn!("Cloud Pass 心跳失败: {}", e);
-        }
-
-        // 等待定时刷新或手动刷新信号
-        let notify = state.wait_for_refresh();
         tokio::select! {
-            _ = tokio::time::sleep(interval) => {},
-            _ = notify.notified() => {
-                tracing::info!("Cloud Pass 收到手动刷新请求");
-            },
+            _ = tokio::time::sleep(RECONCILE_INTERVAL) => {},
+            _ = state.wait_for_reconcile() => {},
         }
     }
 }
 
-/// 执行一次凭证刷新
-async fn do_refresh(
-    client: &CloudPassClient,
-    token_manager: &MultiTokenManager,
-    reassign: bool,
+/// 按最新配置启动、重启或停止各激活码的刷新任务
+fn reconcile(
+    token_manager: &Arc<MultiTokenManager>,
     state: &CloudPassState,
     config: &CloudPassConfig,
-) -> anyhow::Result<()> {
-    // 获取凭证
-    let creds = client.get_credentials(reassign).await?;
-
-    // 检查 kicked 状态
-    if creds.kicked {
-        state.record_kicked();
-        tracing::warn!("Cloud Pass: 当前设备已被踢出");
-        if reassign {
-            tracing::info!("Cloud Pass: 尝试重新抢占...");
-            client.claim_active().await?;
-            // 重新获取凭证
-            let creds = client.get_credentials(true).await?;
-            if creds.kicked {
-                anyhow::bail!("重新抢占后仍被踢出，请检查激活码");
-            }
-            return inject_credentials(client, token_manager, &creds, state, config).await;
-        }
-        anyhow::bail!("设备已被踢出，启用 reassign 可自动抢占");
+    tasks: &mut HashMap<String, LicenseTask>,
+) {
+    let licenses = config.all_licenses();
+    let unchanged = tasks.len() == licenses.len()
+        && licenses.iter().all(|license| {
+            tasks
+                .get(&license.license_code)
+                .is_some_and(|task| task.is_current(config, license))
+        });
+    if unchanged && state.snapshot().enabled {
+        return;
     }
-
-    if let Some(ref expires) = creds.license_expires_at {
-        tracing::info!("Cloud Pass license 有效至: {}", expires);
+    if licenses.is_empty() {
+        tracing::warn!("Cloud Pass 未配置激活码（licenseCode / licenses），跳过刷新");
+    } else {
+        tracing::info!(
+            "Cloud Pass 服务器: {}，刷新间隔: {}s，激活码 {} 个",
+            config.server_url,
+            config.refresh_interval,
+            licenses.len()
+        );
     }
 
-    inject_credentials(client, token_manager, &creds, state, config).await
-}
-
-/// 将凭证注入到 token_manager
-async fn inject_credentials(
-    client: &CloudPassClient,
-    token_manager: &MultiTokenManager,
-    creds: &super::model::ResolvedCredentials,
-    state: &CloudPassState,
-    config: &CloudPassConfig,
-) -> anyhow::Result<()> {
-    let refresh_token = creds
-        .refresh_token
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("服务器未返回 refreshToken"))?;
-
-    // 构建完整的 KiroCredentials，写入所有字段
-    let new_cred = KiroCredentials {
-        id: None,
-        access_token: creds.access_token.clone(),
-        refresh_token: Some(refresh_token.clone()),
-        profile_arn: creds.profile_arn.clone(),
-        expires_at: creds.expires_at.clone(),
-        auth_method: Some("idc".to_string()),
-        client_id: creds.client_id.clone(),
-        client_secret: creds.client_secret.clone(),
-        priority: 0,
-        region: creds.region.clone(),
-        auth_region: None,
-        api_region: None,
-        machine_id: config.machine_id.clone().or_else(|| Some(client.device_id().to_string())), // 优先使用配置的固定 machineId，否则用 deviceId
-        email: None,
-        subscription_title: None,
-        proxy_url: None,
-        proxy_username: None,
-        proxy_password: None,
-        disabled: false,
-    };
-
-    // 日志（脱敏）
-    tracing::info!(
-        "Cloud Pass 凭证: accessToken={}***, refreshToken={}***, region={}, profileArn={}",
-        creds
-            .access_token
-            .as_deref()
-            .unwrap_or("N/A")
-            .get(..8)
-            .unwrap_or("N/A"),
-        refresh_token.get(..8).unwrap_or("N/A"),
-        creds.region.as_deref().unwrap_or("N/A"),
-        creds.profile_arn.as_deref().unwrap_or("N/A"),
-    );
-
-    // 通过 token_manager 注入（与 Admin API 相同路径）
-    match token_manager.add_credential(new_cred).await {
-        Ok(id) => {
-            tracing::info!("Cloud Pass 凭证已注入，ID: {}", id);
-            state.record_success(
-                Some(id),
-                creds.license_expires_at.clone(),
-                creds.kicked,
-            );
-            // 主动获取订阅等级
-            if let Err(e) = token_manager.get_usage_limits_for(id).await {
-                tracing::warn!("获取订阅等级失败（不影响使用）: {}", e);
-            }
-            Ok(())
-        }
-        Err(e) => {
-            let err_msg = e.to_string();
-            // refreshToken 重复 = 凭证没变，不需要注入
-            if err_msg.contains("重复") || err_msg.contains("duplicate") {
-                tracing::info!("Cloud Pass 凭证未变化，跳过注入");
-                state.record_success(
-                    None,
-                    creds.license_expires_at.clone(),
-                    creds.kicked,
-                );
-                Ok(())
-            } else {
-                Err(e)
-            }
+    let clients = CloudPassClient::for_config(config);
+    state.apply_config(config, &clients);
+    tasks.retain(|code, _| licenses.iter().any(|l| &l.license_code == code));
+    for (license, client) in licenses.into_iter().zip(clients) {
+        if tasks
+            .get(&license.license_code)
+            .is_some_and(|task| task.is_current(config, &license))
+        {
+            continue;
         }
+        let provider = Arc::new(CloudPassProvider::new(
+            config.clone(),
+            license.clone(),
+            client,
+            token_manager.clone(),
+            state.clone(),
+        ));
+        tracing::info!(
+            "{} 刷新任务启动，设备 ID: {}",
+            provider.name(),
+            provider.device_id()
+        );
+        let heartbeat = (config.heartbeat_interval > 0).then(|| {
+            tokio::spawn(run_heartbeat_loop(
+                provider.clone(),
+                Duration::from_secs(config.heartbeat_interval),
+            ))
+            .abort_handle()
+        });
+        let options = RefreshOptions {
+            interval: Duration::from_secs(config.refresh_interval),
+            stale_action: config.stale_credential_action,
+            refresh_notify: state.wait_for_refresh(),
+        };
+        let handle =
+            tokio::spawn(run_refresh_loop(provider, token_manager.clone(), options)).abort_handle();
+        tasks.insert(
+            license.license_code.clone(),
+            LicenseTask {
+                config: config.clone(),
+                license,
+                handle,
+                heartbeat,
+            },
+        );
     }
 }
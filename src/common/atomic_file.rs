@@ -0,0 +1,186 @@
+//! 崩溃安全的文件写入
+//!
+//! 先完整写入同目录下的临时文件并 fsync，把当前版本复制为 `.bak`，
+//! 最后原子 rename 覆盖目标文件并 fsync 目录。任一步骤中途崩溃时，
+//! 目标文件要么是旧版本要么是新版本，不会出现截断的半个文件。
+//! 临时文件名包含进程 ID 与序号，并发写入同一文件时互不截断对方的临时文件
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Context;
+
+/// 在文件名后追加后缀（`credentials.json` -> `credentials.json.bak`）
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name: OsString = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// 临时文件序号（同一进程内唯一）
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 本次写入专用的临时文件路径（`credentials.json` -> `credentials.json.<pid>.<序号>.tmp`）
+fn unique_tmp_path(path: &Path) -> PathBuf {
+    let seq = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    with_suffix(path, &format!(".{}.{}.tmp", std::process::id(), seq))
+}
+
+/// 创建（或截断）文件并设置权限：沿用目标文件原有的权限，目标不存在时在 Unix 上为 0600
+fn create_with_permissions(
+    path: &Path,
+    permissions: Option<&Permissions>,
+) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path)?;
+    // mode 只在新建时生效，已存在的文件（如残留的 .tmp）需要显式设置
+    match permissions {
+        Some(permissions) => file.set_permissions(permissions.clone())?,
+        #[cfg(unix)]
+        None => {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(Permissions::from_mode(0o600))?
+        }
+        #[cfg(not(unix))]
+        None => {}
+    }
+    Ok(file)
+}
+
+/// 上一版本的备份路径
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+/// 原子写入文件，并保留上一版本为 `<文件名>.bak`
+///
+/// 新文件沿用目标文件原有的权限（如 0600），避免替换后凭据文件变为所有用户可读。
+/// rename 失败时（如目标是单独挂载进容器的文件）退回直接覆盖写入，此时仍有 `.bak` 可恢复
+pub fn write_atomic(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let tmp = unique_tmp_path(path);
+    let permissions = fs::metadata(path).ok().map(|m| m.permissions());
+    {
+        let mut file = create_with_permissions(&tmp, permissions.as_ref())
+            .with_context(|| format!("创建临时文件失败: {:?}", tmp))?;
+        file.write_all(contents)
+            .and_then(|_| file.sync_all())
+            .with_context(|| format!("写入临时文件失败: {:?}", tmp))?;
+    }
+
+    if path.exists() {
+        // 备份同样先写临时文件再 rename，并发写入时 .bak 总是某个完整的版本
+        let backup = backup_path(path);
+        let backup_tmp = unique_tmp_path(&backup);
+        fs::copy(path, &backup_tmp)
+            .and_then(|_| File::open(&backup_tmp)?.sync_all())
+            .and_then(|_| fs::rename(&backup_tmp, &backup))
+            .with_context(|| format!("备份文件失败: {:?}", backup))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&backup_tmp);
+            })?;
+    }
+
+    if let Err(e) = fs::rename(&tmp, path) {
+        tracing::warn!("原子替换 {:?} 失败，改为直接写入: {}", path, e);
+        let _ = fs::remove_file(&tmp);
+        let mut file = create_with_permissions(path, permissions.as_ref())
+            .with_context(|| format!("写入文件失败: {:?}", path))?;
+        file.write_all(contents)
+            .and_then(|_| file.sync_all())
+            .with_context(|| format!("写入文件失败: {:?}", path))?;
+        return Ok(());
+    }
+
+    // rename 本身需要目录落盘才能在掉电后保留（Windows 不支持打开目录）
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        File::open(dir).and_then(|d| d.sync_all()).ok();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_keeps_backup() {
+        let dir = std::env::temp_dir().join(format!("kiro-atomic-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("credentials.json");
+
+        write_atomic(&path, b"v1").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"v1");
+        assert!(!backup_path(&path).exists());
+
+        write_atomic(&path, b"v2").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"v2");
+        assert_eq!(fs::read(backup_path(&path)).unwrap(), b"v1");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2, "临时文件应已清理");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_writes_do_not_share_tmp_file() {
+        let dir = std::env::temp_dir().join(format!("kiro-atomic-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("credentials.json");
+        let versions: Vec<Vec<u8>> = (0..8)
+            .map(|i| format!("{{\"version\":{}}}", i).repeat(1000).into_bytes())
+            .collect();
+
+        std::thread::scope(|scope| {
+            for contents in &versions {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        write_atomic(path, contents).unwrap();
+                    }
+                });
+            }
+        });
+
+        // 目标文件与备份都是某次完整写入的内容，且不残留临时文件
+        assert!(versions.contains(&fs::read(&path).unwrap()));
+        assert!(versions.contains(&fs::read(backup_path(&path)).unwrap()));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_preserves_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("kiro-atomic-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        // 新建的文件默认仅所有者可读写
+        let path = dir.join("credentials.json");
+        write_atomic(&path, b"v1").unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        // 已有文件的权限在多次写入后保持不变
+        let path = dir.join("locked.json");
+        fs::write(&path, b"v0").unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+        write_atomic(&path, b"v1").unwrap();
+        write_atomic(&path, b"v2").unwrap();
+        assert_eq!(mode(&path), 0o640);
+        assert_eq!(fs::read(&path).unwrap(), b"v2");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
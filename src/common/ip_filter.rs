@@ -0,0 +1,175 @@
+//! 客户端 IP 访问控制
+//!
+//! 主 API 与 Admin API 分别配置 CIDR 允许列表与拒绝列表（`apiIpFilter` / `adminIpFilter`）。
+//! 拒绝列表优先；允许列表非空时只放行命中的地址。被拒绝的请求在认证之前返回 403，
+//! 并写入请求审计日志（如果配置了）。
+//!
+//! 客户端地址取自 TCP 连接的对端地址，不信任 `X-Forwarded-For`。
+//! 通过 Unix socket 接入或嵌入时未提供连接信息的请求地址未知，允许列表非空时一律拒绝
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, OriginalUri, State, connect_info::Connected},
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+    serve::IncomingStream,
+};
+use ipnet::IpNet;
+use serde_json::json;
+
+use crate::audit::AuditLog;
+use crate::model::config::IpFilterConfig;
+
+/// 客户端地址（由 `into_make_service_with_connect_info::<ClientAddr>()` 写入请求扩展）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClientAddr(pub Option<IpAddr>);
+
+impl Connected<IncomingStream<'_, tokio::net::TcpListener>> for ClientAddr {
+    fn connect_info(stream: IncomingStream<'_, tokio::net::TcpListener>) -> Self {
+        Self(Some(stream.remote_addr().ip().to_canonical()))
+    }
+}
+
+#[cfg(unix)]
+impl Connected<IncomingStream<'_, tokio::net::UnixListener>> for ClientAddr {
+    fn connect_info(_: IncomingStream<'_, tokio::net::UnixListener>) -> Self {
+        Self(None)
+    }
+}
+
+/// HTTPS 监听（axum-server）提供的对端地址
+impl Connected<SocketAddr> for ClientAddr {
+    fn connect_info(addr: SocketAddr) -> Self {
+        Self(Some(addr.ip().to_canonical()))
+    }
+}
+
+/// CIDR 允许 / 拒绝列表
+#[derive(Debug, Clone)]
+pub struct IpFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl IpFilter {
+    /// 解析配置（单个 IP 视为 /32 或 /128）
+    pub fn new(config: &IpFilterConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            allow: parse_nets(&config.allow)?,
+            deny: parse_nets(&config.deny)?,
+        })
+    }
+
+    /// 检查客户端地址是否放行（地址未知时仅在没有允许列表时放行）
+    pub fn allows(&self, ip: Option<IpAddr>) -> bool {
+        match ip {
+            Some(ip) => {
+                !self.deny.iter().any(|net| net.contains(&ip))
+                    && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip)))
+            }
+            None => self.allow.is_empty(),
+        }
+    }
+}
+
+fn parse_nets(items: &[String]) -> anyhow::Result<Vec<IpNet>> {
+    items
+        .iter()
+        .map(|item| {
+            let item = item.trim();
+            item.parse::<IpNet>()
+                .or_else(|_| item.parse::<IpAddr>().map(IpNet::from))
+                .with_context(|| format!("无效的 IP 或 CIDR: {}", item))
+        })
+        .collect()
+}
+
+/// IP 访问控制中间件状态
+#[derive(Clone)]
+pub struct IpFilterState {
+    pub filter: Arc<IpFilter>,
+    /// 被保护的 API（用于日志，"api" / "admin"）
+    pub scope: &'static str,
+    /// 请求审计日志（可选，记录被拒绝的请求）
+    pub audit: Option<Arc<AuditLog>>,
+}
+
+/// IP 访问控制中间件（位于认证中间件之前）
+pub async fn ip_filter_middleware(
+    State(state): State<IpFilterState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<ClientAddr>>()
+        .and_then(|info| info.0.0);
+    if state.filter.allows(ip) {
+        return next.run(request).await;
+    }
+
+    let endpoint = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path().to_string(),
+        None => request.uri().path().to_string(),
+    };
+    let client = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+    tracing::warn!("拒绝来自 {} 的 {} 请求: {}", client, state.scope, endpoint);
+    if let Some(audit) = &state.audit {
+        audit.record_rejection(&endpoint, ip, StatusCode::FORBIDDEN.as_u16());
+    }
+
+    let body = json!({
+        "error": {
+            "type": "permission_error",
+            "message": format!("Client IP {} is not allowed", client),
+        }
+    });
+    (StatusCode::FORBIDDEN, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(allow: &[&str], deny: &[&str]) -> IpFilter {
+        IpFilter::new(&IpFilterConfig {
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            deny: deny.iter().map(|s| s.to_string()).collect(),
+        })
+        .unwrap()
+    }
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_allow_and_deny() {
+        let admin = filter(&["127.0.0.1", "10.8.0.0/16", "::1"], &["10.8.0.66/32"]);
+        assert!(admin.allows(ip("127.0.0.1")));
+        assert!(admin.allows(ip("10.8.3.4")));
+        assert!(admin.allows(ip("::1")));
+        assert!(!admin.allows(ip("10.8.0.66")));
+        assert!(!admin.allows(ip("192.168.1.2")));
+        assert!(!admin.allows(None));
+
+        // 只有拒绝列表时其余地址（包括未知地址）放行
+        let api = filter(&[], &["203.0.113.0/24"]);
+        assert!(!api.allows(ip("203.0.113.9")));
+        assert!(api.allows(ip("198.51.100.1")));
+        assert!(api.allows(None));
+
+        assert!(
+            IpFilter::new(&IpFilterConfig {
+                allow: vec!["10.0.0.0/33".to_string()],
+                deny: vec![],
+            })
+            .is_err()
+        );
+    }
+}
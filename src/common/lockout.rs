@@ -0,0 +1,149 @@
+//! API Key 暴力破解防护
+//!
+//! 按客户端 IP 统计认证失败次数：窗口期内失败达到上限后锁定该 IP，
+//! 锁定期间该 IP 的所有请求直接返回 429（不再校验 Key），锁定时写入一条安全事件到审计日志。
+//! 认证成功会清空该 IP 的失败记录；地址未知（Unix socket）的请求不参与统计
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::model::config::AuthLockoutConfig;
+
+/// 跟踪的 IP 数超过该值时清理过期记录
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// 单个 IP 的失败记录
+#[derive(Debug)]
+struct Entry {
+    /// 当前窗口的开始时间
+    window_start: Instant,
+    /// 窗口内的失败次数
+    failures: u32,
+    /// 锁定截止时间
+    locked_until: Option<Instant>,
+}
+
+/// 认证失败锁定器
+pub struct AuthLockout {
+    max_failures: u32,
+    window: Duration,
+    lockout: Duration,
+    entries: Mutex<HashMap<IpAddr, Entry>>,
+}
+
+impl AuthLockout {
+    pub fn new(config: &AuthLockoutConfig) -> Self {
+        Self {
+            max_failures: config.max_failures.max(1),
+            window: Duration::from_secs(config.window_secs.max(1)),
+            lockout: Duration::from_secs(config.lockout_secs.max(1)),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 该 IP 仍处于锁定期时返回剩余秒数
+    pub fn locked(&self, ip: IpAddr) -> Option<u64> {
+        self.locked_at(ip, Instant::now())
+    }
+
+    fn locked_at(&self, ip: IpAddr, now: Instant) -> Option<u64> {
+        let entries = self.entries.lock();
+        let until = entries.get(&ip)?.locked_until?;
+        (until > now).then(|| (until - now).as_secs().max(1))
+    }
+
+    /// 记录一次认证失败，本次失败触发锁定时返回 true
+    pub fn record_failure(&self, ip: IpAddr) -> bool {
+        self.record_failure_at(ip, Instant::now())
+    }
+
+    fn record_failure_at(&self, ip: IpAddr, now: Instant) -> bool {
+        let mut entries = self.entries.lock();
+        if entries.len() >= PRUNE_THRESHOLD {
+            entries.retain(|_, entry| !self.expired(entry, now));
+        }
+
+        let entry = entries.entry(ip).or_insert(Entry {
+            window_start: now,
+            failures: 0,
+            locked_until: None,
+        });
+        if self.expired(entry, now) {
+            *entry = Entry {
+                window_start: now,
+                failures: 0,
+                locked_until: None,
+            };
+        }
+        if entry.locked_until.is_some() {
+            return false;
+        }
+
+        entry.failures += 1;
+        if entry.failures < self.max_failures {
+            return false;
+        }
+        entry.locked_until = Some(now + self.lockout);
+        true
+    }
+
+    /// 认证成功后清空该 IP 的失败记录
+    pub fn record_success(&self, ip: IpAddr) {
+        let mut entries = self.entries.lock();
+        if !entries.is_empty() {
+            entries.remove(&ip);
+        }
+    }
+
+    /// 锁定已结束，或未锁定且窗口已过期
+    fn expired(&self, entry: &Entry, now: Instant) -> bool {
+        match entry.locked_until {
+            Some(until) => until <= now,
+            None => now.duration_since(entry.window_start) >= self.window,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_after_max_failures() {
+        let lockout = AuthLockout::new(&AuthLockoutConfig {
+            max_failures: 3,
+            window_secs: 60,
+            lockout_secs: 300,
+        });
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let other: IpAddr = "203.0.113.8".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(!lockout.record_failure_at(ip, start));
+        assert!(!lockout.record_failure_at(ip, start));
+        assert!(lockout.record_failure_at(ip, start + Duration::from_secs(10)));
+        assert_eq!(
+            lockout.locked_at(ip, start + Duration::from_secs(10)),
+            Some(300)
+        );
+        assert!(lockout.locked_at(other, start).is_none());
+
+        // 锁定结束后重新计数
+        let later = start + Duration::from_secs(311);
+        assert!(lockout.locked_at(ip, later).is_none());
+        assert!(!lockout.record_failure_at(ip, later));
+
+        // 窗口过期的失败不累计
+        assert!(!lockout.record_failure_at(other, start));
+        assert!(!lockout.record_failure_at(other, start + Duration::from_secs(1)));
+        assert!(!lockout.record_failure_at(other, start + Duration::from_secs(61)));
+
+        // 认证成功清空失败记录
+        lockout.record_success(other);
+        assert!(!lockout.record_failure_at(other, start + Duration::from_secs(62)));
+        assert!(!lockout.record_failure_at(other, start + Duration::from_secs(63)));
+    }
+}
@@ -0,0 +1,85 @@
+//! 最近日志缓冲
+//!
+//! 日志除输出到终端外，还以纯文本保存在内存环形缓冲中（仅保留最近若干行），
+//! 诊断包据此附带最近日志，提交问题时无需翻找终端或容器日志
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::LazyLock;
+
+use parking_lot::Mutex;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// 最多保留的日志行数
+const MAX_LINES: usize = 2000;
+
+/// 最近日志的环形缓冲
+#[derive(Debug, Default)]
+pub struct LogBuffer {
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl LogBuffer {
+    fn push(&self, text: &str) {
+        let mut lines = self.lines.lock();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            if lines.len() >= MAX_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+    }
+
+    /// 最近的日志行（按时间升序）
+    pub fn recent(&self) -> Vec<String> {
+        self.lines.lock().iter().cloned().collect()
+    }
+}
+
+/// 写入日志缓冲的 writer（fmt 层每个事件整体写入一次）
+pub struct LogBufferWriter(&'static LogBuffer);
+
+impl io::Write for LogBufferWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.push(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for &'static LogBuffer {
+    type Writer = LogBufferWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogBufferWriter(self)
+    }
+}
+
+/// 全局日志缓冲（tracing 订阅器与诊断包共用）
+pub fn buffer() -> &'static LogBuffer {
+    static BUFFER: LazyLock<LogBuffer> = LazyLock::new(LogBuffer::default);
+    &BUFFER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_most_recent_lines() {
+        let buffer = LogBuffer::default();
+        buffer.push("first\n\nsecond\n");
+        assert_eq!(buffer.recent(), ["first", "second"]);
+
+        for i in 0..MAX_LINES {
+            buffer.push(&format!("line {}\n", i));
+        }
+        let recent = buffer.recent();
+        assert_eq!(recent.len(), MAX_LINES);
+        assert_eq!(recent[0], "line 0");
+        assert_eq!(recent[MAX_LINES - 1], format!("line {}", MAX_LINES - 1));
+    }
+}
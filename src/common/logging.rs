@@ -0,0 +1,106 @@
+//! 日志初始化
+//!
+//! 日志在加载配置之前就需要可用，因此先以文本格式初始化，
+//! 读取配置后再按 `logFormat` 切换终端输出格式。最近日志缓冲始终使用文本格式。
+//! 日志级别过滤器可在运行时替换（Admin API `PUT /log-level`），排查线上问题无需修改 `RUST_LOG` 重启
+
+use std::sync::OnceLock;
+
+use tracing_subscriber::layer::Layered;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, Layer, Registry, fmt, reload};
+
+use super::log_buffer;
+use crate::model::config::LogFormat;
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+type FilterSubscriber = Layered<reload::Layer<BoxedLayer, Registry>, Registry>;
+
+/// 运行时日志级别过滤器（`init` 之后可用）
+static FILTER: OnceLock<FilterState> = OnceLock::new();
+
+struct FilterState {
+    handle: reload::Handle<EnvFilter, FilterSubscriber>,
+    /// 启动时的过滤规则（重置时恢复）
+    initial: String,
+}
+
+/// 切换终端日志格式的句柄
+pub struct LoggingHandle {
+    stdout: reload::Handle<BoxedLayer, Registry>,
+}
+
+impl LoggingHandle {
+    /// 切换终端日志格式
+    pub fn set_format(&self, format: LogFormat) {
+        if format == LogFormat::Text {
+            return;
+        }
+        if let Err(e) = self.stdout.reload(stdout_layer(format)) {
+            tracing::warn!("切换日志格式失败: {}", e);
+        }
+    }
+}
+
+/// 初始化全局日志（终端输出 + 最近日志缓冲），日志级别取自 `RUST_LOG`（默认 info）
+pub fn init() -> LoggingHandle {
+    let (stdout, handle) = reload::Layer::new(stdout_layer(LogFormat::Text));
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let initial = filter.to_string();
+    let (filter, filter_handle) = reload::Layer::new(filter);
+    tracing_subscriber::registry()
+        .with(stdout)
+        .with(filter)
+        .with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(log_buffer::buffer()),
+        )
+        .init();
+    FILTER
+        .set(FilterState {
+            handle: filter_handle,
+            initial,
+        })
+        .ok();
+    LoggingHandle { stdout: handle }
+}
+
+/// 当前的日志过滤规则（日志未经 `init` 初始化时返回 None，如作为库嵌入时）
+pub fn current_filter() -> Option<String> {
+    FILTER
+        .get()?
+        .handle
+        .with_current(|filter| filter.to_string())
+        .ok()
+}
+
+/// 替换日志过滤规则（语法同 `RUST_LOG`，如 `info,kiro_rs::kiro::parser=trace`），为空时恢复启动时的规则
+///
+/// 返回生效后的规则
+pub fn set_filter(directives: &str) -> anyhow::Result<String> {
+    let state = FILTER
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("日志未由 kiro-rs 初始化，无法调整日志级别"))?;
+    let directives = match directives.trim() {
+        "" => state.initial.as_str(),
+        directives => directives,
+    };
+    let filter = EnvFilter::try_new(directives)?;
+    let applied = filter.to_string();
+    state.handle.reload(filter)?;
+    Ok(applied)
+}
+
+fn stdout_layer(format: LogFormat) -> BoxedLayer {
+    match format {
+        LogFormat::Text => fmt::layer().boxed(),
+        // 事件字段展开到顶层，所在 span 的字段（如 request_id）随 span 一起输出
+        LogFormat::Json => fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    }
+}
@@ -1,3 +1,10 @@
 //! 公共工具模块
 
+pub mod atomic_file;
 pub mod auth;
+pub mod ip_filter;
+pub mod lockout;
+pub mod log_buffer;
+pub mod logging;
+pub mod request_id;
+pub mod time;
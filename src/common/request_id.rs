@@ -0,0 +1,77 @@
+//! 请求 ID
+//!
+//! 每个进入的请求分配一个 ID（客户端通过 `X-Request-Id` 传入合法值时沿用），
+//! 处理期间的日志都带有该 ID，响应头与发往上游的请求头中同样携带，便于端到端关联日志
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::Instrument;
+
+/// 请求 ID 头
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// 客户端传入的请求 ID 最大长度
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// 当前请求的 ID（不在请求处理中时返回 None）
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// 请求 ID 中间件
+///
+/// 在名为 `request` 的 span（字段 `request_id`）中处理请求，并在响应头中返回请求 ID
+pub async fn request_id_middleware(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    let span = tracing::info_span!("request", request_id = %id);
+    let mut response = REQUEST_ID
+        .scope(id.clone(), next.run(request))
+        .instrument(span)
+        .await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// 客户端传入的请求 ID 是否可以沿用（限制长度与字符，避免日志注入）
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_id_scope_and_validation() {
+        assert_eq!(current(), None);
+        let id = REQUEST_ID
+            .scope("abc-123".to_string(), async { current() })
+            .await;
+        assert_eq!(id.as_deref(), Some("abc-123"));
+
+        assert!(is_valid("0f8e2c1a-trace:1.2_x"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("a b"));
+        assert!(!is_valid("id\nforged"));
+        assert!(!is_valid(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+}
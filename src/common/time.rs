@@ -0,0 +1,121 @@
+//! 时区与时间展示工具函数
+
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use serde::Serialize;
+
+/// 解析固定 UTC 偏移（运营者时区）
+///
+/// 支持 `UTC` / `Z`、`+08:00`、`+0800`、`+8`、`UTC+8`、`GMT-05:30` 等写法，
+/// 偏移需在 ±14 小时以内；无法解析时返回 None
+pub fn parse_utc_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    let upper = value.to_ascii_uppercase();
+    let rest = upper
+        .strip_prefix("UTC")
+        .or_else(|| upper.strip_prefix("GMT"))
+        .unwrap_or(&upper);
+    if rest.is_empty() || rest == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let (sign, digits) = match rest.as_bytes()[0] {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match digits.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+    if hours.is_empty() || hours.len() > 2 || minutes.len() > 2 {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// 同时以 UTC 和运营者时区表示的时间点
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZonedTime {
+    /// Unix 时间戳（秒）
+    pub timestamp: i64,
+    /// UTC 时间（RFC3339）
+    pub utc: String,
+    /// 运营者时区的本地时间（RFC3339，带偏移）
+    pub local: String,
+    /// 运营者时区（UTC 偏移，如 `+08:00`）
+    pub timezone: String,
+}
+
+impl ZonedTime {
+    pub fn new(time: DateTime<Utc>, offset: FixedOffset) -> Self {
+        Self {
+            timestamp: time.timestamp(),
+            utc: time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            local: time
+                .with_timezone(&offset)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            timezone: offset.to_string(),
+        }
+    }
+
+    /// 从上游返回的 Unix 时间戳（秒，可带小数）构建
+    ///
+    /// 兼容以毫秒表示的时间戳
+    pub fn from_timestamp(timestamp: f64, offset: FixedOffset) -> Option<Self> {
+        timestamp_to_utc(timestamp).map(|t| Self::new(t, offset))
+    }
+}
+
+/// 将上游 Unix 时间戳（秒，可带小数；超过 1e11 视为毫秒）转换为 UTC 时间
+pub fn timestamp_to_utc(timestamp: f64) -> Option<DateTime<Utc>> {
+    if !timestamp.is_finite() || timestamp <= 0.0 {
+        return None;
+    }
+    let millis = if timestamp > 1e11 {
+        timestamp
+    } else {
+        timestamp * 1000.0
+    };
+    DateTime::from_timestamp_millis(millis as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_utc_offset_formats() {
+        let east8 = FixedOffset::east_opt(8 * 3600);
+        assert_eq!(parse_utc_offset("+08:00"), east8);
+        assert_eq!(parse_utc_offset("+0800"), east8);
+        assert_eq!(parse_utc_offset("utc+8"), east8);
+        assert_eq!(parse_utc_offset("GMT-05:30"), FixedOffset::west_opt(5 * 3600 + 1800));
+        assert_eq!(parse_utc_offset("UTC"), FixedOffset::east_opt(0));
+        assert_eq!(parse_utc_offset("Z"), FixedOffset::east_opt(0));
+
+        assert!(parse_utc_offset("Asia/Shanghai").is_none());
+        assert!(parse_utc_offset("+15").is_none());
+        assert!(parse_utc_offset("+08:60").is_none());
+    }
+
+    #[test]
+    fn test_zoned_time_from_timestamp() {
+        let offset = FixedOffset::east_opt(8 * 3600).unwrap();
+        let zoned = ZonedTime::from_timestamp(1_767_225_600.0, offset).unwrap();
+        assert_eq!(zoned.utc, "2026-01-01T00:00:00Z");
+        assert_eq!(zoned.local, "2026-01-01T08:00:00+08:00");
+        assert_eq!(zoned.timezone, "+08:00");
+
+        // 毫秒时间戳
+        let millis = ZonedTime::from_timestamp(1_767_225_600_000.0, offset).unwrap();
+        assert_eq!(millis, zoned);
+        assert!(ZonedTime::from_timestamp(0.0, offset).is_none());
+    }
+}
@@ -0,0 +1,71 @@
+//! 外部凭证来源
+//!
+//! [`CredentialProvider`] 抽象"从外部拉取凭证并注入凭据池"的来源：Cloud Pass 是其中一种，
+//! 提供凭证 JSON 的 HTTP 端点、对象存储、密钥管理服务等都可以实现同一 trait。
+//! 来源只负责拉取与心跳，定时刷新、失败退避、注入、旧凭据清理与状态记录由 [`worker`] 统一完成
+
+pub mod worker;
+
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::kiro::model::credentials::{CredentialId, KiroCredentials};
+
+/// 一次拉取得到的凭证
+#[derive(Debug, Clone)]
+pub struct ProvidedCredentials {
+    /// 待注入凭据池的凭据（`id` 为空，注入时分配）
+    pub credentials: KiroCredentials,
+    /// 来源侧的有效期（如 Cloud Pass License 到期时间），仅用于状态展示
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+/// 凭证来源
+pub trait CredentialProvider: Send + Sync + 'static {
+    /// 运行时状态
+    type Status: ProviderStatus;
+
+    /// 日志中显示的名称（需脱敏）
+    fn name(&self) -> &str;
+
+    /// 拉取当前凭证；凭证未变化时返回与上次相同的凭据即可，注入时按 refreshToken 去重
+    fn fetch(&self) -> impl Future<Output = anyhow::Result<ProvidedCredentials>> + Send;
+
+    /// 心跳保活，不需要心跳的来源使用默认实现
+    fn heartbeat(&self) -> impl Future<Output = anyhow::Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// 运行时状态（由刷新与心跳循环写入）
+    fn status(&self) -> &Self::Status;
+}
+
+/// 凭证来源的运行时状态记录
+pub trait ProviderStatus: Send + Sync {
+    /// 记录拉取并注入成功（`credential_id` 为空表示凭证未变化）
+    fn record_success(
+        &self,
+        credential_id: Option<CredentialId>,
+        valid_until: Option<DateTime<Utc>>,
+    );
+
+    /// 记录刷新失败，返回连续失败次数
+    fn record_failure(&self, error: &str) -> u32;
+
+    /// 记录下次刷新计划（`backoff` 为失败重试的退避时长）
+    fn record_schedule(&self, delay: Duration, backoff: bool);
+
+    /// 记录心跳结果
+    fn record_heartbeat(&self, error: Option<String>);
+
+    /// 当前注入的凭据 ID
+    fn injected_credential_id(&self) -> Option<CredentialId>;
+
+    /// 是否有已被替换、尚未清理的旧凭据
+    fn has_superseded_credentials(&self) -> bool;
+
+    /// 取出全部待清理的旧凭据 ID（取出后清空）
+    fn take_superseded_credentials(&self) -> Vec<CredentialId>;
+}
@@ -0,0 +1,224 @@
+//! 凭证来源的通用刷新与心跳循环
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+use crate::kiro::model::credentials::{CredentialId, KiroCredentials};
+use crate::kiro::token_manager::MultiTokenManager;
+use crate::model::config::StaleCredentialAction;
+
+use super::{CredentialProvider, ProviderStatus};
+
+/// 网络错误（连接失败、超时）的首次重试间隔，之后每次翻倍，不超过刷新间隔
+const TRANSIENT_RETRY_BASE: Duration = Duration::from_secs(15);
+/// 其他失败的最长退避时长
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(4 * 3600);
+/// 退避时长的随机抖动比例（±）
+const RETRY_JITTER: f64 = 0.2;
+
+/// 刷新循环参数
+#[derive(Clone)]
+pub struct RefreshOptions {
+    /// 正常刷新间隔
+    pub interval: Duration,
+    /// 被替换的旧凭据如何处理
+    pub stale_action: StaleCredentialAction,
+    /// 手动刷新通知器（收到通知后立即开始下一轮）
+    pub refresh_notify: Arc<Notify>,
+}
+
+/// 刷新循环：拉取凭证并注入凭据池，失败后按指数退避重试
+pub async fn run_refresh_loop<P: CredentialProvider>(
+    provider: Arc<P>,
+    token_manager: Arc<MultiTokenManager>,
+    options: RefreshOptions,
+) {
+    loop {
+        let (delay, backoff) =
+            match refresh_once(provider.as_ref(), &token_manager, options.stale_action).await {
+                Ok(()) => {
+                    tracing::info!("{} 凭证刷新成功", provider.name());
+                    (options.interval, false)
+                }
+                Err(e) => {
+                    let failures = provider.status().record_failure(&e.to_string());
+                    let delay =
+                        with_jitter(retry_delay(failures, is_transient(&e), options.interval));
+                    tracing::error!(
+                        "{} 凭证刷新失败（连续 {} 次），{}s 后重试: {}",
+                        provider.name(),
+                        failures,
+                        delay.as_secs(),
+                        e
+                    );
+                    (delay, true)
+                }
+            };
+        provider.status().record_schedule(delay, backoff);
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {},
+            _ = options.refresh_notify.notified() => {
+                tracing::info!("{} 收到手动刷新请求", provider.name());
+            },
+        }
+    }
+}
+
+/// 心跳循环（独立于刷新循环，失败不影响刷新）
+pub async fn run_heartbeat_loop<P: CredentialProvider>(provider: Arc<P>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        match provider.heartbeat().await {
+            Ok(()) => provider.status().record_heartbeat(None),
+            Err(e) => {
+                tracing::warn!("{} 心跳失败: {}", provider.name(), e);
+                provider.status().record_heartbeat(Some(e.to_string()));
+            }
+        }
+    }
+}
+
+/// 执行一次拉取与注入
+async fn refresh_once<P: CredentialProvider>(
+    provider: &P,
+    token_manager: &MultiTokenManager,
+    stale_action: StaleCredentialAction,
+) -> anyhow::Result<()> {
+    let provided = provider.fetch().await?;
+    let status = provider.status();
+    let injected = inject_credentials(provider.name(), token_manager, provided.credentials).await?;
+    status.record_success(injected, provided.valid_until);
+
+    // 新注入的凭据，或上次注入后未通过验证、旧凭据仍待清理的当前凭据
+    let to_validate = injected.or_else(|| {
+        status
+            .has_superseded_credentials()
+            .then(|| status.injected_credential_id())
+            .flatten()
+    });
+    let Some(current) = to_validate else {
+        return Ok(());
+    };
+    // 主动获取订阅等级，同时验证凭据可用
+    match token_manager.get_usage_limits_for(current).await {
+        Ok(_) => retire_superseded_credentials(provider, token_manager, stale_action),
+        Err(e) => tracing::warn!("获取订阅等级失败（不影响使用）: {}", e),
+    }
+    Ok(())
+}
+
+/// 将凭证注入到 token_manager（与 Admin API 相同路径），凭证未变化时返回 `None`
+async fn inject_credentials(
+    name: &str,
+    token_manager: &MultiTokenManager,
+    credentials: KiroCredentials,
+) -> anyhow::Result<Option<CredentialId>> {
+    match token_manager.add_credential(credentials).await {
+        Ok(id) => {
+            tracing::info!("{} 凭证已注入，ID: {}", name, id);
+            Ok(Some(id))
+        }
+        Err(e) => {
+            let err_msg = e.to_string();
+            // refreshToken 重复 = 凭证没变，不需要注入
+            if err_msg.contains("重复") || err_msg.contains("duplicate") {
+                tracing::info!("{} 凭证未变化，跳过注入", name);
+                Ok(None)
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
+/// 新凭据验证通过后，按 `stale_action` 清理所有被替换的旧凭据
+fn retire_superseded_credentials<P: CredentialProvider>(
+    provider: &P,
+    token_manager: &MultiTokenManager,
+    stale_action: StaleCredentialAction,
+) {
+    for stale in provider.status().take_superseded_credentials() {
+        retire_credential(provider.name(), token_manager, stale, stale_action);
+    }
+}
+
+fn retire_credential(
+    name: &str,
+    token_manager: &MultiTokenManager,
+    stale: CredentialId,
+    stale_action: StaleCredentialAction,
+) {
+    let result = match stale_action {
+        StaleCredentialAction::Keep => {
+            tracing::info!("{} 的旧凭据 #{} 已被替换，保留", name, stale);
+            return;
+        }
+        StaleCredentialAction::Disable => token_manager.set_disabled(stale, true),
+        StaleCredentialAction::Delete => token_manager
+            .set_disabled(stale, true)
+            .and_then(|_| token_manager.delete_credential(stale)),
+    };
+    match result {
+        Ok(()) => tracing::info!(
+            "{} 的旧凭据 #{} 已被替换，已{}",
+            name,
+            stale,
+            if stale_action == StaleCredentialAction::Delete {
+                "删除"
+            } else {
+                "禁用"
+            }
+        ),
+        Err(e) => tracing::warn!("{} 清理旧凭据 #{} 失败: {}", name, stale, e),
+    }
+}
+
+/// 计算第 `failures` 次连续失败后的重试间隔（不含抖动）
+///
+/// 网络错误按 15s 起步翻倍、以刷新间隔封顶，尽快从短暂的网络故障中恢复；
+/// 服务端错误（拒绝、解密失败、被踢出等）从刷新间隔起步翻倍，避免服务端故障期间持续请求
+fn retry_delay(failures: u32, transient: bool, interval: Duration) -> Duration {
+    let exponent = failures.saturating_sub(1).min(16);
+    if transient {
+        (TRANSIENT_RETRY_BASE * 2u32.pow(exponent)).min(interval)
+    } else {
+        interval
+            .saturating_mul(2u32.pow(exponent))
+            .min(MAX_RETRY_BACKOFF.max(interval))
+    }
+}
+
+/// 加入 ±20% 的随机抖动，避免多个实例或来源同时重试
+fn with_jitter(delay: Duration) -> Duration {
+    delay.mul_f64(1.0 + RETRY_JITTER * (fastrand::f64() * 2.0 - 1.0))
+}
+
+/// 是否为连接失败、超时等网络层的暂时性错误
+fn is_transient(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| e.is_connect() || e.is_timeout())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_backoff_schedules() {
+        let interval = Duration::from_secs(900);
+        assert_eq!(retry_delay(1, true, interval), Duration::from_secs(15));
+        assert_eq!(retry_delay(3, true, interval), Duration::from_secs(60));
+        assert_eq!(retry_delay(10, true, interval), interval);
+
+        assert_eq!(retry_delay(1, false, interval), interval);
+        assert_eq!(retry_delay(2, false, interval), Duration::from_secs(1800));
+        assert_eq!(retry_delay(40, false, interval), MAX_RETRY_BACKOFF);
+
+        let jittered = with_jitter(Duration::from_secs(100));
+        assert!(jittered >= Duration::from_secs(80) && jittered <= Duration::from_secs(120));
+    }
+}
@@ -0,0 +1,202 @@
+//! 开发模式控制台输出（`--dev`）
+//!
+//! 将每个请求转换后的上游请求体格式化打印，并把解码出的上游事件流逐条压缩为一行输出，
+//! 编写新的协议转换时无需翻阅 debug 日志即可对照输入与输出。
+//! 输出写到 stderr，终端下带颜色；超长的字符串值按 `--dev-max-chars` 截断
+
+use std::fmt::Write as _;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+use serde_json::Value;
+
+use crate::kiro::model::events::Event;
+
+/// 开发模式设置（未启用时为空）
+static DEV: OnceLock<DevPrinter> = OnceLock::new();
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+
+struct DevPrinter {
+    max_chars: usize,
+    color: bool,
+}
+
+impl DevPrinter {
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// 超过上限的字符串截断并标注省略的字符数
+    fn truncate(&self, text: &str) -> String {
+        match text.char_indices().nth(self.max_chars) {
+            Some((idx, _)) => {
+                let omitted = text[idx..].chars().count();
+                format!("{}…(+{} chars)", &text[..idx], omitted)
+            }
+            None => text.to_string(),
+        }
+    }
+
+    fn write_json(&self, out: &mut String, value: &Value, indent: usize) {
+        let pad = "  ".repeat(indent + 1);
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                out.push_str("{\n");
+                for (i, (key, value)) in map.iter().enumerate() {
+                    let key = self.paint(CYAN, &format!("{:?}", key));
+                    let _ = write!(out, "{}{}: ", pad, key);
+                    self.write_json(out, value, indent + 1);
+                    out.push_str(if i + 1 < map.len() { ",\n" } else { "\n" });
+                }
+                let _ = write!(out, "{}}}", "  ".repeat(indent));
+            }
+            Value::Array(items) if !items.is_empty() => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    out.push_str(&pad);
+                    self.write_json(out, item, indent + 1);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                let _ = write!(out, "{}]", "  ".repeat(indent));
+            }
+            Value::String(s) => {
+                out.push_str(&self.paint(GREEN, &format!("{:?}", self.truncate(s))));
+            }
+            Value::Number(n) => out.push_str(&self.paint(YELLOW, &n.to_string())),
+            Value::Bool(_) | Value::Null => out.push_str(&self.paint(MAGENTA, &value.to_string())),
+            _ => out.push_str(&value.to_string()),
+        }
+    }
+
+    fn format_request(&self, label: &str, body: &str) -> String {
+        let mut out = self.paint(BOLD, &format!("━━ ▶ {} ({} bytes) ━━", label, body.len()));
+        out.push('\n');
+        match serde_json::from_str::<Value>(body) {
+            Ok(value) => self.write_json(&mut out, &value, 0),
+            Err(_) => out.push_str(&self.truncate(body)),
+        }
+        out
+    }
+
+    fn format_event(&self, event: &Event) -> String {
+        let (tag, color, detail) = match event {
+            Event::AssistantResponse(e) => {
+                ("text", GREEN, format!("{:?}", self.truncate(&e.content)))
+            }
+            Event::ToolUse(e) => (
+                "tool_use",
+                CYAN,
+                format!(
+                    "{} id={}{} {}",
+                    e.name,
+                    e.tool_use_id,
+                    if e.stop { " stop" } else { "" },
+                    self.truncate(&e.input)
+                ),
+            ),
+            Event::ContextUsage(e) => (
+                "context",
+                YELLOW,
+                format!("{}%", e.context_usage_percentage),
+            ),
+            Event::ReasoningContent(e) => (
+                "reasoning",
+                DIM,
+                format!("{:?}", self.truncate(&e.text)),
+            ),
+            Event::Metering(()) => ("metering", DIM, String::new()),
+            Event::Unknown {} => ("unknown", DIM, String::new()),
+            Event::Error {
+                error_code,
+                error_message,
+            } => (
+                "error",
+                RED,
+                format!("{}: {}", error_code, self.truncate(error_message)),
+            ),
+            Event::Exception {
+                exception_type,
+                message,
+            } => (
+                "exception",
+                RED,
+                format!("{}: {}", exception_type, self.truncate(message)),
+            ),
+        };
+        format!(
+            "  ◀ {} {}",
+            self.paint(color, &format!("{:<9}", tag)),
+            detail
+        )
+    }
+}
+
+/// 启用开发模式输出
+pub fn enable(max_chars: usize) {
+    let _ = DEV.set(DevPrinter {
+        max_chars: max_chars.max(1),
+        color: std::io::stderr().is_terminal(),
+    });
+}
+
+/// 格式化打印转换后的上游请求体
+pub fn print_request(label: &str, body: &str) {
+    if let Some(dev) = DEV.get() {
+        eprintln!("{}", dev.format_request(label, body));
+    }
+}
+
+/// 以一行压缩形式打印解码出的上游事件
+pub fn print_event(event: &Event) {
+    if let Some(dev) = DEV.get() {
+        eprintln!("{}", dev.format_event(event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_pretty_print_truncates_strings() {
+        let dev = DevPrinter {
+            max_chars: 5,
+            color: false,
+        };
+        let out = dev.format_request("kiro", r#"{"a":"hello world","b":[1,true],"c":{}}"#);
+        assert_eq!(
+            out.lines().skip(1).collect::<Vec<_>>(),
+            [
+                "{",
+                r#"  "a": "hello…(+6 chars)","#,
+                r#"  "b": ["#,
+                "    1,",
+                "    true",
+                "  ],",
+                r#"  "c": {}"#,
+                "}",
+            ]
+        );
+
+        let event = Event::Exception {
+            exception_type: "ThrottlingException".to_string(),
+            message: "slow down please".to_string(),
+        };
+        assert_eq!(
+            dev.format_event(&event),
+            "  ◀ exception ThrottlingException: slow …(+11 chars)"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! `doctor` 子命令：部署自检
+//!
+//! 依次检查配置、凭证文件、监听与 HTTPS 设置、网络与代理、每个凭据的 Token 刷新与上游连通性、
+//! Cloud Pass 服务器，输出逐项的通过 / 警告 / 失败报告。前置检查失败时跳过依赖它的后续检查
+
+use std::fmt;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::common::ip_filter::IpFilter;
+use crate::http_client::build_client;
+use crate::kiro::model::credentials::CredentialsConfig;
+use crate::kiro::token_manager::MultiTokenManager;
+use crate::listen::ListenAddr;
+use crate::model::config::Config;
+use crate::server_tls::TlsPaths;
+
+/// 网络检查的超时时间（秒）
+const NETWORK_TIMEOUT_SECS: u64 = 15;
+
+/// 检查结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+    Skip,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Self::Pass => "PASS",
+            Self::Warn => "WARN",
+            Self::Fail => "FAIL",
+            Self::Skip => "SKIP",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// 单项检查
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// 自检报告
+#[derive(Debug, Default, Serialize)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    fn push(&mut self, name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        });
+    }
+
+    fn pass(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.push(name, CheckStatus::Pass, detail);
+    }
+
+    fn warn(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.push(name, CheckStatus::Warn, detail);
+    }
+
+    fn fail(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.push(name, CheckStatus::Fail, detail);
+    }
+
+    fn skip(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.push(name, CheckStatus::Skip, detail);
+    }
+
+    fn count(&self, status: CheckStatus) -> usize {
+        self.checks.iter().filter(|c| c.status == status).count()
+    }
+
+    /// 是否有失败项
+    pub fn has_failures(&self) -> bool {
+        self.count(CheckStatus::Fail) > 0
+    }
+
+    /// 文本报告（每项一行，末尾为汇总）
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for check in &self.checks {
+            out.push_str(&format!(
+                "[{}] {}: {}\n",
+                check.status, check.name, check.detail
+            ));
+        }
+        out.push_str(&format!(
+            "共 {} 项：通过 {}，警告 {}，失败 {}，跳过 {}\n",
+            self.checks.len(),
+            self.count(CheckStatus::Pass),
+            self.count(CheckStatus::Warn),
+            self.count(CheckStatus::Fail),
+            self.count(CheckStatus::Skip)
+        ));
+        out
+    }
+}
+
+/// 执行全部检查
+pub async fn run(config_path: &str, credentials_path: &str) -> DoctorReport {
+    let mut report = DoctorReport::default();
+
+    let config = match Config::load(config_path) {
+        Ok(config) if Path::new(config_path).exists() => {
+            report.pass("配置文件", config_path);
+            config
+        }
+        Ok(config) => {
+            report.warn("配置文件", format!("{} 不存在，使用默认配置", config_path));
+            config
+        }
+        Err(e) => {
+            report.fail("配置文件", format!("{}: {:#}", config_path, e));
+            report.skip("其余检查", "配置文件无法加载");
+            return report;
+        }
+    };
+    check_config(&config, &mut report);
+    check_network(&config, &mut report).await;
+
+    match CredentialsConfig::load(credentials_path) {
+        Ok(credentials) => {
+            let is_multiple_format = credentials.is_multiple();
+            let credentials = credentials.into_sorted_credentials();
+            if credentials.is_empty() {
+                report.warn("凭证文件", format!("{} 中没有凭据", credentials_path));
+            } else {
+                report.pass(
+                    "凭证文件",
+                    format!("{}（{} 个凭据）", credentials_path, credentials.len()),
+                );
+            }
+            match MultiTokenManager::new(
+                config.clone(),
+                credentials,
+                config.proxy_config(),
+                Some(credentials_path.into()),
+                is_multiple_format,
+            ) {
+                Ok(token_manager) => check_credentials(&token_manager, &mut report).await,
+                Err(e) => report.fail("凭据", format!("{:#}", e)),
+            }
+        }
+        Err(e) => {
+            report.fail("凭证文件", format!("{}: {:#}", credentials_path, e));
+            report.skip("凭据", "凭证文件无法加载");
+        }
+    }
+
+    check_cloud_pass(&config, &mut report).await;
+    report
+}
+
+/// 配置项的静态校验（不访问网络）
+fn check_config(config: &Config, report: &mut DoctorReport) {
+    if config
+        .api_key
+        .as_deref()
+        .is_none_or(|k| k.trim().is_empty())
+    {
+        report.warn("apiKey", "未配置，只有托管 API Key 能访问主 API");
+    } else {
+        report.pass("apiKey", "已配置");
+    }
+
+    match ListenAddr::from_config(config) {
+        Ok(addr) => report.pass("监听地址", addr.to_string()),
+        Err(e) => report.fail("监听地址", format!("{:#}", e)),
+    }
+
+    match TlsPaths::from_config(config) {
+        Ok(None) => report.skip("HTTPS 证书", "未配置 tlsCertPath / tlsKeyPath"),
+        Ok(Some(paths)) => match paths.load() {
+            Ok(_) => report.pass("HTTPS 证书", paths.cert.display().to_string()),
+            Err(e) => report.fail("HTTPS 证书", format!("{:#}", e)),
+        },
+        Err(e) => report.fail("HTTPS 证书", format!("{:#}", e)),
+    }
+
+    for (name, filter) in [
+        ("apiIpFilter", &config.api_ip_filter),
+        ("adminIpFilter", &config.admin_ip_filter),
+    ] {
+        match filter.as_ref().map(IpFilter::new) {
+            None => {}
+            Some(Ok(_)) => report.pass(name, "规则有效"),
+            Some(Err(e)) => report.fail(name, format!("{:#}", e)),
+        }
+    }
+}
+
+/// 上游网络（配置了全局代理时经代理访问）
+async fn check_network(config: &Config, report: &mut DoctorReport) {
+    let name = if config.proxy_url.is_some() {
+        "代理"
+    } else {
+        "上游网络"
+    };
+    let url = format!("https://q.{}.amazonaws.com/", config.effective_api_region());
+    let proxy = config.proxy_config();
+    let client = match build_client(proxy.as_ref(), NETWORK_TIMEOUT_SECS, config.tls_backend) {
+        Ok(client) => client,
+        Err(e) => {
+            report.fail(name, format!("创建 HTTP 客户端失败: {:#}", e));
+            return;
+        }
+    };
+    // 任何 HTTP 响应都说明网络可达，状态码不影响结果
+    match client.get(&url).send().await {
+        Ok(response) => report.pass(name, format!("{} 可达（HTTP {}）", url, response.status())),
+        Err(e) => report.fail(name, format!("无法访问 {}: {}", url, e)),
+    }
+}
+
+/// 每个凭据：强制刷新 Token，再查询使用额度验证上游可用
+async fn check_credentials(token_manager: &MultiTokenManager, report: &mut DoctorReport) {
+    for entry in token_manager.snapshot().entries {
+        let name = match &entry.email {
+            Some(email) => format!("凭据 #{}（{}）", entry.id, email),
+            None => format!("凭据 #{}", entry.id),
+        };
+        if entry.disabled {
+            report.skip(name, "已禁用");
+            continue;
+        }
+
+        if let Err(e) = token_manager.refresh_token_for(entry.id, true).await {
+            report.fail(format!("{} Token 刷新", name), format!("{:#}", e));
+            report.skip(format!("{} 上游", name), "Token 刷新失败");
+            continue;
+        }
+        report.pass(format!("{} Token 刷新", name), "刷新成功");
+
+        match token_manager.get_usage_limits_for(entry.id).await {
+            Ok(usage) => report.pass(
+                format!("{} 上游", name),
+                format!(
+                    "{}，已用 {:.1} / {:.1}",
+                    usage.subscription_title().unwrap_or("未知订阅"),
+                    usage.current_usage(),
+                    usage.usage_limit()
+                ),
+            ),
+            Err(e) => report.fail(format!("{} 上游", name), format!("{:#}", e)),
+        }
+    }
+}
+
+/// Cloud Pass 服务器可达性
+async fn check_cloud_pass(config: &Config, report: &mut DoctorReport) {
+    let Some(cloud_pass) = &config.cloud_pass else {
+        report.skip("Cloud Pass", "未配置");
+        return;
+    };
+    if cloud_pass.disabled {
+        report.skip("Cloud Pass", "已暂停");
+        return;
+    }
+    if cloud_pass.all_licenses().is_empty() {
+        report.fail("Cloud Pass", "未配置激活码（licenseCode / licenses）");
+        return;
+    }
+    let client = match build_client(None, NETWORK_TIMEOUT_SECS, config.tls_backend) {
+        Ok(client) => client,
+        Err(e) => {
+            report.fail("Cloud Pass", format!("创建 HTTP 客户端失败: {:#}", e));
+            return;
+        }
+    };
+    match client.get(&cloud_pass.server_url).send().await {
+        Ok(response) => report.pass(
+            "Cloud Pass",
+            format!(
+                "{} 可达（HTTP {}）",
+                cloud_pass.server_url,
+                response.status()
+            ),
+        ),
+        Err(e) => report.fail(
+            "Cloud Pass",
+            format!("无法访问 {}: {}", cloud_pass.server_url, e),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_summary() {
+        let mut report = DoctorReport::default();
+        report.pass("配置文件", "config.json");
+        report.fail("凭证文件", "credentials.json: 不存在");
+        report.skip("Cloud Pass", "未配置");
+
+        assert!(report.has_failures());
+        let text = report.render();
+        assert!(text.starts_with("[PASS] 配置文件: config.json\n"));
+        assert!(text.contains("[FAIL] 凭证文件: credentials.json: 不存在\n"));
+        assert!(text.ends_with("共 3 项：通过 1，警告 0，失败 1，跳过 1\n"));
+    }
+}
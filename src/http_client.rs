@@ -1,10 +1,18 @@
 //! HTTP Client 构建模块
 //!
-//! 提供统一的 HTTP Client 构建功能，支持代理配置
+//! 提供统一的 HTTP Client 构建功能，支持代理配置、自定义 CA 与证书固定
 
-use reqwest::{Client, Proxy};
+use std::sync::Arc;
 use std::time::Duration;
 
+use reqwest::{Certificate, Client, ClientBuilder, Proxy};
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+
 use crate::model::config::TlsBackend;
 
 /// 代理配置
@@ -36,6 +44,28 @@ impl ProxyConfig {
     }
 }
 
+/// 出站 TLS 配置
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TlsConfig {
+    /// TLS 后端
+    pub backend: TlsBackend,
+    /// 额外信任的 CA 证书（PEM 文件路径，可包含多个证书）
+    pub ca_cert_path: Option<String>,
+    /// 固定的服务端证书 SHA-256 指纹（十六进制，可含冒号），非空时仅接受匹配的证书
+    pub pinned_cert_sha256: Vec<String>,
+}
+
+impl TlsConfig {
+    /// 仅指定 TLS 后端的配置
+    pub fn new(backend: TlsBackend) -> Self {
+        Self {
+            backend,
+            ca_cert_path: None,
+            pinned_cert_sha256: Vec::new(),
+        }
+    }
+}
+
 /// 构建 HTTP Client
 ///
 /// # Arguments
@@ -49,10 +79,53 @@ pub fn build_client(
     timeout_secs: u64,
     tls_backend: TlsBackend,
 ) -> anyhow::Result<Client> {
+    build_client_with_tls(proxy, timeout_secs, &TlsConfig::new(tls_backend))
+}
+
+/// 使用完整 TLS 配置构建 HTTP Client（凭据级 TLS 设置）
+pub fn build_client_with_tls(
+    proxy: Option<&ProxyConfig>,
+    timeout_secs: u64,
+    tls: &TlsConfig,
+) -> anyhow::Result<Client> {
+    Ok(client_builder(proxy, timeout_secs, tls)?.build()?)
+}
+
+/// 按代理、超时与 TLS 配置预设 `ClientBuilder`（供需要额外设置的调用方使用，如重定向策略）
+pub fn client_builder(
+    proxy: Option<&ProxyConfig>,
+    timeout_secs: u64,
+    tls: &TlsConfig,
+) -> anyhow::Result<ClientBuilder> {
     let mut builder = Client::builder().timeout(Duration::from_secs(timeout_secs));
 
-    if tls_backend == TlsBackend::Rustls {
-        builder = builder.use_rustls_tls();
+    let ca_pem = match &tls.ca_cert_path {
+        Some(path) => Some(
+            std::fs::read(path)
+                .map_err(|e| anyhow::anyhow!("读取 CA 证书文件 {} 失败: {}", path, e))?,
+        ),
+        None => None,
+    };
+
+    if !tls.pinned_cert_sha256.is_empty() {
+        // 证书固定需要自定义校验器，只能通过预配置的 rustls 实现
+        anyhow::ensure!(
+            tls.backend == TlsBackend::Rustls,
+            "证书固定仅支持 rustls 后端"
+        );
+        builder = builder.use_preconfigured_tls(pinned_tls_config(
+            ca_pem.as_deref(),
+            &tls.pinned_cert_sha256,
+        )?);
+    } else {
+        if tls.backend == TlsBackend::Rustls {
+            builder = builder.use_rustls_tls();
+        }
+        if let Some(pem) = &ca_pem {
+            for cert in Certificate::from_pem_bundle(pem)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
     }
 
     if let Some(proxy_config) = proxy {
@@ -67,7 +140,101 @@ pub fn build_client(
         tracing::debug!("HTTP Client 使用代理: {}", proxy_config.url);
     }
 
-    Ok(builder.build()?)
+    Ok(builder)
+}
+
+/// 解析证书指纹（64 位十六进制，允许冒号分隔）
+fn parse_fingerprint(fingerprint: &str) -> anyhow::Result<[u8; 32]> {
+    let hex_str: String = fingerprint.chars().filter(|c| *c != ':').collect();
+    hex::decode(&hex_str)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("无效的证书 SHA-256 指纹: {}", fingerprint))
+}
+
+/// 构建带证书固定的 rustls 配置（内置根证书 + 自定义 CA）
+fn pinned_tls_config(
+    ca_pem: Option<&[u8]>,
+    pins: &[String],
+) -> anyhow::Result<rustls::ClientConfig> {
+    let pins = pins
+        .iter()
+        .map(|pin| parse_fingerprint(pin))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    if let Some(pem) = ca_pem {
+        for cert in CertificateDer::pem_slice_iter(pem) {
+            roots.add(cert?)?;
+        }
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()?;
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier { inner, pins }))
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// 证书固定校验器：先按常规证书链校验，再要求叶子证书指纹命中固定列表
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        let fingerprint: [u8; 32] = Sha256::digest(end_entity.as_ref()).into();
+        if self.pins.contains(&fingerprint) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            tracing::warn!(
+                "上游证书指纹 {} 不在固定列表中，拒绝连接",
+                hex::encode(fingerprint)
+            );
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
 }
 
 #[cfg(test)]
@@ -96,6 +263,35 @@ mod tests {
         assert!(client.is_ok());
     }
 
+    #[test]
+    fn test_build_client_with_pinned_cert() {
+        let pin = "AB:".repeat(31) + "AB";
+        let tls = TlsConfig {
+            pinned_cert_sha256: vec![pin.clone()],
+            ..TlsConfig::new(TlsBackend::Rustls)
+        };
+        assert!(build_client_with_tls(None, 30, &tls).is_ok());
+        assert_eq!(parse_fingerprint(&pin).unwrap(), [0xAB; 32]);
+
+        let native = TlsConfig {
+            backend: TlsBackend::NativeTls,
+            ..tls.clone()
+        };
+        assert!(build_client_with_tls(None, 30, &native).is_err());
+
+        let invalid = TlsConfig {
+            pinned_cert_sha256: vec!["abcd".to_string()],
+            ..tls
+        };
+        assert!(build_client_with_tls(None, 30, &invalid).is_err());
+
+        let missing_ca = TlsConfig {
+            ca_cert_path: Some("/nonexistent/ca.pem".to_string()),
+            ..TlsConfig::new(TlsBackend::Rustls)
+        };
+        assert!(build_client_with_tls(None, 30, &missing_ca).is_err());
+    }
+
     #[test]
     fn test_build_client_with_proxy() {
         let config = ProxyConfig::new("http://127.0.0.1:7890");
@@ -0,0 +1,39 @@
+//! 定时余额刷新
+//!
+//! 按固定间隔依次查询每个启用凭据的使用额度，保持 Admin 余额展示、
+//! 配额感知准入、余额不足与用量警戒线检测所用的数据新鲜，无需手动触发查询
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Duration as ChronoDuration;
+
+use crate::kiro::token_manager::MultiTokenManager;
+
+/// 两次查询之间的间隔，避免集中请求触发上游限流
+const BALANCE_REFRESH_SPACING: Duration = Duration::from_secs(2);
+
+/// 启动定时余额刷新后台任务
+///
+/// 启动后立即刷新一轮；距上次查询不足半个间隔的凭据本轮跳过
+pub async fn start_balance_refresh_worker(
+    token_manager: Arc<MultiTokenManager>,
+    interval_secs: u64,
+) {
+    let interval = Duration::from_secs(interval_secs);
+    tracing::info!("定时余额刷新任务启动，刷新间隔: {}s", interval_secs);
+
+    loop {
+        let max_age = ChronoDuration::seconds((interval_secs / 2) as i64);
+        let targets = token_manager.balance_refresh_targets(max_age);
+        tracing::debug!("开始刷新余额，共 {} 个凭据", targets.len());
+        for id in targets {
+            if let Err(e) = token_manager.get_usage_limits_for(id).await {
+                tracing::warn!("凭据 #{} 定时刷新余额失败: {}", id, e);
+            }
+            tokio::time::sleep(BALANCE_REFRESH_SPACING).await;
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
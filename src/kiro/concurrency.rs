@@ -0,0 +1,167 @@
+//! 凭据并发限制
+//!
+//! 每个凭据同时进行的上游请求数有上限，超出的请求在该凭据的队列中按到达顺序等待，
+//! 队列已满或等待超时时拒绝请求（由调用方返回 503 与 `Retry-After`），
+//! 避免突发流量集中打到同一账号引发上游限流
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::kiro::model::credentials::CredentialId;
+
+/// 并发许可（随上游响应一起释放）
+#[derive(Debug, Clone)]
+pub struct ConcurrencyPermit {
+    _permit: Arc<OwnedSemaphorePermit>,
+}
+
+/// 并发已满且无法排队（队列已满或等待超时）
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConcurrencyLimited {
+    pub id: CredentialId,
+    /// 是否为排队超时（否则为队列已满）
+    pub timed_out: bool,
+    /// 建议客户端重试前等待的秒数
+    pub retry_after_secs: u64,
+}
+
+impl std::fmt::Display for ConcurrencyLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.timed_out {
+            write!(f, "凭据 #{} 并发已满，排队等待超时", self.id)
+        } else {
+            write!(f, "凭据 #{} 并发已满且等待队列已满", self.id)
+        }
+    }
+}
+
+impl std::error::Error for ConcurrencyLimited {}
+
+/// 单个凭据的并发槽位
+struct Slot {
+    limit: u32,
+    semaphore: Arc<Semaphore>,
+    /// 正在排队的请求数
+    waiting: AtomicUsize,
+}
+
+/// 排队计数守卫（等待结束或被取消时递减）
+struct WaitingGuard<'a>(&'a AtomicUsize);
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 按凭据限制并发的排队器
+#[derive(Default)]
+pub struct ConcurrencyLimiter {
+    slots: Mutex<HashMap<CredentialId, Arc<Slot>>>,
+}
+
+impl ConcurrencyLimiter {
+    /// 获取并发许可，并发已满时最多排队 `queue_size` 个请求、等待 `timeout`
+    ///
+    /// 上限变化（热重载或修改凭据）后新请求使用新的槽位，已发出的请求仍占用旧槽位直到结束
+    pub async fn acquire(
+        &self,
+        id: CredentialId,
+        limit: u32,
+        queue_size: usize,
+        timeout: Duration,
+    ) -> Result<ConcurrencyPermit, ConcurrencyLimited> {
+        let slot = self.slot(id, limit);
+        if let Ok(permit) = slot.semaphore.clone().try_acquire_owned() {
+            return Ok(ConcurrencyPermit {
+                _permit: Arc::new(permit),
+            });
+        }
+
+        let retry_after_secs = timeout.as_secs().max(1);
+        if slot.waiting.fetch_add(1, Ordering::Relaxed) >= queue_size {
+            slot.waiting.fetch_sub(1, Ordering::Relaxed);
+            return Err(ConcurrencyLimited {
+                id,
+                timed_out: false,
+                retry_after_secs,
+            });
+        }
+        let _guard = WaitingGuard(&slot.waiting);
+
+        match tokio::time::timeout(timeout, slot.semaphore.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(ConcurrencyPermit {
+                _permit: Arc::new(permit),
+            }),
+            _ => Err(ConcurrencyLimited {
+                id,
+                timed_out: true,
+                retry_after_secs,
+            }),
+        }
+    }
+
+    fn slot(&self, id: CredentialId, limit: u32) -> Arc<Slot> {
+        let mut slots = self.slots.lock();
+        let slot = slots.entry(id).or_insert_with(|| Self::new_slot(limit));
+        if slot.limit != limit {
+            *slot = Self::new_slot(limit);
+        }
+        slot.clone()
+    }
+
+    fn new_slot(limit: u32) -> Arc<Slot> {
+        Arc::new(Slot {
+            limit,
+            semaphore: Arc::new(Semaphore::new(limit as usize)),
+            waiting: AtomicUsize::new(0),
+        })
+    }
+
+    /// 各凭据正在进行的请求数（仅包含有请求的凭据）
+    pub fn in_flight(&self) -> HashMap<CredentialId, usize> {
+        self.slots
+            .lock()
+            .iter()
+            .map(|(id, slot)| {
+                let used = (slot.limit as usize).saturating_sub(slot.semaphore.available_permits());
+                (*id, used)
+            })
+            .filter(|(_, used)| *used > 0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queues_then_rejects() {
+        let limiter = ConcurrencyLimiter::default();
+        let id = CredentialId(1);
+        let timeout = Duration::from_millis(50);
+
+        let first = limiter.acquire(id, 1, 1, timeout).await.unwrap();
+        assert_eq!(limiter.in_flight().get(&id), Some(&1));
+
+        // 并发已满：排队等待超时
+        let err = limiter.acquire(id, 1, 1, timeout).await.unwrap_err();
+        assert!(err.timed_out);
+
+        // 队列长度为 0 时直接拒绝
+        let err = limiter.acquire(id, 1, 0, timeout).await.unwrap_err();
+        assert!(!err.timed_out);
+
+        // 释放后排队的请求获得许可
+        let waiter = limiter.acquire(id, 1, 1, Duration::from_secs(5));
+        drop(first);
+        assert!(waiter.await.is_ok());
+        assert!(limiter.in_flight().is_empty());
+    }
+}
@@ -0,0 +1,339 @@
+//! 自动发现并导入 Kiro IDE 的凭据
+//!
+//! 扫描 Kiro IDE 的 Token 缓存（默认 `~/.aws/sso/cache` 与 `~/.kiro`）中的 Token 文件，
+//! IdC 登录的 Token 通过 `clientIdHash` 关联同目录下的客户端注册文件补全 clientId / clientSecret。
+//! 导入过的源 refreshToken 哈希记录在 `kiro_discovery.json`，
+//! 刷新轮换 refreshToken 后再次扫描到 IDE 缓存中的旧 Token 时不会重复导入
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::kiro::model::credentials::{CredentialId, KiroCredentials};
+use crate::kiro::token_manager::{MultiTokenManager, sha256_hex};
+use crate::model::config::CredentialDiscoveryConfig;
+
+/// 已导入源 Token 的记录文件名（位于凭证文件所在目录）
+const STATE_FILE: &str = "kiro_discovery.json";
+
+/// Kiro IDE 的 Token 文件
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenFile {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    expires_at: Option<String>,
+    /// `social` 或 `IdC`
+    auth_method: Option<String>,
+    profile_arn: Option<String>,
+    region: Option<String>,
+    /// IdC 客户端注册文件名（不含扩展名）
+    client_id_hash: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+/// IdC 客户端注册文件
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClientRegistration {
+    client_id: String,
+    client_secret: String,
+}
+
+/// 已导入源 Token 的记录
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiscoveryState {
+    /// 源 refreshToken 的 SHA-256 哈希
+    imported: BTreeSet<String>,
+}
+
+/// 扫描到的 Token 文件
+#[derive(Debug)]
+pub struct DiscoveredToken {
+    pub path: PathBuf,
+    pub credential: Result<KiroCredentials, String>,
+}
+
+/// 单个 Token 文件的导入状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoveryStatus {
+    /// 已导入
+    Imported,
+    /// 已存在或之前导入过
+    Duplicate,
+    /// 解析或导入失败
+    Failed,
+}
+
+/// 单个 Token 文件的导入结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveryResult {
+    pub path: String,
+    pub status: DiscoveryStatus,
+    /// 新凭据 ID（仅导入成功时）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<CredentialId>,
+    /// 失败原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// 发现报告
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveryReport {
+    /// 扫描的目录或文件
+    pub paths: Vec<String>,
+    pub imported: usize,
+    pub duplicates: usize,
+    pub failed: usize,
+    pub results: Vec<DiscoveryResult>,
+}
+
+/// 扫描路径：配置了 `paths` 时使用配置值，否则使用 Kiro IDE 的默认缓存目录
+pub fn resolve_paths(config: Option<&CredentialDiscoveryConfig>) -> Vec<PathBuf> {
+    let home = home_dir();
+    match config {
+        Some(config) if !config.paths.is_empty() => config
+            .paths
+            .iter()
+            .map(|p| match p.strip_prefix("~/") {
+                Some(rest) => home.join(rest),
+                None => PathBuf::from(p),
+            })
+            .collect(),
+        _ => vec![
+            home.join(".aws").join("sso").join("cache"),
+            home.join(".kiro"),
+        ],
+    }
+}
+
+fn home_dir() -> PathBuf {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var_os(var).map(PathBuf::from).unwrap_or_default()
+}
+
+/// 扫描目录（不递归）或单个文件中的 Token 文件
+///
+/// 不存在的路径和不含 refreshToken 的 JSON 文件直接忽略
+pub fn scan(paths: &[PathBuf]) -> Vec<DiscoveredToken> {
+    let mut found = Vec::new();
+    for path in paths {
+        if path.is_file() {
+            found.extend(read_token_file(path));
+            continue;
+        }
+        let Ok(dir) = std::fs::read_dir(path) else {
+            continue;
+        };
+        let mut files: Vec<PathBuf> = dir
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+        found.extend(files.iter().filter_map(|p| read_token_file(p)));
+    }
+    found
+}
+
+/// 读取 Token 文件；不是 Kiro Token 文件时返回 None
+fn read_token_file(path: &Path) -> Option<DiscoveredToken> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let token: TokenFile = serde_json::from_str(&content).ok()?;
+    // 只识别 Kiro IDE 写入的 Token（带 authMethod），忽略 AWS CLI 等其他工具的 SSO 缓存
+    token.refresh_token.as_ref()?;
+    token.auth_method.as_ref()?;
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    Some(DiscoveredToken {
+        path: path.to_path_buf(),
+        credential: to_credential(token, dir),
+    })
+}
+
+fn to_credential(token: TokenFile, dir: &Path) -> Result<KiroCredentials, String> {
+    let auth_method = token.auth_method.unwrap_or_default().to_lowercase();
+    let mut credential = KiroCredentials {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at: token
+            .expires_at
+            .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+            .map(|t| t.with_timezone(&Utc)),
+        profile_arn: token.profile_arn,
+        region: token.region,
+        client_id: token.client_id,
+        client_secret: token.client_secret,
+        auth_method: Some(auth_method.clone()),
+        ..Default::default()
+    };
+
+    if auth_method != "social" && credential.client_id.is_none() {
+        let hash = token
+            .client_id_hash
+            .ok_or("IdC Token 缺少 clientIdHash，无法定位客户端注册文件")?;
+        let registration_path = dir.join(format!("{}.json", hash));
+        let registration: ClientRegistration = std::fs::read_to_string(&registration_path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+            .map_err(|e| {
+                format!(
+                    "读取客户端注册文件 {} 失败: {}",
+                    registration_path.display(),
+                    e
+                )
+            })?;
+        credential.client_id = Some(registration.client_id);
+        credential.client_secret = Some(registration.client_secret);
+    }
+    Ok(credential)
+}
+
+/// 扫描并导入凭据
+///
+/// 与现有凭据 refreshToken 相同、或源 Token 之前导入过的跳过；
+/// 新凭据逐个调用 `add_credential`（会刷新 Token 验证有效性）
+pub async fn discover_credentials(
+    token_manager: &MultiTokenManager,
+    paths: &[PathBuf],
+) -> DiscoveryReport {
+    let state_path = token_manager.cache_dir().map(|d| d.join(STATE_FILE));
+    let mut state = state_path
+        .as_deref()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str::<DiscoveryState>(&s).ok())
+        .unwrap_or_default();
+    let state_len = state.imported.len();
+
+    let mut results = Vec::new();
+    for token in scan(paths) {
+        let path = token.path.display().to_string();
+        let result = match token.credential {
+            Err(message) => DiscoveryResult {
+                path,
+                status: DiscoveryStatus::Failed,
+                credential_id: None,
+                message: Some(message),
+            },
+            Ok(credential) => {
+                let refresh_token = credential.refresh_token.clone().unwrap_or_default();
+                let hash = sha256_hex(&refresh_token);
+                if state.imported.contains(&hash)
+                    || token_manager.contains_refresh_token(&refresh_token)
+                {
+                    DiscoveryResult {
+                        path,
+                        status: DiscoveryStatus::Duplicate,
+                        credential_id: None,
+                        message: None,
+                    }
+                } else {
+                    match token_manager.add_credential(credential).await {
+                        Ok(id) => {
+                            tracing::info!("已从 {} 导入凭据 #{}", path, id);
+                            state.imported.insert(hash);
+                            // 主动获取订阅等级，避免首次请求时 Free 账号绕过 Opus 模型过滤
+                            if let Err(e) = token_manager.get_usage_limits_for(id).await {
+                                tracing::warn!("导入凭据 #{} 后获取订阅等级失败: {}", id, e);
+                            }
+                            DiscoveryResult {
+                                path,
+                                status: DiscoveryStatus::Imported,
+                                credential_id: Some(id),
+                                message: None,
+                            }
+                        }
+                        Err(e) => DiscoveryResult {
+                            path,
+                            status: DiscoveryStatus::Failed,
+                            credential_id: None,
+                            message: Some(e.to_string()),
+                        },
+                    }
+                }
+            }
+        };
+        results.push(result);
+    }
+
+    if state.imported.len() != state_len
+        && let Some(state_path) = &state_path
+    {
+        let json = serde_json::to_string_pretty(&state).unwrap_or_default();
+        if let Err(e) = std::fs::write(state_path, json) {
+            tracing::warn!("保存凭据发现记录失败: {}", e);
+        }
+    }
+
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    DiscoveryReport {
+        paths: paths.iter().map(|p| p.display().to_string()).collect(),
+        imported: count(DiscoveryStatus::Imported),
+        duplicates: count(DiscoveryStatus::Duplicate),
+        failed: count(DiscoveryStatus::Failed),
+        results,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_kiro_token_files() {
+        let dir = std::env::temp_dir().join(format!("kiro-discovery-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("kiro-auth-token.json"),
+            r#"{"accessToken":"a","refreshToken":"r1","expiresAt":"2025-01-01T00:00:00.000Z","authMethod":"IdC","provider":"BuilderId","region":"us-east-1","clientIdHash":"abc"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("abc.json"),
+            r#"{"clientId":"cid","clientSecret":"secret","expiresAt":"2025-06-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("social.json"),
+            r#"{"refreshToken":"r2","authMethod":"social","provider":"Github","profileArn":"arn:aws:codewhisperer:us-east-1:1:profile/X"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("orphan.json"),
+            r#"{"refreshToken":"r3","authMethod":"IdC","clientIdHash":"missing"}"#,
+        )
+        .unwrap();
+        // AWS CLI 的 SSO 缓存没有 authMethod，不识别
+        std::fs::write(
+            dir.join("cli.json"),
+            r#"{"startUrl":"https://x.awsapps.com/start","refreshToken":"r4","clientId":"c","clientSecret":"s"}"#,
+        )
+        .unwrap();
+
+        let found = scan(&[dir.clone(), dir.join("not-exist")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found.len(), 3);
+        let idc = found[0].credential.as_ref().unwrap();
+        assert!(found[0].path.ends_with("kiro-auth-token.json"));
+        assert_eq!(idc.auth_method.as_deref(), Some("idc"));
+        assert_eq!(idc.client_id.as_deref(), Some("cid"));
+        assert_eq!(idc.client_secret.as_deref(), Some("secret"));
+        assert!(idc.expires_at.is_some());
+
+        assert!(found[1].path.ends_with("orphan.json"));
+        assert!(found[1].credential.is_err());
+
+        let social = found[2].credential.as_ref().unwrap();
+        assert_eq!(social.auth_method.as_deref(), Some("social"));
+        assert!(social.profile_arn.is_some());
+        assert!(social.client_id.is_none());
+    }
+}
@@ -0,0 +1,439 @@
+//! 凭据批量导入（CSV / TSV）
+//!
+//! 支持通过列映射把任意表头的表格转换为凭据，逐行校验并报告结果；
+//! CLI `import` 子命令与 Admin API `POST /credentials/import` 共用此模块
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::kiro::model::credentials::{CredentialId, KiroCredentials};
+use crate::kiro::token_manager::MultiTokenManager;
+
+/// 可映射的凭据字段（与 credentials.json 的字段名一致）
+const IMPORT_FIELDS: &[&str] = &[
+    "refreshToken",
+    "authMethod",
+    "clientId",
+    "clientSecret",
+    "priority",
+    "region",
+    "authRegion",
+    "apiRegion",
+    "machineId",
+    "email",
+    "proxyUrl",
+    "proxyUsername",
+    "proxyPassword",
+    "rateLimitRpm",
+    "tags",
+    "note",
+];
+
+/// 表格格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Tsv,
+}
+
+impl ImportFormat {
+    /// 根据表头推断格式：表头含制表符时视为 TSV
+    pub fn detect(content: &str) -> Self {
+        let header = content.lines().next().unwrap_or_default();
+        if header.contains('\t') {
+            ImportFormat::Tsv
+        } else {
+            ImportFormat::Csv
+        }
+    }
+
+    /// 根据文件扩展名推断格式，无法识别时按内容推断
+    pub fn from_path(path: &str, content: &str) -> Self {
+        if path.to_lowercase().ends_with(".tsv") {
+            ImportFormat::Tsv
+        } else {
+            Self::detect(content)
+        }
+    }
+
+    fn delimiter(self) -> u8 {
+        match self {
+            ImportFormat::Csv => b',',
+            ImportFormat::Tsv => b'\t',
+        }
+    }
+}
+
+/// 列映射：凭据字段 -> 表格列名
+///
+/// 未映射的字段按同名列匹配（忽略大小写，兼容 snake_case 列名）
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ColumnMapping(HashMap<String, String>);
+
+impl ColumnMapping {
+    pub fn new(mapping: HashMap<String, String>) -> Self {
+        Self(mapping)
+    }
+
+    /// 解析 CLI 映射参数，格式：`refreshToken=rt,email=mail`
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let mut mapping = HashMap::new();
+        for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (field, column) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("列映射格式错误（应为 字段=列名）: {}", pair))?;
+            mapping.insert(field.trim().to_string(), column.trim().to_string());
+        }
+        Ok(Self(mapping))
+    }
+
+    /// 根据表头计算每个字段对应的列下标
+    fn resolve(&self, headers: &[String]) -> anyhow::Result<HashMap<&'static str, usize>> {
+        for field in self.0.keys() {
+            if !IMPORT_FIELDS.contains(&field.as_str()) {
+                anyhow::bail!("未知的映射字段: {}", field);
+            }
+        }
+
+        let find = |name: &str| {
+            headers.iter().position(|h| {
+                let h = h.trim();
+                h.eq_ignore_ascii_case(name) || h.replace('_', "").eq_ignore_ascii_case(name)
+            })
+        };
+
+        let mut resolved = HashMap::new();
+        for field in IMPORT_FIELDS {
+            match self.0.get(*field) {
+                Some(column) => {
+                    let idx = headers
+                        .iter()
+                        .position(|h| h.trim() == column)
+                        .ok_or_else(|| anyhow::anyhow!("表格中不存在列: {}", column))?;
+                    resolved.insert(*field, idx);
+                }
+                None => {
+                    if let Some(idx) = find(field) {
+                        resolved.insert(*field, idx);
+                    }
+                }
+            }
+        }
+
+        if !resolved.contains_key("refreshToken") {
+            anyhow::bail!("缺少 refreshToken 列（可通过列映射指定）");
+        }
+        Ok(resolved)
+    }
+}
+
+/// 单行解析结果
+#[derive(Debug)]
+pub struct ParsedRow {
+    /// 行号（含表头，从 1 开始）
+    pub line: u64,
+    pub credential: Result<KiroCredentials, String>,
+}
+
+/// 解析表格内容为凭据列表
+///
+/// 表头或列映射错误时返回 Err；单行错误记录在对应的 `ParsedRow` 中
+pub fn parse_table(
+    content: &str,
+    format: ImportFormat,
+    mapping: &ColumnMapping,
+) -> anyhow::Result<Vec<ParsedRow>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(format.delimiter())
+        .flexible(true)
+        .from_reader(content.as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| anyhow::anyhow!("读取表头失败: {}", e))?
+        .iter()
+        .map(|h| h.trim_start_matches('\u{feff}').to_string())
+        .collect();
+    let columns = mapping.resolve(&headers)?;
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let (line, credential) = match record {
+            Ok(record) => {
+                let line = record.position().map(|p| p.line()).unwrap_or_default();
+                if record.iter().all(|v| v.trim().is_empty()) {
+                    continue;
+                }
+                let get = |field: &str| {
+                    columns
+                        .get(field)
+                        .and_then(|idx| record.get(*idx))
+                        .map(str::trim)
+                        .filter(|v| !v.is_empty())
+                        .map(str::to_string)
+                };
+                (line, build_credential(get))
+            }
+            Err(e) => {
+                let line = e.position().map(|p| p.line()).unwrap_or_default();
+                (line, Err(format!("解析失败: {}", e)))
+            }
+        };
+        rows.push(ParsedRow { line, credential });
+    }
+    Ok(rows)
+}
+
+/// 根据字段取值函数构建凭据
+fn build_credential(get: impl Fn(&str) -> Option<String>) -> Result<KiroCredentials, String> {
+    let parse_number = |field: &str| -> Result<Option<u32>, String> {
+        get(field)
+            .map(|v| v.parse::<u32>().map_err(|_| format!("{} 不是有效数字: {}", field, v)))
+            .transpose()
+    };
+
+    let mut cred = KiroCredentials {
+        refresh_token: get("refreshToken"),
+        client_id: get("clientId"),
+        client_secret: get("clientSecret"),
+        priority: parse_number("priority")?.unwrap_or(0),
+        region: get("region"),
+        auth_region: get("authRegion"),
+        api_region: get("apiRegion"),
+        machine_id: get("machineId"),
+        email: get("email"),
+        proxy_url: get("proxyUrl"),
+        proxy_username: get("proxyUsername"),
+        proxy_password: get("proxyPassword"),
+        rate_limit_rpm: parse_number("rateLimitRpm")?,
+        usage_cap: None,
+        usage_watermark: None,
+        // 多个标签以分号分隔
+        tags: get("tags")
+            .map(|v| KiroCredentials::normalize_tags(v.split(';')))
+            .unwrap_or_default(),
+        note: KiroCredentials::normalize_note(get("note")),
+        ..Default::default()
+    };
+
+    // 未指定认证方式时，带 clientId/clientSecret 的视为 IdC
+    cred.auth_method = Some(get("authMethod").unwrap_or_else(|| {
+        if cred.client_id.is_some() && cred.client_secret.is_some() {
+            "idc".to_string()
+        } else {
+            "social".to_string()
+        }
+    }));
+
+    if cred.refresh_token.is_none() {
+        return Err("缺少 refreshToken".to_string());
+    }
+    Ok(cred)
+}
+
+/// 单行导入状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportRowStatus {
+    /// dry-run 校验通过
+    Valid,
+    /// 已导入
+    Imported,
+    /// 校验或导入失败
+    Failed,
+}
+
+/// 单行导入结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRowResult {
+    /// 行号（含表头，从 1 开始）
+    pub line: u64,
+    pub status: ImportRowStatus,
+    /// 新凭据 ID（仅导入成功时）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<CredentialId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// 失败原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// 导入报告
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// 数据行总数
+    pub total: usize,
+    /// 校验通过或导入成功的行数
+    pub succeeded: usize,
+    pub failed: usize,
+    pub rows: Vec<ImportRowResult>,
+}
+
+/// 导入凭据
+///
+/// dry-run 模式只做本地校验（格式、截断、与现有凭据及文件内其他行的重复），
+/// 不请求上游，避免刷新 Token 导致 refreshToken 轮换；
+/// 正式导入时逐行调用 `add_credential`（会刷新 Token 验证有效性）
+pub async fn import_credentials(
+    token_manager: &MultiTokenManager,
+    rows: Vec<ParsedRow>,
+    dry_run: bool,
+) -> ImportReport {
+    let mut results = Vec::with_capacity(rows.len());
+    let mut seen: HashMap<String, u64> = HashMap::new();
+    let mut imported_ids = HashSet::new();
+
+    for row in rows {
+        let email = row.credential.as_ref().ok().and_then(|c| c.email.clone());
+        let outcome = match row.credential {
+            Err(e) => Err(e),
+            Ok(cred) => {
+                let token = cred.refresh_token.clone().unwrap_or_default();
+                if let Some(first) = seen.get(&token) {
+                    Err(format!("与第 {} 行 refreshToken 重复", first))
+                } else {
+                    seen.insert(token, row.line);
+                    if dry_run {
+                        token_manager
+                            .precheck_credential(&cred)
+                            .map(|_| None)
+                            .map_err(|e| e.to_string())
+                    } else {
+                        token_manager
+                            .add_credential(cred)
+                            .await
+                            .map(Some)
+                            .map_err(|e| e.to_string())
+                    }
+                }
+            }
+        };
+
+        let result = match outcome {
+            Ok(credential_id) => {
+                if let Some(id) = credential_id {
+                    imported_ids.insert(id);
+                }
+                ImportRowResult {
+                    line: row.line,
+                    status: if dry_run {
+                        ImportRowStatus::Valid
+                    } else {
+                        ImportRowStatus::Imported
+                    },
+                    credential_id,
+                    email,
+                    message: None,
+                }
+            }
+            Err(message) => ImportRowResult {
+                line: row.line,
+                status: ImportRowStatus::Failed,
+                credential_id: None,
+                email,
+                message: Some(message),
+            },
+        };
+        results.push(result);
+    }
+
+    // 主动获取订阅等级，避免首次请求时 Free 账号绕过 Opus 模型过滤
+    for id in imported_ids {
+        if let Err(e) = token_manager.get_usage_limits_for(id).await {
+            tracing::warn!("导入凭据 #{} 后获取订阅等级失败（不影响导入）: {}", id, e);
+        }
+    }
+
+    let failed = results
+        .iter()
+        .filter(|r| r.status == ImportRowStatus::Failed)
+        .count();
+    ImportReport {
+        dry_run,
+        total: results.len(),
+        succeeded: results.len() - failed,
+        failed,
+        rows: results,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::config::Config;
+
+    fn token(c: char) -> String {
+        std::iter::repeat_n(c, 120).collect()
+    }
+
+    #[test]
+    fn test_parse_table_with_mapping_and_tsv() {
+        let content = format!(
+            "rt\tmail\tprio\n{}\ta@example.com\t2\n{}\t\tabc\n",
+            token('a'),
+            token('b')
+        );
+        assert_eq!(ImportFormat::detect(&content), ImportFormat::Tsv);
+
+        let mapping = ColumnMapping::parse("refreshToken=rt, email=mail, priority=prio").unwrap();
+        let rows = parse_table(&content, ImportFormat::Tsv, &mapping).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let first = rows[0].credential.as_ref().unwrap();
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(first.email.as_deref(), Some("a@example.com"));
+        assert_eq!(first.priority, 2);
+        assert_eq!(first.auth_method.as_deref(), Some("social"));
+        assert!(rows[1].credential.as_ref().unwrap_err().contains("priority"));
+    }
+
+    #[test]
+    fn test_parse_table_default_mapping_and_errors() {
+        let content = format!("refresh_token,client_id,client_secret\n{},cid,secret\n", token('a'));
+        let rows = parse_table(&content, ImportFormat::Csv, &ColumnMapping::default()).unwrap();
+        let cred = rows[0].credential.as_ref().unwrap();
+        assert_eq!(cred.client_id.as_deref(), Some("cid"));
+        assert_eq!(cred.auth_method.as_deref(), Some("idc"));
+
+        let missing = parse_table("email\nx\n", ImportFormat::Csv, &ColumnMapping::default());
+        assert!(missing.unwrap_err().to_string().contains("refreshToken"));
+
+        let unknown = ColumnMapping::parse("token=rt").unwrap();
+        assert!(parse_table(&content, ImportFormat::Csv, &unknown).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_per_row_results() {
+        let existing = KiroCredentials {
+            refresh_token: Some(token('c')),
+            ..Default::default()
+        };
+        let manager =
+            MultiTokenManager::new(Config::default(), vec![existing], None, None, false).unwrap();
+
+        let content = format!(
+            "refreshToken\n{}\n{}\n{}\nshort\n",
+            token('a'),
+            token('a'),
+            token('c')
+        );
+        let rows = parse_table(&content, ImportFormat::Csv, &ColumnMapping::default()).unwrap();
+        let report = import_credentials(&manager, rows, true).await;
+
+        assert!(report.dry_run);
+        assert_eq!(report.total, 4);
+        assert_eq!(report.succeeded, 1);
+        assert_eq!(report.rows[0].status, ImportRowStatus::Valid);
+        assert!(report.rows[1].message.as_ref().unwrap().contains("第 2 行"));
+        assert!(report.rows[2].message.as_ref().unwrap().contains("已存在"));
+        assert!(report.rows[3].message.as_ref().unwrap().contains("截断"));
+        // dry-run 不会添加凭据
+        assert_eq!(manager.total_count(), 1);
+    }
+}
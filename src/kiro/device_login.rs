@@ -0,0 +1,248 @@
+//! IdC 设备授权登录（AWS SSO OIDC device code flow）
+//!
+//! 注册 OIDC 客户端，申请设备码后由用户在浏览器中打开验证地址并确认，
+//! 期间按服务端要求的间隔轮询换取 Token，得到的 refreshToken 与 clientId / clientSecret
+//! 组成一个新的 IdC 凭据。默认使用 AWS Builder ID，指定 start URL 时登录 IAM Identity Center
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, bail};
+use chrono::Utc;
+use reqwest::Client;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::http_client::{ProxyConfig, build_client};
+use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::model::token_refresh::{
+    DeviceAuthorizationRequest, DeviceAuthorizationResponse, DeviceTokenRequest,
+    IdcRefreshResponse, OidcErrorResponse, RegisterClientRequest, RegisterClientResponse,
+};
+use crate::kiro::token_manager::IDC_AMZ_USER_AGENT;
+use crate::model::config::Config;
+
+/// AWS Builder ID 的 start URL
+pub const BUILDER_ID_START_URL: &str = "https://view.awsapps.com/start";
+
+/// 注册 OIDC 客户端时申请的权限范围
+const SCOPES: &[&str] = &[
+    "codewhisperer:completions",
+    "codewhisperer:analysis",
+    "codewhisperer:conversations",
+    "codewhisperer:transformations",
+    "codewhisperer:taskassist",
+];
+
+/// 设备码换取 Token 的 grant type
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// 服务端未指定轮询间隔时的默认值（秒）
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 5;
+
+/// 设备授权登录
+pub struct DeviceLogin {
+    client: Client,
+    region: String,
+    start_url: String,
+}
+
+/// 已发起的设备授权（等待用户在浏览器中确认）
+pub struct PendingAuthorization {
+    client: RegisterClientResponse,
+    authorization: DeviceAuthorizationResponse,
+}
+
+impl PendingAuthorization {
+    /// 用户需要打开的验证地址（优先使用已带上用户码的地址）
+    pub fn verification_url(&self) -> &str {
+        self.authorization
+            .verification_uri_complete
+            .as_deref()
+            .unwrap_or(&self.authorization.verification_uri)
+    }
+
+    /// 用户码
+    pub fn user_code(&self) -> &str {
+        &self.authorization.user_code
+    }
+}
+
+impl DeviceLogin {
+    /// `region` 为 IdC 所在区域，`start_url` 未指定时使用 AWS Builder ID
+    pub fn new(
+        config: &Config,
+        proxy: Option<&ProxyConfig>,
+        region: Option<String>,
+        start_url: Option<String>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: build_client(proxy, 60, config.tls_backend)?,
+            region: region.unwrap_or_else(|| config.effective_auth_region().to_string()),
+            start_url: start_url.unwrap_or_else(|| BUILDER_ID_START_URL.to_string()),
+        })
+    }
+
+    /// 注册 OIDC 客户端并申请设备码
+    pub async fn start(&self) -> anyhow::Result<PendingAuthorization> {
+        let client: RegisterClientResponse = self
+            .post(
+                "client/register",
+                &RegisterClientRequest {
+                    client_name: "Kiro IDE".to_string(),
+                    client_type: "public".to_string(),
+                    scopes: SCOPES.iter().map(|s| s.to_string()).collect(),
+                },
+            )
+            .await
+            .context("注册 OIDC 客户端失败")?;
+
+        let authorization: DeviceAuthorizationResponse = self
+            .post(
+                "device_authorization",
+                &DeviceAuthorizationRequest {
+                    client_id: client.client_id.clone(),
+                    client_secret: client.client_secret.clone(),
+                    start_url: self.start_url.clone(),
+                },
+            )
+            .await
+            .context("申请设备码失败")?;
+
+        Ok(PendingAuthorization {
+            client,
+            authorization,
+        })
+    }
+
+    /// 轮询直到用户确认授权，返回新的 IdC 凭据（设备码过期或用户拒绝时返回错误）
+    pub async fn wait(&self, pending: PendingAuthorization) -> anyhow::Result<KiroCredentials> {
+        let PendingAuthorization {
+            client,
+            authorization,
+        } = pending;
+        let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+        let mut interval = authorization
+            .interval
+            .unwrap_or(DEFAULT_POLL_INTERVAL_SECS)
+            .max(1);
+        let request = DeviceTokenRequest {
+            client_id: client.client_id.clone(),
+            client_secret: client.client_secret.clone(),
+            device_code: authorization.device_code.clone(),
+            grant_type: DEVICE_CODE_GRANT_TYPE.to_string(),
+        };
+
+        loop {
+            if Instant::now() >= deadline {
+                bail!("设备码已过期，请重新登录");
+            }
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+
+            let token: IdcRefreshResponse = match self.post("token", &request).await {
+                Ok(token) => token,
+                Err(e) => match e.downcast_ref::<OidcError>().map(|e| e.error.as_str()) {
+                    Some("authorization_pending") => continue,
+                    Some("slow_down") => {
+                        interval += DEFAULT_POLL_INTERVAL_SECS;
+                        continue;
+                    }
+                    _ => return Err(e.context("获取 Token 失败")),
+                },
+            };
+            let refresh_token = token
+                .refresh_token
+                .context("OIDC 服务没有返回 refreshToken")?;
+
+            return Ok(KiroCredentials {
+                access_token: Some(token.access_token),
+                refresh_token: Some(refresh_token),
+                expires_at: token
+                    .expires_in
+                    .map(|secs| Utc::now() + chrono::Duration::seconds(secs)),
+                auth_method: Some("idc".to_string()),
+                client_id: Some(client.client_id),
+                client_secret: Some(client.client_secret),
+                region: Some(self.region.clone()),
+                ..Default::default()
+            });
+        }
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+    ) -> anyhow::Result<T> {
+        let url = format!("https://oidc.{}.amazonaws.com/{}", self.region, path);
+        let response = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("x-amz-user-agent", IDC_AMZ_USER_AGENT)
+            .header("User-Agent", "node")
+            .json(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(match serde_json::from_str::<OidcErrorResponse>(&text) {
+                Ok(e) => OidcError {
+                    error: e.error,
+                    description: e.error_description,
+                }
+                .into(),
+                Err(_) => anyhow::anyhow!("{} {}", status, text),
+            });
+        }
+        serde_json::from_str(&text).with_context(|| format!("解析响应失败: {}", text))
+    }
+}
+
+/// OIDC 服务返回的错误
+#[derive(Debug)]
+struct OidcError {
+    error: String,
+    description: Option<String>,
+}
+
+impl std::fmt::Display for OidcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.description {
+            Some(description) => write!(f, "{}: {}", self.error, description),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
+impl std::error::Error for OidcError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verification_url_prefers_complete_uri() {
+        let authorization: DeviceAuthorizationResponse = serde_json::from_str(
+            r#"{"deviceCode":"dc","userCode":"ABCD-EFGH","verificationUri":"https://device.sso.us-east-1.amazonaws.com/","expiresIn":600,"interval":1}"#,
+        )
+        .unwrap();
+        let mut pending = PendingAuthorization {
+            client: RegisterClientResponse {
+                client_id: "id".to_string(),
+                client_secret: "secret".to_string(),
+            },
+            authorization,
+        };
+        assert_eq!(pending.user_code(), "ABCD-EFGH");
+        assert_eq!(
+            pending.verification_url(),
+            "https://device.sso.us-east-1.amazonaws.com/"
+        );
+
+        pending.authorization.verification_uri_complete =
+            Some("https://device.sso.us-east-1.amazonaws.com/?user_code=ABCD-EFGH".to_string());
+        assert!(pending.verification_url().ends_with("user_code=ABCD-EFGH"));
+    }
+}
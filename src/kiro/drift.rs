@@ -0,0 +1,289 @@
+//! 上游协议漂移检测
+//!
+//! 统计上游事件流中未知的消息 / 事件类型、已知事件负载中出现的新字段以及负载解析失败，
+//! 上游调整格式时运营者可以通过 Admin API 提前发现，而不是等客户端报错后再排查。
+//! 首次出现的漂移特征与一分钟内漂移占比突增都会输出告警日志
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::LazyLock;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// 已知事件负载字段（camelCase）；未列出的事件类型不检查字段
+const KNOWN_FIELDS: [(&str, &[&str]); 4] = [
+    (
+        "assistantResponseEvent",
+        &[
+            "content",
+            "conversationId",
+            "messageId",
+            "messageStatus",
+            "followupPrompt",
+            "codeReference",
+            "supplementaryWebLinks",
+        ],
+    ),
+    ("toolUseEvent", &["name", "toolUseId", "input", "stop"]),
+    ("contextUsageEvent", &["contextUsagePercentage"]),
+    ("reasoningContentEvent", &["text", "signature"]),
+];
+
+/// 最多记录的漂移特征数，超过后新特征只计入总数
+const MAX_SIGNATURES: usize = 200;
+
+/// 样本负载保留的最大字符数
+const SAMPLE_MAX_CHARS: usize = 200;
+
+/// 按分钟统计的窗口长度
+const WINDOW_MINUTES: usize = 60;
+
+/// 一分钟内至少出现这么多漂移事件才判定为突增
+const SPIKE_MIN_EVENTS: u64 = 10;
+
+/// 一分钟内漂移事件占帧数的比例达到此值判定为突增
+const SPIKE_RATIO: f64 = 0.05;
+
+/// 漂移类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftKind {
+    /// 未知的消息类型（`:message-type` 头）
+    UnknownMessageType,
+    /// 未知的事件类型（`:event-type` 头）
+    UnknownEventType,
+    /// 已知事件负载中出现未知字段
+    UnexpectedField,
+    /// 已知事件负载无法按现有结构解析
+    SchemaError,
+}
+
+/// 单个漂移特征的统计
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DriftEntry {
+    pub kind: DriftKind,
+    /// 事件类型（未知消息类型时为消息类型）
+    pub event_type: String,
+    /// 具体特征：未知字段名或解析错误
+    pub detail: String,
+    pub count: u64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// 首次出现时的负载样本（已截断）
+    pub sample: String,
+}
+
+/// 漂移报告
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DriftReport {
+    /// 启动以来解析的上游帧数
+    pub total_frames: u64,
+    /// 启动以来的漂移事件数
+    pub total_drift: u64,
+    /// 最近一小时解析的帧数
+    pub last_hour_frames: u64,
+    /// 最近一小时的漂移事件数
+    pub last_hour_drift: u64,
+    /// 各漂移特征（按最近出现时间倒序）
+    pub entries: Vec<DriftEntry>,
+}
+
+#[derive(Debug)]
+struct MinuteBucket {
+    minute: i64,
+    frames: u64,
+    drift: u64,
+    warned: bool,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: HashMap<(DriftKind, String, String), DriftEntry>,
+    buckets: VecDeque<MinuteBucket>,
+    total_frames: u64,
+    total_drift: u64,
+}
+
+impl Inner {
+    fn bucket(&mut self, now: DateTime<Utc>) -> &mut MinuteBucket {
+        let minute = now.timestamp() / 60;
+        if self.buckets.back().is_none_or(|b| b.minute != minute) {
+            if self.buckets.len() >= WINDOW_MINUTES {
+                self.buckets.pop_front();
+            }
+            self.buckets.push_back(MinuteBucket {
+                minute,
+                frames: 0,
+                drift: 0,
+                warned: false,
+            });
+        }
+        self.buckets.back_mut().expect("bucket just pushed")
+    }
+}
+
+/// 上游协议漂移统计（仅内存）
+#[derive(Debug, Default)]
+pub struct DriftTracker {
+    inner: Mutex<Inner>,
+}
+
+impl DriftTracker {
+    /// 记录一个已解析的上游帧（用于计算漂移占比）
+    pub fn record_frame(&self) {
+        let mut inner = self.inner.lock();
+        inner.total_frames += 1;
+        inner.bucket(Utc::now()).frames += 1;
+    }
+
+    /// 记录一次漂移
+    pub fn record(&self, kind: DriftKind, event_type: &str, detail: &str, payload: &[u8]) {
+        self.record_at(kind, event_type, detail, payload, Utc::now());
+    }
+
+    /// 检查已知事件的负载字段，记录未知字段
+    pub fn check_fields(&self, event_type: &str, payload: &[u8]) {
+        let Some((_, known)) = KNOWN_FIELDS.iter().find(|(name, _)| *name == event_type) else {
+            return;
+        };
+        let Ok(fields) =
+            serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(payload)
+        else {
+            return;
+        };
+        for field in fields.keys() {
+            if !known.contains(&field.as_str()) {
+                self.record(DriftKind::UnexpectedField, event_type, field, payload);
+            }
+        }
+    }
+
+    /// 记录漂移，返回是否触发了突增告警
+    fn record_at(
+        &self,
+        kind: DriftKind,
+        event_type: &str,
+        detail: &str,
+        payload: &[u8],
+        now: DateTime<Utc>,
+    ) -> bool {
+        let mut inner = self.inner.lock();
+        inner.total_drift += 1;
+
+        let key = (kind, event_type.to_string(), detail.to_string());
+        if let Some(entry) = inner.entries.get_mut(&key) {
+            entry.count += 1;
+            entry.last_seen = now;
+        } else if inner.entries.len() < MAX_SIGNATURES {
+            tracing::warn!(
+                "检测到上游协议变化: {:?} event_type={} detail={}",
+                kind,
+                event_type,
+                detail
+            );
+            let sample: String = String::from_utf8_lossy(payload)
+                .chars()
+                .take(SAMPLE_MAX_CHARS)
+                .collect();
+            inner.entries.insert(
+                key,
+                DriftEntry {
+                    kind,
+                    event_type: event_type.to_string(),
+                    detail: detail.to_string(),
+                    count: 1,
+                    first_seen: now,
+                    last_seen: now,
+                    sample,
+                },
+            );
+        }
+
+        let bucket = inner.bucket(now);
+        bucket.drift += 1;
+        let spiking = bucket.drift >= SPIKE_MIN_EVENTS
+            && bucket.drift as f64 >= bucket.frames as f64 * SPIKE_RATIO;
+        if spiking && !bucket.warned {
+            bucket.warned = true;
+            tracing::warn!(
+                "上游协议漂移突增: 最近一分钟 {} 个漂移事件 / {} 帧，上游格式可能已变化，请检查 /api/admin/drift",
+                bucket.drift,
+                bucket.frames
+            );
+            return true;
+        }
+        false
+    }
+
+    /// 生成漂移报告
+    pub fn report(&self) -> DriftReport {
+        let inner = self.inner.lock();
+        let mut entries: Vec<DriftEntry> = inner.entries.values().cloned().collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_seen));
+        let cutoff = Utc::now().timestamp() / 60 - WINDOW_MINUTES as i64;
+        let recent = || inner.buckets.iter().filter(move |b| b.minute > cutoff);
+        DriftReport {
+            total_frames: inner.total_frames,
+            total_drift: inner.total_drift,
+            last_hour_frames: recent().map(|b| b.frames).sum(),
+            last_hour_drift: recent().map(|b| b.drift).sum(),
+            entries,
+        }
+    }
+}
+
+/// 全局漂移统计（事件解析处没有可用的上下文对象，统一记录到这里）
+pub fn tracker() -> &'static DriftTracker {
+    static TRACKER: LazyLock<DriftTracker> = LazyLock::new(DriftTracker::default);
+    &TRACKER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_fields_records_unknown_fields() {
+        let tracker = DriftTracker::default();
+        tracker.check_fields(
+            "toolUseEvent",
+            br#"{"name":"a","toolUseId":"t","input":""}"#,
+        );
+        tracker.check_fields("meteringEvent", br#"{"usage":1}"#);
+        assert!(tracker.report().entries.is_empty());
+
+        let payload = br#"{"content":"hi","citations":[]}"#;
+        tracker.check_fields("assistantResponseEvent", payload);
+        tracker.check_fields("assistantResponseEvent", payload);
+
+        let report = tracker.report();
+        assert_eq!(report.total_drift, 2);
+        assert_eq!(report.entries.len(), 1);
+        let entry = &report.entries[0];
+        assert_eq!(entry.kind, DriftKind::UnexpectedField);
+        assert_eq!(entry.detail, "citations");
+        assert_eq!(entry.count, 2);
+        assert_eq!(entry.sample, r#"{"content":"hi","citations":[]}"#);
+    }
+
+    #[test]
+    fn test_spike_warns_once_per_minute() {
+        let tracker = DriftTracker::default();
+        let now = Utc::now();
+        for _ in 0..100 {
+            tracker.record_frame();
+        }
+        let warnings: Vec<bool> = (0..SPIKE_MIN_EVENTS + 5)
+            .map(|_| tracker.record_at(DriftKind::UnknownEventType, "newEvent", "", b"{}", now))
+            .collect();
+        assert_eq!(warnings.iter().filter(|w| **w).count(), 1);
+        assert!(warnings[SPIKE_MIN_EVENTS as usize - 1]);
+
+        let report = tracker.report();
+        assert_eq!(report.total_drift, SPIKE_MIN_EVENTS + 5);
+        assert_eq!(report.entries[0].count, SPIKE_MIN_EVENTS + 5);
+    }
+}
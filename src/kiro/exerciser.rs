@@ -0,0 +1,113 @@
+//! 闲置凭据夜间保活探测
+//!
+//! 每天在指定时刻向闲置凭据发送一个极小请求：
+//! - 刷新 Token，保持 refreshToken 活跃
+//! - 及早发现被静默吊销的凭据，并将结果写入健康状态
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Duration as ChronoDuration, Timelike, Utc};
+
+use crate::kiro::model::credentials::CredentialId;
+use crate::kiro::provider::KiroProvider;
+use crate::kiro::token_manager::MultiTokenManager;
+
+/// 两次探测之间的间隔，避免短时间内集中请求
+const EXERCISE_SPACING: Duration = Duration::from_secs(5);
+
+/// 启动夜间保活探测后台任务
+pub async fn start_exerciser_worker(
+    token_manager: Arc<MultiTokenManager>,
+    provider: KiroProvider,
+    hour_utc: u8,
+) {
+    let config = token_manager.config();
+    let idle = ChronoDuration::hours(config.exercise_idle_hours as i64);
+    let limit = config.exercise_max_credentials;
+    tracing::info!(
+        "闲置凭据保活探测任务启动：每天 {:02}:00 UTC，闲置阈值 {}h，单次最多 {} 个",
+        hour_utc,
+        config.exercise_idle_hours,
+        limit
+    );
+
+    loop {
+        tokio::time::sleep(duration_until_hour(Utc::now(), hour_utc)).await;
+
+        let targets = token_manager.idle_credentials(idle, limit);
+        tracing::info!("开始保活探测，共 {} 个闲置凭据", targets.len());
+        for id in targets {
+            exercise_one(&token_manager, &provider, id).await;
+            tokio::time::sleep(EXERCISE_SPACING).await;
+        }
+    }
+}
+
+/// 探测单个凭据并把结果写入健康状态
+///
+/// Token 刷新失败、401/403、额度用尽视为不健康；网络错误和其他状态码结果不确定，不更新健康状态
+async fn exercise_one(
+    token_manager: &MultiTokenManager,
+    provider: &KiroProvider,
+    id: CredentialId,
+) {
+    let ctx = match token_manager.acquire_context_for_id(id).await {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            tracing::warn!("凭据 #{} 保活探测失败（Token 刷新）: {}", id, e);
+            let _ = token_manager.record_health_check(id, false);
+            return;
+        }
+    };
+
+    let healthy = match provider.exercise_credential(&ctx).await {
+        Ok(status) if status.is_success() => Some(true),
+        Ok(status) if matches!(status.as_u16(), 401..=403) => Some(false),
+        Ok(status) => {
+            tracing::warn!("凭据 #{} 保活探测结果不确定: {}", id, status);
+            None
+        }
+        Err(e) => {
+            tracing::warn!("凭据 #{} 保活探测请求失败: {}", id, e);
+            None
+        }
+    };
+
+    if let Some(healthy) = healthy {
+        tracing::info!("凭据 #{} 保活探测完成: {}", id, if healthy { "正常" } else { "异常" });
+        let _ = token_manager.record_health_check(id, healthy);
+    }
+}
+
+/// 计算距离下一次指定 UTC 整点的时间
+fn duration_until_hour(now: DateTime<Utc>, hour_utc: u8) -> Duration {
+    let hour = u32::from(hour_utc.min(23));
+    let today = now
+        .with_hour(hour)
+        .and_then(|t| t.with_minute(0))
+        .and_then(|t| t.with_second(0))
+        .and_then(|t| t.with_nanosecond(0))
+        .unwrap_or(now);
+    let next = if today > now {
+        today
+    } else {
+        today + ChronoDuration::days(1)
+    };
+    (next - now).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duration_until_hour() {
+        let now = DateTime::parse_from_rfc3339("2026-01-01T01:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(duration_until_hour(now, 3), Duration::from_secs(90 * 60));
+        // 已过当天时刻时顺延到第二天
+        assert_eq!(duration_until_hour(now, 1), Duration::from_secs((24 * 60 - 30) * 60));
+    }
+}
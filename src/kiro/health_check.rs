@@ -0,0 +1,44 @@
+//! 凭据健康检查与自动恢复后台任务
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::kiro::token_manager::MultiTokenManager;
+
+/// 自动恢复任务的检查间隔
+const RECOVERY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 启动禁用凭据自动恢复后台任务
+///
+/// 定期探测因连续失败被禁用且冷却期已过的凭据，以及额度用尽且已到上游重置时间的凭据，
+/// 探测成功后重新启用
+pub async fn start_recovery_worker(token_manager: Arc<MultiTokenManager>) {
+    tracing::info!("禁用凭据自动恢复任务启动");
+
+    loop {
+        tokio::time::sleep(RECOVERY_CHECK_INTERVAL).await;
+        token_manager.recover_disabled_credentials().await;
+    }
+}
+
+/// 启动凭据健康检查后台任务
+///
+/// 按固定间隔对每个启用（或已降级）的凭据查询一次使用额度，
+/// 失败的凭据会被降级暂停使用，连续成功后自动恢复
+pub async fn start_health_check_worker(token_manager: Arc<MultiTokenManager>, interval_secs: u64) {
+    let interval = Duration::from_secs(interval_secs);
+    tracing::info!("凭据健康检查任务启动，检查间隔: {}s", interval_secs);
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let targets = token_manager.health_check_targets();
+        tracing::debug!("开始健康检查，共 {} 个凭据", targets.len());
+        for id in targets {
+            if let Err(e) = token_manager.check_credential_health(id).await {
+                // 凭据在检查期间被删除等情况
+                tracing::debug!("凭据 #{} 健康检查跳过: {}", id, e);
+            }
+        }
+    }
+}
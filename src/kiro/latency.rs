@@ -0,0 +1,215 @@
+//! 上游请求延迟统计
+//!
+//! 按凭据记录最近若干次成功请求的首字节时间（TTFB）与总耗时，
+//! 汇总为 P50/P95/P99 与分桶直方图，供 Admin API 判断哪些上游账号较慢
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::kiro::model::credentials::CredentialId;
+
+/// 每个凭据每项指标保留的最近样本数
+const MAX_SAMPLES: usize = 1000;
+
+/// 直方图分桶上界（毫秒），最后一个桶收纳超过最大上界的样本
+const HISTOGRAM_BOUNDS_MS: [u64; 9] = [100, 250, 500, 1000, 2500, 5000, 10000, 30000, 60000];
+
+/// 直方图分桶
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistogramBucket {
+    /// 分桶上界（毫秒，包含），None 表示 +Inf
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+/// 延迟汇总（基于最近的样本窗口）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencySummary {
+    /// 窗口内样本数
+    pub count: usize,
+    pub avg_ms: f64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    pub histogram: Vec<HistogramBucket>,
+}
+
+impl LatencySummary {
+    fn from_samples<'a>(samples: impl Iterator<Item = &'a u64>) -> Self {
+        let mut sorted: Vec<u64> = samples.copied().collect();
+        sorted.sort_unstable();
+
+        let mut histogram: Vec<HistogramBucket> = HISTOGRAM_BOUNDS_MS
+            .iter()
+            .map(|le| HistogramBucket {
+                le_ms: Some(*le),
+                count: 0,
+            })
+            .chain(std::iter::once(HistogramBucket {
+                le_ms: None,
+                count: 0,
+            }))
+            .collect();
+        for value in &sorted {
+            let index = HISTOGRAM_BOUNDS_MS
+                .iter()
+                .position(|le| value <= le)
+                .unwrap_or(HISTOGRAM_BOUNDS_MS.len());
+            histogram[index].count += 1;
+        }
+
+        let avg_ms = if sorted.is_empty() {
+            0.0
+        } else {
+            sorted.iter().sum::<u64>() as f64 / sorted.len() as f64
+        };
+
+        Self {
+            count: sorted.len(),
+            avg_ms,
+            p50_ms: percentile(&sorted, 50.0),
+            p95_ms: percentile(&sorted, 95.0),
+            p99_ms: percentile(&sorted, 99.0),
+            max_ms: sorted.last().copied().unwrap_or(0),
+            histogram,
+        }
+    }
+}
+
+/// 最近邻秩百分位（`sorted` 需已升序），空样本返回 0
+pub(crate) fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// 单个凭据的延迟统计
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialLatency {
+    pub id: CredentialId,
+    /// 首字节时间（发送请求到收到响应头）
+    pub ttfb: LatencySummary,
+    /// 总耗时（发送请求到响应体读取完毕）
+    pub total: LatencySummary,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    ttfb: VecDeque<u64>,
+    total: VecDeque<u64>,
+}
+
+fn push_sample(samples: &mut VecDeque<u64>, value: Duration) {
+    if samples.len() >= MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(value.as_millis() as u64);
+}
+
+/// 按凭据的延迟样本（仅内存）
+#[derive(Debug, Default)]
+pub struct LatencyStats {
+    samples: Mutex<BTreeMap<CredentialId, Samples>>,
+}
+
+impl LatencyStats {
+    pub fn record(&self, id: CredentialId, ttfb: Duration, total: Duration) {
+        let mut samples = self.samples.lock();
+        let entry = samples.entry(id).or_default();
+        push_sample(&mut entry.ttfb, ttfb);
+        push_sample(&mut entry.total, total);
+    }
+
+    /// 移除凭据的样本（凭据删除时调用）
+    pub fn remove(&self, id: CredentialId) {
+        self.samples.lock().remove(&id);
+    }
+
+    /// 各凭据的延迟汇总（按 ID 升序，仅包含有样本的凭据）
+    pub fn per_credential(&self) -> Vec<CredentialLatency> {
+        self.samples
+            .lock()
+            .iter()
+            .map(|(id, s)| CredentialLatency {
+                id: *id,
+                ttfb: LatencySummary::from_samples(s.ttfb.iter()),
+                total: LatencySummary::from_samples(s.total.iter()),
+            })
+            .collect()
+    }
+
+    /// 所有凭据合并后的 (TTFB, 总耗时) 汇总
+    pub fn overall(&self) -> (LatencySummary, LatencySummary) {
+        let samples = self.samples.lock();
+        (
+            LatencySummary::from_samples(samples.values().flat_map(|s| s.ttfb.iter())),
+            LatencySummary::from_samples(samples.values().flat_map(|s| s.total.iter())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles_and_histogram() {
+        let stats = LatencyStats::default();
+        for ms in 1..=100 {
+            stats.record(
+                CredentialId(1),
+                Duration::from_millis(ms),
+                Duration::from_millis(ms * 100),
+            );
+        }
+        stats.record(
+            CredentialId(2),
+            Duration::from_millis(5),
+            Duration::from_millis(70000),
+        );
+
+        let per_credential = stats.per_credential();
+        assert_eq!(per_credential.len(), 2);
+        let ttfb = &per_credential[0].ttfb;
+        assert_eq!((ttfb.p50_ms, ttfb.p95_ms, ttfb.p99_ms, ttfb.max_ms), (50, 95, 99, 100));
+        assert_eq!(ttfb.avg_ms, 50.5);
+        assert_eq!(ttfb.histogram[0].count, 100);
+
+        // 总耗时 100ms..10000ms，各分桶按上界包含计数
+        let total = &per_credential[0].total;
+        assert_eq!(total.histogram[0].count, 1);
+        assert_eq!(total.histogram[6].count, 50);
+        assert_eq!(total.histogram.iter().map(|b| b.count).sum::<u64>(), 100);
+
+        let (overall_ttfb, overall_total) = stats.overall();
+        assert_eq!(overall_ttfb.count, 101);
+        assert_eq!(overall_total.histogram.last().unwrap().count, 1);
+    }
+
+    #[test]
+    fn test_sample_window_is_bounded() {
+        let stats = LatencyStats::default();
+        for ms in 0..(MAX_SAMPLES as u64 + 10) {
+            stats.record(
+                CredentialId(1),
+                Duration::from_millis(ms),
+                Duration::from_millis(ms),
+            );
+        }
+        let summary = &stats.per_credential()[0].ttfb;
+        assert_eq!(summary.count, MAX_SAMPLES);
+        assert_eq!(percentile(&[], 99.0), 0);
+
+        stats.remove(CredentialId(1));
+        assert!(stats.per_credential().is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 //! 设备指纹生成器
 //!
+//! 同时提供与官方客户端一致的设备 Machine ID 派生（见 `derive_machine_id`），
+//! 便于从真实设备迁移凭据时复现绑定的机器码
 
 use sha2::{Digest, Sha256};
 
@@ -61,6 +63,122 @@ pub fn generate_from_credentials(credentials: &KiroCredentials, config: &Config)
     None
 }
 
+/// 由设备原始标识派生 Machine ID
+///
+/// 不指定 salt 时与官方客户端一致：对标准化后的原始标识做 SHA256，输出 64 位十六进制。
+/// salt 是本项目的扩展（官方客户端不加 salt）：哈希输入为 `{salt}/{原始标识}`，
+/// 用于区分同一设备上的多个实例，结果不会与官方客户端生成的 Machine ID 相同
+pub fn derive_machine_id(raw_id: &str, salt: Option<&str>) -> String {
+    let raw = normalize_raw_id(raw_id);
+    match salt.filter(|s| !s.is_empty()) {
+        Some(salt) => sha256_hex(&format!("{}/{}", salt, raw)),
+        None => sha256_hex(&raw),
+    }
+}
+
+/// 标准化设备原始标识：去除空白、引号和等号并转为小写
+fn normalize_raw_id(raw_id: &str) -> String {
+    raw_id
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '"' && *c != '=')
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// 读取当前设备的原始标识
+///
+/// - Linux：`/var/lib/dbus/machine-id` 或 `/etc/machine-id`
+/// - macOS：`ioreg` 输出中的 `IOPlatformUUID`
+/// - Windows：注册表 `HKLM\SOFTWARE\Microsoft\Cryptography\MachineGuid`
+/// - FreeBSD：`kenv smbios.system.uuid`，回退到 `sysctl kern.hostuuid`
+pub fn read_system_id() -> anyhow::Result<String> {
+    let raw = read_platform_id()?;
+    let raw = normalize_raw_id(&raw);
+    if raw.is_empty() {
+        anyhow::bail!("设备标识为空");
+    }
+    Ok(raw)
+}
+
+#[cfg(target_os = "linux")]
+fn read_platform_id() -> anyhow::Result<String> {
+    for path in ["/var/lib/dbus/machine-id", "/etc/machine-id"] {
+        if let Ok(content) = std::fs::read_to_string(path)
+            && let Some(line) = content.lines().map(str::trim).find(|l| !l.is_empty())
+        {
+            return Ok(line.to_string());
+        }
+    }
+    anyhow::bail!("未找到 /var/lib/dbus/machine-id 或 /etc/machine-id")
+}
+
+#[cfg(target_os = "macos")]
+fn read_platform_id() -> anyhow::Result<String> {
+    let output = run_command("ioreg", &["-rd1", "-c", "IOPlatformExpertDevice"])?;
+    parse_ioreg_output(&output).ok_or_else(|| anyhow::anyhow!("ioreg 输出中未找到 IOPlatformUUID"))
+}
+
+#[cfg(target_os = "windows")]
+fn read_platform_id() -> anyhow::Result<String> {
+    let output = run_command(
+        "REG.exe",
+        &[
+            "QUERY",
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Cryptography",
+            "/v",
+            "MachineGuid",
+        ],
+    )?;
+    parse_reg_output(&output).ok_or_else(|| anyhow::anyhow!("注册表中未找到 MachineGuid"))
+}
+
+#[cfg(target_os = "freebsd")]
+fn read_platform_id() -> anyhow::Result<String> {
+    run_command("kenv", &["-q", "smbios.system.uuid"])
+        .or_else(|_| run_command("sysctl", &["-n", "kern.hostuuid"]))
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "windows",
+    target_os = "freebsd"
+)))]
+fn read_platform_id() -> anyhow::Result<String> {
+    anyhow::bail!("当前平台不支持读取设备标识，请使用 --from 指定原始标识")
+}
+
+/// 执行系统命令并返回标准输出
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "freebsd"))]
+fn run_command(program: &str, args: &[&str]) -> anyhow::Result<String> {
+    let output = std::process::Command::new(program).args(args).output()?;
+    if !output.status.success() {
+        anyhow::bail!("{} 执行失败: {}", program, output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// 从 `ioreg -rd1 -c IOPlatformExpertDevice` 输出中提取 IOPlatformUUID
+#[cfg(any(target_os = "macos", test))]
+fn parse_ioreg_output(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find(|l| l.contains("IOPlatformUUID"))
+        .and_then(|l| l.split('=').nth(1))
+        .map(|v| v.trim().trim_matches('"').to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// 从 `REG QUERY ... /v MachineGuid` 输出中提取 MachineGuid
+#[cfg(any(target_os = "windows", test))]
+fn parse_reg_output(output: &str) -> Option<String> {
+    output
+        .split("REG_SZ")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(str::to_string)
+}
+
 /// SHA256 哈希实现（返回十六进制字符串）
 fn sha256_hex(input: &str) -> String {
     let mut hasher = Sha256::new();
@@ -156,6 +274,55 @@ mod tests {
         assert!(normalize_machine_id(&"g".repeat(64)).is_none()); // 非十六进制
     }
 
+    #[test]
+    fn test_derive_machine_id_normalizes_and_salts() {
+        let plain = derive_machine_id("2582956E-CC88-4669-B546-07ADBFFCB894\n", None);
+        assert_eq!(plain, sha256_hex("2582956e-cc88-4669-b546-07adbffcb894"));
+        assert_eq!(
+            plain,
+            derive_machine_id(" 2582956e-cc88-4669-b546-07adbffcb894", Some(""))
+        );
+
+        let salted = derive_machine_id("2582956e-cc88-4669-b546-07adbffcb894", Some("kiro"));
+        assert_eq!(
+            salted,
+            sha256_hex("kiro/2582956e-cc88-4669-b546-07adbffcb894")
+        );
+        assert_ne!(salted, plain);
+    }
+
+    #[test]
+    fn test_derive_machine_id_known_values() {
+        let raw = "2582956E-CC88-4669-B546-07ADBFFCB894";
+        assert_eq!(
+            derive_machine_id(raw, None),
+            "1d470cd290bffb7cbc14bb2afa12a31a8411a1ba2906fbb5e5623088b6075396"
+        );
+        assert_eq!(
+            derive_machine_id(raw, Some("kiro")),
+            "8af4d46d030ee411efa845f8853278758437c9a0c355caed23ed2a0aafa80d3e"
+        );
+    }
+
+    #[test]
+    fn test_parse_platform_outputs() {
+        let ioreg = r#"  "IOPlatformSerialNumber" = "C02XXXXX"
+  "IOPlatformUUID" = "2582956E-CC88-4669-B546-07ADBFFCB894"
+"#;
+        assert_eq!(
+            parse_ioreg_output(ioreg).as_deref(),
+            Some("2582956E-CC88-4669-B546-07ADBFFCB894")
+        );
+
+        let reg = "\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Cryptography\r\n    \
+                   MachineGuid    REG_SZ    2582956e-cc88-4669-b546-07adbffcb894\r\n\r\n";
+        assert_eq!(
+            parse_reg_output(reg).as_deref(),
+            Some("2582956e-cc88-4669-b546-07adbffcb894")
+        );
+        assert!(parse_reg_output("ERROR").is_none());
+    }
+
     #[test]
     fn test_generate_with_uuid_machine_id() {
         let mut credentials = KiroCredentials::default();
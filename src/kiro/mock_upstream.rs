@@ -0,0 +1,162 @@
+//! 模拟上游（`kiro-rs mock-upstream`）
+//!
+//! 以 `FrameBuilder` 合成的 AWS Event Stream 响应应答 `generateAssistantResponse`，
+//! 并返回固定的 `getUsageLimits` 额度，配合配置项 `upstreamBaseUrl` 指向本服务，
+//! 可在不消耗真实额度的情况下对整个代理做压测
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::Json;
+use axum::Router;
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde_json::json;
+
+use super::parser::frame::FrameBuilder;
+
+/// 模拟响应参数
+#[derive(Debug, Clone)]
+pub struct MockUpstreamOptions {
+    /// 每个响应的文本帧数
+    pub chunks: usize,
+    /// 每个文本帧的字符数
+    pub chunk_chars: usize,
+    /// 首帧前的延迟
+    pub first_byte_delay: Duration,
+    /// 相邻帧之间的间隔
+    pub interval: Duration,
+}
+
+impl Default for MockUpstreamOptions {
+    fn default() -> Self {
+        Self {
+            chunks: 20,
+            chunk_chars: 16,
+            first_byte_delay: Duration::ZERO,
+            interval: Duration::ZERO,
+        }
+    }
+}
+
+/// 一次响应的全部帧：文本片段、计费与上下文使用率
+pub fn response_frames(options: &MockUpstreamOptions) -> Vec<Bytes> {
+    let text = "lorem ipsum ".repeat(options.chunk_chars / 12 + 1);
+    let content = &text[..options.chunk_chars];
+
+    let mut frames: Vec<Bytes> = (0..options.chunks)
+        .map(|_| {
+            FrameBuilder::event("assistantResponseEvent")
+                .json(&json!({ "content": content }))
+                .to_bytes()
+        })
+        .collect();
+    frames.push(
+        FrameBuilder::event("meteringEvent")
+            .json(&json!({ "unit": "credit", "usage": 0.01 }))
+            .to_bytes(),
+    );
+    frames.push(
+        FrameBuilder::event("contextUsageEvent")
+            .json(&json!({ "contextUsagePercentage": 1.0 }))
+            .to_bytes(),
+    );
+    frames
+}
+
+/// 模拟上游路由
+pub fn router(options: MockUpstreamOptions) -> Router {
+    Router::new()
+        .route(
+            "/generateAssistantResponse",
+            post(generate_assistant_response),
+        )
+        .route("/getUsageLimits", get(get_usage_limits))
+        .with_state(options)
+}
+
+/// 监听并运行模拟上游，直到进程退出
+pub async fn serve(addr: SocketAddr, options: MockUpstreamOptions) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("模拟上游已启动: http://{}", listener.local_addr()?);
+    axum::serve(listener, router(options)).await?;
+    Ok(())
+}
+
+async fn generate_assistant_response(State(options): State<MockUpstreamOptions>) -> Response {
+    let first_byte_delay = options.first_byte_delay;
+    let interval = options.interval;
+    let frames = response_frames(&options);
+
+    let stream =
+        futures::stream::iter(frames.into_iter().enumerate()).then(move |(i, frame)| async move {
+            let delay = if i == 0 { first_byte_delay } else { interval };
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            Ok::<_, Infallible>(frame)
+        });
+
+    (
+        [(header::CONTENT_TYPE, "application/vnd.amazon.eventstream")],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+async fn get_usage_limits() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(json!({
+            "subscriptionInfo": { "subscriptionTitle": "KIRO MOCK" },
+            "usageBreakdownList": [{
+                "currentUsage": 0,
+                "currentUsageWithPrecision": 0.0,
+                "usageLimit": 1000000,
+                "usageLimitWithPrecision": 1000000.0
+            }],
+            "userInfo": { "email": "mock@example.com" }
+        })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::events::Event;
+    use crate::kiro::parser::decoder::EventStreamDecoder;
+
+    #[test]
+    fn test_response_frames_decode() {
+        let options = MockUpstreamOptions {
+            chunks: 3,
+            chunk_chars: 5,
+            ..Default::default()
+        };
+        let mut decoder = EventStreamDecoder::new();
+        for frame in response_frames(&options) {
+            decoder.feed(&frame).unwrap();
+        }
+
+        let events: Vec<Event> = decoder
+            .decode_iter()
+            .map(|frame| Event::from_frame(frame.unwrap()).unwrap())
+            .collect();
+        assert_eq!(events.len(), 5);
+        let text: String = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::AssistantResponse(e) => Some(e.content.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "loremloremlorem");
+        assert!(matches!(events[4], Event::ContextUsage(_)));
+    }
+}
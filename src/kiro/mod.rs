@@ -1,7 +1,21 @@
 //! Kiro API 客户端模块
 
+pub mod balance_refresh;
+pub mod concurrency;
+pub mod credential_discovery;
+pub mod credential_import;
+pub mod device_login;
+pub mod drift;
+pub mod exerciser;
+pub mod health_check;
+pub mod latency;
 pub mod machine_id;
+pub mod mock_upstream;
 pub mod model;
 pub mod parser;
+pub mod peer_sync;
+pub mod priority_preset;
 pub mod provider;
+pub mod request_size;
+pub mod shared_state;
 pub mod token_manager;
@@ -3,12 +3,43 @@
 //! 支持从 Kiro IDE 的凭证文件加载，使用 Social 认证方式
 //! 支持单凭据和多凭据配置格式
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs;
 use std::path::Path;
+use std::str::FromStr;
+
+use crate::http_client::{ProxyConfig, TlsConfig};
+use crate::model::config::{Config, TlsBackend, UsageCap, UsageWatermark};
+
+/// 凭据 ID（自增分配，序列化为数字）
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct CredentialId(pub u64);
+
+impl CredentialId {
+    /// 下一个 ID
+    pub fn next(self) -> Self {
+        Self(self.0 + 1)
+    }
+}
 
-use crate::http_client::ProxyConfig;
-use crate::model::config::Config;
+impl fmt::Display for CredentialId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for CredentialId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
 
 /// Kiro OAuth 凭证
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -16,7 +47,7 @@ use crate::model::config::Config;
 pub struct KiroCredentials {
     /// 凭据唯一标识符（自增 ID）
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<u64>,
+    pub id: Option<CredentialId>,
 
     /// 访问令牌
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -30,9 +61,9 @@ pub struct KiroCredentials {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub profile_arn: Option<String>,
 
-    /// 过期时间 (RFC3339 格式)
+    /// 过期时间（文件中为 RFC3339 格式，格式错误时加载失败）
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub expires_at: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
 
     /// 认证方式 (social / idc)
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -69,10 +100,15 @@ pub struct KiroCredentials {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub machine_id: Option<String>,
 
-    /// 用户邮箱（从 Anthropic API 获取）
+    /// 用户邮箱（可手动填写，查询使用额度时以上游返回的账户邮箱为准）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
 
+    /// 上游账户 ID（查询使用额度时自动获取）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub account_id: Option<String>,
+
     /// 订阅等级（KIRO PRO+ / KIRO FREE 等）
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
@@ -93,6 +129,59 @@ pub struct KiroCredentials {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub proxy_password: Option<String>,
 
+    /// 凭据级 TLS 后端（可选，覆盖 config.json 的 tlsBackend）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_backend: Option<TlsBackend>,
+
+    /// 凭据级额外信任的 CA 证书（PEM 文件路径，可选）
+    /// 经企业 MITM 代理出站时用于信任代理签发的证书
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert_path: Option<String>,
+
+    /// 固定的上游服务端证书 SHA-256 指纹（十六进制，可含冒号；命中任一即可，仅 rustls）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pinned_cert_sha256: Vec<String>,
+
+    /// 凭据级速率限制（每分钟请求数，可选）
+    /// 未配置时回退到 config.json 的 credentialRpm；0 表示不限制
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_rpm: Option<u32>,
+
+    /// 凭据级最大并发请求数（可选）
+    /// 未配置时回退到 config.json 的 credentialMaxConcurrency；0 表示不限制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u32>,
+
+    /// 凭据级滚动窗口用量上限（可选）
+    /// 未配置时回退到 config.json 的 credentialUsageCap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_cap: Option<UsageCap>,
+
+    /// 凭据级用量警戒线（可选）
+    /// 未配置时回退到 config.json 的 usageWatermark
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_watermark: Option<UsageWatermark>,
+
+    /// 自由标签（可选），用于 Admin 筛选和按凭据池（X-Kiro-Pool 请求头）路由
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// 备注（可选，如账号来源、到期时间），仅用于 Admin 展示
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+
+    /// 模型白名单（可选，按子串匹配、不区分大小写），非空时仅处理匹配的模型
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_models: Vec<String>,
+
+    /// 模型黑名单（可选，按子串匹配、不区分大小写），优先于白名单
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_models: Vec<String>,
+
+    /// 从源实例同步而来的凭据在源实例上的 ID（多实例凭据同步按此匹配）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_source_id: Option<CredentialId>,
+
     /// 凭据是否被禁用（默认为 false）
     #[serde(default)]
     pub disabled: bool,
@@ -235,6 +324,15 @@ impl KiroCredentials {
         }
     }
 
+    /// 获取有效的出站 TLS 配置（凭据级设置覆盖全局 tlsBackend）
+    pub fn effective_tls(&self, global_backend: TlsBackend) -> TlsConfig {
+        TlsConfig {
+            backend: self.tls_backend.unwrap_or(global_backend),
+            ca_cert_path: self.ca_cert_path.clone(),
+            pinned_cert_sha256: self.pinned_cert_sha256.clone(),
+        }
+    }
+
     /// 从 JSON 字符串解析凭证
     pub fn from_json(json_string: &str) -> Result<Self, serde_json::Error> {
         serde_json::from_str(json_string)
@@ -267,6 +365,28 @@ impl KiroCredentials {
         }
     }
 
+    /// 备注的最大字符数
+    pub const MAX_NOTE_CHARS: usize = 500;
+
+    /// 规范化备注：去除首尾空白，空备注视为未设置
+    pub fn normalize_note(note: Option<String>) -> Option<String> {
+        note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty())
+    }
+
+    /// 规范化标签列表：去除首尾空白、丢弃空标签并去重（保持原有顺序）
+    pub fn normalize_tags<S: AsRef<str>>(tags: impl IntoIterator<Item = S>) -> Vec<String> {
+        let mut seen = std::collections::HashSet::new();
+        tags.into_iter()
+            .map(|t| t.as_ref().trim().to_string())
+            .filter(|t| !t.is_empty() && seen.insert(t.clone()))
+            .collect()
+    }
+
+    /// 检查凭据是否属于指定凭据池（未指定凭据池时总是属于）
+    pub fn in_pool(&self, pool: Option<&str>) -> bool {
+        pool.is_none_or(|pool| self.tags.iter().any(|t| t == pool))
+    }
+
     /// 检查凭据是否支持 Opus 模型
     ///
     /// Free 账号不支持 Opus 模型，需要 PRO 或更高等级订阅
@@ -281,6 +401,23 @@ impl KiroCredentials {
             None => true,
         }
     }
+
+    /// 检查凭据是否可以处理指定模型（未指定模型时总是可以）
+    ///
+    /// 依次检查 Opus 订阅等级、模型黑名单、模型白名单
+    pub fn supports_model(&self, model: Option<&str>) -> bool {
+        let Some(model) = model else {
+            return true;
+        };
+        let model = model.to_lowercase();
+        if model.contains("opus") && !self.supports_opus() {
+            return false;
+        }
+
+        let matches = |pattern: &String| model.contains(&pattern.to_lowercase());
+        !self.blocked_models.iter().any(matches)
+            && (self.allowed_models.is_empty() || self.allowed_models.iter().any(matches))
+    }
 }
 
 #[cfg(test)]
@@ -288,6 +425,24 @@ mod tests {
     use super::*;
     use crate::model::config::Config;
 
+    #[test]
+    fn test_supports_model_applies_allow_and_block_lists() {
+        let mut cred = KiroCredentials::default();
+        assert!(cred.supports_model(Some("claude-opus-4.5")));
+        assert!(cred.supports_model(None));
+
+        cred.allowed_models = vec!["Sonnet".to_string(), "haiku".to_string()];
+        cred.blocked_models = vec!["sonnet-4.5".to_string()];
+        assert!(cred.supports_model(Some("claude-sonnet-4")));
+        assert!(cred.supports_model(Some("claude-haiku-4.5")));
+        assert!(!cred.supports_model(Some("claude-sonnet-4.5")));
+        assert!(!cred.supports_model(Some("claude-opus-4.5")));
+
+        cred.allowed_models.clear();
+        cred.subscription_title = Some("KIRO FREE".to_string());
+        assert!(!cred.supports_model(Some("claude-opus-4.5")));
+    }
+
     #[test]
     fn test_from_json() {
         let json = r#"{
@@ -302,10 +457,29 @@ mod tests {
         assert_eq!(creds.access_token, Some("test_token".to_string()));
         assert_eq!(creds.refresh_token, Some("test_refresh".to_string()));
         assert_eq!(creds.profile_arn, Some("arn:aws:test".to_string()));
-        assert_eq!(creds.expires_at, Some("2024-01-01T00:00:00Z".to_string()));
+        assert_eq!(
+            creds.expires_at,
+            "2024-01-01T00:00:00Z".parse::<DateTime<Utc>>().ok()
+        );
         assert_eq!(creds.auth_method, Some("social".to_string()));
     }
 
+    #[test]
+    fn test_typed_id_and_expires_at() {
+        let creds =
+            KiroCredentials::from_json(r#"{"id": 7, "expiresAt": "2025-06-01T08:00:00+08:00"}"#)
+                .unwrap();
+        assert_eq!(creds.id, Some(CredentialId(7)));
+        // 带偏移的时间统一转换为 UTC，序列化后 ID 仍为数字
+        let json = creds.to_pretty_json().unwrap();
+        assert!(json.contains(r#""id": 7"#));
+        assert!(json.contains(r#""expiresAt": "2025-06-01T00:00:00Z""#));
+        assert_eq!("12".parse::<CredentialId>().unwrap().next(), CredentialId(13));
+
+        // 无效时间在加载时即被拒绝
+        assert!(KiroCredentials::from_json(r#"{"expiresAt": "tomorrow"}"#).is_err());
+    }
+
     #[test]
     fn test_from_json_with_unknown_keys() {
         let json = r#"{
@@ -334,10 +508,23 @@ mod tests {
             api_region: None,
             machine_id: None,
             email: None,
+            account_id: None,
             subscription_title: None,
             proxy_url: None,
             proxy_username: None,
             proxy_password: None,
+            tls_backend: None,
+            ca_cert_path: None,
+            pinned_cert_sha256: Vec::new(),
+            rate_limit_rpm: None,
+            max_concurrency: None,
+            usage_cap: None,
+            usage_watermark: None,
+            tags: Vec::new(),
+            note: None,
+            allowed_models: Vec::new(),
+            blocked_models: Vec::new(),
+            peer_source_id: None,
             disabled: false,
         };
 
@@ -452,10 +639,23 @@ mod tests {
             api_region: None,
             machine_id: None,
             email: None,
+            account_id: None,
             subscription_title: None,
             proxy_url: None,
             proxy_username: None,
             proxy_password: None,
+            tls_backend: None,
+            ca_cert_path: None,
+            pinned_cert_sha256: Vec::new(),
+            rate_limit_rpm: None,
+            max_concurrency: None,
+            usage_cap: None,
+            usage_watermark: None,
+            tags: Vec::new(),
+            note: None,
+            allowed_models: Vec::new(),
+            blocked_models: Vec::new(),
+            peer_source_id: None,
             disabled: false,
         };
 
@@ -482,10 +682,23 @@ mod tests {
             api_region: None,
             machine_id: None,
             email: None,
+            account_id: None,
             subscription_title: None,
             proxy_url: None,
             proxy_username: None,
             proxy_password: None,
+            tls_backend: None,
+            ca_cert_path: None,
+            pinned_cert_sha256: Vec::new(),
+            rate_limit_rpm: None,
+            max_concurrency: None,
+            usage_cap: None,
+            usage_watermark: None,
+            tags: Vec::new(),
+            note: None,
+            allowed_models: Vec::new(),
+            blocked_models: Vec::new(),
+            peer_source_id: None,
             disabled: false,
         };
 
@@ -564,11 +777,14 @@ mod tests {
         }"#;
 
         let creds = KiroCredentials::from_json(json).unwrap();
-        assert_eq!(creds.id, Some(1));
+        assert_eq!(creds.id, Some(CredentialId(1)));
         assert_eq!(creds.access_token, Some("access".to_string()));
         assert_eq!(creds.refresh_token, Some("refresh".to_string()));
         assert_eq!(creds.profile_arn, Some("arn:aws:test".to_string()));
-        assert_eq!(creds.expires_at, Some("2025-12-31T00:00:00Z".to_string()));
+        assert_eq!(
+            creds.expires_at,
+            "2025-12-31T00:00:00Z".parse::<DateTime<Utc>>().ok()
+        );
         assert_eq!(creds.auth_method, Some("idc".to_string()));
         assert_eq!(creds.client_id, Some("client123".to_string()));
         assert_eq!(creds.client_secret, Some("secret456".to_string()));
@@ -580,7 +796,7 @@ mod tests {
     fn test_region_roundtrip() {
         // 测试序列化和反序列化的往返一致性
         let original = KiroCredentials {
-            id: Some(42),
+            id: Some(CredentialId(42)),
             access_token: Some("token".to_string()),
             refresh_token: Some("refresh".to_string()),
             profile_arn: None,
@@ -594,10 +810,23 @@ mod tests {
             api_region: None,
             machine_id: Some("c".repeat(64)),
             email: None,
+            account_id: None,
             subscription_title: None,
             proxy_url: None,
             proxy_username: None,
             proxy_password: None,
+            tls_backend: None,
+            ca_cert_path: None,
+            pinned_cert_sha256: Vec::new(),
+            rate_limit_rpm: None,
+            max_concurrency: None,
+            usage_cap: None,
+            usage_watermark: None,
+            tags: Vec::new(),
+            note: None,
+            allowed_models: Vec::new(),
+            blocked_models: Vec::new(),
+            peer_source_id: None,
             disabled: false,
         };
 
@@ -2,6 +2,7 @@
 //!
 //! 定义事件类型枚举、trait 和统一事件结构
 
+use crate::kiro::drift::{self, DriftKind};
 use crate::kiro::parser::error::{ParseError, ParseResult};
 use crate::kiro::parser::frame::Frame;
 
@@ -16,6 +17,8 @@ pub enum EventType {
     Metering,
     /// 上下文使用率事件
     ContextUsage,
+    /// 推理内容事件
+    ReasoningContent,
     /// 未知事件类型
     Unknown,
 }
@@ -28,6 +31,7 @@ impl EventType {
             "toolUseEvent" => Self::ToolUse,
             "meteringEvent" => Self::Metering,
             "contextUsageEvent" => Self::ContextUsage,
+            "reasoningContentEvent" => Self::ReasoningContent,
             _ => Self::Unknown,
         }
     }
@@ -39,6 +43,7 @@ impl EventType {
             Self::ToolUse => "toolUseEvent",
             Self::Metering => "meteringEvent",
             Self::ContextUsage => "contextUsageEvent",
+            Self::ReasoningContent => "reasoningContentEvent",
             Self::Unknown => "unknown",
         }
     }
@@ -58,6 +63,15 @@ pub trait EventPayload: Sized {
     fn from_frame(frame: &Frame) -> ParseResult<Self>;
 }
 
+/// 内容策略（guardrail）拦截相关的错误码 / 异常类型关键字（小写匹配）
+const CONTENT_FILTER_MARKERS: [&str; 5] = [
+    "guardrail",
+    "contentfilter",
+    "content_filter",
+    "contentpolicy",
+    "responsibleai",
+];
+
 /// 统一事件枚举
 ///
 /// 封装所有可能的事件类型
@@ -71,6 +85,8 @@ pub enum Event {
     Metering(()),
     /// 上下文使用率
     ContextUsage(super::ContextUsageEvent),
+    /// 推理内容
+    ReasoningContent(super::ReasoningContentEvent),
     /// 未知事件 (保留原始帧数据)
     Unknown {},
     /// 服务端错误
@@ -90,15 +106,39 @@ pub enum Event {
 }
 
 impl Event {
-    /// 从帧解析事件
+    /// 是否为上游内容策略拦截事件
+    ///
+    /// 上游以 error / exception 帧告知输出被内容策略拦截，按错误码或异常类型识别
+    pub fn is_content_filter(&self) -> bool {
+        let kind = match self {
+            Self::Error { error_code, .. } => error_code,
+            Self::Exception { exception_type, .. } => exception_type,
+            _ => return false,
+        };
+        let kind = kind.to_ascii_lowercase();
+        CONTENT_FILTER_MARKERS
+            .iter()
+            .any(|marker| kind.contains(marker))
+    }
+
+    /// 从帧解析事件（同时记录协议漂移统计）
     pub fn from_frame(frame: Frame) -> ParseResult<Self> {
         let message_type = frame.message_type().unwrap_or("event");
+        drift::tracker().record_frame();
 
         match message_type {
             "event" => Self::parse_event(frame),
             "error" => Self::parse_error(frame),
             "exception" => Self::parse_exception(frame),
-            other => Err(ParseError::InvalidMessageType(other.to_string())),
+            other => {
+                drift::tracker().record(
+                    DriftKind::UnknownMessageType,
+                    other,
+                    "",
+                    &frame.payload,
+                );
+                Err(ParseError::InvalidMessageType(other.to_string()))
+            }
         }
     }
 
@@ -106,21 +146,49 @@ impl Event {
     fn parse_event(frame: Frame) -> ParseResult<Self> {
         let event_type_str = frame.event_type().unwrap_or("unknown");
         let event_type = EventType::from_str(event_type_str);
+        let result = Self::parse_payload(event_type, &frame);
+
+        let tracker = drift::tracker();
+        match &result {
+            Ok(Self::Unknown {}) => {
+                tracker.record(
+                    DriftKind::UnknownEventType,
+                    event_type_str,
+                    "",
+                    &frame.payload,
+                );
+            }
+            Ok(_) => tracker.check_fields(event_type_str, &frame.payload),
+            Err(e) => tracker.record(
+                DriftKind::SchemaError,
+                event_type_str,
+                &e.to_string(),
+                &frame.payload,
+            ),
+        }
+        result
+    }
 
+    /// 按事件类型解析负载
+    fn parse_payload(event_type: EventType, frame: &Frame) -> ParseResult<Self> {
         match event_type {
             EventType::AssistantResponse => {
-                let payload = super::AssistantResponseEvent::from_frame(&frame)?;
+                let payload = super::AssistantResponseEvent::from_frame(frame)?;
                 Ok(Self::AssistantResponse(payload))
             }
             EventType::ToolUse => {
-                let payload = super::ToolUseEvent::from_frame(&frame)?;
+                let payload = super::ToolUseEvent::from_frame(frame)?;
                 Ok(Self::ToolUse(payload))
             }
             EventType::Metering => Ok(Self::Metering(())),
             EventType::ContextUsage => {
-                let payload = super::ContextUsageEvent::from_frame(&frame)?;
+                let payload = super::ContextUsageEvent::from_frame(frame)?;
                 Ok(Self::ContextUsage(payload))
             }
+            EventType::ReasoningContent => {
+                let payload = super::ReasoningContentEvent::from_frame(frame)?;
+                Ok(Self::ReasoningContent(payload))
+            }
             EventType::Unknown => Ok(Self::Unknown {}),
         }
     }
@@ -172,6 +240,10 @@ mod tests {
             EventType::from_str("contextUsageEvent"),
             EventType::ContextUsage
         );
+        assert_eq!(
+            EventType::from_str("reasoningContentEvent"),
+            EventType::ReasoningContent
+        );
         assert_eq!(EventType::from_str("unknown_type"), EventType::Unknown);
     }
 
@@ -183,4 +255,22 @@ mod tests {
         );
         assert_eq!(EventType::ToolUse.as_str(), "toolUseEvent");
     }
+
+    #[test]
+    fn test_is_content_filter() {
+        let exception = |kind: &str| Event::Exception {
+            exception_type: kind.to_string(),
+            message: String::new(),
+        };
+        assert!(exception("GuardrailInterventionException").is_content_filter());
+        assert!(
+            Event::Error {
+                error_code: "ContentPolicyViolation".to_string(),
+                error_message: String::new(),
+            }
+            .is_content_filter()
+        );
+        assert!(!exception("ContentLengthExceededException").is_content_filter());
+        assert!(!Event::Unknown {}.is_content_filter());
+    }
 }
@@ -0,0 +1,36 @@
+//! 推理内容事件
+//!
+//! 处理 reasoningContentEvent 类型的事件
+
+use serde::Deserialize;
+
+use crate::kiro::parser::error::ParseResult;
+use crate::kiro::parser::frame::Frame;
+
+use super::base::EventPayload;
+
+/// 推理内容事件
+///
+/// 上游以独立事件流式输出的模型推理（thinking）片段，签名通常随最后一个片段到达
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReasoningContentEvent {
+    /// 推理文本片段
+    #[serde(default)]
+    pub text: String,
+    /// 推理块签名
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl EventPayload for ReasoningContentEvent {
+    fn from_frame(frame: &Frame) -> ParseResult<Self> {
+        frame.payload_as_json()
+    }
+}
+
+impl std::fmt::Display for ReasoningContentEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
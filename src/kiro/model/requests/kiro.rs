@@ -14,7 +14,8 @@ use super::conversation::ConversationState;
 ///
 /// ```rust
 /// use kiro_rs::kiro::model::requests::{
-///     KiroRequest, ConversationState, CurrentMessage, UserInputMessage, Tool
+///     conversation::{ConversationState, CurrentMessage, UserInputMessage},
+///     kiro::KiroRequest,
 /// };
 ///
 /// // 创建简单请求
@@ -24,8 +25,11 @@ use super::conversation::ConversationState;
 ///         UserInputMessage::new("Hello", "claude-3-5-sonnet")
 ///     ));
 ///
-/// let request = KiroRequest::new(state);
-/// let json = request.to_json().unwrap();
+/// let request = KiroRequest {
+///     conversation_state: state,
+///     profile_arn: None,
+/// };
+/// let json = serde_json::to_string(&request).unwrap();
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -42,3 +42,63 @@ pub struct IdcRefreshResponse {
     #[serde(default)]
     pub expires_in: Option<i64>,
 }
+
+/// OIDC 客户端注册请求体（设备授权登录）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterClientRequest {
+    pub client_name: String,
+    pub client_type: String,
+    pub scopes: Vec<String>,
+}
+
+/// OIDC 客户端注册响应体
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterClientResponse {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// 设备授权请求体
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceAuthorizationRequest {
+    pub client_id: String,
+    pub client_secret: String,
+    pub start_url: String,
+}
+
+/// 设备授权响应体
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceAuthorizationResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    /// 设备码有效期（秒）
+    pub expires_in: u64,
+    /// 轮询间隔（秒）
+    #[serde(default)]
+    pub interval: Option<u64>,
+}
+
+/// 设备码换取 Token 的请求体
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceTokenRequest {
+    pub client_id: String,
+    pub client_secret: String,
+    pub device_code: String,
+    pub grant_type: String,
+}
+
+/// OIDC 错误响应体（轮询中的 authorization_pending / slow_down 等）
+#[derive(Debug, Deserialize)]
+pub struct OidcErrorResponse {
+    pub error: String,
+    #[serde(default)]
+    pub error_description: Option<String>,
+}
@@ -2,10 +2,10 @@
 //!
 //! 包含 getUsageLimits API 的响应类型定义
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// 使用额度查询响应
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UsageLimitsResponse {
     /// 下次重置日期 (Unix 时间戳)
@@ -19,10 +19,27 @@ pub struct UsageLimitsResponse {
     /// 使用量明细列表
     #[serde(default)]
     pub usage_breakdown_list: Vec<UsageBreakdown>,
+
+    /// 账户信息（请求带 `isEmailRequired=true` 时返回）
+    #[serde(default)]
+    pub user_info: Option<UserInfo>,
+}
+
+/// 账户信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserInfo {
+    /// 账户邮箱
+    #[serde(default)]
+    pub email: Option<String>,
+
+    /// 账户 ID
+    #[serde(default)]
+    pub user_id: Option<String>,
 }
 
 /// 订阅信息
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SubscriptionInfo {
     /// 订阅标题 (KIRO PRO+ / KIRO FREE 等)
@@ -31,7 +48,7 @@ pub struct SubscriptionInfo {
 }
 
 /// 使用量明细
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UsageBreakdown {
     /// 当前使用量
@@ -64,7 +81,7 @@ pub struct UsageBreakdown {
 }
 
 /// 奖励额度
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Bonus {
     /// 当前使用量
@@ -91,7 +108,7 @@ impl Bonus {
 }
 
 /// 免费试用信息
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FreeTrialInfo {
     /// 当前使用量
@@ -139,11 +156,41 @@ impl UsageLimitsResponse {
             .and_then(|info| info.subscription_title.as_deref())
     }
 
+    /// 获取账户邮箱
+    pub fn email(&self) -> Option<&str> {
+        self.user_info
+            .as_ref()
+            .and_then(|info| info.email.as_deref())
+            .filter(|s| !s.is_empty())
+    }
+
+    /// 获取账户 ID
+    pub fn user_id(&self) -> Option<&str> {
+        self.user_info
+            .as_ref()
+            .and_then(|info| info.user_id.as_deref())
+            .filter(|s| !s.is_empty())
+    }
+
+    /// 下次额度重置时间（Unix 时间戳），顶层缺失时使用第一个使用量明细中的值
+    pub fn next_reset_at(&self) -> Option<f64> {
+        self.next_date_reset
+            .or_else(|| self.primary_breakdown().and_then(|b| b.next_date_reset))
+    }
+
     /// 获取第一个使用量明细
     fn primary_breakdown(&self) -> Option<&UsageBreakdown> {
         self.usage_breakdown_list.first()
     }
 
+    /// 免费试用是否处于激活状态
+    pub fn free_trial_active(&self) -> bool {
+        self.primary_breakdown()
+            .and_then(|b| b.free_trial_info.as_ref())
+            .map(|t| t.is_active())
+            .unwrap_or(false)
+    }
+
     /// 获取总使用限额（精确值）
     ///
     /// 累加基础额度、激活的免费试用额度和激活的奖励额度
@@ -0,0 +1,131 @@
+//! AWS Event Stream 编解码器（`tokio_util::codec`）
+//!
+//! 解码基于 `EventStreamDecoder` 的状态机：可恢复的错误（CRC 失败、垃圾数据等）在内部跳过并记录日志，
+//! 只有解码器停止或缓冲区超限时才返回错误。`FramedRead` 在解码返回错误后会结束流，
+//! 因此不能把可恢复的错误交给上层。
+//!
+//! ```ignore
+//! let frames = FramedRead::new(reader, EventStreamCodec::new(decoder));
+//! ```
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::decoder::EventStreamDecoder;
+use super::error::ParseError;
+use super::frame::Frame;
+
+/// 事件流编解码器
+#[derive(Default)]
+pub struct EventStreamCodec {
+    decoder: EventStreamDecoder,
+}
+
+impl EventStreamCodec {
+    pub fn new(decoder: EventStreamDecoder) -> Self {
+        Self { decoder }
+    }
+
+    /// 底层解码器（读取解码统计）
+    pub fn decoder(&self) -> &EventStreamDecoder {
+        &self.decoder
+    }
+}
+
+impl From<EventStreamDecoder> for EventStreamCodec {
+    fn from(decoder: EventStreamDecoder) -> Self {
+        Self::new(decoder)
+    }
+}
+
+impl Decoder for EventStreamCodec {
+    type Item = Frame;
+    type Error = ParseError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, ParseError> {
+        self.decoder.on_buffered(src.len())?;
+
+        // 解码器直接在 FramedRead 的读缓冲区上工作，结束后换回剩余数据
+        std::mem::swap(self.decoder.buffer_mut(), src);
+        let result = loop {
+            match self.decoder.decode() {
+                Ok(frame) => break Ok(frame),
+                Err(e) if self.decoder.is_stopped() => break Err(e),
+                Err(e) => tracing::warn!("解码事件失败: {}", e),
+            }
+        };
+        std::mem::swap(self.decoder.buffer_mut(), src);
+        result
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, ParseError> {
+        let frame = self.decode(src)?;
+        if frame.is_none() && !src.is_empty() {
+            tracing::warn!("流末尾有 {} 字节未构成完整帧，已丢弃", src.len());
+            src.clear();
+        }
+        Ok(frame)
+    }
+}
+
+impl Encoder<Frame> for EventStreamCodec {
+    type Error = ParseError;
+
+    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> Result<(), ParseError> {
+        frame.encode(dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::parser::frame::FrameBuilder;
+    use crate::kiro::parser::header::HeaderValue;
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    fn event(payload: &'static str) -> Frame {
+        FrameBuilder::event("assistantResponseEvent")
+            .header("x-seq", HeaderValue::Integer(7))
+            .payload(payload)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_codec_round_trip_skips_garbage() {
+        let mut encoded = Vec::new();
+        let mut writer = FramedWrite::new(&mut encoded, EventStreamCodec::default());
+        writer.send(event(r#"{"content":"a"}"#)).await.unwrap();
+        drop(writer);
+        // 两帧之间夹杂垃圾数据，末尾是不完整的帧
+        encoded.extend_from_slice(&[0xff; 40]);
+        let mut writer = FramedWrite::new(&mut encoded, EventStreamCodec::default());
+        writer.send(event(r#"{"content":"b"}"#)).await.unwrap();
+        drop(writer);
+        encoded.extend_from_slice(&[0, 0, 0, 64, 0, 0]);
+
+        let frames: Vec<Frame> = FramedRead::new(&encoded[..], EventStreamCodec::default())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].event_type(), Some("assistantResponseEvent"));
+        assert_eq!(
+            frames[0].headers.get("x-seq"),
+            Some(&HeaderValue::Integer(7))
+        );
+        assert_eq!(frames[1].payload_as_str(), r#"{"content":"b"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_codec_stops_after_too_many_errors() {
+        let codec = EventStreamCodec::new(EventStreamDecoder::with_config(64, 1, 1024));
+        let data = [0xffu8; 32];
+        let mut frames = FramedRead::new(&data[..], codec);
+        assert!(matches!(
+            frames.next().await,
+            Some(Err(ParseError::TooManyErrors { .. }))
+        ));
+        assert!(frames.next().await.is_none());
+    }
+}
@@ -30,9 +30,11 @@
 //!                  └────────────┘
 //! ```
 
-use super::error::{ParseError, ParseResult};
-use super::frame::{Frame, PRELUDE_SIZE, parse_frame};
-use bytes::{Buf, BytesMut};
+use super::crc::crc32;
+use super::error::{HEXDUMP_MAX_BYTES, ParseError, ParseResult, hexdump};
+use super::frame::{Frame, MAX_MESSAGE_SIZE, MIN_MESSAGE_SIZE, PRELUDE_SIZE, check_frame};
+use super::stats;
+use bytes::{Buf, Bytes, BytesMut};
 
 /// 默认最大缓冲区大小 (16 MB)
 pub const DEFAULT_MAX_BUFFER_SIZE: usize = 16 * 1024 * 1024;
@@ -43,6 +45,12 @@ pub const DEFAULT_MAX_ERRORS: usize = 5;
 /// 默认初始缓冲区容量
 pub const DEFAULT_BUFFER_CAPACITY: usize = 8192;
 
+/// 缓冲区容量超过该值且大部分已消费时收缩（大帧解析完成后释放内存）
+const SHRINK_THRESHOLD: usize = 1024 * 1024;
+
+/// 单次错误恢复最多向后扫描的字节数，超出部分留给下一次恢复
+pub const MAX_RESYNC_SCAN: usize = 64 * 1024;
+
 /// 解码器状态
 ///
 /// 采用四态模型，参考 kiro-kt 的设计：
@@ -99,6 +107,16 @@ pub struct EventStreamDecoder {
     max_buffer_size: usize,
     /// 跳过的字节数（用于调试）
     bytes_skipped: usize,
+    /// 缓冲区起始位置在响应流中的偏移
+    stream_offset: usize,
+    /// 解析失败时是否附带偏移与 hexdump
+    error_context: bool,
+    /// CRC 校验失败时是否仍交付帧（宽松模式）
+    lenient_crc: bool,
+    /// 本解码器遇到的 CRC 校验失败帧数
+    corrupt_frames: usize,
+    /// 缓冲区达到过的最大字节数
+    buffer_high_water: usize,
 }
 
 impl Default for EventStreamDecoder {
@@ -123,6 +141,11 @@ impl EventStreamDecoder {
             max_errors: DEFAULT_MAX_ERRORS,
             max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
             bytes_skipped: 0,
+            stream_offset: 0,
+            error_context: false,
+            lenient_crc: false,
+            corrupt_frames: 0,
+            buffer_high_water: 0,
         }
     }
 
@@ -136,25 +159,94 @@ impl EventStreamDecoder {
             max_errors,
             max_buffer_size,
             bytes_skipped: 0,
+            stream_offset: 0,
+            error_context: false,
+            lenient_crc: false,
+            corrupt_frames: 0,
+            buffer_high_water: 0,
+        }
+    }
+
+    /// 解析失败时附带出错位置的流偏移与有界 hexdump（`ParseError::WithContext`），
+    /// 调用方记录错误日志时一并输出，用于诊断损坏的响应流
+    pub fn with_error_context(mut self, enabled: bool) -> Self {
+        self.error_context = enabled;
+        self
+    }
+
+    /// 设置缓冲区上限：未完成的帧使缓冲区超过上限时 `feed` 返回 `BufferOverflow`
+    pub fn with_max_buffer_size(mut self, max_buffer_size: usize) -> Self {
+        self.max_buffer_size = max_buffer_size;
+        self
+    }
+
+    /// 宽松 CRC 模式：Prelude / Message CRC 校验失败时记录警告并照常交付帧，而不是报错跳过
+    ///
+    /// 长度与头部仍需有效，用于上游或中间代理改写了帧内容但载荷仍可用的场景
+    pub fn with_lenient_crc(mut self, enabled: bool) -> Self {
+        self.lenient_crc = enabled;
+        self
+    }
+
+    /// 丢弃缓冲区头部的字节并推进流偏移
+    fn consume(&mut self, len: usize) {
+        // advance 之后 capacity 只统计剩余部分，需在之前取得
+        let capacity = self.buffer.capacity();
+        self.buffer.advance(len);
+        self.consumed(len, capacity);
+    }
+
+    /// 从缓冲区头部切出一个完整的帧（与缓冲区共享内存，不复制）
+    fn split_frame(&mut self, len: usize) -> Bytes {
+        let capacity = self.buffer.capacity();
+        let frame = self.buffer.split_to(len).freeze();
+        self.consumed(len, capacity);
+        frame
+    }
+
+    fn consumed(&mut self, len: usize, capacity: usize) {
+        self.stream_offset += len;
+        if capacity > SHRINK_THRESHOLD && self.buffer.len() < capacity / 4 {
+            self.shrink();
         }
     }
 
+    /// 大帧解析完成后缓冲区占用的内存可能远超剩余数据，复制剩余数据到较小的缓冲区以释放内存
+    ///
+    /// `BytesMut` 没有 `shrink_to_fit`，advance 掉的前部空间只在再次扩容时才可能被复用
+    fn shrink(&mut self) {
+        let capacity = self.buffer.len().max(DEFAULT_BUFFER_CAPACITY);
+        let mut shrunk = BytesMut::with_capacity(capacity);
+        shrunk.extend_from_slice(&self.buffer);
+        self.buffer = shrunk;
+    }
+
     /// 向解码器提供数据
     ///
     /// # Returns
     /// - `Ok(())` - 数据已添加到缓冲区
     /// - `Err(BufferOverflow)` - 缓冲区已满
     pub fn feed(&mut self, data: &[u8]) -> ParseResult<()> {
+        self.on_buffered(self.buffer.len() + data.len())?;
+        self.buffer.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// 缓冲数据增长到 `len` 字节：检查上限、更新高水位，并从 Recovering 回到 Ready
+    pub(super) fn on_buffered(&mut self, len: usize) -> ParseResult<()> {
         // 检查缓冲区大小限制
-        let new_size = self.buffer.len() + data.len();
-        if new_size > self.max_buffer_size {
+        if len > self.max_buffer_size {
+            stats::buffers().record_overflow();
             return Err(ParseError::BufferOverflow {
-                size: new_size,
+                size: len,
                 max: self.max_buffer_size,
             });
         }
 
-        self.buffer.extend_from_slice(data);
+        if len > self.buffer_high_water {
+            self.buffer_high_water = len;
+            stats::buffers().record_len(len);
+        }
 
         // 从 Recovering 状态恢复到 Ready
         if self.state == DecoderState::Recovering {
@@ -164,6 +256,11 @@ impl EventStreamDecoder {
         Ok(())
     }
 
+    /// 内部缓冲区（编解码器与 `FramedRead` 的读缓冲区交换使用）
+    pub(super) fn buffer_mut(&mut self) -> &mut BytesMut {
+        &mut self.buffer
+    }
+
     /// 尝试解码下一个帧
     ///
     /// # Returns
@@ -188,46 +285,107 @@ impl EventStreamDecoder {
         // 转移到 Parsing 状态
         self.state = DecoderState::Parsing;
 
-        match parse_frame(&self.buffer) {
-            Ok(Some((frame, consumed))) => {
-                // 成功解析
-                self.buffer.advance(consumed);
-                self.state = DecoderState::Ready;
-                self.frames_decoded += 1;
-                self.error_count = 0; // 重置连续错误计数
-                Ok(Some(frame))
-            }
+        let bounds = match check_frame(&self.buffer, self.lenient_crc) {
+            Ok(Some(bounds)) => bounds,
             Ok(None) => {
                 // 数据不足，回到 Ready 状态等待更多数据
                 self.state = DecoderState::Ready;
-                Ok(None)
+                return Ok(None);
             }
             Err(e) => {
-                self.error_count += 1;
-                let error_msg = e.to_string();
-
-                // 检查是否超过最大错误数
-                if self.error_count >= self.max_errors {
-                    self.state = DecoderState::Stopped;
-                    tracing::error!(
-                        "解码器停止: 连续 {} 次错误，最后错误: {}",
-                        self.error_count,
-                        error_msg
+                // 在恢复逻辑跳过字节之前截取出错位置的上下文
+                let context = self.context_at(&self.buffer, self.stream_offset);
+                return self.fail(e, context, true);
+            }
+        };
+
+        // 校验通过，整帧切出后头部与负载都引用这段内存
+        let offset = self.stream_offset;
+        let bytes = self.split_frame(bounds.total_length);
+        match Frame::from_bytes(bytes.clone(), bounds.headers_end) {
+            Ok(frame) => {
+                // 成功解析（宽松模式下可能带有被容忍的 CRC 校验失败）
+                let mismatch = bounds.mismatch;
+                if mismatch.any() {
+                    self.corrupt_frames += 1;
+                    stats::corrupt_frames().record(mismatch.prelude, mismatch.message, true);
+                    tracing::warn!(
+                        "帧 CRC 校验失败（宽松模式，照常处理）: 偏移 {}，{:?}",
+                        offset,
+                        mismatch
                     );
-                    return Err(ParseError::TooManyErrors {
-                        count: self.error_count,
-                        last_error: error_msg,
-                    });
                 }
-
-                // 根据错误类型采用不同的恢复策略
-                self.try_recover(&e);
-                self.state = DecoderState::Recovering;
-                Err(e)
+                self.state = DecoderState::Ready;
+                self.frames_decoded += 1;
+                self.error_count = 0; // 重置连续错误计数
+                Ok(Some(frame))
+            }
+            Err(e) => {
+                // 帧边界可信但头部损坏，整帧已切出，相当于跳过该帧
+                tracing::warn!("头部解析失败: 跳过损坏帧 ({} 字节)", bounds.total_length);
+                self.bytes_skipped += bounds.total_length;
+                let context = self.context_at(&bytes, offset);
+                self.fail(e, context, false)
             }
         }
     }
 
+    /// 出错位置的上下文（偏移与十六进制转储），未开启时返回 None
+    fn context_at(&self, data: &[u8], offset: usize) -> Option<(usize, String)> {
+        self.error_context.then(|| {
+            let len = data.len().min(HEXDUMP_MAX_BYTES);
+            (offset, hexdump(&data[..len], offset))
+        })
+    }
+
+    /// 记录一次解码错误，`recover` 为 true 时按错误类型跳过损坏数据
+    fn fail(
+        &mut self,
+        e: ParseError,
+        context: Option<(usize, String)>,
+        recover: bool,
+    ) -> ParseResult<Option<Frame>> {
+        self.error_count += 1;
+        let error_msg = e.to_string();
+        let (prelude, message) = match &e {
+            ParseError::PreludeCrcMismatch { .. } => (true, false),
+            ParseError::MessageCrcMismatch { .. } => (false, true),
+            _ => (false, false),
+        };
+        if prelude || message {
+            self.corrupt_frames += 1;
+            stats::corrupt_frames().record(prelude, message, false);
+        }
+
+        // 检查是否超过最大错误数
+        if self.error_count >= self.max_errors {
+            self.state = DecoderState::Stopped;
+            tracing::error!(
+                "解码器停止: 连续 {} 次错误，最后错误: {}",
+                self.error_count,
+                error_msg
+            );
+            return Err(ParseError::TooManyErrors {
+                count: self.error_count,
+                last_error: error_msg,
+            });
+        }
+
+        // 根据错误类型采用不同的恢复策略
+        if recover {
+            self.try_recover(&e);
+        }
+        self.state = DecoderState::Recovering;
+        match context {
+            Some((offset, hexdump)) => Err(ParseError::WithContext {
+                error: Box::new(e),
+                offset,
+                hexdump,
+            }),
+            None => Err(e),
+        }
+    }
+
     /// 创建解码迭代器
     pub fn decode_iter(&mut self) -> DecodeIter<'_> {
         DecodeIter { decoder: self }
@@ -236,7 +394,7 @@ impl EventStreamDecoder {
     /// 尝试容错恢复
     ///
     /// 根据错误类型采用不同的恢复策略（参考 kiro-kt 的设计）：
-    /// - Prelude 阶段错误（CRC 失败、长度异常）：跳过 1 字节，尝试找下一帧边界
+    /// - Prelude 阶段错误（CRC 失败、长度异常）：向后扫描下一个可能的帧边界，一次丢弃中间的字节
     /// - Data 阶段错误（Message CRC 失败、Header 解析失败）：跳过整个损坏帧
     fn try_recover(&mut self, error: &ParseError) {
         if self.buffer.is_empty() {
@@ -244,18 +402,11 @@ impl EventStreamDecoder {
         }
 
         match error {
-            // Prelude 阶段错误：可能是帧边界错位，逐字节扫描找下一个有效边界
+            // Prelude 阶段错误：可能是帧边界错位，扫描找下一个有效边界
             ParseError::PreludeCrcMismatch { .. }
             | ParseError::MessageTooSmall { .. }
             | ParseError::MessageTooLarge { .. } => {
-                let skipped_byte = self.buffer[0];
-                self.buffer.advance(1);
-                self.bytes_skipped += 1;
-                tracing::warn!(
-                    "Prelude 错误恢复: 跳过字节 0x{:02x} (累计跳过 {} 字节)",
-                    skipped_byte,
-                    self.bytes_skipped
-                );
+                self.resync("Prelude 错误恢复");
             }
 
             // Data 阶段错误：帧边界正确但数据损坏，跳过整个帧
@@ -272,37 +423,36 @@ impl EventStreamDecoder {
                     // 确保 total_length 合理且缓冲区有足够数据
                     if total_length >= 16 && total_length <= self.buffer.len() {
                         tracing::warn!("Data 错误恢复: 跳过损坏帧 ({} 字节)", total_length);
-                        self.buffer.advance(total_length);
+                        self.consume(total_length);
                         self.bytes_skipped += total_length;
                         return;
                     }
                 }
 
-                // 无法确定帧长度，回退到逐字节跳过
-                let skipped_byte = self.buffer[0];
-                self.buffer.advance(1);
-                self.bytes_skipped += 1;
-                tracing::warn!(
-                    "Data 错误恢复 (回退): 跳过字节 0x{:02x} (累计跳过 {} 字节)",
-                    skipped_byte,
-                    self.bytes_skipped
-                );
+                // 无法确定帧长度，回退到扫描下一个帧边界
+                self.resync("Data 错误恢复 (回退)");
             }
 
-            // 其他错误：逐字节跳过
-            _ => {
-                let skipped_byte = self.buffer[0];
-                self.buffer.advance(1);
-                self.bytes_skipped += 1;
-                tracing::warn!(
-                    "通用错误恢复: 跳过字节 0x{:02x} (累计跳过 {} 字节)",
-                    skipped_byte,
-                    self.bytes_skipped
-                );
-            }
+            // 其他错误：扫描下一个帧边界
+            _ => self.resync("通用错误恢复"),
         }
     }
 
+    /// 丢弃缓冲区头部直到下一个可能的帧边界
+    ///
+    /// 逐字节跳过在大段垃圾数据上是 O(n²)（每跳一字节都要重新解析），这里一次扫描定位后整体丢弃
+    fn resync(&mut self, reason: &str) {
+        let skip = resync_skip(&self.buffer);
+        self.consume(skip);
+        self.bytes_skipped += skip;
+        tracing::warn!(
+            "{}: 跳过 {} 字节 (累计跳过 {} 字节)",
+            reason,
+            skip,
+            self.bytes_skipped
+        );
+    }
+
     // ==================== 生命周期管理方法 ====================
 
     /// 重置解码器到初始状态
@@ -314,6 +464,9 @@ impl EventStreamDecoder {
         self.frames_decoded = 0;
         self.error_count = 0;
         self.bytes_skipped = 0;
+        self.stream_offset = 0;
+        self.corrupt_frames = 0;
+        self.buffer_high_water = 0;
     }
 
     /// 获取当前状态
@@ -351,6 +504,16 @@ impl EventStreamDecoder {
         self.bytes_skipped
     }
 
+    /// 获取 CRC 校验失败的帧数（包括宽松模式下照常交付的帧）
+    pub fn corrupt_frames(&self) -> usize {
+        self.corrupt_frames
+    }
+
+    /// 获取缓冲区达到过的最大字节数
+    pub fn buffer_high_water(&self) -> usize {
+        self.buffer_high_water
+    }
+
     /// 获取缓冲区中待处理的字节数
     pub fn buffer_len(&self) -> usize {
         self.buffer.len()
@@ -369,6 +532,34 @@ impl EventStreamDecoder {
     }
 }
 
+/// 计算重新同步需要丢弃的字节数
+///
+/// 从偏移 1 起查找可能的 Prelude（长度合理且 Prelude CRC 匹配）。未找到时丢弃已扫描的区域，
+/// 但保留末尾放不下完整 Prelude 的字节（可能是下一帧的开头，等待更多数据）；
+/// 扫描范围受 `MAX_RESYNC_SCAN` 限制。至少丢弃 1 字节以保证前进
+fn resync_skip(buffer: &[u8]) -> usize {
+    let last_start = buffer
+        .len()
+        .saturating_sub(PRELUDE_SIZE)
+        .min(MAX_RESYNC_SCAN);
+    (1..=last_start)
+        .find(|&i| is_plausible_prelude(&buffer[i..]))
+        .unwrap_or(last_start + 1)
+}
+
+/// 是否为可能的帧起始：total_length 在有效范围内、header_length 不超出消息边界、Prelude CRC 匹配
+fn is_plausible_prelude(data: &[u8]) -> bool {
+    if data.len() < PRELUDE_SIZE {
+        return false;
+    }
+    let total_length = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    let header_length = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    let prelude_crc = u32::from_be_bytes([data[8], data[9], data[10], data[11]]);
+    (MIN_MESSAGE_SIZE as u32..=MAX_MESSAGE_SIZE).contains(&total_length)
+        && header_length <= total_length - MIN_MESSAGE_SIZE as u32
+        && crc32(&data[..8]) == prelude_crc
+}
+
 /// 解码迭代器
 pub struct DecodeIter<'a> {
     decoder: &'a mut EventStreamDecoder,
@@ -396,6 +587,7 @@ impl<'a> Iterator for DecodeIter<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::kiro::parser::frame::FrameBuilder;
 
     #[test]
     fn test_decoder_new() {
@@ -419,6 +611,23 @@ mod tests {
         assert!(matches!(result, Err(ParseError::BufferOverflow { .. })));
     }
 
+    #[test]
+    fn test_decoder_buffer_high_water_and_shrink() {
+        let mut decoder = EventStreamDecoder::new();
+        let payload = vec![b'x'; 2 * SHRINK_THRESHOLD];
+        let mut data = raw_frame(&payload);
+        data.extend(raw_frame(b"tail"));
+        decoder.feed(&data).unwrap();
+        assert_eq!(decoder.buffer_high_water(), data.len());
+
+        let frame = decoder.decode().unwrap().unwrap();
+        assert_eq!(frame.payload.len(), payload.len());
+        // 大帧消费后缓冲区收缩为默认容量，剩余的帧不受影响
+        assert_eq!(decoder.buffer.capacity(), DEFAULT_BUFFER_CAPACITY);
+        assert_eq!(decoder.decode().unwrap().unwrap().payload, &b"tail"[..]);
+        assert_eq!(decoder.buffer_high_water(), data.len());
+    }
+
     #[test]
     fn test_decoder_insufficient_data() {
         let mut decoder = EventStreamDecoder::new();
@@ -429,6 +638,101 @@ mod tests {
         assert_eq!(decoder.state(), DecoderState::Ready);
     }
 
+    #[test]
+    fn test_decoder_error_context_includes_offset_and_hexdump() {
+        let mut decoder = EventStreamDecoder::new().with_error_context(true);
+        // total_length = 0 小于最小消息长度
+        let mut corrupt = vec![0; 8];
+        corrupt.extend_from_slice(b"garbage-bytes");
+        decoder.feed(&corrupt).unwrap();
+
+        match decoder.decode() {
+            Err(ParseError::WithContext {
+                error,
+                offset,
+                hexdump,
+            }) => {
+                assert!(matches!(*error, ParseError::MessageTooSmall { .. }));
+                assert_eq!(offset, 0);
+                assert!(hexdump.starts_with("00000000  00 00 00 00 00 00 00 00 67 61"));
+                assert!(hexdump.contains("|........garbage-|\n00000010  62 79"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // 恢复逻辑扫描不到有效帧边界，跳过所有可能容纳 Prelude 的起始位置（保留末尾 11 字节），
+        // 下一次错误的偏移随之推进
+        assert_eq!(decoder.bytes_skipped(), 10);
+        decoder.feed(&[0; 8]).unwrap();
+        match decoder.decode() {
+            Err(e @ ParseError::WithContext { .. }) => {
+                let message = e.to_string();
+                assert!(message.contains("(流偏移 10)"));
+                assert!(message.contains("0000000a  72 62 61 67"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    /// 编码一个无头部的消息帧
+    fn raw_frame(payload: &[u8]) -> Vec<u8> {
+        FrameBuilder::new()
+            .payload(Bytes::copy_from_slice(payload))
+            .to_bytes()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_decoder_resync_skips_garbage_in_one_step() {
+        let mut decoder = EventStreamDecoder::new();
+        let mut data = vec![0xff; 1000];
+        data.extend(raw_frame(b"ok"));
+        decoder.feed(&data).unwrap();
+
+        assert!(matches!(
+            decoder.decode(),
+            Err(ParseError::MessageTooLarge { .. })
+        ));
+        assert_eq!(decoder.bytes_skipped(), 1000);
+        decoder.feed(&[]).unwrap();
+        let frame = decoder.decode().unwrap().unwrap();
+        assert_eq!(frame.payload, &b"ok"[..]);
+        assert_eq!(decoder.error_count(), 0);
+    }
+
+    #[test]
+    fn test_decoder_skips_frame_with_bad_headers() {
+        // CRC 正确但头部值类型非法
+        let headers = [1u8, b'x', 42];
+        let mut data = Vec::new();
+        data.extend_from_slice(&((MIN_MESSAGE_SIZE + headers.len()) as u32).to_be_bytes());
+        data.extend_from_slice(&(headers.len() as u32).to_be_bytes());
+        data.extend_from_slice(&crc32(&data).to_be_bytes());
+        data.extend_from_slice(&headers);
+        data.extend_from_slice(&crc32(&data).to_be_bytes());
+        let bad_len = data.len();
+        data.extend(raw_frame(b"ok"));
+
+        let mut decoder = EventStreamDecoder::new();
+        decoder.feed(&data).unwrap();
+        assert!(matches!(
+            decoder.decode(),
+            Err(ParseError::InvalidHeaderType(42))
+        ));
+        assert_eq!(decoder.bytes_skipped(), bad_len);
+        decoder.feed(&[]).unwrap();
+        let frame = decoder.decode().unwrap().unwrap();
+        assert_eq!(frame.payload, &b"ok"[..]);
+    }
+
+    #[test]
+    fn test_hexdump_is_bounded() {
+        let dump = hexdump(&[0x41; 200], 0x20);
+        assert_eq!(dump.lines().count(), HEXDUMP_MAX_BYTES / 16);
+        assert!(dump.starts_with("00000020  41 41"));
+        assert!(dump.ends_with("|AAAAAAAAAAAAAAAA|"));
+    }
+
     #[test]
     fn test_decoder_reset() {
         let mut decoder = EventStreamDecoder::new();
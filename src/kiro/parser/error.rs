@@ -29,6 +29,14 @@ pub enum ParseError {
     TooManyErrors { count: usize, last_error: String },
     /// 缓冲区溢出
     BufferOverflow { size: usize, max: usize },
+    /// 附带字节上下文的解析错误（启用 decodeErrorHexdump 时由解码器包装）
+    WithContext {
+        error: Box<ParseError>,
+        /// 出错帧在响应流中的起始偏移
+        offset: usize,
+        /// 出错位置起的有界 hexdump
+        hexdump: String,
+    },
 }
 
 impl std::error::Error for ParseError {}
@@ -74,6 +82,13 @@ impl fmt::Display for ParseError {
             Self::BufferOverflow { size, max } => {
                 write!(f, "缓冲区溢出: {} 字节 (最大 {})", size, max)
             }
+            Self::WithContext {
+                error,
+                offset,
+                hexdump,
+            } => {
+                write!(f, "{} (流偏移 {})\n{}", error, offset, hexdump)
+            }
         }
     }
 }
@@ -92,3 +107,35 @@ impl From<serde_json::Error> for ParseError {
 
 /// 解析结果类型
 pub type ParseResult<T> = Result<T, ParseError>;
+
+/// hexdump 最多包含的字节数
+pub const HEXDUMP_MAX_BYTES: usize = 64;
+
+/// 生成有界 hexdump（每行 16 字节，行首为流中的绝对偏移，超过上限的字节被截断）
+pub fn hexdump(bytes: &[u8], base_offset: usize) -> String {
+    let bytes = &bytes[..bytes.len().min(HEXDUMP_MAX_BYTES)];
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!(
+                "{:08x}  {:<47}  |{}|",
+                base_offset + i * 16,
+                hex.join(" "),
+                ascii
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -16,9 +16,11 @@
 //! - Payload: 载荷数据（通常是 JSON）
 //! - Message CRC: 整个消息（不含 Message CRC 自身）的 CRC32 校验
 
+use bytes::{BufMut, Bytes, BytesMut};
+
 use super::crc::crc32;
 use super::error::{ParseError, ParseResult};
-use super::header::{Headers, parse_headers};
+use super::header::{ByteStr, HeaderValue, Headers, parse_headers};
 
 /// Prelude 固定大小 (12 字节)
 pub const PRELUDE_SIZE: usize = 12;
@@ -30,15 +32,50 @@ pub const MIN_MESSAGE_SIZE: usize = PRELUDE_SIZE + 4;
 pub const MAX_MESSAGE_SIZE: u32 = 16 * 1024 * 1024;
 
 /// 解析后的消息帧
+///
+/// 头部与负载都是对解码器缓冲区的 `Bytes` 切片，不复制数据
 #[derive(Debug, Clone)]
 pub struct Frame {
     /// 消息头部
     pub headers: Headers,
     /// 消息负载
-    pub payload: Vec<u8>,
+    pub payload: Bytes,
 }
 
 impl Frame {
+    /// 从一个完整且已通过 [`check_frame`] 校验的帧构造（`headers_end` 为头部结束位置）
+    pub fn from_bytes(bytes: Bytes, headers_end: usize) -> ParseResult<Self> {
+        let headers = parse_headers(&bytes.slice(PRELUDE_SIZE..headers_end))?;
+        // 去除最后 4 字节的 message_crc
+        let payload = bytes.slice(headers_end..bytes.len() - 4);
+        Ok(Self { headers, payload })
+    }
+
+    /// 编码为完整的消息帧（计算长度与两个 CRC）追加到 `dst`
+    pub fn encode(&self, dst: &mut BytesMut) -> ParseResult<()> {
+        let mut headers = BytesMut::new();
+        self.headers.encode(&mut headers)?;
+        let total_length = MIN_MESSAGE_SIZE + headers.len() + self.payload.len();
+        if total_length > MAX_MESSAGE_SIZE as usize {
+            return Err(ParseError::MessageTooLarge {
+                length: total_length.min(u32::MAX as usize) as u32,
+                max: MAX_MESSAGE_SIZE,
+            });
+        }
+
+        let start = dst.len();
+        dst.reserve(total_length);
+        dst.put_u32(total_length as u32);
+        dst.put_u32(headers.len() as u32);
+        let prelude_crc = crc32(&dst[start..]);
+        dst.put_u32(prelude_crc);
+        dst.put_slice(&headers);
+        dst.put_slice(&self.payload);
+        let message_crc = crc32(&dst[start..]);
+        dst.put_u32(message_crc);
+        Ok(())
+    }
+
     /// 获取消息类型
     pub fn message_type(&self) -> Option<&str> {
         self.headers.message_type()
@@ -60,10 +97,119 @@ impl Frame {
     }
 }
 
+/// 消息帧构造器
+///
+/// 用于测试与模拟上游合成响应流，长度与 CRC 在编码时计算：
+///
+/// ```ignore
+/// let bytes = FrameBuilder::event("assistantResponseEvent")
+///     .json(&json!({"content": "Hello"}))
+///     .to_bytes();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FrameBuilder {
+    headers: Headers,
+    payload: Bytes,
+}
+
+impl FrameBuilder {
+    /// 无头部、空负载的帧
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// event 消息（`:event-type` 为事件类型，负载为 JSON）
+    pub fn event(event_type: impl Into<ByteStr>) -> Self {
+        Self::message("event")
+            .string_header(":event-type", event_type)
+            .string_header(":content-type", "application/json")
+    }
+
+    /// exception 消息（负载为异常消息）
+    pub fn exception(exception_type: impl Into<ByteStr>) -> Self {
+        Self::message("exception").string_header(":exception-type", exception_type)
+    }
+
+    /// error 消息（负载为错误消息）
+    pub fn error(error_code: impl Into<ByteStr>) -> Self {
+        Self::message("error").string_header(":error-code", error_code)
+    }
+
+    fn message(message_type: &'static str) -> Self {
+        Self::new().string_header(":message-type", message_type)
+    }
+
+    /// 添加头部（同名头部覆盖）
+    pub fn header(mut self, name: impl Into<ByteStr>, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// 添加字符串头部
+    pub fn string_header(self, name: impl Into<ByteStr>, value: impl Into<ByteStr>) -> Self {
+        self.header(name, HeaderValue::String(value.into()))
+    }
+
+    pub fn payload(mut self, payload: impl Into<Bytes>) -> Self {
+        self.payload = payload.into();
+        self
+    }
+
+    /// 以 JSON 作为负载
+    pub fn json(self, value: &serde_json::Value) -> Self {
+        self.payload(value.to_string())
+    }
+
+    pub fn build(self) -> Frame {
+        Frame {
+            headers: self.headers,
+            payload: self.payload,
+        }
+    }
+
+    /// 编码为完整的消息帧
+    ///
+    /// # Panics
+    /// 头部或负载超出协议限制时（单个字符串头部超过 64 KB、整帧超过 `MAX_MESSAGE_SIZE`）
+    pub fn to_bytes(&self) -> Bytes {
+        let frame = Frame {
+            headers: self.headers.clone(),
+            payload: self.payload.clone(),
+        };
+        let mut dst = BytesMut::new();
+        frame.encode(&mut dst).expect("帧超出协议限制");
+        dst.freeze()
+    }
+}
+
+/// 宽松模式下被容忍的 CRC 校验失败
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CrcMismatch {
+    pub prelude: bool,
+    pub message: bool,
+}
+
+impl CrcMismatch {
+    pub fn any(&self) -> bool {
+        self.prelude || self.message
+    }
+}
+
+/// 校验通过的帧边界
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameBounds {
+    /// 整帧长度（即消费的字节数）
+    pub total_length: usize,
+    /// 头部结束位置（负载起始位置）
+    pub headers_end: usize,
+    /// 宽松模式下被容忍的 CRC 校验失败
+    pub mismatch: CrcMismatch,
+}
+
 /// 尝试从缓冲区解析一个完整的帧
 ///
-/// 这是一个无状态的纯函数，每次调用独立解析。
-/// 缓冲区管理由上层 `EventStreamDecoder` 负责。
+/// 这是一个无状态的纯函数，每次调用独立解析，帧数据会复制一份。
+/// 缓冲区管理由上层 `EventStreamDecoder` 负责（解码器直接切分缓冲区，不复制）。
 ///
 /// # Arguments
 /// * `buffer` - 输入缓冲区
@@ -73,6 +219,31 @@ impl Frame {
 /// - `Ok(None)` - 数据不足，需要更多数据
 /// - `Err(e)` - 解析错误
 pub fn parse_frame(buffer: &[u8]) -> ParseResult<Option<(Frame, usize)>> {
+    Ok(parse_frame_with(buffer, false)?.map(|(frame, consumed, _)| (frame, consumed)))
+}
+
+/// 解析一个完整的帧，`lenient` 为 true 时 CRC 校验失败不报错
+///
+/// 宽松模式下长度与头部仍需有效，校验失败的情况通过 [`CrcMismatch`] 返回，由调用方记录
+pub fn parse_frame_with(
+    buffer: &[u8],
+    lenient: bool,
+) -> ParseResult<Option<(Frame, usize, CrcMismatch)>> {
+    let Some(bounds) = check_frame(buffer, lenient)? else {
+        return Ok(None);
+    };
+    let bytes = Bytes::copy_from_slice(&buffer[..bounds.total_length]);
+    let frame = Frame::from_bytes(bytes, bounds.headers_end)?;
+    Ok(Some((frame, bounds.total_length, bounds.mismatch)))
+}
+
+/// 校验缓冲区头部的帧：长度、Prelude / Message CRC 与头部边界（不解析头部内容）
+///
+/// # Returns
+/// - `Ok(Some(bounds))` - 缓冲区包含一个完整的帧
+/// - `Ok(None)` - 数据不足，需要更多数据
+/// - `Err(e)` - 校验失败
+pub fn check_frame(buffer: &[u8], lenient: bool) -> ParseResult<Option<FrameBounds>> {
     // 检查是否有足够的数据读取 prelude
     if buffer.len() < PRELUDE_SIZE {
         return Ok(None);
@@ -107,12 +278,16 @@ pub fn parse_frame(buffer: &[u8]) -> ParseResult<Option<(Frame, usize)>> {
     }
 
     // 验证 Prelude CRC
+    let mut mismatch = CrcMismatch::default();
     let actual_prelude_crc = crc32(&buffer[..8]);
     if actual_prelude_crc != prelude_crc {
-        return Err(ParseError::PreludeCrcMismatch {
-            expected: prelude_crc,
-            actual: actual_prelude_crc,
-        });
+        if !lenient {
+            return Err(ParseError::PreludeCrcMismatch {
+                expected: prelude_crc,
+                actual: actual_prelude_crc,
+            });
+        }
+        mismatch.prelude = true;
     }
 
     // 读取 Message CRC
@@ -126,31 +301,28 @@ pub fn parse_frame(buffer: &[u8]) -> ParseResult<Option<(Frame, usize)>> {
     // 验证 Message CRC (对整个消息不含最后4字节)
     let actual_message_crc = crc32(&buffer[..total_length - 4]);
     if actual_message_crc != message_crc {
-        return Err(ParseError::MessageCrcMismatch {
-            expected: message_crc,
-            actual: actual_message_crc,
-        });
+        if !lenient {
+            return Err(ParseError::MessageCrcMismatch {
+                expected: message_crc,
+                actual: actual_message_crc,
+            });
+        }
+        mismatch.message = true;
     }
 
-    // 解析头部
-    let headers_start = PRELUDE_SIZE;
-    let headers_end = headers_start + header_length;
-
     // 验证头部边界
+    let headers_end = PRELUDE_SIZE + header_length;
     if headers_end > total_length - 4 {
         return Err(ParseError::HeaderParseFailed(
             "头部长度超出消息边界".to_string(),
         ));
     }
 
-    let headers = parse_headers(&buffer[headers_start..headers_end], header_length)?;
-
-    // 提取 payload (去除最后4字节的 message_crc)
-    let payload_start = headers_end;
-    let payload_end = total_length - 4;
-    let payload = buffer[payload_start..payload_end].to_vec();
-
-    Ok(Some((Frame { headers, payload }, total_length)))
+    Ok(Some(FrameBounds {
+        total_length,
+        headers_end,
+        mismatch,
+    }))
 }
 
 #[cfg(test)]
@@ -175,4 +347,51 @@ mod tests {
         let result = parse_frame(&buffer);
         assert!(matches!(result, Err(ParseError::MessageTooSmall { .. })));
     }
+
+    #[test]
+    fn test_frame_builder_round_trip() {
+        let bytes = FrameBuilder::event("assistantResponseEvent")
+            .header("x-seq", HeaderValue::Long(3))
+            .json(&serde_json::json!({"content": "Hello"}))
+            .to_bytes();
+        let (frame, consumed) = parse_frame(&bytes).unwrap().unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(frame.message_type(), Some("event"));
+        assert_eq!(frame.event_type(), Some("assistantResponseEvent"));
+        assert_eq!(frame.headers.get("x-seq"), Some(&HeaderValue::Long(3)));
+        assert_eq!(frame.payload_as_str(), r#"{"content":"Hello"}"#);
+
+        let bytes = FrameBuilder::exception("ThrottlingException")
+            .payload("slow down")
+            .to_bytes();
+        let (frame, _) = parse_frame(&bytes).unwrap().unwrap();
+        assert_eq!(frame.headers.exception_type(), Some("ThrottlingException"));
+        assert_eq!(frame.payload_as_str(), "slow down");
+    }
+
+    #[test]
+    fn test_frame_lenient_crc() {
+        // 无头部、载荷为 "hi" 的帧，篡改 Message CRC
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&18u32.to_be_bytes());
+        buffer.extend_from_slice(&0u32.to_be_bytes());
+        buffer.extend_from_slice(&crc32(&buffer[0..8]).to_be_bytes());
+        buffer.extend_from_slice(b"hi");
+        buffer.extend_from_slice(&0xdeadbeefu32.to_be_bytes());
+
+        assert!(matches!(
+            parse_frame(&buffer),
+            Err(ParseError::MessageCrcMismatch { .. })
+        ));
+        let (frame, consumed, mismatch) = parse_frame_with(&buffer, true).unwrap().unwrap();
+        assert_eq!(frame.payload, &b"hi"[..]);
+        assert_eq!(consumed, 18);
+        assert_eq!(
+            mismatch,
+            CrcMismatch {
+                prelude: false,
+                message: true
+            }
+        );
+    }
 }
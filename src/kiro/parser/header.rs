@@ -3,7 +3,71 @@
 //! 实现 AWS Event Stream 协议的头部解析功能
 
 use super::error::{ParseError, ParseResult};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+/// UTF-8 字符串的 `Bytes` 切片
+///
+/// 头部名称与字符串值直接引用帧数据，构造时校验 UTF-8（非法时按 lossy 转换复制一份）
+#[derive(Clone, PartialEq, Eq)]
+pub struct ByteStr(Bytes);
+
+impl ByteStr {
+    pub fn from_utf8_lossy(bytes: Bytes) -> Self {
+        match std::str::from_utf8(&bytes) {
+            Ok(_) => Self(bytes),
+            Err(_) => Self(Bytes::from(String::from_utf8_lossy(&bytes).into_owned())),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // 构造时已校验
+        std::str::from_utf8(&self.0).unwrap_or_default()
+    }
+}
+
+impl Deref for ByteStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for ByteStr {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// 与 str 的哈希保持一致，以便 HashMap 以 &str 查找
+impl Hash for ByteStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Debug for ByteStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl From<&'static str> for ByteStr {
+    fn from(s: &'static str) -> Self {
+        Self(Bytes::from_static(s.as_bytes()))
+    }
+}
+
+impl From<String> for ByteStr {
+    fn from(s: String) -> Self {
+        Self(Bytes::from(s))
+    }
+}
 
 /// 头部值类型标识
 ///
@@ -53,8 +117,8 @@ pub enum HeaderValue {
     Short(i16),
     Integer(i32),
     Long(i64),
-    ByteArray(Vec<u8>),
-    String(String),
+    ByteArray(Bytes),
+    String(ByteStr),
     Timestamp(i64),
     Uuid([u8; 16]),
 }
@@ -67,12 +131,62 @@ impl HeaderValue {
             _ => None,
         }
     }
+
+    /// 编码为值类型 + 值
+    fn encode(&self, dst: &mut BytesMut) -> ParseResult<()> {
+        match self {
+            Self::Bool(true) => dst.put_u8(HeaderValueType::BoolTrue as u8),
+            Self::Bool(false) => dst.put_u8(HeaderValueType::BoolFalse as u8),
+            Self::Byte(v) => {
+                dst.put_u8(HeaderValueType::Byte as u8);
+                dst.put_i8(*v);
+            }
+            Self::Short(v) => {
+                dst.put_u8(HeaderValueType::Short as u8);
+                dst.put_i16(*v);
+            }
+            Self::Integer(v) => {
+                dst.put_u8(HeaderValueType::Integer as u8);
+                dst.put_i32(*v);
+            }
+            Self::Long(v) => {
+                dst.put_u8(HeaderValueType::Long as u8);
+                dst.put_i64(*v);
+            }
+            Self::ByteArray(v) => {
+                dst.put_u8(HeaderValueType::ByteArray as u8);
+                put_u16_prefixed(dst, v)?;
+            }
+            Self::String(v) => {
+                dst.put_u8(HeaderValueType::String as u8);
+                put_u16_prefixed(dst, v.as_bytes())?;
+            }
+            Self::Timestamp(v) => {
+                dst.put_u8(HeaderValueType::Timestamp as u8);
+                dst.put_i64(*v);
+            }
+            Self::Uuid(v) => {
+                dst.put_u8(HeaderValueType::Uuid as u8);
+                dst.put_slice(v);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 写入 2 字节长度前缀与数据
+fn put_u16_prefixed(dst: &mut BytesMut, data: &[u8]) -> ParseResult<()> {
+    let len = u16::try_from(data.len())
+        .map_err(|_| ParseError::HeaderParseFailed(format!("头部值过长: {} 字节", data.len())))?;
+    dst.put_u16(len);
+    dst.put_slice(data);
+    Ok(())
 }
 
 /// 消息头部集合
 #[derive(Debug, Clone, Default)]
 pub struct Headers {
-    inner: HashMap<String, HeaderValue>,
+    inner: HashMap<ByteStr, HeaderValue>,
 }
 
 impl Headers {
@@ -84,8 +198,8 @@ impl Headers {
     }
 
     /// 插入头部
-    pub fn insert(&mut self, name: String, value: HeaderValue) {
-        self.inner.insert(name, value);
+    pub fn insert(&mut self, name: impl Into<ByteStr>, value: HeaderValue) {
+        self.inner.insert(name.into(), value);
     }
 
     /// 获取头部值
@@ -117,33 +231,39 @@ impl Headers {
     pub fn error_code(&self) -> Option<&str> {
         self.get_string(":error-code")
     }
+
+    /// 按线上格式编码全部头部（顺序不固定）
+    pub fn encode(&self, dst: &mut BytesMut) -> ParseResult<()> {
+        for (name, value) in &self.inner {
+            let len = u8::try_from(name.len())
+                .ok()
+                .filter(|len| *len > 0)
+                .ok_or_else(|| {
+                    ParseError::HeaderParseFailed(format!("头部名称长度无效: {:?}", name))
+                })?;
+            dst.put_u8(len);
+            dst.put_slice(name.as_bytes());
+            value.encode(dst)?;
+        }
+        Ok(())
+    }
 }
 
 /// 从字节流解析头部
 ///
+/// 名称、字符串值与字节数组值均为 `data` 的切片，不复制数据
+///
 /// # Arguments
-/// * `data` - 头部数据切片
-/// * `header_length` - 头部总长度
+/// * `data` - 头部数据（整段均为头部）
 ///
 /// # Returns
 /// 解析后的 Headers 结构
-pub fn parse_headers(data: &[u8], header_length: usize) -> ParseResult<Headers> {
-    // 验证数据长度是否足够
-    if data.len() < header_length {
-        return Err(ParseError::Incomplete {
-            needed: header_length,
-            available: data.len(),
-        });
-    }
-
+pub fn parse_headers(data: &Bytes) -> ParseResult<Headers> {
     let mut headers = Headers::new();
     let mut offset = 0;
 
-    while offset < header_length {
+    while offset < data.len() {
         // 读取头部名称长度 (1 byte)
-        if offset >= data.len() {
-            break;
-        }
         let name_len = data[offset] as usize;
         offset += 1;
 
@@ -161,7 +281,7 @@ pub fn parse_headers(data: &[u8], header_length: usize) -> ParseResult<Headers>
                 available: data.len() - offset,
             });
         }
-        let name = String::from_utf8_lossy(&data[offset..offset + name_len]).to_string();
+        let name = ByteStr::from_utf8_lossy(data.slice(offset..offset + name_len));
         offset += name_len;
 
         // 读取值类型 (1 byte)
@@ -175,7 +295,7 @@ pub fn parse_headers(data: &[u8], header_length: usize) -> ParseResult<Headers>
         offset += 1;
 
         // 根据类型解析值
-        let value = parse_header_value(&data[offset..], value_type, &mut offset)?;
+        let value = parse_header_value(&data.slice(offset..), value_type, &mut offset)?;
         headers.insert(name, value);
     }
 
@@ -184,7 +304,7 @@ pub fn parse_headers(data: &[u8], header_length: usize) -> ParseResult<Headers>
 
 /// 解析头部值
 fn parse_header_value(
-    data: &[u8],
+    data: &Bytes,
     value_type: HeaderValueType,
     global_offset: &mut usize,
 ) -> ParseResult<HeaderValue> {
@@ -231,7 +351,7 @@ fn parse_header_value(
             ensure_bytes(data, 2)?;
             let len = u16::from_be_bytes([data[0], data[1]]) as usize;
             ensure_bytes(data, 2 + len)?;
-            let v = data[2..2 + len].to_vec();
+            let v = data.slice(2..2 + len);
             local_offset = 2 + len;
             Ok(HeaderValue::ByteArray(v))
         }
@@ -239,7 +359,7 @@ fn parse_header_value(
             ensure_bytes(data, 2)?;
             let len = u16::from_be_bytes([data[0], data[1]]) as usize;
             ensure_bytes(data, 2 + len)?;
-            let v = String::from_utf8_lossy(&data[2..2 + len]).to_string();
+            let v = ByteStr::from_utf8_lossy(data.slice(2..2 + len));
             local_offset = 2 + len;
             Ok(HeaderValue::String(v))
         }
@@ -287,7 +407,7 @@ mod tests {
 
     #[test]
     fn test_header_value_as_str() {
-        let value = HeaderValue::String("test".to_string());
+        let value = HeaderValue::String("test".into());
         assert_eq!(value.as_str(), Some("test"));
 
         let value = HeaderValue::Bool(true);
@@ -297,10 +417,7 @@ mod tests {
     #[test]
     fn test_headers_get_string() {
         let mut headers = Headers::new();
-        headers.insert(
-            ":message-type".to_string(),
-            HeaderValue::String("event".to_string()),
-        );
+        headers.insert(":message-type", HeaderValue::String("event".into()));
         assert_eq!(headers.message_type(), Some("event"));
     }
 
@@ -310,8 +427,13 @@ mod tests {
         // 头部名: "x" (长度 1)
         // 值类型: 7 (String)
         // 值: "ab" (长度 2)
-        let data = [1u8, b'x', 7, 0, 2, b'a', b'b'];
-        let headers = parse_headers(&data, data.len()).unwrap();
+        let data = Bytes::from_static(&[1u8, b'x', 7, 0, 2, b'a', b'b']);
+        let headers = parse_headers(&data).unwrap();
         assert_eq!(headers.get_string("x"), Some("ab"));
+        // 字符串值引用原始数据
+        let Some(HeaderValue::String(value)) = headers.get("x") else {
+            panic!("expected string header");
+        };
+        assert_eq!(value.as_ptr(), data[5..].as_ptr());
     }
 }
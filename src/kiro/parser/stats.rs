@@ -0,0 +1,98 @@
+//! 解码器统计
+//!
+//! 解码器按请求创建，CRC 校验失败次数与缓冲区用量统一累计到全局计数，由 Admin API `GET /stats` 展示：
+//! 前者用于判断上游或中间代理是否在破坏响应流，后者供内存受限的部署调整 `decodeMaxBufferBytes`
+
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Serialize;
+
+/// 损坏帧计数
+#[derive(Debug, Default)]
+pub struct CorruptFrameStats {
+    prelude_crc: AtomicU64,
+    message_crc: AtomicU64,
+    tolerated: AtomicU64,
+}
+
+/// 损坏帧统计快照
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorruptFrameSummary {
+    /// Prelude CRC 校验失败次数（严格模式下帧边界错位时逐字节重新同步，每次尝试都计入）
+    pub prelude_crc_mismatches: u64,
+    /// Message CRC 校验失败次数
+    pub message_crc_mismatches: u64,
+    /// 宽松模式下校验失败但仍交付的帧数
+    pub tolerated_frames: u64,
+}
+
+impl CorruptFrameStats {
+    /// 记录一次 CRC 校验失败，`tolerated` 表示帧仍被交付（宽松模式）
+    pub fn record(&self, prelude: bool, message: bool, tolerated: bool) {
+        if prelude {
+            self.prelude_crc.fetch_add(1, Ordering::Relaxed);
+        }
+        if message {
+            self.message_crc.fetch_add(1, Ordering::Relaxed);
+        }
+        if tolerated {
+            self.tolerated.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn summary(&self) -> CorruptFrameSummary {
+        CorruptFrameSummary {
+            prelude_crc_mismatches: self.prelude_crc.load(Ordering::Relaxed),
+            message_crc_mismatches: self.message_crc.load(Ordering::Relaxed),
+            tolerated_frames: self.tolerated.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 全局损坏帧统计
+pub fn corrupt_frames() -> &'static CorruptFrameStats {
+    static STATS: LazyLock<CorruptFrameStats> = LazyLock::new(CorruptFrameStats::default);
+    &STATS
+}
+
+/// 解码缓冲区计数
+#[derive(Debug, Default)]
+pub struct BufferStats {
+    high_water: AtomicU64,
+    overflows: AtomicU64,
+}
+
+/// 解码缓冲区统计快照
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferSummary {
+    /// 启动以来单个解码器缓冲区的最大字节数
+    pub high_water_bytes: u64,
+    /// 超过 `decodeMaxBufferBytes` 被拒绝的次数
+    pub overflows: u64,
+}
+
+impl BufferStats {
+    pub fn record_len(&self, len: usize) {
+        self.high_water.fetch_max(len as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_overflow(&self) {
+        self.overflows.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn summary(&self) -> BufferSummary {
+        BufferSummary {
+            high_water_bytes: self.high_water.load(Ordering::Relaxed),
+            overflows: self.overflows.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 全局解码缓冲区统计
+pub fn buffers() -> &'static BufferStats {
+    static STATS: LazyLock<BufferStats> = LazyLock::new(BufferStats::default);
+    &STATS
+}
@@ -0,0 +1,346 @@
+//! 多实例凭据同步
+//!
+//! 源实例通过 `GET /api/admin/sync` 提供全部凭据（附带 ETag），从实例按 `peerSync` 配置定时拉取，
+//! 把新增的凭据、禁用状态与优先级变更应用到本地凭据池，小规模集群无需共享存储即可保持一致。
+//! 从实例记录每个凭据在源实例上的 ID，之后按该 ID 匹配；尚未关联的凭据按 refreshToken、
+//! Profile ARN + Machine ID 或邮箱（不区分大小写）匹配。源实例轮换令牌后更新已有条目，
+//! 不会重复加入；源实例删除的凭据不会在从实例删除
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use serde::{Deserialize, Serialize};
+
+use crate::http_client::build_client;
+use crate::kiro::model::credentials::{CredentialId, KiroCredentials};
+use crate::kiro::token_manager::{MultiTokenManager, sha256_hex};
+use crate::model::config::PeerSyncConfig;
+
+/// 拉取请求超时（秒）
+const SYNC_TIMEOUT_SECS: u64 = 30;
+
+/// 同步快照（`GET /api/admin/sync` 响应体）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSnapshot {
+    pub credentials: Vec<KiroCredentials>,
+}
+
+impl SyncSnapshot {
+    /// 序列化快照并计算 ETag（响应体的 SHA-256）
+    pub fn encode(&self) -> serde_json::Result<(Vec<u8>, String)> {
+        let body = serde_json::to_vec(self)?;
+        let etag = format!("\"{}\"", sha256_hex(&String::from_utf8_lossy(&body)));
+        Ok((body, etag))
+    }
+}
+
+/// `If-None-Match` 是否命中当前 ETag（忽略弱校验前缀）
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// 一次同步需要应用到本地的变更
+#[derive(Debug, Default)]
+pub struct SyncPlan {
+    /// 本地没有的凭据（源实例中已禁用的不会新增）
+    pub add: Vec<KiroCredentials>,
+    /// 令牌已在源实例轮换或尚未关联源凭据 ID 的本地凭据（按源凭据更新令牌）
+    pub update: Vec<(CredentialId, KiroCredentials)>,
+    /// 需要禁用的本地凭据
+    pub disable: Vec<CredentialId>,
+    /// 需要启用的本地凭据
+    pub enable: Vec<CredentialId>,
+    /// 优先级变更
+    pub priority: Vec<(CredentialId, u32)>,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty()
+            && self.update.is_empty()
+            && self.disable.is_empty()
+            && self.enable.is_empty()
+            && self.priority.is_empty()
+    }
+}
+
+/// 对比本地与源实例的凭据，生成同步计划
+pub fn plan_sync(local: &[KiroCredentials], remote: &[KiroCredentials]) -> SyncPlan {
+    let mut plan = SyncPlan::default();
+    for remote_cred in remote {
+        let Some(local_cred) = find_local(local, remote_cred) else {
+            if !remote_cred.disabled && remote_cred.refresh_token.is_some() {
+                plan.add.push(KiroCredentials {
+                    id: None,
+                    peer_source_id: remote_cred.id,
+                    ..remote_cred.clone()
+                });
+            }
+            continue;
+        };
+        let Some(id) = local_cred.id else {
+            continue;
+        };
+        if remote_cred.refresh_token.is_some()
+            && (local_cred.refresh_token != remote_cred.refresh_token
+                || local_cred.peer_source_id != remote_cred.id)
+        {
+            plan.update.push((id, remote_cred.clone()));
+        }
+        if remote_cred.disabled && !local_cred.disabled {
+            plan.disable.push(id);
+        } else if !remote_cred.disabled && local_cred.disabled {
+            plan.enable.push(id);
+        }
+        if remote_cred.priority != local_cred.priority {
+            plan.priority.push((id, remote_cred.priority));
+        }
+    }
+    plan
+}
+
+/// 查找源凭据对应的本地凭据：优先按记录的源凭据 ID，其次在尚未关联的凭据中按账号匹配
+fn find_local<'a>(
+    local: &'a [KiroCredentials],
+    remote: &KiroCredentials,
+) -> Option<&'a KiroCredentials> {
+    if let Some(source_id) = remote.id
+        && let Some(linked) = local.iter().find(|l| l.peer_source_id == Some(source_id))
+    {
+        return Some(linked);
+    }
+    local
+        .iter()
+        .find(|l| l.peer_source_id.is_none() && same_account(l, remote))
+}
+
+/// 是否指向同一账号：refreshToken 相同、Profile ARN 与 Machine ID 均相同，或邮箱相同（不区分大小写）
+fn same_account(a: &KiroCredentials, b: &KiroCredentials) -> bool {
+    let same_token = a.refresh_token.is_some() && a.refresh_token == b.refresh_token;
+    let same_device = a.profile_arn.is_some()
+        && a.machine_id.is_some()
+        && a.profile_arn == b.profile_arn
+        && a.machine_id == b.machine_id;
+    let same_email = match (a.email.as_deref(), b.email.as_deref()) {
+        (Some(x), Some(y)) => !x.is_empty() && x.eq_ignore_ascii_case(y),
+        _ => false,
+    };
+    same_token || same_device || same_email
+}
+
+/// 启动凭据同步后台任务（从实例）
+///
+/// 仅在启动时配置了 `peerSync` 时运行。每轮按最新配置拉取源实例快照，
+/// ETag 未变化时源实例返回 304，不做任何处理
+pub async fn start_peer_sync_worker(token_manager: Arc<MultiTokenManager>) {
+    let Some(mut sync) = token_manager.config().peer_sync.clone() else {
+        return;
+    };
+    tracing::info!("凭据同步任务启动");
+    let mut etag: Option<String> = None;
+
+    loop {
+        // 热重载修改的 sourceUrl 与 interval 在下一轮生效
+        if let Some(latest) = token_manager.config().peer_sync.clone() {
+            sync = latest;
+        }
+
+        match pull(&token_manager, &sync, etag.as_deref()).await {
+            Ok(Some(new_etag)) => etag = Some(new_etag),
+            Ok(None) => tracing::debug!("源实例凭据未变化"),
+            Err(e) => tracing::warn!("从 {} 同步凭据失败: {:#}", sync.source_url, e),
+        }
+
+        tokio::time::sleep(Duration::from_secs(sync.interval.max(1))).await;
+    }
+}
+
+/// 拉取一次快照并应用，返回新的 ETag（源实例未变化时返回 `None`）
+async fn pull(
+    token_manager: &MultiTokenManager,
+    sync: &PeerSyncConfig,
+    etag: Option<&str>,
+) -> anyhow::Result<Option<String>> {
+    let client = build_client(None, SYNC_TIMEOUT_SECS, token_manager.config().tls_backend)?;
+    let url = format!("{}/api/admin/sync", sync.source_url.trim_end_matches('/'));
+    let mut request = client.get(&url).header("x-api-key", &sync.api_key);
+    if let Some(etag) = etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        anyhow::bail!("源实例返回 HTTP {}", response.status());
+    }
+    let new_etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let snapshot: SyncSnapshot = response.json().await?;
+
+    let plan = plan_sync(&token_manager.export_credentials(), &snapshot.credentials);
+    if !plan.is_empty() {
+        apply(token_manager, plan).await;
+    }
+    // 没有 ETag 时每轮都完整拉取
+    Ok(new_etag)
+}
+
+/// 应用同步计划（单项失败只记录日志，不影响其他变更）
+async fn apply(token_manager: &MultiTokenManager, plan: SyncPlan) {
+    tracing::info!(
+        "同步源实例凭据：新增 {}，令牌更新 {}，禁用 {}，启用 {}，优先级变更 {}",
+        plan.add.len(),
+        plan.update.len(),
+        plan.disable.len(),
+        plan.enable.len(),
+        plan.priority.len()
+    );
+    for (id, source) in plan.update {
+        if let Err(e) = token_manager.apply_synced_tokens(id, &source) {
+            tracing::warn!("同步凭据 #{} 令牌失败: {}", id, e);
+        }
+    }
+    for id in plan.disable {
+        if let Err(e) = token_manager.set_disabled(id, true) {
+            tracing::warn!("同步禁用凭据 #{} 失败: {}", id, e);
+        }
+    }
+    for id in plan.enable {
+        if let Err(e) = token_manager.set_disabled(id, false) {
+            tracing::warn!("同步启用凭据 #{} 失败: {}", id, e);
+        }
+    }
+    for (id, priority) in plan.priority {
+        if let Err(e) = token_manager.set_priority(id, priority) {
+            tracing::warn!("同步凭据 #{} 优先级失败: {}", id, e);
+        }
+    }
+    for cred in plan.add {
+        match token_manager.add_credential(cred).await {
+            Ok(id) => tracing::info!("已从源实例同步新凭据 #{}", id),
+            Err(e) => tracing::warn!("同步新凭据失败: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cred(id: u64, token: &str, email: Option<&str>) -> KiroCredentials {
+        KiroCredentials {
+            id: Some(CredentialId(id)),
+            refresh_token: Some(token.to_string()),
+            email: email.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_plan_sync_matches_by_token_or_email() {
+        let local = vec![
+            cred(1, "rt-a", None),
+            KiroCredentials {
+                disabled: true,
+                ..cred(2, "rt-b-old", Some("B@example.com"))
+            },
+        ];
+        let remote = vec![
+            KiroCredentials {
+                priority: 5,
+                ..cred(10, "rt-a", None)
+            },
+            cred(11, "rt-b-new", Some("b@example.com")),
+            cred(12, "rt-c", None),
+            KiroCredentials {
+                disabled: true,
+                ..cred(13, "rt-d", None)
+            },
+        ];
+
+        let plan = plan_sync(&local, &remote);
+        assert_eq!(plan.priority, vec![(CredentialId(1), 5)]);
+        assert_eq!(plan.enable, vec![CredentialId(2)]);
+        assert!(plan.disable.is_empty());
+        assert_eq!(plan.add.len(), 1);
+        assert_eq!(plan.add[0].refresh_token.as_deref(), Some("rt-c"));
+        assert_eq!(plan.add[0].id, None);
+        assert_eq!(plan.add[0].peer_source_id, Some(CredentialId(12)));
+        // 匹配到的凭据关联源凭据 ID，邮箱匹配的同时更新为源实例的新令牌
+        let updates: Vec<_> = plan
+            .update
+            .iter()
+            .map(|(id, source)| (*id, source.id, source.refresh_token.as_deref()))
+            .collect();
+        assert_eq!(
+            updates,
+            vec![
+                (CredentialId(1), Some(CredentialId(10)), Some("rt-a")),
+                (CredentialId(2), Some(CredentialId(11)), Some("rt-b-new")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_sync_updates_rotated_token_in_place() {
+        // 已关联源凭据 #10 的本地凭据，没有邮箱
+        let local = vec![KiroCredentials {
+            peer_source_id: Some(CredentialId(10)),
+            ..cred(1, "rt-old", None)
+        }];
+        let remote = vec![cred(10, "rt-rotated", None)];
+
+        let plan = plan_sync(&local, &remote);
+        assert!(plan.add.is_empty());
+        assert_eq!(plan.update.len(), 1);
+        assert_eq!(plan.update[0].0, CredentialId(1));
+        assert_eq!(
+            plan.update[0].1.refresh_token.as_deref(),
+            Some("rt-rotated")
+        );
+
+        // 更新后不再产生变更
+        let synced = vec![KiroCredentials {
+            peer_source_id: Some(CredentialId(10)),
+            ..cred(1, "rt-rotated", None)
+        }];
+        assert!(plan_sync(&synced, &remote).is_empty());
+
+        // 未关联的凭据按 Profile ARN + Machine ID 匹配
+        let device = |id, token: &str| KiroCredentials {
+            profile_arn: Some("arn:aws:profile/a".to_string()),
+            machine_id: Some("machine-a".to_string()),
+            ..cred(id, token, None)
+        };
+        let plan = plan_sync(&[device(1, "rt-old")], &[device(10, "rt-rotated")]);
+        assert!(plan.add.is_empty());
+        assert_eq!(plan.update[0].0, CredentialId(1));
+    }
+
+    #[test]
+    fn test_snapshot_etag() {
+        let snapshot = SyncSnapshot {
+            credentials: vec![cred(1, "rt-a", None)],
+        };
+        let (body, etag) = snapshot.encode().unwrap();
+        assert!(!body.is_empty());
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_eq!(snapshot.encode().unwrap().1, etag);
+
+        assert!(etag_matches(&etag, &etag));
+        assert!(etag_matches(&format!("W/{}, \"other\"", etag), &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"other\"", &etag));
+    }
+}
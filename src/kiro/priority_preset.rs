@@ -0,0 +1,136 @@
+//! 凭据优先级预设定时切换
+//!
+//! 按 `priorityPresets.*.schedule` 配置的每日时刻（运营者时区）自动应用对应预设，
+//! 例如白天让交互用的账号优先、夜间切换给批处理账号，无需运营者每天手动调整
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Duration as ChronoDuration, FixedOffset, NaiveTime, Utc};
+
+use crate::kiro::token_manager::MultiTokenManager;
+use crate::model::config::PriorityPreset;
+
+/// 重新读取配置的最长间隔（配置热重载后新的计划在此时间内生效）
+const RECHECK_INTERVAL: Duration = Duration::from_secs(600);
+
+/// 启动优先级预设定时切换后台任务
+pub async fn start_priority_preset_worker(token_manager: Arc<MultiTokenManager>) {
+    for (name, preset) in &token_manager.config().priority_presets {
+        if preset.schedule_times().len() != preset.schedule.len() {
+            tracing::warn!(
+                "优先级预设 {} 的 schedule 含无法解析的时刻（应为 HH:MM），已忽略",
+                name
+            );
+        }
+    }
+    tracing::info!("优先级预设定时切换任务启动");
+
+    loop {
+        let config = token_manager.config();
+        let now = Utc::now().with_timezone(&config.timezone_offset());
+        let Some((wait, name)) = next_scheduled(&config.priority_presets, now) else {
+            tokio::time::sleep(RECHECK_INTERVAL).await;
+            continue;
+        };
+        if wait > RECHECK_INTERVAL {
+            tokio::time::sleep(RECHECK_INTERVAL).await;
+            continue;
+        }
+
+        tokio::time::sleep(wait).await;
+        if let Err(e) = token_manager.apply_priority_preset(&name) {
+            tracing::warn!("定时应用优先级预设 {} 失败: {}", name, e);
+        }
+        // 避免同一时刻在 sleep 精度误差下重复触发
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// 下一次需要应用的预设及等待时间（多个预设时刻相同时取名称排序靠前的）
+fn next_scheduled(
+    presets: &BTreeMap<String, PriorityPreset>,
+    now: DateTime<FixedOffset>,
+) -> Option<(Duration, String)> {
+    presets
+        .iter()
+        .flat_map(|(name, preset)| {
+            preset
+                .schedule_times()
+                .into_iter()
+                .map(move |time| (duration_until(now, time), name))
+        })
+        .min_by_key(|(wait, _)| *wait)
+        .map(|(wait, name)| (wait, name.clone()))
+}
+
+/// 距离下一次（当天或次日）指定本地时刻的时间
+fn duration_until(now: DateTime<FixedOffset>, time: NaiveTime) -> Duration {
+    let today = now
+        .date_naive()
+        .and_time(time)
+        .and_local_timezone(*now.offset())
+        .single()
+        .unwrap_or(now);
+    let next = if today > now {
+        today
+    } else {
+        today + ChronoDuration::days(1)
+    };
+    (next - now).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(schedule: &[&str]) -> PriorityPreset {
+        PriorityPreset {
+            schedule: schedule.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_next_scheduled_picks_nearest_time() {
+        let presets = BTreeMap::from([
+            ("daytime".to_string(), preset(&["08:00"])),
+            ("overnight-batch".to_string(), preset(&["20:30", "bad"])),
+        ]);
+        // 北京时间 10:00
+        let now = DateTime::parse_from_rfc3339("2026-01-01T10:00:00+08:00").unwrap();
+        let (wait, name) = next_scheduled(&presets, now).unwrap();
+        assert_eq!(name, "overnight-batch");
+        assert_eq!(wait, Duration::from_secs((10 * 60 + 30) * 60));
+
+        // 已过当天所有时刻时顺延到次日
+        let now = DateTime::parse_from_rfc3339("2026-01-01T21:00:00+08:00").unwrap();
+        let (wait, name) = next_scheduled(&presets, now).unwrap();
+        assert_eq!(name, "daytime");
+        assert_eq!(wait, Duration::from_secs(11 * 3600));
+
+        assert!(next_scheduled(&BTreeMap::new(), now).is_none());
+    }
+
+    #[test]
+    fn test_priority_for_tags() {
+        let preset = PriorityPreset {
+            tags: BTreeMap::from([("batch".to_string(), 5), ("prod".to_string(), 0)]),
+            default_priority: Some(10),
+            schedule: Vec::new(),
+        };
+        assert_eq!(
+            preset.priority_for(&["batch".to_string(), "prod".to_string()]),
+            Some(0)
+        );
+        assert_eq!(preset.priority_for(&["batch".to_string()]), Some(5));
+        assert_eq!(preset.priority_for(&[]), Some(10));
+
+        let keep_untagged = PriorityPreset {
+            default_priority: None,
+            ..preset
+        };
+        assert_eq!(keep_untagged.priority_for(&["other".to_string()]), None);
+    }
+}
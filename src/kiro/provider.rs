@@ -8,22 +8,133 @@ use reqwest::Client;
 use reqwest::header::{AUTHORIZATION, CONNECTION, CONTENT_TYPE, HOST, HeaderMap, HeaderValue};
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 use uuid::Uuid;
 
-use crate::http_client::{ProxyConfig, build_client};
+use crate::common::request_id;
+use crate::http_client::{ProxyConfig, TlsConfig, build_client, build_client_with_tls};
 use crate::kiro::machine_id;
-use crate::kiro::model::credentials::KiroCredentials;
-use crate::kiro::token_manager::{CallContext, MultiTokenManager};
+use crate::kiro::model::credentials::{CredentialId, KiroCredentials};
+use crate::kiro::model::requests::conversation::{
+    ConversationState, CurrentMessage, UserInputMessage,
+};
+use crate::kiro::model::requests::kiro::KiroRequest;
+use crate::kiro::token_manager::{CallContext, MultiTokenManager, NoAvailableCredentials};
 use crate::model::config::TlsBackend;
 use parking_lot::Mutex;
 
 /// 每个凭据的最大重试次数
 const MAX_RETRIES_PER_CREDENTIAL: usize = 3;
 
-/// 总重试次数硬上限（避免无限重试）
-const MAX_TOTAL_RETRIES: usize = 9;
+/// "wait" 降级策略下轮询凭据可用性的间隔
+const DEGRADATION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 请求头：覆盖本次请求的最大尝试次数（受 config.maxRetriesCeiling 约束）
+pub const MAX_RETRIES_HEADER: &str = "x-kiro-max-retries";
+
+/// 请求头：禁止本次请求切换凭据（凭据级错误直接返回）
+pub const NO_FAILOVER_HEADER: &str = "x-kiro-no-failover";
+
+/// 请求头：将请求限定到带有指定标签的凭据池
+pub const POOL_HEADER: &str = "x-kiro-pool";
+
+/// 单次请求的重试策略
+///
+/// 默认按凭据数量计算尝试次数并允许故障转移；
+/// 交互式客户端可降低尝试次数或禁止故障转移以缩短等待，批处理客户端可提高尝试次数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// 最大尝试次数覆盖（None 表示按凭据数量计算）
+    pub max_retries: Option<usize>,
+    /// 是否禁止切换凭据
+    pub no_failover: bool,
+}
+
+impl RetryPolicy {
+    /// 从请求头解析重试策略，无法解析的值会被忽略
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let max_retries = headers
+            .get(MAX_RETRIES_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<usize>().ok());
+
+        let no_failover = headers
+            .get(NO_FAILOVER_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| {
+                let v = v.trim();
+                v == "1" || v.eq_ignore_ascii_case("true") || v.eq_ignore_ascii_case("yes")
+            })
+            .unwrap_or(false);
+
+        Self {
+            max_retries,
+            no_failover,
+        }
+    }
+
+    /// 计算本次请求的最大尝试次数
+    ///
+    /// - 未覆盖时：min(凭据数量 × 每凭据重试次数, ceiling)
+    /// - 覆盖时：限制在 [1, ceiling] 范围内
+    fn resolve_max_retries(&self, total_credentials: usize, ceiling: usize) -> usize {
+        let ceiling = ceiling.max(1);
+        match self.max_retries {
+            Some(n) => n.clamp(1, ceiling),
+            None => (total_credentials * MAX_RETRIES_PER_CREDENTIAL).min(ceiling),
+        }
+    }
+}
+
+/// 单次请求的路由参数
+///
+/// 汇总重试策略、粘性会话标识与凭据池，决定请求由哪些凭据处理
+#[derive(Debug, Clone, Default)]
+pub struct RequestRouting {
+    /// 重试策略
+    pub policy: RetryPolicy,
+    /// 粘性会话的客户端标识（None 表示按负载均衡策略选择凭据）
+    pub session_key: Option<String>,
+    /// 凭据池标签（None 表示不限制）
+    pub pool: Option<String>,
+    /// 预计消耗的 tokens（用于配额感知准入，0 表示不检查）
+    pub estimated_tokens: u64,
+    /// 需要避开的凭据（流中断续传时排除已中断的凭据，没有其他可用凭据时仍会使用）
+    pub avoid: Vec<CredentialId>,
+}
+
+impl RequestRouting {
+    /// 从请求头解析凭据池标签（`X-Kiro-Pool`），空值视为未指定
+    pub fn pool_from_headers(headers: &HeaderMap) -> Option<String> {
+        headers
+            .get(POOL_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    }
+}
+
+/// 成功请求的上游计时（与凭据 ID `CredentialId` 一同写入响应扩展）
+///
+/// 调用方在响应体读取完毕后据此记录总耗时
+#[derive(Debug, Clone, Copy)]
+pub struct UpstreamTiming {
+    /// 发送请求的时间
+    pub started: Instant,
+    /// 首字节时间（发送请求到收到响应头）
+    pub ttfb: Duration,
+}
+
+/// 成功请求的尝试统计（与 `CredentialId` 一同写入响应扩展）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UpstreamAttempts {
+    /// 发送请求的总次数（含最终成功的一次）
+    pub attempts: u32,
+    /// 切换凭据的次数
+    pub failovers: u32,
+}
 
 /// Kiro API Provider
 ///
@@ -31,47 +142,44 @@ const MAX_TOTAL_RETRIES: usize = 9;
 /// 支持多凭据故障转移和重试机制
 pub struct KiroProvider {
     token_manager: Arc<MultiTokenManager>,
-    /// 全局代理配置（用于凭据无自定义代理时的回退）
-    global_proxy: Option<ProxyConfig>,
-    /// Client 缓存：key = effective proxy config, value = reqwest::Client
-    /// 不同代理配置的凭据使用不同的 Client，共享相同代理的凭据复用 Client
-    client_cache: Mutex<HashMap<Option<ProxyConfig>, Client>>,
+    /// Client 缓存：key = (effective proxy config, effective TLS config), value = reqwest::Client
+    /// 不同代理或 TLS 配置的凭据使用不同的 Client，配置相同的凭据复用 Client
+    client_cache: Mutex<HashMap<(Option<ProxyConfig>, TlsConfig), Client>>,
     /// TLS 后端配置
     tls_backend: TlsBackend,
 }
 
 impl KiroProvider {
     /// 创建新的 KiroProvider 实例
+    ///
+    /// 全局代理从 token_manager 读取，配置热重载后自动使用新代理
     pub fn new(token_manager: Arc<MultiTokenManager>) -> Self {
-        Self::with_proxy(token_manager, None)
-    }
-
-    /// 创建带代理配置的 KiroProvider 实例
-    pub fn with_proxy(token_manager: Arc<MultiTokenManager>, proxy: Option<ProxyConfig>) -> Self {
         let tls_backend = token_manager.config().tls_backend;
+        let proxy = token_manager.proxy();
         // 预热：构建全局代理对应的 Client
         let initial_client = build_client(proxy.as_ref(), 720, tls_backend)
             .expect("创建 HTTP 客户端失败");
         let mut cache = HashMap::new();
-        cache.insert(proxy.clone(), initial_client);
+        cache.insert((proxy, TlsConfig::new(tls_backend)), initial_client);
 
         Self {
             token_manager,
-            global_proxy: proxy,
             client_cache: Mutex::new(cache),
             tls_backend,
         }
     }
 
-    /// 根据凭据的代理配置获取（或创建并缓存）对应的 reqwest::Client
+    /// 根据凭据的代理与 TLS 配置获取（或创建并缓存）对应的 reqwest::Client
     fn client_for(&self, credentials: &KiroCredentials) -> anyhow::Result<Client> {
-        let effective = credentials.effective_proxy(self.global_proxy.as_ref());
+        let proxy = credentials.effective_proxy(self.token_manager.proxy().as_ref());
+        let tls = credentials.effective_tls(self.tls_backend);
+        let key = (proxy, tls);
         let mut cache = self.client_cache.lock();
-        if let Some(client) = cache.get(&effective) {
+        if let Some(client) = cache.get(&key) {
             return Ok(client.clone());
         }
-        let client = build_client(effective.as_ref(), 720, self.tls_backend)?;
-        cache.insert(effective, client.clone());
+        let client = build_client_with_tls(key.0.as_ref(), 720, &key.1)?;
+        cache.insert(key, client.clone());
         Ok(client)
     }
 
@@ -82,18 +190,17 @@ impl KiroProvider {
 
     /// 获取 API 基础 URL（使用 config 级 api_region）
     pub fn base_url(&self) -> String {
+        let config = self.token_manager.config();
         format!(
-            "https://q.{}.amazonaws.com/generateAssistantResponse",
-            self.token_manager.config().effective_api_region()
+            "{}/generateAssistantResponse",
+            config.api_base_url(config.effective_api_region())
         )
     }
 
     /// 获取 MCP API URL（使用 config 级 api_region）
     pub fn mcp_url(&self) -> String {
-        format!(
-            "https://q.{}.amazonaws.com/mcp",
-            self.token_manager.config().effective_api_region()
-        )
+        let config = self.token_manager.config();
+        format!("{}/mcp", config.api_base_url(config.effective_api_region()))
     }
 
     /// 获取 API 基础域名（使用 config 级 api_region）
@@ -103,17 +210,19 @@ impl KiroProvider {
 
     /// 获取凭据级 API 基础 URL
     fn base_url_for(&self, credentials: &KiroCredentials) -> String {
+        let config = self.token_manager.config();
         format!(
-            "https://q.{}.amazonaws.com/generateAssistantResponse",
-            credentials.effective_api_region(self.token_manager.config())
+            "{}/generateAssistantResponse",
+            config.api_base_url(credentials.effective_api_region(&config))
         )
     }
 
     /// 获取凭据级 MCP API URL
     fn mcp_url_for(&self, credentials: &KiroCredentials) -> String {
+        let config = self.token_manager.config();
         format!(
-            "https://q.{}.amazonaws.com/mcp",
-            credentials.effective_api_region(self.token_manager.config())
+            "{}/mcp",
+            config.api_base_url(credentials.effective_api_region(&config))
         )
     }
 
@@ -121,7 +230,7 @@ impl KiroProvider {
     fn base_domain_for(&self, credentials: &KiroCredentials) -> String {
         format!(
             "q.{}.amazonaws.com",
-            credentials.effective_api_region(self.token_manager.config())
+            credentials.effective_api_region(&self.token_manager.config())
         )
     }
 
@@ -149,7 +258,7 @@ impl KiroProvider {
     fn build_headers(&self, ctx: &CallContext) -> anyhow::Result<HeaderMap> {
         let config = self.token_manager.config();
 
-        let machine_id = machine_id::generate_from_credentials(&ctx.credentials, config)
+        let machine_id = machine_id::generate_from_credentials(&ctx.credentials, &config)
             .ok_or_else(|| anyhow::anyhow!("无法生成 machine_id，请检查凭证配置"))?;
 
         let kiro_version = &config.kiro_version;
@@ -193,6 +302,7 @@ impl KiroProvider {
             HeaderValue::from_str(&format!("Bearer {}", ctx.token)).unwrap(),
         );
         headers.insert(CONNECTION, HeaderValue::from_static("close"));
+        insert_request_id(&mut headers);
 
         Ok(headers)
     }
@@ -201,7 +311,7 @@ impl KiroProvider {
     fn build_mcp_headers(&self, ctx: &CallContext) -> anyhow::Result<HeaderMap> {
         let config = self.token_manager.config();
 
-        let machine_id = machine_id::generate_from_credentials(&ctx.credentials, config)
+        let machine_id = machine_id::generate_from_credentials(&ctx.credentials, &config)
             .ok_or_else(|| anyhow::anyhow!("无法生成 machine_id，请检查凭证配置"))?;
 
         let kiro_version = &config.kiro_version;
@@ -238,6 +348,7 @@ impl KiroProvider {
             HeaderValue::from_str(&format!("Bearer {}", ctx.token)).unwrap(),
         );
         headers.insert("Connection", HeaderValue::from_static("close"));
+        insert_request_id(&mut headers);
 
         Ok(headers)
     }
@@ -256,7 +367,18 @@ impl KiroProvider {
     /// # Returns
     /// 返回原始的 HTTP Response，不做解析
     pub async fn call_api(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        self.call_api_with_retry(request_body, false).await
+        self.call_api_with_retry(request_body, false, &RequestRouting::default()).await
+    }
+
+    /// 发送非流式 API 请求（使用请求级路由参数）
+    ///
+    /// `routing` 包含重试策略、粘性会话标识与凭据池
+    pub async fn call_api_with_policy(
+        &self,
+        request_body: &str,
+        routing: &RequestRouting,
+    ) -> anyhow::Result<reqwest::Response> {
+        self.call_api_queued(request_body, false, routing).await
     }
 
     /// 发送流式 API 请求
@@ -273,7 +395,18 @@ impl KiroProvider {
     /// # Returns
     /// 返回原始的 HTTP Response，调用方负责处理流式数据
     pub async fn call_api_stream(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        self.call_api_with_retry(request_body, true).await
+        self.call_api_with_retry(request_body, true, &RequestRouting::default()).await
+    }
+
+    /// 发送流式 API 请求（使用请求级路由参数）
+    ///
+    /// `routing` 包含重试策略、粘性会话标识与凭据池
+    pub async fn call_api_stream_with_policy(
+        &self,
+        request_body: &str,
+        routing: &RequestRouting,
+    ) -> anyhow::Result<reqwest::Response> {
+        self.call_api_queued(request_body, true, routing).await
     }
 
     /// 发送 MCP API 请求
@@ -292,13 +425,16 @@ impl KiroProvider {
     /// 内部方法：带重试逻辑的 MCP API 调用
     async fn call_mcp_with_retry(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
         let total_credentials = self.token_manager.total_count();
-        let max_retries = (total_credentials * MAX_RETRIES_PER_CREDENTIAL).min(MAX_TOTAL_RETRIES);
+        let max_retries = RetryPolicy::default().resolve_max_retries(
+            total_credentials,
+            self.token_manager.config().max_retries_ceiling,
+        );
         let mut last_error: Option<anyhow::Error> = None;
 
         for attempt in 0..max_retries {
             // 获取调用上下文
             // MCP 调用（WebSearch 等工具）不涉及模型选择，无需按模型过滤凭据
-            let ctx = match self.token_manager.acquire_context(None).await {
+            let ctx = match self.token_manager.acquire_context(None, None).await {
                 Ok(c) => c,
                 Err(e) => {
                     last_error = Some(e);
@@ -332,6 +468,12 @@ impl KiroProvider {
                         max_retries,
                         e
                     );
+                    self.token_manager.record_upstream_error(
+                        ctx.id,
+                        "network_error",
+                        None,
+                        &e.to_string(),
+                    );
                     last_error = Some(e.into());
                     if attempt + 1 < max_retries {
                         sleep(Self::retry_delay(attempt)).await;
@@ -350,6 +492,7 @@ impl KiroProvider {
 
             // 失败响应
             let body = response.text().await.unwrap_or_default();
+            self.record_upstream_error(ctx.id, status, &body);
 
             // 402 额度用尽
             if status.as_u16() == 402 && Self::is_monthly_request_limit(&body) {
@@ -409,28 +552,106 @@ impl KiroProvider {
         }))
     }
 
+    /// 内部方法：按降级策略排队的 API 调用
+    ///
+    /// degradationMode 为 "wait" 时，无可用凭据的请求会轮询等待凭据恢复
+    /// （自愈、冷却恢复、速率限制令牌补充等），超过 degradationWaitSecs 后返回错误
+    async fn call_api_queued(
+        &self,
+        request_body: &str,
+        is_stream: bool,
+        routing: &RequestRouting,
+    ) -> anyhow::Result<reqwest::Response> {
+        let config = self.token_manager.config();
+        let deadline = (config.degradation_mode == "wait")
+            .then(|| Instant::now() + Duration::from_secs(config.degradation_wait_secs));
+
+        loop {
+            let result = self
+                .call_api_with_retry(request_body, is_stream, routing)
+                .await;
+            match (result, deadline) {
+                (Err(e), Some(deadline))
+                    if e.is::<NoAvailableCredentials>()
+                        && Instant::now() + DEGRADATION_POLL_INTERVAL < deadline =>
+                {
+                    tracing::debug!("无可用凭据，排队等待: {}", e);
+                    sleep(DEGRADATION_POLL_INTERVAL).await;
+                }
+                (result, _) => return result,
+            }
+        }
+    }
+
     /// 内部方法：带重试逻辑的 API 调用
     ///
     /// 重试策略：
     /// - 每个凭据最多重试 MAX_RETRIES_PER_CREDENTIAL 次
-    /// - 总重试次数 = min(凭据数量 × 每凭据重试次数, MAX_TOTAL_RETRIES)
-    /// - 硬上限 9 次，避免无限重试
+    /// - 总重试次数 = min(凭据数量 × 每凭据重试次数, config.maxRetriesCeiling)
+    /// - 请求级策略可覆盖总重试次数（仍受上限约束），或禁止故障转移
+    /// - 提供 session_key 时按粘性会话选择凭据
+    /// - 提供 pool 时仅使用带有该标签的凭据
     async fn call_api_with_retry(
         &self,
         request_body: &str,
         is_stream: bool,
+        routing: &RequestRouting,
     ) -> anyhow::Result<reqwest::Response> {
+        let policy = routing.policy;
+        let pool = routing.pool.as_deref();
         let total_credentials = self.token_manager.total_count();
-        let max_retries = (total_credentials * MAX_RETRIES_PER_CREDENTIAL).min(MAX_TOTAL_RETRIES);
+        if total_credentials == 0 {
+            return Err(NoAvailableCredentials::new("没有配置任何凭据").into());
+        }
+        let config = self.token_manager.config();
+        // 失败自动换凭据（请求禁止故障转移时不生效）
+        let failover = config
+            .retry_failover
+            .as_ref()
+            .filter(|_| !policy.no_failover);
+        let max_retries = match (failover, policy.max_retries) {
+            (Some(f), None) => f.max_attempts.clamp(1, config.max_retries_ceiling.max(1)),
+            _ => policy.resolve_max_retries(total_credentials, config.max_retries_ceiling),
+        };
+        let delay = |attempt: usize| match failover {
+            Some(f) => Self::backoff_delay(f.backoff_ms, f.max_backoff_ms, attempt),
+            None => Self::retry_delay(attempt),
+        };
+        // 本次请求中失败过或需要避开的凭据（选择时跳过）
+        let mut failed_ids: Vec<CredentialId> = routing.avoid.clone();
         let mut last_error: Option<anyhow::Error> = None;
         let api_type = if is_stream { "流式" } else { "非流式" };
+        // 禁止故障转移时，固定使用第一次获取到的凭据
+        let mut pinned_id: Option<CredentialId> = None;
+        // 上一次尝试使用的凭据与切换次数（用于 kiro_meta 回显）
+        let mut last_id: Option<CredentialId> = None;
+        let mut failovers = 0u32;
+        // 最后一次尝试的上游状态码（重试耗尽时判断是否因限流失败）
+        let mut last_status: Option<reqwest::StatusCode> = None;
+
+        if policy != RetryPolicy::default() {
+            tracing::debug!(
+                "使用请求级重试策略: max_retries={}, no_failover={}",
+                max_retries,
+                policy.no_failover
+            );
+        }
 
         // 尝试从请求体中提取模型信息
         let model = Self::extract_model_from_request(request_body);
 
         for attempt in 0..max_retries {
             // 获取调用上下文（绑定 index、credentials、token）
-            let ctx = match self.token_manager.acquire_context(model.as_deref()).await {
+            // 粘性会话优先使用客户端已绑定的凭据，不可用时自动重新绑定
+            let ctx_result = match routing.session_key.as_deref() {
+                Some(key) => {
+                    self.token_manager
+                        .acquire_context_for_session(model.as_deref(), pool, key)
+                        .await
+                }
+                None => self.token_manager.acquire_context(model.as_deref(), pool).await,
+            };
+            let ctx = match ctx_result {
                 Ok(c) => c,
                 Err(e) => {
                     last_error = Some(e);
@@ -438,6 +659,67 @@ impl KiroProvider {
                 }
             };
 
+            // 负载均衡仍选中失败过或需要避开的凭据时改用下一个可用凭据
+            let ctx = if failed_ids.contains(&ctx.id)
+                && let Some(alt) =
+                    self.token_manager
+                        .failover_candidate(model.as_deref(), pool, &failed_ids)
+            {
+                tracing::info!(
+                    "故障转移：凭据 #{} 本次请求已失败，改用凭据 #{} 重试",
+                    ctx.id,
+                    alt
+                );
+                match self.token_manager.acquire_context_for_id(alt).await {
+                    Ok(c) => c,
+                    Err(e) => {
+                        failed_ids.push(alt);
+                        last_error = Some(e);
+                        continue;
+                    }
+                }
+            } else {
+                ctx
+            };
+
+            // 配额感知准入：剩余额度不足时改用额度充足的凭据，都不足时直接拒绝
+            let ctx = match self.token_manager.admit_by_quota(
+                ctx.id,
+                model.as_deref(),
+                pool,
+                routing.estimated_tokens,
+            )? {
+                None => ctx,
+                Some(alt) => match self.token_manager.acquire_context_for_id(alt).await {
+                    Ok(c) => c,
+                    Err(e) => {
+                        last_error = Some(e);
+                        continue;
+                    }
+                },
+            };
+
+            if policy.no_failover {
+                match pinned_id {
+                    None => pinned_id = Some(ctx.id),
+                    Some(id) if id != ctx.id => {
+                        anyhow::bail!(
+                            "{} API 请求失败：凭据 #{} 不可用且请求禁止故障转移",
+                            api_type,
+                            id
+                        );
+                    }
+                    Some(_) => {}
+                }
+            }
+            if last_id.is_some_and(|id| id != ctx.id) {
+                failovers += 1;
+            }
+            last_id = Some(ctx.id);
+
+            // 凭据并发已满时在该凭据的队列中等待，队列已满或等待超时直接拒绝
+            let permit = self.token_manager.acquire_concurrency(&ctx).await?;
+
             let url = self.base_url_for(&ctx.credentials);
             let headers = match self.build_headers(&ctx) {
                 Ok(h) => h,
@@ -448,7 +730,8 @@ impl KiroProvider {
             };
 
             // 发送请求
-            let response = match self
+            let started = Instant::now();
+            let mut response = match self
                 .client_for(&ctx.credentials)?
                 .post(&url)
                 .headers(headers)
@@ -464,26 +747,47 @@ impl KiroProvider {
                         max_retries,
                         e
                     );
+                    self.token_manager.record_upstream_error(
+                        ctx.id,
+                        "network_error",
+                        None,
+                        &e.to_string(),
+                    );
                     // 网络错误通常是上游/链路瞬态问题，不应导致"禁用凭据"或"切换凭据"
                     // （否则一段时间网络抖动会把所有凭据都误禁用，需要重启才能恢复）
                     last_error = Some(e.into());
+                    last_status = None;
                     if attempt + 1 < max_retries {
-                        sleep(Self::retry_delay(attempt)).await;
+                        sleep(delay(attempt)).await;
                     }
                     continue;
                 }
             };
 
             let status = response.status();
+            last_status = Some(status);
 
-            // 成功响应
+            // 成功响应：在响应扩展中标记所用凭据与计时，供调用方记录 token 用量和延迟
             if status.is_success() {
                 self.token_manager.report_success(ctx.id);
+                response.extensions_mut().insert(ctx.id);
+                response.extensions_mut().insert(UpstreamTiming {
+                    started,
+                    ttfb: started.elapsed(),
+                });
+                response.extensions_mut().insert(UpstreamAttempts {
+                    attempts: attempt as u32 + 1,
+                    failovers,
+                });
+                if let Some(permit) = permit {
+                    response.extensions_mut().insert(permit);
+                }
                 return Ok(response);
             }
 
             // 失败响应：读取 body 用于日志/错误信息
             let body = response.text().await.unwrap_or_default();
+            self.record_upstream_error(ctx.id, status, &body);
 
             // 402 Payment Required 且额度用尽：禁用凭据并故障转移
             if status.as_u16() == 402 && Self::is_monthly_request_limit(&body) {
@@ -504,6 +808,14 @@ impl KiroProvider {
                         body
                     );
                 }
+                if policy.no_failover {
+                    anyhow::bail!(
+                        "{} API 请求失败（已禁止故障转移）: {} {}",
+                        api_type,
+                        status,
+                        body
+                    );
+                }
 
                 last_error = Some(anyhow::anyhow!(
                     "{} API 请求失败: {} {}",
@@ -538,6 +850,14 @@ impl KiroProvider {
                         body
                     );
                 }
+                if policy.no_failover {
+                    anyhow::bail!(
+                        "{} API 请求失败（已禁止故障转移）: {} {}",
+                        api_type,
+                        status,
+                        body
+                    );
+                }
 
                 last_error = Some(anyhow::anyhow!(
                     "{} API 请求失败: {} {}",
@@ -545,6 +865,12 @@ impl KiroProvider {
                     status,
                     body
                 ));
+                if failover.is_some() {
+                    failed_ids.push(ctx.id);
+                    if attempt + 1 < max_retries {
+                        sleep(delay(attempt)).await;
+                    }
+                }
                 continue;
             }
 
@@ -564,8 +890,11 @@ impl KiroProvider {
                     status,
                     body
                 ));
+                if failover.is_some() {
+                    failed_ids.push(ctx.id);
+                }
                 if attempt + 1 < max_retries {
-                    sleep(Self::retry_delay(attempt)).await;
+                    sleep(delay(attempt)).await;
                 }
                 continue;
             }
@@ -590,11 +919,15 @@ impl KiroProvider {
                 body
             ));
             if attempt + 1 < max_retries {
-                sleep(Self::retry_delay(attempt)).await;
+                sleep(delay(attempt)).await;
             }
         }
 
         // 所有重试都失败
+        if last_status == Some(reqwest::StatusCode::TOO_MANY_REQUESTS) {
+            self.token_manager
+                .notify_rate_limited(&format!("上游持续返回 429（已尝试 {} 次）", max_retries));
+        }
         Err(last_error.unwrap_or_else(|| {
             anyhow::anyhow!(
                 "{} API 请求失败：已达到最大重试次数（{}次）",
@@ -605,16 +938,86 @@ impl KiroProvider {
     }
 
     fn retry_delay(attempt: usize) -> Duration {
-        // 指数退避 + 少量抖动，避免上游抖动时放大故障
         const BASE_MS: u64 = 200;
         const MAX_MS: u64 = 2_000;
-        let exp = BASE_MS.saturating_mul(2u64.saturating_pow(attempt.min(6) as u32));
-        let backoff = exp.min(MAX_MS);
+        Self::backoff_delay(BASE_MS, MAX_MS, attempt)
+    }
+
+    fn backoff_delay(base_ms: u64, max_ms: u64, attempt: usize) -> Duration {
+        // 指数退避 + 少量抖动，避免上游抖动时放大故障
+        let exp = base_ms.saturating_mul(2u64.saturating_pow(attempt.min(6) as u32));
+        let backoff = exp.min(max_ms);
         let jitter_max = (backoff / 4).max(1);
         let jitter = fastrand::u64(0..=jitter_max);
         Duration::from_millis(backoff.saturating_add(jitter))
     }
 
+    /// 使用指定凭据发送一个极小的请求（保活探测）
+    ///
+    /// 不重试、不故障转移、不计入调用统计；只读取状态码，不消费响应流
+    pub async fn exercise_credential(
+        &self,
+        ctx: &CallContext,
+    ) -> anyhow::Result<reqwest::StatusCode> {
+        let id = ctx.id;
+
+        let message = UserInputMessage::new("hi", "claude-haiku-4.5");
+        let state = ConversationState::new(Uuid::new_v4().to_string())
+            .with_agent_task_type("vibe")
+            .with_chat_trigger_type("MANUAL")
+            .with_current_message(CurrentMessage::new(message));
+        let request = KiroRequest {
+            conversation_state: state,
+            profile_arn: ctx.credentials.profile_arn.clone(),
+        };
+
+        let response = self
+            .client_for(&ctx.credentials)?
+            .post(self.base_url_for(&ctx.credentials))
+            .headers(self.build_headers(ctx)?)
+            .body(serde_json::to_string(&request)?)
+            .send()
+            .await
+            .inspect_err(|e| {
+                let message = e.to_string();
+                self.token_manager
+                    .record_upstream_error(id, "network_error", None, &message)
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            self.record_upstream_error(id, status, &body);
+        }
+        Ok(status)
+    }
+
+    /// 记录凭据最近一次上游错误响应
+    fn record_upstream_error(&self, id: CredentialId, status: reqwest::StatusCode, body: &str) {
+        let error_type = Self::upstream_error_type(status, body);
+        let message = if body.is_empty() {
+            status.to_string()
+        } else {
+            body.to_string()
+        };
+        self.token_manager
+            .record_upstream_error(id, error_type, Some(status.as_u16()), &message);
+    }
+
+    /// 根据状态码和响应体归类上游错误
+    fn upstream_error_type(status: reqwest::StatusCode, body: &str) -> &'static str {
+        match status.as_u16() {
+            402 if Self::is_monthly_request_limit(body) => "quota_exhausted",
+            400 => "bad_request",
+            401 | 403 => "auth_error",
+            408 => "timeout",
+            429 => "rate_limited",
+            _ if status.is_server_error() => "server_error",
+            _ if status.is_client_error() => "client_error",
+            _ => "unknown",
+        }
+    }
+
     fn is_monthly_request_limit(body: &str) -> bool {
         if body.contains("MONTHLY_REQUEST_COUNT") {
             return true;
@@ -639,6 +1042,13 @@ impl KiroProvider {
     }
 }
 
+/// 把当前请求 ID 带到上游请求中（后台任务发起的请求没有请求 ID）
+fn insert_request_id(headers: &mut HeaderMap) {
+    if let Some(value) = request_id::current().and_then(|id| HeaderValue::from_str(&id).ok()) {
+        headers.insert(request_id::REQUEST_ID_HEADER, value);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -680,7 +1090,7 @@ mod tests {
 
         let provider = create_test_provider(config, credentials.clone());
         let ctx = CallContext {
-            id: 1,
+            id: CredentialId(1),
             credentials,
             token: "test_token".to_string(),
         };
@@ -700,6 +1110,24 @@ mod tests {
         assert_eq!(headers.get(CONNECTION).unwrap(), "close");
     }
 
+    #[tokio::test]
+    async fn test_call_api_reports_no_available_credentials() {
+        let mut config = Config::default();
+        config.degradation_mode = "wait".to_string();
+        config.degradation_wait_secs = 1;
+        let mut credentials = KiroCredentials::default();
+        credentials.disabled = true;
+        let provider = create_test_provider(config, credentials);
+
+        let started = Instant::now();
+        let err = provider
+            .call_api_with_policy("{}", &RequestRouting::default())
+            .await
+            .unwrap_err();
+        assert!(err.is::<NoAvailableCredentials>(), "实际错误: {}", err);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
     #[test]
     fn test_is_monthly_request_limit_detects_reason() {
         let body = r#"{"message":"You have reached the limit.","reason":"MONTHLY_REQUEST_COUNT"}"#;
@@ -717,4 +1145,64 @@ mod tests {
         let body = r#"{"message":"nope","reason":"DAILY_REQUEST_COUNT"}"#;
         assert!(!KiroProvider::is_monthly_request_limit(body));
     }
+
+    #[test]
+    fn test_retry_policy_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(RetryPolicy::from_headers(&headers), RetryPolicy::default());
+
+        headers.insert(MAX_RETRIES_HEADER, HeaderValue::from_static("2"));
+        headers.insert(NO_FAILOVER_HEADER, HeaderValue::from_static("true"));
+        let policy = RetryPolicy::from_headers(&headers);
+        assert_eq!(policy.max_retries, Some(2));
+        assert!(policy.no_failover);
+
+        headers.insert(MAX_RETRIES_HEADER, HeaderValue::from_static("abc"));
+        headers.insert(NO_FAILOVER_HEADER, HeaderValue::from_static("0"));
+        assert_eq!(RetryPolicy::from_headers(&headers), RetryPolicy::default());
+    }
+
+    #[test]
+    fn test_retry_policy_resolve_max_retries_bounded_by_ceiling() {
+        let default_policy = RetryPolicy::default();
+        assert_eq!(default_policy.resolve_max_retries(2, 9), 6);
+        assert_eq!(default_policy.resolve_max_retries(5, 9), 9);
+
+        let aggressive = RetryPolicy {
+            max_retries: Some(50),
+            no_failover: false,
+        };
+        assert_eq!(aggressive.resolve_max_retries(1, 9), 9);
+
+        let interactive = RetryPolicy {
+            max_retries: Some(0),
+            no_failover: true,
+        };
+        assert_eq!(interactive.resolve_max_retries(3, 9), 1);
+    }
+
+    #[test]
+    fn test_record_upstream_error_classifies_and_truncates() {
+        let provider = create_test_provider(Config::default(), KiroCredentials::default());
+        let body = r#"{"reason":"MONTHLY_REQUEST_COUNT"}"#;
+        assert_eq!(
+            KiroProvider::upstream_error_type(reqwest::StatusCode::PAYMENT_REQUIRED, body),
+            "quota_exhausted"
+        );
+        assert_eq!(
+            KiroProvider::upstream_error_type(reqwest::StatusCode::BAD_GATEWAY, ""),
+            "server_error"
+        );
+
+        provider.record_upstream_error(
+            CredentialId(1),
+            reqwest::StatusCode::FORBIDDEN,
+            &"x".repeat(2000),
+        );
+        let snapshot = provider.token_manager.snapshot();
+        let error = snapshot.entries[0].last_error.as_ref().unwrap();
+        assert_eq!(error.error_type, "auth_error");
+        assert_eq!(error.status, Some(403));
+        assert_eq!(error.message.chars().count(), 503);
+    }
 }
@@ -0,0 +1,153 @@
+//! 上游请求体大小统计
+//!
+//! 长上下文的 Agent 请求体可达数 MB，上行带宽较慢时上传本身就要数秒。
+//! 这里记录最近若干次上游请求体的字节数，汇总为百分位与分桶直方图，
+//! 供 Admin API 判断首字节时间偏高是否由请求体过大导致
+
+use std::collections::VecDeque;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use super::latency::percentile;
+
+/// 保留的最近样本数
+const MAX_SAMPLES: usize = 1000;
+
+/// 直方图分桶上界（字节），最后一个桶收纳超过最大上界的样本
+const HISTOGRAM_BOUNDS_BYTES: [u64; 7] = [
+    16 << 10,
+    64 << 10,
+    256 << 10,
+    1 << 20,
+    4 << 20,
+    16 << 20,
+    64 << 20,
+];
+
+/// 直方图分桶
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeBucket {
+    /// 分桶上界（字节，包含），None 表示 +Inf
+    pub le_bytes: Option<u64>,
+    pub count: u64,
+}
+
+/// 请求体大小汇总
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestSizeSummary {
+    /// 启动以来的请求数
+    pub total_requests: u64,
+    /// 启动以来发送的请求体总字节数
+    pub total_bytes: u64,
+    /// 窗口内样本数
+    pub count: usize,
+    pub avg_bytes: f64,
+    pub p50_bytes: u64,
+    pub p95_bytes: u64,
+    pub p99_bytes: u64,
+    pub max_bytes: u64,
+    pub histogram: Vec<SizeBucket>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    samples: VecDeque<u64>,
+    total_requests: u64,
+    total_bytes: u64,
+}
+
+/// 上游请求体大小样本（仅内存）
+#[derive(Debug, Default)]
+pub struct RequestSizeStats {
+    inner: Mutex<Inner>,
+}
+
+impl RequestSizeStats {
+    pub fn record(&self, bytes: usize) {
+        let bytes = bytes as u64;
+        let mut inner = self.inner.lock();
+        if inner.samples.len() >= MAX_SAMPLES {
+            inner.samples.pop_front();
+        }
+        inner.samples.push_back(bytes);
+        inner.total_requests += 1;
+        inner.total_bytes += bytes;
+    }
+
+    pub fn summary(&self) -> RequestSizeSummary {
+        let inner = self.inner.lock();
+        let mut sorted: Vec<u64> = inner.samples.iter().copied().collect();
+        sorted.sort_unstable();
+
+        let mut histogram: Vec<SizeBucket> = HISTOGRAM_BOUNDS_BYTES
+            .iter()
+            .map(|le| SizeBucket {
+                le_bytes: Some(*le),
+                count: 0,
+            })
+            .chain(std::iter::once(SizeBucket {
+                le_bytes: None,
+                count: 0,
+            }))
+            .collect();
+        for value in &sorted {
+            let index = HISTOGRAM_BOUNDS_BYTES
+                .iter()
+                .position(|le| value <= le)
+                .unwrap_or(HISTOGRAM_BOUNDS_BYTES.len());
+            histogram[index].count += 1;
+        }
+
+        let avg_bytes = if sorted.is_empty() {
+            0.0
+        } else {
+            sorted.iter().sum::<u64>() as f64 / sorted.len() as f64
+        };
+
+        RequestSizeSummary {
+            total_requests: inner.total_requests,
+            total_bytes: inner.total_bytes,
+            count: sorted.len(),
+            avg_bytes,
+            p50_bytes: percentile(&sorted, 50.0),
+            p95_bytes: percentile(&sorted, 95.0),
+            p99_bytes: percentile(&sorted, 99.0),
+            max_bytes: sorted.last().copied().unwrap_or(0),
+            histogram,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_size_summary() {
+        let stats = RequestSizeStats::default();
+        for kib in 1..=100 {
+            stats.record(kib << 10);
+        }
+        stats.record(100 << 20);
+
+        let summary = stats.summary();
+        assert_eq!(summary.total_requests, 101);
+        assert_eq!(summary.count, 101);
+        assert_eq!(summary.p50_bytes, 51 << 10);
+        assert_eq!(summary.max_bytes, 100 << 20);
+        assert_eq!(summary.histogram[0].count, 16);
+        assert_eq!(summary.histogram[1].count, 48);
+        assert_eq!(summary.histogram[2].count, 36);
+        assert_eq!(summary.histogram.last().unwrap().count, 1);
+
+        for _ in 0..MAX_SAMPLES {
+            stats.record(1);
+        }
+        let summary = stats.summary();
+        assert_eq!(summary.count, MAX_SAMPLES);
+        assert_eq!(summary.total_requests, 101 + MAX_SAMPLES as u64);
+    }
+}
@@ -0,0 +1,263 @@
+//! 多副本共享状态
+//!
+//! 多个副本部署在负载均衡之后、共享同一份凭据文件时，各自在内存中维护速率限制令牌桶、
+//! 成功次数与额度数据，选择凭据时互不知晓。配置 `sharedState` 后，后台任务按固定间隔经 Redis 交换：
+//! - 各凭据的累计请求数、成功次数与 tokens：推送本副本的增量，再把其他副本的增量计入本地
+//!   （扣减速率限制令牌、计入滚动窗口用量与 balanced 模式使用的成功次数）
+//! - 最近一次额度查询结果：采用任一副本查到的最新余额
+//! - 当前凭据（priority 模式的负载均衡游标）：一个副本切换后其余副本跟随
+//!
+//! 凭据按 ID 对应，各副本的凭据 ID 需一致
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+
+use crate::kiro::model::credentials::CredentialId;
+use crate::kiro::model::usage_limits::UsageLimitsResponse;
+use crate::kiro::token_manager::{LocalActivity, MultiTokenManager};
+
+/// 未配置 `sharedState` 时检查配置的间隔（支持热重载后开始同步）
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// 单轮同步超时
+const SYNC_TIMEOUT: Duration = Duration::from_secs(10);
+/// 共享余额的有效期（秒），避免已删除凭据的数据长期残留
+const BALANCE_TTL_SECS: u64 = 3600;
+
+/// Redis 中保存的额度查询结果
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SharedBalance {
+    fetched_at: DateTime<Utc>,
+    usage: UsageLimitsResponse,
+}
+
+/// 启动多副本共享状态同步后台任务
+///
+/// 每轮按最新配置同步，Redis 地址变更后重新连接；连接断开时由连接管理器自动重连
+pub async fn start_shared_state_worker(token_manager: Arc<MultiTokenManager>) {
+    tracing::info!("多副本共享状态同步任务启动");
+    let mut connection: Option<(String, ConnectionManager)> = None;
+    let mut sync = SyncState::default();
+
+    loop {
+        let config = token_manager.config();
+        let Some(shared) = config.shared_state.clone() else {
+            connection = None;
+            tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+            continue;
+        };
+
+        if connection
+            .as_ref()
+            .is_none_or(|(url, _)| *url != shared.redis_url)
+        {
+            sync = SyncState::default();
+            connection = match connect(&shared.redis_url).await {
+                Ok(conn) => {
+                    tracing::info!("已连接共享状态 Redis");
+                    Some((shared.redis_url.clone(), conn))
+                }
+                Err(e) => {
+                    tracing::warn!("连接共享状态 Redis 失败: {:#}", e);
+                    None
+                }
+            };
+        }
+
+        if let Some((_, conn)) = connection.as_mut() {
+            let round = sync.run(conn, &token_manager, &shared.key_prefix);
+            match tokio::time::timeout(SYNC_TIMEOUT, round).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::warn!("同步共享状态失败: {:#}", e),
+                Err(_) => tracing::warn!("同步共享状态超时"),
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(shared.sync_interval.max(1))).await;
+    }
+}
+
+async fn connect(url: &str) -> anyhow::Result<ConnectionManager> {
+    let client = redis::Client::open(url)?;
+    let conn = tokio::time::timeout(SYNC_TIMEOUT, ConnectionManager::new(client)).await??;
+    Ok(conn)
+}
+
+/// 同步进度（重新连接后清空）
+#[derive(Default)]
+struct SyncState {
+    /// 各凭据已推送的本副本累计用量
+    pushed: HashMap<CredentialId, LocalActivity>,
+    /// 各凭据上次看到的全局累计用量
+    seen: HashMap<CredentialId, LocalActivity>,
+    /// 上次同步时的当前凭据
+    cursor: Option<CredentialId>,
+}
+
+impl SyncState {
+    async fn run(
+        &mut self,
+        conn: &mut ConnectionManager,
+        token_manager: &MultiTokenManager,
+        prefix: &str,
+    ) -> anyhow::Result<()> {
+        for (id, local) in token_manager.local_activity() {
+            let pushed = self.pushed.get(&id).copied().unwrap_or_default();
+            let delta = activity_sub(local, pushed);
+            let key = format!("{}:activity:{}", prefix, id);
+            let (requests, successes, tokens): (u64, u64, u64) = redis::pipe()
+                .atomic()
+                .cmd("HINCRBY")
+                .arg(&key)
+                .arg("requests")
+                .arg(delta.requests)
+                .cmd("HINCRBY")
+                .arg(&key)
+                .arg("successes")
+                .arg(delta.successes)
+                .cmd("HINCRBY")
+                .arg(&key)
+                .arg("tokens")
+                .arg(delta.tokens)
+                .query_async(conn)
+                .await?;
+            let total = LocalActivity {
+                requests,
+                successes,
+                tokens,
+            };
+            // 首次看到的累计值包含历史用量，只作为基准
+            if let Some(previous) = self.seen.get(&id) {
+                let remote = activity_sub(activity_sub(total, *previous), delta);
+                if remote != LocalActivity::default() {
+                    token_manager.apply_remote_activity(id, remote);
+                }
+            }
+            self.pushed.insert(id, local);
+            self.seen.insert(id, total);
+
+            sync_balance(conn, token_manager, prefix, id).await?;
+        }
+        self.sync_cursor(conn, token_manager, prefix).await
+    }
+
+    /// 本副本切换了当前凭据时推送，否则跟随其他副本的选择
+    async fn sync_cursor(
+        &mut self,
+        conn: &mut ConnectionManager,
+        token_manager: &MultiTokenManager,
+        prefix: &str,
+    ) -> anyhow::Result<()> {
+        let key = format!("{}:cursor", prefix);
+        let mut local = token_manager.current_id();
+        let changed_locally = self.cursor.is_some_and(|c| c != local);
+        let remote = if changed_locally {
+            None
+        } else {
+            redis::cmd("GET")
+                .arg(&key)
+                .query_async::<Option<u64>>(conn)
+                .await?
+                .map(CredentialId)
+        };
+        match remote {
+            Some(remote) if remote == local => {}
+            Some(remote) if token_manager.adopt_current_id(remote) => {
+                tracing::debug!("跟随其他副本切换到凭据 #{}", remote);
+                local = remote;
+            }
+            _ => {
+                redis::cmd("SET")
+                    .arg(&key)
+                    .arg(local.0)
+                    .query_async::<()>(conn)
+                    .await?;
+            }
+        }
+        self.cursor = Some(local);
+        Ok(())
+    }
+}
+
+/// 本地额度数据较新时推送，Redis 中的较新时采用
+async fn sync_balance(
+    conn: &mut ConnectionManager,
+    token_manager: &MultiTokenManager,
+    prefix: &str,
+    id: CredentialId,
+) -> anyhow::Result<()> {
+    let key = format!("{}:balance:{}", prefix, id);
+    let remote: Option<SharedBalance> = redis::cmd("GET")
+        .arg(&key)
+        .query_async::<Option<String>>(conn)
+        .await?
+        .and_then(|s| serde_json::from_str(&s).ok());
+
+    match (token_manager.latest_usage_limits(id), remote) {
+        (Some((fetched_at, usage)), remote)
+            if remote.as_ref().is_none_or(|r| r.fetched_at < fetched_at) =>
+        {
+            let body = serde_json::to_string(&SharedBalance { fetched_at, usage })?;
+            redis::cmd("SET")
+                .arg(&key)
+                .arg(body)
+                .arg("EX")
+                .arg(BALANCE_TTL_SECS)
+                .query_async::<()>(conn)
+                .await?;
+        }
+        (_, Some(remote)) => {
+            let adopted =
+                token_manager.apply_remote_usage_limits(id, remote.fetched_at, remote.usage);
+            if adopted {
+                tracing::debug!("已采用其他副本查询的凭据 #{} 余额", id);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 逐项相减（不足时取 0）
+fn activity_sub(a: LocalActivity, b: LocalActivity) -> LocalActivity {
+    LocalActivity {
+        requests: a.requests.saturating_sub(b.requests),
+        successes: a.successes.saturating_sub(b.successes),
+        tokens: a.tokens.saturating_sub(b.tokens),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(requests: u64, successes: u64, tokens: u64) -> LocalActivity {
+        LocalActivity {
+            requests,
+            successes,
+            tokens,
+        }
+    }
+
+    #[test]
+    fn test_remote_activity_excludes_own_delta() {
+        // 上次看到全局 10/8/1000，本次推送 2/2/300 后全局为 15/12/1500：其他副本贡献 3/2/200
+        let previous = activity(10, 8, 1000);
+        let total = activity(15, 12, 1500);
+        let delta = activity(2, 2, 300);
+        assert_eq!(
+            activity_sub(activity_sub(total, previous), delta),
+            activity(3, 2, 200)
+        );
+        // 其他副本没有用量
+        assert_eq!(
+            activity_sub(activity_sub(activity(12, 10, 1300), previous), delta),
+            LocalActivity::default()
+        );
+    }
+}
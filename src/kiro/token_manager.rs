@@ -4,25 +4,33 @@
 //! 支持单凭据 (TokenManager) 和多凭据 (MultiTokenManager) 管理
 
 use anyhow::bail;
-use chrono::{DateTime, Duration, Utc};
-use parking_lot::Mutex;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tokio::sync::Mutex as TokioMutex;
+use tokio::sync::broadcast;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration as StdDuration, Instant};
 
-use crate::http_client::{ProxyConfig, build_client};
+use crate::common::atomic_file::write_atomic;
+use crate::common::time::{parse_utc_offset, timestamp_to_utc};
+use crate::http_client::{ProxyConfig, build_client_with_tls};
+use crate::kiro::concurrency::{ConcurrencyLimited, ConcurrencyLimiter, ConcurrencyPermit};
+use crate::kiro::latency::{CredentialLatency, LatencyStats, LatencySummary};
+use crate::kiro::request_size::{RequestSizeStats, RequestSizeSummary};
 use crate::kiro::machine_id;
-use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::model::credentials::{CredentialId, KiroCredentials};
 use crate::kiro::model::token_refresh::{
     IdcRefreshRequest, IdcRefreshResponse, RefreshRequest, RefreshResponse,
 };
 use crate::kiro::model::usage_limits::UsageLimitsResponse;
-use crate::model::config::Config;
+use crate::model::config::{Config, PriorityPreset, UsageCap, UsageWatermark, WatermarkAction};
+use crate::notify::{EventKind, NotificationEvent};
 
 /// Token 管理器
 ///
@@ -89,8 +97,6 @@ pub(crate) fn is_token_expiring_within(
 ) -> Option<bool> {
     credentials
         .expires_at
-        .as_ref()
-        .and_then(|expires_at| DateTime::parse_from_rfc3339(expires_at).ok())
         .map(|expires| expires <= Utc::now() + Duration::minutes(minutes))
 }
 
@@ -104,7 +110,7 @@ pub(crate) fn is_token_expiring_soon(credentials: &KiroCredentials) -> bool {
     is_token_expiring_within(credentials, 10).unwrap_or(false)
 }
 
-fn sha256_hex(input: &str) -> String {
+pub(crate) fn sha256_hex(input: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(input.as_bytes());
     let result = hasher.finalize();
@@ -180,7 +186,8 @@ async fn refresh_social_token(
         .ok_or_else(|| anyhow::anyhow!("无法生成 machineId"))?;
     let kiro_version = &config.kiro_version;
 
-    let client = build_client(proxy, 60, config.tls_backend)?;
+    let tls = credentials.effective_tls(config.tls_backend);
+    let client = build_client_with_tls(proxy, 60, &tls)?;
     let body = RefreshRequest {
         refresh_token: refresh_token.to_string(),
     };
@@ -227,15 +234,14 @@ async fn refresh_social_token(
     }
 
     if let Some(expires_in) = data.expires_in {
-        let expires_at = Utc::now() + Duration::seconds(expires_in);
-        new_credentials.expires_at = Some(expires_at.to_rfc3339());
+        new_credentials.expires_at = Some(Utc::now() + Duration::seconds(expires_in));
     }
 
     Ok(new_credentials)
 }
 
 /// IdC Token 刷新所需的 x-amz-user-agent header
-const IDC_AMZ_USER_AGENT: &str = "aws-sdk-js/3.738.0 ua/2.1 os/other lang/js md/browser#unknown_unknown api/sso-oidc#3.738.0 m/E KiroIDE";
+pub(crate) const IDC_AMZ_USER_AGENT: &str = "aws-sdk-js/3.738.0 ua/2.1 os/other lang/js md/browser#unknown_unknown api/sso-oidc#3.738.0 m/E KiroIDE";
 
 /// 刷新 IdC Token (AWS SSO OIDC)
 async fn refresh_idc_token(
@@ -259,7 +265,8 @@ async fn refresh_idc_token(
     let region = credentials.effective_auth_region(config);
     let refresh_url = format!("https://oidc.{}.amazonaws.com/token", region);
 
-    let client = build_client(proxy, 60, config.tls_backend)?;
+    let tls = credentials.effective_tls(config.tls_backend);
+    let client = build_client_with_tls(proxy, 60, &tls)?;
     let body = IdcRefreshRequest {
         client_id: client_id.to_string(),
         client_secret: client_secret.to_string(),
@@ -305,8 +312,7 @@ async fn refresh_idc_token(
     }
 
     if let Some(expires_in) = data.expires_in {
-        let expires_at = Utc::now() + Duration::seconds(expires_in);
-        new_credentials.expires_at = Some(expires_at.to_rfc3339());
+        new_credentials.expires_at = Some(Utc::now() + Duration::seconds(expires_in));
     }
 
     Ok(new_credentials)
@@ -333,8 +339,8 @@ pub(crate) async fn get_usage_limits(
 
     // 构建 URL
     let mut url = format!(
-        "https://{}/getUsageLimits?origin=AI_EDITOR&resourceType=AGENTIC_REQUEST",
-        host
+        "{}/getUsageLimits?origin=AI_EDITOR&resourceType=AGENTIC_REQUEST&isEmailRequired=true",
+        config.api_base_url(region)
     );
 
     // profileArn 是可选的
@@ -353,7 +359,8 @@ pub(crate) async fn get_usage_limits(
         USAGE_LIMITS_AMZ_USER_AGENT_PREFIX, kiro_version, machine_id
     );
 
-    let client = build_client(proxy, 60, config.tls_backend)?;
+    let tls = credentials.effective_tls(config.tls_backend);
+    let client = build_client_with_tls(proxy, 60, &tls)?;
 
     let response = client
         .get(&url)
@@ -391,7 +398,7 @@ pub(crate) async fn get_usage_limits(
 /// 单个凭据条目的状态
 struct CredentialEntry {
     /// 凭据唯一 ID
-    id: u64,
+    id: CredentialId,
     /// 凭据信息
     credentials: KiroCredentials,
     /// API 调用连续失败次数
@@ -403,7 +410,283 @@ struct CredentialEntry {
     /// API 调用成功次数
     success_count: u64,
     /// 最后一次 API 调用时间（RFC3339 格式）
-    last_used_at: Option<String>,
+    last_used_at: Option<DateTime<Utc>>,
+    /// 上一次额度查询观测到的订阅状态（用于检测订阅变更）
+    last_usage: Option<UsageObservation>,
+    /// 速率限制令牌桶（未配置 RPM 时为 None）
+    rate_limiter: Option<TokenBucket>,
+    /// 自动禁用（连续失败）的时间，用于冷却后自动恢复
+    disabled_at: Option<Instant>,
+    /// 冷却后探测失败的次数（用于指数退避）
+    recovery_attempts: u32,
+    /// 最近一次上游错误
+    last_error: Option<UpstreamErrorRecord>,
+    /// 健康检查状态
+    health: HealthStatus,
+    /// 最后一次健康检查时间（RFC3339 格式）
+    last_health_check_at: Option<DateTime<Utc>>,
+    /// 连续健康检查成功次数（用于降级凭据恢复）
+    health_success_streak: u32,
+    /// 按日（运营者时区）汇总的用量历史
+    daily_usage: BTreeMap<NaiveDate, DailyUsage>,
+    /// 上游返回的下次额度重置时间（额度用尽禁用后据此自动恢复）
+    quota_reset_at: Option<DateTime<Utc>>,
+    /// 滚动窗口用量（用于用量上限）
+    rolling_usage: RollingUsage,
+    /// 估算的剩余额度（额度查询时更新，此后按配额感知准入的折算扣减；未查询时为 None）
+    quota_remaining: Option<f64>,
+    /// 是否已发出余额不足通知（额度回升到阈值以上后重置）
+    low_balance_notified: bool,
+    /// 用量是否已达到警戒线（用量回落到警戒线以下后重置）
+    watermark_reached: bool,
+    /// 最近一次额度查询的结果及时间（Admin 余额展示复用）
+    latest_usage: Option<(DateTime<Utc>, UsageLimitsResponse)>,
+    /// 本实例产生的累计用量（不含从其他副本同步的部分）
+    local_activity: LocalActivity,
+}
+
+impl CredentialEntry {
+    /// 获取当天（运营者时区）的用量桶，并清理超出保留期的历史
+    fn today_usage(&mut self, offset: FixedOffset) -> &mut DailyUsage {
+        let today = Utc::now().with_timezone(&offset).date_naive();
+        if !self.daily_usage.contains_key(&today) {
+            let cutoff = today - Duration::days(USAGE_HISTORY_DAYS);
+            self.daily_usage.retain(|date, _| *date > cutoff);
+        }
+        self.daily_usage.entry(today).or_default()
+    }
+
+    /// 重新启用凭据并清空失败及恢复状态
+    fn enable(&mut self) {
+        self.disabled = false;
+        self.disabled_reason = None;
+        self.failure_count = 0;
+        self.disabled_at = None;
+        self.recovery_attempts = 0;
+    }
+
+    /// 是否因失败（API 调用或健康检查）被自动禁用，可参与全灭自愈
+    fn is_auto_disabled(&self) -> bool {
+        matches!(
+            self.disabled_reason,
+            Some(DisabledReason::TooManyFailures | DisabledReason::Unhealthy)
+        )
+    }
+
+    /// 当前是否未触发速率限制
+    fn within_rate_limit(&self, now: Instant) -> bool {
+        self.rate_limiter
+            .as_ref()
+            .is_none_or(|bucket| bucket.has_token(now))
+    }
+
+    /// 生效的滚动窗口用量上限（凭据级配置整体覆盖全局 credentialUsageCap）
+    fn usage_cap<'a>(&'a self, config: &'a Config) -> Option<&'a UsageCap> {
+        self.credentials
+            .usage_cap
+            .as_ref()
+            .or(config.credential_usage_cap.as_ref())
+            .filter(|cap| !cap.is_unlimited())
+    }
+
+    /// 生效的用量警戒线（凭据级配置整体覆盖全局 usageWatermark）
+    fn usage_watermark<'a>(&'a self, config: &'a Config) -> Option<&'a UsageWatermark> {
+        self.credentials
+            .usage_watermark
+            .as_ref()
+            .or(config.usage_watermark.as_ref())
+    }
+
+    /// 是否因达到用量警戒线而降低优先级（仅在没有其他可用凭据时选择）
+    fn watermark_deprioritized(&self, config: &Config) -> bool {
+        self.watermark_reached
+            && self
+                .usage_watermark(config)
+                .is_some_and(|w| w.action == WatermarkAction::Deprioritize)
+    }
+
+    /// 按额度查询得到的用量百分比更新警戒线状态，刚越过警戒线时返回生效的警戒线
+    ///
+    /// 只在越过时处理一次：手动启用后不会再次禁用，便于应急使用；
+    /// 回落到警戒线以下（通常是额度重置）时恢复因警戒线禁用的凭据
+    fn observe_usage_percent(
+        &mut self,
+        config: &Config,
+        usage_percent: f64,
+    ) -> Option<UsageWatermark> {
+        match self.usage_watermark(config).cloned() {
+            Some(watermark) if usage_percent >= watermark.percent => {
+                if self.watermark_reached {
+                    return None;
+                }
+                self.watermark_reached = true;
+                if watermark.action == WatermarkAction::Disable && !self.disabled {
+                    self.disabled = true;
+                    self.disabled_reason = Some(DisabledReason::UsageWatermark);
+                }
+                Some(watermark)
+            }
+            _ => {
+                self.watermark_reached = false;
+                if self.disabled_reason == Some(DisabledReason::UsageWatermark) {
+                    self.enable();
+                    tracing::info!("凭据 #{} 用量已回落到警戒线以下，已重新启用", self.id);
+                }
+                None
+            }
+        }
+    }
+
+    /// 滚动窗口用量是否尚未达到上限
+    fn within_usage_cap(&self, config: &Config, minute: i64) -> bool {
+        self.usage_cap(config)
+            .is_none_or(|cap| !self.rolling_usage.window(minute).exceeds(cap))
+    }
+}
+
+/// 滚动窗口长度（分钟）
+const ROLLING_WINDOW_MINUTES: i64 = 24 * 60;
+
+/// 当前 Unix 分钟数（滚动窗口分桶键）
+fn current_minute() -> i64 {
+    Utc::now().timestamp().div_euclid(60)
+}
+
+/// 最近 1 小时 / 24 小时的用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowUsage {
+    pub hour_requests: u64,
+    pub hour_tokens: u64,
+    pub day_requests: u64,
+    pub day_tokens: u64,
+}
+
+impl WindowUsage {
+    /// 是否有任一项达到上限
+    fn exceeds(&self, cap: &UsageCap) -> bool {
+        let reached = |limit: Option<u64>, used: u64| limit.is_some_and(|l| l > 0 && used >= l);
+        reached(cap.hourly_requests, self.hour_requests)
+            || reached(cap.hourly_tokens, self.hour_tokens)
+            || reached(cap.daily_requests, self.day_requests)
+            || reached(cap.daily_tokens, self.day_tokens)
+    }
+}
+
+/// 本实例产生的累计用量（只增不减，多副本共享状态据此计算增量）
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LocalActivity {
+    /// 上游请求数（消耗速率限制令牌的次数）
+    pub requests: u64,
+    /// 成功次数
+    pub successes: u64,
+    /// tokens（输入 + 输出）
+    pub tokens: u64,
+}
+
+/// 按分钟分桶的滚动用量，仅保留最近 24 小时（不持久化，重启后清零）
+#[derive(Debug, Default)]
+struct RollingUsage {
+    /// (分钟, 请求数, tokens)，按时间升序
+    buckets: VecDeque<(i64, u64, u64)>,
+}
+
+impl RollingUsage {
+    fn add(&mut self, minute: i64, requests: u64, tokens: u64) {
+        match self.buckets.back_mut() {
+            Some(bucket) if bucket.0 == minute => {
+                bucket.1 += requests;
+                bucket.2 += tokens;
+            }
+            _ => self.buckets.push_back((minute, requests, tokens)),
+        }
+        while self
+            .buckets
+            .front()
+            .is_some_and(|b| b.0 <= minute - ROLLING_WINDOW_MINUTES)
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    fn window(&self, minute: i64) -> WindowUsage {
+        let mut usage = WindowUsage::default();
+        for &(m, requests, tokens) in &self.buckets {
+            if m > minute - ROLLING_WINDOW_MINUTES {
+                usage.day_requests += requests;
+                usage.day_tokens += tokens;
+            }
+            if m > minute - 60 {
+                usage.hour_requests += requests;
+                usage.hour_tokens += tokens;
+            }
+        }
+        usage
+    }
+}
+
+/// 令牌桶限流器
+///
+/// 容量等于每分钟请求数，按 RPM/60 的速率匀速补充；
+/// 允许令牌透支为负数，透支部分会延长后续等待时间
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    rpm: u32,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rpm: u32) -> Self {
+        Self {
+            rpm,
+            tokens: rpm as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// 根据凭据与全局配置构建令牌桶，RPM 为 0 或未配置时不限流
+    fn for_credential(credentials: &KiroCredentials, config: &Config) -> Option<Self> {
+        credentials
+            .rate_limit_rpm
+            .or(config.credential_rpm)
+            .filter(|rpm| *rpm > 0)
+            .map(Self::new)
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.rpm as f64 / 60.0
+    }
+
+    fn available(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        (self.tokens + elapsed * self.refill_per_sec()).min(self.rpm as f64)
+    }
+
+    fn has_token(&self, now: Instant) -> bool {
+        self.available(now) >= 1.0
+    }
+
+    /// 距离下一个令牌可用的等待时间
+    fn wait_time(&self, now: Instant) -> StdDuration {
+        let missing = 1.0 - self.available(now);
+        if missing <= 0.0 {
+            StdDuration::ZERO
+        } else {
+            StdDuration::from_secs_f64(missing / self.refill_per_sec())
+        }
+    }
+
+    fn consume(&mut self, now: Instant) {
+        self.tokens = self.available(now) - 1.0;
+        self.last_refill = now;
+    }
+
+    /// 扣除其他副本消耗的令牌（最多透支一分钟的配额，等待时间相应延长）
+    fn drain(&mut self, count: u64, now: Instant) {
+        self.tokens = (self.available(now) - count as f64).max(-(self.rpm as f64));
+        self.last_refill = now;
+    }
 }
 
 /// 禁用原因
@@ -415,25 +698,400 @@ enum DisabledReason {
     TooManyFailures,
     /// 额度已用尽（如 MONTHLY_REQUEST_COUNT）
     QuotaExceeded,
+    /// 健康检查失败后降级
+    Unhealthy,
+    /// 用量达到警戒线（action 为 disable）
+    UsageWatermark,
 }
 
 /// 统计数据持久化条目
 #[derive(Serialize, Deserialize)]
 struct StatsEntry {
     success_count: u64,
-    last_used_at: Option<String>,
+    last_used_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    daily_usage: BTreeMap<NaiveDate, DailyUsage>,
+}
+
+/// 一次额度查询中与订阅相关的观测值
+#[derive(Debug, Clone, PartialEq)]
+struct UsageObservation {
+    subscription_title: Option<String>,
+    usage_limit: f64,
+    free_trial_active: bool,
+}
+
+impl UsageObservation {
+    fn from_usage(usage: &UsageLimitsResponse) -> Self {
+        Self {
+            subscription_title: usage.subscription_title().map(|s| s.to_string()),
+            usage_limit: usage.usage_limit(),
+            free_trial_active: usage.free_trial_active(),
+        }
+    }
+}
+
+/// 用额度查询返回的订阅等级、账户邮箱与账户 ID 更新凭据，返回是否有变化（需要持久化）
+fn enrich_account_metadata(
+    id: CredentialId,
+    credentials: &mut KiroCredentials,
+    usage: &UsageLimitsResponse,
+) -> bool {
+    let mut changed = false;
+    let fields = [
+        (
+            "订阅等级",
+            &mut credentials.subscription_title,
+            usage.subscription_title(),
+        ),
+        ("账户邮箱", &mut credentials.email, usage.email()),
+        ("账户 ID", &mut credentials.account_id, usage.user_id()),
+    ];
+    for (name, field, observed) in fields {
+        if let Some(value) = observed.filter(|v| field.as_deref() != Some(*v)) {
+            tracing::info!("凭据 #{} {}已更新: {:?} -> {}", id, name, field, value);
+            *field = Some(value.to_string());
+            changed = true;
+        }
+    }
+    changed
+}
+
+// ============================================================================
+// 订阅变更检测
+// ============================================================================
+
+/// 订阅变更类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionChangeKind {
+    /// 订阅等级提升（如 FREE -> PRO）
+    Upgrade,
+    /// 订阅等级降低（如 PRO -> FREE）
+    Downgrade,
+    /// 免费试用到期
+    TrialExpired,
+    /// 订阅等级未变，但使用限额发生变化
+    LimitChanged,
+}
+
+/// 订阅变更事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionChangeEvent {
+    /// 凭据 ID
+    pub credential_id: CredentialId,
+    /// 变更类型
+    pub kind: SubscriptionChangeKind,
+    /// 变更前订阅等级
+    pub old_title: Option<String>,
+    /// 变更后订阅等级
+    pub new_title: Option<String>,
+    /// 变更前使用限额（首次观测时为空）
+    pub old_usage_limit: Option<f64>,
+    /// 变更后使用限额
+    pub new_usage_limit: f64,
+    /// 检测时间（RFC3339 格式）
+    pub detected_at: DateTime<Utc>,
+}
+
+/// 保留的订阅变更历史条数
+const SUBSCRIPTION_CHANGE_HISTORY_LIMIT: usize = 100;
+/// 订阅变更广播通道容量
+const SUBSCRIPTION_CHANGE_CHANNEL_CAPACITY: usize = 64;
+/// 通知事件广播通道容量
+const NOTIFICATION_CHANNEL_CAPACITY: usize = 64;
+
+/// 订阅等级排序（数值越大等级越高）
+///
+/// 未识别的等级按 PRO 处理，与 `supports_opus` 的宽松策略保持一致
+fn subscription_tier_rank(title: Option<&str>) -> u8 {
+    let Some(title) = title else {
+        return 1;
+    };
+    let upper = title.to_uppercase();
+    if upper.contains("FREE") {
+        0
+    } else if upper.contains("POWER") {
+        3
+    } else if upper.contains("PRO+") || upper.contains("PRO PLUS") {
+        2
+    } else {
+        1
+    }
+}
+
+/// 比较前后两次观测，判断是否发生订阅变更
+///
+/// - `previous`: 上一次额度查询的观测值（进程启动后首次查询时为 None）
+/// - `stored_title`: 凭据中已持久化的订阅等级（用于首次查询时的比较）
+fn detect_subscription_change(
+    id: CredentialId,
+    previous: Option<&UsageObservation>,
+    stored_title: Option<&str>,
+    current: &UsageObservation,
+) -> Option<SubscriptionChangeEvent> {
+    let old_title = previous
+        .map(|p| p.subscription_title.as_deref())
+        .unwrap_or(stored_title);
+    let new_title = current.subscription_title.as_deref();
+
+    let trial_expired = previous.is_some_and(|p| p.free_trial_active && !current.free_trial_active);
+    let limit_changed =
+        previous.is_some_and(|p| (p.usage_limit - current.usage_limit).abs() > f64::EPSILON);
+
+    let kind = if trial_expired {
+        SubscriptionChangeKind::TrialExpired
+    } else if old_title.is_some() && new_title.is_some() && old_title != new_title {
+        match subscription_tier_rank(new_title).cmp(&subscription_tier_rank(old_title)) {
+            std::cmp::Ordering::Less => SubscriptionChangeKind::Downgrade,
+            _ => SubscriptionChangeKind::Upgrade,
+        }
+    } else if limit_changed {
+        SubscriptionChangeKind::LimitChanged
+    } else {
+        return None;
+    };
+
+    Some(SubscriptionChangeEvent {
+        credential_id: id,
+        kind,
+        old_title: old_title.map(|s| s.to_string()),
+        new_title: new_title.map(|s| s.to_string()),
+        old_usage_limit: previous.map(|p| p.usage_limit),
+        new_usage_limit: current.usage_limit,
+        detected_at: Utc::now(),
+    })
+}
+
+// ============================================================================
+// 健康检查
+// ============================================================================
+
+/// 凭据健康状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    /// 尚未执行健康检查
+    #[default]
+    Unknown,
+    /// 最近一次健康检查通过
+    Healthy,
+    /// 健康检查失败，已暂停使用，等待连续成功后恢复
+    Degraded,
 }
 
 // ============================================================================
 // Admin API 公开结构
 // ============================================================================
 
+/// 最近一次应用的优先级预设
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedPriorityPreset {
+    pub name: String,
+    pub applied_at: DateTime<Utc>,
+    /// 优先级发生变化的凭据数
+    pub changed: usize,
+}
+
+/// 重复凭据分组
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// 保留的凭据 ID
+    pub keep: CredentialId,
+    /// 合并后删除的凭据 ID
+    pub remove: Vec<CredentialId>,
+    /// 判定为重复的依据（refreshToken / email）
+    pub reasons: Vec<String>,
+}
+
+/// 按预设重新设置凭据优先级，返回优先级发生变化的凭据数
+fn apply_preset_to_entries(entries: &mut [CredentialEntry], preset: &PriorityPreset) -> usize {
+    let mut changed = 0;
+    for entry in entries.iter_mut() {
+        if let Some(priority) = preset.priority_for(&entry.credentials.tags)
+            && entry.credentials.priority != priority
+        {
+            entry.credentials.priority = priority;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// 检测重复凭据：refreshToken 相同或账号邮箱相同（不区分大小写）的凭据归为一组
+///
+/// 每组保留一个凭据：启用优先，其次优先级最高、成功次数最多、最近使用、ID 最小
+fn duplicate_groups(entries: &[CredentialEntry]) -> Vec<DuplicateGroup> {
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    let mut parent: Vec<usize> = (0..entries.len()).collect();
+    let mut reasons: Vec<BTreeSet<&'static str>> = vec![BTreeSet::new(); entries.len()];
+    let mut first_seen: HashMap<(&'static str, String), usize> = HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let keys = [
+            entry
+                .credentials
+                .refresh_token
+                .as_deref()
+                .filter(|t| !t.is_empty())
+                .map(|t| ("refreshToken", t.to_string())),
+            entry
+                .credentials
+                .email
+                .as_deref()
+                .map(str::trim)
+                .filter(|m| !m.is_empty())
+                .map(|m| ("email", m.to_lowercase())),
+        ];
+        for key in keys.into_iter().flatten() {
+            let reason = key.0;
+            match first_seen.get(&key) {
+                Some(&j) => {
+                    let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                    parent[a] = b;
+                    reasons[i].insert(reason);
+                    reasons[j].insert(reason);
+                }
+                None => {
+                    first_seen.insert(key, i);
+                }
+            }
+        }
+    }
+
+    let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..entries.len() {
+        members.entry(root(&mut parent, i)).or_default().push(i);
+    }
+
+    members
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|group| {
+            let keep = group
+                .iter()
+                .map(|&i| &entries[i])
+                .min_by_key(|e| {
+                    (
+                        e.disabled,
+                        e.credentials.priority,
+                        std::cmp::Reverse(e.success_count),
+                        std::cmp::Reverse(e.last_used_at),
+                        e.id,
+                    )
+                })
+                .map(|e| e.id)
+                .unwrap_or_default();
+            DuplicateGroup {
+                keep,
+                remove: group
+                    .iter()
+                    .map(|&i| entries[i].id)
+                    .filter(|id| *id != keep)
+                    .collect(),
+                reasons: group
+                    .iter()
+                    .flat_map(|&i| reasons[i].iter())
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .map(|r| r.to_string())
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// 凭据单日用量
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyUsage {
+    /// 成功请求数
+    pub requests: u64,
+    /// 输入 tokens 累计
+    pub input_tokens: u64,
+    /// 输出 tokens 累计
+    pub output_tokens: u64,
+    /// 上游错误次数（每次失败的尝试计一次）
+    pub errors: u64,
+    /// 被上游内容策略拦截的次数
+    #[serde(default)]
+    pub content_filtered: u64,
+}
+
+/// 上游错误消息保留的最大字符数
+const UPSTREAM_ERROR_MESSAGE_LIMIT: usize = 500;
+
+/// 凭据最近一次上游错误
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamErrorRecord {
+    /// 错误类型（如 auth_error / rate_limited / server_error / token_refresh_error）
+    pub error_type: String,
+    /// HTTP 状态码（网络错误、Token 刷新失败等场景为空）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// 错误信息（超长时截断）
+    pub message: String,
+    /// 发生时间（RFC3339 格式）
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// 没有可用凭据（全部禁用、均无法获取 Token 或速率限制等待超时）
+///
+/// 调用方通过 `anyhow::Error::downcast_ref` 识别，按配置执行降级策略
+#[derive(Debug)]
+pub struct NoAvailableCredentials(String);
+
+impl NoAvailableCredentials {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl std::fmt::Display for NoAvailableCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NoAvailableCredentials {}
+
+/// 剩余额度不足以承担请求的预计开销（配额感知准入拒绝）
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaInsufficient {
+    /// 预计所需额度（含安全余量）
+    pub required: f64,
+    /// 可用凭据中最多的剩余额度
+    pub remaining: f64,
+}
+
+impl std::fmt::Display for QuotaInsufficient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "剩余额度不足：请求预计需要 {:.2}，可用凭据最多剩余 {:.2}",
+            self.required, self.remaining
+        )
+    }
+}
+
+impl std::error::Error for QuotaInsufficient {}
+
 /// 凭据条目快照（用于 Admin API 读取）
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CredentialEntrySnapshot {
     /// 凭据唯一 ID
-    pub id: u64,
+    pub id: CredentialId,
     /// 优先级
     pub priority: u32,
     /// 是否被禁用
@@ -445,15 +1103,21 @@ pub struct CredentialEntrySnapshot {
     /// 是否有 Profile ARN
     pub has_profile_arn: bool,
     /// Token 过期时间
-    pub expires_at: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 计划的主动刷新时间（未启用主动刷新或凭据已禁用时为空）
+    pub next_refresh_at: Option<DateTime<Utc>>,
     /// refreshToken 的 SHA-256 哈希（用于前端重复检测）
     pub refresh_token_hash: Option<String>,
     /// 用户邮箱（用于前端显示）
     pub email: Option<String>,
+    /// 上游账户 ID
+    pub account_id: Option<String>,
+    /// 订阅等级
+    pub subscription_title: Option<String>,
     /// API 调用成功次数
     pub success_count: u64,
     /// 最后一次 API 调用时间（RFC3339 格式）
-    pub last_used_at: Option<String>,
+    pub last_used_at: Option<DateTime<Utc>>,
     /// 是否配置了凭据级代理
     pub has_proxy: bool,
     /// 代理 URL（用于前端展示）
@@ -462,6 +1126,42 @@ pub struct CredentialEntrySnapshot {
     /// 凭据级 Machine ID（用于标识 Cloud Pass 来源）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub machine_id: Option<String>,
+    /// 生效的速率限制（每分钟请求数，None 表示不限制）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_rpm: Option<u32>,
+    /// 生效的最大并发请求数（None 表示不限制）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u32>,
+    /// 正在进行的上游请求数（仅在限制并发时统计）
+    pub in_flight: usize,
+    /// 生效的滚动窗口用量上限（None 表示不限制）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_cap: Option<UsageCap>,
+    /// 滚动窗口用量
+    pub window_usage: WindowUsage,
+    /// 生效的用量警戒线（None 表示不检测）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_watermark: Option<UsageWatermark>,
+    /// 最近一次额度查询时用量是否已达到警戒线
+    pub watermark_reached: bool,
+    /// 凭据标签
+    pub tags: Vec<String>,
+    /// 备注
+    pub note: Option<String>,
+    /// 模型白名单
+    pub allowed_models: Vec<String>,
+    /// 模型黑名单
+    pub blocked_models: Vec<String>,
+    /// 上游返回的下次额度重置时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_reset_at: Option<DateTime<Utc>>,
+    /// 最近一次上游错误
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<UpstreamErrorRecord>,
+    /// 健康检查状态
+    pub health_status: HealthStatus,
+    /// 最后一次健康检查时间（RFC3339 格式）
+    pub last_health_check_at: Option<DateTime<Utc>>,
 }
 
 /// 凭据管理器状态快照
@@ -471,7 +1171,7 @@ pub struct ManagerSnapshot {
     /// 凭据条目列表
     pub entries: Vec<CredentialEntrySnapshot>,
     /// 当前活跃凭据 ID
-    pub current_id: u64,
+    pub current_id: CredentialId,
     /// 总凭据数量
     pub total: usize,
     /// 可用凭据数量
@@ -483,16 +1183,20 @@ pub struct ManagerSnapshot {
 /// 支持多个凭据的管理，实现固定优先级 + 故障转移策略
 /// 故障统计基于 API 调用结果，而非 Token 刷新结果
 pub struct MultiTokenManager {
-    config: Config,
-    proxy: Option<ProxyConfig>,
+    /// 应用配置（支持运行时热重载）
+    config: RwLock<Arc<Config>>,
+    /// 全局代理配置（随配置热重载更新）
+    proxy: Mutex<Option<ProxyConfig>>,
     /// 凭据条目列表
     entries: Mutex<Vec<CredentialEntry>>,
     /// 当前活动凭据 ID
-    current_id: Mutex<u64>,
+    current_id: Mutex<CredentialId>,
     /// Token 刷新锁，确保同一时间只有一个刷新操作
     refresh_lock: TokioMutex<()>,
     /// 凭据文件路径（用于回写）
     credentials_path: Option<PathBuf>,
+    /// 凭据回写锁（导出快照到写入完成期间持有，避免较旧的快照覆盖新轮换的 refreshToken）
+    persist_lock: Mutex<()>,
     /// 是否为多凭据格式（数组格式才回写）
     is_multiple_format: bool,
     /// 负载均衡模式（运行时可修改）
@@ -501,12 +1205,63 @@ pub struct MultiTokenManager {
     last_stats_save_at: Mutex<Option<Instant>>,
     /// 统计数据是否有未落盘更新
     stats_dirty: AtomicBool,
+    /// 最近的订阅变更记录（最新的在末尾）
+    subscription_changes: Mutex<VecDeque<SubscriptionChangeEvent>>,
+    /// 订阅变更事件广播
+    subscription_events: broadcast::Sender<SubscriptionChangeEvent>,
+    /// 通知事件广播（Webhook 等通知渠道订阅）
+    notification_events: broadcast::Sender<NotificationEvent>,
+    /// 最近一次发出 `all_credentials_rate_limited` 通知的时间（用于限频）
+    last_rate_limit_notice: Mutex<Option<Instant>>,
+    /// 粘性会话绑定（客户端标识 -> 凭据 ID）
+    sticky_bindings: Mutex<HashMap<String, CredentialId>>,
+    /// 上游请求延迟统计
+    latency: LatencyStats,
+    /// 上游请求体大小统计
+    request_sizes: RequestSizeStats,
+    /// 凭据并发限制与排队
+    concurrency: ConcurrencyLimiter,
+    /// 最近一次应用的优先级预设
+    active_priority_preset: Mutex<Option<AppliedPriorityPreset>>,
 }
 
 /// 每个凭据最大 API 调用失败次数
 const MAX_FAILURES_PER_CREDENTIAL: u32 = 3;
+/// 计算自动恢复的冷却时间：初始冷却按探测失败次数指数翻倍，不超过上限
+fn recovery_cooldown(base_secs: u64, max_secs: u64, attempts: u32) -> StdDuration {
+    let factor = 1u64.checked_shl(attempts).unwrap_or(u64::MAX);
+    StdDuration::from_secs(base_secs.saturating_mul(factor).min(max_secs.max(base_secs)))
+}
+
+/// 所有凭据均触发速率限制时的最长等待时间，超过则直接返回错误
+const MAX_RATE_LIMIT_WAIT: StdDuration = StdDuration::from_secs(30);
+/// `all_credentials_rate_limited` 通知的最小间隔（限流期间每个请求都会失败，避免刷屏）
+const RATE_LIMIT_NOTICE_INTERVAL: StdDuration = StdDuration::from_secs(600);
 /// 统计数据持久化防抖间隔
 const STATS_SAVE_DEBOUNCE: StdDuration = StdDuration::from_secs(30);
+/// 额度重置后探测仍未恢复时的再次探测间隔
+const QUOTA_RESET_RECHECK_SECS: i64 = 600;
+/// 用量历史保留天数
+const USAGE_HISTORY_DAYS: i64 = 90;
+/// 主动刷新失败后的重试间隔
+const PROACTIVE_REFRESH_RETRY: StdDuration = StdDuration::from_secs(300);
+/// 主动刷新调度的最长休眠时间（新增凭据与配置变更最迟在此时间后生效）
+const PROACTIVE_REFRESH_MAX_SLEEP: StdDuration = StdDuration::from_secs(60);
+
+/// 计划的主动刷新时间：过期前 `margin_secs` 秒，再按凭据 ID 额外提前最多 1/4 的提前量，
+/// 错开同时到期的凭据（未配置提前量或没有过期时间时返回 None）
+fn proactive_refresh_at(
+    id: CredentialId,
+    credentials: &KiroCredentials,
+    margin_secs: u64,
+) -> Option<DateTime<Utc>> {
+    if margin_secs == 0 {
+        return None;
+    }
+    let spread = margin_secs / 4 + 1;
+    let offset = margin_secs + id.0.wrapping_mul(61) % spread;
+    Some(credentials.expires_at? - Duration::seconds(offset as i64))
+}
 
 /// API 调用上下文
 ///
@@ -515,7 +1270,7 @@ const STATS_SAVE_DEBOUNCE: StdDuration = StdDuration::from_secs(30);
 #[derive(Clone)]
 pub struct CallContext {
     /// 凭据 ID（用于 report_success/report_failure）
-    pub id: u64,
+    pub id: CredentialId,
     /// 凭据信息（用于构建请求头）
     pub credentials: KiroCredentials,
     /// 访问 Token
@@ -539,8 +1294,12 @@ impl MultiTokenManager {
         is_multiple_format: bool,
     ) -> anyhow::Result<Self> {
         // 计算当前最大 ID，为没有 ID 的凭据分配新 ID
-        let max_existing_id = credentials.iter().filter_map(|c| c.id).max().unwrap_or(0);
-        let mut next_id = max_existing_id + 1;
+        let max_existing_id = credentials
+            .iter()
+            .filter_map(|c| c.id)
+            .max()
+            .unwrap_or_default();
+        let mut next_id = max_existing_id.next();
         let mut has_new_ids = false;
         let mut has_new_machine_ids = false;
         let config_ref = &config;
@@ -551,7 +1310,7 @@ impl MultiTokenManager {
                 cred.canonicalize_auth_method();
                 let id = cred.id.unwrap_or_else(|| {
                     let id = next_id;
-                    next_id += 1;
+                    next_id = next_id.next();
                     cred.id = Some(id);
                     has_new_ids = true;
                     id
@@ -576,6 +1335,22 @@ impl MultiTokenManager {
                     },
                     success_count: 0,
                     last_used_at: None,
+                    last_usage: None,
+                    rate_limiter: TokenBucket::for_credential(&cred, config_ref),
+                    disabled_at: None,
+                    recovery_attempts: 0,
+                    last_error: None,
+                    health: HealthStatus::Unknown,
+                    last_health_check_at: None,
+                    health_success_streak: 0,
+                    daily_usage: BTreeMap::new(),
+                    quota_reset_at: None,
+                    rolling_usage: RollingUsage::default(),
+                    quota_remaining: None,
+                    low_balance_notified: false,
+                    watermark_reached: false,
+                    latest_usage: None,
+                    local_activity: LocalActivity::default(),
                 }
             })
             .collect();
@@ -597,20 +1372,30 @@ impl MultiTokenManager {
             .iter()
             .min_by_key(|e| e.credentials.priority)
             .map(|e| e.id)
-            .unwrap_or(0);
+            .unwrap_or_default();
 
         let load_balancing_mode = config.load_balancing_mode.clone();
         let manager = Self {
-            config,
-            proxy,
+            config: RwLock::new(Arc::new(config)),
+            proxy: Mutex::new(proxy),
             entries: Mutex::new(entries),
             current_id: Mutex::new(initial_id),
             refresh_lock: TokioMutex::new(()),
             credentials_path,
+            persist_lock: Mutex::new(()),
             is_multiple_format,
             load_balancing_mode: Mutex::new(load_balancing_mode),
             last_stats_save_at: Mutex::new(None),
             stats_dirty: AtomicBool::new(false),
+            subscription_changes: Mutex::new(VecDeque::new()),
+            subscription_events: broadcast::channel(SUBSCRIPTION_CHANGE_CHANNEL_CAPACITY).0,
+            notification_events: broadcast::channel(NOTIFICATION_CHANNEL_CAPACITY).0,
+            last_rate_limit_notice: Mutex::new(None),
+            sticky_bindings: Mutex::new(HashMap::new()),
+            latency: LatencyStats::default(),
+            request_sizes: RequestSizeStats::default(),
+            concurrency: ConcurrencyLimiter::default(),
+            active_priority_preset: Mutex::new(None),
         };
 
         // 如果有新分配的 ID 或新生成的 machineId，立即持久化到配置文件
@@ -625,12 +1410,71 @@ impl MultiTokenManager {
         // 加载持久化的统计数据（success_count, last_used_at）
         manager.load_stats();
 
+        // Cloud Pass 轮换、重复导入等会积累重复凭据，启动时提示合并
+        let groups = duplicate_groups(&manager.entries.lock());
+        if !groups.is_empty() {
+            tracing::warn!(
+                "检测到 {} 组重复凭据（refreshToken 或账号邮箱相同），可通过 `kiro-rs dedupe` \
+                 或 POST /api/admin/credentials/dedupe 合并",
+                groups.len()
+            );
+            for group in &groups {
+                tracing::warn!(
+                    "  保留 #{}，重复 {:?}（{}）",
+                    group.keep,
+                    group.remove,
+                    group.reasons.join(", ")
+                );
+            }
+        }
+
         Ok(manager)
     }
 
-    /// 获取配置的引用
-    pub fn config(&self) -> &Config {
-        &self.config
+    /// 获取当前配置
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().clone()
+    }
+
+    /// 获取当前全局代理配置
+    pub fn proxy(&self) -> Option<ProxyConfig> {
+        self.proxy.lock().clone()
+    }
+
+    /// 热重载配置（Admin API）
+    ///
+    /// 替换运行时配置并应用以下变更：
+    /// - 全局代理（后续请求按新代理创建 Client）
+    /// - 负载均衡模式
+    /// - 凭据默认速率限制（重建令牌桶）
+    ///
+    /// 其余按需读取配置的功能（重试上限、粘性会话等）在下一次使用时自动生效
+    pub fn reload_config(&self, config: Config) -> anyhow::Result<()> {
+        let mode = config.load_balancing_mode.clone();
+        if mode != "priority" && mode != "balanced" {
+            anyhow::bail!("无效的负载均衡模式: {}", mode);
+        }
+        if let Some(tz) = config.timezone.as_deref()
+            && parse_utc_offset(tz).is_none()
+        {
+            anyhow::bail!("无效的时区: {}（应为 UTC 偏移，如 +08:00）", tz);
+        }
+
+        let rpm_changed = self.config().credential_rpm != config.credential_rpm;
+        *self.proxy.lock() = config.proxy_config();
+        *self.config.write() = Arc::new(config);
+        *self.load_balancing_mode.lock() = mode;
+
+        if rpm_changed {
+            let config = self.config();
+            let mut entries = self.entries.lock();
+            for entry in entries.iter_mut() {
+                entry.rate_limiter = TokenBucket::for_credential(&entry.credentials, &config);
+            }
+        }
+
+        tracing::info!("配置已热重载");
+        Ok(())
     }
 
     /// 获取当前活动凭据的克隆
@@ -654,6 +1498,13 @@ impl MultiTokenManager {
         self.entries.lock().iter().filter(|e| !e.disabled).count()
     }
 
+    /// 是否存在可处理指定模型的凭据（未禁用、属于凭据池且支持该模型）
+    pub fn has_credential_for_model(&self, model: &str, pool: Option<&str>) -> bool {
+        self.entries.lock().iter().any(|e| {
+            !e.disabled && e.credentials.in_pool(pool) && e.credentials.supports_model(Some(model))
+        })
+    }
+
     /// 根据负载均衡模式选择下一个凭据
     ///
     /// - priority 模式：选择优先级最高（priority 最小）的可用凭据
@@ -661,26 +1512,33 @@ impl MultiTokenManager {
     ///
     /// # 参数
     /// - `model`: 可选的模型名称，用于过滤支持该模型的凭据（如 opus 模型需要付费订阅）
-    fn select_next_credential(&self, model: Option<&str>) -> Option<(u64, KiroCredentials)> {
+    /// - `pool`: 可选的凭据池（标签），仅选择带有该标签的凭据
+    fn select_next_credential(
+        &self,
+        model: Option<&str>,
+        pool: Option<&str>,
+    ) -> Option<(CredentialId, KiroCredentials)> {
         let entries = self.entries.lock();
 
-        // 检查是否是 opus 模型
-        let is_opus = model
-            .map(|m| m.to_lowercase().contains("opus"))
-            .unwrap_or(false);
-
         // 过滤可用凭据
+        let now = Instant::now();
+        let config = self.config();
+        let minute = current_minute();
         let available: Vec<_> = entries
             .iter()
             .filter(|e| {
                 if e.disabled {
                     return false;
                 }
-                // 如果是 opus 模型，需要检查订阅等级
-                if is_opus && !e.credentials.supports_opus() {
+                // 需要支持请求模型（Opus 订阅等级、模型白名单/黑名单）
+                if !e.credentials.supports_model(model) {
                     return false;
                 }
-                true
+                if !e.credentials.in_pool(pool) {
+                    return false;
+                }
+                // 已触发速率限制或达到滚动窗口用量上限的凭据暂不参与选择
+                e.within_rate_limit(now) && e.within_usage_cap(&config, minute)
             })
             .collect();
 
@@ -688,6 +1546,16 @@ impl MultiTokenManager {
             return None;
         }
 
+        // 达到用量警戒线的凭据只在没有其他可用凭据时选择
+        let available = if available.iter().all(|e| e.watermark_deprioritized(&config)) {
+            available
+        } else {
+            available
+                .into_iter()
+                .filter(|e| !e.watermark_deprioritized(&config))
+                .collect()
+        };
+
         let mode = self.load_balancing_mode.lock().clone();
         let mode = mode.as_str();
 
@@ -719,59 +1587,72 @@ impl MultiTokenManager {
     ///
     /// # 参数
     /// - `model`: 可选的模型名称，用于过滤支持该模型的凭据（如 opus 模型需要付费订阅）
-    pub async fn acquire_context(&self, model: Option<&str>) -> anyhow::Result<CallContext> {
+    /// - `pool`: 可选的凭据池（标签），仅使用带有该标签的凭据
+    pub async fn acquire_context(
+        &self,
+        model: Option<&str>,
+        pool: Option<&str>,
+    ) -> anyhow::Result<CallContext> {
         let total = self.total_count();
         let mut tried_count = 0;
 
         loop {
             if tried_count >= total {
-                anyhow::bail!(
+                return Err(NoAvailableCredentials::new(format!(
                     "所有凭据均无法获取有效 Token（可用: {}/{}）",
                     self.available_count(),
                     total
-                );
+                ))
+                .into());
             }
 
-            let (id, credentials) = {
+            let selected = {
                 let is_balanced = self.load_balancing_mode.lock().as_str() == "balanced";
 
                 // balanced 模式：每次请求都轮询选择，不固定 current_id
                 // priority 模式：优先使用 current_id 指向的凭据
+                // 当前凭据不支持请求模型时（如降级为 FREE 后请求 Opus、模型被列入黑名单）视为未命中
                 let current_hit = if is_balanced {
                     None
                 } else {
+                    let config = self.config();
                     let entries = self.entries.lock();
                     let current_id = *self.current_id.lock();
                     entries
                         .iter()
-                        .find(|e| e.id == current_id && !e.disabled)
+                        .find(|e| {
+                            e.id == current_id
+                                && !e.disabled
+                                && e.credentials.supports_model(model)
+                                && e.credentials.in_pool(pool)
+                                && e.within_rate_limit(Instant::now())
+                                && e.within_usage_cap(&config, current_minute())
+                                && !e.watermark_deprioritized(&config)
+                        })
                         .map(|e| (e.id, e.credentials.clone()))
                 };
 
                 if let Some(hit) = current_hit {
-                    hit
+                    Some(hit)
                 } else {
                     // 当前凭据不可用或 balanced 模式，根据负载均衡策略选择
-                    let mut best = self.select_next_credential(model);
+                    let mut best = self.select_next_credential(model, pool);
 
                     // 没有可用凭据：如果是"自动禁用导致全灭"，做一次类似重启的自愈
-                    if best.is_none() {
+                    // 仅因速率限制暂不可用时无需自愈
+                    if best.is_none() && self.rate_limit_wait(model, pool).is_none() {
                         let mut entries = self.entries.lock();
-                        if entries.iter().any(|e| {
-                            e.disabled && e.disabled_reason == Some(DisabledReason::TooManyFailures)
-                        }) {
+                        if entries.iter().any(|e| e.disabled && e.is_auto_disabled()) {
                             tracing::warn!(
                                 "所有凭据均已被自动禁用，执行自愈：重置失败计数并重新启用（等价于重启）"
                             );
                             for e in entries.iter_mut() {
-                                if e.disabled_reason == Some(DisabledReason::TooManyFailures) {
-                                    e.disabled = false;
-                                    e.disabled_reason = None;
-                                    e.failure_count = 0;
+                                if e.is_auto_disabled() {
+                                    e.enable();
                                 }
                             }
                             drop(entries);
-                            best = self.select_next_credential(model);
+                            best = self.select_next_credential(model, pool);
                         }
                     }
 
@@ -779,18 +1660,62 @@ impl MultiTokenManager {
                         // 更新 current_id
                         let mut current_id = self.current_id.lock();
                         *current_id = new_id;
-                        (new_id, new_creds)
+                        Some((new_id, new_creds))
+                    } else if self.rate_limit_wait(model, pool).is_some() {
+                        // 仍有可用凭据，只是均已触发速率限制，等待令牌补充
+                        None
                     } else {
+                        let config = self.config();
+                        let minute = current_minute();
                         let entries = self.entries.lock();
+                        let in_pool: Vec<_> = entries
+                            .iter()
+                            .filter(|e| !e.disabled && e.credentials.in_pool(pool))
+                            .collect();
+                        if let Some(model) = model
+                            && !in_pool.is_empty()
+                            && !in_pool.iter().any(|e| e.credentials.supports_model(Some(model)))
+                        {
+                            return Err(NoAvailableCredentials::new(format!(
+                                "没有可用凭据支持模型 {}（检查凭据的订阅等级与模型白名单/黑名单）",
+                                model
+                            ))
+                            .into());
+                        }
+                        if in_pool.iter().any(|e| !e.within_usage_cap(&config, minute)) {
+                            return Err(NoAvailableCredentials::new(
+                                "所有可用凭据均已达到滚动窗口用量上限，等待窗口滑动后恢复",
+                            )
+                            .into());
+                        }
                         // 注意：必须在 bail! 之前计算 available_count，
                         // 因为 available_count() 会尝试获取 entries 锁，
                         // 而此时我们已经持有该锁，会导致死锁
                         let available = entries.iter().filter(|e| !e.disabled).count();
-                        anyhow::bail!("所有凭据均已禁用（{}/{}）", available, total);
+                        return Err(NoAvailableCredentials::new(format!(
+                            "所有凭据均已禁用（{}/{}）",
+                            available, total
+                        ))
+                        .into());
                     }
                 }
             };
 
+            let Some((id, credentials)) = selected else {
+                let wait = self.rate_limit_wait(model, pool).unwrap_or_default();
+                if wait > MAX_RATE_LIMIT_WAIT {
+                    let message = format!(
+                        "所有可用凭据均已触发速率限制（需等待 {:.1} 秒）",
+                        wait.as_secs_f64()
+                    );
+                    self.notify_rate_limited(&message);
+                    return Err(NoAvailableCredentials::new(message).into());
+                }
+                tracing::debug!("所有可用凭据均已触发速率限制，等待 {:?}", wait);
+                tokio::time::sleep(wait).await;
+                continue;
+            };
+
             // 尝试获取/刷新 Token
             match self.try_ensure_token(id, &credentials).await {
                 Ok(ctx) => {
@@ -807,12 +1732,170 @@ impl MultiTokenManager {
         }
     }
 
-    /// 切换到下一个优先级最高的可用凭据（内部方法）
-    fn switch_to_next_by_priority(&self) {
-        let entries = self.entries.lock();
-        let mut current_id = self.current_id.lock();
+    /// 获取粘性会话的 API 调用上下文
+    ///
+    /// 同一客户端标识固定使用同一凭据，保证对话上下文和限流状态一致：
+    /// - 已绑定的凭据可用（未禁用、支持请求模型、Token 有效）时直接使用
+    /// - 否则重新选择绑定数最少的可用凭据并更新绑定（故障转移）
+    pub async fn acquire_context_for_session(
+        &self,
+        model: Option<&str>,
+        pool: Option<&str>,
+        session_key: &str,
+    ) -> anyhow::Result<CallContext> {
+        let bound = {
+            let bindings = self.sticky_bindings.lock();
+            bindings.get(session_key).copied()
+        };
 
-        // 选择优先级最高的未禁用凭据（排除当前凭据）
+        if let Some(bound_id) = bound {
+            let bound_entry = {
+                let config = self.config();
+                let entries = self.entries.lock();
+                let now = Instant::now();
+                entries
+                    .iter()
+                    .find(|e| {
+                        e.id == bound_id
+                            && !e.disabled
+                            && e.credentials.supports_model(model)
+                            && e.credentials.in_pool(pool)
+                            && e.within_usage_cap(&config, current_minute())
+                    })
+                    .map(|e| {
+                        let wait = e.rate_limiter.as_ref().map(|b| b.wait_time(now));
+                        (e.credentials.clone(), wait.unwrap_or_default())
+                    })
+            };
+
+            // 绑定凭据仅触发速率限制时等待令牌补充，等待过久才重新绑定
+            let bound_creds = match bound_entry {
+                Some((creds, wait)) if wait <= MAX_RATE_LIMIT_WAIT => {
+                    if !wait.is_zero() {
+                        tracing::debug!(
+                            "粘性会话绑定的凭据 #{} 触发速率限制，等待 {:?}",
+                            bound_id,
+                            wait
+                        );
+                        tokio::time::sleep(wait).await;
+                    }
+                    Some(creds)
+                }
+                _ => None,
+            };
+
+            match bound_creds {
+                Some(creds) => match self.try_ensure_token(bound_id, &creds).await {
+                    Ok(ctx) => return Ok(ctx),
+                    Err(e) => {
+                        tracing::warn!(
+                            "粘性会话绑定的凭据 #{} Token 刷新失败，重新绑定: {}",
+                            bound_id,
+                            e
+                        );
+                    }
+                },
+                None => {
+                    tracing::info!("粘性会话绑定的凭据 #{} 不可用，重新绑定", bound_id);
+                }
+            }
+            self.sticky_bindings.lock().remove(session_key);
+        }
+
+        // 优先选择绑定数最少的凭据，失败时回退到常规选择（含自愈逻辑）
+        let ctx = match self.select_sticky_credential(model, pool, bound) {
+            Some((id, creds)) => match self.try_ensure_token(id, &creds).await {
+                Ok(ctx) => ctx,
+                Err(e) => {
+                    tracing::warn!("凭据 #{} Token 刷新失败，回退到常规选择: {}", id, e);
+                    self.acquire_context(model, pool).await?
+                }
+            },
+            None => self.acquire_context(model, pool).await?,
+        };
+
+        self.sticky_bindings
+            .lock()
+            .insert(session_key.to_string(), ctx.id);
+        tracing::debug!("粘性会话已绑定到凭据 #{}", ctx.id);
+
+        Ok(ctx)
+    }
+
+    /// 为新的粘性会话选择凭据（内部方法）
+    ///
+    /// 在可用凭据中选择绑定数最少的，平局时按优先级；
+    /// `exclude` 为刚失效的绑定凭据，存在其他候选时跳过
+    fn select_sticky_credential(
+        &self,
+        model: Option<&str>,
+        pool: Option<&str>,
+        exclude: Option<CredentialId>,
+    ) -> Option<(CredentialId, KiroCredentials)> {
+        let mut counts: HashMap<CredentialId, usize> = HashMap::new();
+        for id in self.sticky_bindings.lock().values() {
+            *counts.entry(*id).or_insert(0) += 1;
+        }
+
+        let config = self.config();
+        let minute = current_minute();
+        let entries = self.entries.lock();
+        let now = Instant::now();
+        let candidates: Vec<_> = entries
+            .iter()
+            .filter(|e| !e.disabled && e.credentials.supports_model(model))
+            .filter(|e| e.credentials.in_pool(pool) && e.within_rate_limit(now))
+            .filter(|e| e.within_usage_cap(&config, minute))
+            .collect();
+
+        candidates
+            .iter()
+            .filter(|e| candidates.len() == 1 || Some(e.id) != exclude)
+            .min_by_key(|e| {
+                (
+                    counts.get(&e.id).copied().unwrap_or(0),
+                    e.credentials.priority,
+                )
+            })
+            .map(|e| (e.id, e.credentials.clone()))
+    }
+
+    /// 计算最早可用凭据的速率限制等待时间（内部方法）
+    ///
+    /// 仅统计未禁用、支持请求模型、属于凭据池且未达到用量上限的凭据；
+    /// 没有受限流约束的候选凭据时返回 None
+    fn rate_limit_wait(&self, model: Option<&str>, pool: Option<&str>) -> Option<StdDuration> {
+        let now = Instant::now();
+        let config = self.config();
+        let minute = current_minute();
+
+        let entries = self.entries.lock();
+        entries
+            .iter()
+            .filter(|e| !e.disabled && e.credentials.supports_model(model))
+            .filter(|e| e.credentials.in_pool(pool) && e.within_usage_cap(&config, minute))
+            .filter_map(|e| e.rate_limiter.as_ref().map(|b| b.wait_time(now)))
+            .min()
+    }
+
+    /// 消耗指定凭据的一个速率限制令牌，并计入滚动窗口请求数（内部方法）
+    fn consume_rate_limit(&self, id: CredentialId) {
+        let mut entries = self.entries.lock();
+        if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+            if let Some(bucket) = entry.rate_limiter.as_mut() {
+                bucket.consume(Instant::now());
+            }
+            entry.rolling_usage.add(current_minute(), 1, 0);
+            entry.local_activity.requests += 1;
+        }
+    }
+
+    /// 切换到下一个优先级最高的可用凭据（内部方法）
+    fn switch_to_next_by_priority(&self) {
+        let entries = self.entries.lock();
+        let mut current_id = self.current_id.lock();
+
+        // 选择优先级最高的未禁用凭据（排除当前凭据）
         if let Some(entry) = entries
             .iter()
             .filter(|e| !e.disabled && e.id != *current_id)
@@ -862,7 +1945,7 @@ impl MultiTokenManager {
     /// * `credentials` - 凭据信息
     async fn try_ensure_token(
         &self,
-        id: u64,
+        id: CredentialId,
         credentials: &KiroCredentials,
     ) -> anyhow::Result<CallContext> {
         // 第一次检查（无锁）：快速判断是否需要刷新
@@ -884,9 +1967,14 @@ impl MultiTokenManager {
 
             if is_token_expired(&current_creds) || is_token_expiring_soon(&current_creds) {
                 // 确实需要刷新
-                let effective_proxy = current_creds.effective_proxy(self.proxy.as_ref());
+                let effective_proxy = current_creds.effective_proxy(self.proxy().as_ref());
                 let new_creds =
-                    refresh_token(&current_creds, &self.config, effective_proxy.as_ref()).await?;
+                    refresh_token(&current_creds, &self.config(), effective_proxy.as_ref())
+                        .await
+                        .inspect_err(|e| {
+                            let message = e.to_string();
+                            self.record_upstream_error(id, "token_refresh_error", None, &message)
+                        })?;
 
                 if is_token_expired(&new_creds) {
                     anyhow::bail!("刷新后的 Token 仍然无效或已过期");
@@ -920,6 +2008,8 @@ impl MultiTokenManager {
             .clone()
             .ok_or_else(|| anyhow::anyhow!("没有可用的 accessToken"))?;
 
+        self.consume_rate_limit(id);
+
         Ok(CallContext {
             id,
             credentials: creds,
@@ -950,36 +2040,33 @@ impl MultiTokenManager {
             None => return Ok(false),
         };
 
-        // 收集所有凭据
-        let credentials: Vec<KiroCredentials> = {
-            let entries = self.entries.lock();
-            entries
-                .iter()
-                .map(|e| {
-                    let mut cred = e.credentials.clone();
-                    cred.canonicalize_auth_method();
-                    // 同步 disabled 状态到凭据对象
-                    cred.disabled = e.disabled;
-                    cred
-                })
-                .collect()
-        };
+        // 收集所有凭据（持有回写锁直到写入完成，后写入的总是较新的快照）
+        let _persist = self.persist_lock.lock();
+        let credentials = self.export_credentials();
 
         // 序列化为 pretty JSON
         let json = serde_json::to_string_pretty(&credentials).context("序列化凭据失败")?;
 
-        // 写入文件（在 Tokio runtime 内使用 block_in_place 避免阻塞 worker）
+        // 原子写入并保留上一版本为 .bak：上游刷新时可能轮换 refreshToken，
+        // 写入中途崩溃也不能丢失唯一有效的 refreshToken
+        // （在 Tokio runtime 内使用 block_in_place 避免阻塞 worker）
         if tokio::runtime::Handle::try_current().is_ok() {
-            tokio::task::block_in_place(|| std::fs::write(path, &json))
+            tokio::task::block_in_place(|| write_atomic(path, json.as_bytes()))
                 .with_context(|| format!("回写凭据文件失败: {:?}", path))?;
         } else {
-            std::fs::write(path, &json).with_context(|| format!("回写凭据文件失败: {:?}", path))?;
+            write_atomic(path, json.as_bytes())
+                .with_context(|| format!("回写凭据文件失败: {:?}", path))?;
         }
 
         tracing::debug!("已回写凭据到文件: {:?}", path);
         Ok(true)
     }
 
+    /// 凭证文件是否为多凭据格式（只有多凭据格式会回写新增的凭据）
+    pub fn is_multiple_format(&self) -> bool {
+        self.is_multiple_format
+    }
+
     /// 获取缓存目录（凭据文件所在目录）
     pub fn cache_dir(&self) -> Option<PathBuf> {
         self.credentials_path
@@ -1016,7 +2103,8 @@ impl MultiTokenManager {
         for entry in entries.iter_mut() {
             if let Some(s) = stats.get(&entry.id.to_string()) {
                 entry.success_count = s.success_count;
-                entry.last_used_at = s.last_used_at.clone();
+                entry.last_used_at = s.last_used_at;
+                entry.daily_usage = s.daily_usage.clone();
             }
         }
         *self.last_stats_save_at.lock() = Some(Instant::now());
@@ -1040,7 +2128,8 @@ impl MultiTokenManager {
                         e.id.to_string(),
                         StatsEntry {
                             success_count: e.success_count,
-                            last_used_at: e.last_used_at.clone(),
+                            last_used_at: e.last_used_at,
+                            daily_usage: e.daily_usage.clone(),
                         },
                     )
                 })
@@ -1060,6 +2149,13 @@ impl MultiTokenManager {
         }
     }
 
+    /// 立即写入尚未落盘的统计数据（服务关闭时调用）
+    pub fn flush_stats(&self) {
+        if self.stats_dirty.load(Ordering::Relaxed) {
+            self.save_stats();
+        }
+    }
+
     /// 标记统计数据已更新，并按 debounce 策略决定是否立即落盘
     fn save_stats_debounced(&self) {
         self.stats_dirty.store(true, Ordering::Relaxed);
@@ -1083,13 +2179,16 @@ impl MultiTokenManager {
     ///
     /// # Arguments
     /// * `id` - 凭据 ID（来自 CallContext）
-    pub fn report_success(&self, id: u64) {
+    pub fn report_success(&self, id: CredentialId) {
+        let offset = self.config().timezone_offset();
         {
             let mut entries = self.entries.lock();
             if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
                 entry.failure_count = 0;
                 entry.success_count += 1;
-                entry.last_used_at = Some(Utc::now().to_rfc3339());
+                entry.local_activity.successes += 1;
+                entry.last_used_at = Some(Utc::now());
+                entry.today_usage(offset).requests += 1;
                 tracing::debug!(
                     "凭据 #{} API 调用成功（累计 {} 次）",
                     id,
@@ -1100,6 +2199,152 @@ impl MultiTokenManager {
         self.save_stats_debounced();
     }
 
+    /// 记录指定凭据最近一次上游错误（用于 Admin API 展示失败原因）
+    ///
+    /// 仅记录，不影响失败计数和禁用状态
+    pub fn record_upstream_error(
+        &self,
+        id: CredentialId,
+        error_type: &str,
+        status: Option<u16>,
+        message: &str,
+    ) {
+        let message = match message.char_indices().nth(UPSTREAM_ERROR_MESSAGE_LIMIT) {
+            Some((idx, _)) => format!("{}...", &message[..idx]),
+            None => message.to_string(),
+        };
+        let offset = self.config().timezone_offset();
+        {
+            let mut entries = self.entries.lock();
+            if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+                entry.last_error = Some(UpstreamErrorRecord {
+                    error_type: error_type.to_string(),
+                    status,
+                    message,
+                    occurred_at: Utc::now(),
+                });
+                entry.today_usage(offset).errors += 1;
+            }
+        }
+        self.save_stats_debounced();
+    }
+
+    /// 记录指定凭据一次成功请求的上游延迟（首字节时间与总耗时）
+    pub fn record_latency(&self, id: CredentialId, ttfb: StdDuration, total: StdDuration) {
+        self.latency.record(id, ttfb, total);
+    }
+
+    /// 各凭据的上游延迟汇总
+    pub fn latency_per_credential(&self) -> Vec<CredentialLatency> {
+        self.latency.per_credential()
+    }
+
+    /// 所有凭据合并后的上游延迟汇总 (TTFB, 总耗时)
+    pub fn latency_overall(&self) -> (LatencySummary, LatencySummary) {
+        self.latency.overall()
+    }
+
+    /// 记录一次上游请求的请求体大小（字节）
+    pub fn record_request_size(&self, bytes: usize) {
+        self.request_sizes.record(bytes);
+    }
+
+    /// 上游请求体大小汇总
+    pub fn request_size_summary(&self) -> RequestSizeSummary {
+        self.request_sizes.summary()
+    }
+
+    /// 获取凭据的并发许可（未限制并发时返回 None），并发已满时排队等待
+    pub async fn acquire_concurrency(
+        &self,
+        ctx: &CallContext,
+    ) -> Result<Option<ConcurrencyPermit>, ConcurrencyLimited> {
+        let config = self.config();
+        let Some(limit) = Self::max_concurrency(&ctx.credentials, &config) else {
+            return Ok(None);
+        };
+        self.concurrency
+            .acquire(
+                ctx.id,
+                limit,
+                config.concurrency_queue_size,
+                StdDuration::from_secs(config.concurrency_queue_timeout_secs),
+            )
+            .await
+            .map(Some)
+    }
+
+    /// 生效的并发上限（凭据级设置覆盖全局 credentialMaxConcurrency，0 表示不限制）
+    fn max_concurrency(credentials: &KiroCredentials, config: &Config) -> Option<u32> {
+        credentials
+            .max_concurrency
+            .or(config.credential_max_concurrency)
+            .filter(|limit| *limit > 0)
+    }
+
+    /// 记录指定凭据一次请求的 token 用量（计入当天的用量桶）
+    pub fn record_token_usage(&self, id: CredentialId, input_tokens: i32, output_tokens: i32) {
+        let config = self.config();
+        let offset = config.timezone_offset();
+        {
+            let mut entries = self.entries.lock();
+            if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+                let usage = entry.today_usage(offset);
+                usage.input_tokens += input_tokens.max(0) as u64;
+                usage.output_tokens += output_tokens.max(0) as u64;
+                let tokens = input_tokens.max(0) as u64 + output_tokens.max(0) as u64;
+                entry.rolling_usage.add(current_minute(), 0, tokens);
+                entry.local_activity.tokens += tokens;
+                // 两次额度查询之间按折算扣减估算的剩余额度
+                if let (Some(remaining), Some(cost)) = (
+                    entry.quota_remaining.as_mut(),
+                    config.quota_admission.as_ref().and_then(|a| a.cost(tokens)),
+                ) {
+                    *remaining -= cost;
+                }
+            }
+        }
+        self.save_stats_debounced();
+    }
+
+    /// 记录指定凭据一次被上游内容策略拦截的响应
+    ///
+    /// 频繁被拦截的账号可能面临策略处罚，运营者可据此提前调整
+    pub fn record_content_filter(&self, id: CredentialId) {
+        let offset = self.config().timezone_offset();
+        {
+            let mut entries = self.entries.lock();
+            if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+                entry.today_usage(offset).content_filtered += 1;
+            }
+        }
+        self.save_stats_debounced();
+    }
+
+    /// 获取指定凭据的按日用量（运营者时区日期，闭区间，未指定时不限制）
+    ///
+    /// 仅返回有记录的日期，按日期升序排列
+    pub fn usage_history(
+        &self,
+        id: CredentialId,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> anyhow::Result<Vec<(NaiveDate, DailyUsage)>> {
+        let entries = self.entries.lock();
+        let entry = entries
+            .iter()
+            .find(|e| e.id == id)
+            .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+        Ok(entry
+            .daily_usage
+            .iter()
+            .filter(|(date, _)| {
+                from.is_none_or(|f| **date >= f) && to.is_none_or(|t| **date <= t)
+            })
+            .map(|(date, usage)| (*date, usage.clone()))
+            .collect())
+    }
+
     /// 报告指定凭据 API 调用失败
     ///
     /// 增加失败计数，达到阈值时禁用凭据并切换到优先级最高的可用凭据
@@ -1107,7 +2352,8 @@ impl MultiTokenManager {
     ///
     /// # Arguments
     /// * `id` - 凭据 ID（来自 CallContext）
-    pub fn report_failure(&self, id: u64) -> bool {
+    pub fn report_failure(&self, id: CredentialId) -> bool {
+        let mut disabled = None;
         let result = {
             let mut entries = self.entries.lock();
             let mut current_id = self.current_id.lock();
@@ -1118,7 +2364,7 @@ impl MultiTokenManager {
             };
 
             entry.failure_count += 1;
-            entry.last_used_at = Some(Utc::now().to_rfc3339());
+            entry.last_used_at = Some(Utc::now());
             let failure_count = entry.failure_count;
 
             tracing::warn!(
@@ -1131,6 +2377,8 @@ impl MultiTokenManager {
             if failure_count >= MAX_FAILURES_PER_CREDENTIAL {
                 entry.disabled = true;
                 entry.disabled_reason = Some(DisabledReason::TooManyFailures);
+                entry.disabled_at = Some(Instant::now());
+                disabled = Some(entry.credentials.email.clone());
                 tracing::error!("凭据 #{} 已连续失败 {} 次，已被禁用", id, failure_count);
 
                 // 切换到优先级最高的可用凭据
@@ -1152,6 +2400,13 @@ impl MultiTokenManager {
 
             entries.iter().any(|e| !e.disabled)
         };
+        if let Some(email) = disabled {
+            self.notify_disabled(
+                id,
+                email,
+                &format!("连续失败 {} 次", MAX_FAILURES_PER_CREDENTIAL),
+            );
+        }
         self.save_stats_debounced();
         result
     }
@@ -1162,7 +2417,8 @@ impl MultiTokenManager {
     /// - 立即禁用该凭据（不等待连续失败阈值）
     /// - 切换到下一个可用凭据继续重试
     /// - 返回是否还有可用凭据
-    pub fn report_quota_exhausted(&self, id: u64) -> bool {
+    pub fn report_quota_exhausted(&self, id: CredentialId) -> bool {
+        let email;
         let result = {
             let mut entries = self.entries.lock();
             let mut current_id = self.current_id.lock();
@@ -1178,9 +2434,10 @@ impl MultiTokenManager {
 
             entry.disabled = true;
             entry.disabled_reason = Some(DisabledReason::QuotaExceeded);
-            entry.last_used_at = Some(Utc::now().to_rfc3339());
+            entry.last_used_at = Some(Utc::now());
             // 设为阈值，便于在管理面板中直观看到该凭据已不可用
             entry.failure_count = MAX_FAILURES_PER_CREDENTIAL;
+            email = entry.credentials.email.clone();
 
             tracing::error!("凭据 #{} 额度已用尽（MONTHLY_REQUEST_COUNT），已被禁用", id);
 
@@ -1202,6 +2459,7 @@ impl MultiTokenManager {
                 false
             }
         };
+        self.notify_disabled(id, email, "额度已用尽");
         self.save_stats_debounced();
         result
     }
@@ -1234,11 +2492,11 @@ impl MultiTokenManager {
 
     /// 获取使用额度信息
     pub async fn get_usage_limits(&self) -> anyhow::Result<UsageLimitsResponse> {
-        let ctx = self.acquire_context(None).await?;
-        let effective_proxy = ctx.credentials.effective_proxy(self.proxy.as_ref());
+        let ctx = self.acquire_context(None, None).await?;
+        let effective_proxy = ctx.credentials.effective_proxy(self.proxy().as_ref());
         get_usage_limits(
             &ctx.credentials,
-            &self.config,
+            &self.config(),
             &ctx.token,
             effective_proxy.as_ref(),
         )
@@ -1251,9 +2509,12 @@ impl MultiTokenManager {
 
     /// 获取管理器状态快照（用于 Admin API）
     pub fn snapshot(&self) -> ManagerSnapshot {
+        let config = self.config();
+        let minute = current_minute();
         let entries = self.entries.lock();
         let current_id = *self.current_id.lock();
         let available = entries.iter().filter(|e| !e.disabled).count();
+        let in_flight = self.concurrency.in_flight();
 
         ManagerSnapshot {
             entries: entries
@@ -1271,14 +2532,40 @@ impl MultiTokenManager {
                         }
                     }),
                     has_profile_arn: e.credentials.profile_arn.is_some(),
-                    expires_at: e.credentials.expires_at.clone(),
+                    expires_at: e.credentials.expires_at,
+                    next_refresh_at: if e.disabled {
+                        None
+                    } else {
+                        proactive_refresh_at(
+                            e.id,
+                            &e.credentials,
+                            config.proactive_refresh_margin_secs,
+                        )
+                    },
                     refresh_token_hash: e.credentials.refresh_token.as_deref().map(sha256_hex),
                     email: e.credentials.email.clone(),
+                    account_id: e.credentials.account_id.clone(),
+                    subscription_title: e.credentials.subscription_title.clone(),
                     success_count: e.success_count,
-                    last_used_at: e.last_used_at.clone(),
+                    last_used_at: e.last_used_at,
                     has_proxy: e.credentials.proxy_url.is_some(),
                     proxy_url: e.credentials.proxy_url.clone(),
                     machine_id: e.credentials.machine_id.clone(),
+                    rate_limit_rpm: e.rate_limiter.as_ref().map(|b| b.rpm),
+                    max_concurrency: Self::max_concurrency(&e.credentials, &config),
+                    in_flight: in_flight.get(&e.id).copied().unwrap_or(0),
+                    usage_cap: e.usage_cap(&config).cloned(),
+                    window_usage: e.rolling_usage.window(minute),
+                    usage_watermark: e.usage_watermark(&config).cloned(),
+                    watermark_reached: e.watermark_reached,
+                    tags: e.credentials.tags.clone(),
+                    note: e.credentials.note.clone(),
+                    allowed_models: e.credentials.allowed_models.clone(),
+                    blocked_models: e.credentials.blocked_models.clone(),
+                    quota_reset_at: e.quota_reset_at,
+                    last_error: e.last_error.clone(),
+                    health_status: e.health,
+                    last_health_check_at: e.last_health_check_at,
                 })
                 .collect(),
             current_id,
@@ -1288,19 +2575,18 @@ impl MultiTokenManager {
     }
 
     /// 设置凭据禁用状态（Admin API）
-    pub fn set_disabled(&self, id: u64, disabled: bool) -> anyhow::Result<()> {
+    pub fn set_disabled(&self, id: CredentialId, disabled: bool) -> anyhow::Result<()> {
         {
             let mut entries = self.entries.lock();
             let entry = entries
                 .iter_mut()
                 .find(|e| e.id == id)
                 .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
-            entry.disabled = disabled;
             if !disabled {
                 // 启用时重置失败计数
-                entry.failure_count = 0;
-                entry.disabled_reason = None;
+                entry.enable();
             } else {
+                entry.disabled = true;
                 entry.disabled_reason = Some(DisabledReason::Manual);
             }
         }
@@ -1313,7 +2599,7 @@ impl MultiTokenManager {
     ///
     /// 修改优先级后会立即按新优先级重新选择当前凭据。
     /// 即使持久化失败，内存中的优先级和当前凭据选择也会生效。
-    pub fn set_priority(&self, id: u64, priority: u32) -> anyhow::Result<()> {
+    pub fn set_priority(&self, id: CredentialId, priority: u32) -> anyhow::Result<()> {
         {
             let mut entries = self.entries.lock();
             let entry = entries
@@ -1329,25 +2615,216 @@ impl MultiTokenManager {
         Ok(())
     }
 
+    /// 设置凭据级速率限制（Admin API）
+    ///
+    /// `rpm` 为 None 时回退到全局配置 credentialRpm，为 0 时不限制该凭据。
+    /// 修改后令牌桶重新以满容量开始计数。
+    pub fn set_rate_limit(&self, id: CredentialId, rpm: Option<u32>) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.credentials.rate_limit_rpm = rpm;
+            entry.rate_limiter = TokenBucket::for_credential(&entry.credentials, &self.config());
+        }
+        // 持久化更改
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 设置凭据级用量警戒线（Admin API，None 表示回退到全局配置）
+    ///
+    /// 下次查询余额时按新的警戒线重新判断
+    pub fn set_usage_watermark(
+        &self,
+        id: CredentialId,
+        watermark: Option<UsageWatermark>,
+    ) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.credentials.usage_watermark = watermark;
+            entry.watermark_reached = false;
+        }
+        // 持久化更改
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 设置凭据标签（Admin API）
+    pub fn set_tags(&self, id: CredentialId, tags: Vec<String>) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.credentials.tags = KiroCredentials::normalize_tags(tags);
+        }
+        // 持久化更改
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 设置凭据备注（Admin API，空白备注视为清除）
+    pub fn set_note(&self, id: CredentialId, note: Option<String>) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.credentials.note = KiroCredentials::normalize_note(note);
+        }
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 设置凭据的模型白名单 / 黑名单（Admin API，整体替换）
+    ///
+    /// 选择凭据时跳过不支持请求模型的凭据，由其他凭据处理
+    pub fn set_models(
+        &self,
+        id: CredentialId,
+        allowed_models: Vec<String>,
+        blocked_models: Vec<String>,
+    ) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.credentials.allowed_models = KiroCredentials::normalize_tags(allowed_models);
+            entry.credentials.blocked_models = KiroCredentials::normalize_tags(blocked_models);
+        }
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 设置凭据级出站代理（Admin API）
+    ///
+    /// `proxy_url` 为 None 时回退到全局代理，特殊值 "direct" 表示不使用代理。
+    /// 后续请求按新代理选择 HTTP Client，无需重启。
+    pub fn set_proxy(
+        &self,
+        id: CredentialId,
+        proxy_url: Option<String>,
+        proxy_username: Option<String>,
+        proxy_password: Option<String>,
+    ) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            let has_proxy = proxy_url.is_some();
+            entry.credentials.proxy_url = proxy_url;
+            // 清除代理时一并清除认证信息，避免残留
+            entry.credentials.proxy_username = proxy_username.filter(|_| has_proxy);
+            entry.credentials.proxy_password = proxy_password.filter(|_| has_proxy);
+        }
+        // 持久化更改
+        self.persist_credentials()?;
+        Ok(())
+    }
+
     /// 重置凭据失败计数并重新启用（Admin API）
-    pub fn reset_and_enable(&self, id: u64) -> anyhow::Result<()> {
+    pub fn reset_and_enable(&self, id: CredentialId) -> anyhow::Result<()> {
         {
             let mut entries = self.entries.lock();
             let entry = entries
                 .iter_mut()
                 .find(|e| e.id == id)
                 .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
-            entry.failure_count = 0;
-            entry.disabled = false;
-            entry.disabled_reason = None;
+            entry.enable();
         }
         // 持久化更改
         self.persist_credentials()?;
         Ok(())
     }
 
-    /// 获取指定凭据的使用额度（Admin API）
-    pub async fn get_usage_limits_for(&self, id: u64) -> anyhow::Result<UsageLimitsResponse> {
+    /// 主动刷新调度（`proactiveRefreshMarginSecs` 为 0 时空转）
+    ///
+    /// 在每个启用凭据的计划时间（过期前的提前量）到达时逐个刷新 Token，
+    /// 避免请求在 Token 过期时才同步刷新造成延迟尖峰；失败的凭据等待一段时间后重试
+    pub async fn run_refresh_scheduler(&self) {
+        let mut retry_at: HashMap<CredentialId, Instant> = HashMap::new();
+        loop {
+            let (due, next) = self.refresh_schedule(Utc::now());
+            let mut sleep = next
+                .map(|t| (t - Utc::now()).to_std().unwrap_or_default())
+                .unwrap_or(PROACTIVE_REFRESH_MAX_SLEEP);
+
+            retry_at.retain(|id, _| due.contains(id));
+            for id in due {
+                let now = Instant::now();
+                if let Some(&at) = retry_at.get(&id).filter(|&&at| at > now) {
+                    sleep = sleep.min(at - now);
+                    continue;
+                }
+                match self.refresh_token_for(id, true).await {
+                    Ok(_) if self.refresh_due(id) => {
+                        // 提前量不小于 Token 有效期时刷新后仍处于到期状态，按失败间隔重试避免空转
+                        tracing::warn!(
+                            "凭据 #{} 刷新后的 Token 有效期短于 proactiveRefreshMarginSecs",
+                            id
+                        );
+                        retry_at.insert(id, Instant::now() + PROACTIVE_REFRESH_RETRY);
+                        sleep = sleep.min(PROACTIVE_REFRESH_RETRY);
+                    }
+                    Ok(_) => {
+                        retry_at.remove(&id);
+                        tracing::debug!("凭据 #{} Token 已主动刷新", id);
+                    }
+                    Err(e) => {
+                        tracing::warn!("凭据 #{} 主动刷新 Token 失败: {:#}", id, e);
+                        retry_at.insert(id, Instant::now() + PROACTIVE_REFRESH_RETRY);
+                        sleep = sleep.min(PROACTIVE_REFRESH_RETRY);
+                    }
+                }
+            }
+
+            let sleep = sleep.clamp(StdDuration::from_secs(1), PROACTIVE_REFRESH_MAX_SLEEP);
+            tokio::time::sleep(sleep).await;
+        }
+    }
+
+    /// 凭据是否已到计划刷新时间
+    fn refresh_due(&self, id: CredentialId) -> bool {
+        let margin_secs = self.config().proactive_refresh_margin_secs;
+        let entries = self.entries.lock();
+        entries
+            .iter()
+            .find(|e| e.id == id)
+            .and_then(|e| proactive_refresh_at(e.id, &e.credentials, margin_secs))
+            .is_some_and(|at| at <= Utc::now())
+    }
+
+    /// 已到计划刷新时间的启用凭据，以及其余凭据中最早的计划刷新时间
+    fn refresh_schedule(&self, now: DateTime<Utc>) -> (Vec<CredentialId>, Option<DateTime<Utc>>) {
+        let margin_secs = self.config().proactive_refresh_margin_secs;
+        let entries = self.entries.lock();
+        let mut due = Vec::new();
+        let mut next: Option<DateTime<Utc>> = None;
+        for entry in entries.iter().filter(|e| !e.disabled) {
+            match proactive_refresh_at(entry.id, &entry.credentials, margin_secs) {
+                Some(at) if at <= now => due.push(entry.id),
+                Some(at) => next = Some(next.map_or(at, |n| n.min(at))),
+                None => {}
+            }
+        }
+        (due, next)
+    }
+
+    /// 获取指定凭据的 access token，即将过期或 `force` 为 true 时先刷新并回写凭据文件
+    pub async fn refresh_token_for(&self, id: CredentialId, force: bool) -> anyhow::Result<String> {
         let credentials = {
             let entries = self.entries.lock();
             entries
@@ -1359,7 +2836,7 @@ impl MultiTokenManager {
 
         // 检查是否需要刷新 token
         let needs_refresh =
-            is_token_expired(&credentials) || is_token_expiring_soon(&credentials);
+            force || is_token_expired(&credentials) || is_token_expiring_soon(&credentials);
 
         let token = if needs_refresh {
             let _guard = self.refresh_lock.lock().await;
@@ -1372,10 +2849,11 @@ impl MultiTokenManager {
                     .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
             };
 
-            if is_token_expired(&current_creds) || is_token_expiring_soon(&current_creds) {
-                let effective_proxy = current_creds.effective_proxy(self.proxy.as_ref());
+            if force || is_token_expired(&current_creds) || is_token_expiring_soon(&current_creds)
+            {
+                let effective_proxy = current_creds.effective_proxy(self.proxy().as_ref());
                 let new_creds =
-                    refresh_token(&current_creds, &self.config, effective_proxy.as_ref()).await?;
+                    refresh_token(&current_creds, &self.config(), effective_proxy.as_ref()).await?;
                 {
                     let mut entries = self.entries.lock();
                     if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
@@ -1399,6 +2877,15 @@ impl MultiTokenManager {
                 .access_token
                 .ok_or_else(|| anyhow::anyhow!("凭据无 access_token"))?
         };
+        Ok(token)
+    }
+
+    /// 获取指定凭据的使用额度（Admin API）
+    pub async fn get_usage_limits_for(
+        &self,
+        id: CredentialId,
+    ) -> anyhow::Result<UsageLimitsResponse> {
+        let token = self.refresh_token_for(id, false).await?;
 
         let credentials = {
             let entries = self.entries.lock();
@@ -1409,43 +2896,709 @@ impl MultiTokenManager {
                 .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
         };
 
-        let effective_proxy = credentials.effective_proxy(self.proxy.as_ref());
-        let usage_limits = get_usage_limits(&credentials, &self.config, &token, effective_proxy.as_ref()).await?;
-
-        // 更新订阅等级到凭据（仅在发生变化时持久化）
-        if let Some(subscription_title) = usage_limits.subscription_title() {
-            let changed = {
-                let mut entries = self.entries.lock();
-                if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
-                    let old_title = entry.credentials.subscription_title.clone();
-                    if old_title.as_deref() != Some(subscription_title) {
-                        entry.credentials.subscription_title =
-                            Some(subscription_title.to_string());
-                        tracing::info!(
-                            "凭据 #{} 订阅等级已更新: {:?} -> {}",
-                            id,
-                            old_title,
-                            subscription_title
-                        );
-                        true
-                    } else {
-                        false
+        let effective_proxy = credentials.effective_proxy(self.proxy().as_ref());
+        let usage_limits = get_usage_limits(&credentials, &self.config(), &token, effective_proxy.as_ref()).await?;
+
+        // 与上一次观测比较，检测订阅变更并更新订阅等级（仅在等级变化时持久化）
+        let observation = UsageObservation::from_usage(&usage_limits);
+        let remaining = usage_limits.usage_limit() - usage_limits.current_usage();
+        let usage_percent = if usage_limits.usage_limit() > 0.0 {
+            usage_limits.current_usage() / usage_limits.usage_limit() * 100.0
+        } else {
+            0.0
+        };
+        let config = self.config();
+        let mut low_balance = None;
+        let mut watermark_hit = None;
+        let mut watermark_disabled = false;
+        let (change, metadata_changed) = {
+            let mut entries = self.entries.lock();
+            match entries.iter_mut().find(|e| e.id == id) {
+                Some(entry) => {
+                    let change = detect_subscription_change(
+                        id,
+                        entry.last_usage.as_ref(),
+                        entry.credentials.subscription_title.as_deref(),
+                        &observation,
+                    );
+                    let metadata_changed =
+                        enrich_account_metadata(id, &mut entry.credentials, &usage_limits);
+                    entry.last_usage = Some(observation);
+                    entry.latest_usage = Some((Utc::now(), usage_limits.clone()));
+                    entry.quota_remaining = Some(remaining);
+                    // 只在跌破阈值时通知一次，回升到阈值以上后重新计
+                    match config.low_balance_threshold {
+                        Some(threshold) if remaining < threshold => {
+                            if !entry.low_balance_notified {
+                                entry.low_balance_notified = true;
+                                low_balance = Some((threshold, entry.credentials.email.clone()));
+                            }
+                        }
+                        _ => entry.low_balance_notified = false,
                     }
-                } else {
-                    false
+                    if let Some(watermark) = entry.observe_usage_percent(&config, usage_percent) {
+                        watermark_disabled =
+                            entry.disabled_reason == Some(DisabledReason::UsageWatermark);
+                        watermark_hit = Some((watermark, entry.credentials.email.clone()));
+                    }
+                    if let Some(reset_at) = usage_limits.next_reset_at().and_then(timestamp_to_utc) {
+                        entry.quota_reset_at = Some(reset_at);
+                    }
+                    (change, metadata_changed)
                 }
-            };
+                None => (None, false),
+            }
+        };
 
-            if changed {
-                if let Err(e) = self.persist_credentials() {
-                    tracing::warn!("订阅等级更新后持久化失败（不影响本次请求）: {}", e);
-                }
+        if metadata_changed {
+            if let Err(e) = self.persist_credentials() {
+                tracing::warn!("账户信息更新后持久化失败（不影响本次请求）: {}", e);
+            }
+        }
+
+        if let Some(change) = change {
+            self.record_subscription_change(change);
+        }
+
+        if let Some((threshold, email)) = low_balance {
+            self.notify(
+                NotificationEvent::new(
+                    EventKind::BalanceLow,
+                    format!(
+                        "凭据 #{} 剩余额度 {:.1} 低于阈值 {:.1}",
+                        id, remaining, threshold
+                    ),
+                )
+                .with_credential(id, email)
+                .with_data(serde_json::json!({
+                    "remaining": remaining,
+                    "usageLimit": usage_limits.usage_limit(),
+                    "threshold": threshold,
+                })),
+            );
+        }
+
+        if let Some((watermark, email)) = watermark_hit {
+            tracing::warn!(
+                "凭据 #{} 已用额度 {:.1}% 达到警戒线 {:.1}%（{:?}）",
+                id,
+                usage_percent,
+                watermark.percent,
+                watermark.action
+            );
+            self.notify(
+                NotificationEvent::new(
+                    EventKind::UsageWatermark,
+                    format!(
+                        "凭据 #{} 已用额度 {:.1}% 达到警戒线 {:.1}%",
+                        id, usage_percent, watermark.percent
+                    ),
+                )
+                .with_credential(id, email.clone())
+                .with_data(serde_json::json!({
+                    "usagePercent": usage_percent,
+                    "percent": watermark.percent,
+                    "action": watermark.action,
+                })),
+            );
+            if watermark_disabled {
+                self.notify_disabled(id, email, "用量达到警戒线");
             }
         }
 
         Ok(usage_limits)
     }
 
+    /// 最近一次额度查询的结果及时间
+    pub fn latest_usage_limits(
+        &self,
+        id: CredentialId,
+    ) -> Option<(DateTime<Utc>, UsageLimitsResponse)> {
+        self.entries
+            .lock()
+            .iter()
+            .find(|e| e.id == id)
+            .and_then(|e| e.latest_usage.clone())
+    }
+
+    /// 采用其他副本的额度查询结果（比本地保存的更新时），返回是否采用
+    ///
+    /// 只更新余额展示与配额感知准入所用的数据，余额不足等通知由发起查询的副本发出
+    pub fn apply_remote_usage_limits(
+        &self,
+        id: CredentialId,
+        at: DateTime<Utc>,
+        usage: UsageLimitsResponse,
+    ) -> bool {
+        let mut entries = self.entries.lock();
+        let Some(entry) = entries.iter_mut().find(|e| e.id == id) else {
+            return false;
+        };
+        if entry
+            .latest_usage
+            .as_ref()
+            .is_some_and(|(local_at, _)| *local_at >= at)
+        {
+            return false;
+        }
+        entry.quota_remaining = Some(usage.usage_limit() - usage.current_usage());
+        if let Some(reset_at) = usage.next_reset_at().and_then(timestamp_to_utc) {
+            entry.quota_reset_at = Some(reset_at);
+        }
+        entry.latest_usage = Some((at, usage));
+        true
+    }
+
+    /// 各凭据本实例产生的累计用量
+    pub fn local_activity(&self) -> Vec<(CredentialId, LocalActivity)> {
+        self.entries
+            .lock()
+            .iter()
+            .map(|e| (e.id, e.local_activity))
+            .collect()
+    }
+
+    /// 计入其他副本产生的用量
+    ///
+    /// 扣减速率限制令牌，计入滚动窗口用量与成功次数，并按折算扣减估算的剩余额度
+    pub fn apply_remote_activity(&self, id: CredentialId, activity: LocalActivity) {
+        let config = self.config();
+        let mut entries = self.entries.lock();
+        let Some(entry) = entries.iter_mut().find(|e| e.id == id) else {
+            return;
+        };
+        if let Some(bucket) = entry.rate_limiter.as_mut() {
+            bucket.drain(activity.requests, Instant::now());
+        }
+        entry
+            .rolling_usage
+            .add(current_minute(), activity.requests, activity.tokens);
+        entry.success_count += activity.successes;
+        if let (Some(remaining), Some(cost)) = (
+            entry.quota_remaining.as_mut(),
+            config
+                .quota_admission
+                .as_ref()
+                .and_then(|a| a.cost(activity.tokens)),
+        ) {
+            *remaining -= cost;
+        }
+    }
+
+    /// 当前活动凭据 ID
+    pub fn current_id(&self) -> CredentialId {
+        *self.current_id.lock()
+    }
+
+    /// 切换当前活动凭据（多副本共享负载均衡游标），凭据不存在或已禁用时忽略并返回 false
+    pub fn adopt_current_id(&self, id: CredentialId) -> bool {
+        let entries = self.entries.lock();
+        if !entries.iter().any(|e| e.id == id && !e.disabled) {
+            return false;
+        }
+        *self.current_id.lock() = id;
+        true
+    }
+
+    /// 清除保存的额度查询结果
+    pub fn clear_latest_usage(&self, id: CredentialId) -> anyhow::Result<()> {
+        let mut entries = self.entries.lock();
+        let entry = entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+        entry.latest_usage = None;
+        Ok(())
+    }
+
+    /// 清除所有凭据保存的额度查询结果
+    pub fn clear_all_latest_usage(&self) {
+        for entry in self.entries.lock().iter_mut() {
+            entry.latest_usage = None;
+        }
+    }
+
+    /// 需要定时刷新余额的凭据 ID 列表
+    ///
+    /// 仅包括启用的凭据，跳过 `max_age` 内已查询过额度的凭据（如刚在 Admin 中查看过）
+    pub fn balance_refresh_targets(&self, max_age: Duration) -> Vec<CredentialId> {
+        let cutoff = Utc::now() - max_age;
+        self.entries
+            .lock()
+            .iter()
+            .filter(|e| !e.disabled)
+            .filter(|e| e.latest_usage.as_ref().is_none_or(|(at, _)| *at <= cutoff))
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// 获取需要探测恢复的凭据 ID 列表
+    ///
+    /// - 因连续失败被自动禁用且冷却期已过的凭据（冷却时间按探测失败次数指数退避）
+    /// - 因额度用尽或达到用量警戒线被禁用且已到达上游额度重置时间的凭据
+    pub fn credentials_due_for_recovery(&self) -> Vec<CredentialId> {
+        let config = self.config();
+        let base = config.failure_cooldown_secs;
+        let max = config.failure_cooldown_max_secs;
+        let now = Utc::now();
+
+        let entries = self.entries.lock();
+        entries
+            .iter()
+            .filter(|e| e.disabled)
+            .filter(|e| match e.disabled_reason {
+                Some(DisabledReason::TooManyFailures) => {
+                    base > 0
+                        && e.disabled_at.is_some_and(|at| {
+                            at.elapsed() >= recovery_cooldown(base, max, e.recovery_attempts)
+                        })
+                }
+                Some(DisabledReason::QuotaExceeded | DisabledReason::UsageWatermark) => {
+                    e.quota_reset_at.is_some_and(|reset_at| reset_at <= now)
+                }
+                _ => false,
+            })
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// 探测到期的禁用凭据，成功则重置失败计数并重新加入轮换
+    ///
+    /// 额度用尽的凭据需要探测到剩余额度才视为恢复
+    pub async fn recover_disabled_credentials(&self) {
+        for id in self.credentials_due_for_recovery() {
+            let success = match self.get_usage_limits_for(id).await {
+                Ok(usage) => {
+                    !self.is_quota_disabled(id) || usage.current_usage() < usage.usage_limit()
+                }
+                Err(e) => {
+                    tracing::warn!("凭据 #{} 恢复探测失败: {}", id, e);
+                    false
+                }
+            };
+            self.record_recovery_probe(id, success);
+        }
+    }
+
+    /// 凭据是否因额度用尽被禁用（内部方法）
+    fn is_quota_disabled(&self, id: CredentialId) -> bool {
+        self.entries
+            .lock()
+            .iter()
+            .any(|e| e.id == id && e.disabled_reason == Some(DisabledReason::QuotaExceeded))
+    }
+
+    /// 记录恢复探测结果（内部方法）
+    fn record_recovery_probe(&self, id: CredentialId, success: bool) {
+        let config = self.config();
+        let mut entries = self.entries.lock();
+        let Some(entry) = entries.iter_mut().find(|e| e.id == id) else {
+            return;
+        };
+
+        // 探测期间可能已被手动启用或因其他原因禁用
+        match entry.disabled_reason {
+            Some(DisabledReason::TooManyFailures) if success => {
+                entry.enable();
+                tracing::info!("凭据 #{} 冷却后探测成功，已重新启用", id);
+            }
+            Some(DisabledReason::TooManyFailures) => {
+                entry.recovery_attempts = entry.recovery_attempts.saturating_add(1);
+                entry.disabled_at = Some(Instant::now());
+                let next = recovery_cooldown(
+                    config.failure_cooldown_secs,
+                    config.failure_cooldown_max_secs,
+                    entry.recovery_attempts,
+                );
+                tracing::warn!("凭据 #{} 仍不可用，{:?} 后再次探测", id, next);
+            }
+            Some(DisabledReason::QuotaExceeded) if success => {
+                entry.enable();
+                tracing::info!("凭据 #{} 额度已重置，已重新启用", id);
+            }
+            // 用量警戒线在探测（额度查询）时已回落则已重新启用，走到这里说明仍未回落
+            Some(DisabledReason::QuotaExceeded | DisabledReason::UsageWatermark) => {
+                // 上游尚未给出新的重置时间时，稍后再探测
+                let now = Utc::now();
+                let next = entry
+                    .quota_reset_at
+                    .filter(|at| *at > now)
+                    .unwrap_or(now + Duration::seconds(QUOTA_RESET_RECHECK_SECS));
+                entry.quota_reset_at = Some(next);
+                let local = next.with_timezone(&config.timezone_offset());
+                tracing::warn!(
+                    "凭据 #{} 额度仍未恢复，将于 {} 再次探测",
+                    id,
+                    local.format("%Y-%m-%d %H:%M:%S %:z")
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// 获取闲置凭据 ID 列表（用于保活探测）
+    ///
+    /// 仅包括启用的凭据；从未使用或最后使用时间早于 `idle` 的视为闲置，
+    /// 按最后使用时间从早到晚排序，最多返回 `limit` 个
+    pub fn idle_credentials(&self, idle: Duration, limit: usize) -> Vec<CredentialId> {
+        let cutoff = Utc::now() - idle;
+        let entries = self.entries.lock();
+        let mut idle_entries: Vec<_> = entries
+            .iter()
+            .filter(|e| !e.disabled)
+            .filter_map(|e| {
+                let last_used = e.last_used_at;
+                match last_used {
+                    Some(t) if t > cutoff => None,
+                    _ => Some((last_used, e.id)),
+                }
+            })
+            .collect();
+        idle_entries.sort();
+        idle_entries.into_iter().take(limit).map(|(_, id)| id).collect()
+    }
+
+    /// 获取指定凭据的 API 调用上下文（不参与负载均衡选择）
+    ///
+    /// 用于保活探测等需要针对单个凭据发起请求的场景，凭据被禁用时返回错误
+    pub async fn acquire_context_for_id(&self, id: CredentialId) -> anyhow::Result<CallContext> {
+        let credentials = {
+            let entries = self.entries.lock();
+            let entry = entries
+                .iter()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            if entry.disabled {
+                anyhow::bail!("凭据 #{} 已禁用", id);
+            }
+            entry.credentials.clone()
+        };
+        self.try_ensure_token(id, &credentials).await
+    }
+
+    /// 选择故障转移的下一个凭据：排除本次请求已失败的凭据，其余条件与负载均衡选择相同，
+    /// 按优先级取最高者；没有时返回 None（调用方继续使用原凭据重试）
+    pub fn failover_candidate(
+        &self,
+        model: Option<&str>,
+        pool: Option<&str>,
+        exclude: &[CredentialId],
+    ) -> Option<CredentialId> {
+        let config = self.config();
+        let now = Instant::now();
+        let minute = current_minute();
+        let entries = self.entries.lock();
+        entries
+            .iter()
+            .filter(|e| {
+                !exclude.contains(&e.id)
+                    && !e.disabled
+                    && e.credentials.supports_model(model)
+                    && e.credentials.in_pool(pool)
+                    && e.within_rate_limit(now)
+                    && e.within_usage_cap(&config, minute)
+            })
+            .min_by_key(|e| e.credentials.priority)
+            .map(|e| e.id)
+    }
+
+    /// 配额感知准入：检查凭据的估算剩余额度能否承担预计 tokens（含安全余量）
+    ///
+    /// 额度不足时改选同样可用（支持该模型、属于同一凭据池）且剩余额度最多的凭据，
+    /// 已知额度都不足时才考虑尚未查询过额度的凭据，仍没有则返回 [`QuotaInsufficient`]。
+    /// 未配置 quotaAdmission、预计 tokens 低于 minTokens 或凭据尚未查询过额度时直接放行。
+    /// 返回 `Some(id)` 表示应改用该凭据
+    pub fn admit_by_quota(
+        &self,
+        id: CredentialId,
+        model: Option<&str>,
+        pool: Option<&str>,
+        tokens: u64,
+    ) -> Result<Option<CredentialId>, QuotaInsufficient> {
+        let config = self.config();
+        let Some(required) = config
+            .quota_admission
+            .as_ref()
+            .and_then(|a| a.required(tokens))
+        else {
+            return Ok(None);
+        };
+
+        let entries = self.entries.lock();
+        let current = entries
+            .iter()
+            .find(|e| e.id == id)
+            .and_then(|e| e.quota_remaining);
+        let remaining = match current {
+            Some(remaining) if remaining < required => remaining,
+            _ => return Ok(None),
+        };
+
+        let candidates: Vec<&CredentialEntry> = entries
+            .iter()
+            .filter(|e| {
+                e.id != id
+                    && !e.disabled
+                    && e.credentials.supports_model(model)
+                    && e.credentials.in_pool(pool)
+            })
+            .collect();
+        let best = candidates
+            .iter()
+            .filter_map(|e| e.quota_remaining.map(|r| (e.id, r)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        let alternative = match best {
+            Some((alt, r)) if r >= required => Some(alt),
+            _ => candidates
+                .iter()
+                .find(|e| e.quota_remaining.is_none())
+                .map(|e| e.id),
+        };
+
+        match alternative {
+            Some(alt) => {
+                tracing::info!(
+                    "凭据 #{} 剩余额度 {:.2} 不足以承担预计 {} tokens（需 {:.2}），改用凭据 #{}",
+                    id,
+                    remaining,
+                    tokens,
+                    required,
+                    alt
+                );
+                Ok(Some(alt))
+            }
+            None => Err(QuotaInsufficient {
+                required,
+                remaining: best.map_or(remaining, |(_, r)| r.max(remaining)),
+            }),
+        }
+    }
+
+    /// 获取需要健康检查的凭据 ID 列表
+    ///
+    /// 包括所有启用的凭据，以及因健康检查失败而降级的凭据（用于判断能否恢复）
+    pub fn health_check_targets(&self) -> Vec<CredentialId> {
+        let entries = self.entries.lock();
+        entries
+            .iter()
+            .filter(|e| !e.disabled || e.disabled_reason == Some(DisabledReason::Unhealthy))
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// 对指定凭据执行一次健康检查
+    ///
+    /// 通过查询使用额度验证凭据可用，并根据结果更新健康状态
+    pub async fn check_credential_health(&self, id: CredentialId) -> anyhow::Result<HealthStatus> {
+        let result = self.get_usage_limits_for(id).await;
+        if let Err(e) = &result {
+            tracing::warn!("凭据 #{} 健康检查失败: {}", id, e);
+        }
+        self.record_health_check(id, result.is_ok())
+    }
+
+    /// 记录健康检查结果
+    ///
+    /// - 失败：标记为降级并禁用（已被其他原因禁用的凭据只更新状态）
+    /// - 成功：降级凭据连续成功达到阈值后恢复启用
+    pub fn record_health_check(
+        &self,
+        id: CredentialId,
+        healthy: bool,
+    ) -> anyhow::Result<HealthStatus> {
+        let threshold = self.config().health_check_recovery_threshold.max(1);
+        let mut entries = self.entries.lock();
+        let entry = entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+        entry.last_health_check_at = Some(Utc::now());
+        let mut disabled = None;
+
+        if healthy {
+            entry.health_success_streak = entry.health_success_streak.saturating_add(1);
+            if entry.health != HealthStatus::Degraded {
+                entry.health = HealthStatus::Healthy;
+            } else if entry.health_success_streak >= threshold {
+                entry.health = HealthStatus::Healthy;
+                if entry.disabled_reason == Some(DisabledReason::Unhealthy) {
+                    entry.enable();
+                }
+                tracing::info!("凭据 #{} 健康检查连续成功 {} 次，已恢复", id, threshold);
+            }
+        } else {
+            entry.health_success_streak = 0;
+            entry.health = HealthStatus::Degraded;
+            if !entry.disabled {
+                entry.disabled = true;
+                entry.disabled_reason = Some(DisabledReason::Unhealthy);
+                disabled = Some(entry.credentials.email.clone());
+                tracing::error!("凭据 #{} 健康检查失败，已标记为降级并暂停使用", id);
+            }
+        }
+        let health = entry.health;
+        drop(entries);
+
+        if let Some(email) = disabled {
+            self.notify_disabled(id, email, "健康检查失败");
+        }
+        Ok(health)
+    }
+
+    /// 记录订阅变更并广播事件
+    ///
+    /// 按订阅等级路由（如 Opus 过滤）直接读取凭据的 `subscription_title`，
+    /// 等级更新后下一次选择凭据即自动生效，无需额外处理
+    fn record_subscription_change(&self, change: SubscriptionChangeEvent) {
+        tracing::warn!(
+            "凭据 #{} 订阅变更（{:?}）: {:?} -> {:?}，限额 {:?} -> {}",
+            change.credential_id,
+            change.kind,
+            change.old_title,
+            change.new_title,
+            change.old_usage_limit,
+            change.new_usage_limit
+        );
+
+        {
+            let mut history = self.subscription_changes.lock();
+            if history.len() >= SUBSCRIPTION_CHANGE_HISTORY_LIMIT {
+                history.pop_front();
+            }
+            history.push_back(change.clone());
+        }
+
+        // 没有订阅者时 send 返回错误，忽略即可
+        let _ = self.subscription_events.send(change);
+    }
+
+    /// 获取最近的订阅变更记录（最新的在前，Admin API）
+    pub fn subscription_changes(&self) -> Vec<SubscriptionChangeEvent> {
+        self.subscription_changes.lock().iter().rev().cloned().collect()
+    }
+
+    /// 订阅订阅变更事件（用于通知等下游消费者）
+    pub fn subscribe_subscription_changes(&self) -> broadcast::Receiver<SubscriptionChangeEvent> {
+        self.subscription_events.subscribe()
+    }
+
+    /// 广播通知事件（没有订阅者时直接丢弃）
+    pub fn notify(&self, event: NotificationEvent) {
+        let _ = self.notification_events.send(event);
+    }
+
+    /// 订阅通知事件
+    pub fn subscribe_notifications(&self) -> broadcast::Receiver<NotificationEvent> {
+        self.notification_events.subscribe()
+    }
+
+    /// 请求因凭据均被限流而失败时发出通知（每 10 分钟最多一次）
+    pub fn notify_rate_limited(&self, reason: &str) {
+        {
+            let mut last = self.last_rate_limit_notice.lock();
+            if last.is_some_and(|t| t.elapsed() < RATE_LIMIT_NOTICE_INTERVAL) {
+                return;
+            }
+            *last = Some(Instant::now());
+        }
+        self.notify(NotificationEvent::new(
+            EventKind::AllCredentialsRateLimited,
+            format!("请求因凭据被限流而失败：{}", reason),
+        ));
+    }
+
+    /// 凭据被自动禁用后发出通知，没有可用凭据时追加 `all_credentials_exhausted`
+    fn notify_disabled(&self, id: CredentialId, email: Option<String>, reason: &str) {
+        self.notify(
+            NotificationEvent::new(
+                EventKind::CredentialDisabled,
+                format!("凭据 #{} 已被禁用：{}", id, reason),
+            )
+            .with_credential(id, email),
+        );
+        let (total, available) = {
+            let entries = self.entries.lock();
+            (entries.len(), entries.iter().filter(|e| !e.disabled).count())
+        };
+        if available == 0 {
+            self.notify(NotificationEvent::new(
+                EventKind::AllCredentialsExhausted,
+                format!("所有 {} 个凭据均已禁用，请求将无法处理", total),
+            ));
+        }
+    }
+
+    /// 添加前的本地预检（不请求上游）
+    ///
+    /// 校验 refreshToken 格式，并基于 refreshToken 的 SHA-256 哈希检测重复；
+    /// 批量导入的 dry-run 模式也使用此检查
+    pub fn precheck_credential(&self, cred: &KiroCredentials) -> anyhow::Result<()> {
+        validate_refresh_token(cred)?;
+
+        let refresh_token = cred
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("缺少 refreshToken"))?;
+        if self.contains_refresh_token(refresh_token) {
+            anyhow::bail!("凭据已存在（refreshToken 重复）");
+        }
+        Ok(())
+    }
+
+    /// 导出所有凭据（与凭证文件内容一致，含 ID 与禁用状态）
+    pub fn export_credentials(&self) -> Vec<KiroCredentials> {
+        let entries = self.entries.lock();
+        entries
+            .iter()
+            .map(|e| {
+                let mut cred = e.credentials.clone();
+                cred.canonicalize_auth_method();
+                // 同步 disabled 状态到凭据对象
+                cred.disabled = e.disabled;
+                cred
+            })
+            .collect()
+    }
+
+    /// 是否已有使用该 refreshToken 的凭据（按 SHA-256 哈希比较）
+    pub fn contains_refresh_token(&self, refresh_token: &str) -> bool {
+        let refresh_token_hash = sha256_hex(refresh_token);
+        let entries = self.entries.lock();
+        entries.iter().any(|entry| {
+            entry
+                .credentials
+                .refresh_token
+                .as_deref()
+                .map(sha256_hex)
+                .as_deref()
+                == Some(refresh_token_hash.as_str())
+        })
+    }
+
+    /// 用源实例的令牌更新已有凭据（多实例凭据同步）
+    ///
+    /// 源实例轮换 refreshToken 后沿用本地已有的条目（保留 ID、统计与本地设置），
+    /// 只替换令牌并记录源凭据 ID，避免作为新凭据重复加入
+    pub fn apply_synced_tokens(
+        &self,
+        id: CredentialId,
+        source: &KiroCredentials,
+    ) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.credentials.refresh_token = source.refresh_token.clone();
+            entry.credentials.access_token = source.access_token.clone();
+            entry.credentials.expires_at = source.expires_at;
+            entry.credentials.peer_source_id = source.id;
+        }
+        self.persist_credentials()?;
+        Ok(())
+    }
+
     /// 添加新凭据（Admin API）
     ///
     /// # 流程
@@ -1457,43 +3610,21 @@ impl MultiTokenManager {
     /// 6. 持久化到配置文件
     ///
     /// # 返回
-    /// - `Ok(u64)` - 新凭据 ID
+    /// - `Ok(CredentialId)` - 新凭据 ID
     /// - `Err(_)` - 验证失败或添加失败
-    pub async fn add_credential(&self, new_cred: KiroCredentials) -> anyhow::Result<u64> {
-        // 1. 基本验证
-        validate_refresh_token(&new_cred)?;
-
-        // 2. 基于 refreshToken 的 SHA-256 哈希检测重复
-        let new_refresh_token = new_cred
-            .refresh_token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("缺少 refreshToken"))?;
-        let new_refresh_token_hash = sha256_hex(new_refresh_token);
-        let duplicate_exists = {
-            let entries = self.entries.lock();
-            entries.iter().any(|entry| {
-                entry
-                    .credentials
-                    .refresh_token
-                    .as_deref()
-                    .map(sha256_hex)
-                    .as_deref()
-                    == Some(new_refresh_token_hash.as_str())
-            })
-        };
-        if duplicate_exists {
-            anyhow::bail!("凭据已存在（refreshToken 重复）");
-        }
+    pub async fn add_credential(&self, new_cred: KiroCredentials) -> anyhow::Result<CredentialId> {
+        // 1-2. 基本验证与重复检测
+        self.precheck_credential(&new_cred)?;
 
         // 3. 尝试刷新 Token 验证凭据有效性
-        let effective_proxy = new_cred.effective_proxy(self.proxy.as_ref());
+        let effective_proxy = new_cred.effective_proxy(self.proxy().as_ref());
         let mut validated_cred =
-            refresh_token(&new_cred, &self.config, effective_proxy.as_ref()).await?;
+            refresh_token(&new_cred, &self.config(), effective_proxy.as_ref()).await?;
 
         // 4. 分配新 ID
         let new_id = {
             let entries = self.entries.lock();
-            entries.iter().map(|e| e.id).max().unwrap_or(0) + 1
+            entries.iter().map(|e| e.id).max().unwrap_or_default().next()
         };
 
         // 5. 设置 ID 并保留用户输入的元数据
@@ -1516,6 +3647,9 @@ impl MultiTokenManager {
         validated_cred.proxy_url = new_cred.proxy_url;
         validated_cred.proxy_username = new_cred.proxy_username;
         validated_cred.proxy_password = new_cred.proxy_password;
+        validated_cred.rate_limit_rpm = new_cred.rate_limit_rpm;
+        validated_cred.usage_cap = new_cred.usage_cap;
+        let rate_limiter = TokenBucket::for_credential(&validated_cred, &self.config());
 
         {
             let mut entries = self.entries.lock();
@@ -1527,6 +3661,22 @@ impl MultiTokenManager {
                 disabled_reason: None,
                 success_count: 0,
                 last_used_at: None,
+                last_usage: None,
+                rate_limiter,
+                disabled_at: None,
+                recovery_attempts: 0,
+                last_error: None,
+                health: HealthStatus::Unknown,
+                last_health_check_at: None,
+                health_success_streak: 0,
+                daily_usage: BTreeMap::new(),
+                quota_reset_at: None,
+                rolling_usage: RollingUsage::default(),
+                quota_remaining: None,
+                low_balance_notified: false,
+                watermark_reached: false,
+                latest_usage: None,
+                local_activity: LocalActivity::default(),
             });
         }
 
@@ -1553,7 +3703,7 @@ impl MultiTokenManager {
     /// # 返回
     /// - `Ok(())` - 删除成功
     /// - `Err(_)` - 凭据不存在、未禁用或持久化失败
-    pub fn delete_credential(&self, id: u64) -> anyhow::Result<()> {
+    pub fn delete_credential(&self, id: CredentialId) -> anyhow::Result<()> {
         let was_current = {
             let mut entries = self.entries.lock();
 
@@ -1578,6 +3728,10 @@ impl MultiTokenManager {
             was_current
         };
 
+        // 清理指向已删除凭据的粘性会话绑定与延迟样本
+        self.sticky_bindings.lock().retain(|_, bound| *bound != id);
+        self.latency.remove(id);
+
         // 如果删除的是当前凭据，切换到优先级最高的可用凭据
         if was_current {
             self.select_highest_priority();
@@ -1588,7 +3742,7 @@ impl MultiTokenManager {
             let entries = self.entries.lock();
             if entries.is_empty() {
                 let mut current_id = self.current_id.lock();
-                *current_id = 0;
+                *current_id = CredentialId::default();
                 tracing::info!("所有凭据已删除，current_id 已重置为 0");
             }
         }
@@ -1603,15 +3757,114 @@ impl MultiTokenManager {
         Ok(())
     }
 
-    /// 获取负载均衡模式（Admin API）
-    pub fn get_load_balancing_mode(&self) -> String {
-        self.load_balancing_mode.lock().clone()
+    /// 应用配置中的优先级预设（Admin API / 定时任务）
+    ///
+    /// 按凭据标签重新设置优先级，未匹配任何标签且预设未配置默认优先级的凭据保持不变
+    pub fn apply_priority_preset(&self, name: &str) -> anyhow::Result<AppliedPriorityPreset> {
+        let config = self.config();
+        let preset = config
+            .priority_presets
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("优先级预设不存在: {}", name))?;
+
+        let changed = apply_preset_to_entries(&mut self.entries.lock(), preset);
+        self.select_highest_priority();
+        if changed > 0 {
+            self.persist_credentials()?;
+        }
+        tracing::info!("已应用优先级预设 {}，{} 个凭据优先级变更", name, changed);
+
+        let applied = AppliedPriorityPreset {
+            name: name.to_string(),
+            applied_at: Utc::now(),
+            changed,
+        };
+        *self.active_priority_preset.lock() = Some(applied.clone());
+        Ok(applied)
     }
 
-    fn persist_load_balancing_mode(&self, mode: &str) -> anyhow::Result<()> {
-        use anyhow::Context;
+    /// 最近一次应用的优先级预设（进程内记录，重启后为空）
+    pub fn active_priority_preset(&self) -> Option<AppliedPriorityPreset> {
+        self.active_priority_preset.lock().clone()
+    }
+
+    /// 检测并合并重复凭据（Admin API / CLI）
+    ///
+    /// 被删除凭据的成功次数与按日用量累加到保留的凭据上，标签取并集、优先级取最高；
+    /// `dry_run` 为 true 时只返回分组结果不做修改
+    pub fn merge_duplicates(&self, dry_run: bool) -> anyhow::Result<Vec<DuplicateGroup>> {
+        let (groups, removed_current) = {
+            let mut entries = self.entries.lock();
+            let groups = duplicate_groups(&entries);
+            if dry_run || groups.is_empty() {
+                return Ok(groups);
+            }
+
+            for group in &groups {
+                let (removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut *entries)
+                    .into_iter()
+                    .partition(|e| group.remove.contains(&e.id));
+                *entries = kept;
+
+                let keeper = entries
+                    .iter_mut()
+                    .find(|e| e.id == group.keep)
+                    .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", group.keep))?;
+                for entry in removed {
+                    keeper.success_count += entry.success_count;
+                    keeper.last_used_at = keeper.last_used_at.take().max(entry.last_used_at);
+                    keeper.credentials.priority =
+                        keeper.credentials.priority.min(entry.credentials.priority);
+                    for (date, usage) in entry.daily_usage {
+                        let day = keeper.daily_usage.entry(date).or_default();
+                        day.requests += usage.requests;
+                        day.input_tokens += usage.input_tokens;
+                        day.output_tokens += usage.output_tokens;
+                        day.errors += usage.errors;
+                        day.content_filtered += usage.content_filtered;
+                    }
+                    let tags = keeper.credentials.tags.iter().chain(&entry.credentials.tags);
+                    keeper.credentials.tags = KiroCredentials::normalize_tags(tags.cloned());
+                }
+            }
+
+            let current_id = *self.current_id.lock();
+            let removed_current = groups.iter().any(|g| g.remove.contains(&current_id));
+            (groups, removed_current)
+        };
+
+        // 清理指向已删除凭据的粘性会话绑定与延迟样本
+        let removed: HashSet<CredentialId> = groups.iter().flat_map(|g| g.remove.clone()).collect();
+        self.sticky_bindings
+            .lock()
+            .retain(|_, bound| !removed.contains(bound));
+        for id in &removed {
+            self.latency.remove(*id);
+        }
+        if removed_current {
+            self.select_highest_priority();
+        }
+
+        self.persist_credentials()?;
+        self.save_stats();
+
+        tracing::info!(
+            "已合并 {} 组重复凭据，删除 {} 个凭据",
+            groups.len(),
+            removed.len()
+        );
+        Ok(groups)
+    }
+
+    /// 获取负载均衡模式（Admin API）
+    pub fn get_load_balancing_mode(&self) -> String {
+        self.load_balancing_mode.lock().clone()
+    }
 
-        let config_path = match self.config.config_path() {
+    fn persist_load_balancing_mode(&self, mode: &str) -> anyhow::Result<()> {
+        use anyhow::Context;
+
+        let config_path = match self.config().config_path() {
             Some(path) => path.to_path_buf(),
             None => {
                 tracing::warn!("配置文件路径未知，负载均衡模式仅在当前进程生效: {}", mode);
@@ -1655,15 +3908,14 @@ impl MultiTokenManager {
 
 impl Drop for MultiTokenManager {
     fn drop(&mut self) {
-        if self.stats_dirty.load(Ordering::Relaxed) {
-            self.save_stats();
-        }
+        self.flush_stats();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::config::QuotaAdmission;
 
     #[test]
     fn test_token_manager_new() {
@@ -1676,7 +3928,7 @@ mod tests {
     #[test]
     fn test_is_token_expired_with_expired_token() {
         let mut credentials = KiroCredentials::default();
-        credentials.expires_at = Some("2020-01-01T00:00:00Z".to_string());
+        credentials.expires_at = "2020-01-01T00:00:00Z".parse().ok();
         assert!(is_token_expired(&credentials));
     }
 
@@ -1684,7 +3936,7 @@ mod tests {
     fn test_is_token_expired_with_valid_token() {
         let mut credentials = KiroCredentials::default();
         let future = Utc::now() + Duration::hours(1);
-        credentials.expires_at = Some(future.to_rfc3339());
+        credentials.expires_at = Some(future);
         assert!(!is_token_expired(&credentials));
     }
 
@@ -1692,7 +3944,7 @@ mod tests {
     fn test_is_token_expired_within_5_minutes() {
         let mut credentials = KiroCredentials::default();
         let expires = Utc::now() + Duration::minutes(3);
-        credentials.expires_at = Some(expires.to_rfc3339());
+        credentials.expires_at = Some(expires);
         assert!(is_token_expired(&credentials));
     }
 
@@ -1706,7 +3958,7 @@ mod tests {
     fn test_is_token_expiring_soon_within_10_minutes() {
         let mut credentials = KiroCredentials::default();
         let expires = Utc::now() + Duration::minutes(8);
-        credentials.expires_at = Some(expires.to_rfc3339());
+        credentials.expires_at = Some(expires);
         assert!(is_token_expiring_soon(&credentials));
     }
 
@@ -1714,7 +3966,7 @@ mod tests {
     fn test_is_token_expiring_soon_beyond_10_minutes() {
         let mut credentials = KiroCredentials::default();
         let expires = Utc::now() + Duration::minutes(15);
-        credentials.expires_at = Some(expires.to_rfc3339());
+        credentials.expires_at = Some(expires);
         assert!(!is_token_expiring_soon(&credentials));
     }
 
@@ -1742,6 +3994,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_merge_duplicates_keeps_best_credential() {
+        let credential = |refresh_token: &str, email: Option<&str>, priority: u32| KiroCredentials {
+            refresh_token: Some(refresh_token.to_string()),
+            email: email.map(str::to_string),
+            priority,
+            ..Default::default()
+        };
+        let a = KiroCredentials {
+            tags: vec!["team-a".to_string()],
+            ..credential("rt-1", None, 3)
+        };
+        let b = KiroCredentials {
+            tags: vec!["prod".to_string()],
+            ..credential("rt-1", Some("User@Example.com"), 5)
+        };
+        let c = KiroCredentials {
+            disabled: true,
+            ..credential("rt-2", Some("user@example.com "), 1)
+        };
+        let d = credential("rt-3", None, 0);
+
+        let manager =
+            MultiTokenManager::new(Config::default(), vec![a, b, c, d], None, None, false)
+                .unwrap();
+        manager.entries.lock()[1].success_count = 7;
+
+        let expected = vec![DuplicateGroup {
+            keep: CredentialId(1),
+            remove: vec![CredentialId(2), CredentialId(3)],
+            reasons: vec!["email".to_string(), "refreshToken".to_string()],
+        }];
+        assert_eq!(manager.merge_duplicates(true).unwrap(), expected);
+        assert_eq!(manager.total_count(), 4);
+
+        assert_eq!(manager.merge_duplicates(false).unwrap(), expected);
+        let snapshot = manager.snapshot();
+        assert_eq!(snapshot.entries.len(), 2);
+        let kept = &snapshot.entries[0];
+        assert_eq!((kept.id, kept.priority, kept.success_count), (CredentialId(1), 1, 7));
+        assert_eq!(kept.tags, vec!["team-a".to_string(), "prod".to_string()]);
+        assert!(manager.merge_duplicates(false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_apply_priority_preset_by_tags() {
+        let config: Config = serde_json::from_str(
+            r#"{"priorityPresets": {"overnight-batch": {"tags": {"batch": 0, "prod": 5}}}}"#,
+        )
+        .unwrap();
+        let tagged = |tag: &str, priority: u32| KiroCredentials {
+            tags: vec![tag.to_string()],
+            priority,
+            ..Default::default()
+        };
+        let credentials = vec![tagged("prod", 0), tagged("batch", 5), tagged("other", 3)];
+        let manager = MultiTokenManager::new(config, credentials, None, None, false).unwrap();
+        assert!(manager.active_priority_preset().is_none());
+
+        let applied = manager.apply_priority_preset("overnight-batch").unwrap();
+        assert_eq!((applied.name.as_str(), applied.changed), ("overnight-batch", 2));
+        let priorities: Vec<u32> = manager.snapshot().entries.iter().map(|e| e.priority).collect();
+        assert_eq!(priorities, vec![5, 0, 3]);
+        assert_eq!(manager.active_priority_preset(), Some(applied));
+
+        assert!(manager.apply_priority_preset("missing").is_err());
+    }
+
     #[tokio::test]
     async fn test_add_credential_reject_duplicate_refresh_token() {
         let config = Config::default();
@@ -1790,9 +4110,9 @@ mod tests {
     fn test_multi_token_manager_duplicate_ids() {
         let config = Config::default();
         let mut cred1 = KiroCredentials::default();
-        cred1.id = Some(1);
+        cred1.id = Some(CredentialId(1));
         let mut cred2 = KiroCredentials::default();
-        cred2.id = Some(1); // 重复 ID
+        cred2.id = Some(CredentialId(1)); // 重复 ID
 
         let result = MultiTokenManager::new(config, vec![cred1, cred2], None, None, false);
         assert!(result.is_err());
@@ -1815,18 +4135,18 @@ mod tests {
 
         // 凭据会自动分配 ID（从 1 开始）
         // 前两次失败不会禁用（使用 ID 1）
-        assert!(manager.report_failure(1));
-        assert!(manager.report_failure(1));
+        assert!(manager.report_failure(CredentialId(1)));
+        assert!(manager.report_failure(CredentialId(1)));
         assert_eq!(manager.available_count(), 2);
 
         // 第三次失败会禁用第一个凭据
-        assert!(manager.report_failure(1));
+        assert!(manager.report_failure(CredentialId(1)));
         assert_eq!(manager.available_count(), 1);
 
         // 继续失败第二个凭据（使用 ID 2）
-        assert!(manager.report_failure(2));
-        assert!(manager.report_failure(2));
-        assert!(!manager.report_failure(2)); // 所有凭据都禁用了
+        assert!(manager.report_failure(CredentialId(2)));
+        assert!(manager.report_failure(CredentialId(2)));
+        assert!(!manager.report_failure(CredentialId(2))); // 所有凭据都禁用了
         assert_eq!(manager.available_count(), 0);
     }
 
@@ -1838,15 +4158,15 @@ mod tests {
         let manager = MultiTokenManager::new(config, vec![cred], None, None, false).unwrap();
 
         // 失败两次（使用 ID 1）
-        manager.report_failure(1);
-        manager.report_failure(1);
+        manager.report_failure(CredentialId(1));
+        manager.report_failure(CredentialId(1));
 
         // 成功后重置计数（使用 ID 1）
-        manager.report_success(1);
+        manager.report_success(CredentialId(1));
 
         // 再失败两次不会禁用
-        manager.report_failure(1);
-        manager.report_failure(1);
+        manager.report_failure(CredentialId(1));
+        manager.report_failure(CredentialId(1));
         assert_eq!(manager.available_count(), 1);
     }
 
@@ -1875,6 +4195,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_concurrent_persists_write_latest_state() {
+        let dir = std::env::temp_dir().join(format!("kiro-persist-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("credentials.json");
+        let credentials: Vec<KiroCredentials> = (1..=4)
+            .map(|id| KiroCredentials {
+                id: Some(CredentialId(id)),
+                refresh_token: Some(format!("token{}", id)),
+                ..Default::default()
+            })
+            .collect();
+        let manager =
+            MultiTokenManager::new(Config::default(), credentials, None, Some(path.clone()), true)
+                .unwrap();
+
+        // 多个线程同时修改不同凭据并回写，最终文件必须包含每个线程的最后一次修改
+        std::thread::scope(|scope| {
+            for id in 1..=4 {
+                let manager = &manager;
+                scope.spawn(move || {
+                    for priority in 0..25 {
+                        manager.set_priority(CredentialId(id), priority).unwrap();
+                    }
+                });
+            }
+        });
+
+        let persisted: Vec<KiroCredentials> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(persisted.len(), 4);
+        assert!(persisted.iter().all(|c| c.priority == 24));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_set_load_balancing_mode_persists_to_config_file() {
         let config_path = std::env::temp_dir().join(format!(
@@ -1904,31 +4260,221 @@ mod tests {
         std::fs::remove_file(&config_path).unwrap();
     }
 
+    #[test]
+    fn test_auto_disable_emits_notifications() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![KiroCredentials::default(), KiroCredentials::default()],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let mut events = manager.subscribe_notifications();
+
+        for _ in 0..MAX_FAILURES_PER_CREDENTIAL {
+            manager.report_failure(CredentialId(1));
+        }
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.event, EventKind::CredentialDisabled);
+        assert_eq!(event.credential_id, Some(CredentialId(1)));
+        assert!(events.try_recv().is_err());
+
+        manager.report_quota_exhausted(CredentialId(2));
+        assert_eq!(events.try_recv().unwrap().event, EventKind::CredentialDisabled);
+        assert_eq!(
+            events.try_recv().unwrap().event,
+            EventKind::AllCredentialsExhausted
+        );
+    }
+
+    #[test]
+    fn test_refresh_schedule_staggers_and_skips_disabled() {
+        let mut config = Config::default();
+        config.proactive_refresh_margin_secs = 600;
+        let now = Utc::now();
+        let due = KiroCredentials {
+            expires_at: Some(now + Duration::seconds(300)),
+            ..Default::default()
+        };
+        let later = KiroCredentials {
+            expires_at: Some(now + Duration::hours(1)),
+            ..Default::default()
+        };
+        let disabled = KiroCredentials {
+            expires_at: Some(now + Duration::seconds(60)),
+            disabled: true,
+            ..Default::default()
+        };
+
+        let manager =
+            MultiTokenManager::new(config, vec![due, later, disabled], None, None, false).unwrap();
+        let (due_ids, next) = manager.refresh_schedule(now);
+        assert_eq!(due_ids, vec![CredentialId(1)]);
+
+        // 过期前 600s，再按 ID 错开最多 150s
+        let next = next.unwrap();
+        assert!(next <= now + Duration::seconds(3600 - 600));
+        assert!(next >= now + Duration::seconds(3600 - 600 - 151));
+        let snapshot = manager.snapshot();
+        assert_eq!(snapshot.entries[1].next_refresh_at, Some(next));
+        assert!(snapshot.entries[2].next_refresh_at.is_none());
+
+        // 未启用或没有过期时间时不调度
+        let mut credentials = KiroCredentials::default();
+        assert!(proactive_refresh_at(CredentialId(1), &credentials, 600).is_none());
+        credentials.expires_at = Some(now);
+        assert!(proactive_refresh_at(CredentialId(1), &credentials, 0).is_none());
+    }
+
+    #[test]
+    fn test_balance_refresh_targets_skip_recent_and_disabled() {
+        let disabled = KiroCredentials {
+            disabled: true,
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![
+                KiroCredentials::default(),
+                KiroCredentials::default(),
+                disabled,
+            ],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let max_age = Duration::seconds(60);
+        assert_eq!(
+            manager.balance_refresh_targets(max_age),
+            vec![CredentialId(1), CredentialId(2)]
+        );
+
+        let usage: UsageLimitsResponse = serde_json::from_str("{}").unwrap();
+        manager.entries.lock()[0].latest_usage = Some((Utc::now(), usage));
+        assert_eq!(
+            manager.balance_refresh_targets(max_age),
+            vec![CredentialId(2)]
+        );
+        assert!(manager.latest_usage_limits(CredentialId(1)).is_some());
+        assert_eq!(
+            manager.balance_refresh_targets(Duration::zero()),
+            vec![CredentialId(1), CredentialId(2)]
+        );
+    }
+
+    #[test]
+    fn test_enrich_account_metadata_from_usage() {
+        let usage: UsageLimitsResponse = serde_json::from_str(
+            r#"{"subscriptionInfo":{"subscriptionTitle":"KIRO PRO"},"userInfo":{"email":"a@example.com","userId":"u-1"}}"#,
+        )
+        .unwrap();
+        let mut credentials = KiroCredentials {
+            email: Some("old@example.com".to_string()),
+            ..Default::default()
+        };
+        assert!(enrich_account_metadata(
+            CredentialId(1),
+            &mut credentials,
+            &usage
+        ));
+        assert_eq!(credentials.email.as_deref(), Some("a@example.com"));
+        assert_eq!(credentials.account_id.as_deref(), Some("u-1"));
+        assert_eq!(credentials.subscription_title.as_deref(), Some("KIRO PRO"));
+        assert!(!enrich_account_metadata(
+            CredentialId(1),
+            &mut credentials,
+            &usage
+        ));
+
+        // 上游未返回账户信息时保留已有值
+        let empty: UsageLimitsResponse = serde_json::from_str("{}").unwrap();
+        assert!(!enrich_account_metadata(
+            CredentialId(1),
+            &mut credentials,
+            &empty
+        ));
+        assert_eq!(credentials.email.as_deref(), Some("a@example.com"));
+    }
+
+    #[test]
+    fn test_usage_watermark_deprioritizes_and_disables() {
+        let mut config = Config::default();
+        config.usage_watermark = Some(UsageWatermark {
+            percent: 90.0,
+            action: WatermarkAction::Disable,
+        });
+        let reserved = KiroCredentials {
+            priority: 0,
+            usage_watermark: Some(UsageWatermark {
+                percent: 80.0,
+                action: WatermarkAction::Deprioritize,
+            }),
+            ..Default::default()
+        };
+        let other = KiroCredentials {
+            priority: 1,
+            ..Default::default()
+        };
+        let manager =
+            MultiTokenManager::new(config.clone(), vec![reserved, other], None, None, false)
+                .unwrap();
+        let pick = || manager.select_next_credential(None, None).map(|(id, _)| id);
+        assert_eq!(pick(), Some(CredentialId(1)));
+
+        // 凭据级警戒线覆盖全局配置，只在越过时触发一次
+        {
+            let mut entries = manager.entries.lock();
+            assert!(entries[0].observe_usage_percent(&config, 85.0).is_some());
+            assert!(entries[0].observe_usage_percent(&config, 86.0).is_none());
+            assert!(!entries[0].disabled);
+        }
+        assert_eq!(pick(), Some(CredentialId(2)));
+
+        // 全局警戒线禁用凭据后，降级的凭据仍可作为最后的选择
+        {
+            let mut entries = manager.entries.lock();
+            let watermark = entries[1].observe_usage_percent(&config, 95.0).unwrap();
+            assert_eq!(watermark.action, WatermarkAction::Disable);
+            assert!(entries[1].disabled);
+        }
+        assert_eq!(pick(), Some(CredentialId(1)));
+
+        // 额度重置后用量回落，自动恢复
+        {
+            let mut entries = manager.entries.lock();
+            assert!(entries[1].observe_usage_percent(&config, 5.0).is_none());
+            assert!(!entries[1].disabled);
+        }
+        assert_eq!(pick(), Some(CredentialId(2)));
+    }
+
     #[tokio::test]
     async fn test_multi_token_manager_acquire_context_auto_recovers_all_disabled() {
         let config = Config::default();
         let mut cred1 = KiroCredentials::default();
         cred1.access_token = Some("t1".to_string());
-        cred1.expires_at = Some((Utc::now() + Duration::hours(1)).to_rfc3339());
+        cred1.expires_at = Some(Utc::now() + Duration::hours(1));
         let mut cred2 = KiroCredentials::default();
         cred2.access_token = Some("t2".to_string());
-        cred2.expires_at = Some((Utc::now() + Duration::hours(1)).to_rfc3339());
+        cred2.expires_at = Some(Utc::now() + Duration::hours(1));
 
         let manager =
             MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
 
         // 凭据会自动分配 ID（从 1 开始）
         for _ in 0..MAX_FAILURES_PER_CREDENTIAL {
-            manager.report_failure(1);
+            manager.report_failure(CredentialId(1));
         }
         for _ in 0..MAX_FAILURES_PER_CREDENTIAL {
-            manager.report_failure(2);
+            manager.report_failure(CredentialId(2));
         }
 
         assert_eq!(manager.available_count(), 0);
 
         // 应触发自愈：重置失败计数并重新启用，避免必须重启进程
-        let ctx = manager.acquire_context(None).await.unwrap();
+        let ctx = manager.acquire_context(None, None).await.unwrap();
         assert!(ctx.token == "t1" || ctx.token == "t2");
         assert_eq!(manager.available_count(), 2);
     }
@@ -1944,11 +4490,11 @@ mod tests {
 
         // 凭据会自动分配 ID（从 1 开始）
         assert_eq!(manager.available_count(), 2);
-        assert!(manager.report_quota_exhausted(1));
+        assert!(manager.report_quota_exhausted(CredentialId(1)));
         assert_eq!(manager.available_count(), 1);
 
         // 再禁用第二个后，无可用凭据
-        assert!(!manager.report_quota_exhausted(2));
+        assert!(!manager.report_quota_exhausted(CredentialId(2)));
         assert_eq!(manager.available_count(), 0);
     }
 
@@ -1961,11 +4507,11 @@ mod tests {
         let manager =
             MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
 
-        manager.report_quota_exhausted(1);
-        manager.report_quota_exhausted(2);
+        manager.report_quota_exhausted(CredentialId(1));
+        manager.report_quota_exhausted(CredentialId(2));
         assert_eq!(manager.available_count(), 0);
 
-        let err = manager.acquire_context(None).await.err().unwrap().to_string();
+        let err = manager.acquire_context(None, None).await.err().unwrap().to_string();
         assert!(
             err.contains("所有凭据均已禁用"),
             "错误应提示所有凭据禁用，实际: {}",
@@ -2126,4 +4672,707 @@ mod tests {
         assert_eq!(credentials.effective_auth_region(&config), "auth-only");
         assert_eq!(credentials.effective_api_region(&config), "api-only");
     }
+
+    fn observation(title: &str, usage_limit: f64, free_trial_active: bool) -> UsageObservation {
+        UsageObservation {
+            subscription_title: Some(title.to_string()),
+            usage_limit,
+            free_trial_active,
+        }
+    }
+
+    #[test]
+    fn test_detect_subscription_change_downgrade_and_upgrade() {
+        let pro = observation("KIRO PRO", 1000.0, false);
+        let free = observation("KIRO FREE", 50.0, false);
+
+        let change =
+
+            detect_subscription_change(CredentialId(1), Some(&pro), None, &free).unwrap();
+        assert_eq!(change.kind, SubscriptionChangeKind::Downgrade);
+        assert_eq!(change.old_title.as_deref(), Some("KIRO PRO"));
+        assert_eq!(change.old_usage_limit, Some(1000.0));
+
+        let change =
+
+            detect_subscription_change(CredentialId(1), Some(&free), None, &pro).unwrap();
+        assert_eq!(change.kind, SubscriptionChangeKind::Upgrade);
+    }
+
+    #[test]
+    fn test_detect_subscription_change_uses_stored_title_on_first_fetch() {
+        let free = observation("KIRO FREE", 50.0, false);
+        let change =
+            detect_subscription_change(CredentialId(1), None, Some("KIRO PRO+"), &free).unwrap();
+        assert_eq!(change.kind, SubscriptionChangeKind::Downgrade);
+        assert_eq!(change.old_usage_limit, None);
+
+        // 首次获取且未持久化过订阅等级，不视为变更
+        assert!(detect_subscription_change(CredentialId(1), None, None, &free).is_none());
+    }
+
+    #[test]
+    fn test_detect_subscription_change_trial_expired_and_limit_changed() {
+        let trial = observation("KIRO FREE", 550.0, true);
+        let expired = observation("KIRO FREE", 50.0, false);
+        let change =
+            detect_subscription_change(CredentialId(1), Some(&trial), None, &expired).unwrap();
+        assert_eq!(change.kind, SubscriptionChangeKind::TrialExpired);
+
+        let bonus = observation("KIRO FREE", 150.0, false);
+        let change =
+            detect_subscription_change(CredentialId(1), Some(&expired), None, &bonus).unwrap();
+        assert_eq!(change.kind, SubscriptionChangeKind::LimitChanged);
+
+        assert!(detect_subscription_change(CredentialId(1), Some(&bonus), None, &bonus).is_none());
+    }
+
+    #[test]
+    fn test_record_subscription_change_emits_event() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![KiroCredentials::default()],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let mut rx = manager.subscribe_subscription_changes();
+
+        let change = detect_subscription_change(
+            CredentialId(1),
+            Some(&observation("KIRO PRO", 1000.0, false)),
+            None,
+            &observation("KIRO FREE", 50.0, false),
+        )
+        .unwrap();
+        manager.record_subscription_change(change);
+
+        let received = rx.try_recv().unwrap();
+        assert_eq!(received.credential_id, CredentialId(1));
+        assert_eq!(received.kind, SubscriptionChangeKind::Downgrade);
+        assert_eq!(manager.subscription_changes().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_context_skips_current_credential_without_opus_support() {
+        let mut cred1 = KiroCredentials::default();
+        cred1.access_token = Some("t1".to_string());
+        cred1.expires_at = Some(Utc::now() + Duration::hours(1));
+        cred1.subscription_title = Some("KIRO FREE".to_string());
+        let mut cred2 = KiroCredentials::default();
+        cred2.access_token = Some("t2".to_string());
+        cred2.expires_at = Some(Utc::now() + Duration::hours(1));
+        cred2.priority = 1;
+        cred2.subscription_title = Some("KIRO PRO".to_string());
+
+        let manager =
+            MultiTokenManager::new(Config::default(), vec![cred1, cred2], None, None, false)
+                .unwrap();
+
+        // 当前凭据为 #1（FREE），请求 Opus 时应路由到 #2
+        let ctx = manager.acquire_context(Some("claude-opus-4-6"), None).await.unwrap();
+        assert_eq!(ctx.id, CredentialId(2));
+
+        let ctx = manager.acquire_context(Some("claude-sonnet-4-6"), None).await.unwrap();
+        assert_eq!(ctx.id, CredentialId(2));
+    }
+
+    fn valid_credential(token: &str, priority: u32) -> KiroCredentials {
+        let mut cred = KiroCredentials::default();
+        cred.access_token = Some(token.to_string());
+        cred.expires_at = Some(Utc::now() + Duration::hours(1));
+        cred.priority = priority;
+        cred
+    }
+
+    #[tokio::test]
+    async fn test_sticky_session_pins_clients_to_distinct_credentials() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![valid_credential("t1", 0), valid_credential("t2", 1)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let a = manager.acquire_context_for_session(None, None, "client-a").await.unwrap();
+        let b = manager.acquire_context_for_session(None, None, "client-b").await.unwrap();
+        assert_ne!(a.id, b.id);
+
+        // 同一客户端重复请求保持绑定
+        for _ in 0..3 {
+            let again = manager.acquire_context_for_session(None, None, "client-a").await.unwrap();
+            assert_eq!(again.id, a.id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sticky_session_fails_over_when_bound_credential_disabled() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![valid_credential("t1", 0), valid_credential("t2", 1)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let first = manager.acquire_context_for_session(None, None, "client").await.unwrap();
+        assert_eq!(first.id, CredentialId(1));
+
+        manager.report_quota_exhausted(CredentialId(1));
+        let second = manager.acquire_context_for_session(None, None, "client").await.unwrap();
+        assert_eq!(second.id, CredentialId(2));
+
+        // 新绑定生效
+        let third = manager.acquire_context_for_session(None, None, "client").await.unwrap();
+        assert_eq!(third.id, CredentialId(2));
+    }
+
+    #[test]
+    fn test_token_bucket_refill_and_wait() {
+        let mut bucket = TokenBucket::new(60);
+        let start = bucket.last_refill;
+        for _ in 0..60 {
+            assert!(bucket.has_token(start));
+            bucket.consume(start);
+        }
+        assert!(!bucket.has_token(start));
+        assert!(bucket.wait_time(start) > StdDuration::ZERO);
+
+        // 60 RPM 每秒补充一个令牌，且不超过容量
+        assert!(bucket.has_token(start + StdDuration::from_secs(1)));
+        let later = start + StdDuration::from_secs(3600);
+        assert_eq!(bucket.available(later), 60.0);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_context_skips_rate_limited_credential() {
+        let mut limited = valid_credential("t1", 0);
+        limited.rate_limit_rpm = Some(1);
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![limited, valid_credential("t2", 1)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let first = manager.acquire_context(None, None).await.unwrap();
+        assert_eq!(first.id, CredentialId(1));
+
+        // #1 令牌耗尽后应路由到未限流的 #2
+        let second = manager.acquire_context(None, None).await.unwrap();
+        assert_eq!(second.id, CredentialId(2));
+
+        // 取消限制后 #1 恢复可用
+        manager.set_rate_limit(CredentialId(1), Some(0)).unwrap();
+        let snapshot = manager.snapshot();
+        assert_eq!(snapshot.entries[0].rate_limit_rpm, None);
+        assert!(manager.entries.lock()[0].within_rate_limit(Instant::now()));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_context_skips_credential_over_usage_cap() {
+        let mut capped = valid_credential("t1", 0);
+        capped.usage_cap = Some(UsageCap {
+            hourly_requests: Some(1),
+            ..Default::default()
+        });
+        let mut token_capped = valid_credential("t2", 1);
+        token_capped.usage_cap = Some(UsageCap {
+            daily_tokens: Some(1000),
+            ..Default::default()
+        });
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![capped, token_capped],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(manager.acquire_context(None, None).await.unwrap().id, CredentialId(1));
+
+        // #1 达到每小时请求上限后路由到 #2
+        assert_eq!(manager.acquire_context(None, None).await.unwrap().id, CredentialId(2));
+        let snapshot = manager.snapshot();
+        assert_eq!(snapshot.entries[0].window_usage.hour_requests, 1);
+        assert_eq!(snapshot.entries[1].usage_cap.as_ref().unwrap().daily_tokens, Some(1000));
+
+        // 两个凭据都达到上限后返回无可用凭据
+        manager.record_token_usage(CredentialId(2), 800, 200);
+        let err = manager
+            .acquire_context(None, None)
+            .await
+            .err()
+            .expect("两个凭据均已达到用量上限");
+        assert!(err.downcast_ref::<NoAvailableCredentials>().is_some());
+        assert!(err.to_string().contains("用量上限"));
+    }
+
+    #[test]
+    fn test_failover_candidate_skips_failed_credentials() {
+        let mut tagged = valid_credential("t3", 0);
+        tagged.tags = vec!["prod".to_string()];
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![
+                valid_credential("t1", 1),
+                valid_credential("t2", 2),
+                tagged,
+            ],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(manager.failover_candidate(None, None, &[]), Some(CredentialId(3)));
+        assert_eq!(
+            manager.failover_candidate(None, None, &[CredentialId(3)]),
+            Some(CredentialId(1))
+        );
+        let failed = [CredentialId(1), CredentialId(3)];
+        assert_eq!(manager.failover_candidate(None, None, &failed), Some(CredentialId(2)));
+        manager.set_disabled(CredentialId(2), true).unwrap();
+        assert_eq!(manager.failover_candidate(None, None, &failed), None);
+        // 凭据池内没有其他凭据
+        assert_eq!(manager.failover_candidate(None, Some("prod"), &[CredentialId(3)]), None);
+    }
+
+    #[test]
+    fn test_admit_by_quota_reroutes_or_rejects() {
+        let mut config = Config::default();
+        config.quota_admission = Some(QuotaAdmission {
+            tokens_per_unit: 1000.0,
+            safety_margin: 1.0,
+            min_tokens: 100,
+        });
+        let manager = MultiTokenManager::new(
+            config,
+            vec![
+                valid_credential("t1", 0),
+                valid_credential("t2", 1),
+                valid_credential("t3", 2),
+            ],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let set_remaining = |remaining: [Option<f64>; 3]| {
+            for (entry, r) in manager.entries.lock().iter_mut().zip(remaining) {
+                entry.quota_remaining = r;
+            }
+        };
+
+        // 尚未查询过额度或小请求直接放行
+        assert_eq!(manager.admit_by_quota(CredentialId(1), None, None, 50_000), Ok(None));
+        set_remaining([Some(2.0), Some(30.0), Some(80.0)]);
+        assert_eq!(manager.admit_by_quota(CredentialId(1), None, None, 50), Ok(None));
+
+        // 需要 5 + 1：#1 不足，改用剩余最多的 #3
+        assert_eq!(
+            manager.admit_by_quota(CredentialId(1), None, None, 5_000),
+            Ok(Some(CredentialId(3)))
+        );
+        assert_eq!(manager.admit_by_quota(CredentialId(2), None, None, 5_000), Ok(None));
+
+        // 已知额度都不足时改用尚未查询过额度的凭据，没有则拒绝
+        set_remaining([Some(2.0), None, Some(3.0)]);
+        assert_eq!(
+            manager.admit_by_quota(CredentialId(1), None, None, 5_000),
+            Ok(Some(CredentialId(2)))
+        );
+        set_remaining([Some(2.0), Some(1.0), Some(3.0)]);
+        assert_eq!(
+            manager.admit_by_quota(CredentialId(1), None, None, 5_000),
+            Err(QuotaInsufficient {
+                required: 6.0,
+                remaining: 3.0,
+            })
+        );
+
+        // 用量按折算扣减估算的剩余额度
+        manager.record_token_usage(CredentialId(3), 1500, 500);
+        assert_eq!(manager.entries.lock()[2].quota_remaining, Some(1.0));
+    }
+
+    #[test]
+    fn test_rolling_usage_slides_out_old_buckets() {
+        let mut usage = RollingUsage::default();
+        usage.add(0, 1, 100);
+        usage.add(30, 1, 0);
+        usage.add(30, 0, 50);
+
+        let window = usage.window(59);
+        assert_eq!((window.hour_requests, window.hour_tokens), (2, 150));
+        let window = usage.window(60);
+        assert_eq!((window.hour_requests, window.hour_tokens), (1, 50));
+        assert_eq!(window.day_requests, 2);
+
+        usage.add(ROLLING_WINDOW_MINUTES, 1, 0);
+        assert_eq!(usage.buckets.len(), 2);
+        assert_eq!(usage.window(ROLLING_WINDOW_MINUTES).day_tokens, 50);
+    }
+
+    #[test]
+    fn test_reload_config_applies_mode_proxy_and_rate_limit() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![valid_credential("t1", 0)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(manager.entries.lock()[0].rate_limiter.is_none());
+
+        let mut config = Config::default();
+        config.load_balancing_mode = "balanced".to_string();
+        config.proxy_url = Some("http://127.0.0.1:7890".to_string());
+        config.credential_rpm = Some(30);
+        manager.reload_config(config).unwrap();
+
+        assert_eq!(manager.get_load_balancing_mode(), "balanced");
+        assert_eq!(manager.proxy().unwrap().url, "http://127.0.0.1:7890");
+        assert_eq!(manager.snapshot().entries[0].rate_limit_rpm, Some(30));
+
+        // 无效模式整体拒绝，不修改当前配置
+        let mut invalid = Config::default();
+        invalid.load_balancing_mode = "random".to_string();
+        assert!(manager.reload_config(invalid).is_err());
+        assert_eq!(manager.config().credential_rpm, Some(30));
+    }
+
+    #[test]
+    fn test_set_proxy_updates_and_clears_credential_proxy() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![valid_credential("t1", 0)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        manager
+            .set_proxy(
+                CredentialId(1),
+                Some("socks5://10.0.0.1:1080".to_string()),
+                Some("user".to_string()),
+                Some("pass".to_string()),
+            )
+            .unwrap();
+        let proxy = manager.entries.lock()[0].credentials.effective_proxy(None).unwrap();
+        assert_eq!(proxy.url, "socks5://10.0.0.1:1080");
+        assert_eq!(proxy.username.as_deref(), Some("user"));
+
+        // 清除代理时认证信息一并清除
+        manager
+            .set_proxy(CredentialId(1), None, Some("user".to_string()), Some("pass".to_string()))
+            .unwrap();
+        let creds = manager.entries.lock()[0].credentials.clone();
+        assert!(creds.proxy_url.is_none() && creds.proxy_username.is_none());
+        assert!(manager.set_proxy(CredentialId(99), None, None, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_context_restricts_to_tagged_pool() {
+        let mut tagged = valid_credential("t2", 1);
+        tagged.tags = vec!["prod".to_string()];
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![valid_credential("t1", 0), tagged],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        // 不指定凭据池时按优先级选择
+        assert_eq!(manager.acquire_context(None, None).await.unwrap().token, "t1");
+        for _ in 0..3 {
+            let ctx = manager.acquire_context(None, Some("prod")).await.unwrap();
+            assert_eq!(ctx.token, "t2");
+        }
+
+        // 未知凭据池没有可用凭据
+        let result = manager.acquire_context(None, Some("staging")).await;
+        assert!(result.is_err_and(|e| e.is::<NoAvailableCredentials>()));
+
+        manager.set_tags(CredentialId(1), vec![" staging ".to_string(), "".to_string()]).unwrap();
+        assert_eq!(manager.entries.lock()[0].credentials.tags, vec!["staging"]);
+        let ctx = manager.acquire_context(None, Some("staging")).await.unwrap();
+        assert_eq!(ctx.token, "t1");
+
+        manager
+            .set_note(CredentialId(1), Some(" 工作账号 ".to_string()))
+            .unwrap();
+        assert_eq!(
+            manager.snapshot().entries[0].note.as_deref(),
+            Some("工作账号")
+        );
+        manager
+            .set_note(CredentialId(1), Some("  ".to_string()))
+            .unwrap();
+        assert_eq!(manager.entries.lock()[0].credentials.note, None);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_context_falls_back_for_unsupported_model() {
+        let mut sonnet_only = valid_credential("t1", 0);
+        sonnet_only.allowed_models = vec!["sonnet".to_string()];
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![sonnet_only, valid_credential("t2", 1)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let ctx = manager.acquire_context(Some("claude-sonnet-4.5"), None).await.unwrap();
+        assert_eq!(ctx.id, CredentialId(1));
+        let ctx = manager.acquire_context(Some("claude-haiku-4.5"), None).await.unwrap();
+        assert_eq!(ctx.id, CredentialId(2));
+        assert!(manager.has_credential_for_model("claude-haiku-4.5", None));
+
+        // 没有任何凭据支持时返回明确的错误
+        manager.set_models(CredentialId(2), Vec::new(), vec!["haiku".to_string()]).unwrap();
+        let err = manager
+            .acquire_context(Some("claude-haiku-4.5"), None)
+            .await
+            .err()
+            .expect("没有凭据支持 haiku");
+        assert!(err.to_string().contains("claude-haiku-4.5"));
+        assert!(!manager.has_credential_for_model("claude-haiku-4.5", None));
+        assert!(manager.has_credential_for_model("claude-sonnet-4.5", None));
+        assert!(manager.set_models(CredentialId(99), Vec::new(), Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_usage_history_records_and_persists_daily_buckets() {
+        let dir = std::env::temp_dir().join(format!("kiro-usage-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let credentials_path = dir.join("credentials.json");
+        let new_manager = || {
+            MultiTokenManager::new(
+                Config::default(),
+                vec![valid_credential("t1", 0)],
+                None,
+                Some(credentials_path.clone()),
+                false,
+            )
+            .unwrap()
+        };
+
+        let manager = new_manager();
+        manager.report_success(CredentialId(1));
+        manager.record_token_usage(CredentialId(1), 120, 30);
+        manager.record_upstream_error(CredentialId(1), "http_error", Some(500), "boom");
+        manager.record_content_filter(CredentialId(1));
+        manager.save_stats();
+
+        let today = Utc::now().date_naive();
+        let expected = DailyUsage {
+            requests: 1,
+            input_tokens: 120,
+            output_tokens: 30,
+            errors: 1,
+            content_filtered: 1,
+        };
+        assert_eq!(
+            manager.usage_history(CredentialId(1), None, None).unwrap(),
+            vec![(today, expected.clone())]
+        );
+        let tomorrow = today + Duration::days(1);
+        assert!(manager.usage_history(CredentialId(1), Some(tomorrow), None).unwrap().is_empty());
+        assert!(manager.usage_history(CredentialId(99), None, None).is_err());
+
+        // 重启后从统计缓存恢复
+        drop(manager);
+        let reloaded = new_manager();
+        assert_eq!(
+            reloaded.usage_history(CredentialId(1), Some(today), Some(today)).unwrap(),
+            vec![(today, expected)]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_health_check_degrades_and_recovers_after_streak() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![valid_credential("t1", 0), valid_credential("t2", 1)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+
+            manager.record_health_check(CredentialId(1), false).unwrap(),
+
+            HealthStatus::Degraded
+
+        );
+        assert_eq!(manager.available_count(), 1);
+        // 降级凭据仍参与健康检查
+        assert_eq!(manager.health_check_targets(), [CredentialId(1), CredentialId(2)]);
+        let ctx = manager.acquire_context(None, None).await.unwrap();
+        assert_eq!(ctx.id, CredentialId(2));
+
+        // 默认需要连续 2 次成功才恢复，中途失败会重置计数
+        assert_eq!(
+            manager.record_health_check(CredentialId(1), true).unwrap(),
+            HealthStatus::Degraded
+        );
+        manager.record_health_check(CredentialId(1), false).unwrap();
+        manager.record_health_check(CredentialId(1), true).unwrap();
+        assert_eq!(manager.available_count(), 1);
+        assert_eq!(
+            manager.record_health_check(CredentialId(1), true).unwrap(),
+            HealthStatus::Healthy
+        );
+        assert_eq!(manager.available_count(), 2);
+
+        let snapshot = manager.snapshot();
+        assert_eq!(snapshot.entries[0].health_status, HealthStatus::Healthy);
+        assert!(snapshot.entries[0].last_health_check_at.is_some());
+    }
+
+    #[test]
+    fn test_health_check_does_not_override_manual_disable() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![valid_credential("t1", 0), valid_credential("t2", 1)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        manager.set_disabled(CredentialId(1), true).unwrap();
+        assert_eq!(manager.health_check_targets(), [CredentialId(2)]);
+        manager.record_health_check(CredentialId(1), false).unwrap();
+        for _ in 0..3 {
+            manager.record_health_check(CredentialId(1), true).unwrap();
+        }
+        assert_eq!(manager.available_count(), 1);
+    }
+
+    #[test]
+    fn test_recovery_cooldown_backoff() {
+        assert_eq!(recovery_cooldown(300, 3600, 0), StdDuration::from_secs(300));
+        assert_eq!(recovery_cooldown(300, 3600, 2), StdDuration::from_secs(1200));
+        assert_eq!(recovery_cooldown(300, 3600, 5), StdDuration::from_secs(3600));
+        assert_eq!(recovery_cooldown(300, 3600, 80), StdDuration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_recovery_probe_reenables_after_cooldown() {
+        let mut config = Config::default();
+        config.failure_cooldown_secs = 1;
+        let manager = MultiTokenManager::new(
+            config,
+            vec![valid_credential("t1", 0), valid_credential("t2", 1)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        for _ in 0..MAX_FAILURES_PER_CREDENTIAL {
+            manager.report_failure(CredentialId(1));
+        }
+        assert_eq!(manager.available_count(), 1);
+        // 冷却期内不探测
+        assert!(manager.credentials_due_for_recovery().is_empty());
+
+        // 模拟冷却期已过
+        let elapsed = Instant::now() - StdDuration::from_millis(1500);
+        manager.entries.lock()[0].disabled_at = Some(elapsed);
+        assert_eq!(manager.credentials_due_for_recovery(), [CredentialId(1)]);
+
+        // 探测失败：冷却时间翻倍，暂不再探测
+        manager.record_recovery_probe(CredentialId(1), false);
+        manager.entries.lock()[0].disabled_at = Some(elapsed);
+        assert!(manager.credentials_due_for_recovery().is_empty());
+
+        manager.record_recovery_probe(CredentialId(1), true);
+        assert_eq!(manager.available_count(), 2);
+        assert_eq!(manager.entries.lock()[0].failure_count, 0);
+    }
+
+    #[test]
+    fn test_quota_disabled_recovers_after_reset_time() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![valid_credential("t1", 0), valid_credential("t2", 1)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        manager.report_quota_exhausted(CredentialId(1));
+        // 未知重置时间时不探测
+        assert!(manager.credentials_due_for_recovery().is_empty());
+
+        manager.entries.lock()[0].quota_reset_at = Some(Utc::now() + Duration::hours(1));
+        assert!(manager.credentials_due_for_recovery().is_empty());
+
+        manager.entries.lock()[0].quota_reset_at = Some(Utc::now() - Duration::minutes(1));
+        assert_eq!(manager.credentials_due_for_recovery(), [CredentialId(1)]);
+
+        // 重置后额度仍未恢复：推迟再次探测
+        manager.record_recovery_probe(CredentialId(1), false);
+        assert!(manager.credentials_due_for_recovery().is_empty());
+        assert_eq!(manager.available_count(), 1);
+
+        manager.record_recovery_probe(CredentialId(1), true);
+        assert_eq!(manager.available_count(), 2);
+    }
+
+    #[test]
+    fn test_idle_credentials_oldest_first_with_limit() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![
+                valid_credential("t1", 0),
+                valid_credential("t2", 1),
+                valid_credential("t3", 2),
+                valid_credential("t4", 3),
+            ],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        {
+            let mut entries = manager.entries.lock();
+            entries[0].last_used_at = Some(Utc::now() - Duration::hours(30));
+            entries[1].last_used_at = Some(Utc::now());
+            entries[2].last_used_at = Some(Utc::now() - Duration::hours(48));
+        }
+        manager.set_disabled(CredentialId(4), true).unwrap();
+
+        // 从未使用的 #4 已禁用；#2 最近使用过，不算闲置
+        assert_eq!(
+            manager.idle_credentials(Duration::hours(24), 10),
+            [CredentialId(3), CredentialId(1)]
+        );
+        assert_eq!(
+            manager.idle_credentials(Duration::hours(24), 1),
+            [CredentialId(3)]
+        );
+    }
 }
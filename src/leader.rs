@@ -0,0 +1,237 @@
+//! 主备模式选主
+//!
+//! 两个实例共享凭据存储时，如果都运行 Cloud Pass 刷新与 Token 主动刷新，会并发刷新同一凭据，
+//! 新签发的 refreshToken 使对方手中的失效。配置 `leaderElection` 后，各实例通过共享目录中的租约文件选主：
+//! 持有未过期租约的节点为主节点，只有主节点运行这些任务；主节点停止续约后，备节点在租约过期时接管
+
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::model::config::LeaderElectionConfig;
+
+/// 租约文件内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Lease {
+    node_id: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// 选主状态（Admin 调试接口展示）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderStatus {
+    pub node_id: String,
+    pub leader: bool,
+}
+
+/// 基于租约文件的选主
+pub struct LeaderElection {
+    node_id: String,
+    lease_file: PathBuf,
+    ttl: Duration,
+    leader: watch::Sender<bool>,
+}
+
+impl LeaderElection {
+    pub fn new(config: &LeaderElectionConfig) -> Self {
+        let node_id = config
+            .node_id
+            .clone()
+            .filter(|id| !id.trim().is_empty())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        Self {
+            node_id,
+            lease_file: PathBuf::from(&config.lease_file),
+            ttl: Duration::from_secs(config.lease_ttl.max(3)),
+            leader: watch::Sender::new(false),
+        }
+    }
+
+    /// 本节点 ID
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// 当前是否为主节点
+    pub fn is_leader(&self) -> bool {
+        *self.leader.borrow()
+    }
+
+    pub fn status(&self) -> LeaderStatus {
+        LeaderStatus {
+            node_id: self.node_id.clone(),
+            leader: self.is_leader(),
+        }
+    }
+
+    /// 选主后台任务：每 1/3 租约有效期尝试获取或续约一次
+    ///
+    /// 读写租约文件失败时按备节点处理，宁可短暂无人刷新也不与其他节点同时刷新
+    pub async fn run(self: Arc<Self>) {
+        tracing::info!(
+            "主备选主任务启动，节点 ID: {}，租约文件: {:?}",
+            self.node_id,
+            self.lease_file
+        );
+        loop {
+            let leader = match self.try_acquire(Utc::now()) {
+                Ok(leader) => leader,
+                Err(e) => {
+                    tracing::warn!("读写租约文件失败: {:#}", e);
+                    false
+                }
+            };
+            self.set_leader(leader);
+            tokio::time::sleep(self.ttl / 3).await;
+        }
+    }
+
+    /// 释放租约（服务关闭时调用，让备节点无需等待租约过期即可接管）
+    pub fn release(&self) {
+        if self.is_leader()
+            && read_lease(&self.lease_file)
+                .ok()
+                .flatten()
+                .is_some_and(|lease| lease.node_id == self.node_id)
+        {
+            fs::remove_file(&self.lease_file).ok();
+        }
+        self.set_leader(false);
+    }
+
+    /// 尝试获取或续约租约，返回本节点是否为主节点
+    ///
+    /// 写入后回读确认：两个节点同时抢占时以最后写入者为准，
+    /// 另一节点最迟在下次续约时发现租约已被占用并退为备节点
+    fn try_acquire(&self, now: DateTime<Utc>) -> anyhow::Result<bool> {
+        let current = read_lease(&self.lease_file)?;
+        if !can_acquire(current.as_ref(), &self.node_id, now) {
+            return Ok(false);
+        }
+        let lease = Lease {
+            node_id: self.node_id.clone(),
+            expires_at: now + chrono::Duration::from_std(self.ttl)?,
+        };
+        write_lease(&self.lease_file, &self.node_id, &lease)?;
+        Ok(read_lease(&self.lease_file)?.is_some_and(|l| l.node_id == self.node_id))
+    }
+
+    fn set_leader(&self, leader: bool) {
+        let changed = self
+            .leader
+            .send_if_modified(|current| std::mem::replace(current, leader) != leader);
+        if changed {
+            if leader {
+                tracing::info!("节点 {} 成为主节点", self.node_id);
+            } else {
+                tracing::info!("节点 {} 退为备节点", self.node_id);
+            }
+        }
+    }
+
+    /// 等待成为主节点（`true`）或退为备节点（`false`）
+    async fn wait_until(&self, leader: bool) {
+        let mut rx = self.leader.subscribe();
+        // Sender 与 self 同生命周期，wait_for 不会因通道关闭返回错误
+        let _ = rx.wait_for(|current| *current == leader).await;
+    }
+}
+
+/// 仅在本节点为主节点时运行任务
+///
+/// 未配置选主时直接运行；成为主节点时启动任务，退为备节点时停止任务并等待重新当选
+pub async fn run_as_leader<F, Fut>(election: Option<Arc<LeaderElection>>, name: &str, make: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let Some(election) = election else {
+        make().await;
+        return;
+    };
+    loop {
+        election.wait_until(true).await;
+        tracing::info!("主节点启动 {} 任务", name);
+        tokio::select! {
+            _ = make() => return,
+            _ = election.wait_until(false) => {
+                tracing::info!("已退为备节点，停止 {} 任务", name);
+            }
+        }
+    }
+}
+
+/// 租约是否可由该节点获取：无人持有、已过期或本就由该节点持有
+fn can_acquire(current: Option<&Lease>, node_id: &str, now: DateTime<Utc>) -> bool {
+    current.is_none_or(|lease| lease.node_id == node_id || lease.expires_at <= now)
+}
+
+/// 读取租约文件（文件不存在或内容损坏时视为无人持有）
+fn read_lease(path: &Path) -> anyhow::Result<Option<Lease>> {
+    match fs::read(path) {
+        Ok(content) => Ok(serde_json::from_slice(&content).ok()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 写入租约文件（临时文件名带节点 ID，避免多个节点同时写入时互相覆盖临时文件）
+fn write_lease(path: &Path, node_id: &str, lease: &Lease) -> anyhow::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", node_id));
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, serde_json::to_vec(lease)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn election(lease_file: &Path, node_id: &str) -> LeaderElection {
+        LeaderElection::new(&LeaderElectionConfig {
+            lease_file: lease_file.to_string_lossy().into_owned(),
+            lease_ttl: 30,
+            node_id: Some(node_id.to_string()),
+        })
+    }
+
+    #[test]
+    fn test_lease_acquire_renew_and_takeover() {
+        let dir = std::env::temp_dir().join(format!("kiro-leader-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let lease_file = dir.join("leader.json");
+        let a = election(&lease_file, "a");
+        let b = election(&lease_file, "b");
+        let now = Utc::now();
+
+        assert!(a.try_acquire(now).unwrap());
+        assert!(!b.try_acquire(now).unwrap());
+        // 续约
+        assert!(a.try_acquire(now + chrono::Duration::seconds(10)).unwrap());
+        assert!(!b.try_acquire(now + chrono::Duration::seconds(30)).unwrap());
+
+        // a 停止续约，租约过期后 b 接管
+        let expired = now + chrono::Duration::seconds(41);
+        assert!(b.try_acquire(expired).unwrap());
+        assert!(!a.try_acquire(expired).unwrap());
+
+        // 释放后其他节点立即可接管
+        b.set_leader(true);
+        b.release();
+        assert!(!b.is_leader());
+        assert!(!lease_file.exists());
+        assert!(a.try_acquire(expired).unwrap());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
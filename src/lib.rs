@@ -0,0 +1,29 @@
+//! kiro-rs：Anthropic Claude API 兼容的 Kiro API 代理
+//!
+//! 除作为独立进程运行外，也可以作为库嵌入到其他 Rust 应用中，
+//! 通过 [`KiroApp`] 组装完整的 axum `Router` 后挂载到现有服务的任意路径下
+
+pub mod admin;
+pub mod admin_ui;
+pub mod anthropic;
+pub mod api_keys;
+pub mod app;
+pub mod audit;
+pub mod cloud_pass;
+pub mod common;
+pub mod credential_provider;
+pub mod dev;
+pub mod doctor;
+pub mod http_client;
+pub mod kiro;
+pub mod leader;
+pub mod listen;
+pub mod model;
+pub mod notify;
+pub mod server_tls;
+pub mod supervisor;
+pub mod token;
+
+pub use app::{KiroApp, ShutdownHandle};
+pub use kiro::token_manager::MultiTokenManager;
+pub use model::config::Config;
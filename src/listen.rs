@@ -0,0 +1,199 @@
+//! 监听地址
+//!
+//! 默认监听 `host:port`。配置 `listen` 后可改为：
+//! - `unix:/path/to.sock`：Unix domain socket，供 nginx 等反向代理通过本地套接字转发
+//! - `systemd`：使用 systemd socket activation 传入的套接字（`LISTEN_FDS`），由 systemd 按需启动
+//! - `host:port`：等同于分别配置 host 与 port
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::model::config::Config;
+
+/// systemd 传入的第一个套接字的文件描述符（SD_LISTEN_FDS_START）
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// 监听地址
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListenAddr {
+    Tcp(String),
+    Unix(PathBuf),
+    Systemd,
+}
+
+impl ListenAddr {
+    /// 从配置读取监听地址（未配置 listen 时使用 host 与 port）
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        match config.listen.as_deref().map(str::trim) {
+            Some(listen) if !listen.is_empty() => Self::parse(listen),
+            _ => Ok(Self::Tcp(format!("{}:{}", config.host, config.port))),
+        }
+    }
+
+    fn parse(listen: &str) -> anyhow::Result<Self> {
+        if listen == "systemd" {
+            return Ok(Self::Systemd);
+        }
+        if let Some(path) = listen.strip_prefix("unix:") {
+            if path.is_empty() {
+                anyhow::bail!("listen 缺少 Unix socket 路径: {}", listen);
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+        if !listen.contains(':') {
+            anyhow::bail!(
+                "无法识别的 listen: {}（支持 host:port、unix:/path/to.sock 或 systemd）",
+                listen
+            );
+        }
+        Ok(Self::Tcp(listen.to_string()))
+    }
+
+    /// 绑定监听地址
+    pub async fn bind(&self) -> anyhow::Result<Listener> {
+        match self {
+            Self::Tcp(addr) => {
+                let listener = tokio::net::TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("监听 {} 失败", addr))?;
+                Ok(Listener::Tcp(listener))
+            }
+            #[cfg(unix)]
+            Self::Unix(path) => {
+                // 清理上次运行遗留的 socket 文件（只删除 socket，避免误删普通文件）
+                use std::os::unix::fs::FileTypeExt;
+                if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                    std::fs::remove_file(path)
+                        .with_context(|| format!("删除旧的 socket 文件失败: {}", path.display()))?;
+                }
+                let listener = tokio::net::UnixListener::bind(path)
+                    .with_context(|| format!("监听 {} 失败", path.display()))?;
+                Ok(Listener::Unix(listener, Some(path.clone())))
+            }
+            #[cfg(unix)]
+            Self::Systemd => systemd_listener(),
+            #[cfg(not(unix))]
+            _ => anyhow::bail!("当前平台仅支持 host:port 监听"),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{}", addr),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+            Self::Systemd => write!(f, "systemd"),
+        }
+    }
+}
+
+/// 已绑定的监听套接字
+pub enum Listener {
+    Tcp(tokio::net::TcpListener),
+    /// Unix socket 与自己创建的 socket 文件路径（由 systemd 传入时为 None，退出时不删除）
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, Option<PathBuf>),
+}
+
+/// 退出时删除自己创建的 socket 文件
+pub fn remove_socket_file(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        tracing::warn!("删除 socket 文件失败: {}: {}", path.display(), e);
+    }
+}
+
+/// 取得 systemd socket activation 传入的第一个套接字（TCP 或 Unix socket）
+#[cfg(unix)]
+fn systemd_listener() -> anyhow::Result<Listener> {
+    use std::os::fd::{FromRawFd, IntoRawFd};
+
+    let pid =
+        std::env::var("LISTEN_PID").context("未设置 LISTEN_PID，进程不是由 systemd socket 启动")?;
+    if pid.trim() != std::process::id().to_string() {
+        anyhow::bail!("LISTEN_PID ({}) 与当前进程不一致", pid);
+    }
+    let fds: u32 = std::env::var("LISTEN_FDS")
+        .context("未设置 LISTEN_FDS")?
+        .trim()
+        .parse()
+        .context("LISTEN_FDS 不是有效数字")?;
+    if fds == 0 {
+        anyhow::bail!("systemd 没有传入监听套接字");
+    }
+    if fds > 1 {
+        tracing::warn!("systemd 传入了 {} 个套接字，仅使用第一个", fds);
+    }
+
+    // SAFETY: LISTEN_PID 校验通过，该描述符由 systemd 传给本进程且只在这里取得所有权
+    let tcp = unsafe { std::net::TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
+    if tcp.local_addr().is_ok() {
+        tcp.set_nonblocking(true)?;
+        return Ok(Listener::Tcp(tokio::net::TcpListener::from_std(tcp)?));
+    }
+
+    // 不是 TCP 套接字，按 Unix socket 处理
+    // SAFETY: 描述符所有权从上面的 TcpListener 转移而来
+    let unix = unsafe { std::os::unix::net::UnixListener::from_raw_fd(tcp.into_raw_fd()) };
+    unix.local_addr()
+        .context("systemd 传入的套接字既不是 TCP 也不是 Unix socket")?;
+    unix.set_nonblocking(true)?;
+    Ok(Listener::Unix(
+        tokio::net::UnixListener::from_std(unix)?,
+        None,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listen() {
+        let mut config = Config::default();
+        assert_eq!(
+            ListenAddr::from_config(&config).unwrap(),
+            ListenAddr::Tcp(format!("{}:{}", config.host, config.port))
+        );
+
+        config.listen = Some("unix:/run/kiro-rs.sock".to_string());
+        assert_eq!(
+            ListenAddr::from_config(&config).unwrap(),
+            ListenAddr::Unix(PathBuf::from("/run/kiro-rs.sock"))
+        );
+
+        config.listen = Some("systemd".to_string());
+        assert_eq!(
+            ListenAddr::from_config(&config).unwrap(),
+            ListenAddr::Systemd
+        );
+
+        config.listen = Some("0.0.0.0:9000".to_string());
+        assert_eq!(
+            ListenAddr::from_config(&config).unwrap(),
+            ListenAddr::Tcp("0.0.0.0:9000".to_string())
+        );
+
+        assert!(ListenAddr::parse("unix:").is_err());
+        assert!(ListenAddr::parse("localhost").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bind_unix_replaces_stale_socket() {
+        let path = std::env::temp_dir().join(format!("kiro-rs-test-{}.sock", std::process::id()));
+        let addr = ListenAddr::Unix(path.clone());
+
+        let first = addr.bind().await.unwrap();
+        drop(first);
+        // 旧 socket 文件仍在，重新绑定时应自动清理
+        let Listener::Unix(_, Some(bound)) = addr.bind().await.unwrap() else {
+            panic!("expected unix listener");
+        };
+        remove_socket_file(&bound);
+        assert!(!path.exists());
+    }
+}
@@ -1,34 +1,100 @@
-mod admin;
-mod admin_ui;
-mod anthropic;
-mod cloud_pass;
-mod common;
-mod http_client;
-mod kiro;
-mod model;
-pub mod token;
-
 use std::sync::Arc;
 
+use axum::extract::connect_info::Connected;
+use axum::serve::IncomingStream;
+use axum_server::tls_rustls::RustlsConfig;
 use clap::Parser;
-use kiro::model::credentials::{CredentialsConfig, KiroCredentials};
-use kiro::provider::KiroProvider;
-use kiro::token_manager::MultiTokenManager;
-use model::arg::Args;
-use model::config::Config;
+use kiro_rs::anthropic::replay::{self, ReplayOptions};
+use kiro_rs::common::ip_filter::ClientAddr;
+use kiro_rs::common::logging;
+use kiro_rs::kiro::{
+    self,
+    mock_upstream::MockUpstreamOptions,
+    model::credentials::{CredentialsConfig, KiroCredentials},
+    token_manager::MultiTokenManager,
+};
+use kiro_rs::listen::{self, ListenAddr, Listener};
+use kiro_rs::model::{
+    arg::{Args, Command},
+    config::Config,
+};
+use kiro_rs::server_tls::{ClientCertAcceptor, TlsPaths};
+use kiro_rs::{KiroApp, ShutdownHandle};
+use tokio::sync::Notify;
 
 #[tokio::main]
 async fn main() {
     // 解析命令行参数
     let args = Args::parse();
 
-    // 初始化日志
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
-        .init();
+    // 初始化日志（同时保留最近日志，供诊断包使用）
+    let logging = logging::init();
+
+    if args.dev {
+        kiro_rs::dev::enable(args.dev_max_chars);
+        tracing::info!("开发模式已启用：上游请求体与事件流将输出到 stderr");
+    }
+
+    // 生成 Machine ID（不依赖配置与凭证）
+    if let Some(Command::MachineId { salt, from, raw }) = &args.command {
+        run_machine_id(salt.as_deref(), from.as_deref(), *raw);
+        return;
+    }
+
+    // 回放捕获的事件流（不依赖配置与凭证）
+    if let Some(Command::Replay {
+        path,
+        model,
+        thinking,
+        chunk_size,
+        lenient_crc,
+    }) = &args.command
+    {
+        let options = ReplayOptions {
+            model: model.clone(),
+            thinking: *thinking,
+            chunk_size: *chunk_size,
+            lenient_crc: *lenient_crc,
+        };
+        run_replay(path, options);
+        return;
+    }
+
+    // 模拟上游（不依赖配置与凭证）
+    if let Some(Command::MockUpstream {
+        listen,
+        chunks,
+        chunk_chars,
+        first_byte_delay_ms,
+        interval_ms,
+    }) = &args.command
+    {
+        let options = MockUpstreamOptions {
+            chunks: *chunks,
+            chunk_chars: *chunk_chars,
+            first_byte_delay: std::time::Duration::from_millis(*first_byte_delay_ms),
+            interval: std::time::Duration::from_millis(*interval_ms),
+        };
+        if let Err(e) = kiro::mock_upstream::serve(*listen, options).await {
+            tracing::error!("模拟上游运行失败: {:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // 部署自检（配置或凭证加载失败时同样输出报告）
+    if let Some(Command::Doctor { json }) = &args.command {
+        let config_path = args
+            .config
+            .clone()
+            .unwrap_or_else(|| Config::default_config_path().to_string());
+        let credentials_path = args
+            .credentials
+            .clone()
+            .unwrap_or_else(|| KiroCredentials::default_credentials_path().to_string());
+        run_doctor(&config_path, &credentials_path, *json).await;
+        return;
+    }
 
     // 加载配置
     let config_path = args
@@ -38,6 +104,7 @@ async fn main() {
         tracing::error!("加载配置失败: {}", e);
         std::process::exit(1);
     });
+    logging.set_format(config.log_format);
 
     // 加载凭证（支持单对象或数组格式）
     let credentials_path = args
@@ -59,20 +126,8 @@ async fn main() {
     let first_credentials = credentials_list.first().cloned().unwrap_or_default();
     tracing::debug!("主凭证: {:?}", first_credentials);
 
-    // 获取 API Key
-    let api_key = config.api_key.clone().unwrap_or_else(|| {
-        tracing::error!("配置文件中未设置 apiKey");
-        std::process::exit(1);
-    });
-
     // 构建代理配置
-    let proxy_config = config.proxy_url.as_ref().map(|url| {
-        let mut proxy = http_client::ProxyConfig::new(url);
-        if let (Some(username), Some(password)) = (&config.proxy_username, &config.proxy_password) {
-            proxy = proxy.with_auth(username, password);
-        }
-        proxy
-    });
+    let proxy_config = config.proxy_config();
 
     if proxy_config.is_some() {
         tracing::info!("已配置 HTTP 代理: {}", config.proxy_url.as_ref().unwrap());
@@ -91,82 +146,76 @@ async fn main() {
         std::process::exit(1);
     });
     let token_manager = Arc::new(token_manager);
-    let kiro_provider = KiroProvider::with_proxy(token_manager.clone(), proxy_config.clone());
-
-    // 初始化 count_tokens 配置
-    token::init_config(token::CountTokensConfig {
-        api_url: config.count_tokens_api_url.clone(),
-        api_key: config.count_tokens_api_key.clone(),
-        auth_type: config.count_tokens_auth_type.clone(),
-        proxy: proxy_config,
-        tls_backend: config.tls_backend,
-    });
-
-    // 创建 Cloud Pass 共享状态
-    let cloud_pass_state = if let Some(ref cp_config) = config.cloud_pass {
-        // 先创建临时 client 获取 device_id
-        let temp_client = cloud_pass::client::CloudPassClient::new(cp_config);
-        let device_id = temp_client.device_id().to_string();
-        Some(cloud_pass::state::CloudPassState::from_config(
-            &cp_config.server_url,
-            &device_id,
-            &cp_config.license_code,
-            cp_config.refresh_interval,
-            cp_config.reassign,
-            &cp_config.client_version,
-        ))
-    } else {
-        None
-    };
-
-    // 构建 Anthropic API 路由（从第一个凭据获取 profile_arn）
-    let anthropic_app = anthropic::create_router_with_provider(
-        &api_key,
-        Some(kiro_provider),
-        first_credentials.profile_arn.clone(),
-    );
 
-    // 构建 Admin API 路由（如果配置了非空的 admin_api_key）
-    // 安全检查：空字符串被视为未配置，防止空 key 绕过认证
-    let admin_key_valid = config
-        .admin_api_key
-        .as_ref()
-        .map(|k| !k.trim().is_empty())
-        .unwrap_or(false);
-
-    let app = if let Some(admin_key) = &config.admin_api_key {
-        if admin_key.trim().is_empty() {
-            tracing::warn!("admin_api_key 配置为空，Admin API 未启用");
-            anthropic_app
-        } else {
-            let admin_service = admin::AdminService::new(token_manager.clone());
-            let mut admin_state = admin::AdminState::new(admin_key, admin_service);
-            if let Some(ref cp_state) = cloud_pass_state {
-                admin_state = admin_state.with_cloud_pass(cp_state.clone());
-            }
-            let admin_app = admin::create_admin_router(admin_state);
+    // 执行子命令（不启动服务）
+    if let Some(Command::Import {
+        file,
+        map,
+        format,
+        dry_run,
+    }) = args.command
+    {
+        if !is_multiple_format && !dry_run {
+            tracing::error!("导入需要多凭据格式（数组）的凭证文件，当前为单凭据格式");
+            std::process::exit(1);
+        }
+        run_import(&token_manager, &file, map.as_deref(), format.as_deref(), dry_run).await;
+        return;
+    }
+    if let Some(Command::Login { start_url, region }) = args.command {
+        if !is_multiple_format {
+            tracing::error!("登录需要多凭据格式（数组）的凭证文件，当前为单凭据格式");
+            std::process::exit(1);
+        }
+        run_login(&token_manager, region, start_url).await;
+        return;
+    }
+    if let Some(Command::Dedupe { dry_run }) = args.command {
+        if !is_multiple_format && !dry_run {
+            tracing::error!("合并重复凭据需要多凭据格式（数组）的凭证文件，当前为单凭据格式");
+            std::process::exit(1);
+        }
+        run_dedupe(&token_manager, dry_run);
+        return;
+    }
 
-            // 创建 Admin UI 路由
-            let admin_ui_app = admin_ui::create_admin_ui_router();
+    // 组装应用（Anthropic API、Admin API 与 Admin UI）
+    let mut app = KiroApp::new(config.clone(), token_manager.clone()).unwrap_or_else(|e| {
+        tracing::error!("{:#}", e);
+        std::process::exit(1);
+    });
+    // 从第一个凭据获取 profile_arn
+    if let Some(arn) = first_credentials.profile_arn.clone() {
+        app = app.with_profile_arn(arn);
+    }
+    let admin_key_valid = app.admin_enabled();
 
-            tracing::info!("Admin API 已启用");
-            tracing::info!("Admin UI 已启用: /admin");
-            anthropic_app
-                .nest("/api/admin", admin_app)
-                .nest("/admin", admin_ui_app)
-        }
-    } else {
-        anthropic_app
-    };
+    // 加载 HTTPS 证书（如果配置了）
+    let tls = TlsPaths::from_config(&config)
+        .and_then(|paths| paths.map(|p| p.load().map(|c| (p, c))).transpose())
+        .unwrap_or_else(|e| {
+            tracing::error!("加载 HTTPS 证书失败: {:#}", e);
+            std::process::exit(1);
+        });
 
     // 启动服务器
-    let addr = format!("{}:{}", config.host, config.port);
-    tracing::info!("启动 Anthropic API 端点: {}", addr);
+    let addr = ListenAddr::from_config(&config).unwrap_or_else(|e| {
+        tracing::error!("{:#}", e);
+        std::process::exit(1);
+    });
+    let api_key = config.api_key.as_deref().unwrap_or_default();
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    tracing::info!("启动 Anthropic API 端点: {}://{}", scheme, addr);
     tracing::info!("API Key: {}***", &api_key[..(api_key.len() / 2)]);
     tracing::info!("可用 API:");
     tracing::info!("  GET  /v1/models");
     tracing::info!("  POST /v1/messages");
     tracing::info!("  POST /v1/messages/count_tokens");
+    tracing::info!("  POST /v1/chat/completions");
+    tracing::info!("  POST /v1/completions");
+    tracing::info!("  POST /v1/embeddings");
+    tracing::info!("  POST /v1beta/models/:model:generateContent");
+    tracing::info!("  POST /v1beta/models/:model:streamGenerateContent");
     if admin_key_valid {
         tracing::info!("Admin API:");
         tracing::info!("  GET  /api/admin/credentials");
@@ -176,22 +225,318 @@ async fn main() {
         tracing::info!("  GET  /api/admin/credentials/:index/balance");
         tracing::info!("Admin UI:");
         tracing::info!("  GET  /admin");
-        if cloud_pass_state.is_some() {
+        if config.cloud_pass.is_some() {
             tracing::info!("Cloud Pass API:");
             tracing::info!("  GET  /api/admin/cloud-pass/status");
         }
     }
 
-    // 启动 Cloud Pass 后台刷新任务（如果配置了）
-    if let Some(cloud_pass_config) = config.cloud_pass.clone() {
-        tracing::info!("Cloud Pass 已配置，启动后台凭证刷新任务");
-        let tm = token_manager.clone();
-        let cp_state = cloud_pass_state.clone().unwrap();
-        tokio::spawn(async move {
-            cloud_pass::worker::start_cloud_pass_worker(tm, cloud_pass_config, cp_state).await;
+    // 在监督下启动后台任务
+    app.spawn_background_workers();
+    let shutdown = app.shutdown_handle();
+    let app = app.into_router();
+
+    let listener = addr.bind().await.unwrap_or_else(|e| {
+        tracing::error!("{:#}", e);
+        std::process::exit(1);
+    });
+    match (listener, tls) {
+        (Listener::Tcp(listener), Some((paths, tls_config))) => {
+            serve_https(listener, app, paths, tls_config, &shutdown).await
+        }
+        (Listener::Tcp(listener), None) => serve_http(listener, app, &shutdown).await,
+        #[cfg(unix)]
+        (Listener::Unix(..), Some(_)) => {
+            tracing::error!("HTTPS 仅支持 TCP 监听，Unix socket 请由反向代理终止 TLS");
+            std::process::exit(1);
+        }
+        #[cfg(unix)]
+        (Listener::Unix(listener, socket_file), None) => {
+            serve_http(listener, app, &shutdown).await;
+            if let Some(path) = socket_file {
+                listen::remove_socket_file(&path);
+            }
+        }
+    }
+
+    shutdown.shutdown();
+    tracing::info!("服务已关闭");
+}
+
+/// 以 HTTP 监听
+///
+/// 收到 SIGTERM / SIGINT 后停止接受新连接，等待进行中的请求（含流式响应）完成，
+/// 超过 shutdownTimeoutSecs 仍未完成时强制关闭
+async fn serve_http<L>(listener: L, app: axum::Router, shutdown: &ShutdownHandle)
+where
+    L: axum::serve::Listener,
+    L::Addr: std::fmt::Debug,
+    for<'a> ClientAddr: Connected<IncomingStream<'a, L>>,
+{
+    let draining = Arc::new(Notify::new());
+    let app = app.into_make_service_with_connect_info::<ClientAddr>();
+    let server = axum::serve(listener, app).with_graceful_shutdown({
+        let draining = draining.clone();
+        let timeout = shutdown.drain_timeout();
+        async move {
+            shutdown_signal().await;
+            tracing::info!(
+                "收到关闭信号，停止接受新连接，等待进行中的请求完成（最长 {} 秒）",
+                timeout.as_secs()
+            );
+            draining.notify_one();
+        }
+    });
+    let deadline = async {
+        draining.notified().await;
+        tokio::time::sleep(shutdown.drain_timeout()).await;
+    };
+    tokio::select! {
+        result = server => result.unwrap(),
+        _ = deadline => tracing::warn!("等待进行中的请求超时，强制关闭"),
+    }
+}
+
+/// 以 HTTPS 监听（证书文件变化后自动重新加载），关闭流程与 HTTP 相同
+async fn serve_https(
+    listener: tokio::net::TcpListener,
+    app: axum::Router,
+    paths: TlsPaths,
+    tls_config: RustlsConfig,
+    shutdown: &ShutdownHandle,
+) {
+    tokio::spawn(paths.clone().watch(tls_config.clone()));
+
+    let handle = axum_server::Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            let timeout = shutdown.drain_timeout();
+            tracing::info!(
+                "收到关闭信号，停止接受新连接，等待进行中的请求完成（最长 {} 秒）",
+                timeout.as_secs()
+            );
+            handle.graceful_shutdown(Some(timeout));
+        }
+    });
+
+    let listener = listener.into_std().unwrap();
+    let result = if paths.client_ca.is_some() {
+        axum_server::from_tcp(listener)
+            .acceptor(ClientCertAcceptor::new(tls_config))
+            .handle(handle)
+            .serve(app.into_make_service_with_connect_info::<ClientAddr>())
+            .await
+    } else {
+        axum_server::from_tcp_rustls(listener, tls_config)
+            .handle(handle)
+            .serve(app.into_make_service_with_connect_info::<ClientAddr>())
+            .await
+    };
+    result.unwrap();
+}
+
+/// 等待关闭信号（Ctrl+C / SIGINT，Unix 下还包括 SIGTERM）
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("监听 Ctrl+C 信号失败: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("监听 SIGTERM 信号失败: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// 执行 `dedupe` 子命令：检测并合并重复凭据，打印分组结果
+fn run_dedupe(token_manager: &MultiTokenManager, dry_run: bool) {
+    let groups = token_manager.merge_duplicates(dry_run).unwrap_or_else(|e| {
+        tracing::error!("合并重复凭据失败: {}", e);
+        std::process::exit(1);
+    });
+    if groups.is_empty() {
+        println!("未发现重复凭据");
+        return;
+    }
+
+    for group in &groups {
+        println!(
+            "保留 #{}，{} {:?}（{}）",
+            group.keep,
+            if dry_run { "将删除" } else { "已删除" },
+            group.remove,
+            group.reasons.join(", ")
+        );
+    }
+    let removed: usize = groups.iter().map(|g| g.remove.len()).sum();
+    println!(
+        "{}：共 {} 组重复凭据，{}删除 {} 个凭据",
+        if dry_run { "检测完成" } else { "合并完成" },
+        groups.len(),
+        if dry_run { "将" } else { "已" },
+        removed
+    );
+}
+
+/// 执行 `import` 子命令：从 CSV / TSV 文件批量导入凭据并打印逐行结果
+/// 执行 `login` 子命令：设备授权登录并把新凭据写入凭证文件
+async fn run_login(
+    token_manager: &MultiTokenManager,
+    region: Option<String>,
+    start_url: Option<String>,
+) {
+    use kiro::device_login::DeviceLogin;
+
+    let config = token_manager.config();
+    let login = DeviceLogin::new(&config, token_manager.proxy().as_ref(), region, start_url)
+        .unwrap_or_else(|e| {
+            tracing::error!("{:#}", e);
+            std::process::exit(1);
         });
+    let pending = login.start().await.unwrap_or_else(|e| {
+        tracing::error!("{:#}", e);
+        std::process::exit(1);
+    });
+    println!("请在浏览器中打开以下地址完成授权：");
+    println!("  {}", pending.verification_url());
+    println!("用户码: {}", pending.user_code());
+    println!("等待授权...");
+
+    let credentials = login.wait(pending).await.unwrap_or_else(|e| {
+        tracing::error!("登录失败: {:#}", e);
+        std::process::exit(1);
+    });
+    match token_manager.add_credential(credentials).await {
+        Ok(id) => println!("登录成功，已添加凭据 #{}", id),
+        Err(e) => {
+            tracing::error!("添加凭据失败: {:#}", e);
+            std::process::exit(1);
+        }
     }
+}
+
+/// 执行 `doctor` 子命令：打印自检报告，有失败项时以非零状态退出
+async fn run_doctor(config_path: &str, credentials_path: &str, json: bool) {
+    let report = kiro_rs::doctor::run(config_path, credentials_path).await;
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("序列化自检报告失败")
+        );
+    } else {
+        print!("{}", report.render());
+    }
+    if report.has_failures() {
+        std::process::exit(1);
+    }
+}
+
+/// 执行 machine-id 子命令
+fn run_machine_id(salt: Option<&str>, from: Option<&str>, raw: bool) {
+    use kiro::machine_id;
 
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let raw_id = match from {
+        Some(id) => id.to_string(),
+        None => machine_id::read_system_id().unwrap_or_else(|e| {
+            tracing::error!("读取设备标识失败: {}", e);
+            std::process::exit(1);
+        }),
+    };
+    if raw {
+        println!("原始标识: {}", raw_id.trim());
+    }
+    println!("{}", machine_id::derive_machine_id(&raw_id, salt));
+}
+
+fn run_replay(path: &str, options: ReplayOptions) {
+    let mut stdout = std::io::stdout().lock();
+    match replay::replay_path(std::path::Path::new(path), options, &mut stdout) {
+        // 存在解码错误时以非零状态退出，便于脚本化回归检查
+        Ok(summary) if summary.errors > 0 => std::process::exit(2),
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!("回放失败: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run_import(
+    token_manager: &MultiTokenManager,
+    file: &str,
+    map: Option<&str>,
+    format: Option<&str>,
+    dry_run: bool,
+) {
+    use kiro::credential_import::{self, ColumnMapping, ImportFormat, ImportRowStatus};
+
+    let content = std::fs::read_to_string(file).unwrap_or_else(|e| {
+        tracing::error!("读取导入文件失败: {}", e);
+        std::process::exit(1);
+    });
+    let format = match format {
+        Some("tsv") => ImportFormat::Tsv,
+        Some(_) => ImportFormat::Csv,
+        None => ImportFormat::from_path(file, &content),
+    };
+    let mapping = map
+        .map(ColumnMapping::parse)
+        .transpose()
+        .unwrap_or_else(|e| {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        })
+        .unwrap_or_default();
+    let rows = credential_import::parse_table(&content, format, &mapping).unwrap_or_else(|e| {
+        tracing::error!("解析导入文件失败: {}", e);
+        std::process::exit(1);
+    });
+
+    let report = credential_import::import_credentials(token_manager, rows, dry_run).await;
+    for row in &report.rows {
+        match row.status {
+            ImportRowStatus::Valid => println!("第 {} 行: 校验通过", row.line),
+            ImportRowStatus::Imported => println!(
+                "第 {} 行: 已导入，凭据 ID {}",
+                row.line,
+                row.credential_id.unwrap_or_default()
+            ),
+            ImportRowStatus::Failed => println!(
+                "第 {} 行: 失败 - {}",
+                row.line,
+                row.message.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+    println!(
+        "{}完成：共 {} 行，成功 {} 行，失败 {} 行",
+        if dry_run { "校验" } else { "导入" },
+        report.total,
+        report.succeeded,
+        report.failed
+    );
+
+    if report.failed > 0 {
+        std::process::exit(1);
+    }
 }
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 /// Anthropic <-> Kiro API 客户端
 #[derive(Parser, Debug)]
@@ -11,4 +11,123 @@ pub struct Args {
     /// 凭证文件路径
     #[arg(long)]
     pub credentials: Option<String>,
+
+    /// 开发模式：在控制台格式化打印转换后的上游请求体与解码出的事件流
+    #[arg(long)]
+    pub dev: bool,
+
+    /// 开发模式下字符串值的最大显示字符数（超出部分截断）
+    #[arg(long, default_value_t = 2000)]
+    pub dev_max_chars: usize,
+
+    /// 子命令（未指定时启动 API 服务）
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// 命令行子命令
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// 从 CSV / TSV 文件批量导入凭据到凭证文件
+    Import {
+        /// 表格文件路径（首行为表头）
+        file: String,
+
+        /// 列映射，格式：字段=列名，多个用逗号分隔（如 refreshToken=rt,email=mail）
+        #[arg(short, long)]
+        map: Option<String>,
+
+        /// 表格格式：csv 或 tsv（默认按扩展名和表头自动识别）
+        #[arg(long, value_parser = ["csv", "tsv"])]
+        format: Option<String>,
+
+        /// 仅校验不导入
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 检测并合并重复凭据（refreshToken 或账号邮箱相同）
+    Dedupe {
+        /// 仅检测不合并
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 通过 IdC 设备授权登录（默认 AWS Builder ID），把得到的凭据写入凭证文件
+    Login {
+        /// IAM Identity Center 的 start URL（未指定时使用 AWS Builder ID）
+        #[arg(long)]
+        start_url: Option<String>,
+
+        /// IdC 所在区域（默认取配置的 authRegion / region）
+        #[arg(long)]
+        region: Option<String>,
+    },
+
+    /// 部署自检：检查配置、凭证文件、网络与代理、每个凭据的 Token 刷新与上游连通性、Cloud Pass 服务器
+    Doctor {
+        /// 以 JSON 输出报告
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// 回放捕获的上游事件流：打印解码出的帧与转换得到的 SSE
+    Replay {
+        /// 请求捕获目录，或原始 AWS Event Stream 文件
+        path: String,
+
+        /// 模型名（默认取自捕获目录中的 meta.json）
+        #[arg(long)]
+        model: Option<String>,
+
+        /// 是否按启用 thinking 转换（默认取自捕获目录中的 meta.json）
+        #[arg(long)]
+        thinking: Option<bool>,
+
+        /// 按固定字节数分块送入解码器，模拟上游分包（默认一次性送入）
+        #[arg(long, default_value_t = 0)]
+        chunk_size: usize,
+
+        /// 宽松 CRC 模式：校验失败的帧照常处理
+        #[arg(long)]
+        lenient_crc: bool,
+    },
+
+    /// 启动模拟上游：以合成的事件流应答请求，配合 upstreamBaseUrl 压测代理而不消耗额度
+    MockUpstream {
+        /// 监听地址
+        #[arg(long, default_value = "127.0.0.1:9090")]
+        listen: std::net::SocketAddr,
+
+        /// 每个响应的文本帧数
+        #[arg(long, default_value_t = 20)]
+        chunks: usize,
+
+        /// 每个文本帧的字符数
+        #[arg(long, default_value_t = 16)]
+        chunk_chars: usize,
+
+        /// 首帧前的延迟（毫秒）
+        #[arg(long, default_value_t = 0)]
+        first_byte_delay_ms: u64,
+
+        /// 相邻帧之间的间隔（毫秒）
+        #[arg(long, default_value_t = 0)]
+        interval_ms: u64,
+    },
+
+    /// 按官方客户端算法生成当前设备的 Machine ID
+    MachineId {
+        /// 派生时附加的 salt（哈希输入为 salt/原始标识；本项目扩展，结果与官方客户端不同）
+        #[arg(long)]
+        salt: Option<String>,
+
+        /// 使用指定的设备原始标识（如从其他设备复制的 MachineGuid / IOPlatformUUID）
+        #[arg(long)]
+        from: Option<String>,
+
+        /// 同时输出设备原始标识
+        #[arg(long)]
+        raw: bool,
+    },
 }
@@ -1,9 +1,22 @@
 use anyhow::Context;
+use chrono::{FixedOffset, NaiveTime, Offset, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+use crate::common::time::parse_utc_offset;
+use crate::http_client::ProxyConfig;
+use crate::notify::EventKind;
+
+/// 配置 include 最大嵌套深度
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// 覆盖配置项的环境变量前缀（`KIRO_PROXY_URL` 对应 `proxyUrl`）
+const ENV_PREFIX: &str = "KIRO_";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "kebab-case")]
 pub enum TlsBackend {
     Rustls,
@@ -16,16 +29,102 @@ impl Default for TlsBackend {
     }
 }
 
+/// 终端日志格式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// 人类可读的文本
+    #[default]
+    Text,
+    /// 每行一个 JSON 对象（便于 Loki / ELK 采集）
+    Json,
+}
+
+/// Admin Key 角色
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AdminRole {
+    /// 只读：只能调用 GET 端点
+    Viewer,
+    /// 完整管理权限
+    #[default]
+    Admin,
+}
+
+/// 带角色的 Admin Key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminKeyConfig {
+    /// 名称（唯一，用于识别与删除）
+    pub name: String,
+    pub key: String,
+    #[serde(default)]
+    pub role: AdminRole,
+}
+
+/// 本地 token 计数方式（未配置外部 count_tokens API 或其调用失败时使用）
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Tokenizer {
+    /// 按字符类别估算
+    #[default]
+    Estimate,
+    /// 内置的近似 BPE 分词（按单词、数字、标点、空白切分后逐段计数）
+    Bpe,
+}
+
+/// 模型推理（thinking）内容的输出方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThinkingOutput {
+    /// 作为 thinking 内容块输出（OpenAI 兼容端点为 `reasoning_content`）
+    #[default]
+    Passthrough,
+    /// 丢弃推理内容
+    Strip,
+    /// 以 `<thinking>` 标签包裹后并入正文
+    Tags,
+}
+
 /// KNA 应用配置
+///
+/// 支持通过 `include` 合并其他配置片段（如不纳入版本控制的密钥文件、团队共享默认值），
+/// 优先级从低到高：先列出的 include < 后列出的 include < 当前文件本身。
+/// 对象字段深度合并，其余类型整体覆盖；相对路径相对于声明 include 的文件所在目录
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
+    /// 需要合并的配置片段路径列表
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
     #[serde(default = "default_host")]
     pub host: String,
 
     #[serde(default = "default_port")]
     pub port: u16,
 
+    /// 监听地址（`host:port`、`unix:/path/to.sock` 或 `systemd`；未配置时使用 host 与 port）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<String>,
+
+    /// HTTPS 证书路径（PEM，可含证书链，需与 tlsKeyPath 同时配置；未配置时以 HTTP 监听）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_cert_path: Option<String>,
+
+    /// HTTPS 私钥路径（PEM）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_key_path: Option<String>,
+
+    /// 客户端证书 CA（PEM，可含多个证书）；配置后启用 mTLS，没有有效客户端证书的连接在握手阶段被拒绝
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_client_ca_path: Option<String>,
+
     #[serde(default = "default_region")]
     pub region: String,
 
@@ -39,6 +138,11 @@ pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub api_region: Option<String>,
 
+    /// 上游 API 地址（替代 `https://q.{apiRegion}.amazonaws.com`，如压测时指向 mock-upstream）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_base_url: Option<String>,
+
     #[serde(default = "default_kiro_version")]
     pub kiro_version: String,
 
@@ -57,6 +161,10 @@ pub struct Config {
     #[serde(default = "default_tls_backend")]
     pub tls_backend: TlsBackend,
 
+    /// 终端日志格式（默认 text）
+    #[serde(default)]
+    pub log_format: LogFormat,
+
     /// 外部 count_tokens API 地址（可选）
     #[serde(default)]
     pub count_tokens_api_url: Option<String>,
@@ -69,6 +177,18 @@ pub struct Config {
     #[serde(default = "default_count_tokens_auth_type")]
     pub count_tokens_auth_type: String,
 
+    /// 本地 token 计数方式（"estimate" 或 "bpe"，默认 "estimate"）
+    #[serde(default)]
+    pub tokenizer: Tokenizer,
+
+    /// 外部 embeddings API 地址（可选，OpenAI 兼容，`/v1/embeddings` 原样转发到此地址）
+    #[serde(default)]
+    pub embeddings_api_url: Option<String>,
+
+    /// embeddings API 密钥（可选，以 `Authorization: Bearer` 发送）
+    #[serde(default)]
+    pub embeddings_api_key: Option<String>,
+
     /// HTTP 代理地址（可选）
     /// 支持格式: http://host:port, https://host:port, socks5://host:port
     #[serde(default)]
@@ -82,22 +202,336 @@ pub struct Config {
     #[serde(default)]
     pub proxy_password: Option<String>,
 
-    /// Admin API 密钥（可选，启用 Admin API 功能）
+    /// Admin API 密钥（可选，启用 Admin API 功能，拥有完整管理权限）
     #[serde(default)]
     pub admin_api_key: Option<String>,
 
+    /// 额外的 Admin Key（各自带角色，可通过 Admin API 增删，修改后立即生效）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub admin_keys: Vec<AdminKeyConfig>,
+
+    /// Admin UI 登录会话有效期（秒，默认 43200 即 12 小时）
+    #[serde(default = "default_admin_session_ttl_secs")]
+    pub admin_session_ttl_secs: u64,
+
     /// 负载均衡模式（"priority" 或 "balanced"）
     #[serde(default = "default_load_balancing_mode")]
     pub load_balancing_mode: String,
 
+    /// 单次请求最大尝试次数上限（默认 9）
+    /// 客户端通过 `x-kiro-max-retries` 请求头覆盖时也不会超过此值
+    #[serde(default = "default_max_retries_ceiling")]
+    pub max_retries_ceiling: usize,
+
+    /// 失败自动换凭据重试（可选）：上游返回 401/403/408/429/5xx 时改用下一个可用凭据重发，
+    /// 未配置时 408/429/5xx 仍在原凭据上重试
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_failover: Option<RetryFailover>,
+
+    /// 流中断续传：上游事件流在响应中途断开时换凭据重放对话并从断点继续，
+    /// 下游仍收到一条完整的 SSE 响应（已开始工具调用的响应除外）
+    #[serde(default)]
+    pub stream_resume: bool,
+
+    /// 单个凭据的默认速率限制（每分钟请求数，可选，0 或未配置表示不限制）
+    /// 可通过 Admin API 为单个凭据覆盖
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_rpm: Option<u32>,
+
+    /// 单个凭据的默认最大并发请求数（可选，0 或未配置表示不限制，凭据级 maxConcurrency 覆盖）
+    /// 并发已满的请求按到达顺序排队，队列已满或等待超时返回 503
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_max_concurrency: Option<u32>,
+
+    /// 每个凭据的并发等待队列长度（默认 32）
+    #[serde(default = "default_concurrency_queue_size")]
+    pub concurrency_queue_size: usize,
+
+    /// 并发排队的最长等待时间（秒，默认 30）
+    #[serde(default = "default_concurrency_queue_timeout_secs")]
+    pub concurrency_queue_timeout_secs: u64,
+
+    /// 单个凭据默认的滚动窗口用量上限（可选，凭据级 usageCap 整体覆盖）
+    /// 达到上限的凭据在窗口滑动前不参与负载均衡，用于均摊各账号用量
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_usage_cap: Option<UsageCap>,
+
+    /// 单个凭据默认的用量警戒线（可选，凭据级 usageWatermark 整体覆盖）
+    /// 查询余额时用量百分比达到警戒线即发出通知，并可降低优先级或禁用以保留剩余额度
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_watermark: Option<UsageWatermark>,
+
+    /// 配额感知准入（可选）：转发前按预计 tokens 检查凭据剩余额度，
+    /// 不足时改用额度充足的凭据或直接拒绝，避免大请求耗尽额度后仍然失败
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_admission: Option<QuotaAdmission>,
+
+    /// 是否启用粘性会话（同一客户端固定使用同一凭据，凭据不可用时自动故障转移）
+    #[serde(default)]
+    pub sticky_session: bool,
+
+    /// 粘性会话客户端标识请求头（可选）
+    /// 未配置或请求中缺失时使用客户端的 API Key 作为标识
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sticky_session_header: Option<String>,
+
+    /// 模型别名映射（请求模型名 → 实际模型名，名称不区分大小写）
+    /// 在选择凭据与转发上游前应用，使硬编码模型名的下游客户端无需修改
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub model_aliases: BTreeMap<String, String>,
+
+    /// 是否下载 URL 形式的图片（`source.type = "url"` 或 http(s) `image_url`）后转发上游（默认关闭）
+    /// 关闭时此类图片直接返回 400；开启后仅访问公网地址，避免服务端代替客户端访问内网
+    #[serde(default)]
+    pub image_url_fetch: bool,
+
+    /// 单张图片解码后的最大字节数（默认 5 MiB），超过时返回 400
+    #[serde(default = "default_max_image_bytes")]
+    pub max_image_bytes: u64,
+
+    /// 不支持图片输入的模型（按子串匹配、不区分大小写），请求含图片时直接返回 400
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub non_vision_models: Vec<String>,
+
+    /// 凭据优先级预设（名称 → 按标签分配的优先级）
+    /// 可按 schedule 每天定时切换，也可通过 Admin API 手动应用
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub priority_presets: BTreeMap<String, PriorityPreset>,
+
+    /// 连续失败被自动禁用的凭据在冷却后自动探测恢复的初始冷却时间（秒，默认 300，0 表示不启用）
+    /// 探测失败后冷却时间按指数退避翻倍
+    #[serde(default = "default_failure_cooldown_secs")]
+    pub failure_cooldown_secs: u64,
+
+    /// 自动恢复冷却时间上限（秒，默认 3600）
+    #[serde(default = "default_failure_cooldown_max_secs")]
+    pub failure_cooldown_max_secs: u64,
+
+    /// 凭据健康检查间隔（秒，0 表示不启用）
+    /// 启用后后台定期查询每个凭据的使用额度，失败的凭据会被标记为降级并暂停使用
+    #[serde(default)]
+    pub health_check_interval: u64,
+
+    /// 定时刷新所有凭据余额的间隔（秒，0 表示不启用，仅在查询时获取）
+    #[serde(default)]
+    pub balance_refresh_interval_secs: u64,
+
+    /// 降级凭据恢复所需的连续健康检查成功次数（默认 2）
+    #[serde(default = "default_health_check_recovery_threshold")]
+    pub health_check_recovery_threshold: u32,
+
+    /// 主动刷新 Token 的提前量（秒，0 表示不启用，仍在请求时按需刷新）
+    /// 启用后后台在 Token 过期前的该时间内刷新，避免请求因同步刷新产生延迟尖峰
+    #[serde(default)]
+    pub proactive_refresh_margin_secs: u64,
+
+    /// 闲置凭据夜间保活探测的执行时刻（UTC 小时，0-23，未配置表示不启用）
+    /// 向闲置凭据发送极小请求，保持 refreshToken 活跃并及早发现静默吊销
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exercise_hour_utc: Option<u8>,
+
+    /// 超过多少小时未使用的凭据视为闲置（默认 24）
+    #[serde(default = "default_exercise_idle_hours")]
+    pub exercise_idle_hours: u64,
+
+    /// 每次保活探测最多探测的凭据数量（成本上限，默认 10）
+    #[serde(default = "default_exercise_max_credentials")]
+    pub exercise_max_credentials: usize,
+
+    /// 运营者时区（固定 UTC 偏移，如 "+08:00"，默认 UTC）
+    /// 用于额度重置时间的本地展示和按日用量统计
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+
+    /// 无可用凭据时的降级策略（默认 "error"）
+    /// - "error": 立即返回 503 错误
+    /// - "wait": 排队等待凭据恢复，超过 degradationWaitSecs 后返回 503
+    /// - "fallback": 返回协议格式正确的兜底回复（内容为 degradationMessage）
+    #[serde(default = "default_degradation_mode")]
+    pub degradation_mode: String,
+
+    /// "wait" 降级策略下的最长等待时间（秒，默认 30）
+    #[serde(default = "default_degradation_wait_secs")]
+    pub degradation_wait_secs: u64,
+
+    /// "fallback" 降级策略下返回的兜底回复内容
+    #[serde(default = "default_degradation_message")]
+    pub degradation_message: String,
+
+    /// 流式响应中单个增量事件内容的最大字节数（默认 0，不拆分）
+    /// 超过时拆分为多个 SSE 事件，避免下游客户端或 Cloudflare 等代理丢弃超大帧
+    #[serde(default)]
+    pub sse_max_chunk_bytes: usize,
+
+    /// 模型推理内容的输出方式（"passthrough"、"strip" 或 "tags"，默认 "passthrough"）
+    #[serde(default)]
+    pub thinking_output: ThinkingOutput,
+
+    /// 是否按请求的 max_tokens 在本地截断输出（默认关闭）
+    /// 上游不支持 max_tokens，输出 tokens 按字符数估算（中文、代码可能偏高），开启后输出可能提前结束
+    #[serde(default)]
+    pub enforce_max_tokens: bool,
+
+    /// 对话请求体的最大字节数（默认 50 MB），超过时返回 413
+    #[serde(default = "default_max_request_bytes")]
+    pub max_request_bytes: usize,
+
+    /// 单个请求的最大消息数（可选，0 或未配置表示不限制），超过时返回 400
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_messages: Option<usize>,
+
+    /// 单个请求估算的最大输入 tokens（可选，0 或未配置表示不限制），超过时在调用上游前返回 400
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_input_tokens: Option<u64>,
+
+    /// 上游事件流解析失败时是否在错误与日志中附带流偏移和 hexdump（默认 false）
+    #[serde(default)]
+    pub decode_error_hexdump: bool,
+
+    /// 上游事件帧 CRC 校验失败时是否记录警告后照常处理（默认 false，校验失败的帧被丢弃）
+    #[serde(default)]
+    pub decode_crc_lenient: bool,
+
+    /// 单个上游响应解码缓冲区的最大字节数（默认 16 MB），帧迟迟不完整时超出部分被拒绝
+    #[serde(default = "default_decode_max_buffer_bytes")]
+    pub decode_max_buffer_bytes: usize,
+
+    /// 后台任务（Cloud Pass 刷新、健康检查等）1 小时内最多自动重启次数（默认 10，0 表示不自动重启）
+    #[serde(default = "default_worker_max_restarts_per_hour")]
+    pub worker_max_restarts_per_hour: u32,
+
+    /// 后台任务首次重启前的等待时间（秒，默认 1），之后每次翻倍
+    #[serde(default = "default_worker_restart_backoff_secs")]
+    pub worker_restart_backoff_secs: u64,
+
+    /// 后台任务重启等待时间上限（秒，默认 300）
+    #[serde(default = "default_worker_restart_backoff_max_secs")]
+    pub worker_restart_backoff_max_secs: u64,
+
+    /// 优雅关闭时等待进行中请求（含流式响应）完成的最长时间（秒，默认 30）
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+
+    /// 响应归档配置（可选，将完整响应以 NDJSON 写入本地目录，默认关闭）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive: Option<ArchiveConfig>,
+
+    /// 请求审计日志配置（可选，记录每个对话请求的元数据，默认关闭）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audit: Option<AuditConfig>,
+
+    /// 响应缓存配置（可选，相同的非流式请求在有效期内直接返回缓存响应，默认关闭）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_cache: Option<ResponseCacheConfig>,
+
+    /// 主 API 的客户端 IP 访问控制（可选，默认不限制）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_ip_filter: Option<IpFilterConfig>,
+
+    /// Admin API 与 Admin UI 的客户端 IP 访问控制（可选，默认不限制）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_ip_filter: Option<IpFilterConfig>,
+
+    /// 主 API 的认证失败锁定（可选，默认关闭）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_lockout: Option<AuthLockoutConfig>,
+
+    /// 凭据事件的 Webhook 通知（可选，默认不通知）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// 凭据事件的 Telegram Bot 通知（可选）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telegram: Option<TelegramConfig>,
+
+    /// 凭据事件的 Slack Incoming Webhook 通知（可选）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slack: Option<ChatWebhookConfig>,
+
+    /// 凭据事件的 Discord Webhook 通知（可选）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discord: Option<ChatWebhookConfig>,
+
+    /// 剩余额度低于该值时发出 `balance_low` 事件（可选，默认不检测）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub low_balance_threshold: Option<f64>,
+
+    /// Cloud Pass License 剩余天数不足该值时发出 `license_expiring` 事件（默认 3）
+    #[serde(default = "default_license_expiry_warn_days")]
+    pub license_expiry_warn_days: u64,
+
+    /// 启动时自动导入 Kiro IDE 的凭据（可选，默认关闭）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_discovery: Option<CredentialDiscoveryConfig>,
+
     /// Cloud Pass 配置（从 eskysoft 服务器自动获取凭证）
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cloud_pass: Option<CloudPassConfig>,
 
+    /// 多实例凭据同步：从源实例定时拉取凭据（可选，源实例无需配置）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_sync: Option<PeerSyncConfig>,
+
+    /// 主备模式选主：多个实例共享凭据存储时只由主节点运行刷新任务（可选）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leader_election: Option<LeaderElectionConfig>,
+
+    /// 多副本共享状态：经 Redis 同步用量计数、余额与负载均衡游标（可选）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared_state: Option<SharedStateConfig>,
+
     /// 配置文件路径（运行时元数据，不写入 JSON）
     #[serde(skip)]
     config_path: Option<PathBuf>,
+
+    /// include 片段合并后的值（运行时元数据，保存时用于避免把片段内容写回主文件）
+    #[serde(skip)]
+    included_values: Option<Value>,
+
+    /// 被环境变量覆盖的配置项（运行时元数据，保存时用于避免把环境变量的值写回文件）
+    #[serde(skip)]
+    env_overrides: Vec<EnvOverride>,
+}
+
+/// 一个被环境变量覆盖的顶层配置项
+#[derive(Debug, Clone)]
+struct EnvOverride {
+    key: String,
+    /// 加载后（已应用环境变量）的值
+    loaded: Value,
+    /// 配置文件中的原始值（文件中没有该字段时为 None）
+    file: Option<Value>,
 }
 
 fn default_host() -> String {
@@ -137,115 +571,897 @@ fn default_load_balancing_mode() -> String {
     "priority".to_string()
 }
 
-fn default_cloud_pass_server() -> String {
-    "http://kiro.eskysoft.com:9123".to_string()
+fn default_max_retries_ceiling() -> usize {
+    9
 }
 
-fn default_cloud_pass_interval() -> u64 {
-    900
+fn default_failure_cooldown_secs() -> u64 {
+    300
 }
 
-fn default_cloud_pass_version() -> String {
-    "1.1.2".to_string()
+fn default_failure_cooldown_max_secs() -> u64 {
+    3600
 }
 
-/// Cloud Pass 配置
-/// 用于从 kiro-cloud-pass 服务器自动获取和刷新凭证
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CloudPassConfig {
-    /// 激活码（必填）
-    pub license_code: String,
+fn default_health_check_recovery_threshold() -> u32 {
+    2
+}
 
-    /// 设备 ID（可选，默认从 ~/.kiro-device-id 读取）
-    #[serde(default)]
-    pub device_id: Option<String>,
+fn default_exercise_idle_hours() -> u64 {
+    24
+}
 
-    /// 服务器地址（可选，默认 http://kiro.eskysoft.com:9123）
-    #[serde(default = "default_cloud_pass_server")]
-    pub server_url: String,
+fn default_exercise_max_credentials() -> usize {
+    10
+}
 
-    /// 刷新间隔（秒，默认 900 = 15分钟）
-    #[serde(default = "default_cloud_pass_interval")]
-    pub refresh_interval: u64,
+fn default_degradation_mode() -> String {
+    "error".to_string()
+}
 
-    /// 是否启用强制抢占（可选，默认 false）
-    #[serde(default)]
-    pub reassign: bool,
+fn default_degradation_wait_secs() -> u64 {
+    30
+}
 
-    /// 客户端版本号（可选，默认 1.1.2）
-    #[serde(default = "default_cloud_pass_version")]
-    pub client_version: String,
+/// 对话请求体的默认最大字节数（50 MB）
+pub const DEFAULT_MAX_REQUEST_BYTES: usize = 50 * 1024 * 1024;
 
-    /// 固定 Machine ID（可选）
-    /// 注入凭证时使用此值作为 machineId，确保与 API 绑定的 machineId 一致
-    /// 未配置时使用 Cloud Pass 的 deviceId
-    #[serde(default)]
-    pub machine_id: Option<String>,
+fn default_max_request_bytes() -> usize {
+    DEFAULT_MAX_REQUEST_BYTES
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            host: default_host(),
-            port: default_port(),
-            region: default_region(),
-            auth_region: None,
-            api_region: None,
-            kiro_version: default_kiro_version(),
-            machine_id: None,
-            api_key: None,
-            system_version: default_system_version(),
-            node_version: default_node_version(),
-            tls_backend: default_tls_backend(),
-            count_tokens_api_url: None,
-            count_tokens_api_key: None,
-            count_tokens_auth_type: default_count_tokens_auth_type(),
-            proxy_url: None,
-            proxy_username: None,
-            proxy_password: None,
-            admin_api_key: None,
-            load_balancing_mode: default_load_balancing_mode(),
-            cloud_pass: None,
-            config_path: None,
-        }
-    }
+fn default_max_image_bytes() -> u64 {
+    5 << 20
 }
 
-impl Config {
-    /// 获取默认配置文件路径
-    pub fn default_config_path() -> &'static str {
-        "config.json"
-    }
+fn default_admin_session_ttl_secs() -> u64 {
+    12 * 3600
+}
 
-    /// 获取有效的 Auth Region（用于 Token 刷新）
-    /// 优先使用 auth_region，未配置时回退到 region
-    pub fn effective_auth_region(&self) -> &str {
-        self.auth_region.as_deref().unwrap_or(&self.region)
-    }
+fn default_degradation_message() -> String {
+    "Service temporarily at capacity, please retry later.".to_string()
+}
 
-    /// 获取有效的 API Region（用于 API 请求）
-    /// 优先使用 api_region，未配置时回退到 region
-    pub fn effective_api_region(&self) -> &str {
-        self.api_region.as_deref().unwrap_or(&self.region)
-    }
+fn default_decode_max_buffer_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_worker_max_restarts_per_hour() -> u32 {
+    10
+}
+
+fn default_worker_restart_backoff_secs() -> u64 {
+    1
+}
+
+fn default_worker_restart_backoff_max_secs() -> u64 {
+    300
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+fn default_archive_redact() -> bool {
+    true
+}
+
+fn default_archive_max_file_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_audit_max_file_bytes() -> u64 {
+    20 * 1024 * 1024
+}
+
+fn default_audit_max_files() -> usize {
+    5
+}
+
+fn default_concurrency_queue_size() -> usize {
+    32
+}
+
+fn default_concurrency_queue_timeout_secs() -> u64 {
+    30
+}
+
+fn default_license_expiry_warn_days() -> u64 {
+    3
+}
+
+fn default_auth_lockout_max_failures() -> u32 {
+    10
+}
+
+fn default_auth_lockout_window_secs() -> u64 {
+    60
+}
+
+fn default_auth_lockout_lockout_secs() -> u64 {
+    900
+}
+
+fn default_response_cache_ttl_secs() -> u64 {
+    300
+}
+
+fn default_response_cache_max_entries() -> usize {
+    1000
+}
+
+fn default_cloud_pass_server() -> String {
+    "http://kiro.eskysoft.com:9123".to_string()
+}
+
+fn default_cloud_pass_interval() -> u64 {
+    900
+}
+
+fn default_cloud_pass_heartbeat_interval() -> u64 {
+    300
+}
+
+fn default_cloud_pass_claim_retries() -> u32 {
+    3
+}
+
+fn default_cloud_pass_claim_retry_delay() -> u64 {
+    5
+}
+
+fn default_peer_sync_interval() -> u64 {
+    60
+}
+
+fn default_leader_lease_ttl() -> u64 {
+    30
+}
+
+fn default_shared_state_key_prefix() -> String {
+    "kiro-rs".to_string()
+}
+
+fn default_shared_state_sync_interval() -> u64 {
+    2
+}
+
+fn default_cloud_pass_version() -> String {
+    "1.1.2".to_string()
+}
+
+/// 凭据滚动窗口用量上限（最近 1 小时 / 24 小时），未配置或为 0 的项不限制
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageCap {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hourly_requests: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hourly_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_requests: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_tokens: Option<u64>,
+}
+
+impl UsageCap {
+    /// 是否所有项都不限制
+    pub fn is_unlimited(&self) -> bool {
+        [
+            self.hourly_requests,
+            self.hourly_tokens,
+            self.daily_requests,
+            self.daily_tokens,
+        ]
+        .iter()
+        .all(|limit| limit.unwrap_or(0) == 0)
+    }
+}
+
+/// 用量警戒线：已用额度占总额度的百分比达到 `percent` 时触发 `action`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageWatermark {
+    /// 用量百分比（0-100）
+    pub percent: f64,
+
+    /// 达到警戒线后的处理方式（默认仅通知）
+    #[serde(default)]
+    pub action: WatermarkAction,
+}
+
+/// 达到用量警戒线后的处理方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatermarkAction {
+    /// 仅发出 `usage_watermark` 通知
+    #[default]
+    Alert,
+    /// 通知并让该凭据只在没有其他可用凭据时才被选中
+    Deprioritize,
+    /// 通知并禁用该凭据，额度重置或手动启用后恢复
+    Disable,
+}
+
+/// 失败自动换凭据重试设置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryFailover {
+    /// 最大尝试次数（含首次请求，受 maxRetriesCeiling 约束；请求头 `x-kiro-max-retries` 优先）
+    #[serde(default = "default_failover_max_attempts")]
+    pub max_attempts: usize,
+
+    /// 首次重试前的退避时间（毫秒），此后每次翻倍
+    #[serde(default = "default_failover_backoff_ms")]
+    pub backoff_ms: u64,
+
+    /// 退避时间上限（毫秒）
+    #[serde(default = "default_failover_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+fn default_failover_max_attempts() -> usize {
+    3
+}
+
+fn default_failover_backoff_ms() -> u64 {
+    200
+}
+
+fn default_failover_max_backoff_ms() -> u64 {
+    2_000
+}
+
+/// 配额感知准入设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaAdmission {
+    /// 每单位额度（与 usageLimit 同一计量单位）折合的 tokens 数，不大于 0 时不检查
+    pub tokens_per_unit: f64,
+
+    /// 安全余量（额度单位）：剩余额度需不低于预计开销加上该值
+    #[serde(default)]
+    pub safety_margin: f64,
+
+    /// 预计 tokens 低于此值的请求不检查
+    #[serde(default = "default_quota_admission_min_tokens")]
+    pub min_tokens: u64,
+}
+
+fn default_quota_admission_min_tokens() -> u64 {
+    10_000
+}
+
+impl QuotaAdmission {
+    /// 预计 tokens 折合的额度，无需检查时返回 None
+    pub fn cost(&self, tokens: u64) -> Option<f64> {
+        (self.tokens_per_unit > 0.0).then(|| tokens as f64 / self.tokens_per_unit)
+    }
+
+    /// 请求准入所需的剩余额度（含安全余量），无需检查时返回 None
+    pub fn required(&self, tokens: u64) -> Option<f64> {
+        if tokens < self.min_tokens {
+            return None;
+        }
+        self.cost(tokens).map(|cost| cost + self.safety_margin.max(0.0))
+    }
+}
+
+/// 凭据优先级预设
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriorityPreset {
+    /// 标签 → 优先级（数字越小优先级越高），凭据匹配多个标签时取最高优先级
+    #[serde(default)]
+    pub tags: BTreeMap<String, u32>,
+
+    /// 未匹配任何标签的凭据使用的优先级（未配置时保持不变）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_priority: Option<u32>,
+
+    /// 每天自动应用的时刻（运营者时区，`HH:MM`，可配置多个）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<String>,
+}
+
+impl PriorityPreset {
+    /// 按凭据标签计算预设优先级，None 表示保持不变
+    pub fn priority_for(&self, tags: &[String]) -> Option<u32> {
+        tags.iter()
+            .filter_map(|tag| self.tags.get(tag).copied())
+            .min()
+            .or(self.default_priority)
+    }
+
+    /// 解析后的每日应用时刻（忽略无法解析的项）
+    pub fn schedule_times(&self) -> Vec<NaiveTime> {
+        self.schedule
+            .iter()
+            .filter_map(|s| NaiveTime::parse_from_str(s.trim(), "%H:%M").ok())
+            .collect()
+    }
+}
+
+/// 响应归档配置
+/// 用于有留档要求的团队，按 NDJSON 每行一条记录写入本地目录并按大小/日期轮转
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveConfig {
+    /// 归档目录（必填）
+    pub dir: String,
+
+    /// 是否同时归档请求内容（system / messages，默认 false）
+    #[serde(default)]
+    pub include_prompts: bool,
+
+    /// 是否对疑似密钥的内容脱敏（默认 true）
+    #[serde(default = "default_archive_redact")]
+    pub redact: bool,
+
+    /// 单个归档文件大小上限（字节，默认 100MB），超过后轮转到新文件
+    #[serde(default = "default_archive_max_file_bytes")]
+    pub max_file_bytes: u64,
+
+    /// 仅归档这些 API Key 的请求（可选，未配置时归档所有请求）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_keys: Option<Vec<String>>,
+}
+
+/// 请求审计日志配置
+/// 每个对话请求以 JSONL 写入一行，按大小轮转，可通过 `GET /api/admin/logs` 查询
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditConfig {
+    /// 日志目录（必填）
+    pub dir: String,
+
+    /// 单个日志文件大小上限（字节，默认 20MB），超过后轮转
+    #[serde(default = "default_audit_max_file_bytes")]
+    pub max_file_bytes: u64,
+
+    /// 保留的日志文件数（含当前文件，默认 5）
+    #[serde(default = "default_audit_max_files")]
+    pub max_files: usize,
+}
+
+/// 客户端 IP 访问控制配置
+/// 列表项为 CIDR（如 `10.8.0.0/16`）或单个 IP，拒绝列表优先
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpFilterConfig {
+    /// 允许列表（为空时放行所有未被拒绝的地址）
+    #[serde(default)]
+    pub allow: Vec<String>,
+
+    /// 拒绝列表
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// 认证失败锁定配置
+/// 同一 IP 在 `windowSecs` 内认证失败 `maxFailures` 次后锁定 `lockoutSecs`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthLockoutConfig {
+    /// 触发锁定的失败次数（默认 10）
+    #[serde(default = "default_auth_lockout_max_failures")]
+    pub max_failures: u32,
+
+    /// 统计失败次数的窗口（秒，默认 60）
+    #[serde(default = "default_auth_lockout_window_secs")]
+    pub window_secs: u64,
+
+    /// 锁定时长（秒，默认 900）
+    #[serde(default = "default_auth_lockout_lockout_secs")]
+    pub lockout_secs: u64,
+}
+
+/// Webhook 配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookConfig {
+    /// 接收事件的 URL
+    pub url: String,
+
+    /// 签名密钥（可选，配置后请求带 `X-Kiro-Signature` 签名头）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+
+    /// 订阅的事件类型（为空时订阅全部）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<EventKind>,
+}
+
+impl WebhookConfig {
+    /// 是否订阅该事件（测试事件总是投递）
+    pub fn accepts(&self, event: EventKind) -> bool {
+        subscribes(&self.events, event)
+    }
+}
+
+/// Telegram Bot 通知配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelegramConfig {
+    /// Bot Token（由 @BotFather 生成）
+    pub bot_token: String,
+
+    /// 接收消息的 chat_id（用户/群组 ID 或 `@频道名`）
+    pub chat_id: String,
+
+    /// 订阅的事件类型（为空时订阅全部）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<EventKind>,
+}
+
+impl TelegramConfig {
+    /// 是否订阅该事件（测试事件总是投递）
+    pub fn accepts(&self, event: EventKind) -> bool {
+        subscribes(&self.events, event)
+    }
+}
+
+/// Slack / Discord Webhook 通知配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatWebhookConfig {
+    /// 平台生成的 Webhook URL
+    pub webhook_url: String,
+
+    /// 订阅的事件类型（为空时订阅全部）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<EventKind>,
+}
+
+impl ChatWebhookConfig {
+    /// 是否订阅该事件（测试事件总是投递）
+    pub fn accepts(&self, event: EventKind) -> bool {
+        subscribes(&self.events, event)
+    }
+}
+
+fn subscribes(events: &[EventKind], event: EventKind) -> bool {
+    event == EventKind::Test || events.is_empty() || events.contains(&event)
+}
+
+/// 凭据自动发现配置
+/// 启动时扫描 Kiro IDE 的 Token 缓存并导入新的凭据
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialDiscoveryConfig {
+    /// 扫描的目录或 Token 文件（支持 `~/` 前缀，默认 `~/.aws/sso/cache` 与 `~/.kiro`）
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+/// 响应缓存配置
+/// 以规范化请求（模型、system、消息、工具与参数）的哈希为键缓存非流式成功响应
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseCacheConfig {
+    /// 缓存有效期（秒，默认 300）
+    #[serde(default = "default_response_cache_ttl_secs")]
+    pub ttl_secs: u64,
+
+    /// 最多缓存的响应数（默认 1000），超过后淘汰最早写入的响应
+    #[serde(default = "default_response_cache_max_entries")]
+    pub max_entries: usize,
+}
+
+/// Cloud Pass 配置
+/// 用于从 kiro-cloud-pass 服务器自动获取和刷新凭证
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudPassConfig {
+    /// 激活码（与 `licenses` 至少配置一个）
+    #[serde(default)]
+    pub license_code: String,
+
+    /// 设备 ID（可选，默认从 ~/.kiro-device-id 读取）
+    #[serde(default)]
+    pub device_id: Option<String>,
+
+    /// 服务器地址（可选，默认 http://kiro.eskysoft.com:9123）
+    #[serde(default = "default_cloud_pass_server")]
+    pub server_url: String,
+
+    /// 刷新间隔（秒，默认 900 = 15分钟）
+    #[serde(default = "default_cloud_pass_interval")]
+    pub refresh_interval: u64,
+
+    /// 心跳间隔（秒，默认 300 = 5分钟，0 表示不发送心跳），与刷新间隔相互独立
+    #[serde(default = "default_cloud_pass_heartbeat_interval")]
+    pub heartbeat_interval: u64,
+
+    /// 是否启用强制抢占（可选，默认 false）
+    #[serde(default)]
+    pub reassign: bool,
+
+    /// 被踢出后自动抢占的最多尝试次数（默认 3），全部失败后发出 `cloud_pass_kicked` 通知
+    #[serde(default = "default_cloud_pass_claim_retries")]
+    pub claim_retries: u32,
+
+    /// 自动抢占两次尝试之间的间隔（秒，默认 5）
+    #[serde(default = "default_cloud_pass_claim_retry_delay")]
+    pub claim_retry_delay: u64,
+
+    /// 客户端版本号（可选，默认 1.1.2）
+    #[serde(default = "default_cloud_pass_version")]
+    pub client_version: String,
+
+    /// 固定 Machine ID（可选）
+    /// 注入凭证时使用此值作为 machineId，确保与 API 绑定的 machineId 一致
+    /// 未配置时使用 Cloud Pass 的 deviceId
+    #[serde(default)]
+    pub machine_id: Option<String>,
+
+    /// 额外的激活码（可选），每个激活码独立刷新并注入一个凭据
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub licenses: Vec<CloudPassLicense>,
+
+    /// 是否暂停 Cloud Pass（默认 false），暂停时保留配置但不刷新
+    #[serde(default)]
+    pub disabled: bool,
+
+    /// 服务器轮换凭证后，如何处理被替换的旧凭据（默认禁用）
+    #[serde(default)]
+    pub stale_credential_action: StaleCredentialAction,
+}
+
+/// 多实例凭据同步配置（从实例）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerSyncConfig {
+    /// 源实例地址（如 `https://kiro-primary:8990`）
+    pub source_url: String,
+
+    /// 源实例的 Admin API Key（需为 admin 角色）
+    pub api_key: String,
+
+    /// 拉取间隔（秒，默认 60）
+    #[serde(default = "default_peer_sync_interval")]
+    pub interval: u64,
+}
+
+/// 主备模式选主配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderElectionConfig {
+    /// 租约文件路径（需位于各实例共享的目录中）
+    pub lease_file: String,
+
+    /// 租约有效期（秒，默认 30），主节点每 1/3 有效期续约一次
+    #[serde(default = "default_leader_lease_ttl")]
+    pub lease_ttl: u64,
+
+    /// 节点 ID（默认启动时随机生成）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<String>,
+}
+
+/// 多副本共享状态配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedStateConfig {
+    /// Redis 地址（如 `redis://:password@127.0.0.1:6379/0`）
+    pub redis_url: String,
+
+    /// 键名前缀（默认 `kiro-rs`），同一 Redis 服务多组副本时用于隔离
+    #[serde(default = "default_shared_state_key_prefix")]
+    pub key_prefix: String,
+
+    /// 同步间隔（秒，默认 2）
+    #[serde(default = "default_shared_state_sync_interval")]
+    pub sync_interval: u64,
+}
+
+/// Cloud Pass 被替换的旧凭据的处理方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum StaleCredentialAction {
+    /// 保留在凭据池中
+    Keep,
+    /// 禁用（可在 Admin 中手动删除或重新启用）
+    #[default]
+    Disable,
+    /// 禁用后从凭据池删除
+    Delete,
+}
+
+impl Default for CloudPassConfig {
+    fn default() -> Self {
+        Self {
+            license_code: String::new(),
+            device_id: None,
+            server_url: default_cloud_pass_server(),
+            refresh_interval: default_cloud_pass_interval(),
+            heartbeat_interval: default_cloud_pass_heartbeat_interval(),
+            reassign: false,
+            claim_retries: default_cloud_pass_claim_retries(),
+            claim_retry_delay: default_cloud_pass_claim_retry_delay(),
+            client_version: default_cloud_pass_version(),
+            machine_id: None,
+            licenses: Vec::new(),
+            disabled: false,
+            stale_credential_action: StaleCredentialAction::default(),
+        }
+    }
+}
+
+/// Cloud Pass 激活码及其设备配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudPassLicense {
+    /// 激活码
+    pub license_code: String,
+
+    /// 设备 ID（可选，默认由本机设备 ID 与激活码派生）
+    #[serde(default)]
+    pub device_id: Option<String>,
+
+    /// 固定 Machine ID（可选，未配置时回退到 `cloudPass.machineId`，再回退到设备 ID）
+    #[serde(default)]
+    pub machine_id: Option<String>,
+}
+
+impl CloudPassConfig {
+    /// 所有激活码：顶层 `licenseCode`（兼容单激活码配置）在前，`licenses` 在后，重复的激活码只保留第一个
+    pub fn all_licenses(&self) -> Vec<CloudPassLicense> {
+        let primary = (!self.license_code.is_empty()).then(|| CloudPassLicense {
+            license_code: self.license_code.clone(),
+            device_id: self.device_id.clone(),
+            machine_id: self.machine_id.clone(),
+        });
+        let extra = self.licenses.iter().map(|license| {
+            let mut license = license.clone();
+            if license.machine_id.is_none() {
+                license.machine_id = self.machine_id.clone();
+            }
+            license
+        });
+        let mut seen = std::collections::HashSet::new();
+        primary
+            .into_iter()
+            .chain(extra)
+            .filter(|license| {
+                !license.license_code.is_empty() && seen.insert(license.license_code.clone())
+            })
+            .collect()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            host: default_host(),
+            port: default_port(),
+            listen: None,
+            tls_cert_path: None,
+            tls_key_path: None,
+            tls_client_ca_path: None,
+            region: default_region(),
+            auth_region: None,
+            api_region: None,
+            upstream_base_url: None,
+            kiro_version: default_kiro_version(),
+            machine_id: None,
+            api_key: None,
+            system_version: default_system_version(),
+            node_version: default_node_version(),
+            tls_backend: default_tls_backend(),
+            log_format: LogFormat::default(),
+            count_tokens_api_url: None,
+            count_tokens_api_key: None,
+            count_tokens_auth_type: default_count_tokens_auth_type(),
+            tokenizer: Tokenizer::default(),
+            embeddings_api_url: None,
+            embeddings_api_key: None,
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            admin_api_key: None,
+            admin_keys: Vec::new(),
+            admin_session_ttl_secs: default_admin_session_ttl_secs(),
+            load_balancing_mode: default_load_balancing_mode(),
+            max_retries_ceiling: default_max_retries_ceiling(),
+            retry_failover: None,
+            stream_resume: false,
+            credential_rpm: None,
+            credential_max_concurrency: None,
+            concurrency_queue_size: default_concurrency_queue_size(),
+            concurrency_queue_timeout_secs: default_concurrency_queue_timeout_secs(),
+            credential_usage_cap: None,
+            usage_watermark: None,
+            quota_admission: None,
+            sticky_session: false,
+            sticky_session_header: None,
+            model_aliases: BTreeMap::new(),
+            image_url_fetch: false,
+            max_image_bytes: default_max_image_bytes(),
+            non_vision_models: Vec::new(),
+            priority_presets: BTreeMap::new(),
+            failure_cooldown_secs: default_failure_cooldown_secs(),
+            failure_cooldown_max_secs: default_failure_cooldown_max_secs(),
+            health_check_interval: 0,
+            balance_refresh_interval_secs: 0,
+            health_check_recovery_threshold: default_health_check_recovery_threshold(),
+            proactive_refresh_margin_secs: 0,
+            exercise_hour_utc: None,
+            exercise_idle_hours: default_exercise_idle_hours(),
+            exercise_max_credentials: default_exercise_max_credentials(),
+            timezone: None,
+            degradation_mode: default_degradation_mode(),
+            degradation_wait_secs: default_degradation_wait_secs(),
+            degradation_message: default_degradation_message(),
+            sse_max_chunk_bytes: 0,
+            thinking_output: ThinkingOutput::default(),
+            enforce_max_tokens: false,
+            max_request_bytes: default_max_request_bytes(),
+            max_messages: None,
+            max_input_tokens: None,
+            decode_error_hexdump: false,
+            decode_crc_lenient: false,
+            decode_max_buffer_bytes: default_decode_max_buffer_bytes(),
+            worker_max_restarts_per_hour: default_worker_max_restarts_per_hour(),
+            worker_restart_backoff_secs: default_worker_restart_backoff_secs(),
+            worker_restart_backoff_max_secs: default_worker_restart_backoff_max_secs(),
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+            archive: None,
+            audit: None,
+            response_cache: None,
+            api_ip_filter: None,
+            admin_ip_filter: None,
+            auth_lockout: None,
+            webhooks: Vec::new(),
+            telegram: None,
+            slack: None,
+            discord: None,
+            low_balance_threshold: None,
+            license_expiry_warn_days: default_license_expiry_warn_days(),
+            credential_discovery: None,
+            cloud_pass: None,
+            peer_sync: None,
+            leader_election: None,
+            shared_state: None,
+            config_path: None,
+            included_values: None,
+            env_overrides: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// 获取默认配置文件路径
+    pub fn default_config_path() -> &'static str {
+        "config.json"
+    }
+
+    /// 获取有效的 Auth Region（用于 Token 刷新）
+    /// 优先使用 auth_region，未配置时回退到 region
+    pub fn effective_auth_region(&self) -> &str {
+        self.auth_region.as_deref().unwrap_or(&self.region)
+    }
+
+    /// 获取有效的 API Region（用于 API 请求）
+    /// 优先使用 api_region，未配置时回退到 region
+    pub fn effective_api_region(&self) -> &str {
+        self.api_region.as_deref().unwrap_or(&self.region)
+    }
+
+    /// 上游 API 基础地址（不含路径），配置了 upstream_base_url 时优先使用
+    pub fn api_base_url(&self, api_region: &str) -> String {
+        match &self.upstream_base_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!("https://q.{}.amazonaws.com", api_region),
+        }
+    }
 
     /// 从文件加载配置
+    ///
+    /// 优先级从高到低：`KIRO_*` 环境变量 > 配置文件（含 include 片段）> 默认值
     pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
-        let path = path.as_ref();
-        if !path.exists() {
-            // 配置文件不存在，返回默认配置
-            let mut config = Self::default();
-            config.config_path = Some(path.to_path_buf());
-            return Ok(config);
-        }
+        Self::load_with_env(path.as_ref(), std::env::vars())
+    }
+
+    fn load_with_env(
+        path: &Path,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> anyhow::Result<Self> {
+        let (mut value, included) = if path.exists() {
+            let mut stack = Vec::new();
+            load_value_with_includes(path, &mut stack)?
+        } else {
+            // 配置文件不存在，使用默认配置
+            (serde_json::to_value(Self::default())?, None)
+        };
 
-        let content = fs::read_to_string(path)?;
-        let mut config: Config = serde_json::from_str(&content)?;
+        let overrides = env_overrides(vars);
+        let file_values: Vec<(String, Option<Value>)> = overrides
+            .keys()
+            .map(|key| (key.clone(), value.get(key).cloned()))
+            .collect();
+        merge_json(&mut value, Value::Object(overrides));
+
+        let mut config: Config = serde_json::from_value(value)?;
         config.config_path = Some(path.to_path_buf());
+        config.included_values = included;
+        if !file_values.is_empty() {
+            let loaded = serde_json::to_value(&config)?;
+            config.env_overrides = file_values
+                .into_iter()
+                .map(|(key, file)| EnvOverride {
+                    loaded: loaded.get(&key).cloned().unwrap_or(Value::Null),
+                    key,
+                    file,
+                })
+                .collect();
+        }
         Ok(config)
     }
 
+    /// 查找模型别名对应的实际模型名（不区分大小写），未配置别名时返回 None
+    pub fn resolve_model_alias(&self, model: &str) -> Option<&str> {
+        self.model_aliases
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(model))
+            .map(|(_, target)| target.as_str())
+    }
+
+    /// 模型是否支持图片输入（未被 nonVisionModels 匹配）
+    pub fn supports_vision(&self, model: &str) -> bool {
+        let model = model.to_lowercase();
+        !self
+            .non_vision_models
+            .iter()
+            .any(|pattern| model.contains(&pattern.to_lowercase()))
+    }
+
+    /// 已配置且未暂停的 Cloud Pass 配置
+    pub fn active_cloud_pass(&self) -> Option<&CloudPassConfig> {
+        self.cloud_pass.as_ref().filter(|c| !c.disabled)
+    }
+
+    /// 根据 proxyUrl / proxyUsername / proxyPassword 构建全局代理配置
+    pub fn proxy_config(&self) -> Option<ProxyConfig> {
+        self.proxy_url.as_ref().map(|url| {
+            let mut proxy = ProxyConfig::new(url);
+            if let (Some(username), Some(password)) = (&self.proxy_username, &self.proxy_password) {
+                proxy = proxy.with_auth(username, password);
+            }
+            proxy
+        })
+    }
+
+    /// 是否配置了可用的 Admin Key（adminApiKey 或 adminKeys，空字符串视为未配置）
+    pub fn admin_enabled(&self) -> bool {
+        self.admin_api_key
+            .as_ref()
+            .is_some_and(|k| !k.trim().is_empty())
+            || self.admin_keys.iter().any(|k| !k.key.trim().is_empty())
+    }
+
+    /// 运营者时区偏移，未配置或无法解析时为 UTC
+    pub fn timezone_offset(&self) -> FixedOffset {
+        self.timezone
+            .as_deref()
+            .and_then(parse_utc_offset)
+            .unwrap_or_else(|| Utc.fix())
+    }
+
+    /// 是否有优先级预设配置了定时切换
+    pub fn has_scheduled_presets(&self) -> bool {
+        self.priority_presets
+            .values()
+            .any(|preset| !preset.schedule.is_empty())
+    }
+
     /// 获取配置文件路径（如果有）
     pub fn config_path(&self) -> Option<&Path> {
         self.config_path.as_deref()
@@ -258,8 +1474,414 @@ impl Config {
             .as_deref()
             .ok_or_else(|| anyhow::anyhow!("配置文件路径未知，无法保存配置"))?;
 
-        let content = serde_json::to_string_pretty(self).context("序列化配置失败")?;
+        let mut value = serde_json::to_value(self).context("序列化配置失败")?;
+
+        // 加载后未被修改的环境变量覆盖项写回文件中的原始值
+        if let Value::Object(own) = &mut value {
+            for item in &self.env_overrides {
+                if own.get(&item.key).unwrap_or(&Value::Null) != &item.loaded {
+                    continue;
+                }
+                match &item.file {
+                    Some(file) => own.insert(item.key.clone(), file.clone()),
+                    None => own.remove(&item.key),
+                };
+            }
+        }
+
+        // 与 include 片段一致的字段（逐层比较到叶子）不写回主文件，避免密钥等内容泄露到主配置
+        if let (Some(Value::Object(included)), Value::Object(own)) =
+            (&self.included_values, &mut value)
+        {
+            strip_included(own, included);
+        }
+
+        let content = serde_json::to_string_pretty(&value).context("序列化配置失败")?;
         fs::write(path, content).with_context(|| format!("写入配置文件失败: {}", path.display()))?;
         Ok(())
     }
 }
+
+/// 从待保存的配置中移除与 include 片段一致的值
+///
+/// 对象逐层比较，只移除相同的叶子；剥离后为空的对象一并移除（重新加载时由 include 合并回来）
+fn strip_included(own: &mut Map<String, Value>, included: &Map<String, Value>) {
+    own.retain(|key, value| {
+        let Some(included_value) = included.get(key) else {
+            return true;
+        };
+        if value == included_value {
+            return false;
+        }
+        match (value, included_value) {
+            (Value::Object(own_map), Value::Object(included_map)) => {
+                strip_included(own_map, included_map);
+                !own_map.is_empty()
+            }
+            _ => true,
+        }
+    });
+}
+
+/// 读取配置文件并递归合并 include 片段
+///
+/// 返回 (合并后的完整值, 仅由 include 片段合并出的值)；
+/// `stack` 记录当前 include 链，用于检测循环引用
+fn load_value_with_includes(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> anyhow::Result<(Value, Option<Value>)> {
+    let canonical = fs::canonicalize(path)
+        .with_context(|| format!("读取配置文件失败: {}", path.display()))?;
+
+    if stack.contains(&canonical) {
+        let chain: Vec<String> = stack
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|p| p.display().to_string())
+            .collect();
+        anyhow::bail!("检测到配置 include 循环: {}", chain.join(" -> "));
+    }
+    if stack.len() >= MAX_INCLUDE_DEPTH {
+        anyhow::bail!("配置 include 嵌套过深（最大 {} 层）", MAX_INCLUDE_DEPTH);
+    }
+
+    let content = fs::read_to_string(&canonical)
+        .with_context(|| format!("读取配置文件失败: {}", path.display()))?;
+    let mut value: Value = serde_json::from_str(&content)
+        .with_context(|| format!("解析配置文件失败: {}", path.display()))?;
+
+    let includes: Vec<String> = match value.get("include") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(v) => serde_json::from_value(v.clone())
+            .with_context(|| format!("include 必须是字符串数组: {}", path.display()))?,
+    };
+    if includes.is_empty() {
+        return Ok((value, None));
+    }
+
+    stack.push(canonical.clone());
+    let base_dir = canonical.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut included = Value::Object(Map::new());
+    for include in &includes {
+        let (fragment, _) = load_value_with_includes(&base_dir.join(include), stack)?;
+        merge_json(&mut included, fragment);
+    }
+    stack.pop();
+
+    // 片段中的 include 已展开，不参与合并，保留当前文件声明的列表
+    if let Value::Object(included_map) = &mut included {
+        included_map.remove("include");
+    }
+
+    let own = std::mem::take(&mut value);
+    let mut merged = included.clone();
+    merge_json(&mut merged, own);
+
+    Ok((merged, Some(included)))
+}
+
+/// 收集 `KIRO_*` 环境变量覆盖的顶层配置项（变量名去掉前缀后转为 camelCase）
+fn env_overrides(vars: impl IntoIterator<Item = (String, String)>) -> Map<String, Value> {
+    let mut overrides = Map::new();
+    for (name, raw) in vars {
+        let Some(suffix) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let key = env_field_name(suffix);
+        if key == "include" || !is_config_field(&key) {
+            continue;
+        }
+        tracing::info!("环境变量 {} 覆盖配置项 {}", name, key);
+        let value = parse_env_value(&key, &raw);
+        overrides.insert(key, value);
+    }
+    overrides
+}
+
+/// `PROXY_URL` → `proxyUrl`
+fn env_field_name(suffix: &str) -> String {
+    let mut name = String::new();
+    for (i, part) in suffix.split('_').filter(|p| !p.is_empty()).enumerate() {
+        let part = part.to_ascii_lowercase();
+        if i == 0 {
+            name.push_str(&part);
+            continue;
+        }
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_ascii_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    name
+}
+
+/// 是否为 Config 的顶层字段（未知字段会被忽略，任何类型都能通过反序列化）
+fn is_config_field(key: &str) -> bool {
+    let probe = serde_json::json!({ key: [[[]]] });
+    serde_json::from_value::<Config>(probe).is_err()
+}
+
+/// 解析环境变量的值：对象、数组与 JSON 字符串按 JSON 解析，
+/// 数字、布尔与 null 仅在字段类型匹配时采用，其余情况按原始字符串处理（如纯数字的 apiKey）
+fn parse_env_value(key: &str, raw: &str) -> Value {
+    match serde_json::from_str::<Value>(raw) {
+        Ok(value @ (Value::Object(_) | Value::Array(_) | Value::String(_))) => value,
+        Ok(value) => {
+            let probe = serde_json::json!({ key: value.clone() });
+            if serde_json::from_value::<Config>(probe).is_ok() {
+                value
+            } else {
+                Value::String(raw.to_string())
+            }
+        }
+        Err(_) => Value::String(raw.to_string()),
+    }
+}
+
+/// 深度合并 JSON：对象按字段递归合并，其余类型由 overlay 整体覆盖
+fn merge_json(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base_map), Value::Object(overlay_map)) => {
+            for (key, value) in overlay_map {
+                match base_map.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base_map.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kiro-config-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_load_merges_includes_with_precedence() {
+        let dir = temp_dir();
+        fs::write(
+            dir.join("defaults.json"),
+            r#"{"port":9000,"region":"eu-west-1","loadBalancingMode":"balanced"}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("secrets.json"),
+            r#"{"apiKey":"sk-secret","region":"ap-east-1"}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("config.json"),
+            r#"{"include":["defaults.json","secrets.json"],"port":8990}"#,
+        )
+        .unwrap();
+
+        let config = Config::load(dir.join("config.json")).unwrap();
+        // 主文件优先于 include
+        assert_eq!(config.port, 8990);
+        // 后列出的 include 优先于先列出的
+        assert_eq!(config.region, "ap-east-1");
+        assert_eq!(config.api_key.as_deref(), Some("sk-secret"));
+        assert_eq!(config.load_balancing_mode, "balanced");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_model_alias_is_case_insensitive() {
+        let config: Config = serde_json::from_str(
+            r#"{"modelAliases": {
+                "GPT-4o": "claude-sonnet-4",
+                "claude-3-5-sonnet-latest": "claude-sonnet-4-5-20250929"
+            }}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.resolve_model_alias("gpt-4o"), Some("claude-sonnet-4"));
+        assert_eq!(
+            config.resolve_model_alias("claude-3-5-sonnet-latest"),
+            Some("claude-sonnet-4-5-20250929")
+        );
+        assert_eq!(config.resolve_model_alias("claude-sonnet-4"), None);
+    }
+
+    #[test]
+    fn test_admin_keys_enable_admin_api() {
+        let config: Config = serde_json::from_str(
+            r#"{"adminApiKey": " ", "adminKeys": [
+                {"name": "ops", "key": "sk-admin-ops-0123456789"},
+                {"name": "dashboard", "key": "sk-admin-view-0123456789", "role": "viewer"}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(config.admin_enabled());
+        assert_eq!(config.admin_keys[0].role, AdminRole::Admin);
+        assert_eq!(config.admin_keys[1].role, AdminRole::Viewer);
+
+        let config: Config = serde_json::from_str(r#"{"adminApiKey": " "}"#).unwrap();
+        assert!(!config.admin_enabled());
+    }
+
+    #[test]
+    fn test_load_detects_include_cycle() {
+        let dir = temp_dir();
+        fs::write(dir.join("a.json"), r#"{"include":["b.json"]}"#).unwrap();
+        fs::write(dir.join("b.json"), r#"{"include":["a.json"]}"#).unwrap();
+
+        let err = Config::load(dir.join("a.json")).unwrap_err().to_string();
+        assert!(err.contains("循环"), "unexpected error: {}", err);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_env_overrides_file_and_defaults() {
+        let dir = temp_dir();
+        let path = dir.join("config.json");
+        fs::write(
+            &path,
+            r#"{"host":"127.0.0.1","apiKey":"sk-file","audit":{"dir":"/var/audit"}}"#,
+        )
+        .unwrap();
+        let vars = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let env = vars(&[
+            ("KIRO_HOST", "0.0.0.0"),
+            ("KIRO_PORT", "9000"),
+            ("KIRO_API_KEY", "123456"),
+            ("KIRO_PROXY_URL", "socks5://proxy:1080"),
+            ("KIRO_AUDIT", r#"{"maxFiles":3}"#),
+            ("KIRO_LICENSE_CODE", "ignored"),
+            ("HOST", "ignored"),
+        ]);
+
+        let mut config = Config::load_with_env(&path, env.clone()).unwrap();
+        assert_eq!(config.host, "0.0.0.0");
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.api_key.as_deref(), Some("123456"));
+        assert_eq!(config.proxy_url.as_deref(), Some("socks5://proxy:1080"));
+        let audit = config.audit.clone().unwrap();
+        assert_eq!((audit.dir.as_str(), audit.max_files), ("/var/audit", 3));
+
+        // 保存时不把环境变量的值写回文件，修改过的字段正常写回
+        config.port = 9100;
+        config.save().unwrap();
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["host"], "127.0.0.1");
+        assert_eq!(saved["apiKey"], "sk-file");
+        assert_eq!(saved["port"], 9100);
+        assert!(saved.get("proxyUrl").is_none());
+        assert!(saved["audit"].get("maxFiles").is_none());
+
+        // 配置文件不存在时环境变量覆盖默认值
+        let env = env.into_iter().filter(|(k, _)| k != "KIRO_AUDIT");
+        let missing = Config::load_with_env(&dir.join("missing.json"), env).unwrap();
+        assert_eq!(missing.port, 9000);
+        assert_eq!(env_field_name("COUNT_TOKENS_API_URL"), "countTokensApiUrl");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_does_not_write_included_values_back() {
+        let dir = temp_dir();
+        fs::write(dir.join("secrets.json"), r#"{"adminApiKey":"sk-admin"}"#).unwrap();
+        fs::write(dir.join("config.json"), r#"{"include":["secrets.json"]}"#).unwrap();
+
+        let mut config = Config::load(dir.join("config.json")).unwrap();
+        config.load_balancing_mode = "balanced".to_string();
+        config.save().unwrap();
+
+        let saved = fs::read_to_string(dir.join("config.json")).unwrap();
+        assert!(!saved.contains("sk-admin"));
+        assert!(saved.contains("balanced"));
+
+        let reloaded = Config::load(dir.join("config.json")).unwrap();
+        assert_eq!(reloaded.admin_api_key.as_deref(), Some("sk-admin"));
+        assert_eq!(reloaded.load_balancing_mode, "balanced");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_does_not_write_nested_included_values_back() {
+        let dir = temp_dir();
+        fs::write(
+            dir.join("secrets.json"),
+            r#"{"cloudPass":{"licenseCode":"LICENSE-SECRET"}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("config.json"), r#"{"include":["secrets.json"]}"#).unwrap();
+
+        let mut config = Config::load(dir.join("config.json")).unwrap();
+        config.load_balancing_mode = "balanced".to_string();
+        config.save().unwrap();
+
+        let saved = fs::read_to_string(dir.join("config.json")).unwrap();
+        assert!(!saved.contains("LICENSE-SECRET"));
+        assert!(saved.contains("balanced"));
+
+        let reloaded = Config::load(dir.join("config.json")).unwrap();
+        let cloud_pass = reloaded.cloud_pass.as_ref().unwrap();
+        assert_eq!(cloud_pass.license_code, "LICENSE-SECRET");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cloud_pass_all_licenses() {
+        let config: CloudPassConfig = serde_json::from_str(
+            r#"{
+                "licenseCode": "AAA",
+                "deviceId": "dev-a",
+                "machineId": "m-shared",
+                "licenses": [
+                    {"licenseCode": "BBB"},
+                    {"licenseCode": "AAA"},
+                    {"licenseCode": "CCC", "deviceId": "dev-c", "machineId": "m-c"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let licenses = config.all_licenses();
+        let codes: Vec<_> = licenses.iter().map(|l| l.license_code.as_str()).collect();
+        assert_eq!(codes, vec!["AAA", "BBB", "CCC"]);
+        assert_eq!(licenses[0].device_id.as_deref(), Some("dev-a"));
+        assert_eq!(licenses[1].device_id, None);
+        assert_eq!(licenses[1].machine_id.as_deref(), Some("m-shared"));
+        assert_eq!(licenses[2].machine_id.as_deref(), Some("m-c"));
+
+        // 只配置 licenses 也可以
+        let only_list: CloudPassConfig =
+            serde_json::from_str(r#"{"licenses": [{"licenseCode": "BBB"}]}"#).unwrap();
+        assert_eq!(only_list.all_licenses().len(), 1);
+        assert_eq!(
+            only_list.stale_credential_action,
+            StaleCredentialAction::Disable
+        );
+        assert_eq!(only_list.claim_retries, 3);
+        assert_eq!(only_list.claim_retry_delay, 5);
+
+        let delete: CloudPassConfig =
+            serde_json::from_str(r#"{"licenseCode": "AAA", "staleCredentialAction": "delete"}"#)
+                .unwrap();
+        assert_eq!(
+            delete.stale_credential_action,
+            StaleCredentialAction::Delete
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! 内置聊天通知渠道：Telegram Bot、Slack Incoming Webhook、Discord Webhook
+//!
+//! 事件渲染为一段纯文本消息，经全局代理（`proxyUrl`）发送，重试策略与 Webhook 渠道一致
+
+use reqwest::{Client, RequestBuilder};
+use serde_json::json;
+
+use super::delivery::{self, MAX_ATTEMPTS};
+use super::{EventKind, NotificationEvent};
+use crate::http_client::{ProxyConfig, build_client};
+use crate::model::config::{ChatWebhookConfig, Config, TelegramConfig, TlsBackend};
+
+/// 消息正文的最大字符数（Discord 上限 2000，Telegram 上限 4096）
+const MAX_TEXT_CHARS: usize = 1900;
+
+/// 一个已配置的聊天渠道
+#[derive(Debug, Clone)]
+pub enum ChatChannel {
+    Telegram(TelegramConfig),
+    Slack(ChatWebhookConfig),
+    Discord(ChatWebhookConfig),
+}
+
+impl ChatChannel {
+    /// 从配置收集已启用的聊天渠道
+    pub fn from_config(config: &Config) -> Vec<ChatChannel> {
+        let mut channels = Vec::new();
+        if let Some(telegram) = &config.telegram {
+            channels.push(ChatChannel::Telegram(telegram.clone()));
+        }
+        if let Some(slack) = &config.slack {
+            channels.push(ChatChannel::Slack(slack.clone()));
+        }
+        if let Some(discord) = &config.discord {
+            channels.push(ChatChannel::Discord(discord.clone()));
+        }
+        channels
+    }
+
+    /// 渠道名称（日志与 Admin API 使用）
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChatChannel::Telegram(_) => "telegram",
+            ChatChannel::Slack(_) => "slack",
+            ChatChannel::Discord(_) => "discord",
+        }
+    }
+
+    /// 是否订阅该事件
+    pub fn accepts(&self, event: EventKind) -> bool {
+        match self {
+            ChatChannel::Telegram(c) => c.accepts(event),
+            ChatChannel::Slack(c) | ChatChannel::Discord(c) => c.accepts(event),
+        }
+    }
+
+    fn request(&self, client: &Client, text: &str) -> RequestBuilder {
+        match self {
+            ChatChannel::Telegram(c) => client
+                .post(format!(
+                    "https://api.telegram.org/bot{}/sendMessage",
+                    c.bot_token
+                ))
+                .json(&json!({
+                    "chat_id": c.chat_id,
+                    "text": text,
+                    "disable_web_page_preview": true,
+                })),
+            ChatChannel::Slack(c) => client.post(&c.webhook_url).json(&json!({ "text": text })),
+            ChatChannel::Discord(c) => client
+                .post(&c.webhook_url)
+                .json(&json!({ "content": text })),
+        }
+    }
+}
+
+/// 事件标题
+fn title(event: EventKind) -> &'static str {
+    match event {
+        EventKind::CredentialDisabled => "凭据已禁用",
+        EventKind::AllCredentialsExhausted => "所有凭据均不可用",
+        EventKind::AllCredentialsRateLimited => "凭据均被限流",
+        EventKind::BalanceLow => "余额不足",
+        EventKind::UsageWatermark => "用量达到警戒线",
+        EventKind::CloudPassKicked => "Cloud Pass 设备被踢出",
+        EventKind::LicenseExpiring => "License 即将到期",
+        EventKind::Test => "测试通知",
+    }
+}
+
+/// 把事件渲染为纯文本消息
+pub fn format_text(event: &NotificationEvent) -> String {
+    let mut text = format!("[kiro-rs] {}\n{}", title(event.event), event.message);
+    if let Some(email) = &event.email {
+        text.push_str(&format!("\n账号: {}", email));
+    }
+    text.push_str(&format!("\n时间: {}", event.timestamp.to_rfc3339()));
+    if text.chars().count() > MAX_TEXT_CHARS {
+        text = text.chars().take(MAX_TEXT_CHARS - 1).collect();
+        text.push('…');
+    }
+    text
+}
+
+/// 投递事件到聊天渠道（含重试），返回最终的 HTTP 状态码
+pub async fn deliver(
+    channel: &ChatChannel,
+    event: &NotificationEvent,
+    proxy: Option<&ProxyConfig>,
+    tls_backend: TlsBackend,
+) -> anyhow::Result<u16> {
+    deliver_with_attempts(channel, event, proxy, tls_backend, MAX_ATTEMPTS).await
+}
+
+/// 只投递一次不重试（Admin API 测试用）
+pub async fn deliver_once(
+    channel: &ChatChannel,
+    event: &NotificationEvent,
+    proxy: Option<&ProxyConfig>,
+    tls_backend: TlsBackend,
+) -> anyhow::Result<u16> {
+    deliver_with_attempts(channel, event, proxy, tls_backend, 1).await
+}
+
+async fn deliver_with_attempts(
+    channel: &ChatChannel,
+    event: &NotificationEvent,
+    proxy: Option<&ProxyConfig>,
+    tls_backend: TlsBackend,
+    max_attempts: u32,
+) -> anyhow::Result<u16> {
+    let client = build_client(proxy, delivery::DELIVERY_TIMEOUT_SECS, tls_backend)?;
+    let text = format_text(event);
+    delivery::send_with_retry(channel.name(), max_attempts, || {
+        channel.request(&client, &text)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::credentials::CredentialId;
+
+    #[test]
+    fn test_chat_channels_format_and_payload() {
+        let event = NotificationEvent::new(EventKind::CredentialDisabled, "凭据 #3 已被禁用")
+            .with_credential(CredentialId(3), Some("a@example.com".to_string()));
+        let text = format_text(&event);
+        assert!(text.starts_with("[kiro-rs] 凭据已禁用\n凭据 #3 已被禁用\n账号: a@example.com"));
+
+        let long = NotificationEvent::new(EventKind::Test, "x".repeat(5000));
+        assert_eq!(format_text(&long).chars().count(), MAX_TEXT_CHARS);
+
+        let mut config = Config::default();
+        config.telegram = Some(TelegramConfig {
+            bot_token: "123:abc".to_string(),
+            chat_id: "-100".to_string(),
+            events: vec![EventKind::AllCredentialsExhausted],
+        });
+        config.discord = Some(ChatWebhookConfig {
+            webhook_url: "https://discord.com/api/webhooks/1/x".to_string(),
+            events: Vec::new(),
+        });
+        let channels = ChatChannel::from_config(&config);
+        assert_eq!(channels.len(), 2);
+        assert!(!channels[0].accepts(EventKind::CredentialDisabled));
+        assert!(channels[0].accepts(EventKind::Test));
+        assert!(channels[1].accepts(EventKind::CredentialDisabled));
+
+        let client = Client::new();
+        let request = channels[0].request(&client, "hi").build().unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.telegram.org/bot123:abc/sendMessage"
+        );
+        let body: serde_json::Value =
+            serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(body["chat_id"], "-100");
+        assert_eq!(body["text"], "hi");
+
+        let request = channels[1].request(&client, "hi").build().unwrap();
+        let body: serde_json::Value =
+            serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(body["content"], "hi");
+    }
+}
@@ -0,0 +1,59 @@
+//! 通知渠道共用的 HTTP 投递与重试
+//!
+//! 网络错误、429 与 5xx 按指数退避重试，其他状态码视为失败直接返回
+
+use std::time::Duration;
+
+use anyhow::bail;
+use reqwest::{RequestBuilder, StatusCode};
+
+/// 单次投递的超时时间（秒）
+pub const DELIVERY_TIMEOUT_SECS: u64 = 10;
+/// 最多尝试次数（含首次）
+pub const MAX_ATTEMPTS: u32 = 4;
+/// 首次重试前的等待时间，之后每次翻倍
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+/// 发送请求直到成功或用尽尝试次数，返回最终的 HTTP 状态码
+///
+/// `build` 每次尝试时调用一次（签名等随时间变化的头需要重新生成），`target` 仅用于日志
+pub async fn send_with_retry(
+    target: &str,
+    max_attempts: u32,
+    mut build: impl FnMut() -> RequestBuilder,
+) -> anyhow::Result<u16> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let error = match build().send().await {
+            Ok(response) if response.status().is_success() => {
+                return Ok(response.status().as_u16());
+            }
+            Ok(response) if !is_retryable(response.status()) => {
+                bail!("HTTP {}", response.status());
+            }
+            Ok(response) => anyhow::anyhow!("HTTP {}", response.status()),
+            Err(e) => e.into(),
+        };
+        if attempt >= max_attempts {
+            if max_attempts > 1 {
+                return Err(error.context(format!("已重试 {} 次", max_attempts - 1)));
+            }
+            return Err(error);
+        }
+        tracing::debug!(
+            "{} 第 {} 次投递失败，{}s 后重试: {:#}",
+            target,
+            attempt,
+            backoff.as_secs(),
+            error
+        );
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+pub(crate) fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
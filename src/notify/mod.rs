@@ -0,0 +1,129 @@
+//! 凭据事件通知
+//!
+//! `MultiTokenManager` 与 Cloud Pass 任务把凭据相关事件（凭据被禁用、全部凭据不可用或被限流、
+//! 余额不足、用量达到警戒线、设备被踢出、License 即将到期）广播到事件总线，后台任务订阅后按配置投递到
+//! Webhook 与 Telegram / Slack / Discord
+
+pub mod chat;
+pub mod delivery;
+pub mod webhook;
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::kiro::model::credentials::CredentialId;
+use crate::kiro::token_manager::MultiTokenManager;
+use chat::ChatChannel;
+
+/// 通知事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// 凭据被自动禁用（连续失败、额度用尽、健康检查失败）
+    CredentialDisabled,
+    /// 所有凭据均不可用
+    AllCredentialsExhausted,
+    /// 请求因所有可用凭据均被限流而失败（上游 429 或本地速率限制）
+    AllCredentialsRateLimited,
+    /// 凭据剩余额度低于 `lowBalanceThreshold`
+    BalanceLow,
+    /// 凭据已用额度百分比达到 `usageWatermark`
+    UsageWatermark,
+    /// Cloud Pass 设备被踢出
+    CloudPassKicked,
+    /// Cloud Pass License 即将到期
+    LicenseExpiring,
+    /// Admin API 触发的测试事件
+    Test,
+}
+
+/// 通知事件（即 Webhook 的 JSON 请求体）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationEvent {
+    pub event: EventKind,
+    pub timestamp: DateTime<Utc>,
+    /// 人类可读的描述
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<CredentialId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// 事件相关的附加数据（如剩余额度与阈值）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl NotificationEvent {
+    pub fn new(event: EventKind, message: impl Into<String>) -> Self {
+        Self {
+            event,
+            timestamp: Utc::now(),
+            message: message.into(),
+            credential_id: None,
+            email: None,
+            data: None,
+        }
+    }
+
+    pub fn with_credential(mut self, id: CredentialId, email: Option<String>) -> Self {
+        self.credential_id = Some(id);
+        self.email = email;
+        self
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+}
+
+/// 启动通知投递后台任务
+///
+/// 每个事件按投递时的配置（支持热重载）分发到订阅了该事件的 Webhook 与聊天渠道，
+/// 每个目标独立投递与重试，慢速端点不会阻塞后续事件
+pub async fn start_notification_worker(token_manager: Arc<MultiTokenManager>) {
+    tracing::info!("通知投递任务启动");
+    let mut events = token_manager.subscribe_notifications();
+
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!("通知投递跟不上事件产生速度，已丢弃 {} 个事件", skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let config = token_manager.config();
+        let event = Arc::new(event);
+        for webhook in config.webhooks.iter().filter(|w| w.accepts(event.event)) {
+            let webhook = webhook.clone();
+            let event = event.clone();
+            let tls_backend = config.tls_backend;
+            tokio::spawn(async move {
+                if let Err(e) = webhook::deliver(&webhook, &event, tls_backend).await {
+                    tracing::warn!("Webhook {} 投递失败: {:#}", webhook.url, e);
+                }
+            });
+        }
+        for channel in ChatChannel::from_config(&config)
+            .into_iter()
+            .filter(|c| c.accepts(event.event))
+        {
+            let event = event.clone();
+            let proxy = config.proxy_config();
+            let tls_backend = config.tls_backend;
+            tokio::spawn(async move {
+                if let Err(e) = chat::deliver(&channel, &event, proxy.as_ref(), tls_backend).await {
+                    tracing::warn!("{} 通知投递失败: {:#}", channel.name(), e);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Webhook 通知渠道
+//!
+//! 以 JSON POST 投递事件；配置了 `secret` 时附带 HMAC-SHA256 签名
+
+use ring::hmac;
+
+use super::NotificationEvent;
+use super::delivery::{self, MAX_ATTEMPTS};
+use crate::http_client::build_client;
+use crate::model::config::{TlsBackend, WebhookConfig};
+
+/// 签名：`sha256=` + HMAC-SHA256(secret, "{timestamp}.{body}") 的十六进制
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, format!("{}.{}", timestamp, body).as_bytes());
+    format!("sha256={}", hex::encode(tag.as_ref()))
+}
+
+/// 投递事件到 Webhook（含重试），返回最终的 HTTP 状态码
+pub async fn deliver(
+    webhook: &WebhookConfig,
+    event: &NotificationEvent,
+    tls_backend: TlsBackend,
+) -> anyhow::Result<u16> {
+    deliver_with_attempts(webhook, event, tls_backend, MAX_ATTEMPTS).await
+}
+
+/// 只投递一次不重试（Admin API 测试用，立即返回结果）
+pub async fn deliver_once(
+    webhook: &WebhookConfig,
+    event: &NotificationEvent,
+    tls_backend: TlsBackend,
+) -> anyhow::Result<u16> {
+    deliver_with_attempts(webhook, event, tls_backend, 1).await
+}
+
+async fn deliver_with_attempts(
+    webhook: &WebhookConfig,
+    event: &NotificationEvent,
+    tls_backend: TlsBackend,
+    max_attempts: u32,
+) -> anyhow::Result<u16> {
+    let client = build_client(None, delivery::DELIVERY_TIMEOUT_SECS, tls_backend)?;
+    let body = serde_json::to_string(event)?;
+    let event_name = serde_json::to_value(event.event)?;
+    let event_name = event_name.as_str().unwrap_or_default();
+
+    delivery::send_with_retry(&format!("Webhook {}", webhook.url), max_attempts, || {
+        let timestamp = chrono::Utc::now().timestamp();
+        let mut request = client
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Kiro-Event", event_name)
+            .header("X-Kiro-Timestamp", timestamp.to_string())
+            .body(body.clone());
+        if let Some(secret) = webhook.secret.as_deref().filter(|s| !s.is_empty()) {
+            request = request.header("X-Kiro-Signature", sign(secret, timestamp, &body));
+        }
+        request
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::delivery::is_retryable;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_sign_covers_timestamp_and_body() {
+        let signature = sign("Jefe", 0, "what do ya want for nothing?");
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_ne!(signature, sign("Jefe", 1, "what do ya want for nothing?"));
+        assert_eq!(signature, sign("Jefe", 0, "what do ya want for nothing?"));
+
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"Jefe");
+        assert!(
+            hmac::verify(
+                &key,
+                b"0.what do ya want for nothing?",
+                &hex::decode(&signature["sha256=".len()..]).unwrap()
+            )
+            .is_ok()
+        );
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+    }
+}
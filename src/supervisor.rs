@@ -0,0 +1,339 @@
+//! 后台任务监督
+//!
+//! Cloud Pass 刷新、凭据自动恢复、健康检查等后台任务以无限循环运行，
+//! 一旦 panic 或意外退出便不会再执行。监督器记录停止原因并按指数退避重启任务，
+//! 1 小时内重启次数达到上限后放弃，避免反复崩溃刷屏。服务关闭时统一停止所有任务
+
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::model::config::Config;
+
+/// 重启次数统计窗口
+const RESTART_WINDOW: Duration = Duration::from_secs(3600);
+
+/// 重启策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// 1 小时内最多重启次数，0 表示不自动重启
+    pub max_restarts_per_hour: u32,
+    /// 首次重启前的等待时间，之后每次翻倍
+    pub initial_backoff: Duration,
+    /// 重启等待时间上限
+    pub max_backoff: Duration,
+}
+
+impl RestartPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_restarts_per_hour: config.worker_max_restarts_per_hour,
+            initial_backoff: Duration::from_secs(config.worker_restart_backoff_secs),
+            max_backoff: Duration::from_secs(
+                config
+                    .worker_restart_backoff_max_secs
+                    .max(config.worker_restart_backoff_secs),
+            ),
+        }
+    }
+
+    /// 第 `attempt` 次连续重启前的等待时间（从 0 开始）
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+/// 后台任务运行状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WorkerState {
+    /// 正在运行
+    Running,
+    /// 已停止，等待退避后重启
+    Restarting,
+    /// 重启次数达到上限（或未启用自动重启），已放弃
+    Failed,
+    /// 随服务关闭停止
+    Stopped,
+}
+
+/// 后台任务存活状态
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerStatus {
+    pub name: String,
+    pub state: WorkerState,
+    /// 累计重启次数
+    pub restarts: u32,
+    /// 最近一次启动时间
+    pub started_at: DateTime<Utc>,
+    /// 最近一次停止时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_exit_at: Option<DateTime<Utc>>,
+    /// 最近一次停止原因（panic 信息或意外退出）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// 后台任务监督器
+pub struct WorkerSupervisor {
+    policy: RestartPolicy,
+    workers: Mutex<BTreeMap<String, WorkerStatus>>,
+    /// 关闭信号（true 表示服务正在关闭）
+    shutdown: watch::Sender<bool>,
+}
+
+impl WorkerSupervisor {
+    pub fn new(policy: RestartPolicy) -> Self {
+        Self {
+            policy,
+            workers: Mutex::new(BTreeMap::new()),
+            shutdown: watch::channel(false).0,
+        }
+    }
+
+    /// 停止所有后台任务（中止正在运行的任务，不再重启）
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// 在监督下启动后台任务
+    ///
+    /// `factory` 每次（重新）启动时调用一次，生成新的任务 Future
+    pub fn spawn<F, Fut>(self: &Arc<Self>, name: &str, factory: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let supervisor = self.clone();
+        let name = name.to_string();
+        tokio::spawn(async move { supervisor.supervise(name, factory).await });
+    }
+
+    async fn supervise<F, Fut>(&self, name: String, factory: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut recent_restarts: VecDeque<Instant> = VecDeque::new();
+        let mut attempt = 0u32;
+        let mut shutdown = self.shutdown.subscribe();
+
+        loop {
+            if *shutdown.borrow() {
+                self.stopped(&name);
+                return;
+            }
+            self.update(&name, |status| {
+                status.state = WorkerState::Running;
+                status.started_at = Utc::now();
+            });
+            let started = Instant::now();
+
+            let mut task = tokio::spawn(factory());
+            let result = tokio::select! {
+                result = &mut task => result,
+                _ = shutdown.wait_for(|stop| *stop) => {
+                    task.abort();
+                    self.stopped(&name);
+                    return;
+                }
+            };
+            let cause = match result {
+                Ok(()) => "任务意外退出".to_string(),
+                Err(e) if e.is_panic() => format!("panic: {}", panic_message(e.into_panic())),
+                Err(e) => format!("任务被取消: {}", e),
+            };
+
+            // 稳定运行超过退避上限后再停止，视为新的故障，退避从头开始
+            if started.elapsed() >= self.policy.max_backoff {
+                attempt = 0;
+            }
+            let now = Instant::now();
+            while recent_restarts
+                .front()
+                .is_some_and(|t| now.duration_since(*t) >= RESTART_WINDOW)
+            {
+                recent_restarts.pop_front();
+            }
+
+            let give_up = recent_restarts.len() >= self.policy.max_restarts_per_hour as usize;
+            self.update(&name, |status| {
+                status.state = if give_up {
+                    WorkerState::Failed
+                } else {
+                    WorkerState::Restarting
+                };
+                status.last_exit_at = Some(Utc::now());
+                status.last_error = Some(cause.clone());
+            });
+            if give_up {
+                tracing::error!(
+                    "后台任务 {} 已停止: {}；1 小时内已重启 {} 次，不再自动重启",
+                    name,
+                    cause,
+                    recent_restarts.len()
+                );
+                return;
+            }
+
+            let backoff = self.policy.backoff(attempt);
+            tracing::error!(
+                "后台任务 {} 已停止: {}；{:.1} 秒后重启",
+                name,
+                cause,
+                backoff.as_secs_f64()
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = shutdown.wait_for(|stop| *stop) => {
+                    self.stopped(&name);
+                    return;
+                }
+            }
+
+            recent_restarts.push_back(Instant::now());
+            attempt = attempt.saturating_add(1);
+            self.update(&name, |status| status.restarts += 1);
+        }
+    }
+
+    fn stopped(&self, name: &str) {
+        self.update(name, |status| {
+            status.state = WorkerState::Stopped;
+            status.last_exit_at = Some(Utc::now());
+        });
+        tracing::info!("后台任务 {} 已停止", name);
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut WorkerStatus)) {
+        let mut workers = self.workers.lock();
+        let status = workers
+            .entry(name.to_string())
+            .or_insert_with(|| WorkerStatus {
+                name: name.to_string(),
+                state: WorkerState::Running,
+                restarts: 0,
+                started_at: Utc::now(),
+                last_exit_at: None,
+                last_error: None,
+            });
+        f(status);
+    }
+
+    /// 所有后台任务的存活状态（按名称排序）
+    pub fn statuses(&self) -> Vec<WorkerStatus> {
+        self.workers.lock().values().cloned().collect()
+    }
+}
+
+/// 提取 panic 信息（`panic!` 的参数为字符串时）
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "未知 panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy(max_restarts_per_hour: u32) -> RestartPolicy {
+        RestartPolicy {
+            max_restarts_per_hour,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+        }
+    }
+
+    async fn wait_for(
+        supervisor: &WorkerSupervisor,
+        state: WorkerState,
+        restarts: u32,
+    ) -> WorkerStatus {
+        for _ in 0..500 {
+            if let Some(status) = supervisor.statuses().pop()
+                && status.state == state
+                && status.restarts == restarts
+            {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        panic!("后台任务未进入 {:?} 状态", state);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = RestartPolicy {
+            max_restarts_per_hour: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+        };
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(3), Duration::from_secs(5));
+        assert_eq!(policy.backoff(40), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_panicked_worker_is_restarted() {
+        let supervisor = Arc::new(WorkerSupervisor::new(policy(10)));
+        let runs = Arc::new(AtomicU32::new(0));
+        let counter = runs.clone();
+        supervisor.spawn("flaky", move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                    panic!("boom");
+                }
+                std::future::pending::<()>().await;
+            }
+        });
+
+        let status = wait_for(&supervisor, WorkerState::Running, 2).await;
+        assert_eq!(status.name, "flaky");
+        assert_eq!(status.last_error.as_deref(), Some("panic: boom"));
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_restarts_are_bounded_per_hour() {
+        let supervisor = Arc::new(WorkerSupervisor::new(policy(3)));
+        supervisor.spawn("exiting", || async {});
+
+        let status = wait_for(&supervisor, WorkerState::Failed, 3).await;
+        assert_eq!(status.last_error.as_deref(), Some("任务意外退出"));
+
+        // 未启用自动重启时首次停止即放弃
+        let supervisor = Arc::new(WorkerSupervisor::new(policy(0)));
+        supervisor.spawn("once", || async { panic!("{}", String::from("fatal")) });
+        let status = wait_for(&supervisor, WorkerState::Failed, 0).await;
+        assert_eq!(status.last_error.as_deref(), Some("panic: fatal"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_running_worker() {
+        let supervisor = Arc::new(WorkerSupervisor::new(policy(10)));
+        supervisor.spawn("idle", std::future::pending::<()>);
+        wait_for(&supervisor, WorkerState::Running, 0).await;
+
+        supervisor.shutdown();
+        let status = wait_for(&supervisor, WorkerState::Stopped, 0).await;
+        assert!(status.last_exit_at.is_some());
+    }
+}
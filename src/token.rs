@@ -1,21 +1,26 @@
 //! Token 计算模块
 //!
-//! 提供文本 token 数量计算功能。
+//! 提供文本 token 数量计算功能。配置了外部 count_tokens API 时优先调用，
+//! 否则按 `tokenizer` 配置在本地离线计算：
 //!
-//! # 计算规则
+//! # estimate（默认）
 //! - 非西文字符：每个计 4.5 个字符单位
 //! - 西文字符：每个计 1 个字符单位
 //! - 4 个字符单位 = 1 token（四舍五入）
+//!
+//! # bpe
+//! 按 BPE 分词器常见的预切分规则把文本切成单词、数字、标点和空白片段，再按片段类型估算
+//! 合并后的 token 数，对代码和多语言混排文本比按字符估算更接近实际值
 
 use crate::anthropic::types::{
     CountTokensRequest, CountTokensResponse, Message, SystemMessage, Tool,
 };
 use crate::http_client::{ProxyConfig, build_client};
-use crate::model::config::TlsBackend;
+use crate::model::config::{TlsBackend, Tokenizer};
 use std::sync::OnceLock;
 
 /// Count Tokens API 配置
-#[derive(Clone, Default)]
+#[derive(Clone, Default, PartialEq)]
 pub struct CountTokensConfig {
     /// 外部 count_tokens API 地址
     pub api_url: Option<String>,
@@ -27,6 +32,8 @@ pub struct CountTokensConfig {
     pub proxy: Option<ProxyConfig>,
 
     pub tls_backend: TlsBackend,
+    /// 本地 token 计数方式
+    pub tokenizer: Tokenizer,
 }
 
 /// 全局配置存储
@@ -34,9 +41,15 @@ static COUNT_TOKENS_CONFIG: OnceLock<CountTokensConfig> = OnceLock::new();
 
 /// 初始化 count_tokens 配置
 ///
-/// 应在应用启动时调用一次
-pub fn init_config(config: CountTokensConfig) {
-    let _ = COUNT_TOKENS_CONFIG.set(config);
+/// 配置为进程级全局状态：重复初始化相同的配置时直接返回，
+/// 与已初始化的配置不同时返回错误（同一进程内的多个应用须使用相同的 count_tokens 配置）
+pub fn init_config(config: CountTokensConfig) -> anyhow::Result<()> {
+    let current = COUNT_TOKENS_CONFIG.get_or_init(|| config.clone());
+    anyhow::ensure!(
+        *current == config,
+        "count_tokens 配置已由同一进程内的其他应用初始化且与本次配置不同"
+    );
+    Ok(())
 }
 
 /// 获取配置
@@ -69,14 +82,21 @@ fn is_non_western_char(c: char) -> bool {
     )
 }
 
-/// 计算文本的 token 数量
+/// 计算文本的 token 数量（按配置的本地 tokenizer）
+pub fn count_tokens(text: &str) -> u64 {
+    match get_config().map(|c| c.tokenizer).unwrap_or_default() {
+        Tokenizer::Estimate => estimate_tokens(text),
+        Tokenizer::Bpe => bpe_tokens(text),
+    }
+}
+
+/// 按字符类别估算 token 数量
 ///
 /// # 计算规则
 /// - 非西文字符：每个计 4.5 个字符单位
 /// - 西文字符：每个计 1 个字符单位
 /// - 4 个字符单位 = 1 token（四舍五入）
-/// ```
-pub fn count_tokens(text: &str) -> u64 {
+fn estimate_tokens(text: &str) -> u64 {
     // println!("text: {}", text);
 
     let char_units: f64 = text
@@ -102,6 +122,95 @@ pub fn count_tokens(text: &str) -> u64 {
     acc_token
 }
 
+/// 预切分片段类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Piece {
+    /// 西文字母（含撇号缩写）
+    Word,
+    /// 数字
+    Digit,
+    /// 空白（不含换行）
+    Space,
+    /// 换行
+    Newline,
+    /// 非西文字符（中日韩等，逐字计数）
+    Ideograph,
+    /// 标点与其他符号
+    Symbol,
+}
+
+impl Piece {
+    fn of(c: char) -> Self {
+        if c == '\n' || c == '\r' {
+            Piece::Newline
+        } else if c.is_whitespace() {
+            Piece::Space
+        } else if c.is_ascii_digit() {
+            Piece::Digit
+        } else if is_non_western_char(c) {
+            if c.is_alphanumeric() {
+                Piece::Ideograph
+            } else {
+                Piece::Symbol
+            }
+        } else if c.is_alphabetic() || c == '\'' {
+            Piece::Word
+        } else {
+            Piece::Symbol
+        }
+    }
+
+    /// 长度为 `len` 个字符的片段合并后的 token 数
+    fn tokens(self, len: u64) -> u64 {
+        match self {
+            // 常见短词是单个 token，长词平均约 6 个字符一个 token
+            Piece::Word => len.div_ceil(6),
+            // 数字最多 3 位一组
+            Piece::Digit => len.div_ceil(3),
+            // 连续空白（缩进）合并为一个 token
+            Piece::Space | Piece::Newline => 1,
+            Piece::Ideograph => len,
+            // 常见标点组合（如 `");`、`=>`）两两合并
+            Piece::Symbol => len.div_ceil(2),
+        }
+    }
+}
+
+/// 内置的近似 BPE 分词计数
+///
+/// 单词前的单个空格与单词合并（与 GPT/Claude 类分词器一致），其余片段按类型计数
+fn bpe_tokens(text: &str) -> u64 {
+    let mut total = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut piece = Piece::of(c);
+        // 单个空格后紧跟单词时并入该单词
+        if c == ' '
+            && let Some(&next) = chars.peek()
+            && Piece::of(next) == Piece::Word
+        {
+            piece = Piece::Word;
+        } else if piece == Piece::Space || piece == Piece::Newline {
+            while chars.peek().is_some_and(|&n| Piece::of(n) == piece) {
+                chars.next();
+            }
+            total += piece.tokens(1);
+            continue;
+        }
+
+        let mut len = u64::from(c != ' ');
+        while let Some(&next) = chars.peek() {
+            if Piece::of(next) != piece {
+                break;
+            }
+            chars.next();
+            len += 1;
+        }
+        total += piece.tokens(len);
+    }
+    total
+}
+
 /// 估算请求的输入 tokens
 ///
 /// 优先调用远程 API，失败时回退到本地计算
@@ -232,6 +341,9 @@ pub(crate) fn estimate_output_tokens(content: &[serde_json::Value]) -> i32 {
         if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
             total += count_tokens(text) as i32;
         }
+        if let Some(thinking) = block.get("thinking").and_then(|v| v.as_str()) {
+            total += count_tokens(thinking) as i32;
+        }
         if block.get("type").and_then(|v| v.as_str()) == Some("tool_use") {
             // 工具调用开销
             if let Some(input) = block.get("input") {
@@ -243,3 +355,20 @@ pub(crate) fn estimate_output_tokens(content: &[serde_json::Value]) -> i32 {
 
     total.max(1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bpe_tokens_by_piece_type() {
+        assert_eq!(bpe_tokens(""), 0);
+        assert_eq!(bpe_tokens("Hello world"), 2);
+        // 长词按约 6 个字符一个 token 拆分
+        assert_eq!(bpe_tokens(" internationalization"), 4);
+        assert_eq!(bpe_tokens("1234567"), 3);
+        assert_eq!(bpe_tokens("你好世界"), 4);
+        // 缩进与换行各自合并
+        assert_eq!(bpe_tokens("fn main() {\n    return;\n}"), 11);
+    }
+}